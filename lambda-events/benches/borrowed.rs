@@ -0,0 +1,88 @@
+//! Compares deserializing a 5MB SQS batch into an `SqsEvent` with deserializing it into an
+//! `SqsEventRef` borrowing from the body of the invocation
+//!
+//! The batch holds a thousand messages with 4kB base64 bodies, which the borrowed event doesn't
+//! copy. Besides the timings criterion measures, the allocations each deserialization makes are
+//! counted by a global allocator wrapping the system one, and printed first.
+//!
+//! Run with `cargo bench -p lambda_events --bench borrowed`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use lambda_events::sqs::{SqsEvent, SqsEventRef};
+use serde_json::Value;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The system allocator, counting allocations and the bytes they request
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// A batch of a thousand copies of the first message of the fixture, with 4kB bodies
+fn fixture() -> Vec<u8> {
+    let mut event: Value = serde_json::from_str(include_str!("../tests/data/sqs_event.json")).unwrap();
+    let mut message = event["Records"][0].take();
+    message["body"] = base64::encode(vec![0x5A; 3 * 1024]).into();
+    event["Records"] = vec![message; 1000].into();
+    serde_json::to_vec(&event).unwrap()
+}
+
+/// The allocations `f` makes, and the bytes they request
+fn allocations<F, R>(f: F) -> (usize, usize)
+where
+    F: FnOnce() -> R,
+{
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    black_box(f());
+    (
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        ALLOCATED.load(Ordering::Relaxed) - allocated,
+    )
+}
+
+fn owned(body: &[u8]) -> SqsEvent {
+    serde_json::from_slice(black_box(body)).expect("failed to deserialize event")
+}
+
+fn borrowed(body: &[u8]) -> SqsEventRef<'_> {
+    SqsEventRef::from_slice(black_box(body)).expect("failed to deserialize event")
+}
+
+fn deserialize(c: &mut Criterion) {
+    let body = fixture();
+    println!("batch of {} bytes", body.len());
+    for (name, (count, bytes)) in &[
+        ("owned", allocations(|| owned(&body))),
+        ("borrowed", allocations(|| borrowed(&body))),
+    ] {
+        println!("{:<9} {:>6} allocations  {:>9} bytes allocated", name, count, bytes);
+    }
+
+    let mut group = c.benchmark_group("sqs");
+    group.throughput(Throughput::Bytes(body.len() as u64));
+    group.bench_function("owned", |b| b.iter(|| owned(&body)));
+    group.bench_function("borrowed", |b| b.iter(|| borrowed(&body)));
+    group.finish();
+}
+
+criterion_group!(benches, deserialize);
+criterion_main!(benches);
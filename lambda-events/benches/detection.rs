@@ -0,0 +1,53 @@
+//! Compares deserializing events into a `LambdaEvent` with deserializing them into their types
+//!
+//! Detection parses events into a `serde_json::Value` first, which costs a few microseconds per
+//! event: next to nothing compared to the milliseconds of an invocation.
+//!
+//! Run with `cargo bench -p lambda_events --bench detection`.
+
+use lambda_events::{event::LambdaEvent, eventbridge::EventBridgeEvent, s3::S3Event, sqs::SqsEvent};
+use serde::de::DeserializeOwned;
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+const ITERATIONS: u32 = 20_000;
+
+fn time<T>(fixture: &str) -> Duration
+where
+    T: DeserializeOwned,
+{
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(serde_json::from_str::<T>(black_box(fixture)).expect("failed to deserialize event"));
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn compare<T>(name: &str, fixture: &str)
+where
+    T: DeserializeOwned,
+{
+    // warm up caches and the allocator before measuring
+    time::<T>(fixture);
+    let typed = time::<T>(fixture);
+    let detected = time::<LambdaEvent>(fixture);
+    println!(
+        "{:<12} typed {:>8.2?}  detected {:>8.2?}  overhead {:>8.2?} ({:.0}%)",
+        name,
+        typed,
+        detected,
+        detected.checked_sub(typed).unwrap_or_default(),
+        (detected.as_secs_f64() / typed.as_secs_f64() - 1.0) * 100.0
+    );
+}
+
+fn main() {
+    compare::<S3Event>("s3", include_str!("../tests/data/s3_object_created_put.json"));
+    compare::<SqsEvent>("sqs", include_str!("../tests/data/sqs_event.json"));
+    compare::<EventBridgeEvent>(
+        "eventbridge",
+        include_str!("../tests/data/eventbridge_custom_event.json"),
+    );
+}
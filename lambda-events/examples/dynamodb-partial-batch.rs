@@ -0,0 +1,42 @@
+use lambda::{handler_fn, Context};
+use lambda_events::{
+    dynamodb::{from_item, DynamoDbEvent},
+    streams::StreamsBatchResponse,
+};
+use serde_derive::Deserialize;
+
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Order {
+    id: u64,
+    total: f64,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    lambda::run(handler_fn(func)).await?;
+    Ok(())
+}
+
+/// Processes the orders of the batch in stream order, stopping at the first order which fails
+/// so that the batch is retried from it.
+///
+/// The event source mapping must have `ReportBatchItemFailures` enabled.
+async fn func(event: DynamoDbEvent, _: Context) -> Result<StreamsBatchResponse, Error> {
+    for record in &event.records {
+        let image = match &record.dynamodb.new_image {
+            Some(image) => image,
+            None => continue,
+        };
+        match from_item::<Order>(image) {
+            Ok(order) => println!("processing order {} totalling {}", order.id, order.total),
+            Err(e) => {
+                println!("failed to parse record {}: {}", record.event_id, e);
+                return Ok(StreamsBatchResponse::failed_at(record));
+            }
+        }
+    }
+    Ok(StreamsBatchResponse::default())
+}
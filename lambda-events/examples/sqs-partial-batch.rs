@@ -0,0 +1,34 @@
+use lambda::{handler_fn, Context};
+use lambda_events::sqs::{SqsBatchResponse, SqsEvent};
+use serde_derive::Deserialize;
+
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Order {
+    order_id: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    lambda::run(handler_fn(func)).await?;
+    Ok(())
+}
+
+/// Processes each order in the batch, so that only the orders which fail are retried.
+///
+/// The event source mapping must have `ReportBatchItemFailures` enabled.
+async fn func(event: SqsEvent, _: Context) -> Result<SqsBatchResponse, Error> {
+    let mut response = SqsBatchResponse::default();
+    for message in &event.records {
+        match serde_json::from_str::<Order>(&message.body) {
+            Ok(order) => println!("processing order {}", order.order_id),
+            Err(e) => {
+                println!("failed to parse message {}: {}", message.message_id, e);
+                response.add_failure(message);
+            }
+        }
+    }
+    Ok(response)
+}
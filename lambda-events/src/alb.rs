@@ -0,0 +1,81 @@
+//! Application Load Balancer request types
+//!
+//! Load balancers forward requests to functions registered in a target group as an
+//! [`AlbTargetGroupRequest`](struct.AlbTargetGroupRequest.html). `lambda_http` turns them into
+//! `http::Request`s, for functions which would rather not handle them as events.
+
+use crate::custom_serde::nullable_default;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A request forwarded by an Application Load Balancer
+///
+/// Target groups with multi value headers enabled fill the multi value headers and query string
+/// parameters, and leave the single value ones empty.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlbTargetGroupRequest {
+    /// The request HTTP method
+    pub http_method: String,
+    /// The request path
+    pub path: String,
+    /// Request headers, keeping the last value of repeated headers
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub headers: HashMap<String, String>,
+    /// Request headers with all their values
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub multi_value_headers: HashMap<String, Vec<String>>,
+    /// Query string parameters, still URL encoded, keeping the last value of repeated parameters
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub query_string_parameters: HashMap<String, String>,
+    /// Query string parameters with all their values, still URL encoded
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub multi_value_query_string_parameters: HashMap<String, Vec<String>>,
+    /// The target group which forwarded the request
+    pub request_context: AlbTargetGroupRequestContext,
+    /// The request body, base64 encoded for binary bodies
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Whether the body is base64 encoded
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub is_base64_encoded: bool,
+}
+
+/// The target group which forwarded a request
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct AlbTargetGroupRequestContext {
+    /// The load balancer details
+    pub elb: ElbContext,
+}
+
+/// The target group details of a forwarded request
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ElbContext {
+    /// The ARN of the target group
+    pub target_group_arn: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserializes_requests() {
+        let request: AlbTargetGroupRequest = serde_json::from_str(include_str!("../tests/data/alb_request.json"))
+            .expect("failed to deserialize request");
+        assert_eq!(request.http_method, "GET");
+        assert_eq!(request.path, "/");
+        assert_eq!(request.headers["host"], "lambda-846800462-us-east-2.elb.amazonaws.com");
+        assert!(request.multi_value_headers.is_empty());
+        assert_eq!(request.query_string_parameters["myKey"], "val2");
+        assert_eq!(
+            request.request_context.elb.target_group_arn,
+            "arn:aws:elasticloadbalancing:region:123456789012:targetgroup/my-target-group/6d0ecf831eec9f09"
+        );
+        assert_eq!(request.body.as_deref(), Some("request_body"));
+        let round_tripped: AlbTargetGroupRequest =
+            serde_json::from_value(serde_json::to_value(&request).unwrap()).expect("failed to deserialize request");
+        assert_eq!(round_tripped, request);
+    }
+}
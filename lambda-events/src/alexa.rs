@@ -0,0 +1,771 @@
+//! Alexa Skills Kit request and response types
+//!
+//! Custom skills invoke their function with an [`AlexaRequest`](struct.AlexaRequest.html)
+//! envelope for every request the user makes, and expect an
+//! [`AlexaResponse`](struct.AlexaResponse.html) telling Alexa what to say and show.
+
+use crate::custom_serde::rfc3339;
+use serde::{
+    de::{Deserializer, Error as _},
+    ser::Serializer,
+};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, time::SystemTime};
+
+/// A request of a custom skill
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AlexaRequest {
+    /// The version of the envelope format
+    pub version: String,
+    /// The session, for requests made in a conversation with the skill
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<AlexaSession>,
+    /// The state of the device and the skill
+    pub context: AlexaContext,
+    /// The request
+    pub request: AlexaRequestBody,
+}
+
+impl AlexaRequest {
+    /// Return the intent, for intent requests
+    pub fn intent(&self) -> Option<&AlexaIntent> {
+        match &self.request {
+            AlexaRequestBody::IntentRequest(request) => Some(&request.intent),
+            _ => None,
+        }
+    }
+
+    /// Return the attribute named `name` of the session
+    pub fn session_attribute(&self, name: &str) -> Option<&Value> {
+        self.session.as_ref()?.attributes.as_ref()?.get(name)
+    }
+}
+
+/// A conversation with a skill
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaSession {
+    /// Whether the request starts the session
+    pub new: bool,
+    /// A unique identifier for the session
+    pub session_id: String,
+    /// The skill
+    pub application: AlexaApplication,
+    /// The attributes the skill returned in its previous response in the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<HashMap<String, Value>>,
+    /// The user
+    pub user: AlexaUser,
+}
+
+/// A skill
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaApplication {
+    /// The id of the skill, to check that requests are meant for it
+    pub application_id: String,
+}
+
+/// The Amazon account using a skill
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaUser {
+    /// The id of the account, unique to the skill
+    pub user_id: String,
+    /// The token of the account linked to the user's, for skills with account linking
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+    /// The permissions the user granted the skill
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<Value>,
+}
+
+/// The state of the device and the skill
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AlexaContext {
+    /// The device, the user and the skill
+    #[serde(rename = "System")]
+    pub system: AlexaSystem,
+    /// The state of the audio player, for devices with an audio player
+    #[serde(rename = "AudioPlayer", default, skip_serializing_if = "Option::is_none")]
+    pub audio_player: Option<AlexaAudioPlayer>,
+}
+
+/// The device, the user and the skill of a request
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaSystem {
+    /// The skill
+    pub application: AlexaApplication,
+    /// The user
+    pub user: AlexaUser,
+    /// The device
+    pub device: AlexaDevice,
+    /// The base URL of the Alexa APIs for the user's region
+    pub api_endpoint: String,
+    /// The token to call the Alexa APIs with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_access_token: Option<String>,
+}
+
+/// A device talking to a skill
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaDevice {
+    /// The id of the device, unique to the skill
+    pub device_id: String,
+    /// The interfaces the device supports, like `AudioPlayer`, by name
+    #[serde(default)]
+    pub supported_interfaces: HashMap<String, Value>,
+}
+
+/// The state of a device's audio player
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaAudioPlayer {
+    /// The token of the stream last played
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// How far into the stream the player is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset_in_milliseconds: Option<u64>,
+    /// What the player does, like `IDLE` or `PLAYING`
+    pub player_activity: String,
+}
+
+/// The request of an envelope
+///
+/// Request types this crate doesn't model, like interface events, deserialize into `Other`
+/// with the request as it was sent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlexaRequestBody {
+    /// The user opens the skill without asking for anything in particular
+    LaunchRequest(AlexaLaunchRequest),
+    /// The user asks for something
+    IntentRequest(AlexaIntentRequest),
+    /// The session ends
+    SessionEndedRequest(AlexaSessionEndedRequest),
+    /// A request of another type
+    Other(Value),
+}
+
+impl AlexaRequestBody {
+    /// Return the type of the request, like `LaunchRequest`
+    pub fn request_type(&self) -> Option<&str> {
+        match self {
+            AlexaRequestBody::LaunchRequest(_) => Some("LaunchRequest"),
+            AlexaRequestBody::IntentRequest(_) => Some("IntentRequest"),
+            AlexaRequestBody::SessionEndedRequest(_) => Some("SessionEndedRequest"),
+            AlexaRequestBody::Other(request) => request.get("type").and_then(Value::as_str),
+        }
+    }
+}
+
+/// The request types modeled by `AlexaRequestBody`, tagged by `type`
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+#[allow(clippy::enum_variant_names)]
+enum KnownRequest {
+    LaunchRequest(AlexaLaunchRequest),
+    IntentRequest(AlexaIntentRequest),
+    SessionEndedRequest(AlexaSessionEndedRequest),
+}
+
+/// The serialized form of `KnownRequest`
+#[derive(Serialize)]
+#[serde(tag = "type")]
+#[allow(clippy::enum_variant_names)]
+enum KnownRequestRef<'a> {
+    LaunchRequest(&'a AlexaLaunchRequest),
+    IntentRequest(&'a AlexaIntentRequest),
+    SessionEndedRequest(&'a AlexaSessionEndedRequest),
+}
+
+impl<'de> serde::de::Deserialize<'de> for AlexaRequestBody {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let request = Value::deserialize(deserializer)?;
+        match request.get("type").and_then(Value::as_str) {
+            Some("LaunchRequest") | Some("IntentRequest") | Some("SessionEndedRequest") => {
+                Ok(match serde_json::from_value(request).map_err(D::Error::custom)? {
+                    KnownRequest::LaunchRequest(request) => AlexaRequestBody::LaunchRequest(request),
+                    KnownRequest::IntentRequest(request) => AlexaRequestBody::IntentRequest(request),
+                    KnownRequest::SessionEndedRequest(request) => AlexaRequestBody::SessionEndedRequest(request),
+                })
+            }
+            _ => Ok(AlexaRequestBody::Other(request)),
+        }
+    }
+}
+
+impl serde::ser::Serialize for AlexaRequestBody {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            AlexaRequestBody::LaunchRequest(request) => KnownRequestRef::LaunchRequest(request).serialize(serializer),
+            AlexaRequestBody::IntentRequest(request) => KnownRequestRef::IntentRequest(request).serialize(serializer),
+            AlexaRequestBody::SessionEndedRequest(request) => {
+                KnownRequestRef::SessionEndedRequest(request).serialize(serializer)
+            }
+            AlexaRequestBody::Other(request) => request.serialize(serializer),
+        }
+    }
+}
+
+/// A request made when the user opens a skill
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaLaunchRequest {
+    /// A unique identifier for the request
+    pub request_id: String,
+    /// When the request was made, to reject replayed requests
+    #[serde(with = "rfc3339")]
+    pub timestamp: SystemTime,
+    /// The locale of the user, like `en-US`
+    pub locale: String,
+}
+
+/// A request made when the user asks for something
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaIntentRequest {
+    /// A unique identifier for the request
+    pub request_id: String,
+    /// When the request was made, to reject replayed requests
+    #[serde(with = "rfc3339")]
+    pub timestamp: SystemTime,
+    /// The locale of the user, like `en-US`
+    pub locale: String,
+    /// How far along the dialog is, for intents with a dialog model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dialog_state: Option<AlexaDialogState>,
+    /// What the user asks for
+    pub intent: AlexaIntent,
+}
+
+/// How far along a dialog is
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlexaDialogState {
+    /// The dialog starts
+    Started,
+    /// The dialog goes on
+    InProgress,
+    /// All the required slots are filled and confirmed
+    Completed,
+}
+
+/// What the user asks for
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaIntent {
+    /// The name of the intent
+    pub name: String,
+    /// Whether the user confirmed the intent
+    pub confirmation_status: AlexaConfirmationStatus,
+    /// The slots of the intent by name, including the ones without a value
+    #[serde(default)]
+    pub slots: HashMap<String, AlexaSlot>,
+}
+
+impl AlexaIntent {
+    /// Return the value of the slot named `name`, if the user gave one
+    pub fn slot_value(&self, name: &str) -> Option<&str> {
+        self.slots.get(name)?.value.as_deref()
+    }
+}
+
+/// Whether the user confirmed an intent or a slot
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlexaConfirmationStatus {
+    /// The user wasn't asked
+    None,
+    /// The user confirmed
+    Confirmed,
+    /// The user denied
+    Denied,
+}
+
+/// A slot of an intent
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaSlot {
+    /// The name of the slot
+    pub name: String,
+    /// The value as the user said it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// Whether the user confirmed the value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmation_status: Option<AlexaConfirmationStatus>,
+    /// Where the value comes from, like `USER`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// The values of the slot types the value resolves to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolutions: Option<AlexaResolutions>,
+}
+
+impl AlexaSlot {
+    /// Return the first slot type value the value resolves to
+    pub fn resolved_value(&self) -> Option<&AlexaResolvedValue> {
+        self.resolutions
+            .as_ref()?
+            .resolutions_per_authority
+            .iter()
+            .filter(|resolution| resolution.status.code == AlexaResolutionStatusCode::SuccessMatch)
+            .flat_map(|resolution| resolution.values.iter())
+            .map(|value| &value.value)
+            .next()
+    }
+}
+
+/// The entity resolutions of a slot's value
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaResolutions {
+    /// The resolutions, one per slot type
+    pub resolutions_per_authority: Vec<AlexaResolution>,
+}
+
+/// The resolution of a slot's value against a slot type
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AlexaResolution {
+    /// The slot type, as an entity resolution authority
+    pub authority: String,
+    /// Whether the value resolved
+    pub status: AlexaResolutionStatus,
+    /// The values of the slot type the value resolves to, best match first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<AlexaResolvedValueWrapper>,
+}
+
+/// Whether a slot's value resolved
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AlexaResolutionStatus {
+    /// The outcome
+    pub code: AlexaResolutionStatusCode,
+}
+
+/// The outcome of a slot value's resolution
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlexaResolutionStatusCode {
+    /// The value matched
+    #[serde(rename = "ER_SUCCESS_MATCH")]
+    SuccessMatch,
+    /// The value didn't match
+    #[serde(rename = "ER_SUCCESS_NO_MATCH")]
+    SuccessNoMatch,
+    /// The resolution timed out
+    #[serde(rename = "ER_ERROR_TIMEOUT")]
+    ErrorTimeout,
+    /// The resolution failed
+    #[serde(rename = "ER_ERROR_EXCEPTION")]
+    ErrorException,
+}
+
+/// A value of a slot type a slot's value resolves to
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AlexaResolvedValueWrapper {
+    /// The value
+    pub value: AlexaResolvedValue,
+}
+
+/// A value of a slot type
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AlexaResolvedValue {
+    /// The canonical name of the value
+    pub name: String,
+    /// The id of the value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+}
+
+/// A request made when the session ends for another reason than the skill ending it
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaSessionEndedRequest {
+    /// A unique identifier for the request
+    pub request_id: String,
+    /// When the request was made, to reject replayed requests
+    #[serde(with = "rfc3339")]
+    pub timestamp: SystemTime,
+    /// The locale of the user, like `en-US`
+    pub locale: String,
+    /// Why the session ended
+    pub reason: AlexaSessionEndedReason,
+    /// What went wrong, for sessions ended by an error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<AlexaSessionEndedError>,
+}
+
+/// Why a session ended
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlexaSessionEndedReason {
+    /// The user ended the session
+    UserInitiated,
+    /// The skill failed
+    Error,
+    /// The user didn't answer too many times
+    ExceededMaxReprompts,
+}
+
+/// What went wrong when a session ended by an error
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AlexaSessionEndedError {
+    /// The kind of error, like `INVALID_RESPONSE`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// A description of the error
+    pub message: String,
+}
+
+/// The answer of a custom skill
+///
+/// ```rust
+/// use lambda_events::alexa::{AlexaCard, AlexaOutputSpeech, AlexaRequest, AlexaResponse};
+///
+/// fn handle(request: AlexaRequest) -> AlexaResponse {
+///     match request.intent().and_then(|intent| intent.slot_value("drink")) {
+///         Some(drink) => AlexaResponse::new()
+///             .with_speech(AlexaOutputSpeech::plain_text(format!("One {} coming up", drink)))
+///             .with_card(AlexaCard::simple("Order", drink))
+///             .with_should_end_session(true),
+///         None => AlexaResponse::new()
+///             .with_speech(AlexaOutputSpeech::plain_text("What would you like?"))
+///             .with_reprompt(AlexaOutputSpeech::plain_text("Coffee or tea?"))
+///             .with_should_end_session(false),
+///     }
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaResponse {
+    /// The version of the response format, `1.0`
+    pub version: String,
+    /// The attributes of the session, sent back with the next request in the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_attributes: Option<HashMap<String, Value>>,
+    /// What Alexa should say and show
+    pub response: AlexaResponseBody,
+}
+
+impl AlexaResponse {
+    /// Build a response saying and showing nothing
+    pub fn new() -> Self {
+        Self {
+            version: "1.0".into(),
+            session_attributes: None,
+            response: AlexaResponseBody::default(),
+        }
+    }
+
+    /// Set what Alexa should say
+    pub fn with_speech(mut self, speech: AlexaOutputSpeech) -> Self {
+        self.response.output_speech = Some(speech);
+        self
+    }
+
+    /// Set what Alexa should say when the user doesn't answer
+    pub fn with_reprompt(mut self, speech: AlexaOutputSpeech) -> Self {
+        self.response.reprompt = Some(AlexaReprompt { output_speech: speech });
+        self
+    }
+
+    /// Set the card to show in the Alexa app
+    pub fn with_card(mut self, card: AlexaCard) -> Self {
+        self.response.card = Some(card);
+        self
+    }
+
+    /// Set whether the session ends with the response
+    pub fn with_should_end_session(mut self, should_end_session: bool) -> Self {
+        self.response.should_end_session = Some(should_end_session);
+        self
+    }
+
+    /// Set the attributes of the session
+    pub fn with_session_attributes(mut self, session_attributes: HashMap<String, Value>) -> Self {
+        self.session_attributes = Some(session_attributes);
+        self
+    }
+}
+
+impl Default for AlexaResponse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What Alexa should say and show
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaResponseBody {
+    /// What to say
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_speech: Option<AlexaOutputSpeech>,
+    /// The card to show in the Alexa app
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card: Option<AlexaCard>,
+    /// What to say when the user doesn't answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reprompt: Option<AlexaReprompt>,
+    /// Whether the session ends, `None` to leave it open without listening, for devices with a screen
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub should_end_session: Option<bool>,
+    /// The directives for the device's interfaces
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub directives: Vec<Value>,
+}
+
+/// Speech to say
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum AlexaOutputSpeech {
+    /// Plain text
+    PlainText {
+        /// The text
+        text: String,
+        /// How the speech plays along queued speech, like `REPLACE_ALL`
+        #[serde(rename = "playBehavior", default, skip_serializing_if = "Option::is_none")]
+        play_behavior: Option<String>,
+    },
+    /// Speech Synthesis Markup Language
+    #[serde(rename = "SSML")]
+    Ssml {
+        /// The markup, wrapped in `<speak>`
+        ssml: String,
+        /// How the speech plays along queued speech, like `REPLACE_ALL`
+        #[serde(rename = "playBehavior", default, skip_serializing_if = "Option::is_none")]
+        play_behavior: Option<String>,
+    },
+}
+
+impl AlexaOutputSpeech {
+    /// Build plain text speech
+    pub fn plain_text(text: impl Into<String>) -> Self {
+        AlexaOutputSpeech::PlainText {
+            text: text.into(),
+            play_behavior: None,
+        }
+    }
+
+    /// Build SSML speech, wrapped in `<speak>`
+    pub fn ssml(ssml: impl Into<String>) -> Self {
+        AlexaOutputSpeech::Ssml {
+            ssml: ssml.into(),
+            play_behavior: None,
+        }
+    }
+}
+
+/// Speech to say when the user doesn't answer
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaReprompt {
+    /// The speech
+    pub output_speech: AlexaOutputSpeech,
+}
+
+/// A card shown in the Alexa app
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum AlexaCard {
+    /// A card with a title and text
+    Simple {
+        /// The title
+        title: String,
+        /// The text
+        content: String,
+    },
+    /// A card with a title, text and image
+    Standard {
+        /// The title
+        title: String,
+        /// The text
+        text: String,
+        /// The image
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<AlexaCardImage>,
+    },
+    /// A card asking the user to link their account
+    LinkAccount,
+    /// A card asking the user for permissions
+    AskForPermissionsConsent {
+        /// The permissions, like `read::alexa:device:all:address`
+        permissions: Vec<String>,
+    },
+}
+
+impl AlexaCard {
+    /// Build a card with a title and text
+    pub fn simple(title: impl Into<String>, content: impl Into<String>) -> Self {
+        AlexaCard::Simple {
+            title: title.into(),
+            content: content.into(),
+        }
+    }
+
+    /// Build a card with a title, text and image
+    pub fn standard(title: impl Into<String>, text: impl Into<String>, image: Option<AlexaCardImage>) -> Self {
+        AlexaCard::Standard {
+            title: title.into(),
+            text: text.into(),
+            image,
+        }
+    }
+}
+
+/// The image of a card, in two resolutions
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaCardImage {
+    /// The URL of the image for small screens, 720 x 480 pixels
+    pub small_image_url: String,
+    /// The URL of the image for large screens, 1200 x 800 pixels
+    pub large_image_url: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn request(fixture: &str) -> AlexaRequest {
+        serde_json::from_str(fixture).expect("failed to deserialize request")
+    }
+
+    #[test]
+    fn round_trips_requests() {
+        for fixture in &[
+            include_str!("../tests/data/alexa_launch_request.json"),
+            include_str!("../tests/data/alexa_intent_request.json"),
+        ] {
+            assert_eq!(
+                serde_json::to_value(request(fixture)).expect("failed to serialize request"),
+                serde_json::from_str::<Value>(fixture).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn deserializes_launch_requests() {
+        let request = request(include_str!("../tests/data/alexa_launch_request.json"));
+        assert_eq!(request.request.request_type(), Some("LaunchRequest"));
+        match &request.request {
+            AlexaRequestBody::LaunchRequest(launch) => {
+                assert_eq!(launch.timestamp, UNIX_EPOCH + Duration::from_secs(1_591_003_800));
+                assert_eq!(launch.locale, "en-US");
+            }
+            other => panic!("unexpected request {:?}", other),
+        }
+        assert!(request.session.as_ref().map(|session| session.new).unwrap_or_default());
+        assert_eq!(request.intent(), None);
+        assert_eq!(request.session_attribute("orders"), None);
+        assert_eq!(
+            request.context.audio_player.map(|player| player.player_activity),
+            Some("IDLE".into())
+        );
+    }
+
+    #[test]
+    fn deserializes_intent_requests() {
+        let request = request(include_str!("../tests/data/alexa_intent_request.json"));
+        assert_eq!(request.session_attribute("orders"), Some(&json!(2)));
+        match &request.request {
+            AlexaRequestBody::IntentRequest(intent) => {
+                assert_eq!(intent.dialog_state, Some(AlexaDialogState::InProgress))
+            }
+            other => panic!("unexpected request {:?}", other),
+        }
+        let intent = request.intent().expect("missing intent");
+        assert_eq!(intent.name, "OrderCoffeeIntent");
+        assert_eq!(intent.slot_value("drink"), Some("flat white"));
+        assert_eq!(intent.slot_value("milk"), None);
+        assert_eq!(
+            intent.slots["drink"].resolved_value(),
+            Some(&AlexaResolvedValue {
+                name: "Flat White".into(),
+                id: Some("FLAT_WHITE".into())
+            })
+        );
+        assert_eq!(intent.slot_value("size"), Some("huge"));
+        assert_eq!(intent.slots["size"].resolved_value(), None);
+        assert_eq!(intent.slots["milk"].resolved_value(), None);
+    }
+
+    #[test]
+    fn deserializes_unknown_requests() {
+        let mut fixture: Value = serde_json::from_str(include_str!("../tests/data/alexa_launch_request.json")).unwrap();
+        fixture["request"] = json!({
+            "type": "AudioPlayer.PlaybackStarted",
+            "requestId": "amzn1.echo-api.request.1",
+            "timestamp": "2020-06-01T09:30:00Z",
+            "token": "episode-42",
+            "offsetInMilliseconds": 0
+        });
+        let request: AlexaRequest = serde_json::from_value(fixture.clone()).expect("failed to deserialize request");
+        assert_eq!(request.request.request_type(), Some("AudioPlayer.PlaybackStarted"));
+        assert_eq!(request.request, AlexaRequestBody::Other(fixture["request"].clone()));
+        assert_eq!(serde_json::to_value(&request).unwrap(), fixture);
+    }
+
+    #[test]
+    fn rejects_malformed_known_requests() {
+        let mut fixture: Value = serde_json::from_str(include_str!("../tests/data/alexa_launch_request.json")).unwrap();
+        fixture["request"] = json!({ "type": "SessionEndedRequest", "requestId": "amzn1.echo-api.request.1" });
+        assert!(serde_json::from_value::<AlexaRequest>(fixture).is_err());
+    }
+
+    #[test]
+    fn serializes_responses() {
+        let mut attributes = HashMap::new();
+        attributes.insert("orders".to_string(), json!(3));
+        let response = AlexaResponse::new()
+            .with_speech(AlexaOutputSpeech::ssml("<speak>One flat white coming up</speak>"))
+            .with_card(AlexaCard::standard(
+                "Order",
+                "Flat white",
+                Some(AlexaCardImage {
+                    small_image_url: "https://example.com/small.png".into(),
+                    large_image_url: "https://example.com/large.png".into(),
+                }),
+            ))
+            .with_reprompt(AlexaOutputSpeech::plain_text("Anything else?"))
+            .with_should_end_session(false)
+            .with_session_attributes(attributes);
+        assert_eq!(
+            serde_json::to_value(&response).expect("failed to serialize response"),
+            json!({
+                "version": "1.0",
+                "sessionAttributes": { "orders": 3 },
+                "response": {
+                    "outputSpeech": { "type": "SSML", "ssml": "<speak>One flat white coming up</speak>" },
+                    "card": {
+                        "type": "Standard",
+                        "title": "Order",
+                        "text": "Flat white",
+                        "image": {
+                            "smallImageUrl": "https://example.com/small.png",
+                            "largeImageUrl": "https://example.com/large.png"
+                        }
+                    },
+                    "reprompt": { "outputSpeech": { "type": "PlainText", "text": "Anything else?" } },
+                    "shouldEndSession": false
+                }
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(AlexaResponse::new().with_card(AlexaCard::LinkAccount)).unwrap(),
+            json!({ "version": "1.0", "response": { "card": { "type": "LinkAccount" } } })
+        );
+    }
+}
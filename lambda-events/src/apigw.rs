@@ -0,0 +1,1022 @@
+//! API Gateway proxy integration requests and Lambda authorizer request and response types
+//!
+//! REST APIs invoke functions behind Lambda proxy integrations with an
+//! [`ApiGatewayProxyRequest`](struct.ApiGatewayProxyRequest.html), and HTTP APIs using payload
+//! format version 2.0 with an [`ApiGatewayV2Request`](struct.ApiGatewayV2Request.html).
+//! `lambda_http` turns both into `http::Request`s, for functions which would rather not
+//! handle them as events.
+//!
+//! API Gateway invokes a [Lambda authorizer](https://docs.aws.amazon.com/apigateway/latest/developerguide/apigateway-use-lambda-authorizer.html)
+//! with either a `TOKEN` or a `REQUEST` payload, represented by [`ApiGatewayCustomAuthorizerRequest`](enum.ApiGatewayCustomAuthorizerRequest.html).
+//! HTTP API authorizers using payload format version 2.0 receive an
+//! [`ApiGatewayV2CustomAuthorizerRequest`](struct.ApiGatewayV2CustomAuthorizerRequest.html) instead.
+//!
+//! Authorizers answer with an IAM policy, most easily assembled with a
+//! [`PolicyDocumentBuilder`](struct.PolicyDocumentBuilder.html), or, for HTTP APIs with simple
+//! responses enabled, an [`ApiGatewayV2CustomAuthorizerSimpleResponse`](struct.ApiGatewayV2CustomAuthorizerSimpleResponse.html).
+
+pub use crate::iam::{
+    Effect as IamPolicyEffect, PolicyDocument as ApiGatewayCustomAuthorizerPolicy,
+    PolicyStatement as IamPolicyStatement,
+};
+use crate::{custom_serde::nullable_default, iam::PolicyDocument};
+use serde::{
+    de::{Deserialize, Deserializer, Error as DeError, MapAccess, Visitor},
+    ser::{Serialize, SerializeMap, Serializer},
+};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+use std::{collections::HashMap, fmt};
+
+/// The IAM action API Gateway checks authorizer policies against
+const INVOKE_ACTION: &str = "execute-api:Invoke";
+
+/// Method ARN scope used when a builder is not scoped to a specific API and stage
+const ANY_API: &str = "arn:aws:execute-api:*:*:*/*";
+
+/// The input to an API Gateway REST API Lambda authorizer
+///
+/// The variant is determined by the `type` field of the event
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "UPPERCASE")]
+#[allow(clippy::large_enum_variant)] // events are deserialized once per invocation, boxing would only cost ergonomics
+pub enum ApiGatewayCustomAuthorizerRequest {
+    /// Input to a `TOKEN` authorizer, which is only provided a bearer token
+    Token(ApiGatewayCustomAuthorizerTokenRequest),
+    /// Input to a `REQUEST` authorizer, which is provided the request's parameters
+    Request(ApiGatewayCustomAuthorizerRequestTypeRequest),
+}
+
+impl ApiGatewayCustomAuthorizerRequest {
+    /// Return the ARN of the method being authorized
+    pub fn method_arn(&self) -> &str {
+        match self {
+            ApiGatewayCustomAuthorizerRequest::Token(token) => &token.method_arn,
+            ApiGatewayCustomAuthorizerRequest::Request(request) => &request.method_arn,
+        }
+    }
+}
+
+/// Input to a `TOKEN` Lambda authorizer
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayCustomAuthorizerTokenRequest {
+    /// The value of the configured token source header
+    pub authorization_token: String,
+    /// ARN of the method being authorized
+    pub method_arn: String,
+}
+
+/// Input to a `REQUEST` Lambda authorizer
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayCustomAuthorizerRequestTypeRequest {
+    /// ARN of the method being authorized
+    pub method_arn: String,
+    /// The API resource path template, i.e. `/pets/{id}`
+    #[serde(default)]
+    pub resource: String,
+    /// The request path
+    #[serde(default)]
+    pub path: String,
+    /// The request HTTP method
+    #[serde(default)]
+    pub http_method: String,
+    /// Request headers, keeping the last value of repeated headers
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub headers: HashMap<String, String>,
+    /// Request headers with all their values
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub multi_value_headers: HashMap<String, Vec<String>>,
+    /// Query string parameters, keeping the last value of repeated parameters
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub query_string_parameters: HashMap<String, String>,
+    /// Query string parameters with all their values
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub multi_value_query_string_parameters: HashMap<String, Vec<String>>,
+    /// Parameters extracted from the path by the resource template
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub path_parameters: HashMap<String, String>,
+    /// Variables of the stage the request was made to
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub stage_variables: HashMap<String, String>,
+    /// Information about the API and the caller
+    #[serde(default)]
+    pub request_context: ApiGatewayCustomAuthorizerRequestContext,
+}
+
+/// API and caller information provided to `REQUEST` Lambda authorizers
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayCustomAuthorizerRequestContext {
+    /// The request path
+    #[serde(default)]
+    pub path: String,
+    /// The AWS account id owning the API
+    #[serde(default)]
+    pub account_id: String,
+    /// The API Gateway resource id
+    #[serde(default)]
+    pub resource_id: String,
+    /// The deployment stage
+    #[serde(default)]
+    pub stage: String,
+    /// The API Gateway request id
+    #[serde(default)]
+    pub request_id: String,
+    /// Information about the caller
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub identity: ApiGatewayRequestIdentity,
+    /// The API resource path template
+    #[serde(default)]
+    pub resource_path: String,
+    /// The request HTTP method
+    #[serde(default)]
+    pub http_method: String,
+    /// The API Gateway API id
+    #[serde(default)]
+    pub api_id: String,
+}
+
+/// Information about the caller of an API
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayRequestIdentity {
+    /// The API key of the caller, when one is required
+    pub api_key: Option<String>,
+    /// The id of the caller's API key
+    pub api_key_id: Option<String>,
+    /// The caller's IP address
+    #[serde(default)]
+    pub source_ip: String,
+    /// The caller's user agent
+    pub user_agent: Option<String>,
+    /// The caller's client certificate when mutual TLS is enabled
+    pub client_cert: Option<Value>,
+}
+
+/// The input to an API Gateway HTTP API Lambda authorizer using payload format version 2.0
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayV2CustomAuthorizerRequest {
+    /// The payload format version, `2.0`
+    pub version: String,
+    /// The authorizer type, `REQUEST`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// ARN of the route being authorized
+    pub route_arn: String,
+    /// Values of the configured identity sources
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub identity_source: Vec<String>,
+    /// The route key of the route being authorized, i.e. `GET /pets`
+    #[serde(default)]
+    pub route_key: String,
+    /// The request path
+    #[serde(default)]
+    pub raw_path: String,
+    /// The raw, undecoded query string
+    #[serde(default)]
+    pub raw_query_string: String,
+    /// Request cookies
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub cookies: Vec<String>,
+    /// Request headers, with repeated headers joined by commas
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub headers: HashMap<String, String>,
+    /// Query string parameters, with repeated parameters joined by commas
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub query_string_parameters: HashMap<String, String>,
+    /// Information about the API and the caller
+    #[serde(default)]
+    pub request_context: ApiGatewayV2CustomAuthorizerRequestContext,
+    /// Parameters extracted from the path by the route
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub path_parameters: HashMap<String, String>,
+    /// Variables of the stage the request was made to
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub stage_variables: HashMap<String, String>,
+}
+
+/// API and caller information provided to HTTP API Lambda authorizers
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayV2CustomAuthorizerRequestContext {
+    /// The AWS account id owning the API
+    #[serde(default)]
+    pub account_id: String,
+    /// The API Gateway API id
+    #[serde(default)]
+    pub api_id: String,
+    /// The caller's client certificate when mutual TLS is enabled
+    pub authentication: Option<Value>,
+    /// The domain name the request was made to
+    #[serde(default)]
+    pub domain_name: String,
+    /// The first label of the domain name the request was made to
+    #[serde(default)]
+    pub domain_prefix: String,
+    /// HTTP details of the request
+    #[serde(default)]
+    pub http: ApiGatewayV2CustomAuthorizerHttp,
+    /// The API Gateway request id
+    #[serde(default)]
+    pub request_id: String,
+    /// The route key of the route being authorized
+    #[serde(default)]
+    pub route_key: String,
+    /// The deployment stage
+    #[serde(default)]
+    pub stage: String,
+    /// The formatted request time
+    #[serde(default)]
+    pub time: String,
+    /// The request time in milliseconds since the unix epoch
+    #[serde(default)]
+    pub time_epoch: i64,
+}
+
+/// HTTP details of an HTTP API request, as provided to functions and Lambda authorizers
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayV2CustomAuthorizerHttp {
+    /// The request HTTP method
+    pub method: String,
+    /// The request path
+    #[serde(default)]
+    pub path: String,
+    /// The request protocol, i.e. `HTTP/1.1`
+    #[serde(default)]
+    pub protocol: String,
+    /// The caller's IP address
+    #[serde(default)]
+    pub source_ip: String,
+    /// The caller's user agent
+    #[serde(default)]
+    pub user_agent: String,
+}
+
+/// A request to a REST API proxied to a function
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayProxyRequest {
+    /// The API resource path template, i.e. `/pets/{id}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource: Option<String>,
+    /// The request path
+    pub path: String,
+    /// The request HTTP method
+    pub http_method: String,
+    /// Request headers, keeping the last value of repeated headers
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub headers: HashMap<String, String>,
+    /// Request headers with all their values
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub multi_value_headers: HashMap<String, Vec<String>>,
+    /// Query string parameters, keeping the last value of repeated parameters
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub query_string_parameters: HashMap<String, String>,
+    /// Query string parameters with all their values
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub multi_value_query_string_parameters: HashMap<String, Vec<String>>,
+    /// Parameters extracted from the path by the resource template
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub path_parameters: HashMap<String, String>,
+    /// Variables of the stage the request was made to
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub stage_variables: HashMap<String, String>,
+    /// Information about the API and the caller
+    pub request_context: ApiGatewayProxyRequestContext,
+    /// The request body, base64 encoded for binary bodies
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Whether the body is base64 encoded
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub is_base64_encoded: bool,
+}
+
+/// API and caller information of a request to a REST API
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayProxyRequestContext {
+    /// The AWS account id owning the API
+    #[serde(default)]
+    pub account_id: String,
+    /// The API Gateway resource id
+    #[serde(default)]
+    pub resource_id: String,
+    /// The deployment stage
+    #[serde(default)]
+    pub stage: String,
+    /// The API Gateway request id
+    #[serde(default)]
+    pub request_id: String,
+    /// Information about the caller
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub identity: ApiGatewayRequestIdentity,
+    /// The API resource path template
+    #[serde(default)]
+    pub resource_path: String,
+    /// The request HTTP method
+    #[serde(default)]
+    pub http_method: String,
+    /// The API Gateway API id
+    #[serde(default)]
+    pub api_id: String,
+    /// The request time in milliseconds since the unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_time_epoch: Option<i64>,
+    /// What the Lambda authorizer or Cognito user pool of the method returned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorizer: Option<Value>,
+}
+
+/// A request to an HTTP API using payload format version 2.0, or to a function URL
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayV2Request {
+    /// The payload format version, `2.0`
+    pub version: String,
+    /// The route key of the matching route, i.e. `GET /pets`
+    #[serde(default)]
+    pub route_key: String,
+    /// The request path
+    pub raw_path: String,
+    /// The raw, undecoded query string
+    #[serde(default)]
+    pub raw_query_string: String,
+    /// Request cookies
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub cookies: Vec<String>,
+    /// Request headers, with repeated headers joined by commas
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub headers: HashMap<String, String>,
+    /// Query string parameters, with repeated parameters joined by commas
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub query_string_parameters: HashMap<String, String>,
+    /// Parameters extracted from the path by the route
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub path_parameters: HashMap<String, String>,
+    /// Variables of the stage the request was made to
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub stage_variables: HashMap<String, String>,
+    /// Information about the API and the caller
+    pub request_context: ApiGatewayV2RequestContext,
+    /// The request body, base64 encoded for binary bodies
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Whether the body is base64 encoded
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub is_base64_encoded: bool,
+}
+
+/// API and caller information of a request to an HTTP API
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayV2RequestContext {
+    /// The AWS account id owning the API
+    #[serde(default)]
+    pub account_id: String,
+    /// The API Gateway API id
+    #[serde(default)]
+    pub api_id: String,
+    /// What the JWT or Lambda authorizer of the route returned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorizer: Option<Value>,
+    /// The caller's client certificate when mutual TLS is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authentication: Option<Value>,
+    /// The domain name the request was made to
+    #[serde(default)]
+    pub domain_name: String,
+    /// The first label of the domain name the request was made to
+    #[serde(default)]
+    pub domain_prefix: String,
+    /// HTTP details of the request
+    pub http: ApiGatewayV2CustomAuthorizerHttp,
+    /// The API Gateway request id
+    #[serde(default)]
+    pub request_id: String,
+    /// The route key of the matching route
+    #[serde(default)]
+    pub route_key: String,
+    /// The deployment stage
+    #[serde(default)]
+    pub stage: String,
+    /// The formatted request time
+    #[serde(default)]
+    pub time: String,
+    /// The request time in milliseconds since the unix epoch
+    #[serde(default)]
+    pub time_epoch: i64,
+}
+
+/// The IAM policy response of an API Gateway Lambda authorizer
+///
+/// HTTP API authorizers which do not use simple responses answer with this type as well.
+/// Instances are most easily assembled with a [`PolicyDocumentBuilder`](struct.PolicyDocumentBuilder.html).
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayCustomAuthorizerResponse {
+    /// Identifies the authorized caller, made available to integrations as `$context.authorizer.principalId`
+    pub principal_id: String,
+    /// The policy API Gateway evaluates against the invoked method
+    pub policy_document: PolicyDocument,
+    /// Key-value pairs made available to integrations as `$context.authorizer.<key>`
+    #[serde(default, skip_serializing_if = "AuthorizerContext::is_empty")]
+    pub context: AuthorizerContext,
+    /// The API key used for usage plans when the API key source is `AUTHORIZER`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_identifier_key: Option<String>,
+}
+
+/// The IAM policy response of an HTTP API Lambda authorizer using payload format version 2.0
+pub type ApiGatewayV2CustomAuthorizerIamResponse = ApiGatewayCustomAuthorizerResponse;
+
+/// The simple response of an HTTP API Lambda authorizer using payload format version 2.0
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGatewayV2CustomAuthorizerSimpleResponse {
+    /// Whether the request is allowed
+    pub is_authorized: bool,
+    /// Key-value pairs made available to integrations as `$context.authorizer.<key>`
+    #[serde(default, skip_serializing_if = "AuthorizerContext::is_empty")]
+    pub context: AuthorizerContext,
+}
+
+/// A value of an authorizer context
+///
+/// API Gateway only accepts strings, numbers and booleans as context values,
+/// so only those types convert into an `AuthorizerContextValue`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum AuthorizerContextValue {
+    /// A string value
+    String(String),
+    /// A numeric value
+    Number(Number),
+    /// A boolean value
+    Boolean(bool),
+}
+
+impl<'a> From<&'a str> for AuthorizerContextValue {
+    fn from(value: &'a str) -> Self {
+        AuthorizerContextValue::String(value.into())
+    }
+}
+
+impl From<String> for AuthorizerContextValue {
+    fn from(value: String) -> Self {
+        AuthorizerContextValue::String(value)
+    }
+}
+
+impl From<bool> for AuthorizerContextValue {
+    fn from(value: bool) -> Self {
+        AuthorizerContextValue::Boolean(value)
+    }
+}
+
+impl From<Number> for AuthorizerContextValue {
+    fn from(value: Number) -> Self {
+        AuthorizerContextValue::Number(value)
+    }
+}
+
+macro_rules! context_value_from_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for AuthorizerContextValue {
+                fn from(value: $ty) -> Self {
+                    AuthorizerContextValue::Number(value.into())
+                }
+            }
+        )*
+    };
+}
+
+context_value_from_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+/// The key-value pairs a Lambda authorizer passes on to integrations
+///
+/// Pairs are serialized in the order they were first inserted.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AuthorizerContext(Vec<(String, AuthorizerContextValue)>);
+
+impl AuthorizerContext {
+    /// Return a new, empty context
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a value, replacing any previous value for the same key
+    pub fn insert<K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<String>,
+        V: Into<AuthorizerContextValue>,
+    {
+        let key = key.into();
+        let value = value.into();
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((key, value)),
+        }
+    }
+
+    /// Return the value for a key, if any
+    pub fn get(&self, key: &str) -> Option<&AuthorizerContextValue> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// Return an iterator over the context's key-value pairs in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &AuthorizerContextValue)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Return the number of key-value pairs
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Return true if the context has no key-value pairs
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Serialize for AuthorizerContext {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for AuthorizerContext {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ContextVisitor;
+
+        impl<'de> Visitor<'de> for ContextVisitor {
+            type Value = AuthorizerContext;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(formatter, "a map of string, number or boolean values")
+            }
+
+            fn visit_unit<E>(self) -> Result<Self::Value, E>
+            where
+                E: DeError,
+            {
+                Ok(AuthorizerContext::default())
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut context = AuthorizerContext::default();
+                while let Some((key, value)) = map.next_entry::<String, AuthorizerContextValue>()? {
+                    context.insert(key, value);
+                }
+                Ok(context)
+            }
+        }
+
+        deserializer.deserialize_any(ContextVisitor)
+    }
+}
+
+/// A builder of [`ApiGatewayCustomAuthorizerResponse`](struct.ApiGatewayCustomAuthorizerResponse.html)s
+///
+/// Each `allow_*` and `deny_*` call adds a statement to the policy. Method helpers build
+/// method ARNs relative to the API and stage set with `for_method_arn`, or relative to any
+/// API and stage when it is not called.
+///
+/// # Example
+///
+/// ```rust
+/// use lambda_events::apigw::PolicyDocumentBuilder;
+///
+/// let response = PolicyDocumentBuilder::new("user")
+///     .for_method_arn("arn:aws:execute-api:us-east-1:123456789012:abcdef123/test/GET/pets")
+///     .allow_method("GET", "/pets/*")
+///     .deny_method("*", "/admin/*")
+///     .context("tier", "gold")
+///     .context("quota", 100)
+///     .build();
+/// assert_eq!(
+///     response.policy_document.statement[0].resource,
+///     vec!["arn:aws:execute-api:us-east-1:123456789012:abcdef123/test/GET/pets/*"]
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct PolicyDocumentBuilder {
+    principal_id: String,
+    scope: String,
+    statements: Vec<IamPolicyStatement>,
+    context: AuthorizerContext,
+    usage_identifier_key: Option<String>,
+}
+
+impl PolicyDocumentBuilder {
+    /// Return a new builder for a response authorizing the given principal
+    pub fn new<P>(principal_id: P) -> Self
+    where
+        P: Into<String>,
+    {
+        Self {
+            principal_id: principal_id.into(),
+            scope: ANY_API.into(),
+            statements: Vec::new(),
+            context: AuthorizerContext::default(),
+            usage_identifier_key: None,
+        }
+    }
+
+    /// Scope method helpers to the API and stage of a method ARN, typically the one being authorized
+    pub fn for_method_arn(self, method_arn: &str) -> Self {
+        let scope = method_arn.splitn(3, '/').take(2).collect::<Vec<_>>().join("/");
+        Self { scope, ..self }
+    }
+
+    /// Allow invoking the method identified by an ARN, which may contain wildcards
+    pub fn allow_arn<A>(self, arn: A) -> Self
+    where
+        A: Into<String>,
+    {
+        self.statement(IamPolicyEffect::Allow, arn.into())
+    }
+
+    /// Deny invoking the method identified by an ARN, which may contain wildcards
+    pub fn deny_arn<A>(self, arn: A) -> Self
+    where
+        A: Into<String>,
+    {
+        self.statement(IamPolicyEffect::Deny, arn.into())
+    }
+
+    /// Allow an IAM action other than `execute-api:Invoke` on a resource, like `iot:Connect`
+    /// in the policies of IoT custom authorizers
+    pub fn allow_action<A, R>(self, action: A, resource: R) -> Self
+    where
+        A: Into<String>,
+        R: Into<String>,
+    {
+        self.action_statement(IamPolicyEffect::Allow, action.into(), resource.into())
+    }
+
+    /// Deny an IAM action other than `execute-api:Invoke` on a resource
+    pub fn deny_action<A, R>(self, action: A, resource: R) -> Self
+    where
+        A: Into<String>,
+        R: Into<String>,
+    {
+        self.action_statement(IamPolicyEffect::Deny, action.into(), resource.into())
+    }
+
+    /// Allow invoking an HTTP method on a resource path, either of which may be `*`
+    pub fn allow_method(self, http_method: &str, resource: &str) -> Self {
+        let arn = self.method_arn(http_method, resource);
+        self.allow_arn(arn)
+    }
+
+    /// Deny invoking an HTTP method on a resource path, either of which may be `*`
+    pub fn deny_method(self, http_method: &str, resource: &str) -> Self {
+        let arn = self.method_arn(http_method, resource);
+        self.deny_arn(arn)
+    }
+
+    /// Allow invoking every method of every resource
+    pub fn allow_all(self) -> Self {
+        self.allow_method("*", "*")
+    }
+
+    /// Deny invoking every method of every resource
+    pub fn deny_all(self) -> Self {
+        self.deny_method("*", "*")
+    }
+
+    /// Add a key-value pair to the context passed on to integrations
+    ///
+    /// Values may only be strings, numbers or booleans
+    pub fn context<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<AuthorizerContextValue>,
+    {
+        self.context.insert(key, value);
+        self
+    }
+
+    /// Set the API key used for usage plans when the API key source is `AUTHORIZER`
+    pub fn usage_identifier_key<K>(self, key: K) -> Self
+    where
+        K: Into<String>,
+    {
+        Self {
+            usage_identifier_key: Some(key.into()),
+            ..self
+        }
+    }
+
+    /// Add the statements of a policy document assembled with a
+    /// [`PolicyBuilder`](../iam/struct.PolicyBuilder.html), like ones with conditions
+    pub fn policy(mut self, policy: PolicyDocument) -> Self {
+        self.statements.extend(policy.statement);
+        self
+    }
+
+    /// Return the assembled response
+    pub fn build(self) -> ApiGatewayCustomAuthorizerResponse {
+        ApiGatewayCustomAuthorizerResponse {
+            principal_id: self.principal_id,
+            policy_document: PolicyDocument::new(self.statements),
+            context: self.context,
+            usage_identifier_key: self.usage_identifier_key,
+        }
+    }
+
+    fn statement(self, effect: IamPolicyEffect, resource: String) -> Self {
+        self.action_statement(effect, INVOKE_ACTION.into(), resource)
+    }
+
+    fn action_statement(mut self, effect: IamPolicyEffect, action: String, resource: String) -> Self {
+        self.statements.push(IamPolicyStatement {
+            action: vec![action],
+            resource: vec![resource],
+            ..IamPolicyStatement::new(effect)
+        });
+        self
+    }
+
+    fn method_arn(&self, http_method: &str, resource: &str) -> String {
+        format!(
+            "{}/{}/{}",
+            self.scope,
+            http_method.to_uppercase(),
+            resource.trim_start_matches('/')
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn deserializes_token_request() {
+        let request: ApiGatewayCustomAuthorizerRequest =
+            serde_json::from_str(include_str!("../tests/data/apigw_custom_authorizer_token_request.json"))
+                .expect("failed to deserialize request");
+        match request {
+            ApiGatewayCustomAuthorizerRequest::Token(token) => {
+                assert_eq!(token.authorization_token, "allow");
+                assert_eq!(
+                    token.method_arn,
+                    "arn:aws:execute-api:us-west-2:123456789012:ymy8tbxw7b/*/GET/"
+                );
+            }
+            other => panic!("expected a TOKEN request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deserializes_request_type_request() {
+        let request: ApiGatewayCustomAuthorizerRequest =
+            serde_json::from_str(include_str!("../tests/data/apigw_custom_authorizer_request.json"))
+                .expect("failed to deserialize request");
+        assert_eq!(
+            request.method_arn(),
+            "arn:aws:execute-api:us-east-1:123456789012:abcdef123/test/GET/request"
+        );
+        match request {
+            ApiGatewayCustomAuthorizerRequest::Request(request) => {
+                assert_eq!(request.http_method, "GET");
+                assert_eq!(
+                    request.headers.get("HeaderAuth1").map(String::as_str),
+                    Some("headerValue1")
+                );
+                assert_eq!(
+                    request.multi_value_query_string_parameters.get("QueryString1"),
+                    Some(&vec!["queryValue1".to_string()])
+                );
+                assert_eq!(
+                    request.stage_variables.get("StageVar1").map(String::as_str),
+                    Some("stageValue1")
+                );
+                assert_eq!(request.request_context.api_id, "abcdef123");
+                assert_eq!(request.request_context.identity.source_ip, "IP");
+                assert!(request.request_context.identity.client_cert.is_some());
+            }
+            other => panic!("expected a REQUEST request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deserializes_v2_request() {
+        let request: ApiGatewayV2CustomAuthorizerRequest =
+            serde_json::from_str(include_str!("../tests/data/apigw_v2_custom_authorizer_request.json"))
+                .expect("failed to deserialize request");
+        assert_eq!(request.version, "2.0");
+        assert_eq!(request.kind, "REQUEST");
+        assert_eq!(request.identity_source, vec!["user1", "123"]);
+        assert_eq!(request.request_context.http.method, "POST");
+        assert_eq!(request.request_context.time_epoch, 1_583_348_638_390);
+    }
+
+    #[test]
+    fn deserializes_proxy_requests() {
+        let request: ApiGatewayProxyRequest =
+            serde_json::from_str(include_str!("../tests/data/apigw_proxy_request.json"))
+                .expect("failed to deserialize request");
+        assert_eq!(request.resource.as_deref(), Some("/{proxy+}"));
+        assert_eq!(request.http_method, "GET");
+        assert_eq!(request.path_parameters["proxy"], "hello");
+        assert_eq!(request.query_string_parameters["name"], "me");
+        assert_eq!(request.request_context.identity.source_ip, "192.168.100.1");
+        assert_eq!(request.request_context.stage, "test");
+        assert_eq!(request.body, None);
+        assert!(!request.is_base64_encoded);
+    }
+
+    #[test]
+    fn deserializes_v2_proxy_requests() {
+        let request: ApiGatewayV2Request =
+            serde_json::from_str(include_str!("../tests/data/apigw_v2_proxy_request.json"))
+                .expect("failed to deserialize request");
+        assert_eq!(request.raw_path, "/my/path");
+        assert_eq!(request.cookies, vec!["cookie1=value1", "cookie2=value2"]);
+        assert_eq!(request.request_context.http.method, "POST");
+        assert_eq!(
+            request
+                .request_context
+                .authorizer
+                .as_ref()
+                .map(|authorizer| &authorizer["jwt"]["scopes"]),
+            Some(&json!(["scope1", "scope2"]))
+        );
+        assert_eq!(request.body.as_deref(), Some("Hello from Lambda"));
+        let round_tripped: ApiGatewayV2Request =
+            serde_json::from_value(serde_json::to_value(&request).unwrap()).expect("failed to deserialize request");
+        assert_eq!(round_tripped, request);
+    }
+
+    #[test]
+    fn serializes_documented_allow_response() {
+        let response = PolicyDocumentBuilder::new("user")
+            .allow_arn("arn:aws:execute-api:us-west-2:123456789012:ymy8tbxw7b/*/GET/")
+            .context("stringKey", "value")
+            .context("numberKey", "1")
+            .context("booleanKey", "true")
+            .usage_identifier_key("{api-key}")
+            .build();
+        assert_eq!(
+            serde_json::to_string_pretty(&response).expect("failed to serialize response"),
+            include_str!("../tests/data/apigw_custom_authorizer_allow_response.json").trim_end()
+        );
+    }
+
+    #[test]
+    fn serializes_documented_deny_response() {
+        let response = PolicyDocumentBuilder::new("user")
+            .for_method_arn("arn:aws:execute-api:us-west-2:123456789012:ymy8tbxw7b/*/GET/")
+            .deny_method("GET", "/")
+            .build();
+        assert_eq!(
+            serde_json::to_string_pretty(&response).expect("failed to serialize response"),
+            include_str!("../tests/data/apigw_custom_authorizer_deny_response.json").trim_end()
+        );
+    }
+
+    #[test]
+    fn action_helpers_keep_their_actions() {
+        let response = PolicyDocumentBuilder::new("thing")
+            .allow_action("iot:Connect", "arn:aws:iot:eu-west-1:123456789012:client/thing")
+            .deny_action("iot:Publish", "arn:aws:iot:eu-west-1:123456789012:topic/admin/*")
+            .allow_all()
+            .build();
+        let actions: Vec<_> = response
+            .policy_document
+            .statement
+            .iter()
+            .map(|statement| (statement.effect, statement.action[0].as_str()))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                (IamPolicyEffect::Allow, "iot:Connect"),
+                (IamPolicyEffect::Deny, "iot:Publish"),
+                (IamPolicyEffect::Allow, "execute-api:Invoke"),
+            ]
+        );
+    }
+
+    #[test]
+    fn policy_documents_add_their_statements() {
+        let policy = PolicyDocument::allow()
+            .action("execute-api:Invoke")
+            .resource("arn:aws:execute-api:us-west-2:123456789012:ymy8tbxw7b/*/GET/")
+            .condition("IpAddress", "aws:SourceIp", "203.0.113.0/24")
+            .build()
+            .expect("invalid policy");
+        let response = PolicyDocumentBuilder::new("user").deny_all().policy(policy).build();
+        assert_eq!(
+            serde_json::to_value(&response.policy_document).expect("failed to serialize policy"),
+            json!({
+                "Version": "2012-10-17",
+                "Statement": [
+                    { "Action": "execute-api:Invoke", "Effect": "Deny", "Resource": "arn:aws:execute-api:*:*:*/*/*/*" },
+                    {
+                        "Action": "execute-api:Invoke",
+                        "Effect": "Allow",
+                        "Resource": "arn:aws:execute-api:us-west-2:123456789012:ymy8tbxw7b/*/GET/",
+                        "Condition": { "IpAddress": { "aws:SourceIp": "203.0.113.0/24" } }
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn wildcard_helpers_default_to_any_api() {
+        let response = PolicyDocumentBuilder::new("user").allow_all().deny_all().build();
+        let resources: Vec<_> = response
+            .policy_document
+            .statement
+            .iter()
+            .map(|statement| (statement.effect, statement.resource[0].as_str()))
+            .collect();
+        assert_eq!(
+            resources,
+            vec![
+                (IamPolicyEffect::Allow, "arn:aws:execute-api:*:*:*/*/*/*"),
+                (IamPolicyEffect::Deny, "arn:aws:execute-api:*:*:*/*/*/*"),
+            ]
+        );
+    }
+
+    #[test]
+    fn context_values_keep_their_types() {
+        let response = PolicyDocumentBuilder::new("user")
+            .allow_all()
+            .context("name", "value")
+            .context("count", 1)
+            .context("admin", false)
+            .context("name", "replaced")
+            .build();
+        assert_eq!(
+            serde_json::to_value(&response.context).expect("failed to serialize context"),
+            json!({ "name": "replaced", "count": 1, "admin": false })
+        );
+    }
+
+    #[test]
+    fn rejects_nested_context_values() {
+        let result = serde_json::from_value::<ApiGatewayV2CustomAuthorizerSimpleResponse>(json!({
+            "isAuthorized": true,
+            "context": { "nested": { "not": "allowed" } }
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn serializes_v2_simple_response() {
+        let mut context = AuthorizerContext::new();
+        context.insert("exampleKey", "exampleValue");
+        let response = ApiGatewayV2CustomAuthorizerSimpleResponse {
+            is_authorized: true,
+            context,
+        };
+        assert_eq!(
+            serde_json::to_string(&response).expect("failed to serialize response"),
+            r#"{"isAuthorized":true,"context":{"exampleKey":"exampleValue"}}"#
+        );
+    }
+
+    #[test]
+    fn response_statements_accept_resource_lists() {
+        let response: ApiGatewayCustomAuthorizerResponse = serde_json::from_value(json!({
+            "principalId": "user",
+            "policyDocument": {
+                "Version": "2012-10-17",
+                "Statement": [{
+                    "Action": "execute-api:Invoke",
+                    "Effect": "Allow",
+                    "Resource": ["arn:aws:execute-api:*:*:*/*/GET/a", "arn:aws:execute-api:*:*:*/*/GET/b"]
+                }]
+            }
+        }))
+        .expect("failed to deserialize response");
+        assert_eq!(response.policy_document.statement[0].resource.len(), 2);
+        assert!(response.context.is_empty());
+    }
+}
@@ -0,0 +1,375 @@
+//! AppSync Lambda resolver event types
+//!
+//! AppSync invokes direct Lambda resolvers with an [`AppSyncEvent`](struct.AppSyncEvent.html)
+//! per field to resolve, or, for batching resolvers, with a list of them.
+//! [`AppSyncPayload`](enum.AppSyncPayload.html) accepts both forms.
+
+use serde::ser::{Serialize, Serializer};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, error::Error, fmt};
+
+/// The resolution of a field, generic over the field's arguments `A` and the parent object `S`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AppSyncEvent<A = Value, S = Value> {
+    /// The arguments of the field
+    pub arguments: A,
+    /// Who makes the request
+    #[serde(default)]
+    pub identity: AppSyncIdentity,
+    /// The object the field belongs to, `None` for fields of root types like `Query`
+    pub source: Option<S>,
+    /// The HTTP request
+    pub request: AppSyncRequest,
+    /// The result of the previous function, for pipeline resolvers
+    #[serde(default)]
+    pub prev: Option<Value>,
+    /// The field to resolve
+    pub info: AppSyncInfo,
+    /// The values functions of a pipeline resolver share
+    #[serde(default)]
+    pub stash: HashMap<String, Value>,
+}
+
+/// The resolutions of fields a function is invoked with, a single one or, for batching
+/// resolvers, a list of them
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
+pub enum AppSyncPayload<A = Value, S = Value> {
+    /// A single resolution
+    Single(AppSyncEvent<A, S>),
+    /// A batch of resolutions, to answer with a list of results in the same order
+    Batch(Vec<AppSyncEvent<A, S>>),
+}
+
+impl<A, S> AppSyncPayload<A, S> {
+    /// Return the resolutions, a single one for `Single`
+    pub fn into_events(self) -> Vec<AppSyncEvent<A, S>> {
+        match self {
+            AppSyncPayload::Single(event) => vec![event],
+            AppSyncPayload::Batch(events) => events,
+        }
+    }
+}
+
+/// Who makes a request, depending on the API's authorization mode
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum AppSyncIdentity {
+    /// A user of a Cognito user pool
+    Cognito(AppSyncCognitoIdentity),
+    /// An IAM principal
+    Iam(AppSyncIamIdentity),
+    /// A user of an OpenID Connect provider
+    Oidc(AppSyncOidcIdentity),
+    /// A caller authorized by a Lambda authorizer
+    Lambda(AppSyncLambdaIdentity),
+    /// A caller with an API key, about whom AppSync knows nothing
+    #[default]
+    ApiKey,
+}
+
+/// A user of a Cognito user pool
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSyncCognitoIdentity {
+    /// The subject of the user's token
+    pub sub: String,
+    /// The issuer of the user's token
+    pub issuer: String,
+    /// The name of the user
+    pub username: String,
+    /// The claims of the user's token
+    pub claims: HashMap<String, Value>,
+    /// The IP addresses the request went through
+    pub source_ip: Vec<String>,
+    /// Whether the user pool's configuration allows or denies access by default
+    pub default_auth_strategy: String,
+    /// The groups of the user
+    pub groups: Option<Vec<String>>,
+}
+
+/// An IAM principal
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSyncIamIdentity {
+    /// The account of the principal
+    pub account_id: String,
+    /// The Cognito identity provider which authenticated the caller, for Cognito identity pool credentials
+    pub cognito_identity_auth_provider: Option<String>,
+    /// Whether the caller is `authenticated` or `unauthenticated`, for Cognito identity pool credentials
+    pub cognito_identity_auth_type: Option<String>,
+    /// The Cognito identity of the caller, for Cognito identity pool credentials
+    pub cognito_identity_id: Option<String>,
+    /// The Cognito identity pool of the caller, for Cognito identity pool credentials
+    pub cognito_identity_pool_id: Option<String>,
+    /// The IP addresses the request went through
+    pub source_ip: Vec<String>,
+    /// The ARN of the principal
+    pub user_arn: String,
+    /// The id of the principal
+    pub username: String,
+}
+
+/// A user of an OpenID Connect provider
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AppSyncOidcIdentity {
+    /// The claims of the user's token
+    pub claims: HashMap<String, Value>,
+    /// The issuer of the user's token
+    pub issuer: String,
+    /// The subject of the user's token
+    pub sub: String,
+}
+
+/// A caller authorized by a Lambda authorizer
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSyncLambdaIdentity {
+    /// The context the authorizer returned
+    pub resolver_context: HashMap<String, Value>,
+}
+
+/// The HTTP request of a resolution
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSyncRequest {
+    /// The headers of the request, by lowercase name
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// The custom domain the request was made on
+    #[serde(default)]
+    pub domain_name: Option<String>,
+}
+
+/// The field to resolve
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSyncInfo {
+    /// The name of the field
+    pub field_name: String,
+    /// The name of the type the field belongs to
+    pub parent_type_name: String,
+    /// The paths of the fields selected below the field, like `customer/name`
+    #[serde(default)]
+    pub selection_set_list: Vec<String>,
+    /// The selection set below the field, as GraphQL
+    #[serde(rename = "selectionSetGraphQL")]
+    pub selection_set_graphql: String,
+    /// The variables of the operation
+    #[serde(default)]
+    pub variables: HashMap<String, Value>,
+}
+
+/// An error AppSync reports as a GraphQL error of the field
+///
+/// It serializes as `{ "error": { "message": ..., "type": ..., "info": ... } }`, the shape
+/// which a resolver's response mapping template passes on with `$util.error`.
+///
+/// ```rust
+/// use lambda_events::appsync::{AppSyncError, AppSyncEvent};
+/// use serde_json::Value;
+///
+/// fn resolve(event: AppSyncEvent) -> Result<Value, AppSyncError> {
+///     event
+///         .stash
+///         .get("order")
+///         .cloned()
+///         .ok_or_else(|| AppSyncError::new("order not found").with_type("NotFound"))
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppSyncError {
+    /// The message of the error
+    pub message: String,
+    /// The type of the error
+    pub error_type: Option<String>,
+    /// More details about the error
+    pub error_info: Option<Value>,
+}
+
+impl AppSyncError {
+    /// Build an error with the message `message`
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            error_type: None,
+            error_info: None,
+        }
+    }
+
+    /// Set the type of the error
+    pub fn with_type(mut self, error_type: impl Into<String>) -> Self {
+        self.error_type = Some(error_type.into());
+        self
+    }
+
+    /// Set more details about the error
+    pub fn with_info(mut self, error_info: Value) -> Self {
+        self.error_info = Some(error_info);
+        self
+    }
+}
+
+/// The serialized form of `AppSyncError`
+#[derive(Serialize)]
+struct AppSyncErrorEnvelope<'a> {
+    error: AppSyncErrorBody<'a>,
+}
+
+#[derive(Serialize)]
+struct AppSyncErrorBody<'a> {
+    message: &'a str,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    error_type: Option<&'a str>,
+    #[serde(rename = "info", skip_serializing_if = "Option::is_none")]
+    error_info: Option<&'a Value>,
+}
+
+impl Serialize for AppSyncError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        AppSyncErrorEnvelope {
+            error: AppSyncErrorBody {
+                message: &self.message,
+                error_type: self.error_type.as_deref(),
+                error_info: self.error_info.as_ref(),
+            },
+        }
+        .serialize(serializer)
+    }
+}
+
+impl fmt::Display for AppSyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error_type {
+            Some(error_type) => write!(f, "{}: {}", error_type, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl Error for AppSyncError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_json::json;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct GetOrder {
+        id: String,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct Order {
+        id: String,
+        customer_id: String,
+    }
+
+    #[test]
+    fn round_trips_events() {
+        for fixture in &[
+            include_str!("../tests/data/appsync_cognito_event.json"),
+            include_str!("../tests/data/appsync_iam_batch_event.json"),
+        ] {
+            assert_round_trips::<AppSyncPayload>(fixture);
+        }
+    }
+
+    #[test]
+    fn deserializes_cognito_resolutions() {
+        let event: AppSyncEvent<GetOrder> =
+            serde_json::from_str(include_str!("../tests/data/appsync_cognito_event.json"))
+                .expect("failed to deserialize event");
+        assert_eq!(event.arguments, GetOrder { id: "order-42".into() });
+        assert_eq!(event.source, None);
+        assert_eq!(
+            event.info.selection_set_list,
+            vec!["id", "total", "customer", "customer/name"]
+        );
+        match &event.identity {
+            AppSyncIdentity::Cognito(identity) => {
+                assert_eq!(identity.username, "jane");
+                assert_eq!(identity.groups, Some(vec!["admins".into()]));
+                assert_eq!(identity.claims["token_use"], "id");
+            }
+            other => panic!("unexpected identity {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deserializes_iam_batches() {
+        let payload: AppSyncPayload<Value, Order> =
+            serde_json::from_str(include_str!("../tests/data/appsync_iam_batch_event.json"))
+                .expect("failed to deserialize event");
+        let events = payload.into_events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| event.source.as_ref().map(|order| order.customer_id.as_str()))
+                .collect::<Vec<_>>(),
+            vec![Some("customer-7"), Some("customer-9")]
+        );
+        match &events[0].identity {
+            AppSyncIdentity::Iam(identity) => {
+                assert_eq!(identity.account_id, "123456789012");
+                assert_eq!(identity.cognito_identity_id, None);
+            }
+            other => panic!("unexpected identity {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deserializes_other_identities() {
+        let mut fixture: Value =
+            serde_json::from_str(include_str!("../tests/data/appsync_cognito_event.json")).unwrap();
+        for (identity, expected) in [
+            (Value::Null, AppSyncIdentity::ApiKey),
+            (
+                json!({ "claims": { "sub": "user-1" }, "issuer": "https://auth.example.com", "sub": "user-1" }),
+                AppSyncIdentity::Oidc(AppSyncOidcIdentity {
+                    claims: vec![("sub".to_string(), json!("user-1"))].into_iter().collect(),
+                    issuer: "https://auth.example.com".into(),
+                    sub: "user-1".into(),
+                }),
+            ),
+            (
+                json!({ "resolverContext": { "tenant": "acme" } }),
+                AppSyncIdentity::Lambda(AppSyncLambdaIdentity {
+                    resolver_context: vec![("tenant".to_string(), json!("acme"))].into_iter().collect(),
+                }),
+            ),
+        ] {
+            fixture["identity"] = identity;
+            let event: AppSyncEvent = serde_json::from_value(fixture.clone()).expect("failed to deserialize event");
+            assert_eq!(event.identity, expected);
+            assert_eq!(serde_json::to_value(event).unwrap(), fixture);
+        }
+        fixture.as_object_mut().unwrap().remove("identity");
+        let event: AppSyncEvent = serde_json::from_value(fixture).expect("failed to deserialize event");
+        assert_eq!(event.identity, AppSyncIdentity::ApiKey);
+    }
+
+    #[test]
+    fn serializes_errors() {
+        let error = AppSyncError::new("order not found")
+            .with_type("NotFound")
+            .with_info(json!({ "id": "order-42" }));
+        assert_eq!(error.to_string(), "NotFound: order not found");
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({ "error": { "message": "order not found", "type": "NotFound", "info": { "id": "order-42" } } })
+        );
+        assert_eq!(
+            serde_json::to_value(AppSyncError::new("boom")).unwrap(),
+            json!({ "error": { "message": "boom" } })
+        );
+    }
+}
@@ -0,0 +1,14 @@
+//! Plumbing shared by the partial batch responses of queue and stream event sources
+
+use serde_derive::{Deserialize, Serialize};
+
+/// A message or record which failed to be processed
+///
+/// Identifies SQS messages by their message id, and Kinesis and DynamoDB stream records by
+/// their sequence number.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchItemFailure {
+    /// The message id or sequence number of the failed item
+    pub item_identifier: String,
+}
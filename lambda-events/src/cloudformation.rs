@@ -0,0 +1,596 @@
+//! CloudFormation custom resource event types
+//!
+//! CloudFormation invokes the function backing a custom resource with a
+//! [`CfnRequest`](struct.CfnRequest.html) when the resource is created, updated or deleted, and
+//! then waits, for up to an hour, for a [`CfnResponse`](struct.CfnResponse.html) to be uploaded
+//! to the request's presigned URL. The function's own output is ignored.
+//!
+//! With the opt-in `cloudformation` feature, [`send_cfn_response`](fn.send_cfn_response.html)
+//! uploads responses, and [`cfn_resource`](fn.cfn_resource.html) wraps a handler so that a
+//! response is uploaded even when the handler fails or panics.
+
+use serde::ser::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+#[cfg(feature = "cloudformation")]
+use std::{
+    error::Error,
+    fmt,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+};
+
+/// A lifecycle event of a custom resource, generic over the type of its properties
+///
+/// ```rust
+/// use lambda_events::cloudformation::CfnRequest;
+/// use serde_derive::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// #[serde(rename_all = "PascalCase")]
+/// struct Seeder {
+///     bucket_name: String,
+/// }
+///
+/// fn bucket(event: &CfnRequest<Seeder>) -> &str {
+///     &event.resource_properties.bucket_name
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct CfnRequest<P = Value> {
+    /// Whether the resource is created, updated or deleted
+    pub request_type: CfnRequestType,
+    /// The ARN of the function backing the resource
+    pub service_token: String,
+    /// The presigned S3 URL the response has to be uploaded to
+    #[serde(rename = "ResponseURL")]
+    pub response_url: String,
+    /// The ARN of the stack the resource belongs to
+    pub stack_id: String,
+    /// A unique identifier for the request
+    pub request_id: String,
+    /// The name of the resource in the template
+    pub logical_resource_id: String,
+    /// The identifier the function gave the resource, absent for `Create` requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub physical_resource_id: Option<String>,
+    /// The type of the resource in the template, like `Custom::BucketSeeder`
+    pub resource_type: String,
+    /// The properties of the resource in the template
+    pub resource_properties: P,
+    /// The properties of the resource before the update, for `Update` requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_resource_properties: Option<P>,
+}
+
+/// The lifecycle event of a custom resource
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CfnRequestType {
+    /// The resource is created
+    Create,
+    /// The resource's properties changed
+    Update,
+    /// The resource is deleted, or replaced by a resource with another physical id
+    Delete,
+}
+
+/// The answer to a `CfnRequest`
+///
+/// ```rust
+/// use lambda_events::cloudformation::CfnResponse;
+///
+/// let response = CfnResponse::success()
+///     .physical_resource_id("orders-assets-seed")
+///     .data("ObjectCount", 2);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CfnResponse {
+    /// Whether the request succeeded
+    pub status: CfnResponseStatus,
+    /// Why the request failed, shown in the stack's events
+    pub reason: Option<String>,
+    /// The identifier of the resource, the request's when left out
+    ///
+    /// Returning a different identifier from an `Update` request replaces the resource, and
+    /// CloudFormation follows up with a `Delete` request for the old identifier.
+    pub physical_resource_id: Option<String>,
+    /// The attributes of the resource, available to the template with `Fn::GetAtt`
+    pub data: HashMap<String, Value>,
+    /// Whether `data` is masked in the stack's outputs
+    pub no_echo: bool,
+}
+
+/// Whether a `CfnRequest` succeeded
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CfnResponseStatus {
+    /// The request succeeded
+    Success,
+    /// The request failed, and the stack operation rolls back
+    Failed,
+}
+
+impl CfnResponse {
+    /// Build a response reporting success
+    pub fn success() -> Self {
+        Self {
+            status: CfnResponseStatus::Success,
+            reason: None,
+            physical_resource_id: None,
+            data: HashMap::new(),
+            no_echo: false,
+        }
+    }
+
+    /// Build a response reporting a failure because of `reason`
+    pub fn failed(reason: impl Into<String>) -> Self {
+        Self {
+            status: CfnResponseStatus::Failed,
+            reason: Some(reason.into()),
+            ..Self::success()
+        }
+    }
+
+    /// Set the identifier of the resource
+    pub fn physical_resource_id(mut self, physical_resource_id: impl Into<String>) -> Self {
+        self.physical_resource_id = Some(physical_resource_id.into());
+        self
+    }
+
+    /// Add the attribute `name`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` fails to serialize into JSON, like maps with non string keys
+    pub fn data<T>(mut self, name: impl Into<String>, value: T) -> Self
+    where
+        T: Serialize,
+    {
+        self.data.insert(
+            name.into(),
+            serde_json::to_value(value).expect("failed to serialize attribute"),
+        );
+        self
+    }
+
+    /// Mask the attributes in the stack's outputs
+    pub fn no_echo(mut self, no_echo: bool) -> Self {
+        self.no_echo = no_echo;
+        self
+    }
+}
+
+/// The fields of a request a response is addressed with
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "cloudformation"), allow(dead_code))]
+struct Recipient {
+    response_url: String,
+    stack_id: String,
+    request_id: String,
+    logical_resource_id: String,
+    physical_resource_id: Option<String>,
+}
+
+impl<P> From<&CfnRequest<P>> for Recipient {
+    fn from(request: &CfnRequest<P>) -> Self {
+        Self {
+            response_url: request.response_url.clone(),
+            stack_id: request.stack_id.clone(),
+            request_id: request.request_id.clone(),
+            logical_resource_id: request.logical_resource_id.clone(),
+            physical_resource_id: request.physical_resource_id.clone(),
+        }
+    }
+}
+
+/// The document uploaded to a request's presigned URL
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+#[cfg_attr(not(feature = "cloudformation"), allow(dead_code))]
+struct CfnResponseBody<'a> {
+    status: CfnResponseStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+    physical_resource_id: &'a str,
+    stack_id: &'a str,
+    request_id: &'a str,
+    logical_resource_id: &'a str,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    no_echo: bool,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    data: &'a HashMap<String, Value>,
+}
+
+#[cfg_attr(not(feature = "cloudformation"), allow(dead_code))]
+impl Recipient {
+    fn body<'a>(&'a self, response: &'a CfnResponse) -> CfnResponseBody<'a> {
+        CfnResponseBody {
+            status: response.status,
+            reason: response.reason.as_deref(),
+            physical_resource_id: response
+                .physical_resource_id
+                .as_deref()
+                .or(self.physical_resource_id.as_deref())
+                .unwrap_or(&self.request_id),
+            stack_id: &self.stack_id,
+            request_id: &self.request_id,
+            logical_resource_id: &self.logical_resource_id,
+            no_echo: response.no_echo,
+            data: &response.data,
+        }
+    }
+}
+
+/// Upload `response` to the presigned URL of `request`
+///
+/// The URL is an HTTPS URL, so `client` needs a connector supporting TLS. Responses without a
+/// physical resource id keep the request's, or use the request id for `Create` requests.
+#[cfg(feature = "cloudformation")]
+pub async fn send_cfn_response<C, P>(
+    client: &hyper::Client<C>,
+    request: &CfnRequest<P>,
+    response: &CfnResponse,
+) -> Result<(), CfnError>
+where
+    C: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+{
+    send(client, &Recipient::from(request), response).await
+}
+
+#[cfg(feature = "cloudformation")]
+async fn send<C>(client: &hyper::Client<C>, recipient: &Recipient, response: &CfnResponse) -> Result<(), CfnError>
+where
+    C: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+{
+    use hyper::{header, Body, Request};
+
+    let body = serde_json::to_vec(&recipient.body(response)).map_err(CfnError::Json)?;
+    // the URL is signed for an empty content type, anything else is rejected with a 403
+    let req = Request::put(recipient.response_url.as_str())
+        .header(header::CONTENT_TYPE, "")
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(Body::from(body))
+        .map_err(CfnError::Request)?;
+    let res = client.request(req).await.map_err(CfnError::Http)?;
+    if !res.status().is_success() {
+        return Err(CfnError::Status(res.status()));
+    }
+    Ok(())
+}
+
+/// Wrap `handler` so that its responses are uploaded with `client`
+///
+/// Errors and panics of `handler` are uploaded as `FAILED` responses, so that the stack
+/// operation fails right away instead of waiting an hour for a response.
+///
+/// ```rust,no_run
+/// use lambda::{handler_fn, Context};
+/// use lambda_events::cloudformation::{cfn_resource, CfnRequest, CfnResponse};
+///
+/// type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     // presigned URLs are HTTPS URLs, use a client with a TLS connector in practice
+///     lambda::run(cfn_resource(hyper::Client::new(), handler_fn(func))).await?;
+///     Ok(())
+/// }
+///
+/// async fn func(_: CfnRequest, _: Context) -> Result<CfnResponse, Error> {
+///     Ok(CfnResponse::success())
+/// }
+/// ```
+#[cfg(feature = "cloudformation")]
+pub fn cfn_resource<C, H>(client: hyper::Client<C>, handler: H) -> CfnResource<C, H> {
+    CfnResource { client, handler }
+}
+
+/// A `lambda::Handler` uploading the responses of a custom resource handler
+///
+/// Returned by [`cfn_resource`](fn.cfn_resource.html).
+#[cfg(feature = "cloudformation")]
+#[derive(Debug, Clone)]
+pub struct CfnResource<C, H> {
+    client: hyper::Client<C>,
+    handler: H,
+}
+
+#[cfg(feature = "cloudformation")]
+impl<C, H, P> lambda::Handler<CfnRequest<P>, ()> for CfnResource<C, H>
+where
+    C: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+    H: lambda::Handler<CfnRequest<P>, CfnResponse>,
+    H::Error: fmt::Display,
+    H::Fut: Send + 'static,
+{
+    type Error = CfnError;
+    type Fut = Pin<Box<dyn Future<Output = Result<(), CfnError>> + Send>>;
+
+    fn call(&mut self, event: CfnRequest<P>, context: lambda::Context) -> Self::Fut {
+        use futures::FutureExt;
+
+        let client = self.client.clone();
+        let recipient = Recipient::from(&event);
+        let handler = &mut self.handler;
+        let fut = panic::catch_unwind(AssertUnwindSafe(|| handler.call(event, context)));
+        Box::pin(async move {
+            let outcome = match fut {
+                Ok(fut) => AssertUnwindSafe(fut).catch_unwind().await,
+                Err(panic) => Err(panic),
+            };
+            let response = response(outcome);
+            send(&client, &recipient, &response).await
+        })
+    }
+}
+
+/// Turn the outcome of a handler into the response to upload
+#[cfg(feature = "cloudformation")]
+fn response<E>(outcome: Result<Result<CfnResponse, E>, Box<dyn std::any::Any + Send>>) -> CfnResponse
+where
+    E: fmt::Display,
+{
+    match outcome {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => CfnResponse::failed(e.to_string()),
+        Err(panic) => CfnResponse::failed(match panic.downcast_ref::<&str>() {
+            Some(message) => format!("handler panicked: {}", message),
+            None => match panic.downcast_ref::<String>() {
+                Some(message) => format!("handler panicked: {}", message),
+                None => "handler panicked".to_string(),
+            },
+        }),
+    }
+}
+
+/// Returned when a response can't be uploaded
+#[cfg(feature = "cloudformation")]
+#[derive(Debug)]
+pub enum CfnError {
+    /// Returned when the response fails to serialize into JSON
+    Json(serde_json::Error),
+    /// Returned when the request's response URL is not a valid URL
+    Request(hyper::http::Error),
+    /// Returned when the response fails to upload
+    Http(hyper::Error),
+    /// Returned when the presigned URL rejects the response
+    Status(hyper::StatusCode),
+}
+
+#[cfg(feature = "cloudformation")]
+impl fmt::Display for CfnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CfnError::Json(json) => write!(f, "failed to serialize response into JSON: {}", json),
+            CfnError::Request(request) => write!(f, "invalid response URL: {}", request),
+            CfnError::Http(http) => write!(f, "failed to upload response: {}", http),
+            CfnError::Status(status) => write!(f, "response URL rejected the response with status {}", status),
+        }
+    }
+}
+
+#[cfg(feature = "cloudformation")]
+impl Error for CfnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CfnError::Json(json) => Some(json),
+            CfnError::Request(request) => Some(request),
+            CfnError::Http(http) => Some(http),
+            CfnError::Status(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_json::json;
+
+    #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct Seeder {
+        service_token: String,
+        bucket_name: String,
+        objects: Vec<String>,
+    }
+
+    #[test]
+    fn round_trips_events() {
+        for fixture in &[
+            include_str!("../tests/data/cloudformation_create_event.json"),
+            include_str!("../tests/data/cloudformation_update_event.json"),
+            include_str!("../tests/data/cloudformation_delete_event.json"),
+        ] {
+            assert_round_trips::<CfnRequest<Seeder>>(fixture);
+        }
+    }
+
+    #[test]
+    fn deserializes_typed_properties() {
+        let create: CfnRequest<Seeder> =
+            assert_round_trips(include_str!("../tests/data/cloudformation_create_event.json"));
+        assert_eq!(create.request_type, CfnRequestType::Create);
+        assert_eq!(create.physical_resource_id, None);
+        assert_eq!(create.old_resource_properties, None);
+        assert_eq!(create.resource_properties.bucket_name, "orders-assets");
+
+        let update: CfnRequest<Seeder> =
+            assert_round_trips(include_str!("../tests/data/cloudformation_update_event.json"));
+        assert_eq!(update.request_type, CfnRequestType::Update);
+        assert_eq!(update.physical_resource_id.as_deref(), Some("orders-assets-seed"));
+        assert_eq!(update.resource_properties.objects.len(), 3);
+        assert_eq!(update.old_resource_properties.map(|old| old.objects.len()), Some(2));
+    }
+
+    #[test]
+    fn serializes_response_bodies() {
+        let create: CfnRequest = assert_round_trips(include_str!("../tests/data/cloudformation_create_event.json"));
+        let recipient = Recipient::from(&create);
+        assert_eq!(
+            serde_json::to_value(recipient.body(&CfnResponse::failed("bucket missing"))).unwrap(),
+            json!({
+                "Status": "FAILED",
+                "Reason": "bucket missing",
+                "PhysicalResourceId": "5e6f7a8b-9c0d-1e2f-3a4b-5c6d7e8f9a0b",
+                "StackId": "arn:aws:cloudformation:us-east-1:123456789012:stack/orders/1a2b3c4d-5e6f-7a8b-9c0d-1e2f3a4b5c6d",
+                "RequestId": "5e6f7a8b-9c0d-1e2f-3a4b-5c6d7e8f9a0b",
+                "LogicalResourceId": "Seeder"
+            })
+        );
+
+        let update: CfnRequest = assert_round_trips(include_str!("../tests/data/cloudformation_update_event.json"));
+        let recipient = Recipient::from(&update);
+        let response = CfnResponse::success().data("ObjectCount", 3).no_echo(true);
+        let body = serde_json::to_value(recipient.body(&response)).unwrap();
+        assert_eq!(body["PhysicalResourceId"], "orders-assets-seed");
+        assert_eq!(body["NoEcho"], true);
+        assert_eq!(body["Data"], json!({ "ObjectCount": 3 }));
+        assert_eq!(body.get("Reason"), None);
+
+        let response = CfnResponse::success().physical_resource_id("replacement");
+        assert_eq!(
+            serde_json::to_value(recipient.body(&response)).unwrap()["PhysicalResourceId"],
+            "replacement"
+        );
+    }
+
+    #[cfg(feature = "cloudformation")]
+    mod client {
+        use super::*;
+        use hyper::{
+            header::HeaderMap,
+            service::{make_service_fn, service_fn},
+            Body, Method, Response, Server,
+        };
+        use lambda::{handler_fn, Context, Handler};
+        use std::convert::Infallible;
+        use tokio::sync::mpsc;
+
+        type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+        struct Upload {
+            method: Method,
+            headers: HeaderMap,
+            body: Value,
+        }
+
+        /// Start a server recording uploads and answering them with `status`, returning a
+        /// request whose response URL points at the server
+        fn stub(status: u16) -> (CfnRequest<Seeder>, mpsc::UnboundedReceiver<Upload>) {
+            let (tx, rx) = mpsc::unbounded_channel();
+            let make_service = make_service_fn(move |_| {
+                let tx = tx.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req: hyper::Request<Body>| {
+                        let tx = tx.clone();
+                        async move {
+                            let (parts, body) = req.into_parts();
+                            let body = hyper::body::to_bytes(body).await?;
+                            let _ = tx.send(Upload {
+                                method: parts.method,
+                                headers: parts.headers,
+                                body: serde_json::from_slice(&body).expect("invalid response body"),
+                            });
+                            Ok::<_, hyper::Error>(Response::builder().status(status).body(Body::empty()).unwrap())
+                        }
+                    }))
+                }
+            });
+            let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+            let addr = server.local_addr();
+            tokio::spawn(server);
+
+            let mut request = assert_round_trips::<CfnRequest<Seeder>>(include_str!(
+                "../tests/data/cloudformation_update_event.json"
+            ));
+            request.response_url = format!("http://{}/response?X-Amz-Signature=abc", addr);
+            (request, rx)
+        }
+
+        async fn seed(event: CfnRequest<Seeder>, _: Context) -> Result<CfnResponse, Error> {
+            Ok(CfnResponse::success().data("ObjectCount", event.resource_properties.objects.len()))
+        }
+
+        async fn fail(_: CfnRequest<Seeder>, _: Context) -> Result<CfnResponse, Error> {
+            Err("bucket missing".into())
+        }
+
+        async fn panic(_: CfnRequest<Seeder>, _: Context) -> Result<CfnResponse, Error> {
+            panic!("bucket exploded")
+        }
+
+        #[tokio::test]
+        async fn puts_responses_with_empty_content_types() {
+            let (request, mut uploads) = stub(200);
+            send_cfn_response(&hyper::Client::new(), &request, &CfnResponse::success())
+                .await
+                .expect("failed to send response");
+            let upload = uploads.recv().await.expect("missing upload");
+            assert_eq!(upload.method, Method::PUT);
+            assert_eq!(upload.headers["content-type"], "");
+            assert_eq!(
+                upload.body,
+                json!({
+                    "Status": "SUCCESS",
+                    "PhysicalResourceId": "orders-assets-seed",
+                    "StackId": request.stack_id,
+                    "RequestId": request.request_id,
+                    "LogicalResourceId": "Seeder"
+                })
+            );
+        }
+
+        #[tokio::test]
+        async fn reports_rejected_responses() {
+            let (request, _uploads) = stub(403);
+            match send_cfn_response(&hyper::Client::new(), &request, &CfnResponse::success()).await {
+                Err(CfnError::Status(status)) => assert_eq!(status, 403),
+                other => panic!("expected a status error, got {:?}", other),
+            }
+        }
+
+        #[tokio::test]
+        async fn uploads_handler_responses() {
+            let (request, mut uploads) = stub(200);
+            let mut resource = cfn_resource(hyper::Client::new(), handler_fn(seed));
+            resource
+                .call(request, Context::default())
+                .await
+                .expect("failed to send response");
+            let upload = uploads.recv().await.expect("missing upload");
+            assert_eq!(upload.body["Status"], "SUCCESS");
+            assert_eq!(upload.body["Data"], json!({ "ObjectCount": 3 }));
+        }
+
+        #[tokio::test]
+        async fn uploads_failures_when_handlers_fail() {
+            let (request, mut uploads) = stub(200);
+            let mut resource = cfn_resource(hyper::Client::new(), handler_fn(fail));
+            resource
+                .call(request, Context::default())
+                .await
+                .expect("failed to send response");
+            let upload = uploads.recv().await.expect("missing upload");
+            assert_eq!(upload.body["Status"], "FAILED");
+            assert_eq!(upload.body["Reason"], "bucket missing");
+        }
+
+        #[tokio::test]
+        async fn uploads_failures_when_handlers_panic() {
+            let (request, mut uploads) = stub(200);
+            let mut resource = cfn_resource(hyper::Client::new(), handler_fn(panic));
+            resource
+                .call(request, Context::default())
+                .await
+                .expect("failed to send response");
+            let upload = uploads.recv().await.expect("missing upload");
+            assert_eq!(upload.body["Status"], "FAILED");
+            assert_eq!(upload.body["Reason"], "handler panicked: bucket exploded");
+            assert_eq!(upload.body["PhysicalResourceId"], "orders-assets-seed");
+        }
+    }
+}
@@ -0,0 +1,569 @@
+//! CloudFront Lambda@Edge event types
+//!
+//! CloudFront invokes functions associated with a distribution with a [`CfEvent`](struct.CfEvent.html)
+//! at one of four points of a request's life: when it receives the viewer's request, before it
+//! forwards the request to the origin, when it receives the origin's response and before it returns
+//! the response to the viewer.
+//!
+//! Request triggers answer with the, possibly modified, [`CfRequest`](struct.CfRequest.html) to let
+//! it through, or with a [`CfResponse`](struct.CfResponse.html) generated in its place. Response
+//! triggers answer with the, possibly modified, response. [`CfOutput`](enum.CfOutput.html) covers
+//! both. CloudFront rejects outputs with fields it doesn't expect, so optional fields are left out
+//! entirely rather than serialized as `null`. Fields CloudFront sends which aren't modeled here
+//! are kept in `extra` maps, so that returning a request or response it sent doesn't drop them.
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{btree_map, BTreeMap, HashMap};
+
+/// An event delivered by Lambda@Edge
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CfEvent {
+    /// The records of the invocation, of which there is always exactly one
+    #[serde(rename = "Records")]
+    pub records: Vec<CfRecord>,
+    /// Fields of the event which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl CfEvent {
+    /// Consume the event, returning the request of its record
+    pub fn into_request(self) -> Option<CfRequest> {
+        self.records.into_iter().next().map(|record| record.cf.request)
+    }
+
+    /// Consume the event, returning the response of its record, for response triggers
+    pub fn into_response(self) -> Option<CfResponse> {
+        self.records.into_iter().next().and_then(|record| record.cf.response)
+    }
+}
+
+/// A single record of a Lambda@Edge event
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CfRecord {
+    /// The request, and the response for response triggers
+    pub cf: CfMessage,
+    /// Fields of the record which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The request, and the response for response triggers, of a Lambda@Edge event
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CfMessage {
+    /// The distribution and the trigger which invoked the function
+    pub config: CfConfig,
+    /// The viewer's request
+    pub request: CfRequest,
+    /// The response, for `origin-response` and `viewer-response` triggers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<CfResponse>,
+    /// Fields of the message which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The distribution and the trigger which invoked a function
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CfConfig {
+    /// The domain name of the distribution, like `d111111abcdef8.cloudfront.net`
+    pub distribution_domain_name: String,
+    /// The id of the distribution
+    pub distribution_id: String,
+    /// The trigger which invoked the function
+    pub event_type: CfEventType,
+    /// A unique identifier for the viewer's request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Fields of the config which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The point of a request's life at which a function is invoked
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum CfEventType {
+    /// CloudFront received the viewer's request
+    ViewerRequest,
+    /// CloudFront is about to forward the request to the origin
+    OriginRequest,
+    /// CloudFront received the origin's response
+    OriginResponse,
+    /// CloudFront is about to return the response to the viewer
+    ViewerResponse,
+}
+
+/// HTTP headers, keyed by their lowercased names
+///
+/// Each header carries its name as it should be written in `key` along with the value.
+///
+/// ```rust
+/// use lambda_events::cloudfront::CfHeaders;
+///
+/// let mut headers = CfHeaders::default();
+/// headers.insert("Cache-Control", "max-age=60");
+/// assert_eq!(headers.get("cache-control"), Some("max-age=60"));
+/// ```
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct CfHeaders(pub BTreeMap<String, Vec<CfHeader>>);
+
+/// A single value of an HTTP header
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CfHeader {
+    /// The name of the header, as it should be written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// The value of the header
+    pub value: String,
+}
+
+impl CfHeaders {
+    /// Return the first value of the header named `name`, ignoring case
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_all(name).next()
+    }
+
+    /// Return all values of the header named `name`, ignoring case
+    pub fn get_all<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a str> {
+        self.0
+            .get(&name.to_ascii_lowercase())
+            .into_iter()
+            .flatten()
+            .map(|header| header.value.as_str())
+    }
+
+    /// Set the header named `name` to `value`, replacing its previous values
+    pub fn insert(&mut self, name: &str, value: impl Into<String>) {
+        self.0
+            .insert(name.to_ascii_lowercase(), vec![CfHeader::new(name, value)]);
+    }
+
+    /// Add `value` to the values of the header named `name`
+    pub fn append(&mut self, name: &str, value: impl Into<String>) {
+        self.0
+            .entry(name.to_ascii_lowercase())
+            .or_default()
+            .push(CfHeader::new(name, value));
+    }
+
+    /// Remove the header named `name`, returning its values
+    pub fn remove(&mut self, name: &str) -> Option<Vec<CfHeader>> {
+        self.0.remove(&name.to_ascii_lowercase())
+    }
+
+    /// Return `true` if a header named `name` is present, ignoring case
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(&name.to_ascii_lowercase())
+    }
+
+    /// Return an iterator over the lowercased header names and their values
+    pub fn iter(&self) -> btree_map::Iter<'_, String, Vec<CfHeader>> {
+        self.0.iter()
+    }
+
+    /// Return `true` if there are no headers
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl CfHeader {
+    fn new(name: &str, value: impl Into<String>) -> Self {
+        Self {
+            key: Some(name.to_string()),
+            value: value.into(),
+        }
+    }
+}
+
+/// A viewer's request
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CfRequest {
+    /// The request body, when the trigger is configured to include it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<CfRequestBody>,
+    /// The IP address of the viewer
+    pub client_ip: String,
+    /// The request headers
+    pub headers: CfHeaders,
+    /// The request method
+    pub method: String,
+    /// The origin the request is forwarded to, for `origin-request` and `origin-response`
+    /// triggers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<CfOrigin>,
+    /// The query string of the request, without the leading `?`
+    pub querystring: String,
+    /// The path of the request
+    pub uri: String,
+    /// Fields of the request which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The body of a viewer's request
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CfRequestBody {
+    /// Whether the function should replace the body
+    pub action: CfBodyAction,
+    /// The body, encoded as indicated by `encoding`
+    pub data: String,
+    /// How `data` is encoded
+    pub encoding: CfBodyEncoding,
+    /// Whether the body was cut short because it exceeded the size Lambda@Edge exposes
+    #[serde(default)]
+    pub input_truncated: bool,
+    /// Fields of the body which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl CfRequestBody {
+    /// Build a body replacing the request's body with `data`, encoded in base64
+    pub fn replace(data: &[u8]) -> Self {
+        Self {
+            action: CfBodyAction::Replace,
+            data: base64::encode(data),
+            encoding: CfBodyEncoding::Base64,
+            input_truncated: false,
+            extra: HashMap::new(),
+        }
+    }
+
+    /// Return the raw bytes of the body, decoding them from base64 if needed
+    pub fn decoded_data(&self) -> Result<Vec<u8>, base64::DecodeError> {
+        match self.encoding {
+            CfBodyEncoding::Base64 => base64::decode(&self.data),
+            CfBodyEncoding::Text => Ok(self.data.clone().into_bytes()),
+        }
+    }
+}
+
+/// What CloudFront does with a request body returned by a function
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum CfBodyAction {
+    /// Keep the original body
+    ReadOnly,
+    /// Replace the original body with the returned one
+    Replace,
+}
+
+/// How a body is encoded
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum CfBodyEncoding {
+    /// The body is base64 encoded
+    Base64,
+    /// The body is plain text
+    Text,
+}
+
+/// The origin a request is forwarded to
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum CfOrigin {
+    /// A custom HTTP origin
+    #[serde(rename = "custom")]
+    Custom(CfCustomOrigin),
+    /// An S3 bucket
+    #[serde(rename = "s3")]
+    S3(CfS3Origin),
+}
+
+/// A custom HTTP origin
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CfCustomOrigin {
+    /// The headers CloudFront adds to requests forwarded to the origin
+    pub custom_headers: CfHeaders,
+    /// The domain name of the origin
+    pub domain_name: String,
+    /// How long CloudFront keeps idle connections to the origin open, in seconds
+    pub keepalive_timeout: u32,
+    /// The path prepended to request paths
+    pub path: String,
+    /// The port CloudFront connects to
+    pub port: u16,
+    /// The protocol CloudFront connects with, `http` or `https`
+    pub protocol: String,
+    /// How long CloudFront waits for the origin's response, in seconds
+    pub read_timeout: u32,
+    /// The TLS protocols CloudFront may connect with, like `TLSv1.2`
+    pub ssl_protocols: Vec<String>,
+    /// Fields of the origin which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// An S3 bucket origin
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CfS3Origin {
+    /// How CloudFront authenticates to the bucket, `origin-access-identity` or `none`
+    pub auth_method: String,
+    /// The headers CloudFront adds to requests forwarded to the bucket
+    pub custom_headers: CfHeaders,
+    /// The domain name of the bucket
+    pub domain_name: String,
+    /// The path prepended to request paths
+    pub path: String,
+    /// The region of the bucket, when it authenticates with an origin access identity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Fields of the origin which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// A response, received from the origin or generated by a function
+///
+/// ```rust
+/// use lambda_events::cloudfront::CfResponse;
+///
+/// let mut response = CfResponse::new(404).with_body("not found");
+/// response.headers.insert("Content-Type", "text/plain");
+/// assert_eq!(response.status_code(), Some(404));
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CfResponse {
+    /// The response body, for generated responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// How `body` is encoded, `text` when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_encoding: Option<CfBodyEncoding>,
+    /// The response headers
+    #[serde(default)]
+    pub headers: CfHeaders,
+    /// The HTTP status code, as a string
+    pub status: String,
+    /// The reason phrase of the status
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_description: Option<String>,
+    /// Fields of the response which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl CfResponse {
+    /// Build an empty response with the status code `status`
+    pub fn new(status: u16) -> Self {
+        Self {
+            body: None,
+            body_encoding: None,
+            headers: CfHeaders::default(),
+            status: status.to_string(),
+            status_description: None,
+            extra: HashMap::new(),
+        }
+    }
+
+    /// Set a plain text body
+    pub fn with_body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self.body_encoding = Some(CfBodyEncoding::Text);
+        self
+    }
+
+    /// Set a binary body, encoded in base64
+    pub fn with_binary_body(mut self, body: &[u8]) -> Self {
+        self.body = Some(base64::encode(body));
+        self.body_encoding = Some(CfBodyEncoding::Base64);
+        self
+    }
+
+    /// Return the HTTP status code, if it is a valid number
+    pub fn status_code(&self) -> Option<u16> {
+        self.status.parse().ok()
+    }
+}
+
+/// The output of a Lambda@Edge function
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+// outputs are returned once per invocation and serialized right away
+#[allow(clippy::large_enum_variant)]
+pub enum CfOutput {
+    /// Let the request through, for request triggers
+    Request(CfRequest),
+    /// Answer the viewer with this response
+    Response(CfResponse),
+}
+
+impl From<CfRequest> for CfOutput {
+    fn from(request: CfRequest) -> Self {
+        CfOutput::Request(request)
+    }
+}
+
+impl From<CfResponse> for CfOutput {
+    fn from(response: CfResponse) -> Self {
+        CfOutput::Response(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_json::json;
+
+    #[test]
+    fn round_trips_events() {
+        for fixture in &[
+            include_str!("../tests/data/cloudfront_viewer_request_event.json"),
+            include_str!("../tests/data/cloudfront_origin_request_event.json"),
+            include_str!("../tests/data/cloudfront_origin_response_event.json"),
+            include_str!("../tests/data/cloudfront_viewer_response_event.json"),
+        ] {
+            assert_round_trips::<CfEvent>(fixture);
+        }
+    }
+
+    #[test]
+    fn round_trips_unknown_fields_at_every_level() {
+        for (fixture, pointers) in &[
+            (
+                include_str!("../tests/data/cloudfront_origin_request_event.json"),
+                &[
+                    "",
+                    "/Records/0",
+                    "/Records/0/cf",
+                    "/Records/0/cf/config",
+                    "/Records/0/cf/request",
+                    "/Records/0/cf/request/body",
+                    "/Records/0/cf/request/origin/custom",
+                ][..],
+            ),
+            (
+                include_str!("../tests/data/cloudfront_origin_response_event.json"),
+                &["/Records/0/cf/request", "/Records/0/cf/response"][..],
+            ),
+        ] {
+            let mut fixture: Value = serde_json::from_str(fixture).unwrap();
+            for pointer in pointers.iter() {
+                fixture
+                    .pointer_mut(pointer)
+                    .and_then(Value::as_object_mut)
+                    .expect("missing object")
+                    .insert("futureField".into(), json!({ "at": pointer }));
+            }
+            let event: CfEvent = serde_json::from_value(fixture.clone()).expect("failed to deserialize event");
+            assert_eq!(
+                serde_json::to_value(&event).expect("failed to serialize event"),
+                fixture
+            );
+            assert_eq!(
+                serde_json::to_value(CfOutput::from(event.into_request().expect("missing request")))
+                    .expect("failed to serialize output"),
+                fixture["Records"][0]["cf"]["request"]
+            );
+        }
+    }
+
+    #[test]
+    fn deserializes_viewer_requests() {
+        let event = assert_round_trips::<CfEvent>(include_str!("../tests/data/cloudfront_viewer_request_event.json"));
+        assert_eq!(event.records[0].cf.config.event_type, CfEventType::ViewerRequest);
+        assert_eq!(event.records[0].cf.response, None);
+        let request = event.into_request().expect("missing request");
+        assert_eq!(request.headers.get("User-Agent"), Some("curl/7.66.0"));
+        assert_eq!(
+            request.headers.get_all("x-forwarded-for").collect::<Vec<_>>(),
+            vec!["203.0.113.178", "198.51.100.7"]
+        );
+        assert_eq!(request.origin, None);
+        assert_eq!(request.body, None);
+    }
+
+    #[test]
+    fn deserializes_origin_requests_with_bodies() {
+        let request = assert_round_trips::<CfEvent>(include_str!("../tests/data/cloudfront_origin_request_event.json"))
+            .into_request()
+            .expect("missing request");
+        let body = request.body.as_ref().expect("missing body");
+        assert_eq!(body.action, CfBodyAction::ReadOnly);
+        assert_eq!(body.encoding, CfBodyEncoding::Base64);
+        assert_eq!(body.decoded_data().unwrap(), br#"{"orderId":"1"}"#.to_vec());
+        match request.origin {
+            Some(CfOrigin::Custom(origin)) => {
+                assert_eq!(origin.domain_name, "example.org");
+                assert_eq!(origin.port, 443);
+                assert!(origin.custom_headers.is_empty());
+            }
+            other => panic!("expected a custom origin, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deserializes_origin_responses() {
+        let event = assert_round_trips::<CfEvent>(include_str!("../tests/data/cloudfront_origin_response_event.json"));
+        match &event.records[0].cf.request.origin {
+            Some(CfOrigin::S3(origin)) => {
+                assert_eq!(origin.region.as_deref(), Some("us-east-1"));
+                assert_eq!(origin.custom_headers.get("my-origin-custom-header"), Some("Test"));
+            }
+            other => panic!("expected an s3 origin, got {:?}", other),
+        }
+        let response = event.into_response().expect("missing response");
+        assert_eq!(response.status_code(), Some(200));
+        assert_eq!(response.headers.get("etag"), Some("\"a1b2c3d4e5f6\""));
+        assert_eq!(response.body, None);
+    }
+
+    #[test]
+    fn sets_headers_with_cased_keys() {
+        let mut response =
+            assert_round_trips::<CfEvent>(include_str!("../tests/data/cloudfront_viewer_response_event.json"))
+                .into_response()
+                .expect("missing response");
+        response.headers.insert("Strict-Transport-Security", "max-age=63072000");
+        response.headers.append("Set-Cookie", "a=1");
+        response.headers.append("set-cookie", "b=2");
+        assert!(response.headers.remove("SERVER").is_some());
+        assert!(!response.headers.contains("server"));
+        assert_eq!(
+            serde_json::to_value(&response.headers).unwrap(),
+            serde_json::json!({
+                "content-type": [{ "key": "Content-Type", "value": "text/html; charset=UTF-8" }],
+                "set-cookie": [{ "key": "Set-Cookie", "value": "a=1" }, { "key": "set-cookie", "value": "b=2" }],
+                "strict-transport-security": [{ "key": "Strict-Transport-Security", "value": "max-age=63072000" }]
+            })
+        );
+    }
+
+    #[test]
+    fn serializes_generated_responses_without_nulls() {
+        let mut response = CfResponse::new(302);
+        response.headers.insert("Location", "https://example.org/");
+        assert_eq!(
+            serde_json::to_string(&CfOutput::from(response)).expect("failed to serialize response"),
+            r#"{"headers":{"location":[{"key":"Location","value":"https://example.org/"}]},"status":"302"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&CfResponse::new(200).with_binary_body(b"hi")).expect("failed to serialize response"),
+            r#"{"body":"aGk=","bodyEncoding":"base64","headers":{},"status":"200"}"#
+        );
+    }
+
+    #[test]
+    fn serializes_replaced_request_bodies() {
+        let mut request =
+            assert_round_trips::<CfEvent>(include_str!("../tests/data/cloudfront_origin_request_event.json"))
+                .into_request()
+                .expect("missing request");
+        request.body = Some(CfRequestBody::replace(b"{}"));
+        let output = serde_json::to_value(CfOutput::from(request)).expect("failed to serialize request");
+        assert_eq!(
+            output["body"],
+            serde_json::json!({ "action": "replace", "data": "e30=", "encoding": "base64", "inputTruncated": false })
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! CloudWatch Logs subscription filter event types
+//!
+//! Subscription filters invoke functions with a [`CloudWatchLogsEvent`](struct.CloudWatchLogsEvent.html)
+//! whose log events are gzip compressed and base64 encoded.
+//! [`EncodedLogsData::decode`](struct.EncodedLogsData.html#method.decode), available with the
+//! default `gzip` feature, decodes them into [`LogsData`](struct.LogsData.html).
+
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+#[cfg(feature = "gzip")]
+use std::{error::Error, fmt, io};
+
+/// A batch of log events delivered by a subscription filter
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CloudWatchLogsEvent {
+    /// The encoded log events
+    pub awslogs: AwsLogs,
+}
+
+/// The encoded log events of a subscription filter delivery
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AwsLogs {
+    /// The gzip compressed and base64 encoded log events
+    pub data: EncodedLogsData,
+}
+
+/// Gzip compressed and base64 encoded [`LogsData`](struct.LogsData.html)
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct EncodedLogsData(pub String);
+
+/// The decoded log events of a subscription filter delivery
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsData {
+    /// The account the log events were produced in
+    pub owner: String,
+    /// The log group the log events were written to
+    pub log_group: String,
+    /// The log stream the log events were written to
+    pub log_stream: String,
+    /// The names of the subscription filters which matched the log events
+    pub subscription_filters: Vec<String>,
+    /// Whether the delivery carries log events or checks whether the function is reachable
+    pub message_type: MessageType,
+    /// The matching log events
+    pub log_events: Vec<LogEvent>,
+}
+
+/// The kind of a subscription filter delivery
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MessageType {
+    /// The delivery carries log events
+    DataMessage,
+    /// The delivery checks whether the function is reachable, and should be ignored
+    ControlMessage,
+}
+
+/// A single log event
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEvent {
+    /// A unique identifier for the log event
+    pub id: String,
+    /// When the log event was produced, in milliseconds since the Unix epoch
+    pub timestamp: i64,
+    /// The log message
+    pub message: String,
+    /// The fields a filter pattern with named fields extracted from the message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extracted_fields: Option<HashMap<String, String>>,
+}
+
+impl LogsData {
+    /// Return `true` if the delivery only checks whether the function is reachable
+    pub fn is_control_message(&self) -> bool {
+        self.message_type == MessageType::ControlMessage
+    }
+
+    /// Encode the log events the way subscription filters deliver them, for constructing
+    /// events in tests
+    #[cfg(feature = "gzip")]
+    pub fn encode(&self) -> Result<EncodedLogsData, LogsDataError> {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let json = serde_json::to_vec(self).map_err(LogsDataError::Json)?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&json).map_err(LogsDataError::Gzip)?;
+        let compressed = encoder.finish().map_err(LogsDataError::Gzip)?;
+        Ok(EncodedLogsData(base64::encode(&compressed)))
+    }
+}
+
+impl LogEvent {
+    /// Return when the log event was produced
+    pub fn time(&self) -> SystemTime {
+        let millis = Duration::from_millis(self.timestamp.unsigned_abs());
+        if self.timestamp < 0 {
+            UNIX_EPOCH - millis
+        } else {
+            UNIX_EPOCH + millis
+        }
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl CloudWatchLogsEvent {
+    /// Decode the event's log events
+    pub fn decode(&self) -> Result<LogsData, LogsDataError> {
+        self.awslogs.data.decode()
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl EncodedLogsData {
+    /// Decode the log events from base64, decompress them and deserialize them from JSON
+    ///
+    /// ```rust
+    /// use lambda_events::cloudwatch_logs::{CloudWatchLogsEvent, LogsDataError};
+    ///
+    /// fn messages(event: &CloudWatchLogsEvent) -> Result<Vec<String>, LogsDataError> {
+    ///     let logs = event.awslogs.data.decode()?;
+    ///     if logs.is_control_message() {
+    ///         return Ok(Vec::new());
+    ///     }
+    ///     Ok(logs.log_events.into_iter().map(|event| event.message).collect())
+    /// }
+    /// ```
+    pub fn decode(&self) -> Result<LogsData, LogsDataError> {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let compressed = base64::decode(&self.0).map_err(LogsDataError::Base64)?;
+        let mut json = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut json)
+            .map_err(LogsDataError::Gzip)?;
+        serde_json::from_slice(&json).map_err(LogsDataError::Json)
+    }
+}
+
+/// Returned when log events can't be decoded or encoded
+#[cfg(feature = "gzip")]
+#[derive(Debug)]
+pub enum LogsDataError {
+    /// Returned when the log events are not valid base64
+    Base64(base64::DecodeError),
+    /// Returned when the log events are not valid gzip, or when compressing them fails
+    Gzip(io::Error),
+    /// Returned when the log events fail to deserialize from or serialize into JSON
+    Json(serde_json::Error),
+}
+
+#[cfg(feature = "gzip")]
+impl fmt::Display for LogsDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogsDataError::Base64(base64) => write!(f, "log events are not valid base64: {}", base64),
+            LogsDataError::Gzip(gzip) => write!(f, "log events are not valid gzip: {}", gzip),
+            LogsDataError::Json(json) => write!(f, "failed to parse log events from JSON: {}", json),
+        }
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl Error for LogsDataError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LogsDataError::Base64(base64) => Some(base64),
+            LogsDataError::Gzip(gzip) => Some(gzip),
+            LogsDataError::Json(json) => Some(json),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_json::Value;
+
+    #[test]
+    fn round_trips_events() {
+        let fixture = include_str!("../tests/data/cloudwatch_logs_event.json");
+        assert_round_trips::<CloudWatchLogsEvent>(fixture);
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn decodes_log_events() {
+        let logs = assert_round_trips::<CloudWatchLogsEvent>(include_str!("../tests/data/cloudwatch_logs_event.json"))
+            .decode()
+            .expect("failed to decode log events");
+        assert!(!logs.is_control_message());
+        assert_eq!(logs.log_group, "/aws/lambda/orders");
+        assert_eq!(logs.subscription_filters, vec!["orders-errors".to_string()]);
+        assert_eq!(logs.log_events.len(), 3);
+        assert_eq!(logs.log_events[1].message, "ERROR failed to process order 1\n");
+        assert_eq!(
+            logs.log_events[1].time(),
+            UNIX_EPOCH + Duration::from_millis(1_591_005_600_001)
+        );
+        assert_eq!(logs.log_events[0].extracted_fields, None);
+        assert_eq!(
+            logs.log_events[2]
+                .extracted_fields
+                .as_ref()
+                .and_then(|fields| fields.get("orderId"))
+                .map(String::as_str),
+            Some("1")
+        );
+        assert_eq!(
+            serde_json::to_value(&logs).expect("failed to serialize log events"),
+            serde_json::from_str::<Value>(include_str!("../tests/data/cloudwatch_logs_event_decoded.json")).unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn decodes_control_messages() {
+        let logs = assert_round_trips::<CloudWatchLogsEvent>(include_str!(
+            "../tests/data/cloudwatch_logs_control_message_event.json"
+        ))
+        .decode()
+        .expect("failed to decode log events");
+        assert!(logs.is_control_message());
+        assert!(logs.log_group.is_empty());
+        assert!(logs.log_events[0].message.starts_with("CWL CONTROL MESSAGE"));
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn encodes_log_events() {
+        let logs = assert_round_trips::<CloudWatchLogsEvent>(include_str!("../tests/data/cloudwatch_logs_event.json"))
+            .decode()
+            .expect("failed to decode log events");
+        let encoded = logs.encode().expect("failed to encode log events");
+        assert_eq!(encoded.decode().expect("failed to decode log events"), logs);
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn rejects_corrupt_payloads() {
+        match EncodedLogsData("not base64!".into()).decode() {
+            Err(LogsDataError::Base64(_)) => (),
+            other => panic!("expected a base64 error, got {:?}", other),
+        }
+        match EncodedLogsData(base64::encode(b"not gzip")).decode() {
+            Err(LogsDataError::Gzip(_)) => (),
+            other => panic!("expected a gzip error, got {:?}", other),
+        }
+        let encoded =
+            &assert_round_trips::<CloudWatchLogsEvent>(include_str!("../tests/data/cloudwatch_logs_event.json"))
+                .awslogs
+                .data
+                .0;
+        let mut truncated = base64::decode(encoded).unwrap();
+        truncated.truncate(truncated.len() / 2);
+        match EncodedLogsData(base64::encode(&truncated)).decode() {
+            Err(LogsDataError::Gzip(_)) => (),
+            other => panic!("expected a gzip error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,459 @@
+//! Cognito User Pool trigger event types
+//!
+//! User pool triggers invoke functions with a [`CognitoEvent`](struct.CognitoEvent.html) whose
+//! `request` and `response` depend on the trigger. Functions answer by returning the event they
+//! received with its `response` filled in. Fields Cognito sends which aren't modeled here are
+//! kept in `extra` maps, so that echoing an event back doesn't drop them.
+//!
+//! ```rust
+//! use lambda_events::cognito::CognitoPreSignUpEvent;
+//!
+//! fn pre_sign_up(mut event: CognitoPreSignUpEvent) -> CognitoPreSignUpEvent {
+//!     event.response.auto_confirm_user = true;
+//!     event
+//! }
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// An event sent by a user pool trigger, generic over the trigger's request and response
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoEvent<R, S> {
+    /// The version of the trigger's event format
+    pub version: String,
+    /// The region of the user pool
+    pub region: String,
+    /// The id of the user pool
+    pub user_pool_id: String,
+    /// The name of the user the trigger fired for
+    pub user_name: String,
+    /// The client the request was made by
+    pub caller_context: CognitoCallerContext,
+    /// The operation which fired the trigger, like `PreSignUp_SignUp`
+    pub trigger_source: String,
+    /// The trigger specific request
+    pub request: R,
+    /// The trigger specific response, filled in by the function
+    pub response: S,
+    /// Fields of the event which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The client a user pool request was made by
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoCallerContext {
+    /// The version of the SDK the request was made with
+    pub aws_sdk_version: String,
+    /// The id of the user pool app client
+    pub client_id: String,
+    /// Fields of the context which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// A pre sign-up trigger event
+pub type CognitoPreSignUpEvent = CognitoEvent<CognitoPreSignUpRequest, CognitoPreSignUpResponse>;
+
+/// The request of a pre sign-up trigger
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoPreSignUpRequest {
+    /// The attributes the user signs up with
+    pub user_attributes: HashMap<String, String>,
+    /// Data the client passed along for validating the sign-up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation_data: Option<HashMap<String, String>>,
+    /// Data the client passed along to the trigger
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_metadata: Option<HashMap<String, String>>,
+    /// Fields of the request which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The response of a pre sign-up trigger
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoPreSignUpResponse {
+    /// Confirm the user without them having to confirm their sign-up
+    #[serde(default)]
+    pub auto_confirm_user: bool,
+    /// Mark the user's email address as verified
+    #[serde(default)]
+    pub auto_verify_email: bool,
+    /// Mark the user's phone number as verified
+    #[serde(default)]
+    pub auto_verify_phone: bool,
+    /// Fields of the response which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// A post confirmation trigger event
+pub type CognitoPostConfirmationEvent = CognitoEvent<CognitoPostConfirmationRequest, CognitoEmptyResponse>;
+
+/// The request of a post confirmation trigger
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoPostConfirmationRequest {
+    /// The attributes of the confirmed user
+    pub user_attributes: HashMap<String, String>,
+    /// Data the client passed along to the trigger
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_metadata: Option<HashMap<String, String>>,
+    /// Fields of the request which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The response of triggers which don't expect anything back
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct CognitoEmptyResponse {
+    /// Fields of the response which aren't modeled
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// A version 1 pre token generation trigger event
+pub type CognitoPreTokenGenerationEvent =
+    CognitoEvent<CognitoPreTokenGenerationRequest, CognitoPreTokenGenerationResponse>;
+
+/// The request of a pre token generation trigger
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoPreTokenGenerationRequest {
+    /// The attributes of the user tokens are generated for
+    pub user_attributes: HashMap<String, String>,
+    /// The groups and roles of the user
+    pub group_configuration: CognitoGroupConfiguration,
+    /// Data the client passed along to the trigger
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_metadata: Option<HashMap<String, String>>,
+    /// Fields of the request which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The groups and IAM roles of a user
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoGroupConfiguration {
+    /// The names of the user's groups
+    #[serde(default)]
+    pub groups_to_override: Vec<String>,
+    /// The ARNs of the IAM roles of the user's groups
+    #[serde(default)]
+    pub iam_roles_to_override: Vec<String>,
+    /// The ARN of the IAM role the user assumes by default
+    #[serde(default)]
+    pub preferred_role: Option<String>,
+    /// Fields of the configuration which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The response of a version 1 pre token generation trigger
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoPreTokenGenerationResponse {
+    /// Changes to the claims of the ID token, or `None` to leave them as they are
+    #[serde(default)]
+    pub claims_override_details: Option<CognitoClaimsOverrideDetails>,
+    /// Fields of the response which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// Changes to the claims of an ID token
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoClaimsOverrideDetails {
+    /// Claims to add to the token, or whose values to replace
+    #[serde(default)]
+    pub claims_to_add_or_override: HashMap<String, String>,
+    /// Names of claims to remove from the token
+    #[serde(default)]
+    pub claims_to_suppress: Vec<String>,
+    /// Replacements for the user's groups and roles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_override_details: Option<CognitoGroupConfiguration>,
+}
+
+/// A version 2 pre token generation trigger event, which can also change access tokens
+pub type CognitoPreTokenGenerationV2Event =
+    CognitoEvent<CognitoPreTokenGenerationV2Request, CognitoPreTokenGenerationV2Response>;
+
+/// The request of a version 2 pre token generation trigger
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoPreTokenGenerationV2Request {
+    /// The attributes of the user tokens are generated for
+    pub user_attributes: HashMap<String, String>,
+    /// The groups and roles of the user
+    pub group_configuration: CognitoGroupConfiguration,
+    /// The OAuth scopes the access token is generated with
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// Data the client passed along to the trigger
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_metadata: Option<HashMap<String, String>>,
+    /// Fields of the request which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The response of a version 2 pre token generation trigger
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoPreTokenGenerationV2Response {
+    /// Changes to the claims and scopes of the tokens, or `None` to leave them as they are
+    #[serde(default)]
+    pub claims_and_scope_override_details: Option<CognitoClaimsAndScopeOverrideDetails>,
+    /// Fields of the response which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// Changes to the claims and scopes of ID and access tokens
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoClaimsAndScopeOverrideDetails {
+    /// Changes to the ID token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_token_generation: Option<CognitoIdTokenGeneration>,
+    /// Changes to the access token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_token_generation: Option<CognitoAccessTokenGeneration>,
+    /// Replacements for the user's groups and roles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_override_details: Option<CognitoGroupConfiguration>,
+}
+
+/// Changes to the claims of an ID token
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoIdTokenGeneration {
+    /// Claims to add to the token, or whose values to replace, of any JSON type
+    #[serde(default)]
+    pub claims_to_add_or_override: HashMap<String, Value>,
+    /// Names of claims to remove from the token
+    #[serde(default)]
+    pub claims_to_suppress: Vec<String>,
+}
+
+/// Changes to the claims and scopes of an access token
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoAccessTokenGeneration {
+    /// Claims to add to the token, or whose values to replace, of any JSON type
+    #[serde(default)]
+    pub claims_to_add_or_override: HashMap<String, Value>,
+    /// Names of claims to remove from the token
+    #[serde(default)]
+    pub claims_to_suppress: Vec<String>,
+    /// Scopes to add to the token
+    #[serde(default)]
+    pub scopes_to_add: Vec<String>,
+    /// Scopes to remove from the token
+    #[serde(default)]
+    pub scopes_to_suppress: Vec<String>,
+}
+
+/// A custom message trigger event
+pub type CognitoCustomMessageEvent = CognitoEvent<CognitoCustomMessageRequest, CognitoCustomMessageResponse>;
+
+/// The request of a custom message trigger
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoCustomMessageRequest {
+    /// The attributes of the user the message is sent to
+    pub user_attributes: HashMap<String, String>,
+    /// The placeholder a custom message must include for the verification code, like `{####}`
+    #[serde(default)]
+    pub code_parameter: Option<String>,
+    /// The placeholder for the verification link, for link verification messages
+    #[serde(default)]
+    pub link_parameter: Option<String>,
+    /// The placeholder for the user name, for invitation messages
+    #[serde(default)]
+    pub username_parameter: Option<String>,
+    /// Data the client passed along to the trigger
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_metadata: Option<HashMap<String, String>>,
+    /// Fields of the request which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The response of a custom message trigger, where `None` keeps the default message
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoCustomMessageResponse {
+    /// The text message to send
+    #[serde(default)]
+    pub sms_message: Option<String>,
+    /// The email body to send
+    #[serde(default)]
+    pub email_message: Option<String>,
+    /// The email subject to send
+    #[serde(default)]
+    pub email_subject: Option<String>,
+    /// Fields of the response which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_json::json;
+
+    #[test]
+    fn pre_sign_up_events_echo_unknown_fields() {
+        let mut event: CognitoPreSignUpEvent =
+            assert_round_trips(include_str!("../tests/data/cognito_pre_sign_up_event.json"));
+        assert_eq!(event.trigger_source, "PreSignUp_SignUp");
+        assert_eq!(event.caller_context.client_id, "1example23456789");
+        assert_eq!(event.request.user_attributes["email"], "jane@example.com");
+        assert_eq!(
+            event
+                .request
+                .validation_data
+                .as_ref()
+                .map(|data| data["invite"].as_str()),
+            Some("abc123")
+        );
+        assert_eq!(event.request.extra["userNotFound"], json!(false));
+
+        event.response.auto_confirm_user = true;
+        let echoed = serde_json::to_value(&event).expect("failed to serialize event");
+        assert_eq!(echoed["request"]["userNotFound"], json!(false));
+        assert_eq!(
+            echoed["response"],
+            json!({ "autoConfirmUser": true, "autoVerifyEmail": false, "autoVerifyPhone": false })
+        );
+    }
+
+    #[test]
+    fn round_trips_unknown_fields_at_every_level() {
+        let mut fixture: Value =
+            serde_json::from_str(include_str!("../tests/data/cognito_pre_token_generation_event.json")).unwrap();
+        for pointer in &[
+            "",
+            "/callerContext",
+            "/request",
+            "/request/groupConfiguration",
+            "/response",
+        ] {
+            fixture
+                .pointer_mut(pointer)
+                .and_then(Value::as_object_mut)
+                .expect("missing object")
+                .insert("futureField".into(), json!({ "at": pointer }));
+        }
+        let event: CognitoPreTokenGenerationEvent =
+            serde_json::from_value(fixture.clone()).expect("failed to deserialize event");
+        assert_eq!(
+            event.request.group_configuration.extra["futureField"]["at"],
+            "/request/groupConfiguration"
+        );
+        assert_eq!(
+            serde_json::to_value(&event).expect("failed to serialize event"),
+            fixture
+        );
+    }
+
+    #[test]
+    fn deserializes_post_confirmation_events() {
+        let event: CognitoPostConfirmationEvent =
+            assert_round_trips(include_str!("../tests/data/cognito_post_confirmation_event.json"));
+        assert_eq!(event.request.user_attributes["cognito:user_status"], "CONFIRMED");
+        assert_eq!(
+            event
+                .request
+                .client_metadata
+                .as_ref()
+                .map(|metadata| metadata["source"].as_str()),
+            Some("web")
+        );
+        assert_eq!(event.response, CognitoEmptyResponse::default());
+    }
+
+    #[test]
+    fn pre_token_generation_responses_override_claims() {
+        let mut event: CognitoPreTokenGenerationEvent =
+            assert_round_trips(include_str!("../tests/data/cognito_pre_token_generation_event.json"));
+        assert_eq!(
+            event.request.group_configuration.groups_to_override,
+            vec!["admins".to_string()]
+        );
+        assert_eq!(event.response.claims_override_details, None);
+
+        let mut details = CognitoClaimsOverrideDetails::default();
+        details.claims_to_add_or_override.insert("tenant".into(), "acme".into());
+        details.claims_to_suppress.push("email".into());
+        event.response.claims_override_details = Some(details);
+        assert_eq!(
+            serde_json::to_value(&event.response).expect("failed to serialize response"),
+            json!({
+                "claimsOverrideDetails": {
+                    "claimsToAddOrOverride": { "tenant": "acme" },
+                    "claimsToSuppress": ["email"]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn pre_token_generation_v2_responses_override_scopes() {
+        let mut event: CognitoPreTokenGenerationV2Event =
+            assert_round_trips(include_str!("../tests/data/cognito_pre_token_generation_v2_event.json"));
+        assert_eq!(event.version, "2");
+        assert_eq!(event.request.scopes, vec!["openid".to_string(), "email".to_string()]);
+        assert_eq!(event.request.group_configuration.preferred_role, None);
+
+        let mut access = CognitoAccessTokenGeneration::default();
+        access
+            .claims_to_add_or_override
+            .insert("roles".into(), json!(["reader"]));
+        access.scopes_to_add.push("orders/read".into());
+        event.response.claims_and_scope_override_details = Some(CognitoClaimsAndScopeOverrideDetails {
+            access_token_generation: Some(access),
+            ..CognitoClaimsAndScopeOverrideDetails::default()
+        });
+        assert_eq!(
+            serde_json::to_value(&event.response).expect("failed to serialize response"),
+            json!({
+                "claimsAndScopeOverrideDetails": {
+                    "accessTokenGeneration": {
+                        "claimsToAddOrOverride": { "roles": ["reader"] },
+                        "claimsToSuppress": [],
+                        "scopesToAdd": ["orders/read"],
+                        "scopesToSuppress": []
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn custom_message_responses_replace_messages() {
+        let mut event: CognitoCustomMessageEvent =
+            assert_round_trips(include_str!("../tests/data/cognito_custom_message_event.json"));
+        assert_eq!(event.request.code_parameter.as_deref(), Some("{####}"));
+        assert_eq!(event.request.username_parameter, None);
+
+        event.response.email_subject = Some("Welcome".into());
+        event.response.email_message = Some("Your code is {####}".into());
+        assert_eq!(
+            serde_json::to_value(&event.response).expect("failed to serialize response"),
+            json!({ "smsMessage": null, "emailMessage": "Your code is {####}", "emailSubject": "Welcome" })
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! AWS Config custom rule event types
+//!
+//! Config invokes the function of a custom rule with a [`ConfigEvent`](struct.ConfigEvent.html)
+//! when a resource in the rule's scope changes or periodically. The event carries what triggered
+//! it and the rule's parameters as JSON encoded strings, which
+//! [`invoking_event`](struct.ConfigEvent.html#method.invoking_event) and
+//! [`rule_parameters`](struct.ConfigEvent.html#method.rule_parameters) decode.
+//!
+//! Functions report their verdicts themselves, by passing [`Evaluation`](struct.Evaluation.html)s
+//! and the event's `result_token` to the `PutEvaluations` API.
+
+use crate::custom_serde::{rfc3339, rfc3339_opt};
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, time::SystemTime};
+
+/// An invocation of a custom rule
+///
+/// ```rust
+/// use lambda_events::config::{ConfigEvent, ConfigurationItemChangeNotification};
+///
+/// fn resource_id(event: &ConfigEvent) -> Option<String> {
+///     let notification: ConfigurationItemChangeNotification = event.invoking_event().ok()?;
+///     notification.configuration_item.map(|item| item.resource_id)
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigEvent {
+    /// The version of the event format
+    pub version: String,
+    /// What triggered the invocation, JSON encoded
+    pub invoking_event: String,
+    /// The parameters of the rule, JSON encoded, when it has any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_parameters: Option<String>,
+    /// The token evaluations have to be reported with
+    pub result_token: String,
+    /// Whether the resource left the scope of the rule, in which case it should be evaluated
+    /// as `NOT_APPLICABLE`
+    pub event_left_scope: bool,
+    /// The ARN of the role Config assumes
+    pub execution_role_arn: String,
+    /// The ARN of the rule
+    pub config_rule_arn: String,
+    /// The name of the rule
+    pub config_rule_name: String,
+    /// The id of the rule
+    pub config_rule_id: String,
+    /// The account the rule belongs to
+    pub account_id: String,
+}
+
+impl ConfigEvent {
+    /// Deserialize what triggered the invocation from its JSON encoding
+    ///
+    /// Rules triggered by configuration changes decode it into a
+    /// [`ConfigurationItemChangeNotification`](struct.ConfigurationItemChangeNotification.html),
+    /// periodic rules into a [`ScheduledNotification`](struct.ScheduledNotification.html).
+    pub fn invoking_event<T>(&self) -> Result<T, serde_json::Error>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_str(&self.invoking_event)
+    }
+
+    /// Deserialize the parameters of the rule from their JSON encoding, returning `None` for
+    /// rules without parameters
+    pub fn rule_parameters<T>(&self) -> Result<Option<T>, serde_json::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.rule_parameters.as_deref().map(serde_json::from_str).transpose()
+    }
+}
+
+/// The invoking event of a rule triggered by a configuration change
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigurationItemChangeNotification<C = Value> {
+    /// How the configuration changed
+    #[serde(default)]
+    pub configuration_item_diff: Option<Value>,
+    /// The changed configuration of the resource
+    pub configuration_item: Option<ConfigurationItem<C>>,
+    /// When the notification was created
+    #[serde(with = "rfc3339")]
+    pub notification_creation_time: SystemTime,
+    /// Always `ConfigurationItemChangeNotification`
+    pub message_type: String,
+    /// The version of the notification format
+    pub record_version: String,
+}
+
+/// The configuration of a resource at some point in time, generic over the type of the
+/// resource type specific configuration
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigurationItem<C = Value> {
+    /// The CloudTrail event ids of the API calls which caused the change
+    #[serde(default)]
+    pub related_events: Vec<String>,
+    /// The resources related to the resource
+    #[serde(default)]
+    pub relationships: Vec<ConfigRelationship>,
+    /// The resource type specific configuration
+    pub configuration: C,
+    /// The version of the configuration item format
+    pub configuration_item_version: String,
+    /// When the configuration was recorded
+    #[serde(with = "rfc3339")]
+    pub configuration_item_capture_time: SystemTime,
+    /// Identifies the configuration item among the resource's, increasing over time
+    pub configuration_state_id: i64,
+    /// The account of the resource
+    pub aws_account_id: String,
+    /// Whether the resource exists, like `OK` or `ResourceDeleted`
+    pub configuration_item_status: String,
+    /// The type of the resource, like `AWS::EC2::Instance`
+    pub resource_type: String,
+    /// The id of the resource
+    pub resource_id: String,
+    /// The name of the resource, for resources which have one
+    pub resource_name: Option<String>,
+    /// The ARN of the resource
+    #[serde(rename = "ARN")]
+    pub arn: String,
+    /// The region of the resource
+    pub aws_region: String,
+    /// The availability zone of the resource
+    pub availability_zone: String,
+    /// A hash of the configuration
+    pub configuration_state_md5_hash: String,
+    /// When the resource was created
+    #[serde(with = "rfc3339_opt")]
+    pub resource_creation_time: Option<SystemTime>,
+    /// The tags of the resource
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+impl<C> ConfigurationItem<C> {
+    /// Build a verdict on the resource, ordered by when its configuration was recorded
+    pub fn evaluation(&self, compliance_type: ComplianceType) -> Evaluation {
+        Evaluation::new(
+            self.resource_type.clone(),
+            self.resource_id.clone(),
+            compliance_type,
+            self.configuration_item_capture_time,
+        )
+    }
+}
+
+/// A resource related to a resource
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigRelationship {
+    /// The id of the related resource
+    pub resource_id: String,
+    /// The name of the related resource, for resources which have one
+    pub resource_name: Option<String>,
+    /// The type of the related resource
+    pub resource_type: String,
+    /// How the resources are related, like `Is attached to Instance`
+    pub name: String,
+}
+
+/// The invoking event of a periodic rule
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledNotification {
+    /// The account the rule evaluates
+    pub aws_account_id: String,
+    /// When the notification was created
+    #[serde(with = "rfc3339")]
+    pub notification_creation_time: SystemTime,
+    /// Always `ScheduledNotification`
+    pub message_type: String,
+    /// The version of the notification format
+    pub record_version: String,
+}
+
+/// A verdict on a resource, reported with the `PutEvaluations` API
+///
+/// ```rust
+/// use lambda_events::config::{ComplianceType, Evaluation};
+/// use std::time::SystemTime;
+///
+/// let evaluation = Evaluation::new("AWS::::Account", "123456789012", ComplianceType::NonCompliant, SystemTime::now())
+///     .with_annotation("CloudTrail is disabled");
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Evaluation {
+    /// The type of the evaluated resource
+    pub compliance_resource_type: String,
+    /// The id of the evaluated resource
+    pub compliance_resource_id: String,
+    /// The verdict
+    pub compliance_type: ComplianceType,
+    /// Why the resource got its verdict
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<String>,
+    /// When the evaluated configuration was recorded, so that Config keeps the latest verdict
+    #[serde(with = "rfc3339")]
+    pub ordering_timestamp: SystemTime,
+}
+
+impl Evaluation {
+    /// Build a verdict on a resource
+    pub fn new(
+        compliance_resource_type: impl Into<String>,
+        compliance_resource_id: impl Into<String>,
+        compliance_type: ComplianceType,
+        ordering_timestamp: SystemTime,
+    ) -> Self {
+        Self {
+            compliance_resource_type: compliance_resource_type.into(),
+            compliance_resource_id: compliance_resource_id.into(),
+            compliance_type,
+            annotation: None,
+            ordering_timestamp,
+        }
+    }
+
+    /// Explain the verdict
+    pub fn with_annotation(mut self, annotation: impl Into<String>) -> Self {
+        self.annotation = Some(annotation.into());
+        self
+    }
+}
+
+/// A verdict on a resource
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ComplianceType {
+    /// The resource complies with the rule
+    Compliant,
+    /// The resource doesn't comply with the rule
+    NonCompliant,
+    /// The rule doesn't apply to the resource
+    NotApplicable,
+    /// The rule couldn't decide
+    InsufficientData,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_json::json;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct Parameters {
+        desired_instance_type: String,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct Instance {
+        instance_type: String,
+    }
+
+    #[test]
+    fn round_trips_events() {
+        for fixture in &[
+            include_str!("../tests/data/config_configuration_change_event.json"),
+            include_str!("../tests/data/config_scheduled_event.json"),
+        ] {
+            assert_round_trips::<ConfigEvent>(fixture);
+        }
+    }
+
+    #[test]
+    fn decodes_configuration_changes() {
+        let event =
+            assert_round_trips::<ConfigEvent>(include_str!("../tests/data/config_configuration_change_event.json"));
+        assert!(!event.event_left_scope);
+        assert_eq!(
+            event.rule_parameters::<Parameters>().unwrap(),
+            Some(Parameters {
+                desired_instance_type: "t2.micro".into()
+            })
+        );
+
+        let notification: ConfigurationItemChangeNotification<Instance> =
+            event.invoking_event().expect("failed to decode invoking event");
+        assert_eq!(notification.configuration_item_diff, None);
+        let item = notification.configuration_item.expect("missing configuration item");
+        assert_eq!(item.configuration.instance_type, "t2.micro");
+        assert_eq!(item.resource_type, "AWS::EC2::Instance");
+        assert_eq!(item.relationships[0].resource_name, None);
+        assert_eq!(
+            item.resource_creation_time,
+            Some(UNIX_EPOCH + Duration::from_secs(1_590_840_000))
+        );
+        assert_eq!(item.tags["team"], "orders");
+
+        let untyped: ConfigurationItemChangeNotification = event.invoking_event().unwrap();
+        let untyped = serde_json::to_value(untyped).expect("failed to serialize invoking event");
+        assert_eq!(untyped, serde_json::from_str::<Value>(&event.invoking_event).unwrap());
+    }
+
+    #[test]
+    fn decodes_scheduled_notifications() {
+        let event = assert_round_trips::<ConfigEvent>(include_str!("../tests/data/config_scheduled_event.json"));
+        assert_eq!(event.rule_parameters::<Parameters>().unwrap(), None);
+        let notification: ScheduledNotification = event.invoking_event().expect("failed to decode invoking event");
+        assert_eq!(notification.message_type, "ScheduledNotification");
+        assert_eq!(
+            notification.notification_creation_time,
+            UNIX_EPOCH + Duration::from_secs(1_590_994_800)
+        );
+    }
+
+    #[test]
+    fn deserializes_resources_without_creation_times() {
+        let event =
+            assert_round_trips::<ConfigEvent>(include_str!("../tests/data/config_configuration_change_event.json"));
+        let mut notification: Value = serde_json::from_str(&event.invoking_event).unwrap();
+        notification["configurationItem"]["resourceCreationTime"] = Value::Null;
+        let notification: ConfigurationItemChangeNotification = serde_json::from_value(notification).unwrap();
+        assert_eq!(notification.configuration_item.unwrap().resource_creation_time, None);
+    }
+
+    #[test]
+    fn serializes_evaluations() {
+        let event =
+            assert_round_trips::<ConfigEvent>(include_str!("../tests/data/config_configuration_change_event.json"));
+        let notification: ConfigurationItemChangeNotification = event.invoking_event().unwrap();
+        let evaluation = notification
+            .configuration_item
+            .expect("missing configuration item")
+            .evaluation(ComplianceType::NonCompliant)
+            .with_annotation("instance type is t2.micro");
+        assert_eq!(
+            serde_json::to_value(&evaluation).expect("failed to serialize evaluation"),
+            json!({
+                "ComplianceResourceType": "AWS::EC2::Instance",
+                "ComplianceResourceId": "i-0a1b2c3d4e5f67890",
+                "ComplianceType": "NON_COMPLIANT",
+                "Annotation": "instance type is t2.micro",
+                "OrderingTimestamp": "2020-06-01T06:00:00.123Z"
+            })
+        );
+    }
+}
@@ -0,0 +1,506 @@
+//! Deserialization helpers shared by event types
+
+use crate::time::{civil_from_days, days_from_civil};
+use serde::{
+    de::{Deserialize, Deserializer},
+    ser::Serializer,
+    Serialize,
+};
+use serde_derive::Deserialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// deserializes (json) null values to their default values
+// https://github.com/serde-rs/serde/issues/1098
+pub(crate) fn nullable_default<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    let opt = Option::deserialize(deserializer)?;
+    Ok(opt.unwrap_or_else(T::default))
+}
+
+/// IAM policy elements which may be written as a single string or a list of strings
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// deserializes either a single string or a list of strings into a list of strings
+pub(crate) fn deserialize_one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+/// serializes a list holding a single string as that string and any other list as a list
+pub(crate) fn serialize_one_or_many<S>(values: &[String], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match values {
+        [value] => value.serialize(serializer),
+        values => values.serialize(serializer),
+    }
+}
+
+/// Returns the time a signed number of seconds and nanoseconds away from the Unix epoch
+fn from_epoch(seconds: i64, nanos: u32) -> SystemTime {
+    if seconds >= 0 {
+        UNIX_EPOCH + Duration::new(seconds as u64, nanos)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs()) + Duration::from_nanos(u64::from(nanos))
+    }
+}
+
+/// Parses an RFC 3339 timestamp, like `2020-03-12T19:03:58.123Z` or `2020-03-12T20:03:58+01:00`
+///
+/// Offsets without a colon, like the `+0000` CloudWatch writes, are accepted as well.
+pub(crate) fn parse_rfc3339(timestamp: &str) -> Option<SystemTime> {
+    let bytes = timestamp.as_bytes();
+    if bytes.len() < 20
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = timestamp.get(range)?;
+        if digits.bytes().all(|b| b.is_ascii_digit()) {
+            digits.parse().ok()
+        } else {
+            None
+        }
+    };
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let mut rest = &timestamp[19..];
+    let mut nanos = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        // digits beyond nanosecond precision are truncated
+        let precise = &fraction[..digits.min(9)];
+        nanos = format!("{:0<9}", precise).parse().ok()?;
+        rest = &fraction[digits..];
+    }
+    let offset_seconds = match rest {
+        "Z" | "z" => 0,
+        offset if offset.len() == 6 && offset.as_bytes()[3] == b':' => {
+            let sign = match offset.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let hours: i64 = offset[1..3].parse().ok()?;
+            let minutes: i64 = offset[4..6].parse().ok()?;
+            sign * (hours * 3_600 + minutes * 60)
+        }
+        offset if offset.len() == 5 && offset.bytes().skip(1).all(|b| b.is_ascii_digit()) => {
+            let sign = match offset.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let hours: i64 = offset[1..3].parse().ok()?;
+            let minutes: i64 = offset[3..5].parse().ok()?;
+            sign * (hours * 3_600 + minutes * 60)
+        }
+        _ => return None,
+    };
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second - offset_seconds;
+    Some(from_epoch(seconds, nanos))
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp, with milliseconds only when the time has a fraction of a second
+pub(crate) fn format_rfc3339(time: SystemTime) -> String {
+    let (seconds, nanos) = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+        Err(before) => {
+            let before = before.duration();
+            let seconds = -(before.as_secs() as i64);
+            match before.subsec_nanos() {
+                0 => (seconds, 0),
+                nanos => (seconds - 1, 1_000_000_000 - nanos),
+            }
+        }
+    };
+    let (days, second_of_day) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    let (year, month, day) = civil_from_days(days);
+    let mut formatted = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        second_of_day / 3_600,
+        second_of_day % 3_600 / 60,
+        second_of_day % 60
+    );
+    if nanos > 0 {
+        formatted.push_str(&format!(".{:03}", nanos / 1_000_000));
+    }
+    formatted.push('Z');
+    formatted
+}
+
+/// Formats a time the way CloudWatch does, in UTC with milliseconds and a `+0000` offset,
+/// like `2020-03-12T19:03:58.123+0000`
+pub(crate) fn format_iso8601_millis(time: SystemTime) -> String {
+    let formatted = format_rfc3339(time);
+    let formatted = formatted.trim_end_matches('Z');
+    if formatted.contains('.') {
+        format!("{}+0000", formatted)
+    } else {
+        format!("{}.000+0000", formatted)
+    }
+}
+
+/// Parses an RFC 2822 email date, like `Mon, 5 Aug 2019 21:29:57 +0000`
+pub(crate) fn parse_rfc2822(date: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    // the day of the week is optional, and redundant when present
+    let date = match date.find(',') {
+        Some(comma) => &date[comma + 1..],
+        None => date,
+    };
+    let mut parts = date.split_whitespace();
+    let day: i64 = parts.next()?.parse().ok()?;
+    let month_name = parts.next()?;
+    let month = MONTHS.iter().position(|name| name.eq_ignore_ascii_case(month_name))? as i64 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':');
+    let hour: i64 = time.next()?.parse().ok()?;
+    let minute: i64 = time.next()?.parse().ok()?;
+    let second: i64 = time.next().map_or(Some(0), |second| second.parse().ok())?;
+    if !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 || time.next().is_some() {
+        return None;
+    }
+    let offset_seconds = match parts.next()? {
+        "GMT" | "UT" | "UTC" | "Z" => 0,
+        offset if offset.len() == 5 && offset[1..].bytes().all(|b| b.is_ascii_digit()) => {
+            let sign = match offset.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let hours: i64 = offset[1..3].parse().ok()?;
+            let minutes: i64 = offset[3..5].parse().ok()?;
+            sign * (hours * 3_600 + minutes * 60)
+        }
+        _ => return None,
+    };
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second - offset_seconds;
+    Some(from_epoch(seconds, 0))
+}
+
+/// (de)serializes RFC 3339 timestamps as `SystemTime`s
+pub(crate) mod rfc3339 {
+    use super::{format_rfc3339, parse_rfc3339};
+    use serde::{
+        de::{Deserialize, Deserializer, Error as DeError},
+        ser::Serializer,
+    };
+    use std::time::SystemTime;
+
+    pub(crate) fn serialize<S>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format_rfc3339(*time))
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let timestamp = String::deserialize(deserializer)?;
+        parse_rfc3339(&timestamp).ok_or_else(|| D::Error::custom(format!("invalid RFC 3339 timestamp {}", timestamp)))
+    }
+}
+
+/// (de)serializes optional RFC 3339 timestamps as `SystemTime`s, `null` being `None`
+pub(crate) mod rfc3339_opt {
+    use super::{format_rfc3339, parse_rfc3339};
+    use serde::{
+        de::{Deserialize, Deserializer, Error as DeError},
+        ser::Serializer,
+    };
+    use std::time::SystemTime;
+
+    pub(crate) fn serialize<S>(time: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match time {
+            Some(time) => serializer.serialize_str(&format_rfc3339(*time)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<SystemTime>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|timestamp| {
+                parse_rfc3339(&timestamp)
+                    .ok_or_else(|| D::Error::custom(format!("invalid RFC 3339 timestamp {}", timestamp)))
+            })
+            .transpose()
+    }
+}
+
+/// (de)serializes timestamps as `SystemTime`s, formatted the way CloudWatch does, like
+/// `2020-03-12T19:03:58.123+0000`
+pub(crate) mod iso8601_millis {
+    use super::{format_iso8601_millis, parse_rfc3339};
+    use serde::{
+        de::{Deserialize, Deserializer, Error as DeError},
+        ser::Serializer,
+    };
+    use std::time::SystemTime;
+
+    pub(crate) fn serialize<S>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format_iso8601_millis(*time))
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let timestamp = String::deserialize(deserializer)?;
+        parse_rfc3339(&timestamp).ok_or_else(|| D::Error::custom(format!("invalid timestamp {}", timestamp)))
+    }
+}
+
+/// (de)serializes numbers sent as JSON strings, like `"1523232000000"`
+pub(crate) mod string_number {
+    use serde::{
+        de::{Deserialize, Deserializer, Error as DeError},
+        ser::Serializer,
+    };
+    use std::{fmt::Display, str::FromStr};
+
+    pub(crate) fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Display,
+        S: Serializer,
+    {
+        serializer.collect_str(value)
+    }
+
+    pub(crate) fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        value
+            .parse()
+            .map_err(|e| D::Error::custom(format!("invalid number {}: {}", value, e)))
+    }
+}
+
+/// (de)serializes fractional seconds since the Unix epoch, like `1545084650.987`, as `SystemTime`s
+///
+/// Timestamps are kept to microsecond precision, which is finer than any event source reports
+/// them with, so that the rounding of the floating point representation does not leak into
+/// the `SystemTime`.
+pub(crate) mod epoch_seconds {
+    use serde::{
+        de::{Deserialize, Deserializer, Error as DeError},
+        ser::Serializer,
+    };
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    pub(crate) fn serialize<S>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        if since_epoch.subsec_nanos() == 0 {
+            serializer.serialize_u64(since_epoch.as_secs())
+        } else {
+            serializer.serialize_f64(since_epoch.as_secs_f64())
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let seconds = f64::deserialize(deserializer)?;
+        if !seconds.is_finite() || seconds < 0.0 {
+            return Err(D::Error::custom(format!("invalid epoch timestamp {}", seconds)));
+        }
+        Ok(UNIX_EPOCH + Duration::from_micros((seconds * 1e6).round() as u64))
+    }
+}
+
+/// (de)serializes base64 encoded strings as bytes
+pub(crate) mod base64_bytes {
+    use serde::{
+        de::{Deserialize, Deserializer, Error as DeError},
+        ser::Serializer,
+    };
+
+    pub(crate) fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&base64::encode(bytes))
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        base64::decode(&encoded).map_err(|e| D::Error::custom(format!("invalid base64 {}: {}", encoded, e)))
+    }
+}
+
+/// (de)serializes lists of base64 encoded strings as lists of bytes
+pub(crate) mod base64_bytes_list {
+    use serde::{
+        de::{Deserialize, Deserializer, Error as DeError},
+        ser::{SerializeSeq, Serializer},
+    };
+
+    pub(crate) fn serialize<S>(values: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(values.len()))?;
+        for value in values {
+            seq.serialize_element(&base64::encode(value))?;
+        }
+        seq.end()
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|encoded| {
+                base64::decode(encoded).map_err(|e| D::Error::custom(format!("invalid base64 {}: {}", encoded, e)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rfc3339_timestamps() {
+        let expected = UNIX_EPOCH + Duration::from_millis(1_584_039_838_123);
+        assert_eq!(parse_rfc3339("2020-03-12T19:03:58.123Z"), Some(expected));
+        assert_eq!(parse_rfc3339("2020-03-12T20:33:58.123+01:30"), Some(expected));
+        assert_eq!(parse_rfc3339("2020-03-12T19:03:58.123+0000"), Some(expected));
+        assert_eq!(parse_rfc3339("2020-03-12T17:33:58.123-0130"), Some(expected));
+        assert_eq!(
+            parse_rfc3339("2020-03-12T14:03:58.123456-05:00"),
+            Some(expected + Duration::from_micros(456))
+        );
+        assert_eq!(
+            parse_rfc3339("1969-12-31T23:59:59.5Z"),
+            Some(UNIX_EPOCH - Duration::from_millis(500))
+        );
+        for invalid in &[
+            "",
+            "2020-03-12",
+            "2020-03-12T19:03:58",
+            "2020-13-12T19:03:58Z",
+            "2020-03-12T19:03:58.Z",
+            "2020-03-12T19:03:58+00",
+            "2020-03-12T19:03:58+00:0a",
+        ] {
+            assert_eq!(parse_rfc3339(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn formats_rfc3339_timestamps() {
+        assert_eq!(format_rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_rfc3339(UNIX_EPOCH + Duration::from_millis(1_584_039_838_123)),
+            "2020-03-12T19:03:58.123Z"
+        );
+        assert_eq!(
+            format_rfc3339(UNIX_EPOCH - Duration::from_millis(500)),
+            "1969-12-31T23:59:59.500Z"
+        );
+    }
+
+    #[test]
+    fn formats_iso8601_millis_timestamps() {
+        assert_eq!(format_iso8601_millis(UNIX_EPOCH), "1970-01-01T00:00:00.000+0000");
+        assert_eq!(
+            format_iso8601_millis(UNIX_EPOCH + Duration::from_millis(1_584_039_838_123)),
+            "2020-03-12T19:03:58.123+0000"
+        );
+    }
+
+    #[test]
+    fn parses_rfc2822_dates() {
+        let expected = UNIX_EPOCH + Duration::from_secs(1_565_040_597);
+        assert_eq!(parse_rfc2822("Mon, 5 Aug 2019 21:29:57 +0000"), Some(expected));
+        assert_eq!(parse_rfc2822("5 Aug 2019 23:29:57 +0200"), Some(expected));
+        assert_eq!(parse_rfc2822("Mon, 05 Aug 2019 21:29:57 GMT"), Some(expected));
+        assert_eq!(
+            parse_rfc2822("Mon, 5 Aug 2019 21:29 +0000"),
+            Some(expected - Duration::from_secs(57))
+        );
+        for invalid in &[
+            "",
+            "Mon, 5 Foo 2019 21:29:57 +0000",
+            "5 Aug 2019 21:29:57",
+            "5 Aug 2019 25:29:57 +0000",
+        ] {
+            assert_eq!(parse_rfc2822(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[derive(serde_derive::Deserialize, serde_derive::Serialize, Debug, PartialEq)]
+    struct Arrival(#[serde(with = "epoch_seconds")] SystemTime);
+
+    #[test]
+    fn round_trips_epoch_seconds() {
+        for (json, expected) in &[
+            ("1545084650.987", UNIX_EPOCH + Duration::from_millis(1_545_084_650_987)),
+            ("1428537600", UNIX_EPOCH + Duration::from_secs(1_428_537_600)),
+        ] {
+            let arrival: Arrival = serde_json::from_str(json).expect("failed to deserialize timestamp");
+            assert_eq!(arrival.0, *expected);
+            assert_eq!(
+                serde_json::to_string(&arrival).expect("failed to serialize timestamp"),
+                *json
+            );
+        }
+        assert!(serde_json::from_str::<Arrival>("-1").is_err());
+    }
+}
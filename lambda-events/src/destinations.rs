@@ -0,0 +1,285 @@
+//! Lambda destinations invocation record types
+//!
+//! Functions invoked asynchronously with destinations configured send a
+//! [`DestinationEvent`](struct.DestinationEvent.html) describing each invocation to the
+//! destination of its outcome: as the event of a destination function, or as the body of the
+//! SQS, SNS or EventBridge message the destination queue, topic or bus receives.
+
+use crate::custom_serde::rfc3339;
+use serde::de::{Deserialize, DeserializeOwned, Deserializer, Error as _};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{error::Error, fmt, time::SystemTime};
+
+/// The record of an asynchronous invocation, generic over the invocation's event `Req` and
+/// the function's successful response `Resp`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DestinationEvent<Req = Value, Resp = Value> {
+    /// The version of the record format
+    pub version: String,
+    /// When the invocation completed
+    #[serde(with = "rfc3339")]
+    pub timestamp: SystemTime,
+    /// The invocation
+    pub request_context: DestinationRequestContext,
+    /// The event the function was invoked with
+    pub request_payload: Req,
+    /// The outcome of the last attempt, unless the event expired before being attempted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_context: Option<DestinationResponseContext>,
+    /// What the function returned, or the error it failed with
+    pub response_payload: Option<DestinationResponse<Resp>>,
+}
+
+impl<Req, Resp> DestinationEvent<Req, Resp> {
+    /// Return the error the function failed with, for failed invocations
+    pub fn error(&self) -> Option<&ErrorResponse> {
+        match &self.response_payload {
+            Some(DestinationResponse::Error(error)) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// The serialized form of `DestinationEvent`, whose response payload deserializes as an
+/// `ErrorResponse` or as `Resp` depending on its response context
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawDestinationEvent<Req> {
+    version: String,
+    #[serde(with = "rfc3339")]
+    timestamp: SystemTime,
+    request_context: DestinationRequestContext,
+    request_payload: Req,
+    response_context: Option<DestinationResponseContext>,
+    response_payload: Option<Value>,
+}
+
+impl<'de, Req, Resp> Deserialize<'de> for DestinationEvent<Req, Resp>
+where
+    Req: DeserializeOwned,
+    Resp: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawDestinationEvent::<Req>::deserialize(deserializer)?;
+        let failed = raw
+            .response_context
+            .as_ref()
+            .and_then(|context| context.function_error.as_ref())
+            .is_some();
+        let response_payload = match raw.response_payload {
+            Some(payload) if failed => Some(DestinationResponse::Error(
+                serde_json::from_value(payload).map_err(D::Error::custom)?,
+            )),
+            Some(payload) => Some(DestinationResponse::Success(
+                serde_json::from_value(payload).map_err(D::Error::custom)?,
+            )),
+            None => None,
+        };
+        Ok(DestinationEvent {
+            version: raw.version,
+            timestamp: raw.timestamp,
+            request_context: raw.request_context,
+            request_payload: raw.request_payload,
+            response_context: raw.response_context,
+            response_payload,
+        })
+    }
+}
+
+/// An asynchronous invocation
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DestinationRequestContext {
+    /// The id of the request which invoked the function
+    pub request_id: String,
+    /// The ARN of the function, including the version or alias invoked
+    pub function_arn: String,
+    /// Why the record was sent
+    pub condition: DestinationCondition,
+    /// How many times the function was invoked with the event
+    pub approximate_invoke_count: u32,
+}
+
+/// Why the record of an invocation was sent
+///
+/// Conditions Lambda may add in the future deserialize into `Other`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+pub enum DestinationCondition {
+    /// `Success`: the function succeeded
+    Success,
+    /// `RetriesExhausted`: the function failed on every attempt
+    RetriesExhausted,
+    /// `EventAgeExceeded`: the event expired before the function succeeded
+    EventAgeExceeded,
+    /// A condition this crate doesn't know about
+    Other(String),
+}
+
+impl DestinationCondition {
+    /// Return the condition as Lambda names it, like `RetriesExhausted`
+    pub fn as_str(&self) -> &str {
+        match self {
+            DestinationCondition::Success => "Success",
+            DestinationCondition::RetriesExhausted => "RetriesExhausted",
+            DestinationCondition::EventAgeExceeded => "EventAgeExceeded",
+            DestinationCondition::Other(condition) => condition,
+        }
+    }
+}
+
+impl From<String> for DestinationCondition {
+    fn from(condition: String) -> Self {
+        match condition.as_str() {
+            "Success" => DestinationCondition::Success,
+            "RetriesExhausted" => DestinationCondition::RetriesExhausted,
+            "EventAgeExceeded" => DestinationCondition::EventAgeExceeded,
+            _ => DestinationCondition::Other(condition),
+        }
+    }
+}
+
+impl From<DestinationCondition> for String {
+    fn from(condition: DestinationCondition) -> Self {
+        match condition {
+            DestinationCondition::Other(condition) => condition,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DestinationCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The outcome of the last attempt of an invocation
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DestinationResponseContext {
+    /// The status code of the invocation
+    pub status_code: u16,
+    /// The version of the function which ran
+    pub executed_version: String,
+    /// How the function failed, `Handled` or `Unhandled`, for failed invocations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_error: Option<String>,
+}
+
+/// What a function returned, or the error it failed with
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum DestinationResponse<Resp = Value> {
+    /// The response of a successful invocation
+    Success(Resp),
+    /// The error of a failed invocation
+    Error(ErrorResponse),
+}
+
+/// The error a function failed with, as runtimes report it
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorResponse {
+    /// The message of the error
+    pub error_message: String,
+    /// The type of the error, missing for errors of the platform like timeouts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_type: Option<String>,
+    /// Where the error was raised, for runtimes which report it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack_trace: Option<Vec<String>>,
+}
+
+impl fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error_type {
+            Some(error_type) => write!(f, "{}: {}", error_type, self.error_message),
+            None => f.write_str(&self.error_message),
+        }
+    }
+}
+
+impl Error for ErrorResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_json::json;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct Orders {
+        order_ids: Vec<String>,
+    }
+
+    #[test]
+    fn round_trips_events() {
+        for fixture in &[
+            include_str!("../tests/data/destination_on_failure_event.json"),
+            include_str!("../tests/data/destination_on_success_event.json"),
+        ] {
+            assert_round_trips::<DestinationEvent>(fixture);
+        }
+    }
+
+    #[test]
+    fn deserializes_failures() {
+        let event: DestinationEvent<Orders> =
+            serde_json::from_str(include_str!("../tests/data/destination_on_failure_event.json"))
+                .expect("failed to deserialize event");
+        assert_eq!(event.request_context.condition, DestinationCondition::RetriesExhausted);
+        assert_eq!(event.request_context.approximate_invoke_count, 3);
+        assert_eq!(event.request_payload.order_ids, vec!["order-41", "order-42"]);
+        assert_eq!(event.timestamp, UNIX_EPOCH + Duration::from_millis(1_591_003_800_568));
+        let error = event.error().expect("missing error");
+        assert_eq!(error.to_string(), "OrderNotFound: order order-42 not found");
+        assert_eq!(error.stack_trace.as_ref().map(Vec::len), Some(2));
+    }
+
+    #[test]
+    fn deserializes_successes() {
+        let event: DestinationEvent<Vec<Value>> =
+            serde_json::from_str(include_str!("../tests/data/destination_on_success_event.json"))
+                .expect("failed to deserialize event");
+        assert_eq!(event.request_context.condition, DestinationCondition::Success);
+        assert_eq!(event.request_payload[3], json!({ "priority": true }));
+        assert_eq!(event.error(), None);
+        assert_eq!(
+            event.response_payload,
+            Some(DestinationResponse::Success(json!({
+                "processed": 1,
+                "errorMessage": "not an error, the function succeeded"
+            })))
+        );
+    }
+
+    #[test]
+    fn deserializes_expired_events() {
+        let mut fixture: Value =
+            serde_json::from_str(include_str!("../tests/data/destination_on_failure_event.json")).unwrap();
+        fixture["requestContext"]["condition"] = json!("EventAgeExceeded");
+        fixture["requestContext"]["approximateInvokeCount"] = json!(0);
+        fixture["responsePayload"] = Value::Null;
+        fixture.as_object_mut().unwrap().remove("responseContext");
+        let event: DestinationEvent = serde_json::from_value(fixture.clone()).expect("failed to deserialize event");
+        assert_eq!(event.request_context.condition, DestinationCondition::EventAgeExceeded);
+        assert_eq!(event.response_context, None);
+        assert_eq!(event.error(), None);
+        assert_eq!(serde_json::to_value(event).unwrap(), fixture);
+
+        fixture["requestContext"]["condition"] = json!("SomethingNew");
+        let event: DestinationEvent = serde_json::from_value(fixture).expect("failed to deserialize event");
+        assert_eq!(
+            event.request_context.condition,
+            DestinationCondition::Other("SomethingNew".into())
+        );
+    }
+}
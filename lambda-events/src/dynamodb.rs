@@ -0,0 +1,343 @@
+//! DynamoDB Streams event types
+//!
+//! Stream event source mappings invoke functions with a [`DynamoDbEvent`](struct.DynamoDbEvent.html)
+//! holding a batch of item level changes. Item keys and images are maps of attribute names to
+//! [`AttributeValue`](enum.AttributeValue.html)s, in the same typed representation the DynamoDB
+//! API uses, and [`from_item`](fn.from_item.html) and [`to_item`](fn.to_item.html) convert
+//! them from and into `serde` data types.
+
+use crate::custom_serde::{base64_bytes, base64_bytes_list, epoch_seconds};
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, time::SystemTime};
+
+mod item;
+
+pub use self::item::{from_attribute_value, from_item, to_attribute_value, to_item, Error};
+
+/// The attributes of an item, keyed by attribute name
+pub type Item = HashMap<String, AttributeValue>;
+
+/// A batch of DynamoDB stream records
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct DynamoDbEvent {
+    /// The records of the batch
+    #[serde(rename = "Records")]
+    pub records: Vec<DynamoDbRecord>,
+}
+
+/// A single item level change
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DynamoDbRecord {
+    /// A unique identifier for the record
+    #[serde(rename = "eventID")]
+    pub event_id: String,
+    /// The kind of change
+    pub event_name: EventName,
+    /// The version of the record format
+    pub event_version: String,
+    /// Always `aws:dynamodb`
+    pub event_source: String,
+    /// The region of the table
+    pub aws_region: String,
+    /// The change itself
+    pub dynamodb: StreamRecord,
+    /// The ARN of the table's stream
+    #[serde(rename = "eventSourceARN")]
+    pub event_source_arn: String,
+    /// Who made the change, present for items deleted by time to live expiry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_identity: Option<DynamoDbUserIdentity>,
+}
+
+/// The kind of change a record describes
+///
+/// Names DynamoDB may add in the future deserialize into `Other`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+#[non_exhaustive]
+pub enum EventName {
+    /// `INSERT`: a new item was added to the table
+    Insert,
+    /// `MODIFY`: one or more of an existing item's attributes were changed
+    Modify,
+    /// `REMOVE`: an item was deleted from the table
+    Remove,
+    /// A kind of change this crate doesn't know about
+    Other(String),
+}
+
+impl EventName {
+    /// Return the name as DynamoDB records it, like `INSERT`
+    pub fn as_str(&self) -> &str {
+        match self {
+            EventName::Insert => "INSERT",
+            EventName::Modify => "MODIFY",
+            EventName::Remove => "REMOVE",
+            EventName::Other(name) => name,
+        }
+    }
+}
+
+impl From<String> for EventName {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "INSERT" => EventName::Insert,
+            "MODIFY" => EventName::Modify,
+            "REMOVE" => EventName::Remove,
+            _ => EventName::Other(name),
+        }
+    }
+}
+
+impl From<EventName> for String {
+    fn from(name: EventName) -> Self {
+        match name {
+            EventName::Other(name) => name,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for EventName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The item level change of a stream record
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct StreamRecord {
+    /// Approximately when the change was made
+    #[serde(with = "epoch_seconds")]
+    pub approximate_creation_date_time: SystemTime,
+    /// The primary key attributes of the changed item
+    pub keys: Item,
+    /// The item after the change, when the stream view type includes new images
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_image: Option<Item>,
+    /// The item before the change, when the stream view type includes old images
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_image: Option<Item>,
+    /// The position of the record within its shard
+    pub sequence_number: String,
+    /// The size of the record in bytes
+    pub size_bytes: u64,
+    /// Which images of the item the stream records
+    pub stream_view_type: StreamViewType,
+}
+
+/// Which images of a changed item a stream records
+///
+/// View types DynamoDB may add in the future deserialize into `Other`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+#[non_exhaustive]
+pub enum StreamViewType {
+    /// `KEYS_ONLY`: only the key attributes
+    KeysOnly,
+    /// `NEW_IMAGE`: the item after the change
+    NewImage,
+    /// `OLD_IMAGE`: the item before the change
+    OldImage,
+    /// `NEW_AND_OLD_IMAGES`: the item both before and after the change
+    NewAndOldImages,
+    /// A view type this crate doesn't know about
+    Other(String),
+}
+
+impl StreamViewType {
+    /// Return the view type as DynamoDB names it, like `NEW_AND_OLD_IMAGES`
+    pub fn as_str(&self) -> &str {
+        match self {
+            StreamViewType::KeysOnly => "KEYS_ONLY",
+            StreamViewType::NewImage => "NEW_IMAGE",
+            StreamViewType::OldImage => "OLD_IMAGE",
+            StreamViewType::NewAndOldImages => "NEW_AND_OLD_IMAGES",
+            StreamViewType::Other(view_type) => view_type,
+        }
+    }
+}
+
+impl From<String> for StreamViewType {
+    fn from(view_type: String) -> Self {
+        match view_type.as_str() {
+            "KEYS_ONLY" => StreamViewType::KeysOnly,
+            "NEW_IMAGE" => StreamViewType::NewImage,
+            "OLD_IMAGE" => StreamViewType::OldImage,
+            "NEW_AND_OLD_IMAGES" => StreamViewType::NewAndOldImages,
+            _ => StreamViewType::Other(view_type),
+        }
+    }
+}
+
+impl From<StreamViewType> for String {
+    fn from(view_type: StreamViewType) -> Self {
+        match view_type {
+            StreamViewType::Other(view_type) => view_type,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for StreamViewType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The principal which made a change
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DynamoDbUserIdentity {
+    /// The kind of principal, `Service` for time to live expiry
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The principal, `dynamodb.amazonaws.com` for time to live expiry
+    pub principal_id: String,
+}
+
+/// A typed DynamoDB attribute value
+///
+/// Serializes in the DynamoDB JSON representation, like `{"S":"hello"}` or `{"N":"42"}`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    /// A string
+    #[serde(rename = "S")]
+    S(String),
+    /// A number, kept as text because DynamoDB numbers have up to 38 digits of precision
+    #[serde(rename = "N")]
+    N(String),
+    /// Binary data, decoded from base64
+    #[serde(rename = "B", with = "base64_bytes")]
+    B(Vec<u8>),
+    /// A boolean
+    #[serde(rename = "BOOL")]
+    Bool(bool),
+    /// A null value, always `true`
+    #[serde(rename = "NULL")]
+    Null(bool),
+    /// A list of values of any type
+    #[serde(rename = "L")]
+    L(Vec<AttributeValue>),
+    /// A map of attribute names to values of any type
+    #[serde(rename = "M")]
+    M(HashMap<String, AttributeValue>),
+    /// A set of strings
+    #[serde(rename = "SS")]
+    Ss(Vec<String>),
+    /// A set of numbers, kept as text
+    #[serde(rename = "NS")]
+    Ns(Vec<String>),
+    /// A set of binary values, decoded from base64
+    #[serde(rename = "BS", with = "base64_bytes_list")]
+    Bs(Vec<Vec<u8>>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_json::Value;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn event() -> DynamoDbEvent {
+        assert_round_trips(include_str!("../tests/data/dynamodb_event.json"))
+    }
+
+    #[test]
+    fn deserializes_every_event_name() {
+        let event = event();
+        let names: Vec<_> = event.records.iter().map(|record| record.event_name.clone()).collect();
+        assert_eq!(names, vec![EventName::Insert, EventName::Modify, EventName::Remove]);
+
+        let insert = &event.records[0].dynamodb;
+        assert_eq!(
+            insert.approximate_creation_date_time,
+            UNIX_EPOCH + Duration::from_secs(1_428_537_600)
+        );
+        assert_eq!(insert.keys["Id"], AttributeValue::N("101".into()));
+        assert_eq!(insert.old_image, None);
+        assert_eq!(insert.sequence_number, "111");
+        assert_eq!(insert.size_bytes, 26);
+        assert_eq!(insert.stream_view_type, StreamViewType::NewAndOldImages);
+
+        let modify = &event.records[1].dynamodb;
+        assert!(modify.new_image.is_some() && modify.old_image.is_some());
+
+        let remove = &event.records[2];
+        assert_eq!(remove.dynamodb.new_image, None);
+        assert_eq!(
+            remove.dynamodb.approximate_creation_date_time,
+            UNIX_EPOCH + Duration::from_millis(1_428_537_720_500)
+        );
+        assert_eq!(
+            remove
+                .user_identity
+                .as_ref()
+                .map(|identity| identity.principal_id.as_str()),
+            Some("dynamodb.amazonaws.com")
+        );
+        assert_eq!(event.records[0].user_identity, None);
+    }
+
+    #[test]
+    fn deserializes_unknown_names_into_other() {
+        let mut fixture: Value = serde_json::from_str(include_str!("../tests/data/dynamodb_event.json")).unwrap();
+        fixture["Records"][0]["eventName"] = "UPSERT".into();
+        fixture["Records"][0]["dynamodb"]["StreamViewType"] = "DIFF_ONLY".into();
+        let event: DynamoDbEvent = serde_json::from_value(fixture.clone()).expect("failed to deserialize event");
+        let record = &event.records[0];
+        assert_eq!(record.event_name, EventName::Other("UPSERT".into()));
+        assert_eq!(
+            record.dynamodb.stream_view_type,
+            StreamViewType::Other("DIFF_ONLY".into())
+        );
+        assert_eq!(event.records[1].event_name.to_string(), "MODIFY");
+        assert_eq!(
+            serde_json::to_value(&event).expect("failed to serialize event"),
+            fixture
+        );
+    }
+
+    #[test]
+    fn deserializes_every_attribute_value_type() {
+        let event = event();
+        let image = event.records[0]
+            .dynamodb
+            .new_image
+            .as_ref()
+            .expect("expected a new image");
+        assert_eq!(image["Message"], AttributeValue::S("New item!".into()));
+        assert_eq!(image["Id"], AttributeValue::N("101".into()));
+        assert_eq!(image["Thumbnail"], AttributeValue::B(vec![0, 1, 2, 3, 4]));
+        assert_eq!(image["Published"], AttributeValue::Bool(true));
+        assert_eq!(image["Archived"], AttributeValue::Null(true));
+        assert_eq!(
+            image["History"],
+            AttributeValue::L(vec![
+                AttributeValue::S("created".into()),
+                AttributeValue::N("1.5".into())
+            ])
+        );
+        let mut author = HashMap::new();
+        author.insert("Name".to_string(), AttributeValue::S("Jane Doe".into()));
+        author.insert("Age".to_string(), AttributeValue::N("42".into()));
+        assert_eq!(image["Author"], AttributeValue::M(author));
+        assert_eq!(image["Tags"], AttributeValue::Ss(vec!["new".into(), "featured".into()]));
+        assert_eq!(image["Ratings"], AttributeValue::Ns(vec!["4".into(), "5".into()]));
+        assert_eq!(image["Attachments"], AttributeValue::Bs(vec![vec![1], vec![2, 3]]));
+    }
+
+    #[test]
+    fn round_trips_events() {
+        assert_round_trips::<DynamoDbEvent>(include_str!("../tests/data/dynamodb_event.json"));
+    }
+
+    #[test]
+    fn rejects_invalid_binary_values() {
+        assert!(serde_json::from_str::<AttributeValue>(r#"{"B":"not base64!"}"#).is_err());
+        assert!(serde_json::from_str::<AttributeValue>(r#"{"BS":["AQ==","not base64!"]}"#).is_err());
+    }
+}
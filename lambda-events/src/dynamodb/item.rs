@@ -0,0 +1,1078 @@
+//! Conversions between DynamoDB items and `serde` data types
+
+use super::{AttributeValue, Item};
+use serde::{
+    de::{
+        self,
+        value::{BorrowedStrDeserializer, SeqDeserializer},
+        DeserializeSeed, Visitor,
+    },
+    ser::{self, Serialize},
+};
+use std::{collections::HashMap, error::Error as StdError, fmt};
+
+/// Deserialize an instance of `T` from the attributes of an item
+///
+/// Numbers deserialize into any numeric type, or into strings when their full precision must
+/// be kept, binary values into `Vec<u8>` or `serde_bytes` buffers, lists and string, number and
+/// binary sets into any sequence, including `HashSet`s, and maps into nested structs and maps.
+/// `NULL` values deserialize as `None`.
+///
+/// ```rust
+/// use lambda_events::dynamodb::{from_item, DynamoDbRecord};
+/// use serde_derive::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// #[serde(rename_all = "PascalCase")]
+/// struct Order {
+///     id: u64,
+///     total: f64,
+/// }
+///
+/// fn new_order(record: &DynamoDbRecord) -> Option<Order> {
+///     record.dynamodb.new_image.as_ref().and_then(|image| from_item(image).ok())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error naming the path of the offending attribute, like `Lines[1].Quantity`, when
+/// an attribute doesn't match the type it is deserialized into.
+pub fn from_item<'a, T>(item: &'a Item) -> Result<T, Error>
+where
+    T: de::Deserialize<'a>,
+{
+    T::deserialize(Deserializer(View::M(item)))
+}
+
+/// Deserialize an instance of `T` from a single attribute value
+///
+/// See [`from_item`](fn.from_item.html) for how attribute values map onto `serde` data types.
+pub fn from_attribute_value<'a, T>(value: &'a AttributeValue) -> Result<T, Error>
+where
+    T: de::Deserialize<'a>,
+{
+    T::deserialize(Deserializer(View::of(value)))
+}
+
+/// Serialize `value` as the attributes of an item
+///
+/// Numbers serialize as `N` values, byte buffers serializing themselves as bytes, like
+/// `serde_bytes` buffers, as `B` values, sequences, including sets, as `L` values, structs and
+/// maps as `M` values and `None` as a `NULL` value. Plain `Vec<u8>`s serialize as lists of
+/// numbers, which deserialize back into `Vec<u8>`s.
+///
+/// # Errors
+///
+/// Returns an error when `value` doesn't serialize as a struct or map, or when a number can't be
+/// represented in DynamoDB, like `NaN`, naming the path of the offending field.
+pub fn to_item<T>(value: &T) -> Result<Item, Error>
+where
+    T: Serialize + ?Sized,
+{
+    match to_attribute_value(value)? {
+        AttributeValue::M(item) => Ok(item),
+        _ => Err(Error::new("items must serialize as a struct or map")),
+    }
+}
+
+/// Serialize `value` as a single attribute value
+///
+/// See [`to_item`](fn.to_item.html) for how `serde` data types map onto attribute values.
+pub fn to_attribute_value<T>(value: &T) -> Result<AttributeValue, Error>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(Serializer)
+}
+
+/// Returned when an item doesn't match the type it is converted from or into
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    message: String,
+    /// Path segments from the innermost attribute outwards
+    path: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl Error {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            path: Vec::new(),
+        }
+    }
+
+    fn within_key(mut self, key: &str) -> Self {
+        self.path.push(Segment::Key(key.to_string()));
+        self
+    }
+
+    fn within_index(mut self, index: usize) -> Self {
+        self.path.push(Segment::Index(index));
+        self
+    }
+
+    /// Return the path of the offending attribute, like `Lines[1].Quantity`, which is empty
+    /// when the error concerns the item as a whole
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in self.path.iter().rev() {
+            match segment {
+                Segment::Key(key) if path.is_empty() => path.push_str(key),
+                Segment::Key(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                Segment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{}: {}", self.path(), self.message)
+        }
+    }
+}
+
+impl StdError for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Self::new(message.to_string())
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Self::new(message.to_string())
+    }
+}
+
+/// A borrowed attribute value, or an element of a set
+#[derive(Clone, Copy)]
+enum View<'a> {
+    S(&'a str),
+    N(&'a str),
+    B(&'a [u8]),
+    Bool(bool),
+    Null,
+    L(&'a [AttributeValue]),
+    M(&'a HashMap<String, AttributeValue>),
+    Ss(&'a [String]),
+    Ns(&'a [String]),
+    Bs(&'a [Vec<u8>]),
+}
+
+impl<'a> View<'a> {
+    fn of(value: &'a AttributeValue) -> Self {
+        match value {
+            AttributeValue::S(value) => View::S(value),
+            AttributeValue::N(value) => View::N(value),
+            AttributeValue::B(value) => View::B(value),
+            AttributeValue::Bool(value) => View::Bool(*value),
+            AttributeValue::Null(_) => View::Null,
+            AttributeValue::L(values) => View::L(values),
+            AttributeValue::M(values) => View::M(values),
+            AttributeValue::Ss(values) => View::Ss(values),
+            AttributeValue::Ns(values) => View::Ns(values),
+            AttributeValue::Bs(values) => View::Bs(values),
+        }
+    }
+}
+
+struct Deserializer<'a>(View<'a>);
+
+/// Parse a number into the numeric type a visitor asked for
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident: $ty:ty,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+            where
+                V: Visitor<'a>,
+            {
+                match self.0 {
+                    View::N(number) => visitor.$visit(number.parse::<$ty>().map_err(|e| {
+                        Error::new(format!("invalid {} {}: {}", stringify!($ty), number, e))
+                    })?),
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'a> de::Deserializer<'a> for Deserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'a>,
+    {
+        match self.0 {
+            View::S(value) => visitor.visit_borrowed_str(value),
+            View::N(number) => {
+                if let Ok(value) = number.parse::<i64>() {
+                    visitor.visit_i64(value)
+                } else if let Ok(value) = number.parse::<u64>() {
+                    visitor.visit_u64(value)
+                } else {
+                    match number.parse::<f64>() {
+                        Ok(value) if value.is_finite() => visitor.visit_f64(value),
+                        _ => Err(Error::new(format!("invalid number {}", number))),
+                    }
+                }
+            }
+            View::B(value) => visitor.visit_borrowed_bytes(value),
+            View::Bool(value) => visitor.visit_bool(value),
+            View::Null => visitor.visit_unit(),
+            View::L(values) => visit_seq(values.iter().map(View::of), visitor),
+            View::M(values) => visitor.visit_map(MapAccess {
+                entries: values.iter(),
+                value: None,
+            }),
+            View::Ss(values) => visit_seq(values.iter().map(|value| View::S(value)), visitor),
+            View::Ns(values) => visit_seq(values.iter().map(|value| View::N(value)), visitor),
+            View::Bs(values) => visit_seq(values.iter().map(|value| View::B(value)), visitor),
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_i128 => visit_i128: i128,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_u128 => visit_u128: u128,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64,
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'a>,
+    {
+        match self.0 {
+            View::S(value) | View::N(value) => visitor.visit_borrowed_str(value),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'a>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'a>,
+    {
+        match self.0 {
+            View::B(bytes) => {
+                let mut seq = SeqDeserializer::new(bytes.iter().copied());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'a>,
+    {
+        match self.0 {
+            View::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'a>,
+    {
+        match self.0 {
+            View::S(variant) => visitor.visit_enum(BorrowedStrDeserializer::<Error>::new(variant)),
+            View::M(values) if values.len() == 1 => {
+                let (variant, value) = values.iter().next().expect("map has one entry");
+                visitor
+                    .visit_enum(EnumAccess { variant, value })
+                    .map_err(|e| e.within_key(variant))
+            }
+            _ => Err(Error::new("enums must be a string or a map with a single entry")),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        <W: Visitor<'a>>
+        bool char bytes byte_buf unit unit_struct tuple tuple_struct map struct identifier
+    }
+}
+
+fn visit_seq<'a, I, V>(values: I, visitor: V) -> Result<V::Value, Error>
+where
+    I: Iterator<Item = View<'a>>,
+    V: Visitor<'a>,
+{
+    let mut seq = SeqAccess {
+        values: values.enumerate(),
+    };
+    let value = visitor.visit_seq(&mut seq)?;
+    match seq.values.next() {
+        Some((index, _)) => Err(Error::new(format!("unexpected extra element at index {}", index))),
+        None => Ok(value),
+    }
+}
+
+struct SeqAccess<I> {
+    values: std::iter::Enumerate<I>,
+}
+
+impl<'a, I> de::SeqAccess<'a> for SeqAccess<I>
+where
+    I: Iterator<Item = View<'a>>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: DeserializeSeed<'a>,
+    {
+        match self.values.next() {
+            Some((index, value)) => seed
+                .deserialize(Deserializer(value))
+                .map(Some)
+                .map_err(|e| e.within_index(index)),
+            None => Ok(None),
+        }
+    }
+}
+
+struct MapAccess<'a> {
+    entries: std::collections::hash_map::Iter<'a, String, AttributeValue>,
+    value: Option<(&'a str, &'a AttributeValue)>,
+}
+
+impl<'a> de::MapAccess<'a> for MapAccess<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: DeserializeSeed<'a>,
+    {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some((key, value));
+                seed.deserialize(BorrowedStrDeserializer::<Error>::new(key))
+                    .map(Some)
+                    .map_err(|e| e.within_key(key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'a>,
+    {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or_else(|| Error::new("map value requested before its key"))?;
+        seed.deserialize(Deserializer(View::of(value)))
+            .map_err(|e| e.within_key(key))
+    }
+}
+
+struct EnumAccess<'a> {
+    variant: &'a str,
+    value: &'a AttributeValue,
+}
+
+impl<'a> de::EnumAccess<'a> for EnumAccess<'a> {
+    type Error = Error;
+    type Variant = Deserializer<'a>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Error>
+    where
+        V: DeserializeSeed<'a>,
+    {
+        let variant = seed.deserialize(BorrowedStrDeserializer::<Error>::new(self.variant))?;
+        Ok((variant, Deserializer(View::of(self.value))))
+    }
+}
+
+impl<'a> de::VariantAccess<'a> for Deserializer<'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Error>
+    where
+        T: DeserializeSeed<'a>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'a>,
+    {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'a>,
+    {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+struct Serializer;
+
+fn number<T>(value: T) -> Result<AttributeValue, Error>
+where
+    T: fmt::Display,
+{
+    Ok(AttributeValue::N(value.to_string()))
+}
+
+fn float(value: f64) -> Result<AttributeValue, Error> {
+    if value.is_finite() {
+        number(value)
+    } else {
+        Err(Error::new(format!(
+            "{} can't be represented as a DynamoDB number",
+            value
+        )))
+    }
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = AttributeValue;
+    type Error = Error;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeVariant<SerializeList>;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeVariant<SerializeMap>;
+
+    fn serialize_bool(self, value: bool) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::Bool(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<AttributeValue, Error> {
+        number(value)
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<AttributeValue, Error> {
+        number(value)
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<AttributeValue, Error> {
+        number(value)
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<AttributeValue, Error> {
+        number(value)
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<AttributeValue, Error> {
+        number(value)
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<AttributeValue, Error> {
+        number(value)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<AttributeValue, Error> {
+        number(value)
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<AttributeValue, Error> {
+        number(value)
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<AttributeValue, Error> {
+        number(value)
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<AttributeValue, Error> {
+        number(value)
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<AttributeValue, Error> {
+        if value.is_finite() {
+            number(value)
+        } else {
+            float(f64::from(value))
+        }
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<AttributeValue, Error> {
+        float(value)
+    }
+
+    fn serialize_char(self, value: char) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::S(value.to_string()))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::S(value.to_string()))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::B(value.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::Null(true))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<AttributeValue, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::Null(true))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<AttributeValue, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<AttributeValue, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<AttributeValue, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<AttributeValue, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = value.serialize(Serializer).map_err(|e| e.within_key(variant))?;
+        let mut map = HashMap::new();
+        map.insert(variant.to_string(), value);
+        Ok(AttributeValue::M(map))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList, Error> {
+        Ok(SerializeList(Vec::with_capacity(len.unwrap_or_default())))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeList, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeList>, Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap, Error> {
+        Ok(SerializeMap {
+            map: HashMap::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeMap>, Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SerializeList(Vec<AttributeValue>);
+
+impl SerializeList {
+    fn push<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let index = self.0.len();
+        self.0
+            .push(value.serialize(Serializer).map_err(|e| e.within_index(index))?);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::L(self.0))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::L(self.0))
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::L(self.0))
+    }
+}
+
+struct SerializeMap {
+    map: HashMap<String, AttributeValue>,
+    key: Option<String>,
+}
+
+impl SerializeMap {
+    fn insert<T>(&mut self, key: String, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = value.serialize(Serializer).map_err(|e| e.within_key(&key))?;
+        self.map.insert(key, value);
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.key = Some(match key.serialize(Serializer)? {
+            AttributeValue::S(key) | AttributeValue::N(key) => key,
+            _ => return Err(Error::new("map keys must be strings or numbers")),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::new("map value serialized before its key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::M(self.map))
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::M(self.map))
+    }
+}
+
+/// A tuple or struct variant, serialized as a map from the variant's name to its fields
+struct SerializeVariant<T> {
+    variant: &'static str,
+    inner: T,
+}
+
+impl<T> SerializeVariant<T> {
+    fn wrap(variant: &'static str, value: AttributeValue) -> AttributeValue {
+        let mut map = HashMap::new();
+        map.insert(variant.to_string(), value);
+        AttributeValue::M(map)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeList> {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let variant = self.variant;
+        self.inner.push(value).map_err(|e| e.within_key(variant))
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(Self::wrap(self.variant, AttributeValue::L(self.inner.0)))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeMap> {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let variant = self.variant;
+        self.inner
+            .insert(key.to_string(), value)
+            .map_err(|e| e.within_key(variant))
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(Self::wrap(self.variant, AttributeValue::M(self.inner.map)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_derive::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashSet};
+
+    /// (de)serializes bytes the way `serde_bytes` does
+    mod bytes {
+        use serde::{de::Deserializer, ser::Serializer};
+
+        pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(bytes)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+            struct BytesVisitor;
+
+            impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+                type Value = Vec<u8>;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    f.write_str("bytes")
+                }
+
+                fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+                    Ok(bytes.to_vec())
+                }
+            }
+
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct Order {
+        id: u64,
+        customer: Customer,
+        lines: Vec<Line>,
+        tags: HashSet<String>,
+        note: Option<String>,
+        #[serde(with = "bytes")]
+        receipt: Vec<u8>,
+        totals: BTreeMap<String, f64>,
+        status: Status,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct Customer {
+        name: String,
+        address: Option<Address>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct Address {
+        city: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct Line {
+        sku: String,
+        quantity: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Status {
+        Pending,
+        Shipped { carrier: String },
+    }
+
+    fn order() -> Order {
+        let mut totals = BTreeMap::new();
+        totals.insert("net".to_string(), 10.5);
+        totals.insert("gross".to_string(), 12.6);
+        Order {
+            id: 7,
+            customer: Customer {
+                name: "Jane".into(),
+                address: Some(Address { city: "Berlin".into() }),
+            },
+            lines: vec![
+                Line {
+                    sku: "a".into(),
+                    quantity: 1,
+                },
+                Line {
+                    sku: "b".into(),
+                    quantity: 2,
+                },
+            ],
+            tags: vec!["new".to_string()].into_iter().collect(),
+            note: None,
+            receipt: vec![0, 159, 146, 150],
+            totals,
+            status: Status::Shipped { carrier: "dhl".into() },
+        }
+    }
+
+    fn map(entries: Vec<(&str, AttributeValue)>) -> AttributeValue {
+        AttributeValue::M(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn round_trips_structs() {
+        let order = order();
+        let item = to_item(&order).expect("failed to serialize order");
+        assert_eq!(item["Id"], AttributeValue::N("7".into()));
+        assert_eq!(item["Note"], AttributeValue::Null(true));
+        assert_eq!(item["Receipt"], AttributeValue::B(vec![0, 159, 146, 150]));
+        assert_eq!(
+            item["Customer"],
+            map(vec![
+                ("Name", AttributeValue::S("Jane".into())),
+                ("Address", map(vec![("City", AttributeValue::S("Berlin".into()))])),
+            ])
+        );
+        assert_eq!(
+            item["Status"],
+            map(vec![(
+                "Shipped",
+                map(vec![("carrier", AttributeValue::S("dhl".into()))])
+            )])
+        );
+        assert_eq!(from_item::<Order>(&item).expect("failed to deserialize order"), order);
+    }
+
+    #[test]
+    fn deserializes_sets_numbers_and_binary_values() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Stats<'a> {
+            tags: HashSet<String>,
+            ratings: Vec<u8>,
+            blobs: Vec<Vec<u8>>,
+            blob: Vec<u8>,
+            precise: &'a str,
+            ratio: f32,
+            offset: i16,
+            status: Status,
+        }
+
+        let item: Item = vec![
+            ("tags", AttributeValue::Ss(vec!["a".into(), "b".into(), "a".into()])),
+            ("ratings", AttributeValue::Ns(vec!["4".into(), "5".into()])),
+            ("blobs", AttributeValue::Bs(vec![vec![1], vec![2, 3]])),
+            ("blob", AttributeValue::B(vec![9, 8])),
+            ("precise", AttributeValue::N("12345678901234567890.123456789".into())),
+            ("ratio", AttributeValue::N("0.25".into())),
+            ("offset", AttributeValue::N("-3".into())),
+            ("status", AttributeValue::S("Pending".into())),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+        let stats: Stats = from_item(&item).expect("failed to deserialize item");
+        assert_eq!(
+            stats,
+            Stats {
+                tags: vec!["a".to_string(), "b".to_string()].into_iter().collect(),
+                ratings: vec![4, 5],
+                blobs: vec![vec![1], vec![2, 3]],
+                blob: vec![9, 8],
+                precise: "12345678901234567890.123456789",
+                ratio: 0.25,
+                offset: -3,
+                status: Status::Pending,
+            }
+        );
+    }
+
+    #[test]
+    fn deserializes_into_json_values() {
+        let value = map(vec![
+            ("count", AttributeValue::N("3".into())),
+            ("price", AttributeValue::N("1.5".into())),
+            ("gone", AttributeValue::Null(true)),
+        ]);
+        assert_eq!(
+            from_attribute_value::<serde_json::Value>(&value).expect("failed to deserialize value"),
+            serde_json::json!({ "count": 3, "price": 1.5, "gone": null })
+        );
+    }
+
+    #[test]
+    fn errors_name_the_offending_attribute() {
+        let mut item = to_item(&order()).expect("failed to serialize order");
+        if let Some(AttributeValue::L(lines)) = item.get_mut("Lines") {
+            if let AttributeValue::M(line) = &mut lines[1] {
+                line.insert("Quantity".into(), AttributeValue::N("two".into()));
+            }
+        }
+        let error = from_item::<Order>(&item).unwrap_err();
+        assert_eq!(error.path(), "Lines[1].Quantity");
+        assert_eq!(
+            error.to_string(),
+            "Lines[1].Quantity: invalid u32 two: invalid digit found in string"
+        );
+
+        item.insert("Id".into(), AttributeValue::S("7".into()));
+        item.remove("Lines");
+        let error = from_item::<Order>(&item).unwrap_err();
+        assert!(
+            error.to_string().starts_with("Id: invalid type: string \"7\""),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_unrepresentable_values() {
+        #[derive(Serialize)]
+        struct Reading {
+            values: Vec<f64>,
+        }
+
+        let error = to_item(&Reading {
+            values: vec![1.0, f64::NAN],
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "values[1]: NaN can't be represented as a DynamoDB number"
+        );
+        assert!(to_item(&vec![1, 2]).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! Events of functions invoked by several triggers
+//!
+//! Functions wired to, say, an SQS queue and direct invocations can take a
+//! [`LambdaEvent`](enum.LambdaEvent.html), which detects the source of each event from a few
+//! discriminating fields and deserializes it into the matching typed event.
+
+use crate::{
+    alb::AlbTargetGroupRequest,
+    apigw::{ApiGatewayProxyRequest, ApiGatewayV2Request},
+    cloudwatch_logs::CloudWatchLogsEvent,
+    dynamodb::DynamoDbEvent,
+    eventbridge::EventBridgeEvent,
+    kinesis::KinesisEvent,
+    s3::S3Event,
+    sns::SnsEvent,
+    sqs::SqsEvent,
+};
+use serde::de::{Deserialize, Deserializer, Error as _};
+use serde_derive::Serialize;
+use serde_json::Value;
+
+/// An event of any of the sources this crate can detect
+///
+/// Events are told apart by the `eventSource` of their first record, their `requestContext`,
+/// their `detail-type` or their `awslogs` field. Events recognized as coming from a source but
+/// not matching its type fail to deserialize, and any other payload deserializes into `Custom`.
+/// Detection parses the event once, into a `serde_json::Value` the detected type is then
+/// deserialized from, which adds a few microseconds to typical events.
+///
+/// ```rust
+/// use lambda_events::event::LambdaEvent;
+/// use serde_json::json;
+///
+/// fn describe(event: LambdaEvent) -> String {
+///     match event {
+///         LambdaEvent::Sqs(event) => format!("{} messages", event.records.len()),
+///         LambdaEvent::Custom(payload) => format!("invoked with {}", payload),
+///         _ => "something else".to_string(),
+///     }
+/// }
+///
+/// let event = serde_json::from_value(json!({ "orderId": "42" })).unwrap();
+/// assert_eq!(describe(event), r#"invoked with {"orderId":"42"}"#);
+/// ```
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+#[non_exhaustive]
+// events are deserialized once per invocation and usually matched right away
+#[allow(clippy::large_enum_variant)]
+pub enum LambdaEvent {
+    /// S3 event notifications
+    S3(S3Event),
+    /// A batch of SQS messages
+    Sqs(SqsEvent),
+    /// SNS notifications
+    Sns(SnsEvent),
+    /// A batch of DynamoDB stream records
+    DynamoDb(DynamoDbEvent),
+    /// A batch of Kinesis stream records
+    Kinesis(KinesisEvent),
+    /// An event delivered by an EventBridge rule
+    EventBridge(EventBridgeEvent),
+    /// A request to a REST API
+    ApiGateway(ApiGatewayProxyRequest),
+    /// A request to an HTTP API or function URL
+    ApiGatewayV2(ApiGatewayV2Request),
+    /// A request forwarded by an Application Load Balancer
+    Alb(AlbTargetGroupRequest),
+    /// Log events delivered by a CloudWatch Logs subscription filter
+    CloudWatchLogs(CloudWatchLogsEvent),
+    /// Any other payload, like those of direct invocations
+    Custom(Value),
+}
+
+impl<'de> Deserialize<'de> for LambdaEvent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // the document is parsed once, and the detected type deserialized from the parsed value
+        let value = Value::deserialize(deserializer)?;
+        match Source::detect(&value) {
+            Source::S3 => S3Event::deserialize(value).map(LambdaEvent::S3),
+            Source::Sqs => SqsEvent::deserialize(value).map(LambdaEvent::Sqs),
+            Source::Sns => SnsEvent::deserialize(value).map(LambdaEvent::Sns),
+            Source::DynamoDb => DynamoDbEvent::deserialize(value).map(LambdaEvent::DynamoDb),
+            Source::Kinesis => KinesisEvent::deserialize(value).map(LambdaEvent::Kinesis),
+            Source::EventBridge => EventBridgeEvent::deserialize(value).map(LambdaEvent::EventBridge),
+            Source::ApiGateway => ApiGatewayProxyRequest::deserialize(value).map(LambdaEvent::ApiGateway),
+            Source::ApiGatewayV2 => ApiGatewayV2Request::deserialize(value).map(LambdaEvent::ApiGatewayV2),
+            Source::Alb => AlbTargetGroupRequest::deserialize(value).map(LambdaEvent::Alb),
+            Source::CloudWatchLogs => CloudWatchLogsEvent::deserialize(value).map(LambdaEvent::CloudWatchLogs),
+            Source::Unknown => Ok(LambdaEvent::Custom(value)),
+        }
+        .map_err(D::Error::custom)
+    }
+}
+
+/// The source of an event, as told by its discriminating fields
+enum Source {
+    S3,
+    Sqs,
+    Sns,
+    DynamoDb,
+    Kinesis,
+    EventBridge,
+    ApiGateway,
+    ApiGatewayV2,
+    Alb,
+    CloudWatchLogs,
+    Unknown,
+}
+
+impl Source {
+    fn detect(value: &Value) -> Self {
+        let object = match value.as_object() {
+            Some(object) => object,
+            None => return Source::Unknown,
+        };
+        if let Some(records) = object.get("Records").and_then(Value::as_array) {
+            // SNS spells the field in PascalCase
+            let source = records.first().and_then(|record| {
+                record
+                    .get("eventSource")
+                    .or_else(|| record.get("EventSource"))
+                    .and_then(Value::as_str)
+            });
+            return match source {
+                Some("aws:s3") => Source::S3,
+                Some("aws:sqs") => Source::Sqs,
+                Some("aws:sns") => Source::Sns,
+                Some("aws:dynamodb") => Source::DynamoDb,
+                Some("aws:kinesis") => Source::Kinesis,
+                _ => Source::Unknown,
+            };
+        }
+        if let Some(context) = object.get("requestContext") {
+            return if context.get("elb").is_some() {
+                Source::Alb
+            } else if object.get("version").and_then(Value::as_str) == Some("2.0") {
+                Source::ApiGatewayV2
+            } else if object.contains_key("httpMethod") {
+                Source::ApiGateway
+            } else {
+                Source::Unknown
+            };
+        }
+        if object.contains_key("detail-type") && object.contains_key("source") {
+            return Source::EventBridge;
+        }
+        if object.contains_key("awslogs") {
+            return Source::CloudWatchLogs;
+        }
+        Source::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn detect(fixture: &str) -> LambdaEvent {
+        serde_json::from_str(fixture).expect("failed to deserialize event")
+    }
+
+    #[test]
+    fn detects_every_supported_source() {
+        for fixture in &[
+            include_str!("../tests/data/s3_object_created_put.json"),
+            include_str!("../tests/data/s3_object_removed_delete.json"),
+        ] {
+            assert!(matches!(detect(fixture), LambdaEvent::S3(_)));
+        }
+        for fixture in &[
+            include_str!("../tests/data/sqs_event.json"),
+            include_str!("../tests/data/sqs_fifo_event.json"),
+        ] {
+            assert!(matches!(detect(fixture), LambdaEvent::Sqs(_)));
+        }
+        assert!(matches!(
+            detect(include_str!("../tests/data/sns_event.json")),
+            LambdaEvent::Sns(_)
+        ));
+        assert!(matches!(
+            detect(include_str!("../tests/data/dynamodb_event.json")),
+            LambdaEvent::DynamoDb(_)
+        ));
+        assert!(matches!(
+            detect(include_str!("../tests/data/kinesis_event.json")),
+            LambdaEvent::Kinesis(_)
+        ));
+        for fixture in &[
+            include_str!("../tests/data/eventbridge_scheduled_event.json"),
+            include_str!("../tests/data/eventbridge_custom_event.json"),
+            include_str!("../tests/data/eventbridge_ecs_task_state_change_event.json"),
+        ] {
+            assert!(matches!(detect(fixture), LambdaEvent::EventBridge(_)));
+        }
+        assert!(matches!(
+            detect(include_str!("../tests/data/apigw_proxy_request.json")),
+            LambdaEvent::ApiGateway(_)
+        ));
+        assert!(matches!(
+            detect(include_str!("../tests/data/apigw_v2_proxy_request.json")),
+            LambdaEvent::ApiGatewayV2(_)
+        ));
+        assert!(matches!(
+            detect(include_str!("../tests/data/alb_request.json")),
+            LambdaEvent::Alb(_)
+        ));
+        assert!(matches!(
+            detect(include_str!("../tests/data/cloudwatch_logs_event.json")),
+            LambdaEvent::CloudWatchLogs(_)
+        ));
+    }
+
+    #[test]
+    fn keeps_unknown_payloads() {
+        let payload = json!({ "Records": [{ "eventSource": "aws:kafka" }], "orderId": "42" });
+        assert_eq!(
+            serde_json::from_value::<LambdaEvent>(payload.clone()).expect("failed to deserialize event"),
+            LambdaEvent::Custom(payload)
+        );
+        assert_eq!(
+            serde_json::from_str::<LambdaEvent>("[1, 2]").expect("failed to deserialize event"),
+            LambdaEvent::Custom(json!([1, 2]))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_events_of_detected_sources() {
+        let error = serde_json::from_value::<LambdaEvent>(json!({ "Records": [{ "eventSource": "aws:sqs" }] }))
+            .expect_err("deserialized a malformed SQS event");
+        assert!(error.to_string().contains("missing field"), "{}", error);
+    }
+
+    #[test]
+    fn serializes_as_the_detected_event() {
+        let fixture = include_str!("../tests/data/eventbridge_custom_event.json");
+        assert_eq!(
+            serde_json::to_value(detect(fixture)).expect("failed to serialize event"),
+            serde_json::from_str::<Value>(fixture).unwrap()
+        );
+    }
+}
@@ -0,0 +1,786 @@
+//! EventBridge and CloudWatch Events event types
+//!
+//! Rules deliver matching events, and scheduled rules their ticks, in an
+//! [`EventBridgeEvent`](struct.EventBridgeEvent.html) envelope whose `detail` varies by producer.
+//! The envelope is generic over the type of the detail, which defaults to a
+//! `serde_json::Value`.
+
+use crate::custom_serde::{iso8601_millis, rfc3339};
+use serde::de::{Deserialize, DeserializeOwned, Deserializer, Error as _};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    time::SystemTime,
+};
+
+/// An event delivered by an EventBridge or CloudWatch Events rule
+///
+/// ```rust
+/// use lambda_events::eventbridge::EventBridgeEvent;
+/// use serde_derive::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// #[serde(rename_all = "camelCase")]
+/// struct OrderPlaced {
+///     order_id: String,
+/// }
+///
+/// fn order_id(event: EventBridgeEvent<OrderPlaced>) -> String {
+///     event.detail.order_id
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct EventBridgeEvent<T = serde_json::Value> {
+    /// The version of the envelope format, always `0`
+    pub version: String,
+    /// A unique identifier for the event
+    pub id: String,
+    /// Identifies the kind of event along with `source`, like `Scheduled Event`
+    pub detail_type: String,
+    /// The producer of the event, like `aws.events` for scheduled rules
+    pub source: String,
+    /// The account the event was produced in
+    pub account: String,
+    /// When the event was produced
+    #[serde(with = "rfc3339")]
+    pub time: SystemTime,
+    /// The region the event was produced in
+    pub region: String,
+    /// The ARNs of the resources involved in the event, like the rule for scheduled events
+    pub resources: Vec<String>,
+    /// The producer specific payload of the event
+    pub detail: T,
+}
+
+/// The empty detail of a scheduled rule's event
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct ScheduledEventDetail {}
+
+/// The detail type of the events of scheduled rules
+pub const SCHEDULED_EVENT_DETAIL_TYPE: &str = "Scheduled Event";
+
+/// An event delivered by a cron or rate scheduled rule
+///
+/// The event derefs to its [`EventBridgeEvent`](struct.EventBridgeEvent.html) envelope.
+/// Deserializing fails for events whose detail type isn't `Scheduled Event`, so functions
+/// targeted by the wrong rule fail loudly rather than treating any event as a tick.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ScheduledEvent(EventBridgeEvent<ScheduledEventDetail>);
+
+impl ScheduledEvent {
+    /// Create an event of the rule `rule_arn` firing at `time`, taking the account and region
+    /// from the ARN
+    pub fn new<A>(rule_arn: A, time: SystemTime) -> Self
+    where
+        A: Into<String>,
+    {
+        let rule_arn = rule_arn.into();
+        let mut parts = rule_arn.split(':').skip(3);
+        let region = parts.next().unwrap_or_default().to_string();
+        let account = parts.next().unwrap_or_default().to_string();
+        ScheduledEvent(EventBridgeEvent {
+            version: "0".to_string(),
+            id: "00000000-0000-0000-0000-000000000000".to_string(),
+            detail_type: SCHEDULED_EVENT_DETAIL_TYPE.to_string(),
+            source: "aws.events".to_string(),
+            account,
+            time,
+            region,
+            resources: vec![rule_arn],
+            detail: ScheduledEventDetail {},
+        })
+    }
+
+    /// Return the ARN of the rule which fired
+    pub fn rule_arn(&self) -> Option<&str> {
+        self.0.resources.first().map(String::as_str)
+    }
+
+    /// Return the envelope of the event
+    pub fn into_inner(self) -> EventBridgeEvent<ScheduledEventDetail> {
+        self.0
+    }
+}
+
+impl Deref for ScheduledEvent {
+    type Target = EventBridgeEvent<ScheduledEventDetail>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ScheduledEvent {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for ScheduledEvent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let event = EventBridgeEvent::<ScheduledEventDetail>::deserialize(deserializer)?;
+        if event.detail_type != SCHEDULED_EVENT_DETAIL_TYPE {
+            return Err(D::Error::custom(format!(
+                "expected an event of detail-type \"{}\", got \"{}\"",
+                SCHEDULED_EVENT_DETAIL_TYPE, event.detail_type
+            )));
+        }
+        Ok(ScheduledEvent(event))
+    }
+}
+
+/// An invocation by an EventBridge Scheduler schedule, generic over the payload `T` the
+/// schedule was configured with
+///
+/// Schedules targeting a function directly invoke it with their payload as is. Schedules
+/// delivering through an event bus wrap it in an envelope with `source` `aws.scheduler`, whose
+/// detail is the payload: for those the schedule's ARN, the id of the execution and its
+/// scheduled time are captured as well.
+///
+/// ```rust
+/// use lambda_events::eventbridge::SchedulerEvent;
+/// use serde_json::json;
+///
+/// let event: SchedulerEvent = serde_json::from_value(json!({ "report": "daily" })).unwrap();
+/// assert_eq!(event.payload["report"], "daily");
+/// assert_eq!(event.schedule_arn, None);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchedulerEvent<T = serde_json::Value> {
+    /// The ARN of the schedule, for enveloped payloads
+    pub schedule_arn: Option<String>,
+    /// The id of the execution of the schedule, for enveloped payloads
+    pub execution_id: Option<String>,
+    /// When the execution was scheduled, for enveloped payloads
+    pub scheduled_time: Option<SystemTime>,
+    /// The payload the schedule was configured with
+    pub payload: T,
+}
+
+impl<T> SchedulerEvent<T> {
+    /// Create an invocation with `payload`, without execution context
+    pub fn new(payload: T) -> Self {
+        Self {
+            schedule_arn: None,
+            execution_id: None,
+            scheduled_time: None,
+            payload,
+        }
+    }
+
+    /// Set the ARN of the schedule and the id of its execution
+    pub fn with_execution<A, I>(self, schedule_arn: A, execution_id: I) -> Self
+    where
+        A: Into<String>,
+        I: Into<String>,
+    {
+        Self {
+            schedule_arn: Some(schedule_arn.into()),
+            execution_id: Some(execution_id.into()),
+            ..self
+        }
+    }
+
+    /// Set when the execution was scheduled
+    pub fn with_scheduled_time(self, scheduled_time: SystemTime) -> Self {
+        Self {
+            scheduled_time: Some(scheduled_time),
+            ..self
+        }
+    }
+}
+
+impl<'de, T> Deserialize<'de> for SchedulerEvent<T>
+where
+    T: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        if value.get("source").and_then(serde_json::Value::as_str) == Some("aws.scheduler") {
+            let envelope: EventBridgeEvent<T> = serde_json::from_value(value).map_err(D::Error::custom)?;
+            return Ok(Self {
+                schedule_arn: envelope.resources.into_iter().next(),
+                execution_id: Some(envelope.id),
+                scheduled_time: Some(envelope.time),
+                payload: envelope.detail,
+            });
+        }
+        serde_json::from_value(value).map(Self::new).map_err(D::Error::custom)
+    }
+}
+
+/// The detail of an `ECS Task State Change` event
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EcsTaskStateChange {
+    /// The ARN of the cluster running the task
+    pub cluster_arn: String,
+    /// The ARN of the task
+    pub task_arn: String,
+    /// The ARN of the task's definition
+    pub task_definition_arn: String,
+    /// The status the task last reported, like `RUNNING` or `STOPPED`
+    pub last_status: String,
+    /// The status ECS is moving the task to
+    pub desired_status: String,
+    /// How the task was launched, like `FARGATE` or `EC2`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub launch_type: Option<String>,
+    /// The task group, like `service:my-service` or `family:my-task`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Why the task stopped, for stopped tasks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stopped_reason: Option<String>,
+    /// The task's containers
+    #[serde(default)]
+    pub containers: Vec<EcsContainerStateChange>,
+}
+
+/// The state of a container of an `ECS Task State Change` event
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EcsContainerStateChange {
+    /// The ARN of the container
+    pub container_arn: String,
+    /// The name of the container
+    pub name: String,
+    /// The ARN of the task the container belongs to
+    pub task_arn: String,
+    /// The status the container last reported
+    pub last_status: String,
+    /// The container's exit code, once it has exited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Why the container stopped, when ECS stopped it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// An `ECS Task State Change` event
+pub type EcsTaskStateChangeEvent = EventBridgeEvent<EcsTaskStateChange>;
+
+/// The detail of an `EC2 Instance-launch Lifecycle Action` or `EC2 Instance-terminate Lifecycle Action` event
+///
+/// Auto Scaling sends a test notification when a lifecycle hook is created, which has no
+/// instance to act on.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum AutoScalingLifecycleHookDetail {
+    /// An instance waits for the hook
+    Action(AutoScalingLifecycleAction),
+    /// The hook was created
+    TestNotification(AutoScalingTestNotification),
+}
+
+/// An instance of an Auto Scaling group waiting for a lifecycle hook
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct AutoScalingLifecycleAction {
+    /// The token to complete the lifecycle action with
+    pub lifecycle_action_token: String,
+    /// The name of the Auto Scaling group
+    pub auto_scaling_group_name: String,
+    /// The name of the lifecycle hook
+    pub lifecycle_hook_name: String,
+    /// The id of the instance
+    #[serde(rename = "EC2InstanceId")]
+    pub ec2_instance_id: String,
+    /// Whether the instance launches or terminates
+    pub lifecycle_transition: LifecycleTransition,
+    /// The metadata the hook was created with. See [`metadata_json`](#method.metadata_json)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notification_metadata: Option<String>,
+    /// Where the instance comes from, like `EC2` or `AutoScalingGroup`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Where the instance goes, like `AutoScalingGroup` or `EC2`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+}
+
+impl AutoScalingLifecycleAction {
+    /// Deserialize the metadata the hook was created with from JSON
+    pub fn metadata_json<T>(&self) -> Result<Option<T>, serde_json::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.notification_metadata
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+    }
+}
+
+/// The transition an instance of an Auto Scaling group waits in
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LifecycleTransition {
+    /// `autoscaling:EC2_INSTANCE_LAUNCHING`: the instance launches
+    #[serde(rename = "autoscaling:EC2_INSTANCE_LAUNCHING")]
+    Launching,
+    /// `autoscaling:EC2_INSTANCE_TERMINATING`: the instance terminates
+    #[serde(rename = "autoscaling:EC2_INSTANCE_TERMINATING")]
+    Terminating,
+}
+
+/// The notification Auto Scaling sends when a lifecycle hook is created
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct AutoScalingTestNotification {
+    /// The account of the Auto Scaling group
+    pub account_id: String,
+    /// A unique identifier for the notification
+    pub request_id: String,
+    /// The ARN of the Auto Scaling group
+    #[serde(rename = "AutoScalingGroupARN")]
+    pub auto_scaling_group_arn: String,
+    /// The name of the Auto Scaling group
+    pub auto_scaling_group_name: String,
+    /// The sender, `AWS Auto Scaling`
+    pub service: String,
+    /// The kind of notification, `autoscaling:TEST_NOTIFICATION`
+    pub event: String,
+    /// When the notification was sent
+    #[serde(with = "rfc3339")]
+    pub time: SystemTime,
+}
+
+/// An Auto Scaling lifecycle hook event
+pub type AutoScalingLifecycleHookEvent = EventBridgeEvent<AutoScalingLifecycleHookDetail>;
+
+/// The detail of a `CloudWatch Alarm State Change` event
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudWatchAlarmStateChange {
+    /// The name of the alarm
+    pub alarm_name: String,
+    /// What the alarm watches
+    pub configuration: CloudWatchAlarmConfiguration,
+    /// The state the alarm left
+    pub previous_state: CloudWatchAlarmState,
+    /// The state the alarm entered
+    pub state: CloudWatchAlarmState,
+}
+
+/// The state of an alarm
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudWatchAlarmState {
+    /// The state
+    pub value: CloudWatchAlarmStateValue,
+    /// Why the alarm entered the state, for people
+    pub reason: String,
+    /// Why the alarm entered the state, as JSON. See [`reason_data_json`](#method.reason_data_json)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason_data: Option<String>,
+    /// When the alarm entered the state
+    #[serde(with = "iso8601_millis")]
+    pub timestamp: SystemTime,
+    /// What suppresses the actions of a composite alarm, like `WaitPeriod` or `Alarm`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actions_suppressed_by: Option<String>,
+    /// Why the actions of a composite alarm are suppressed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actions_suppressed_reason: Option<String>,
+}
+
+impl CloudWatchAlarmState {
+    /// Deserialize why the alarm entered the state from JSON
+    ///
+    /// Metric alarms report the datapoints they evaluated, composite alarms the alarms which
+    /// triggered them.
+    pub fn reason_data_json<T>(&self) -> Result<Option<T>, serde_json::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.reason_data.as_deref().map(serde_json::from_str).transpose()
+    }
+}
+
+/// The state of an alarm
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CloudWatchAlarmStateValue {
+    /// The watched value is within its threshold
+    Ok,
+    /// The watched value is beyond its threshold
+    Alarm,
+    /// There isn't enough data to tell
+    InsufficientData,
+}
+
+/// What an alarm watches
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum CloudWatchAlarmConfiguration {
+    /// The states of other alarms, for composite alarms
+    Composite(CloudWatchCompositeAlarmConfiguration),
+    /// Metrics, for metric alarms
+    Metric(CloudWatchMetricAlarmConfiguration),
+}
+
+/// What a metric alarm watches
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudWatchMetricAlarmConfiguration {
+    /// The description of the alarm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The metrics and the expressions combining them
+    pub metrics: Vec<CloudWatchMetricDataQuery>,
+}
+
+/// A metric or an expression watched by a metric alarm
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudWatchMetricDataQuery {
+    /// The id of the metric or expression, for expressions to refer to
+    pub id: String,
+    /// The metric, for metrics
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric_stat: Option<CloudWatchMetricStat>,
+    /// The math expression, for expressions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expression: Option<String>,
+    /// The label of the metric or expression
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Whether the alarm watches the metric or expression itself, rather than only using it in expressions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub return_data: Option<bool>,
+}
+
+/// A statistic of a metric over a period
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudWatchMetricStat {
+    /// The metric
+    pub metric: CloudWatchMetric,
+    /// The period in seconds
+    pub period: u32,
+    /// The statistic, like `Average` or `p99`
+    pub stat: String,
+    /// The unit of the metric
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+}
+
+/// A CloudWatch metric
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CloudWatchMetric {
+    /// The dimensions of the metric
+    #[serde(default)]
+    pub dimensions: HashMap<String, String>,
+    /// The name of the metric
+    pub name: String,
+    /// The namespace of the metric, like `AWS/EC2`
+    pub namespace: String,
+}
+
+/// What a composite alarm watches
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudWatchCompositeAlarmConfiguration {
+    /// The rule combining the states of other alarms, like `ALARM(CpuTooHigh) OR ALARM(DiskFull)`
+    pub alarm_rule: String,
+    /// The alarm suppressing the alarm's actions while in `ALARM`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actions_suppressor: Option<String>,
+    /// How long to wait for the suppressor alarm to enter `ALARM`, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actions_suppressor_wait_period: Option<u32>,
+    /// How long to keep suppressing actions after the suppressor alarm leaves `ALARM`, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actions_suppressor_extension_period: Option<u32>,
+    /// The description of the alarm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// A `CloudWatch Alarm State Change` event
+pub type CloudWatchAlarmStateChangeEvent = EventBridgeEvent<CloudWatchAlarmStateChange>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct OrderPlaced {
+        order_id: String,
+        items: Vec<Item>,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+    struct Item {
+        sku: String,
+        quantity: u32,
+    }
+
+    #[test]
+    fn deserializes_scheduled_events() {
+        let fixture = include_str!("../tests/data/eventbridge_scheduled_event.json");
+        let event: EventBridgeEvent = serde_json::from_str(fixture).expect("failed to deserialize event");
+        assert_eq!(event.detail_type, "Scheduled Event");
+        assert_eq!(event.source, "aws.events");
+        assert_eq!(event.time, UNIX_EPOCH + Duration::from_secs(1_444_323_186));
+        assert_eq!(
+            event.resources,
+            vec!["arn:aws:events:us-east-1:123456789012:rule/my-scheduled-rule".to_string()]
+        );
+        assert_eq!(event.detail, json!({}));
+
+        let scheduled: ScheduledEvent = serde_json::from_str(fixture).expect("failed to deserialize event");
+        assert_eq!(scheduled.detail, ScheduledEventDetail {});
+        assert_eq!(
+            serde_json::to_value(&scheduled).expect("failed to serialize event"),
+            serde_json::from_str::<Value>(fixture).unwrap()
+        );
+    }
+
+    #[test]
+    fn constructs_scheduled_events() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_444_323_186);
+        let event = ScheduledEvent::new("arn:aws:events:us-east-1:123456789012:rule/my-scheduled-rule", time);
+        assert_eq!(
+            event.rule_arn(),
+            Some("arn:aws:events:us-east-1:123456789012:rule/my-scheduled-rule")
+        );
+        assert_eq!(event.account, "123456789012");
+        assert_eq!(event.region, "us-east-1");
+        let round_tripped: ScheduledEvent =
+            serde_json::from_value(serde_json::to_value(&event).unwrap()).expect("failed to deserialize event");
+        assert_eq!(round_tripped, event);
+    }
+
+    #[test]
+    fn rejects_scheduled_events_of_other_detail_types() {
+        let error = serde_json::from_str::<ScheduledEvent>(include_str!("../tests/data/eventbridge_custom_event.json"))
+            .expect_err("deserialized an order as a scheduled event");
+        assert!(error
+            .to_string()
+            .contains("expected an event of detail-type \"Scheduled Event\", got \"Order Placed\""));
+    }
+
+    #[test]
+    fn deserializes_scheduler_events() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Report {
+            report: String,
+            recipients: Vec<String>,
+        }
+
+        let event: SchedulerEvent<Report> =
+            serde_json::from_str(include_str!("../tests/data/eventbridge_scheduler_event.json"))
+                .expect("failed to deserialize event");
+        assert_eq!(
+            event,
+            SchedulerEvent::new(Report {
+                report: "sales".into(),
+                recipients: vec!["finance@example.com".into()],
+            })
+            .with_execution(
+                "arn:aws:scheduler:us-east-1:123456789012:schedule/default/nightly-report",
+                "a5b9c1e2-7d3f-4c8a-9e6b-2f1d0c4b8a7e"
+            )
+            .with_scheduled_time(UNIX_EPOCH + Duration::from_secs(1_668_060_000))
+        );
+
+        let event: SchedulerEvent<Report> = serde_json::from_value(json!({ "report": "stock", "recipients": [] }))
+            .expect("failed to deserialize event");
+        assert_eq!(event.schedule_arn, None);
+        assert_eq!(event.execution_id, None);
+        assert_eq!(event.payload.report, "stock");
+    }
+
+    #[test]
+    fn deserializes_custom_details() {
+        let event: EventBridgeEvent<OrderPlaced> =
+            serde_json::from_str(include_str!("../tests/data/eventbridge_custom_event.json"))
+                .expect("failed to deserialize event");
+        assert_eq!(event.detail_type, "Order Placed");
+        assert!(event.resources.is_empty());
+        assert_eq!(
+            event.detail,
+            OrderPlaced {
+                order_id: "1".into(),
+                items: vec![Item {
+                    sku: "a".into(),
+                    quantity: 2
+                }]
+            }
+        );
+    }
+
+    #[test]
+    fn deserializes_ecs_task_state_changes() {
+        let event: EcsTaskStateChangeEvent = serde_json::from_str(include_str!(
+            "../tests/data/eventbridge_ecs_task_state_change_event.json"
+        ))
+        .expect("failed to deserialize event");
+        assert_eq!(event.detail.last_status, "STOPPED");
+        assert_eq!(event.detail.launch_type.as_deref(), Some("FARGATE"));
+        assert_eq!(event.detail.containers[0].exit_code, Some(0));
+        assert_eq!(event.detail.containers[0].reason, None);
+    }
+
+    #[test]
+    fn deserializes_autoscaling_lifecycle_actions() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(rename_all = "camelCase")]
+        struct Metadata {
+            queue: String,
+            drain_seconds: u32,
+        }
+
+        let fixture = include_str!("../tests/data/eventbridge_autoscaling_terminate_lifecycle_action_event.json");
+        let event: AutoScalingLifecycleHookEvent = serde_json::from_str(fixture).expect("failed to deserialize event");
+        match &event.detail {
+            AutoScalingLifecycleHookDetail::Action(action) => {
+                assert_eq!(action.lifecycle_transition, LifecycleTransition::Terminating);
+                assert_eq!(action.ec2_instance_id, "i-1234567890abcdef0");
+                assert_eq!(
+                    action.metadata_json::<Metadata>().expect("failed to parse metadata"),
+                    Some(Metadata {
+                        queue: "orders".into(),
+                        drain_seconds: 120
+                    })
+                );
+            }
+            other => panic!("unexpected detail {:?}", other),
+        }
+        assert_eq!(
+            serde_json::to_value(&event).expect("failed to serialize event"),
+            serde_json::from_str::<Value>(fixture).unwrap()
+        );
+    }
+
+    #[test]
+    fn deserializes_autoscaling_test_notifications() {
+        let fixture = include_str!("../tests/data/eventbridge_autoscaling_test_notification_event.json");
+        let event: AutoScalingLifecycleHookEvent = serde_json::from_str(fixture).expect("failed to deserialize event");
+        match &event.detail {
+            AutoScalingLifecycleHookDetail::TestNotification(notification) => {
+                assert_eq!(notification.event, "autoscaling:TEST_NOTIFICATION");
+                assert_eq!(notification.auto_scaling_group_name, "orders-workers");
+                assert_eq!(notification.time, UNIX_EPOCH + Duration::from_millis(1_591_001_999_289));
+            }
+            other => panic!("unexpected detail {:?}", other),
+        }
+        assert_eq!(
+            serde_json::to_value(&event).expect("failed to serialize event"),
+            serde_json::from_str::<Value>(fixture).unwrap()
+        );
+    }
+
+    #[test]
+    fn deserializes_metric_alarm_state_changes() {
+        #[derive(Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ReasonData {
+            statistic: String,
+            recent_datapoints: Vec<f64>,
+            threshold: f64,
+        }
+
+        let fixture = include_str!("../tests/data/eventbridge_cloudwatch_alarm_state_change_event.json");
+        let event: CloudWatchAlarmStateChangeEvent =
+            serde_json::from_str(fixture).expect("failed to deserialize event");
+        let detail = &event.detail;
+        assert_eq!(detail.previous_state.value, CloudWatchAlarmStateValue::Ok);
+        assert_eq!(detail.state.value, CloudWatchAlarmStateValue::Alarm);
+        assert_eq!(
+            detail.state.timestamp,
+            UNIX_EPOCH + Duration::from_millis(1_591_003_800_989)
+        );
+        let reason = detail
+            .state
+            .reason_data_json::<ReasonData>()
+            .expect("failed to parse reason data")
+            .expect("missing reason data");
+        assert_eq!(reason.statistic, "Average");
+        assert!(reason.recent_datapoints[0] > reason.threshold);
+        match &detail.configuration {
+            CloudWatchAlarmConfiguration::Metric(configuration) => {
+                let stat = configuration.metrics[0].metric_stat.as_ref().expect("missing metric");
+                assert_eq!(stat.metric.name, "CPUUtilization");
+                assert_eq!(stat.metric.dimensions["InstanceId"], "i-1234567890abcdef0");
+            }
+            other => panic!("unexpected configuration {:?}", other),
+        }
+        assert_eq!(
+            serde_json::to_value(&event).expect("failed to serialize event"),
+            serde_json::from_str::<Value>(fixture).unwrap()
+        );
+    }
+
+    #[test]
+    fn deserializes_composite_alarm_state_changes() {
+        let fixture = include_str!("../tests/data/eventbridge_cloudwatch_composite_alarm_state_change_event.json");
+        let event: CloudWatchAlarmStateChangeEvent =
+            serde_json::from_str(fixture).expect("failed to deserialize event");
+        match &event.detail.configuration {
+            CloudWatchAlarmConfiguration::Composite(configuration) => {
+                assert!(configuration.alarm_rule.starts_with("ALARM(OrdersCpuTooHigh)"));
+                assert_eq!(configuration.actions_suppressor_wait_period, Some(120));
+            }
+            other => panic!("unexpected configuration {:?}", other),
+        }
+        let reason: Value = event
+            .detail
+            .state
+            .reason_data_json()
+            .expect("failed to parse reason data")
+            .expect("missing reason data");
+        assert_eq!(
+            reason["triggeringAlarms"][0]["arn"],
+            "arn:aws:cloudwatch:eu-west-1:123456789012:alarm:OrdersCpuTooHigh"
+        );
+        assert_eq!(
+            serde_json::to_value(&event).expect("failed to serialize event"),
+            serde_json::from_str::<Value>(fixture).unwrap()
+        );
+    }
+
+    #[test]
+    fn serializes_constructed_events() {
+        let event = EventBridgeEvent {
+            version: "0".into(),
+            id: "1".into(),
+            detail_type: "Order Placed".into(),
+            source: "com.example.orders".into(),
+            account: "123456789012".into(),
+            time: UNIX_EPOCH + Duration::from_millis(1_590_991_200_500),
+            region: "eu-west-1".into(),
+            resources: vec![],
+            detail: OrderPlaced {
+                order_id: "2".into(),
+                items: vec![],
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&event).expect("failed to serialize event"),
+            json!({
+                "version": "0",
+                "id": "1",
+                "detail-type": "Order Placed",
+                "source": "com.example.orders",
+                "account": "123456789012",
+                "time": "2020-06-01T06:00:00.500Z",
+                "region": "eu-west-1",
+                "resources": [],
+                "detail": { "orderId": "2", "items": [] }
+            })
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Kinesis Data Firehose data transformation event and response types
+//!
+//! Delivery streams with data transformation enabled invoke functions with a
+//! [`FirehoseEvent`](struct.FirehoseEvent.html) and expect a
+//! [`FirehoseResponse`](struct.FirehoseResponse.html) accounting for every one of its records,
+//! exactly once. Firehose fails the whole invocation otherwise.
+//! [`FirehoseEvent::transform`](struct.FirehoseEvent.html#method.transform) builds such a response.
+
+pub use crate::kinesis::DataError;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A batch of records to transform
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FirehoseEvent {
+    /// A unique identifier for the invocation
+    pub invocation_id: String,
+    /// The ARN of the delivery stream
+    pub delivery_stream_arn: String,
+    /// The ARN of the Kinesis stream the delivery stream reads from, if it reads from one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_kinesis_stream_arn: Option<String>,
+    /// The region of the delivery stream
+    pub region: String,
+    /// The records to transform
+    pub records: Vec<FirehoseRecord>,
+}
+
+impl FirehoseEvent {
+    /// Transform every record with `f`, assembling the response in the order of the records
+    ///
+    /// `f` returns the transformed data of a record, `None` to drop the record, or an error to
+    /// report the record as failed, in which case Firehose delivers it to the error output
+    /// untransformed.
+    ///
+    /// ```rust
+    /// use lambda_events::firehose::{FirehoseEvent, FirehoseResponse};
+    ///
+    /// fn uppercase(event: &FirehoseEvent) -> FirehoseResponse {
+    ///     event.transform(|record| {
+    ///         let data = String::from_utf8(record.decoded_data()?)?;
+    ///         Ok::<_, Box<dyn std::error::Error>>(Some(data.to_uppercase().into_bytes()))
+    ///     })
+    /// }
+    /// ```
+    pub fn transform<F, E>(&self, mut f: F) -> FirehoseResponse
+    where
+        F: FnMut(&FirehoseRecord) -> Result<Option<Vec<u8>>, E>,
+    {
+        FirehoseResponse {
+            records: self
+                .records
+                .iter()
+                .map(|record| match f(record) {
+                    Ok(Some(data)) => FirehoseResponseRecord::ok(record.record_id.clone(), &data),
+                    Ok(None) => FirehoseResponseRecord::dropped(record.record_id.clone()),
+                    Err(_) => FirehoseResponseRecord::failed(record.record_id.clone()),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A single record to transform
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FirehoseRecord {
+    /// The identifier the response has to answer the record with
+    pub record_id: String,
+    /// When the record was added to the delivery stream, in milliseconds since the Unix epoch
+    pub approximate_arrival_timestamp: i64,
+    /// The base64 encoded data of the record
+    pub data: String,
+    /// Where the record was read from, for delivery streams reading from a Kinesis stream
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kinesis_record_metadata: Option<FirehoseKinesisRecordMetadata>,
+}
+
+impl FirehoseRecord {
+    /// Return the record's data, decoded from base64
+    pub fn decoded_data(&self) -> Result<Vec<u8>, DataError> {
+        base64::decode(&self.data).map_err(DataError::Base64)
+    }
+
+    /// Deserialize the record's data from JSON
+    pub fn json_data<T>(&self) -> Result<T, DataError>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_slice(&self.decoded_data()?).map_err(DataError::Json)
+    }
+
+    /// Return when the record was added to the delivery stream
+    pub fn approximate_arrival_time(&self) -> SystemTime {
+        let millis = Duration::from_millis(self.approximate_arrival_timestamp.unsigned_abs());
+        if self.approximate_arrival_timestamp < 0 {
+            UNIX_EPOCH - millis
+        } else {
+            UNIX_EPOCH + millis
+        }
+    }
+}
+
+/// Where a record was read from in the Kinesis stream a delivery stream reads from
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FirehoseKinesisRecordMetadata {
+    /// The shard the record was read from
+    pub shard_id: String,
+    /// The partition key of the record
+    pub partition_key: String,
+    /// When the record was added to the Kinesis stream, in milliseconds since the Unix epoch
+    pub approximate_arrival_timestamp: i64,
+    /// The sequence number of the record in its shard
+    pub sequence_number: String,
+    /// The position of the record among the user records aggregated into the same Kinesis record
+    pub subsequence_number: u64,
+}
+
+/// The transformed records of a `FirehoseEvent`
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct FirehoseResponse {
+    /// One record per record of the event
+    pub records: Vec<FirehoseResponseRecord>,
+}
+
+/// The outcome of transforming a single record
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FirehoseResponseRecord {
+    /// The `record_id` of the transformed record
+    pub record_id: String,
+    /// Whether the record was transformed, dropped or failed
+    pub result: FirehoseResult,
+    /// The base64 encoded transformed data
+    pub data: String,
+    /// The partition keys of the record, for delivery streams with dynamic partitioning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<FirehoseResponseRecordMetadata>,
+}
+
+impl FirehoseResponseRecord {
+    /// Report the record as transformed into `data`
+    pub fn ok(record_id: impl Into<String>, data: &[u8]) -> Self {
+        Self::new(record_id, FirehoseResult::Ok, base64::encode(data))
+    }
+
+    /// Report the record as intentionally dropped
+    pub fn dropped(record_id: impl Into<String>) -> Self {
+        Self::new(record_id, FirehoseResult::Dropped, String::new())
+    }
+
+    /// Report the record as failed to transform
+    pub fn failed(record_id: impl Into<String>) -> Self {
+        Self::new(record_id, FirehoseResult::ProcessingFailed, String::new())
+    }
+
+    /// Partition the record by `partition_keys`, for delivery streams with dynamic partitioning
+    pub fn with_partition_keys(mut self, partition_keys: HashMap<String, String>) -> Self {
+        self.metadata = Some(FirehoseResponseRecordMetadata { partition_keys });
+        self
+    }
+
+    fn new(record_id: impl Into<String>, result: FirehoseResult, data: String) -> Self {
+        Self {
+            record_id: record_id.into(),
+            result,
+            data,
+            metadata: None,
+        }
+    }
+}
+
+/// Whether a record was transformed, dropped or failed
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FirehoseResult {
+    /// The record was transformed
+    Ok,
+    /// The record was intentionally dropped
+    Dropped,
+    /// The record failed to transform, and is delivered to the error output
+    ProcessingFailed,
+}
+
+/// The metadata of a transformed record
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FirehoseResponseRecordMetadata {
+    /// The keys the record is partitioned by
+    pub partition_keys: HashMap<String, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_json::json;
+
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct Order {
+        total: f64,
+    }
+
+    #[test]
+    fn round_trips_events() {
+        for fixture in &[
+            include_str!("../tests/data/firehose_event.json"),
+            include_str!("../tests/data/firehose_kinesis_event.json"),
+        ] {
+            assert_round_trips::<FirehoseEvent>(fixture);
+        }
+    }
+
+    #[test]
+    fn deserializes_records() {
+        let event = assert_round_trips::<FirehoseEvent>(include_str!("../tests/data/firehose_kinesis_event.json"));
+        assert!(event.source_kinesis_stream_arn.is_some());
+        let record = &event.records[0];
+        assert_eq!(record.json_data::<Order>().unwrap().total, 12.5);
+        assert_eq!(
+            record.approximate_arrival_time(),
+            UNIX_EPOCH + Duration::from_millis(1_591_005_600_000)
+        );
+        let metadata = record.kinesis_record_metadata.as_ref().expect("missing metadata");
+        assert_eq!(metadata.partition_key, "order-1");
+
+        let event = assert_round_trips::<FirehoseEvent>(include_str!("../tests/data/firehose_event.json"));
+        assert_eq!(event.source_kinesis_stream_arn, None);
+        assert_eq!(event.records[0].decoded_data().unwrap(), b"home".to_vec());
+        assert_eq!(event.records[0].kinesis_record_metadata, None);
+    }
+
+    #[test]
+    fn answers_every_record_exactly_once_in_order() {
+        let event = assert_round_trips::<FirehoseEvent>(include_str!("../tests/data/firehose_kinesis_event.json"));
+        let response = event.transform(|record| {
+            let order: Order = record.json_data()?;
+            if order.total == 0.0 {
+                return Ok(None);
+            }
+            Ok::<_, DataError>(Some(format!("{}\n", order.total).into_bytes()))
+        });
+
+        let input: Vec<_> = event.records.iter().map(|record| &record.record_id).collect();
+        let output: Vec<_> = response.records.iter().map(|record| &record.record_id).collect();
+        assert_eq!(input, output);
+        for id in &input {
+            assert_eq!(output.iter().filter(|output| output == &id).count(), 1);
+        }
+        assert_eq!(
+            response.records.iter().map(|record| record.result).collect::<Vec<_>>(),
+            vec![
+                FirehoseResult::Ok,
+                FirehoseResult::ProcessingFailed,
+                FirehoseResult::Dropped
+            ]
+        );
+        assert_eq!(base64::decode(&response.records[0].data).unwrap(), b"12.5\n".to_vec());
+    }
+
+    #[test]
+    fn serializes_responses() {
+        let mut partition_keys = HashMap::new();
+        partition_keys.insert("customerId".to_string(), "42".to_string());
+        let response = FirehoseResponse {
+            records: vec![
+                FirehoseResponseRecord::ok("1", b"hi").with_partition_keys(partition_keys),
+                FirehoseResponseRecord::dropped("2"),
+                FirehoseResponseRecord::failed("3"),
+            ],
+        };
+        assert_eq!(
+            serde_json::to_value(&response).expect("failed to serialize response"),
+            json!({
+                "records": [
+                    { "recordId": "1", "result": "Ok", "data": "aGk=", "metadata": { "partitionKeys": { "customerId": "42" } } },
+                    { "recordId": "2", "result": "Dropped", "data": "" },
+                    { "recordId": "3", "result": "ProcessingFailed", "data": "" }
+                ]
+            })
+        );
+    }
+}
@@ -0,0 +1,366 @@
+//! IAM policy documents
+//!
+//! API Gateway and IoT Core custom authorizers answer with IAM policy documents, assembled with
+//! a [`PolicyBuilder`](struct.PolicyBuilder.html) started by
+//! [`PolicyDocument::allow`](struct.PolicyDocument.html#method.allow) or
+//! [`PolicyDocument::deny`](struct.PolicyDocument.html#method.deny).
+//!
+//! ```rust
+//! use lambda_events::iam::PolicyDocument;
+//!
+//! let policy = PolicyDocument::allow()
+//!     .action("execute-api:Invoke")
+//!     .resource("arn:aws:execute-api:us-east-1:123456789012:abcdef123/test/GET/pets")
+//!     .deny()
+//!     .action("execute-api:Invoke")
+//!     .resource("arn:aws:execute-api:us-east-1:123456789012:abcdef123/test/*/admin")
+//!     .build()
+//!     .expect("invalid policy");
+//! assert_eq!(policy.statement.len(), 2);
+//! ```
+
+use crate::custom_serde::{deserialize_one_or_many, serialize_one_or_many};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, error::Error, fmt};
+
+/// The policy language version of the documents this module assembles
+const POLICY_VERSION: &str = "2012-10-17";
+
+/// An IAM policy document
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct PolicyDocument {
+    /// The policy language version, `2012-10-17`
+    pub version: String,
+    /// The policy's statements
+    pub statement: Vec<PolicyStatement>,
+}
+
+impl PolicyDocument {
+    /// Create a document of the current policy language version with `statements`
+    pub fn new(statements: Vec<PolicyStatement>) -> Self {
+        Self {
+            version: POLICY_VERSION.into(),
+            statement: statements,
+        }
+    }
+
+    /// Start a document with a statement allowing access
+    pub fn allow() -> PolicyBuilder {
+        PolicyBuilder::starting_with(Effect::Allow)
+    }
+
+    /// Start a document with a statement denying access
+    pub fn deny() -> PolicyBuilder {
+        PolicyBuilder::starting_with(Effect::Deny)
+    }
+}
+
+/// A single IAM policy statement
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct PolicyStatement {
+    /// The actions the statement applies to
+    #[serde(
+        deserialize_with = "deserialize_one_or_many",
+        serialize_with = "serialize_one_or_many"
+    )]
+    pub action: Vec<String>,
+    /// Whether the statement allows or denies its actions
+    pub effect: Effect,
+    /// The resources the statement applies to
+    #[serde(
+        deserialize_with = "deserialize_one_or_many",
+        serialize_with = "serialize_one_or_many"
+    )]
+    pub resource: Vec<String>,
+    /// The conditions under which the statement applies, keyed by condition operator and then
+    /// by condition key, like `{"IpAddress": {"aws:SourceIp": "203.0.113.0/24"}}`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub condition: BTreeMap<String, BTreeMap<String, Value>>,
+}
+
+impl PolicyStatement {
+    /// Create a statement with `effect` and no actions, resources or conditions
+    pub fn new(effect: Effect) -> Self {
+        Self {
+            action: Vec::new(),
+            effect,
+            resource: Vec::new(),
+            condition: BTreeMap::new(),
+        }
+    }
+}
+
+/// The effect of an IAM policy statement
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    /// The statement allows access
+    Allow,
+    /// The statement denies access
+    Deny,
+}
+
+/// A builder of [`PolicyDocument`](struct.PolicyDocument.html)s
+///
+/// `allow` and `deny` start a statement, and the other methods add to the last statement
+/// started.
+///
+/// ```rust
+/// use lambda_events::iam::PolicyDocument;
+///
+/// let policy = PolicyDocument::allow()
+///     .action("iot:Connect")
+///     .resource("arn:aws:iot:eu-west-1:123456789012:client/sensor-1")
+///     .condition("Bool", "aws:SecureTransport", "true")
+///     .build()
+///     .expect("invalid policy");
+/// assert_eq!(
+///     serde_json::to_string(&policy).unwrap(),
+///     r#"{"Version":"2012-10-17","Statement":[{"Action":"iot:Connect","Effect":"Allow","Resource":"arn:aws:iot:eu-west-1:123456789012:client/sensor-1","Condition":{"Bool":{"aws:SecureTransport":"true"}}}]}"#
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct PolicyBuilder {
+    statements: Vec<PolicyStatement>,
+}
+
+impl PolicyBuilder {
+    fn starting_with(effect: Effect) -> Self {
+        Self {
+            statements: vec![PolicyStatement::new(effect)],
+        }
+    }
+
+    /// Start a statement allowing access
+    pub fn allow(self) -> Self {
+        self.statement(PolicyStatement::new(Effect::Allow))
+    }
+
+    /// Start a statement denying access
+    pub fn deny(self) -> Self {
+        self.statement(PolicyStatement::new(Effect::Deny))
+    }
+
+    /// Add a statement assembled separately
+    pub fn statement(mut self, statement: PolicyStatement) -> Self {
+        self.statements.push(statement);
+        self
+    }
+
+    /// Add an action, like `execute-api:Invoke`, to the statement
+    pub fn action<A>(mut self, action: A) -> Self
+    where
+        A: Into<String>,
+    {
+        self.last().action.push(action.into());
+        self
+    }
+
+    /// Add a resource ARN, which may contain wildcards, to the statement
+    pub fn resource<R>(mut self, resource: R) -> Self
+    where
+        R: Into<String>,
+    {
+        self.last().resource.push(resource.into());
+        self
+    }
+
+    /// Add a condition to the statement, like `("IpAddress", "aws:SourceIp", "203.0.113.0/24")`
+    ///
+    /// Conditions with the same operator and key replace each other.
+    pub fn condition<O, K, V>(mut self, operator: O, key: K, value: V) -> Self
+    where
+        O: Into<String>,
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.last()
+            .condition
+            .entry(operator.into())
+            .or_default()
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Return the assembled document
+    ///
+    /// Fails if a statement has no action or no resource, or a resource which is neither `*`
+    /// nor an ARN.
+    pub fn build(self) -> Result<PolicyDocument, PolicyError> {
+        for (index, statement) in self.statements.iter().enumerate() {
+            if statement.action.is_empty() || statement.action.iter().any(String::is_empty) {
+                return Err(PolicyError::MissingAction { statement: index });
+            }
+            if statement.resource.is_empty() {
+                return Err(PolicyError::MissingResource { statement: index });
+            }
+            if let Some(resource) = statement
+                .resource
+                .iter()
+                .find(|resource| *resource != "*" && !resource.starts_with("arn:"))
+            {
+                return Err(PolicyError::InvalidResource {
+                    statement: index,
+                    resource: resource.clone(),
+                });
+            }
+        }
+        Ok(PolicyDocument::new(self.statements))
+    }
+
+    fn last(&mut self) -> &mut PolicyStatement {
+        self.statements.last_mut().expect("builders start with a statement")
+    }
+}
+
+/// Returned when a [`PolicyBuilder`](struct.PolicyBuilder.html) assembled an invalid document
+#[derive(Debug, Clone, PartialEq)]
+pub enum PolicyError {
+    /// A statement has no action
+    MissingAction {
+        /// The index of the statement
+        statement: usize,
+    },
+    /// A statement has no resource
+    MissingResource {
+        /// The index of the statement
+        statement: usize,
+    },
+    /// A statement has a resource which is neither `*` nor an ARN
+    InvalidResource {
+        /// The index of the statement
+        statement: usize,
+        /// The resource
+        resource: String,
+    },
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::MissingAction { statement } => write!(f, "policy statement {} has no action", statement),
+            PolicyError::MissingResource { statement } => {
+                write!(f, "policy statement {} has no resource", statement)
+            }
+            PolicyError::InvalidResource { statement, resource } => write!(
+                f,
+                "policy statement {} has resource {:?}, which is not an ARN",
+                statement, resource
+            ),
+        }
+    }
+}
+
+impl Error for PolicyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serializes_documented_api_gateway_policies() {
+        let policy = PolicyDocument::deny()
+            .action("execute-api:Invoke")
+            .resource("arn:aws:execute-api:us-west-2:123456789012:ymy8tbxw7b/*/GET/")
+            .build()
+            .expect("invalid policy");
+        assert_eq!(
+            serde_json::to_string(&policy).unwrap(),
+            r#"{"Version":"2012-10-17","Statement":[{"Action":"execute-api:Invoke","Effect":"Deny","Resource":"arn:aws:execute-api:us-west-2:123456789012:ymy8tbxw7b/*/GET/"}]}"#
+        );
+    }
+
+    #[test]
+    fn serializes_documented_iot_policies() {
+        let policy = PolicyDocument::allow()
+            .action("iot:Publish")
+            .resource("arn:aws:iot:us-east-1:123456789012:topic/customauthtesting")
+            .build()
+            .expect("invalid policy");
+        assert_eq!(
+            serde_json::to_string(&policy).unwrap(),
+            r#"{"Version":"2012-10-17","Statement":[{"Action":"iot:Publish","Effect":"Allow","Resource":"arn:aws:iot:us-east-1:123456789012:topic/customauthtesting"}]}"#
+        );
+    }
+
+    #[test]
+    fn serializes_several_statements_and_conditions() {
+        let policy = PolicyDocument::allow()
+            .action("iot:Connect")
+            .resource("arn:aws:iot:eu-west-1:123456789012:client/sensor-1")
+            .allow()
+            .action("iot:Publish")
+            .action("iot:Receive")
+            .resource("arn:aws:iot:eu-west-1:123456789012:topic/sensors/1")
+            .resource("arn:aws:iot:eu-west-1:123456789012:topic/sensors/all")
+            .condition(
+                "IpAddress",
+                "aws:SourceIp",
+                json!(["203.0.113.0/24", "198.51.100.0/24"]),
+            )
+            .condition("Bool", "aws:SecureTransport", "true")
+            .deny()
+            .action("iot:*")
+            .resource("*")
+            .build()
+            .expect("invalid policy");
+        assert_eq!(
+            serde_json::to_value(&policy).unwrap(),
+            json!({
+                "Version": "2012-10-17",
+                "Statement": [
+                    {
+                        "Action": "iot:Connect",
+                        "Effect": "Allow",
+                        "Resource": "arn:aws:iot:eu-west-1:123456789012:client/sensor-1"
+                    },
+                    {
+                        "Action": ["iot:Publish", "iot:Receive"],
+                        "Effect": "Allow",
+                        "Resource": [
+                            "arn:aws:iot:eu-west-1:123456789012:topic/sensors/1",
+                            "arn:aws:iot:eu-west-1:123456789012:topic/sensors/all"
+                        ],
+                        "Condition": {
+                            "Bool": { "aws:SecureTransport": "true" },
+                            "IpAddress": { "aws:SourceIp": ["203.0.113.0/24", "198.51.100.0/24"] }
+                        }
+                    },
+                    { "Action": "iot:*", "Effect": "Deny", "Resource": "*" }
+                ]
+            })
+        );
+        let round_tripped: PolicyDocument =
+            serde_json::from_value(serde_json::to_value(&policy).unwrap()).expect("failed to deserialize policy");
+        assert_eq!(round_tripped, policy);
+    }
+
+    #[test]
+    fn rejects_invalid_statements() {
+        assert_eq!(
+            PolicyDocument::allow().build(),
+            Err(PolicyError::MissingAction { statement: 0 })
+        );
+        assert_eq!(
+            PolicyDocument::allow()
+                .action("s3:GetObject")
+                .resource("arn:aws:s3:::bucket/*")
+                .deny()
+                .action("s3:DeleteObject")
+                .build(),
+            Err(PolicyError::MissingResource { statement: 1 })
+        );
+        let error = PolicyDocument::allow()
+            .action("s3:GetObject")
+            .resource("bucket/*")
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"policy statement 0 has resource "bucket/*", which is not an ARN"#
+        );
+    }
+}
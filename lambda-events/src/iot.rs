@@ -0,0 +1,401 @@
+//! IoT Core custom authorizer and provisioning hook types
+//!
+//! Custom authorizers receive an [`IoTCustomAuthorizerRequest`](struct.IoTCustomAuthorizerRequest.html)
+//! when a device connects and answer with an
+//! [`IoTCustomAuthorizerResponse`](struct.IoTCustomAuthorizerResponse.html) carrying the policies
+//! of the connection, assembled with the
+//! [`PolicyDocumentBuilder`](../apigw/struct.PolicyDocumentBuilder.html) API Gateway
+//! authorizers use or as [`PolicyDocument`](../iam/struct.PolicyDocument.html)s. Fleet provisioning hooks receive an
+//! [`IoTProvisioningHookRequest`](struct.IoTProvisioningHookRequest.html) before a device is
+//! provisioned and answer with an [`IoTProvisioningHookResponse`](struct.IoTProvisioningHookResponse.html).
+//!
+//! Rules invoking functions pass them the payload their SQL statement selects as is, so they
+//! have no event type of their own.
+
+use crate::{apigw::PolicyDocumentBuilder, iam::PolicyDocument};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The longest time a connection may stay open, and the longest time before IoT authorizes it again
+const MAX_TTL_SECONDS: u32 = 86_400;
+
+/// The input to an IoT Core custom authorizer
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IoTCustomAuthorizerRequest {
+    /// The token the device sent, for authorizers with token signing or a token key name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Whether IoT verified the signature of the token, for authorizers with token signing
+    pub signature_verified: bool,
+    /// The protocols of the connection, like `tls`, `mqtt` or `http`
+    pub protocols: Vec<String>,
+    /// The details of each protocol of the connection
+    pub protocol_data: IoTProtocolData,
+    /// The connection
+    pub connection_metadata: IoTConnectionMetadata,
+}
+
+/// The details of each protocol of a connection
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct IoTProtocolData {
+    /// The TLS details
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<IoTTlsContext>,
+    /// The HTTP details, for HTTP requests and MQTT over WebSocket
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<IoTHttpContext>,
+    /// The MQTT details, for MQTT connections
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mqtt: Option<IoTMqttContext>,
+}
+
+/// The TLS details of a connection
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IoTTlsContext {
+    /// The server name indication the device sent
+    pub server_name: String,
+}
+
+/// The HTTP details of a connection
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IoTHttpContext {
+    /// The headers of the request
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// The query string of the request, starting with `?`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_string: Option<String>,
+}
+
+/// The MQTT details of a connection
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IoTMqttContext {
+    /// The user name of the `CONNECT` packet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// The password of the `CONNECT` packet, encoded as base64
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// The client id of the `CONNECT` packet
+    pub client_id: String,
+}
+
+impl IoTMqttContext {
+    /// Return the password of the `CONNECT` packet, decoded from base64
+    pub fn decoded_password(&self) -> Result<Option<Vec<u8>>, base64::DecodeError> {
+        self.password.as_ref().map(base64::decode).transpose()
+    }
+}
+
+/// A connection to IoT Core
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct IoTConnectionMetadata {
+    /// A unique identifier for the connection
+    pub id: String,
+}
+
+/// The response of an IoT Core custom authorizer
+///
+/// ```rust
+/// use lambda_events::{
+///     apigw::PolicyDocumentBuilder,
+///     iot::{IoTCustomAuthorizerRequest, IoTCustomAuthorizerResponse},
+/// };
+///
+/// fn authorize(request: IoTCustomAuthorizerRequest) -> IoTCustomAuthorizerResponse {
+///     match request.protocol_data.mqtt {
+///         Some(mqtt) if request.signature_verified => {
+///             let arn = "arn:aws:iot:eu-west-1:123456789012";
+///             let policy = PolicyDocumentBuilder::new(mqtt.client_id.as_str())
+///                 .allow_action("iot:Connect", format!("{}:client/{}", arn, mqtt.client_id))
+///                 .allow_action("iot:Publish", format!("{}:topic/devices/{}/*", arn, mqtt.client_id));
+///             IoTCustomAuthorizerResponse::authenticated(policy).with_refresh_after_in_seconds(3_600)
+///         }
+///         _ => IoTCustomAuthorizerResponse::unauthenticated("anonymous"),
+///     }
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IoTCustomAuthorizerResponse {
+    /// Whether the connection is allowed
+    pub is_authenticated: bool,
+    /// Identifies the device, between 1 and 128 alphanumeric characters
+    pub principal_id: String,
+    /// How long the connection may stay open, between 300 and 86400 seconds
+    pub disconnect_after_in_seconds: u32,
+    /// How long until IoT invokes the authorizer again for the connection, between 300 and 86400 seconds
+    pub refresh_after_in_seconds: u32,
+    /// The policies of the connection
+    #[serde(with = "policy_documents")]
+    pub policy_documents: Vec<PolicyDocument>,
+}
+
+impl IoTCustomAuthorizerResponse {
+    /// Allow the connection with the principal and the policy of a builder
+    ///
+    /// The builder's context and usage identifier key don't apply to IoT and are left out.
+    /// The connection may stay open, and keeps its policy, for the longest time IoT allows.
+    pub fn authenticated(policy: PolicyDocumentBuilder) -> Self {
+        let response = policy.build();
+        Self {
+            is_authenticated: true,
+            principal_id: response.principal_id,
+            disconnect_after_in_seconds: MAX_TTL_SECONDS,
+            refresh_after_in_seconds: MAX_TTL_SECONDS,
+            policy_documents: vec![response.policy_document],
+        }
+    }
+
+    /// Refuse the connection
+    pub fn unauthenticated<P>(principal_id: P) -> Self
+    where
+        P: Into<String>,
+    {
+        Self {
+            is_authenticated: false,
+            principal_id: principal_id.into(),
+            disconnect_after_in_seconds: MAX_TTL_SECONDS,
+            refresh_after_in_seconds: MAX_TTL_SECONDS,
+            policy_documents: Vec::new(),
+        }
+    }
+
+    /// Add a policy document to the connection's policies
+    pub fn with_policy(mut self, policy: PolicyDocument) -> Self {
+        self.policy_documents.push(policy);
+        self
+    }
+
+    /// Set how long the connection may stay open
+    pub fn with_disconnect_after_in_seconds(self, seconds: u32) -> Self {
+        Self {
+            disconnect_after_in_seconds: seconds,
+            ..self
+        }
+    }
+
+    /// Set how long until IoT invokes the authorizer again for the connection
+    pub fn with_refresh_after_in_seconds(self, seconds: u32) -> Self {
+        Self {
+            refresh_after_in_seconds: seconds,
+            ..self
+        }
+    }
+}
+
+/// IoT expects each policy document as a string of JSON, but accepting objects as well costs nothing
+mod policy_documents {
+    use crate::iam;
+    use serde::{
+        de::{Deserialize, Deserializer, Error as _},
+        ser::{Error as _, SerializeSeq, Serializer},
+    };
+    use serde_derive::Deserialize;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PolicyDocument {
+        Json(String),
+        Object(iam::PolicyDocument),
+    }
+
+    pub(super) fn serialize<S>(policies: &[iam::PolicyDocument], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(policies.len()))?;
+        for policy in policies {
+            seq.serialize_element(&serde_json::to_string(policy).map_err(S::Error::custom)?)?;
+        }
+        seq.end()
+    }
+
+    pub(super) fn deserialize<'de, D>(deserializer: D) -> Result<Vec<iam::PolicyDocument>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<PolicyDocument>::deserialize(deserializer)?
+            .into_iter()
+            .map(|policy| match policy {
+                PolicyDocument::Json(json) => serde_json::from_str(&json).map_err(D::Error::custom),
+                PolicyDocument::Object(policy) => Ok(policy),
+            })
+            .collect()
+    }
+}
+
+/// The input to a fleet provisioning pre-provisioning hook
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IoTProvisioningHookRequest {
+    /// The id of the claim certificate the device connected with
+    pub claim_certificate_id: String,
+    /// The id of the certificate to provision
+    pub certificate_id: String,
+    /// The certificate to provision, PEM encoded
+    pub certificate_pem: String,
+    /// The ARN of the provisioning template
+    pub template_arn: String,
+    /// The client id of the device's MQTT connection
+    pub client_id: String,
+    /// The parameters of the provisioning template, as the device sent them
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+}
+
+/// The response of a fleet provisioning pre-provisioning hook
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IoTProvisioningHookResponse {
+    /// Whether the device may be provisioned
+    pub allow_provisioning: bool,
+    /// The parameters of the provisioning template to replace
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub parameter_overrides: HashMap<String, String>,
+}
+
+impl IoTProvisioningHookResponse {
+    /// Allow the device to be provisioned
+    pub fn allow() -> Self {
+        Self {
+            allow_provisioning: true,
+            parameter_overrides: HashMap::new(),
+        }
+    }
+
+    /// Refuse to provision the device
+    pub fn deny() -> Self {
+        Self::default()
+    }
+
+    /// Replace a parameter of the provisioning template
+    pub fn with_parameter_override<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.parameter_overrides.insert(key.into(), value.into());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn round_trips_requests() {
+        let fixture = include_str!("../tests/data/iot_custom_authorizer_mqtt_request.json");
+        let request: IoTCustomAuthorizerRequest = serde_json::from_str(fixture).expect("failed to deserialize request");
+        assert_eq!(
+            serde_json::to_value(request).expect("failed to serialize request"),
+            serde_json::from_str::<Value>(fixture).unwrap()
+        );
+        let fixture = include_str!("../tests/data/iot_provisioning_hook_request.json");
+        let request: IoTProvisioningHookRequest = serde_json::from_str(fixture).expect("failed to deserialize request");
+        assert_eq!(
+            serde_json::to_value(request).expect("failed to serialize request"),
+            serde_json::from_str::<Value>(fixture).unwrap()
+        );
+    }
+
+    #[test]
+    fn authorizes_mqtt_connections() {
+        let request: IoTCustomAuthorizerRequest =
+            serde_json::from_str(include_str!("../tests/data/iot_custom_authorizer_mqtt_request.json"))
+                .expect("failed to deserialize request");
+        assert!(request.signature_verified);
+        assert_eq!(request.protocol_data.http, None);
+        let mqtt = request.protocol_data.mqtt.expect("missing MQTT details");
+        assert_eq!(mqtt.decoded_password().unwrap(), Some(b"s3cr3t".to_vec()));
+
+        let response = IoTCustomAuthorizerResponse::authenticated(
+            PolicyDocumentBuilder::new(mqtt.client_id.as_str())
+                .allow_action("iot:Connect", "arn:aws:iot:eu-west-1:123456789012:client/thermostat-42")
+                .context("ignored", true),
+        )
+        .with_disconnect_after_in_seconds(3_600)
+        .with_refresh_after_in_seconds(300);
+        let policy = r#"{"Version":"2012-10-17","Statement":[{"Action":"iot:Connect","Effect":"Allow","Resource":"arn:aws:iot:eu-west-1:123456789012:client/thermostat-42"}]}"#;
+        let serialized = serde_json::to_value(&response).expect("failed to serialize response");
+        assert_eq!(
+            serialized,
+            json!({
+                "isAuthenticated": true,
+                "principalId": "thermostat-42",
+                "disconnectAfterInSeconds": 3_600,
+                "refreshAfterInSeconds": 300,
+                "policyDocuments": [policy]
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<IoTCustomAuthorizerResponse>(serialized).expect("failed to deserialize response"),
+            response
+        );
+    }
+
+    #[test]
+    fn adds_policy_documents() {
+        let policy = PolicyDocument::allow()
+            .action("iot:Publish")
+            .resource("arn:aws:iot:us-east-1:123456789012:topic/customauthtesting")
+            .build()
+            .expect("invalid policy");
+        let response = IoTCustomAuthorizerResponse::unauthenticated("tester")
+            .with_policy(policy.clone())
+            .with_policy(policy);
+        assert_eq!(
+            serde_json::to_value(&response).expect("failed to serialize response")["policyDocuments"],
+            json!([
+                r#"{"Version":"2012-10-17","Statement":[{"Action":"iot:Publish","Effect":"Allow","Resource":"arn:aws:iot:us-east-1:123456789012:topic/customauthtesting"}]}"#,
+                r#"{"Version":"2012-10-17","Statement":[{"Action":"iot:Publish","Effect":"Allow","Resource":"arn:aws:iot:us-east-1:123456789012:topic/customauthtesting"}]}"#
+            ])
+        );
+    }
+
+    #[test]
+    fn deserializes_policy_documents_as_objects() {
+        let response: IoTCustomAuthorizerResponse = serde_json::from_value(json!({
+            "isAuthenticated": true,
+            "principalId": "thermostat-42",
+            "disconnectAfterInSeconds": 86_400,
+            "refreshAfterInSeconds": 86_400,
+            "policyDocuments": [{
+                "Version": "2012-10-17",
+                "Statement": [{ "Action": "iot:Publish", "Effect": "Allow", "Resource": ["arn:aws:iot:eu-west-1:123456789012:topic/a"] }]
+            }]
+        }))
+        .expect("failed to deserialize response");
+        assert_eq!(response.policy_documents[0].statement[0].action, vec!["iot:Publish"]);
+        assert_eq!(
+            serde_json::to_value(IoTCustomAuthorizerResponse::unauthenticated("anonymous")).unwrap()["policyDocuments"],
+            json!([])
+        );
+    }
+
+    #[test]
+    fn approves_provisioning() {
+        let request: IoTProvisioningHookRequest =
+            serde_json::from_str(include_str!("../tests/data/iot_provisioning_hook_request.json"))
+                .expect("failed to deserialize request");
+        assert_eq!(request.parameters["SerialNumber"], "TH-0042");
+        assert!(request.template_arn.ends_with("provisioningtemplate/thermostats"));
+
+        let response =
+            IoTProvisioningHookResponse::allow().with_parameter_override("AWS::IoT::Certificate::Country", "US");
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({ "allowProvisioning": true, "parameterOverrides": { "AWS::IoT::Certificate::Country": "US" } })
+        );
+        assert_eq!(
+            serde_json::to_value(IoTProvisioningHookResponse::deny()).unwrap(),
+            json!({ "allowProvisioning": false })
+        );
+    }
+}
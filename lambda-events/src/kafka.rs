@@ -0,0 +1,257 @@
+//! Amazon MSK and self-managed Apache Kafka event types
+//!
+//! Event source mappings deliver batches of Kafka records in a [`KafkaEvent`](struct.KafkaEvent.html),
+//! grouped by the topic partition they were read from. Keys and values are base64 encoded, and
+//! either may be `null`: records produced without a key, and tombstones marking deleted keys in
+//! compacted topics.
+
+pub use crate::kinesis::DataError;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A batch of Kafka records
+///
+/// ```rust
+/// use lambda_events::kafka::KafkaEvent;
+///
+/// fn deleted_keys(event: &KafkaEvent) -> Vec<Vec<u8>> {
+///     event
+///         .iter_records()
+///         .filter(|record| record.is_tombstone())
+///         .filter_map(|record| record.decoded_key().ok().flatten())
+///         .collect()
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KafkaEvent {
+    /// Whether the records come from an MSK cluster or a self-managed one
+    pub event_source: KafkaEventSource,
+    /// The ARN of the MSK cluster, absent for self-managed clusters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_source_arn: Option<String>,
+    /// The comma separated brokers of the cluster
+    pub bootstrap_servers: String,
+    /// The records, keyed by the topic partition they were read from, like `orders-0`
+    pub records: HashMap<String, Vec<KafkaRecord>>,
+}
+
+impl KafkaEvent {
+    /// Return an iterator over the records of all topic partitions
+    ///
+    /// Records of the same topic partition are returned in offset order, but topic partitions
+    /// are returned in no particular order.
+    pub fn iter_records(&self) -> impl Iterator<Item = &KafkaRecord> {
+        self.records.values().flatten()
+    }
+}
+
+/// The kind of cluster Kafka records are read from
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KafkaEventSource {
+    /// An Amazon MSK cluster
+    #[serde(rename = "aws:kafka")]
+    Msk,
+    /// A self-managed Apache Kafka cluster
+    #[serde(rename = "SelfManagedKafka")]
+    SelfManaged,
+}
+
+/// A single Kafka record
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KafkaRecord {
+    /// The topic the record was read from
+    pub topic: String,
+    /// The partition the record was read from
+    pub partition: i32,
+    /// The offset of the record in its partition
+    pub offset: i64,
+    /// When the record was created or appended, in milliseconds since the Unix epoch
+    pub timestamp: i64,
+    /// What `timestamp` refers to
+    pub timestamp_type: KafkaTimestampType,
+    /// The base64 encoded key, `None` for records produced without a key
+    #[serde(default)]
+    pub key: Option<String>,
+    /// The base64 encoded value, `None` for tombstones
+    #[serde(default)]
+    pub value: Option<String>,
+    /// The headers of the record, one single entry map per header in their original order
+    #[serde(default)]
+    pub headers: Vec<HashMap<String, Vec<u8>>>,
+}
+
+/// What the timestamp of a Kafka record refers to
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum KafkaTimestampType {
+    /// The producer set the timestamp when it created the record
+    CreateTime,
+    /// The broker set the timestamp when it appended the record to the log
+    LogAppendTime,
+    /// The record has no timestamp
+    NoTimestampType,
+}
+
+impl KafkaRecord {
+    /// Return the record's key, decoded from base64
+    pub fn decoded_key(&self) -> Result<Option<Vec<u8>>, DataError> {
+        decode(self.key.as_deref())
+    }
+
+    /// Return the record's value, decoded from base64
+    pub fn decoded_value(&self) -> Result<Option<Vec<u8>>, DataError> {
+        decode(self.value.as_deref())
+    }
+
+    /// Deserialize the record's value from JSON, returning `None` for tombstones
+    pub fn json_value<T>(&self) -> Result<Option<T>, DataError>
+    where
+        T: DeserializeOwned,
+    {
+        self.decoded_value()?
+            .map(|value| serde_json::from_slice(&value).map_err(DataError::Json))
+            .transpose()
+    }
+
+    /// Return `true` if the record marks its key as deleted
+    pub fn is_tombstone(&self) -> bool {
+        self.value.is_none()
+    }
+
+    /// Return the record's headers as name and value pairs, in their original order
+    pub fn decoded_headers(&self) -> Vec<(String, Vec<u8>)> {
+        self.headers
+            .iter()
+            .flat_map(|header| header.iter().map(|(name, value)| (name.clone(), value.clone())))
+            .collect()
+    }
+
+    /// Return the value of the first header named `name`
+    pub fn header(&self, name: &str) -> Option<&[u8]> {
+        self.headers
+            .iter()
+            .find_map(|header| header.get(name))
+            .map(Vec::as_slice)
+    }
+
+    /// Return when the record was created or appended
+    pub fn time(&self) -> SystemTime {
+        let millis = Duration::from_millis(self.timestamp.unsigned_abs());
+        if self.timestamp < 0 {
+            UNIX_EPOCH - millis
+        } else {
+            UNIX_EPOCH + millis
+        }
+    }
+}
+
+fn decode(encoded: Option<&str>) -> Result<Option<Vec<u8>>, DataError> {
+    encoded
+        .map(|encoded| base64::decode(encoded).map_err(DataError::Base64))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct Order {
+        order_id: String,
+        total: f64,
+    }
+
+    #[test]
+    fn round_trips_events() {
+        for fixture in &[
+            include_str!("../tests/data/kafka_msk_event.json"),
+            include_str!("../tests/data/kafka_self_managed_event.json"),
+        ] {
+            assert_round_trips::<KafkaEvent>(fixture);
+        }
+    }
+
+    #[test]
+    fn deserializes_multi_topic_msk_events() {
+        let event = assert_round_trips::<KafkaEvent>(include_str!("../tests/data/kafka_msk_event.json"));
+        assert_eq!(event.event_source, KafkaEventSource::Msk);
+        assert!(event.event_source_arn.is_some());
+        assert_eq!(event.records.len(), 2);
+        assert_eq!(event.iter_records().count(), 3);
+
+        let orders = &event.records["orders-0"];
+        assert_eq!(orders[0].decoded_key().unwrap(), Some(b"order-1".to_vec()));
+        assert_eq!(
+            orders[0].json_value::<Order>().unwrap(),
+            Some(Order {
+                order_id: "1".into(),
+                total: 12.5
+            })
+        );
+        assert_eq!(
+            orders[0].decoded_headers(),
+            vec![
+                ("content-type".to_string(), b"application/json".to_vec()),
+                ("trace-id".to_string(), b"abc".to_vec())
+            ]
+        );
+        assert_eq!(orders[0].header("trace-id"), Some(&b"abc"[..]));
+        assert_eq!(orders[0].time(), UNIX_EPOCH + Duration::from_millis(1_591_005_600_000));
+
+        let payment = &event.records["payments-1"][0];
+        assert_eq!(payment.timestamp_type, KafkaTimestampType::LogAppendTime);
+        assert_eq!(payment.decoded_key().unwrap(), None);
+    }
+
+    #[test]
+    fn deserializes_tombstones() {
+        let event = assert_round_trips::<KafkaEvent>(include_str!("../tests/data/kafka_msk_event.json"));
+        let tombstone = &event.records["orders-0"][1];
+        assert!(tombstone.is_tombstone());
+        assert_eq!(tombstone.decoded_value().unwrap(), None);
+        assert_eq!(tombstone.json_value::<Order>().unwrap(), None);
+        assert!(tombstone.decoded_headers().is_empty());
+
+        let record: KafkaRecord = serde_json::from_str(
+            r#"{"topic":"orders","partition":0,"offset":1,"timestamp":0,"timestampType":"NO_TIMESTAMP_TYPE"}"#,
+        )
+        .expect("failed to deserialize record");
+        assert!(record.is_tombstone());
+        assert_eq!(record.key, None);
+        assert!(record.headers.is_empty());
+    }
+
+    #[test]
+    fn deserializes_self_managed_events() {
+        let event = assert_round_trips::<KafkaEvent>(include_str!("../tests/data/kafka_self_managed_event.json"));
+        assert_eq!(event.event_source, KafkaEventSource::SelfManaged);
+        assert_eq!(event.event_source_arn, None);
+        let record = &event.records["clicks-0"][0];
+        assert_eq!(record.decoded_value().unwrap(), Some(b"home".to_vec()));
+        assert_eq!(record.header("source"), Some(&b"web"[..]));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let mut record = assert_round_trips::<KafkaEvent>(include_str!("../tests/data/kafka_self_managed_event.json"))
+            .records["clicks-0"][0]
+            .clone();
+        match record.json_value::<Order>() {
+            Err(DataError::Json(_)) => (),
+            other => panic!("expected a json error, got {:?}", other),
+        }
+        record.value = Some("not base64!".into());
+        match record.decoded_value() {
+            Err(DataError::Base64(_)) => (),
+            other => panic!("expected a base64 error, got {:?}", other),
+        }
+    }
+}
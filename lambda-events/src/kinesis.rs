@@ -0,0 +1,367 @@
+//! Kinesis stream event types
+//!
+//! Stream event source mappings invoke functions with a [`KinesisEvent`](struct.KinesisEvent.html)
+//! holding a batch of records from a single shard. Record data arrives base64 encoded, and
+//! [`KinesisRecord::decoded_data`](struct.KinesisRecord.html#method.decoded_data) and
+//! [`KinesisRecord::json_data`](struct.KinesisRecord.html#method.json_data) decode it.
+
+use crate::custom_serde::epoch_seconds;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::{borrow::Cow, error::Error, fmt, time::SystemTime};
+
+/// The first bytes of records aggregated by the Kinesis Producer Library
+const KPL_AGGREGATION_MAGIC: [u8; 4] = [0xF3, 0x89, 0x9A, 0xC2];
+
+/// A batch of Kinesis records
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct KinesisEvent {
+    /// The records of the batch
+    #[serde(rename = "Records")]
+    pub records: Vec<KinesisRecord>,
+}
+
+/// A single Kinesis record along with where it was read from
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KinesisRecord {
+    /// The record itself
+    pub kinesis: KinesisPayload,
+    /// Always `aws:kinesis`
+    pub event_source: String,
+    /// The version of the record format
+    pub event_version: String,
+    /// The shard id and sequence number of the record, like `shardId-000000000006:4959...`
+    #[serde(rename = "eventID")]
+    pub event_id: String,
+    /// Always `aws:kinesis:record`
+    pub event_name: String,
+    /// The ARN of the role the function reads the stream with
+    pub invoke_identity_arn: String,
+    /// The region of the stream
+    pub aws_region: String,
+    /// The ARN of the stream, or of the consumer for enhanced fan-out mappings
+    #[serde(rename = "eventSourceARN")]
+    pub event_source_arn: String,
+}
+
+/// The data and metadata of a Kinesis record
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KinesisPayload {
+    /// The version of the record format
+    pub kinesis_schema_version: String,
+    /// The key which determined the shard of the record
+    pub partition_key: String,
+    /// The position of the record within its shard
+    pub sequence_number: String,
+    /// The base64 encoded data of the record
+    pub data: String,
+    /// When the record was added to the stream
+    #[serde(with = "epoch_seconds")]
+    pub approximate_arrival_timestamp: SystemTime,
+    /// How the record is encrypted at rest, absent for streams which were never encrypted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_type: Option<KinesisEncryptionType>,
+}
+
+/// A batch of Kinesis records borrowing their strings from the event they were deserialized
+/// from
+///
+/// Batches of large records take up to megabytes of base64 encoded data, which
+/// [`KinesisEvent`](struct.KinesisEvent.html) copies into owned strings; this one only copies
+/// those which hold JSON escapes.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct KinesisEventRef<'a> {
+    /// The records of the batch
+    #[serde(rename = "Records", borrow)]
+    pub records: Vec<KinesisRecordRef<'a>>,
+}
+
+impl<'a> KinesisEventRef<'a> {
+    /// Deserialize a batch borrowing from `event`, the JSON body of an invocation
+    pub fn from_slice(event: &'a [u8]) -> serde_json::Result<Self> {
+        serde_json::from_slice(event)
+    }
+
+    /// Copy the batch into an owned one
+    pub fn into_owned(self) -> KinesisEvent {
+        KinesisEvent {
+            records: self.records.into_iter().map(KinesisRecordRef::into_owned).collect(),
+        }
+    }
+}
+
+/// A single Kinesis record borrowing its strings, see
+/// [`KinesisEventRef`](struct.KinesisEventRef.html)
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KinesisRecordRef<'a> {
+    /// The record itself
+    #[serde(borrow)]
+    pub kinesis: KinesisPayloadRef<'a>,
+    /// Always `aws:kinesis`
+    #[serde(borrow)]
+    pub event_source: Cow<'a, str>,
+    /// The version of the record format
+    #[serde(borrow)]
+    pub event_version: Cow<'a, str>,
+    /// The shard id and sequence number of the record, like `shardId-000000000006:4959...`
+    #[serde(rename = "eventID", borrow)]
+    pub event_id: Cow<'a, str>,
+    /// Always `aws:kinesis:record`
+    #[serde(borrow)]
+    pub event_name: Cow<'a, str>,
+    /// The ARN of the role the function reads the stream with
+    #[serde(borrow)]
+    pub invoke_identity_arn: Cow<'a, str>,
+    /// The region of the stream
+    #[serde(borrow)]
+    pub aws_region: Cow<'a, str>,
+    /// The ARN of the stream, or of the consumer for enhanced fan-out mappings
+    #[serde(rename = "eventSourceARN", borrow)]
+    pub event_source_arn: Cow<'a, str>,
+}
+
+/// The data and metadata of a Kinesis record, borrowing its strings
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KinesisPayloadRef<'a> {
+    /// The version of the record format
+    #[serde(borrow)]
+    pub kinesis_schema_version: Cow<'a, str>,
+    /// The key which determined the shard of the record
+    #[serde(borrow)]
+    pub partition_key: Cow<'a, str>,
+    /// The position of the record within its shard
+    #[serde(borrow)]
+    pub sequence_number: Cow<'a, str>,
+    /// The base64 encoded data of the record
+    #[serde(borrow)]
+    pub data: Cow<'a, str>,
+    /// When the record was added to the stream
+    #[serde(with = "epoch_seconds")]
+    pub approximate_arrival_timestamp: SystemTime,
+    /// How the record is encrypted at rest, absent for streams which were never encrypted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_type: Option<KinesisEncryptionType>,
+}
+
+impl KinesisRecordRef<'_> {
+    /// Return the record's data, decoded from base64
+    pub fn decoded_data(&self) -> Result<Vec<u8>, DataError> {
+        base64::decode(self.kinesis.data.as_bytes()).map_err(DataError::Base64)
+    }
+
+    /// Copy the record into an owned one
+    pub fn into_owned(self) -> KinesisRecord {
+        let kinesis = self.kinesis;
+        KinesisRecord {
+            kinesis: KinesisPayload {
+                kinesis_schema_version: kinesis.kinesis_schema_version.into_owned(),
+                partition_key: kinesis.partition_key.into_owned(),
+                sequence_number: kinesis.sequence_number.into_owned(),
+                data: kinesis.data.into_owned(),
+                approximate_arrival_timestamp: kinesis.approximate_arrival_timestamp,
+                encryption_type: kinesis.encryption_type,
+            },
+            event_source: self.event_source.into_owned(),
+            event_version: self.event_version.into_owned(),
+            event_id: self.event_id.into_owned(),
+            event_name: self.event_name.into_owned(),
+            invoke_identity_arn: self.invoke_identity_arn.into_owned(),
+            aws_region: self.aws_region.into_owned(),
+            event_source_arn: self.event_source_arn.into_owned(),
+        }
+    }
+}
+
+/// How a Kinesis record is encrypted at rest
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum KinesisEncryptionType {
+    /// The record is not encrypted
+    None,
+    /// The record is encrypted with a KMS key
+    Kms,
+}
+
+/// Returned when a record's data can't be decoded
+#[derive(Debug)]
+pub enum DataError {
+    /// Returned when a record's data is not valid base64
+    Base64(base64::DecodeError),
+    /// Returned when a record's data fails to deserialize from JSON
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataError::Base64(base64) => write!(f, "record data is not valid base64: {}", base64),
+            DataError::Json(json) => write!(f, "failed to parse record data from JSON: {}", json),
+        }
+    }
+}
+
+impl Error for DataError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DataError::Base64(base64) => Some(base64),
+            DataError::Json(json) => Some(json),
+        }
+    }
+}
+
+impl KinesisRecord {
+    /// Return the record's data, decoded from base64
+    pub fn decoded_data(&self) -> Result<Vec<u8>, DataError> {
+        base64::decode(&self.kinesis.data).map_err(DataError::Base64)
+    }
+
+    /// Deserialize the record's data from JSON
+    ///
+    /// ```rust
+    /// use lambda_events::kinesis::KinesisEvent;
+    /// use serde_derive::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Order {
+    ///     total: f64,
+    /// }
+    ///
+    /// fn revenue(event: &KinesisEvent) -> f64 {
+    ///     event
+    ///         .records
+    ///         .iter()
+    ///         .filter_map(|record| record.json_data::<Order>().ok())
+    ///         .map(|order| order.total)
+    ///         .sum()
+    /// }
+    /// ```
+    pub fn json_data<T>(&self) -> Result<T, DataError>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_slice(&self.decoded_data()?).map_err(DataError::Json)
+    }
+
+    /// Return `true` if the record holds several user records aggregated by the Kinesis
+    /// Producer Library
+    ///
+    /// Aggregated records start with the KPL's magic bytes followed by a protobuf message, and
+    /// have to be deaggregated before their user records can be processed.
+    pub fn is_aggregated(&self) -> bool {
+        self.decoded_data()
+            .map(|data| data.starts_with(&KPL_AGGREGATION_MAGIC))
+            .unwrap_or_default()
+    }
+
+    /// Return when the record was added to the stream
+    pub fn approximate_arrival_time(&self) -> SystemTime {
+        self.kinesis.approximate_arrival_timestamp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_derive::Deserialize;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct Order {
+        order_id: String,
+        total: f64,
+    }
+
+    fn event() -> KinesisEvent {
+        assert_round_trips(include_str!("../tests/data/kinesis_event.json"))
+    }
+
+    #[test]
+    fn deserializes_events() {
+        let event = event();
+        assert_eq!(event.records.len(), 2);
+        let record = &event.records[0];
+        assert_eq!(record.kinesis.partition_key, "1");
+        assert_eq!(
+            record.kinesis.sequence_number,
+            "49590338271490256608559692538361571095921575989136588898"
+        );
+        assert_eq!(
+            record.approximate_arrival_time(),
+            UNIX_EPOCH + Duration::from_millis(1_545_084_650_987)
+        );
+        assert_eq!(record.kinesis.encryption_type, None);
+        assert_eq!(
+            event.records[1].kinesis.encryption_type,
+            Some(KinesisEncryptionType::Kms)
+        );
+    }
+
+    #[test]
+    fn decodes_data() {
+        let event = event();
+        assert_eq!(
+            event.records[0].json_data::<Order>().expect("failed to parse data"),
+            Order {
+                order_id: "1".into(),
+                total: 12.5
+            }
+        );
+        let binary = &event.records[1];
+        assert_eq!(
+            binary.decoded_data().expect("failed to decode data"),
+            vec![0xFF, 0xFE, 0x00, 0x01, 0x80]
+        );
+        match binary.json_data::<Order>() {
+            Err(DataError::Json(_)) => (),
+            other => panic!("expected a JSON error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_base64() {
+        let mut record = event().records.remove(0);
+        record.kinesis.data = "not base64!".into();
+        match record.decoded_data() {
+            Err(DataError::Base64(_)) => (),
+            other => panic!("expected a base64 error, got {:?}", other),
+        }
+        assert!(!record.is_aggregated());
+    }
+
+    #[test]
+    fn detects_aggregated_records() {
+        let mut event = event();
+        assert!(event.records.iter().all(|record| !record.is_aggregated()));
+        let mut aggregated = KPL_AGGREGATION_MAGIC.to_vec();
+        aggregated.extend_from_slice(&[0x0A, 0x01, 0x31]);
+        event.records[0].kinesis.data = base64::encode(&aggregated);
+        assert!(event.records[0].is_aggregated());
+    }
+
+    #[test]
+    fn borrowed_events_match_owned_ones() {
+        let fixture = include_str!("../tests/data/kinesis_event.json");
+        let borrowed = KinesisEventRef::from_slice(fixture.as_bytes()).expect("failed to deserialize event");
+        assert!(matches!(borrowed.records[0].kinesis.data, Cow::Borrowed(_)));
+        assert_eq!(
+            borrowed.records[1].decoded_data().expect("failed to decode data"),
+            event().records[1].decoded_data().expect("failed to decode data")
+        );
+        assert_eq!(
+            serde_json::to_string(&borrowed).unwrap(),
+            serde_json::to_string(&event()).unwrap()
+        );
+        assert_eq!(borrowed.into_owned(), event());
+    }
+
+    #[test]
+    fn round_trips_events() {
+        assert_round_trips::<KinesisEvent>(include_str!("../tests/data/kinesis_event.json"));
+    }
+}
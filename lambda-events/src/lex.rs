@@ -0,0 +1,595 @@
+//! Amazon Lex V2 code hook event and response types
+//!
+//! Lex V2 bots invoke their code hook with a [`LexEvent`](struct.LexEvent.html) to validate a
+//! conversation's slots as they are filled, and to fulfill the intent once they all are. Code
+//! hooks answer with a [`LexResponse`](struct.LexResponse.html) telling Lex what to do next.
+//! Fields Lex sends which aren't modeled here are kept in `extra` maps, so that echoing the
+//! session state or intent of an event back doesn't drop them.
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// An invocation of a Lex V2 code hook
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LexEvent {
+    /// The version of the event format
+    pub message_version: String,
+    /// Whether the code hook is invoked to validate slots or to fulfill the intent
+    pub invocation_source: LexInvocationSource,
+    /// How the user talks to the bot, like `Text`, `Speech` or `DTMF`
+    pub input_mode: String,
+    /// The content type of the messages Lex expects back
+    pub response_content_type: String,
+    /// A unique identifier for the conversation
+    pub session_id: String,
+    /// What the user said, as Lex understood it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_transcript: Option<String>,
+    /// The bot
+    pub bot: LexBot,
+    /// The intents the user's input may match, the most likely one first
+    #[serde(default)]
+    pub interpretations: Vec<LexInterpretation>,
+    /// What Lex would do next without the code hook, for dialog code hooks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proposed_next_state: Option<LexProposedNextState>,
+    /// The attributes the client sent with the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_attributes: Option<HashMap<String, String>>,
+    /// The state of the conversation
+    pub session_state: LexSessionState,
+    /// The transcriptions of the user's speech, for spoken input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcriptions: Option<Vec<Value>>,
+    /// Fields of the event which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// Why a code hook is invoked
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LexInvocationSource {
+    /// Validate the slots filled so far
+    DialogCodeHook,
+    /// Fulfill the intent
+    FulfillmentCodeHook,
+}
+
+/// A Lex V2 bot
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LexBot {
+    /// The id of the bot
+    pub id: String,
+    /// The name of the bot
+    pub name: String,
+    /// The id of the alias the bot is invoked through
+    pub alias_id: String,
+    /// The name of the alias the bot is invoked through
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_name: Option<String>,
+    /// The locale the conversation is held in, like `en_US`
+    pub locale_id: String,
+    /// The version of the bot
+    pub version: String,
+    /// Fields of the bot which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// An intent the user's input may match
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LexInterpretation {
+    /// The intent
+    pub intent: LexIntent,
+    /// How confident Lex is that the input matches the intent, between 0 and 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nlu_confidence: Option<f64>,
+    /// The sentiment of the input, when sentiment analysis is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sentiment_response: Option<Value>,
+    /// What matched the intent, like `Lex` or `Bedrock`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpretation_source: Option<String>,
+    /// Fields of the interpretation which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// An intent and its slots
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LexIntent {
+    /// Whether the user confirmed the intent, for intents with a confirmation prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmation_state: Option<LexConfirmationState>,
+    /// The name of the intent
+    pub name: String,
+    /// The slots of the intent, `None` for slots which aren't filled yet
+    #[serde(default)]
+    pub slots: HashMap<String, Option<LexSlot>>,
+    /// How far along the intent is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<LexIntentState>,
+    /// The Kendra query results, for `AMAZON.KendraSearchIntent`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kendra_response: Option<Value>,
+    /// Fields of the intent which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl LexIntent {
+    /// Return the slot named `name`, if it is filled
+    pub fn slot(&self, name: &str) -> Option<&LexSlot> {
+        self.slots.get(name).and_then(Option::as_ref)
+    }
+}
+
+/// Whether the user confirmed an intent
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LexConfirmationState {
+    /// The user confirmed the intent
+    Confirmed,
+    /// The user denied the intent
+    Denied,
+    /// The user wasn't asked yet
+    None,
+}
+
+/// How far along an intent is
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LexIntentState {
+    /// The intent couldn't be fulfilled
+    Failed,
+    /// The intent was fulfilled
+    Fulfilled,
+    /// The intent is being fulfilled
+    FulfillmentInProgress,
+    /// The slots are being filled
+    InProgress,
+    /// All slots are filled and the intent can be fulfilled
+    ReadyForFulfillment,
+    /// The bot waits for the user
+    Waiting,
+}
+
+/// The value of a slot, either a single value or, for multi-valued slots, a list of values
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LexSlot {
+    /// Whether the slot holds a single value or a list of values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shape: Option<LexSlotShape>,
+    /// The value, for a single value, or all the values as the user said them, for a list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<LexSlotValue>,
+    /// The values, for a list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub values: Option<Vec<LexSlot>>,
+    /// Fields of the slot which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl LexSlot {
+    /// Build a single value slot with the value `interpreted_value`, for responses
+    pub fn new(interpreted_value: impl Into<String>) -> Self {
+        Self {
+            shape: Some(LexSlotShape::Scalar),
+            value: Some(LexSlotValue {
+                original_value: None,
+                interpreted_value: Some(interpreted_value.into()),
+                resolved_values: Vec::new(),
+                extra: HashMap::new(),
+            }),
+            values: None,
+            extra: HashMap::new(),
+        }
+    }
+
+    /// Return the value Lex resolved, for single value slots
+    pub fn interpreted_value(&self) -> Option<&str> {
+        self.value.as_ref()?.interpreted_value.as_deref()
+    }
+
+    /// Return the values Lex resolved, for single value slots as well as for lists
+    pub fn interpreted_values(&self) -> Vec<&str> {
+        match &self.values {
+            Some(values) => values.iter().filter_map(LexSlot::interpreted_value).collect(),
+            None => self.interpreted_value().into_iter().collect(),
+        }
+    }
+}
+
+/// Whether a slot holds a single value or a list of values
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LexSlotShape {
+    /// A single value
+    Scalar,
+    /// A list of values
+    List,
+}
+
+/// A value of a slot
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LexSlotValue {
+    /// The value as the user said it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_value: Option<String>,
+    /// The value Lex resolved, for slot types which restrict values the one it matched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpreted_value: Option<String>,
+    /// The values of the slot type the value may resolve to
+    #[serde(default)]
+    pub resolved_values: Vec<String>,
+    /// Fields of the value which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The state of a conversation
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LexSessionState {
+    /// The contexts active in the conversation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_contexts: Option<Vec<Value>>,
+    /// The attributes of the conversation, kept from turn to turn
+    #[serde(default)]
+    pub session_attributes: HashMap<String, String>,
+    /// Hints improving the recognition of slot values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime_hints: Option<Value>,
+    /// The next step of the conversation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dialog_action: Option<LexDialogAction>,
+    /// The current intent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intent: Option<LexIntent>,
+    /// The id of the request which started the conversation turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub originating_request_id: Option<String>,
+    /// Fields of the state which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// What Lex would do in the next turn of a conversation without the code hook
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LexProposedNextState {
+    /// The next step
+    pub dialog_action: LexDialogAction,
+    /// The intent of the next step
+    pub intent: LexIntent,
+    /// Fields of the state which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// A step of a conversation
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LexDialogAction {
+    /// The slot to ask the user for, for `ElicitSlot` steps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot_to_elicit: Option<String>,
+    /// How the user is expected to spell the slot value, like `SpellByLetter`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot_elicitation_style: Option<String>,
+    /// The kind of step
+    #[serde(rename = "type")]
+    pub kind: LexDialogActionType,
+    /// Fields of the step which aren't modeled above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The kind of a step of a conversation
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LexDialogActionType {
+    /// End the conversation
+    Close,
+    /// Ask the user to confirm the intent
+    ConfirmIntent,
+    /// Let Lex decide the next step
+    Delegate,
+    /// Ask the user what they want
+    ElicitIntent,
+    /// Ask the user for a slot value
+    ElicitSlot,
+}
+
+/// A message for the user
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LexMessage {
+    /// The format of the message
+    pub content_type: LexMessageContentType,
+    /// The message, for all but image response cards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// The card, for image response cards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_response_card: Option<Value>,
+}
+
+impl LexMessage {
+    /// Build a plain text message
+    pub fn plain_text(content: impl Into<String>) -> Self {
+        Self {
+            content_type: LexMessageContentType::PlainText,
+            content: Some(content.into()),
+            image_response_card: None,
+        }
+    }
+
+    /// Build an SSML message
+    pub fn ssml(content: impl Into<String>) -> Self {
+        Self {
+            content_type: LexMessageContentType::Ssml,
+            content: Some(content.into()),
+            image_response_card: None,
+        }
+    }
+}
+
+/// The format of a message
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LexMessageContentType {
+    /// Plain text
+    PlainText,
+    /// Speech Synthesis Markup Language
+    #[serde(rename = "SSML")]
+    Ssml,
+    /// A payload the client interprets
+    CustomPayload,
+    /// A card with an image and buttons
+    ImageResponseCard,
+}
+
+/// The answer of a Lex V2 code hook
+///
+/// Responses for all but `ElicitIntent` steps have to carry the current intent, which
+/// [`with_intent`](#method.with_intent) sets.
+///
+/// ```rust
+/// use lambda_events::lex::{LexEvent, LexIntentState, LexMessage, LexResponse};
+///
+/// fn handle(event: LexEvent) -> LexResponse {
+///     let intent = event.session_state.intent.clone().unwrap_or_default();
+///     let response = match intent.slot("Crust") {
+///         None => LexResponse::elicit_slot("Crust", LexMessage::plain_text("Thin or thick crust?")),
+///         Some(_) => LexResponse::close(LexIntentState::Fulfilled, LexMessage::plain_text("Your pizza is on its way")),
+///     };
+///     response
+///         .with_intent(intent)
+///         .with_session_attributes(event.session_state.session_attributes)
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LexResponse {
+    /// The state of the conversation after the turn
+    pub session_state: LexSessionState,
+    /// The messages for the user
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<LexMessage>,
+    /// The attributes sent back to the client
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_attributes: Option<HashMap<String, String>>,
+}
+
+impl LexResponse {
+    /// Ask the user for the value of the slot `slot`
+    pub fn elicit_slot(slot: impl Into<String>, message: LexMessage) -> Self {
+        let mut response = Self::new(LexDialogActionType::ElicitSlot, Some(message));
+        if let Some(action) = response.session_state.dialog_action.as_mut() {
+            action.slot_to_elicit = Some(slot.into());
+        }
+        response
+    }
+
+    /// End the conversation with the intent in the state `fulfillment_state`, usually
+    /// `Fulfilled` or `Failed`
+    pub fn close(fulfillment_state: LexIntentState, message: LexMessage) -> Self {
+        let mut response = Self::new(LexDialogActionType::Close, Some(message));
+        response.session_state.intent = Some(LexIntent {
+            state: Some(fulfillment_state),
+            ..LexIntent::default()
+        });
+        response
+    }
+
+    /// Let Lex decide the next step
+    pub fn delegate() -> Self {
+        Self::new(LexDialogActionType::Delegate, None)
+    }
+
+    /// Ask the user to confirm the intent
+    pub fn confirm_intent(message: LexMessage) -> Self {
+        Self::new(LexDialogActionType::ConfirmIntent, Some(message))
+    }
+
+    /// Ask the user what they want
+    pub fn elicit_intent(message: LexMessage) -> Self {
+        Self::new(LexDialogActionType::ElicitIntent, Some(message))
+    }
+
+    /// Set the current intent, keeping the state `close` ended the conversation with
+    pub fn with_intent(mut self, mut intent: LexIntent) -> Self {
+        if let Some(state) = self.session_state.intent.as_ref().and_then(|intent| intent.state) {
+            intent.state = Some(state);
+        }
+        self.session_state.intent = Some(intent);
+        self
+    }
+
+    /// Set the attributes of the conversation
+    pub fn with_session_attributes(mut self, session_attributes: HashMap<String, String>) -> Self {
+        self.session_state.session_attributes = session_attributes;
+        self
+    }
+
+    fn new(kind: LexDialogActionType, message: Option<LexMessage>) -> Self {
+        Self {
+            session_state: LexSessionState {
+                dialog_action: Some(LexDialogAction {
+                    slot_to_elicit: None,
+                    slot_elicitation_style: None,
+                    kind,
+                    extra: HashMap::new(),
+                }),
+                ..LexSessionState::default()
+            },
+            messages: message.into_iter().collect(),
+            request_attributes: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_json::json;
+
+    #[test]
+    fn round_trips_events() {
+        for fixture in &[
+            include_str!("../tests/data/lex_v2_dialog_hook_event.json"),
+            include_str!("../tests/data/lex_v2_fulfillment_event.json"),
+        ] {
+            assert_round_trips::<LexEvent>(fixture);
+        }
+    }
+
+    #[test]
+    fn round_trips_unknown_fields_at_every_level() {
+        let mut fixture: Value =
+            serde_json::from_str(include_str!("../tests/data/lex_v2_dialog_hook_event.json")).unwrap();
+        for pointer in &[
+            "",
+            "/bot",
+            "/interpretations/0",
+            "/interpretations/0/intent",
+            "/proposedNextState",
+            "/proposedNextState/dialogAction",
+            "/sessionState",
+            "/sessionState/intent",
+            "/sessionState/intent/slots/Size",
+            "/sessionState/intent/slots/Size/value",
+        ] {
+            fixture
+                .pointer_mut(pointer)
+                .and_then(Value::as_object_mut)
+                .expect("missing object")
+                .insert("futureField".into(), json!({ "at": pointer }));
+        }
+        let event: LexEvent = serde_json::from_value(fixture.clone()).expect("failed to deserialize event");
+        assert_eq!(
+            serde_json::to_value(&event).expect("failed to serialize event"),
+            fixture
+        );
+
+        let response = LexResponse::delegate().with_intent(event.session_state.intent.expect("missing intent"));
+        assert_eq!(
+            serde_json::to_value(&response).expect("failed to serialize response")["sessionState"]["intent"],
+            fixture["sessionState"]["intent"]
+        );
+    }
+
+    #[test]
+    fn deserializes_dialog_hook_invocations() {
+        let event = assert_round_trips::<LexEvent>(include_str!("../tests/data/lex_v2_dialog_hook_event.json"));
+        assert_eq!(event.invocation_source, LexInvocationSource::DialogCodeHook);
+        let intent = event.session_state.intent.as_ref().expect("missing intent");
+        assert_eq!(intent.state, Some(LexIntentState::InProgress));
+        assert_eq!(intent.slots["Crust"], None);
+        assert_eq!(intent.slot("Crust"), None);
+        assert_eq!(intent.slot("Size").and_then(LexSlot::interpreted_value), Some("large"));
+        assert_eq!(
+            intent.slot("Size").map(LexSlot::interpreted_values),
+            Some(vec!["large"])
+        );
+
+        let toppings = intent.slot("Toppings").expect("missing toppings");
+        assert_eq!(toppings.shape, Some(LexSlotShape::List));
+        assert_eq!(toppings.interpreted_values(), vec!["ham", "pineapple"]);
+        assert_eq!(
+            toppings
+                .value
+                .as_ref()
+                .and_then(|value| value.original_value.as_deref()),
+            Some("ham and pineapple")
+        );
+
+        let next = event.proposed_next_state.as_ref().expect("missing proposed next state");
+        assert_eq!(next.dialog_action.kind, LexDialogActionType::ElicitSlot);
+        assert_eq!(next.dialog_action.slot_to_elicit.as_deref(), Some("Crust"));
+        assert_eq!(event.interpretations[1].nlu_confidence, None);
+    }
+
+    #[test]
+    fn deserializes_fulfillment_invocations() {
+        let event = assert_round_trips::<LexEvent>(include_str!("../tests/data/lex_v2_fulfillment_event.json"));
+        assert_eq!(event.invocation_source, LexInvocationSource::FulfillmentCodeHook);
+        assert_eq!(event.proposed_next_state, None);
+        let intent = event.session_state.intent.as_ref().expect("missing intent");
+        assert_eq!(intent.confirmation_state, Some(LexConfirmationState::Confirmed));
+        assert_eq!(intent.state, Some(LexIntentState::ReadyForFulfillment));
+        assert_eq!(event.session_state.session_attributes["customerId"], "42");
+    }
+
+    #[test]
+    fn serializes_elicit_slot_responses() {
+        let event = assert_round_trips::<LexEvent>(include_str!("../tests/data/lex_v2_dialog_hook_event.json"));
+        let mut intent = LexIntent {
+            slots: HashMap::new(),
+            ..event.session_state.intent.clone().expect("missing intent")
+        };
+        intent.slots.insert("Size".into(), Some(LexSlot::new("large")));
+        let response = LexResponse::elicit_slot("Crust", LexMessage::plain_text("Thin or thick crust?"))
+            .with_intent(intent)
+            .with_session_attributes(event.session_state.session_attributes);
+        assert_eq!(
+            serde_json::to_value(&response).expect("failed to serialize response"),
+            json!({
+                "sessionState": {
+                    "sessionAttributes": { "customerId": "42" },
+                    "dialogAction": { "slotToElicit": "Crust", "type": "ElicitSlot" },
+                    "intent": {
+                        "confirmationState": "None",
+                        "name": "OrderPizza",
+                        "slots": {
+                            "Size": { "shape": "Scalar", "value": { "interpretedValue": "large", "resolvedValues": [] } }
+                        },
+                        "state": "InProgress"
+                    }
+                },
+                "messages": [{ "contentType": "PlainText", "content": "Thin or thick crust?" }]
+            })
+        );
+    }
+
+    #[test]
+    fn serializes_close_responses() {
+        let event = assert_round_trips::<LexEvent>(include_str!("../tests/data/lex_v2_fulfillment_event.json"));
+        let response = LexResponse::close(LexIntentState::Fulfilled, LexMessage::ssml("<speak>Enjoy</speak>"))
+            .with_intent(event.session_state.intent.expect("missing intent"));
+        let response = serde_json::to_value(&response).expect("failed to serialize response");
+        assert_eq!(response["sessionState"]["dialogAction"], json!({ "type": "Close" }));
+        assert_eq!(response["sessionState"]["intent"]["state"], "Fulfilled");
+        assert_eq!(response["sessionState"]["intent"]["name"], "OrderPizza");
+        assert_eq!(response["messages"][0]["contentType"], "SSML");
+        assert_eq!(
+            serde_json::to_value(LexResponse::delegate()).unwrap(),
+            json!({ "sessionState": { "sessionAttributes": {}, "dialogAction": { "type": "Delegate" } } })
+        );
+    }
+}
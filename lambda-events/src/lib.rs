@@ -0,0 +1,98 @@
+#![warn(missing_docs)]
+//! Strongly typed representations of the events AWS services send to Lambda functions,
+//! and of the responses some of those services expect back.
+//!
+//! Each event source lives in its own module. Types derive `serde` `Serialize` and `Deserialize`
+//! implementations matching the JSON documents Lambda delivers, so they can be used directly as
+//! the event and output types of a `lambda::Handler`.
+//!
+//! # Round-trip fidelity
+//!
+//! Cognito user pool triggers, CloudFront Lambda@Edge and Lex V2 code hooks answer with the
+//! event, or parts of it, they were sent. The types of the [`cognito`](cognito/index.html),
+//! [`cloudfront`](cloudfront/index.html) and [`lex`](lex/index.html) modules keep the fields
+//! they don't model in `extra` maps, so that serializing a deserialized event reproduces it,
+//! keys aside. Types of other event families drop fields they don't model.
+//!
+//! # Features
+//!
+//! - `gzip`, on by default, decompresses the payloads of CloudWatch Logs subscriptions.
+//! - `cloudformation` is opt-in, as it depends on `lambda` and `hyper`: it adds
+//!   `send_cfn_response` and `cfn_resource` to [`cloudformation`](cloudformation/index.html),
+//!   which upload the responses of custom resources. Its event and response types are available
+//!   without it.
+//! - `test-support` adds builders of events for the unit tests of handlers.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use lambda::{handler_fn, Context};
+//! use lambda_events::apigw::{
+//!     ApiGatewayCustomAuthorizerRequest, ApiGatewayCustomAuthorizerResponse, PolicyDocumentBuilder,
+//! };
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     lambda::run(handler_fn(authorize)).await?;
+//!     Ok(())
+//! }
+//!
+//! async fn authorize(
+//!     request: ApiGatewayCustomAuthorizerRequest,
+//!     _: Context,
+//! ) -> Result<ApiGatewayCustomAuthorizerResponse, Error> {
+//!     Ok(PolicyDocumentBuilder::new("user")
+//!         .allow_arn(request.method_arn())
+//!         .build())
+//! }
+//! ```
+
+pub mod alb;
+pub mod alexa;
+pub mod apigw;
+pub mod appsync;
+mod batch;
+pub mod cloudformation;
+pub mod cloudfront;
+pub mod cloudwatch_logs;
+pub mod codepipeline;
+pub mod cognito;
+pub mod config;
+mod custom_serde;
+pub mod destinations;
+pub mod dynamodb;
+pub mod event;
+pub mod eventbridge;
+pub mod firehose;
+pub mod iam;
+pub mod iot;
+pub mod kafka;
+pub mod kinesis;
+pub mod lex;
+pub mod s3;
+pub mod s3_batch;
+pub mod secrets_manager;
+pub mod ses;
+pub mod sns;
+pub mod sqs;
+pub mod streams;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod time;
+
+/// Deserializes the event of `fixture`, asserting that serializing it reproduces the fixture,
+/// keys aside
+#[cfg(test)]
+pub(crate) fn assert_round_trips<T>(fixture: &str) -> T
+where
+    T: serde::de::DeserializeOwned + serde::Serialize,
+{
+    let event: T = serde_json::from_str(fixture).expect("failed to deserialize event");
+    assert_eq!(
+        serde_json::to_value(&event).expect("failed to serialize event"),
+        serde_json::from_str::<serde_json::Value>(fixture).expect("failed to parse fixture")
+    );
+    event
+}
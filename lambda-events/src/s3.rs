@@ -0,0 +1,362 @@
+//! S3 event notification types
+//!
+//! S3 invokes functions subscribed to [bucket event notifications](https://docs.aws.amazon.com/AmazonS3/latest/dev/notification-content-structure.html)
+//! with an [`S3Event`](struct.S3Event.html) holding one record per object event.
+
+use crate::custom_serde::{nullable_default, rfc3339};
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, time::SystemTime};
+
+/// An S3 event notification
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct S3Event {
+    /// The object events being notified
+    #[serde(rename = "Records")]
+    pub records: Vec<S3EventRecord>,
+}
+
+/// A single object event
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct S3EventRecord {
+    /// The notification format version, i.e. `2.1`
+    pub event_version: String,
+    /// Always `aws:s3`
+    pub event_source: String,
+    /// The region of the bucket
+    pub aws_region: String,
+    /// The time S3 finished processing the request
+    #[serde(with = "rfc3339")]
+    pub event_time: SystemTime,
+    /// The event type, i.e. `ObjectCreated:Put` or `ObjectRemoved:Delete`
+    pub event_name: S3EventName,
+    /// The principal which caused the event
+    pub user_identity: S3UserIdentity,
+    /// Parameters of the request which caused the event
+    pub request_parameters: S3RequestParameters,
+    /// Identifiers of the request which caused the event, keyed by `x-amz-request-id` and `x-amz-id-2`
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub response_elements: HashMap<String, String>,
+    /// The bucket and object of the event
+    pub s3: S3Entity,
+}
+
+/// The type of an object event
+///
+/// Types S3 may add in the future deserialize into `Other`. Names also compare equal to their
+/// string form, like `ObjectCreated:Put`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+#[non_exhaustive]
+pub enum S3EventName {
+    /// `ObjectCreated:Put`: an object was uploaded with a `PUT` request
+    ObjectCreatedPut,
+    /// `ObjectCreated:Post`: an object was uploaded with a browser `POST` form
+    ObjectCreatedPost,
+    /// `ObjectCreated:Copy`: an object was copied
+    ObjectCreatedCopy,
+    /// `ObjectCreated:CompleteMultipartUpload`: a multipart upload completed
+    ObjectCreatedCompleteMultipartUpload,
+    /// `ObjectRemoved:Delete`: an object was deleted
+    ObjectRemovedDelete,
+    /// `ObjectRemoved:DeleteMarkerCreated`: a delete marker was created for a versioned object
+    ObjectRemovedDeleteMarkerCreated,
+    /// `ObjectRestore:Post`: the restoration of an archived object started
+    ObjectRestorePost,
+    /// `ObjectRestore:Completed`: the restoration of an archived object completed
+    ObjectRestoreCompleted,
+    /// `ReducedRedundancyLostObject`: S3 lost a reduced redundancy storage object
+    ReducedRedundancyLostObject,
+    /// `Replication:OperationFailedReplication`: an object failed to replicate
+    ReplicationOperationFailedReplication,
+    /// `Replication:OperationMissedThreshold`: an object took longer than the replication time control threshold to replicate
+    ReplicationOperationMissedThreshold,
+    /// `Replication:OperationReplicatedAfterThreshold`: an object replicated after the replication time control threshold
+    ReplicationOperationReplicatedAfterThreshold,
+    /// `Replication:OperationNotTracked`: an object is no longer tracked by replication metrics
+    ReplicationOperationNotTracked,
+    /// An event type this crate doesn't know about
+    Other(String),
+}
+
+impl S3EventName {
+    /// Return the name as S3 records it, like `ObjectCreated:Put`
+    pub fn as_str(&self) -> &str {
+        match self {
+            S3EventName::ObjectCreatedPut => "ObjectCreated:Put",
+            S3EventName::ObjectCreatedPost => "ObjectCreated:Post",
+            S3EventName::ObjectCreatedCopy => "ObjectCreated:Copy",
+            S3EventName::ObjectCreatedCompleteMultipartUpload => "ObjectCreated:CompleteMultipartUpload",
+            S3EventName::ObjectRemovedDelete => "ObjectRemoved:Delete",
+            S3EventName::ObjectRemovedDeleteMarkerCreated => "ObjectRemoved:DeleteMarkerCreated",
+            S3EventName::ObjectRestorePost => "ObjectRestore:Post",
+            S3EventName::ObjectRestoreCompleted => "ObjectRestore:Completed",
+            S3EventName::ReducedRedundancyLostObject => "ReducedRedundancyLostObject",
+            S3EventName::ReplicationOperationFailedReplication => "Replication:OperationFailedReplication",
+            S3EventName::ReplicationOperationMissedThreshold => "Replication:OperationMissedThreshold",
+            S3EventName::ReplicationOperationReplicatedAfterThreshold => {
+                "Replication:OperationReplicatedAfterThreshold"
+            }
+            S3EventName::ReplicationOperationNotTracked => "Replication:OperationNotTracked",
+            S3EventName::Other(name) => name,
+        }
+    }
+
+    /// Return whether an object was created, by any `ObjectCreated:*` event
+    pub fn is_created(&self) -> bool {
+        self.as_str().starts_with("ObjectCreated:")
+    }
+
+    /// Return whether an object was removed, by any `ObjectRemoved:*` event
+    pub fn is_removed(&self) -> bool {
+        self.as_str().starts_with("ObjectRemoved:")
+    }
+
+    /// Return whether the event concerns the restoration of an archived object, by any
+    /// `ObjectRestore:*` event
+    pub fn is_restore(&self) -> bool {
+        self.as_str().starts_with("ObjectRestore:")
+    }
+
+    /// Return whether the event concerns replication, by any `Replication:*` event
+    pub fn is_replication(&self) -> bool {
+        self.as_str().starts_with("Replication:")
+    }
+}
+
+impl From<String> for S3EventName {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "ObjectCreated:Put" => S3EventName::ObjectCreatedPut,
+            "ObjectCreated:Post" => S3EventName::ObjectCreatedPost,
+            "ObjectCreated:Copy" => S3EventName::ObjectCreatedCopy,
+            "ObjectCreated:CompleteMultipartUpload" => S3EventName::ObjectCreatedCompleteMultipartUpload,
+            "ObjectRemoved:Delete" => S3EventName::ObjectRemovedDelete,
+            "ObjectRemoved:DeleteMarkerCreated" => S3EventName::ObjectRemovedDeleteMarkerCreated,
+            "ObjectRestore:Post" => S3EventName::ObjectRestorePost,
+            "ObjectRestore:Completed" => S3EventName::ObjectRestoreCompleted,
+            "ReducedRedundancyLostObject" => S3EventName::ReducedRedundancyLostObject,
+            "Replication:OperationFailedReplication" => S3EventName::ReplicationOperationFailedReplication,
+            "Replication:OperationMissedThreshold" => S3EventName::ReplicationOperationMissedThreshold,
+            "Replication:OperationReplicatedAfterThreshold" => {
+                S3EventName::ReplicationOperationReplicatedAfterThreshold
+            }
+            "Replication:OperationNotTracked" => S3EventName::ReplicationOperationNotTracked,
+            _ => S3EventName::Other(name),
+        }
+    }
+}
+
+impl From<S3EventName> for String {
+    fn from(name: S3EventName) -> Self {
+        match name {
+            S3EventName::Other(name) => name,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for S3EventName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq<str> for S3EventName {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for S3EventName {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+/// The principal which caused an event or owns a bucket
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct S3UserIdentity {
+    /// The principal's Amazon customer id
+    pub principal_id: String,
+}
+
+/// Parameters of the request which caused an event
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct S3RequestParameters {
+    /// The IP address the request was sent from
+    #[serde(rename = "sourceIPAddress")]
+    pub source_ip_address: String,
+}
+
+/// The bucket and object of an event
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct S3Entity {
+    /// The notification schema version, i.e. `1.0`
+    #[serde(rename = "s3SchemaVersion")]
+    pub schema_version: String,
+    /// The id of the bucket notification configuration which matched the event
+    pub configuration_id: String,
+    /// The bucket of the object
+    pub bucket: S3Bucket,
+    /// The object the event concerns
+    pub object: S3Object,
+}
+
+/// The bucket of an event
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct S3Bucket {
+    /// The bucket name
+    pub name: String,
+    /// The bucket owner
+    pub owner_identity: S3UserIdentity,
+    /// The bucket ARN
+    pub arn: String,
+}
+
+/// The object of an event
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct S3Object {
+    /// The object key, URL encoded. See [`url_decoded_key`](#method.url_decoded_key)
+    pub key: String,
+    /// The object size in bytes, absent for delete events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// The object entity tag, absent for delete events
+    #[serde(default, rename = "eTag", skip_serializing_if = "Option::is_none")]
+    pub e_tag: Option<String>,
+    /// The object version, present for buckets with versioning enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_id: Option<String>,
+    /// A hexadecimal value ordering events for the same key, comparable only between events of one key
+    pub sequencer: String,
+}
+
+impl S3Object {
+    /// Return the object key as it appears in the bucket
+    ///
+    /// Keys are delivered form URL encoded, with spaces as `+` and other reserved or
+    /// non-ASCII bytes percent-encoded, so `my photos/café.jpg` arrives as
+    /// `my+photos/caf%C3%A9.jpg`. Bytes which are not valid UTF-8 once decoded are replaced.
+    pub fn url_decoded_key(&self) -> String {
+        url_decode(&self.key)
+    }
+}
+
+/// Decodes a form URL encoded object key, replacing bytes which are not valid UTF-8 once decoded
+pub(crate) fn url_decode(key: &str) -> String {
+    let bytes = key.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (_, Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', None) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, None) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn deserializes_object_created_put() {
+        let event = assert_round_trips::<S3Event>(include_str!("../tests/data/s3_object_created_put.json"));
+        let record = &event.records[0];
+        assert_eq!(record.event_name, "ObjectCreated:Put");
+        assert_eq!(record.aws_region, "us-east-2");
+        assert_eq!(record.event_time, UNIX_EPOCH + Duration::from_millis(1_567_539_447_192));
+        assert_eq!(record.request_parameters.source_ip_address, "205.255.255.255");
+        assert_eq!(
+            record.response_elements.get("x-amz-request-id").map(String::as_str),
+            Some("D82B88E5F771F645")
+        );
+        assert_eq!(record.s3.bucket.name, "lambda-artifacts-deafc19498e3f2df");
+        assert_eq!(record.s3.bucket.arn, "arn:aws:s3:::lambda-artifacts-deafc19498e3f2df");
+        assert_eq!(record.s3.bucket.owner_identity.principal_id, "A3I5XTEXAMAI3E");
+        let object = &record.s3.object;
+        assert_eq!(object.key, "b21b84d653bb07b05b1e6b33684dc11b");
+        assert_eq!(object.size, Some(1_305_107));
+        assert_eq!(object.e_tag.as_deref(), Some("b21b84d653bb07b05b1e6b33684dc11b"));
+        assert_eq!(object.version_id.as_deref(), Some("ZcXHJbd7mCBGXVOwM.ZdVHStRYbMpaeh"));
+        assert_eq!(object.sequencer, "0C0F6F405D6ED209E1");
+    }
+
+    #[test]
+    fn deserializes_object_removed_delete_without_size() {
+        let event = assert_round_trips::<S3Event>(include_str!("../tests/data/s3_object_removed_delete.json"));
+        let record = &event.records[0];
+        assert_eq!(record.event_name, "ObjectRemoved:Delete");
+        assert_eq!(record.s3.object.size, None);
+        assert_eq!(record.s3.object.e_tag, None);
+    }
+
+    #[test]
+    fn classifies_event_names() {
+        let name = S3EventName::from("ObjectCreated:CompleteMultipartUpload".to_string());
+        assert_eq!(name, S3EventName::ObjectCreatedCompleteMultipartUpload);
+        assert!(name.is_created());
+        assert!(!name.is_removed());
+        assert!(S3EventName::ObjectRemovedDeleteMarkerCreated.is_removed());
+        assert!(!S3EventName::ObjectRestoreCompleted.is_created());
+        assert!(S3EventName::ReplicationOperationFailedReplication.is_replication());
+    }
+
+    #[test]
+    fn deserializes_unknown_event_names_into_other() {
+        let mut fixture: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/data/s3_object_created_put.json")).unwrap();
+        fixture["Records"][0]["eventName"] = "ObjectCreated:Teleport".into();
+        let event: S3Event = serde_json::from_value(fixture.clone()).expect("failed to deserialize event");
+        let name = &event.records[0].event_name;
+        assert_eq!(name, &S3EventName::Other("ObjectCreated:Teleport".into()));
+        assert!(name.is_created());
+        assert_eq!(
+            serde_json::to_value(&event).expect("failed to serialize event"),
+            fixture
+        );
+    }
+
+    #[test]
+    fn decodes_keys_with_spaces_and_unicode() {
+        let event = assert_round_trips::<S3Event>(include_str!("../tests/data/s3_object_created_encoded_key.json"));
+        let object = &event.records[0].s3.object;
+        assert_eq!(object.url_decoded_key(), "holiday photos/café ☕ 100% a+b.jpg");
+        assert_eq!(object.size, Some(0));
+        assert_eq!(object.version_id, None);
+    }
+
+    #[test]
+    fn round_trips_events() {
+        for fixture in &[
+            include_str!("../tests/data/s3_object_created_put.json"),
+            include_str!("../tests/data/s3_object_removed_delete.json"),
+            include_str!("../tests/data/s3_object_created_encoded_key.json"),
+        ] {
+            assert_round_trips::<S3Event>(fixture);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! S3 Batch Operations event and result types
+//!
+//! S3 Batch Operations jobs invoking a function pass it an
+//! [`S3BatchJobEvent`](struct.S3BatchJobEvent.html) with one task per object, and expect an
+//! [`S3BatchJobResponse`](struct.S3BatchJobResponse.html) with one result per task, most easily
+//! started with [`for_event`](struct.S3BatchJobResponse.html#method.for_event).
+
+use crate::s3::url_decode;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// An invocation of a function by an S3 Batch Operations job
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct S3BatchJobEvent {
+    /// The version of the event format, `1.0` or `2.0`
+    pub invocation_schema_version: String,
+    /// A unique identifier for the invocation, to echo back in the response
+    pub invocation_id: String,
+    /// The job
+    pub job: S3BatchJob,
+    /// The tasks to perform, one per object
+    pub tasks: Vec<S3BatchJobTask>,
+}
+
+/// An S3 Batch Operations job
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct S3BatchJob {
+    /// The id of the job
+    pub id: String,
+    /// The arguments the job was created with, for schema version 2.0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_arguments: Option<HashMap<String, String>>,
+}
+
+/// The task of an S3 Batch Operations job for one object
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct S3BatchJobTask {
+    /// The id of the task, to echo back in its result
+    pub task_id: String,
+    /// The object key, URL encoded. See [`url_decoded_key`](#method.url_decoded_key)
+    pub s3_key: String,
+    /// The object version, for jobs whose manifest lists versions
+    pub s3_version_id: Option<String>,
+    /// The ARN of the bucket, for schema version 1.0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s3_bucket_arn: Option<String>,
+    /// The name of the bucket, for schema version 2.0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s3_bucket: Option<String>,
+}
+
+impl S3BatchJobTask {
+    /// Return the object key as it appears in the bucket
+    ///
+    /// Keys are delivered URL encoded, like the keys of
+    /// [`S3Object`](../s3/struct.S3Object.html#method.url_decoded_key)s.
+    pub fn url_decoded_key(&self) -> String {
+        url_decode(&self.s3_key)
+    }
+
+    /// Return the name of the bucket, for either schema version
+    pub fn bucket_name(&self) -> Option<&str> {
+        self.s3_bucket.as_deref().or_else(|| {
+            self.s3_bucket_arn
+                .as_deref()
+                .map(|arn| arn.trim_start_matches("arn:aws:s3:::"))
+        })
+    }
+}
+
+/// The response to an invocation by an S3 Batch Operations job
+///
+/// ```rust
+/// use lambda_events::s3_batch::{S3BatchJobEvent, S3BatchJobResponse, S3BatchJobResult};
+///
+/// fn handle(event: S3BatchJobEvent) -> S3BatchJobResponse {
+///     let results = event
+///         .tasks
+///         .iter()
+///         .map(|task| S3BatchJobResult::succeeded(task, format!("copied {}", task.url_decoded_key())))
+///         .collect();
+///     S3BatchJobResponse::for_event(&event).with_results(results)
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct S3BatchJobResponse {
+    /// The version of the event format, as the event had it
+    pub invocation_schema_version: String,
+    /// The outcome of tasks whose object doesn't exist
+    pub treat_missing_keys_as: S3BatchJobResultCode,
+    /// The id of the invocation, as the event had it
+    pub invocation_id: String,
+    /// The results, one per task
+    pub results: Vec<S3BatchJobResult>,
+}
+
+impl S3BatchJobResponse {
+    /// Start a response to `event`, without results and with missing objects failing permanently
+    pub fn for_event(event: &S3BatchJobEvent) -> Self {
+        Self {
+            invocation_schema_version: event.invocation_schema_version.clone(),
+            treat_missing_keys_as: S3BatchJobResultCode::PermanentFailure,
+            invocation_id: event.invocation_id.clone(),
+            results: Vec::new(),
+        }
+    }
+
+    /// Set the results
+    pub fn with_results(self, results: Vec<S3BatchJobResult>) -> Self {
+        Self { results, ..self }
+    }
+
+    /// Set the outcome of tasks whose object doesn't exist
+    pub fn with_treat_missing_keys_as(self, treat_missing_keys_as: S3BatchJobResultCode) -> Self {
+        Self {
+            treat_missing_keys_as,
+            ..self
+        }
+    }
+}
+
+/// The result of a task of an S3 Batch Operations job
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct S3BatchJobResult {
+    /// The id of the task
+    pub task_id: String,
+    /// The outcome of the task
+    pub result_code: S3BatchJobResultCode,
+    /// A message S3 writes to the job's completion report
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result_string: Option<String>,
+}
+
+impl S3BatchJobResult {
+    /// Report that `task` succeeded
+    pub fn succeeded<M>(task: &S3BatchJobTask, message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        Self::new(task, S3BatchJobResultCode::Succeeded, message.into())
+    }
+
+    /// Report that `task` failed and should be retried
+    pub fn temporary_failure<M>(task: &S3BatchJobTask, message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        Self::new(task, S3BatchJobResultCode::TemporaryFailure, message.into())
+    }
+
+    /// Report that `task` failed for good
+    pub fn permanent_failure<M>(task: &S3BatchJobTask, message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        Self::new(task, S3BatchJobResultCode::PermanentFailure, message.into())
+    }
+
+    fn new(task: &S3BatchJobTask, result_code: S3BatchJobResultCode, message: String) -> Self {
+        Self {
+            task_id: task.task_id.clone(),
+            result_code,
+            result_string: Some(message),
+        }
+    }
+}
+
+/// The outcome of a task of an S3 Batch Operations job
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum S3BatchJobResultCode {
+    /// The task succeeded
+    Succeeded,
+    /// The task failed and S3 retries it
+    TemporaryFailure,
+    /// The task failed and S3 doesn't retry it
+    PermanentFailure,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_json::json;
+
+    #[test]
+    fn round_trips_events() {
+        for fixture in &[
+            include_str!("../tests/data/s3_batch_job_event.json"),
+            include_str!("../tests/data/s3_batch_job_v2_event.json"),
+        ] {
+            assert_round_trips::<S3BatchJobEvent>(fixture);
+        }
+    }
+
+    #[test]
+    fn deserializes_both_schema_versions() {
+        let v1 = assert_round_trips::<S3BatchJobEvent>(include_str!("../tests/data/s3_batch_job_event.json"));
+        let v2 = assert_round_trips::<S3BatchJobEvent>(include_str!("../tests/data/s3_batch_job_v2_event.json"));
+        assert_eq!(v1.job.user_arguments, None);
+        assert_eq!(
+            v2.job
+                .user_arguments
+                .as_ref()
+                .map(|arguments| arguments["width"].as_str()),
+            Some("200")
+        );
+        for event in &[v1, v2] {
+            let task = &event.tasks[0];
+            assert_eq!(task.url_decoded_key(), "customer images/café.jpg");
+            assert_eq!(task.bucket_name(), Some("orders-assets"));
+        }
+    }
+
+    #[test]
+    fn responses_echo_the_invocation() {
+        let event = assert_round_trips::<S3BatchJobEvent>(include_str!("../tests/data/s3_batch_job_v2_event.json"));
+        let task = &event.tasks[0];
+        let response = S3BatchJobResponse::for_event(&event)
+            .with_treat_missing_keys_as(S3BatchJobResultCode::Succeeded)
+            .with_results(vec![
+                S3BatchJobResult::succeeded(task, "done"),
+                S3BatchJobResult::temporary_failure(task, "throttled"),
+                S3BatchJobResult::permanent_failure(task, "access denied"),
+            ]);
+        assert_eq!(
+            serde_json::to_value(&response).expect("failed to serialize response"),
+            json!({
+                "invocationSchemaVersion": "2.0",
+                "treatMissingKeysAs": "Succeeded",
+                "invocationId": "YXNkbGZqYWRmaiBhc2RmdW9hZHNmZGpmaGFzbGtkaGZza2RmaAo",
+                "results": [
+                    { "taskId": "dGFza2lkZ29lc2hlcmUK", "resultCode": "Succeeded", "resultString": "done" },
+                    { "taskId": "dGFza2lkZ29lc2hlcmUK", "resultCode": "TemporaryFailure", "resultString": "throttled" },
+                    { "taskId": "dGFza2lkZ29lc2hlcmUK", "resultCode": "PermanentFailure", "resultString": "access denied" }
+                ]
+            })
+        );
+    }
+}
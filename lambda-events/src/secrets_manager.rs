@@ -0,0 +1,208 @@
+//! Secrets Manager rotation event types
+//!
+//! Secrets Manager rotates a secret by invoking its rotation function with a
+//! [`SecretsManagerRotationEvent`](struct.SecretsManagerRotationEvent.html) once per step of
+//! the rotation. [`dispatch`](struct.SecretsManagerRotationEvent.html#method.dispatch) routes
+//! each event to the code handling its step.
+
+use serde_derive::{Deserialize, Serialize};
+use std::{error::Error, fmt, future::Future};
+
+/// A step of a secret's rotation
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct SecretsManagerRotationEvent {
+    /// The step to perform
+    pub step: RotationStep,
+    /// The ARN of the secret
+    pub secret_id: String,
+    /// The version id of the new version of the secret
+    pub client_request_token: String,
+}
+
+/// A step of a secret's rotation
+///
+/// Steps Secrets Manager may add in the future deserialize into `Other`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+pub enum RotationStep {
+    /// `createSecret`: generate a new secret value and store it under the `AWSPENDING` label
+    CreateSecret,
+    /// `setSecret`: set the pending secret value in the service the secret is for
+    SetSecret,
+    /// `testSecret`: check that the pending secret value works with the service
+    TestSecret,
+    /// `finishSecret`: move the `AWSCURRENT` label to the pending version
+    FinishSecret,
+    /// A step this crate doesn't know about
+    Other(String),
+}
+
+impl RotationStep {
+    /// Return the step as Secrets Manager names it, like `createSecret`
+    pub fn as_str(&self) -> &str {
+        match self {
+            RotationStep::CreateSecret => "createSecret",
+            RotationStep::SetSecret => "setSecret",
+            RotationStep::TestSecret => "testSecret",
+            RotationStep::FinishSecret => "finishSecret",
+            RotationStep::Other(step) => step,
+        }
+    }
+}
+
+impl From<String> for RotationStep {
+    fn from(step: String) -> Self {
+        match step.as_str() {
+            "createSecret" => RotationStep::CreateSecret,
+            "setSecret" => RotationStep::SetSecret,
+            "testSecret" => RotationStep::TestSecret,
+            "finishSecret" => RotationStep::FinishSecret,
+            _ => RotationStep::Other(step),
+        }
+    }
+}
+
+impl From<RotationStep> for String {
+    fn from(step: RotationStep) -> Self {
+        match step {
+            RotationStep::Other(step) => step,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RotationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl SecretsManagerRotationEvent {
+    /// Route the event to the closure handling its step
+    ///
+    /// Events for a step this crate doesn't know about fail with an
+    /// [`UnknownRotationStep`](struct.UnknownRotationStep.html) error, so that the rotation
+    /// is retried rather than silently skipped.
+    ///
+    /// ```rust
+    /// use lambda_events::secrets_manager::SecretsManagerRotationEvent;
+    ///
+    /// type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+    ///
+    /// async fn rotate(event: SecretsManagerRotationEvent) -> Result<(), Error> {
+    ///     event
+    ///         .dispatch(
+    ///             |event| async move { create_secret(&event.secret_id).await },
+    ///             |_| async { Ok(()) },
+    ///             |_| async { Ok(()) },
+    ///             |_| async { Ok(()) },
+    ///         )
+    ///         .await
+    /// }
+    ///
+    /// async fn create_secret(secret_id: &str) -> Result<(), Error> {
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn dispatch<R, E, C, CF, S, SF, T, TF, F, FF>(
+        self,
+        create_secret: C,
+        set_secret: S,
+        test_secret: T,
+        finish_secret: F,
+    ) -> Result<R, E>
+    where
+        E: From<UnknownRotationStep>,
+        C: FnOnce(Self) -> CF,
+        CF: Future<Output = Result<R, E>>,
+        S: FnOnce(Self) -> SF,
+        SF: Future<Output = Result<R, E>>,
+        T: FnOnce(Self) -> TF,
+        TF: Future<Output = Result<R, E>>,
+        F: FnOnce(Self) -> FF,
+        FF: Future<Output = Result<R, E>>,
+    {
+        match self.step {
+            RotationStep::CreateSecret => create_secret(self).await,
+            RotationStep::SetSecret => set_secret(self).await,
+            RotationStep::TestSecret => test_secret(self).await,
+            RotationStep::FinishSecret => finish_secret(self).await,
+            RotationStep::Other(step) => Err(UnknownRotationStep(step).into()),
+        }
+    }
+}
+
+/// Returned when dispatching an event for a step this crate doesn't know about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownRotationStep(pub String);
+
+impl fmt::Display for UnknownRotationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown rotation step {}", self.0)
+    }
+}
+
+impl Error for UnknownRotationStep {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+
+    type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+    fn event(step: &str) -> SecretsManagerRotationEvent {
+        let fixture = include_str!("../tests/data/secrets_manager_rotation_event.json").replace("createSecret", step);
+        serde_json::from_str(&fixture).expect("failed to deserialize event")
+    }
+
+    async fn dispatch(event: SecretsManagerRotationEvent) -> Result<&'static str, Error> {
+        event
+            .dispatch(
+                |_| async { Ok("create") },
+                |_| async { Ok("set") },
+                |_| async { Ok("test") },
+                |event| async move {
+                    assert!(event.secret_id.ends_with("orders/db-a1b2c3"));
+                    Ok("finish")
+                },
+            )
+            .await
+    }
+
+    #[test]
+    fn round_trips_events() {
+        let event: SecretsManagerRotationEvent =
+            assert_round_trips(include_str!("../tests/data/secrets_manager_rotation_event.json"));
+        assert_eq!(event.client_request_token, "9b4e3d7c-2f1a-4c8b-8e6d-5a3f2b1c0d9e");
+    }
+
+    #[test]
+    fn deserializes_steps() {
+        for (name, step) in &[
+            ("createSecret", RotationStep::CreateSecret),
+            ("setSecret", RotationStep::SetSecret),
+            ("testSecret", RotationStep::TestSecret),
+            ("finishSecret", RotationStep::FinishSecret),
+            ("revokeSecret", RotationStep::Other("revokeSecret".into())),
+        ] {
+            let event = event(name);
+            assert_eq!(&event.step, step);
+            assert_eq!(event.step.as_str(), *name);
+            assert_eq!(serde_json::to_value(&event).unwrap()["Step"], *name);
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatches_steps() {
+        assert_eq!(dispatch(event("createSecret")).await.unwrap(), "create");
+        assert_eq!(dispatch(event("setSecret")).await.unwrap(), "set");
+        assert_eq!(dispatch(event("testSecret")).await.unwrap(), "test");
+        assert_eq!(dispatch(event("finishSecret")).await.unwrap(), "finish");
+        assert_eq!(
+            dispatch(event("revokeSecret")).await.unwrap_err().to_string(),
+            "unknown rotation step revokeSecret"
+        );
+    }
+}
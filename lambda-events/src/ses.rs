@@ -0,0 +1,313 @@
+//! SES inbound email event types
+//!
+//! Receipt rules with a Lambda action invoke functions with an [`SesEvent`](struct.SesEvent.html)
+//! describing the received mail and the verdicts of SES's checks. Actions invoked with the
+//! `RequestResponse` invocation type expect an [`SesDisposition`](struct.SesDisposition.html)
+//! back, deciding whether the remaining actions and rules still apply to the mail.
+
+use crate::custom_serde::{parse_rfc2822, rfc3339};
+use serde_derive::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// A mail received by SES
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SesEvent {
+    /// The records of the invocation, of which there is always exactly one
+    #[serde(rename = "Records")]
+    pub records: Vec<SesRecord>,
+}
+
+/// A single record of an SES event
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SesRecord {
+    /// Always `aws:ses`
+    pub event_source: String,
+    /// The version of the record format
+    pub event_version: String,
+    /// The mail and its receipt
+    pub ses: SesMessage,
+}
+
+/// A received mail along with its receipt
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SesMessage {
+    /// The mail
+    pub mail: SesMail,
+    /// The results of SES's checks and the action which invoked the function
+    pub receipt: SesReceipt,
+}
+
+/// The metadata and headers of a received mail
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SesMail {
+    /// When the mail was received
+    #[serde(with = "rfc3339")]
+    pub timestamp: SystemTime,
+    /// The envelope sender of the mail
+    pub source: String,
+    /// The id SES assigned the mail
+    pub message_id: String,
+    /// The envelope recipients of the mail
+    pub destination: Vec<String>,
+    /// Whether the headers were cut short because they exceeded 10 KB
+    pub headers_truncated: bool,
+    /// The headers of the mail in their original order
+    pub headers: Vec<SesHeader>,
+    /// The most common headers of the mail, parsed
+    pub common_headers: SesCommonHeaders,
+}
+
+impl SesMail {
+    /// Return the value of the first header named `name`, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .map(|header| header.value.as_str())
+    }
+}
+
+/// A mail header
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SesHeader {
+    /// The header's name, as it was written
+    pub name: String,
+    /// The header's value
+    pub value: String,
+}
+
+/// The most common headers of a mail, parsed
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SesCommonHeaders {
+    /// The address bounces are sent to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub return_path: Option<String>,
+    /// The `From` addresses
+    #[serde(default)]
+    pub from: Vec<String>,
+    /// The `Sender` address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
+    /// The `Reply-To` addresses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<Vec<String>>,
+    /// The `Date` header, in RFC 2822 format
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// The `To` addresses
+    #[serde(default)]
+    pub to: Vec<String>,
+    /// The `Cc` addresses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cc: Option<Vec<String>>,
+    /// The `Bcc` addresses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bcc: Option<Vec<String>>,
+    /// The `Message-ID` header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    /// The `Subject` header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+}
+
+impl SesCommonHeaders {
+    /// Return when the sender says the mail was written, parsed from the `Date` header
+    pub fn date_time(&self) -> Option<SystemTime> {
+        self.date.as_deref().and_then(parse_rfc2822)
+    }
+}
+
+/// The results of SES's checks of a received mail
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SesReceipt {
+    /// When the action was triggered
+    #[serde(with = "rfc3339")]
+    pub timestamp: SystemTime,
+    /// How long SES took processing the mail, in milliseconds
+    pub processing_time_millis: u64,
+    /// The recipients the receipt rule matched
+    pub recipients: Vec<String>,
+    /// Whether the mail is spam
+    pub spam_verdict: SesVerdict,
+    /// Whether the mail contains a virus
+    pub virus_verdict: SesVerdict,
+    /// Whether the mail passed the Sender Policy Framework check
+    pub spf_verdict: SesVerdict,
+    /// Whether the mail passed the DomainKeys Identified Mail check
+    pub dkim_verdict: SesVerdict,
+    /// Whether the mail passed the DMARC check
+    pub dmarc_verdict: SesVerdict,
+    /// The DMARC policy of the sending domain, when the DMARC check failed, like `reject`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dmarc_policy: Option<String>,
+    /// The action which invoked the function
+    pub action: SesReceiptAction,
+}
+
+/// The verdict of one of SES's checks
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SesVerdict {
+    /// The outcome of the check
+    pub status: SesVerdictStatus,
+}
+
+/// The outcome of one of SES's checks
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SesVerdictStatus {
+    /// The mail passed the check
+    Pass,
+    /// The mail failed the check
+    Fail,
+    /// The check was inconclusive
+    Gray,
+    /// The check couldn't be performed
+    ProcessingFailed,
+    /// The check is disabled for the receipt rule
+    Disabled,
+}
+
+impl SesReceipt {
+    /// Return `true` if the spam and virus checks both passed
+    pub fn is_clean(&self) -> bool {
+        self.spam_verdict.status == SesVerdictStatus::Pass && self.virus_verdict.status == SesVerdictStatus::Pass
+    }
+}
+
+/// The receipt rule action which invoked the function
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SesReceiptAction {
+    /// The kind of action, always `Lambda`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The ARN of the invoked function
+    pub function_arn: String,
+    /// `RequestResponse` when SES waits for an `SesDisposition`, `Event` otherwise
+    pub invocation_type: String,
+}
+
+/// The answer to a `RequestResponse` invocation, deciding how SES continues processing the mail
+///
+/// ```rust
+/// use lambda_events::ses::{SesDisposition, SesEvent};
+///
+/// fn filter(event: SesEvent) -> SesDisposition {
+///     if event.records.iter().all(|record| record.ses.receipt.is_clean()) {
+///         SesDisposition::continue_processing()
+///     } else {
+///         SesDisposition::stop_rule_set()
+///     }
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SesDisposition {
+    /// How SES continues processing the mail
+    pub disposition: Disposition,
+}
+
+/// How SES continues processing a mail after a `RequestResponse` invocation
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Disposition {
+    /// Carry on with the remaining actions and rules
+    Continue,
+    /// Skip the remaining actions of the current rule
+    StopRule,
+    /// Skip the remaining actions and rules
+    StopRuleSet,
+}
+
+impl SesDisposition {
+    /// Carry on with the remaining actions and rules
+    pub const fn continue_processing() -> Self {
+        Self {
+            disposition: Disposition::Continue,
+        }
+    }
+
+    /// Skip the remaining actions of the current rule
+    pub const fn stop_rule() -> Self {
+        Self {
+            disposition: Disposition::StopRule,
+        }
+    }
+
+    /// Skip the remaining actions and rules
+    pub const fn stop_rule_set() -> Self {
+        Self {
+            disposition: Disposition::StopRuleSet,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn deserializes_mail_for_multiple_recipients() {
+        let event = assert_round_trips::<SesEvent>(include_str!("../tests/data/ses_event.json"));
+        let message = &event.records[0].ses;
+        assert_eq!(
+            message.mail.timestamp,
+            UNIX_EPOCH + Duration::from_millis(1_565_040_602_028)
+        );
+        assert_eq!(message.mail.destination.len(), 2);
+        assert_eq!(message.receipt.recipients, message.mail.destination);
+        assert_eq!(message.mail.header("subject"), Some("Quarterly report"));
+        assert_eq!(message.mail.header("X-Missing"), None);
+        assert_eq!(
+            message.mail.common_headers.date_time(),
+            Some(UNIX_EPOCH + Duration::from_secs(1_565_040_597))
+        );
+        assert_eq!(message.mail.common_headers.cc, None);
+        assert!(message.receipt.is_clean());
+        assert_eq!(message.receipt.dmarc_verdict.status, SesVerdictStatus::Gray);
+        assert_eq!(message.receipt.action.invocation_type, "Event");
+    }
+
+    #[test]
+    fn deserializes_failing_verdicts() {
+        let event = assert_round_trips::<SesEvent>(include_str!("../tests/data/ses_spam_event.json"));
+        let receipt = &event.records[0].ses.receipt;
+        assert_eq!(receipt.spam_verdict.status, SesVerdictStatus::Fail);
+        assert_eq!(receipt.virus_verdict.status, SesVerdictStatus::Fail);
+        assert_eq!(receipt.spf_verdict.status, SesVerdictStatus::ProcessingFailed);
+        assert_eq!(receipt.dmarc_policy.as_deref(), Some("reject"));
+        assert!(!receipt.is_clean());
+        assert!(event.records[0].ses.mail.headers_truncated);
+        assert_eq!(event.records[0].ses.mail.common_headers.date_time(), None);
+    }
+
+    #[test]
+    fn round_trips_events() {
+        for fixture in &[
+            include_str!("../tests/data/ses_event.json"),
+            include_str!("../tests/data/ses_spam_event.json"),
+        ] {
+            assert_round_trips::<SesEvent>(fixture);
+        }
+    }
+
+    #[test]
+    fn serializes_dispositions() {
+        for (disposition, expected) in &[
+            (SesDisposition::continue_processing(), r#"{"disposition":"CONTINUE"}"#),
+            (SesDisposition::stop_rule(), r#"{"disposition":"STOP_RULE"}"#),
+            (SesDisposition::stop_rule_set(), r#"{"disposition":"STOP_RULE_SET"}"#),
+        ] {
+            assert_eq!(
+                serde_json::to_string(disposition).expect("failed to serialize disposition"),
+                *expected
+            );
+        }
+    }
+}
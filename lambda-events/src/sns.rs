@@ -0,0 +1,325 @@
+//! SNS event types
+//!
+//! Topics invoke subscribed functions with an [`SnsEvent`](struct.SnsEvent.html) holding a
+//! single notification. Topics fanning out to SQS queues instead deliver the same notification
+//! as a JSON document in the message body, which
+//! [`SqsMessage::sns_envelope`](../sqs/struct.SqsMessage.html#method.sns_envelope) unwraps.
+
+use crate::custom_serde::{nullable_default, rfc3339};
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::HashMap, convert::TryFrom, fmt, str::FromStr, time::SystemTime};
+
+/// An SNS notification delivered to a subscribed function
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SnsEvent {
+    /// The notifications of the invocation, of which there is always exactly one
+    #[serde(rename = "Records")]
+    pub records: Vec<SnsRecord>,
+}
+
+/// A single record of an SNS event
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnsRecord {
+    /// Always `aws:sns`
+    pub event_source: String,
+    /// The version of the record format
+    pub event_version: String,
+    /// The ARN of the subscription which delivered the notification
+    pub event_subscription_arn: String,
+    /// The notification
+    pub sns: SnsMessage,
+}
+
+/// An SNS notification
+///
+/// Notifications delivered to functions and notifications wrapped in SQS message bodies share
+/// this shape, apart from the capitalization of the `SigningCertUrl` and `UnsubscribeUrl`
+/// fields, both of which are accepted.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnsMessage {
+    /// The kind of message, `Notification` for published messages
+    #[serde(rename = "Type")]
+    pub kind: String,
+    /// The message id assigned by SNS
+    pub message_id: String,
+    /// The ARN of the topic the message was published to
+    pub topic_arn: String,
+    /// The subject the message was published with, if any
+    #[serde(default)]
+    pub subject: Option<String>,
+    /// The published message
+    pub message: String,
+    /// When the message was published
+    #[serde(with = "rfc3339")]
+    pub timestamp: SystemTime,
+    /// The version of the signature scheme
+    pub signature_version: String,
+    /// The base64 encoded signature of the notification
+    pub signature: String,
+    /// Where the certificate the notification was signed with can be fetched from
+    #[serde(alias = "SigningCertURL")]
+    pub signing_cert_url: String,
+    /// The URL for unsubscribing from the topic
+    #[serde(alias = "UnsubscribeURL")]
+    pub unsubscribe_url: String,
+    /// Attributes the message was published with
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub message_attributes: HashMap<String, SnsMessageAttribute>,
+}
+
+impl SnsMessage {
+    /// Deserialize the published message from JSON
+    ///
+    /// ```rust
+    /// use lambda_events::sns::SnsEvent;
+    /// use serde_derive::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Order {
+    ///     total: f64,
+    /// }
+    ///
+    /// fn total(event: &SnsEvent) -> Result<f64, serde_json::Error> {
+    ///     event.records[0].sns.parse_message::<Order>().map(|order| order.total)
+    /// }
+    /// ```
+    pub fn parse_message<T>(&self) -> Result<T, serde_json::Error>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_str(&self.message)
+    }
+}
+
+/// An attribute a message was published with
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "RawMessageAttribute", into = "RawMessageAttribute")]
+pub struct SnsMessageAttribute {
+    /// The attribute's data type, including any custom type label, i.e. `Number.float`
+    pub data_type: String,
+    /// The attribute's value
+    pub value: SnsMessageAttributeValue,
+}
+
+/// The value of a message attribute, determined by the base of its data type
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnsMessageAttributeValue {
+    /// A `String` attribute
+    String(String),
+    /// A `String.Array` attribute, kept as the JSON array text SNS delivers because its
+    /// elements may be strings, numbers, booleans or `null`
+    StringArray(String),
+    /// A `Number` attribute, kept as text because SNS numbers have up to 38 digits of precision
+    Number(String),
+    /// A `Binary` attribute, decoded from base64
+    Binary(Vec<u8>),
+}
+
+impl SnsMessageAttribute {
+    /// Return the attribute's text, if it is a `String`, `String.Array` or `Number` attribute
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.value {
+            SnsMessageAttributeValue::String(value)
+            | SnsMessageAttributeValue::StringArray(value)
+            | SnsMessageAttributeValue::Number(value) => Some(value),
+            SnsMessageAttributeValue::Binary(_) => None,
+        }
+    }
+
+    /// Return the attribute parsed as a number, if it is a `Number` attribute
+    pub fn as_number<T>(&self) -> Option<T>
+    where
+        T: FromStr,
+    {
+        match &self.value {
+            SnsMessageAttributeValue::Number(value) => value.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Return the attribute's elements, if it is a `String.Array` attribute
+    pub fn as_array(&self) -> Option<Vec<serde_json::Value>> {
+        match &self.value {
+            SnsMessageAttributeValue::StringArray(value) => serde_json::from_str(value).ok(),
+            _ => None,
+        }
+    }
+
+    /// Return the attribute's bytes, if it is a `Binary` attribute
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match &self.value {
+            SnsMessageAttributeValue::Binary(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// The wire representation of a message attribute
+#[derive(Deserialize, Serialize)]
+struct RawMessageAttribute {
+    #[serde(rename = "Type")]
+    data_type: String,
+    #[serde(rename = "Value")]
+    value: String,
+}
+
+/// Returned when a message attribute's value does not match its data type
+#[derive(Debug)]
+struct InvalidMessageAttribute(String);
+
+impl fmt::Display for InvalidMessageAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<RawMessageAttribute> for SnsMessageAttribute {
+    type Error = InvalidMessageAttribute;
+
+    fn try_from(raw: RawMessageAttribute) -> Result<Self, Self::Error> {
+        let value = if raw.data_type == "String.Array" {
+            SnsMessageAttributeValue::StringArray(raw.value)
+        } else {
+            match raw.data_type.split('.').next().unwrap_or_default() {
+                "String" => SnsMessageAttributeValue::String(raw.value),
+                "Number" => SnsMessageAttributeValue::Number(raw.value),
+                "Binary" => SnsMessageAttributeValue::Binary(base64::decode(&raw.value).map_err(|e| {
+                    InvalidMessageAttribute(format!("Binary message attribute is not valid base64: {}", e))
+                })?),
+                other => {
+                    return Err(InvalidMessageAttribute(format!(
+                        "unsupported message attribute data type {}",
+                        other
+                    )))
+                }
+            }
+        };
+        Ok(Self {
+            data_type: raw.data_type,
+            value,
+        })
+    }
+}
+
+impl From<SnsMessageAttribute> for RawMessageAttribute {
+    fn from(attribute: SnsMessageAttribute) -> Self {
+        let value = match attribute.value {
+            SnsMessageAttributeValue::String(value)
+            | SnsMessageAttributeValue::StringArray(value)
+            | SnsMessageAttributeValue::Number(value) => value,
+            SnsMessageAttributeValue::Binary(value) => base64::encode(value),
+        };
+        Self {
+            data_type: attribute.data_type,
+            value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqs::SqsEvent;
+    use serde_derive::Deserialize;
+    use serde_json::json;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct Order {
+        order_id: String,
+        total: f64,
+    }
+
+    fn event() -> SnsEvent {
+        serde_json::from_str(include_str!("../tests/data/sns_event.json")).expect("failed to deserialize event")
+    }
+
+    #[test]
+    fn deserializes_direct_invocations() {
+        let event = event();
+        assert_eq!(event.records.len(), 1);
+        let record = &event.records[0];
+        assert_eq!(record.event_source, "aws:sns");
+        let message = &record.sns;
+        assert_eq!(message.kind, "Notification");
+        assert_eq!(message.topic_arn, "arn:aws:sns:us-east-2:123456789012:sns-lambda");
+        assert_eq!(message.subject.as_deref(), Some("TestInvoke"));
+        assert_eq!(message.timestamp, UNIX_EPOCH + Duration::from_secs(1_546_433_107));
+        assert!(message.signing_cert_url.ends_with(".pem"));
+        assert_eq!(
+            message.parse_message::<Order>().expect("failed to parse message"),
+            Order {
+                order_id: "1".into(),
+                total: 12.5
+            }
+        );
+    }
+
+    #[test]
+    fn deserializes_typed_message_attributes() {
+        let event = event();
+        let attributes = &event.records[0].sns.message_attributes;
+        assert_eq!(attributes["Test"].as_str(), Some("TestString"));
+        assert_eq!(attributes["Count"].as_number::<u32>(), Some(3));
+        assert_eq!(attributes["Tags"].as_array(), Some(vec![json!("a"), json!("b")]));
+        assert_eq!(attributes["TestBinary"].as_bytes(), Some(&[0_u8, 1, 2, 3, 4][..]));
+    }
+
+    #[test]
+    fn round_trips_through_serialization() {
+        let event = event();
+        let serialized = serde_json::to_value(&event).expect("failed to serialize event");
+        assert_eq!(
+            serialized["Records"][0]["Sns"]["MessageAttributes"]["TestBinary"],
+            json!({ "Type": "Binary", "Value": "AAECAwQ=" })
+        );
+        assert_eq!(
+            serde_json::from_value::<SnsEvent>(serialized).expect("failed to deserialize event"),
+            event
+        );
+    }
+
+    #[test]
+    fn unwraps_notifications_from_sqs_messages() {
+        let event: SqsEvent = serde_json::from_str(include_str!("../tests/data/sqs_sns_envelope_event.json"))
+            .expect("failed to deserialize event");
+        let envelope = event.records[0].sns_envelope().expect("expected an SNS envelope");
+        assert_eq!(envelope.topic_arn, "arn:aws:sns:us-east-2:123456789012:orders");
+        assert_eq!(envelope.subject, None);
+        assert!(envelope
+            .unsubscribe_url
+            .starts_with("https://sns.us-east-2.amazonaws.com/"));
+        assert_eq!(
+            envelope.timestamp,
+            UNIX_EPOCH + Duration::from_millis(1_591_005_600_123)
+        );
+        assert_eq!(envelope.message_attributes["Region"].as_str(), Some("eu"));
+        assert_eq!(
+            envelope.parse_message::<Order>().expect("failed to parse message"),
+            Order {
+                order_id: "2".into(),
+                total: 7.25
+            }
+        );
+    }
+
+    #[test]
+    fn raw_message_delivery_has_no_envelope() {
+        let event: SqsEvent = serde_json::from_str(include_str!("../tests/data/sqs_sns_raw_event.json"))
+            .expect("failed to deserialize event");
+        let message = &event.records[0];
+        assert_eq!(message.sns_envelope(), None);
+        assert_eq!(message.message_attributes["Region"].as_str(), Some("eu"));
+        assert_eq!(
+            serde_json::from_str::<Order>(&message.body).expect("failed to parse body"),
+            Order {
+                order_id: "3".into(),
+                total: 1.0
+            }
+        );
+    }
+}
@@ -0,0 +1,653 @@
+//! SQS event and partial batch response types
+//!
+//! SQS event source mappings invoke functions with an [`SqsEvent`](struct.SqsEvent.html) holding
+//! a batch of messages. Mappings with `ReportBatchItemFailures` enabled accept an
+//! [`SqsBatchResponse`](struct.SqsBatchResponse.html) naming the messages which failed, so only
+//! those are retried while the rest of the batch is deleted from the queue.
+
+pub use crate::batch::BatchItemFailure;
+use crate::{
+    custom_serde::{nullable_default, string_number},
+    sns::SnsMessage,
+};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    convert::TryFrom,
+    error::Error,
+    fmt,
+    iter::FromIterator,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A batch of SQS messages
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SqsEvent {
+    /// The messages of the batch
+    #[serde(rename = "Records")]
+    pub records: Vec<SqsMessage>,
+}
+
+impl SqsEvent {
+    /// Call `f` with each message of the batch, returning a response naming the
+    /// messages for which it failed
+    ///
+    /// ```rust
+    /// use lambda_events::sqs::{SqsBatchResponse, SqsEvent};
+    ///
+    /// fn handle(event: SqsEvent) -> SqsBatchResponse {
+    ///     event.process(|message| message.body.parse::<u32>().map(|_| ()))
+    /// }
+    /// ```
+    pub fn process<F, E>(&self, mut f: F) -> SqsBatchResponse
+    where
+        F: FnMut(&SqsMessage) -> Result<(), E>,
+    {
+        self.records.iter().filter(|message| f(message).is_err()).collect()
+    }
+}
+
+/// A single SQS message
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SqsMessage {
+    /// The message id assigned by SQS
+    pub message_id: String,
+    /// The handle for deleting the message or changing its visibility
+    pub receipt_handle: String,
+    /// The message body
+    pub body: String,
+    /// MD5 digest of the message body
+    pub md5_of_body: String,
+    /// MD5 digest of the message attributes, absent when the message has none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub md5_of_message_attributes: Option<String>,
+    /// Attributes SQS records for every message
+    pub attributes: SqsSystemAttributes,
+    /// Attributes provided by the message's sender
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub message_attributes: HashMap<String, SqsMessageAttribute>,
+    /// Always `aws:sqs`
+    pub event_source: String,
+    /// The ARN of the queue
+    #[serde(rename = "eventSourceARN")]
+    pub event_source_arn: String,
+    /// The region of the queue
+    pub aws_region: String,
+}
+
+impl SqsMessage {
+    /// Return the SNS notification wrapped in the message body, for messages a topic fanned out
+    /// to the queue
+    ///
+    /// Returns `None` for messages which were not sent by SNS, and for those delivered from
+    /// subscriptions with raw message delivery enabled, whose body is the published message
+    /// itself and whose SNS message attributes are carried as SQS message attributes.
+    ///
+    /// ```rust
+    /// use lambda_events::sqs::SqsMessage;
+    ///
+    /// fn published(message: &SqsMessage) -> String {
+    ///     match message.sns_envelope() {
+    ///         Some(notification) => notification.message,
+    ///         None => message.body.clone(),
+    ///     }
+    /// }
+    /// ```
+    pub fn sns_envelope(&self) -> Option<SnsMessage> {
+        serde_json::from_str::<SnsMessage>(&self.body)
+            .ok()
+            .filter(|notification| notification.kind == "Notification")
+    }
+
+    /// Return the message attribute `name` provided by the message's sender
+    pub fn attribute(&self, name: &str) -> Option<&SqsMessageAttribute> {
+        self.message_attributes.get(name)
+    }
+}
+
+/// A batch of SQS messages borrowing their strings from the event they were deserialized from
+///
+/// Batches of large messages take up to megabytes, which [`SqsEvent`](struct.SqsEvent.html)
+/// copies into owned strings; this one only copies those which hold JSON escapes, like bodies
+/// which are JSON documents themselves.
+///
+/// ```rust
+/// use lambda_events::sqs::SqsEventRef;
+///
+/// fn total_size(event: &[u8]) -> Result<usize, serde_json::Error> {
+///     let event = SqsEventRef::from_slice(event)?;
+///     Ok(event.records.iter().map(|message| message.body.len()).sum())
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SqsEventRef<'a> {
+    /// The messages of the batch
+    #[serde(rename = "Records", borrow)]
+    pub records: Vec<SqsMessageRef<'a>>,
+}
+
+impl<'a> SqsEventRef<'a> {
+    /// Deserialize a batch borrowing from `event`, the JSON body of an invocation
+    pub fn from_slice(event: &'a [u8]) -> serde_json::Result<Self> {
+        serde_json::from_slice(event)
+    }
+
+    /// Copy the batch into an owned one
+    pub fn into_owned(self) -> SqsEvent {
+        SqsEvent {
+            records: self.records.into_iter().map(SqsMessageRef::into_owned).collect(),
+        }
+    }
+}
+
+/// A single SQS message borrowing its strings, see [`SqsEventRef`](struct.SqsEventRef.html)
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SqsMessageRef<'a> {
+    /// The message id assigned by SQS
+    #[serde(borrow)]
+    pub message_id: Cow<'a, str>,
+    /// The handle for deleting the message or changing its visibility
+    #[serde(borrow)]
+    pub receipt_handle: Cow<'a, str>,
+    /// The message body
+    #[serde(borrow)]
+    pub body: Cow<'a, str>,
+    /// MD5 digest of the message body
+    #[serde(borrow)]
+    pub md5_of_body: Cow<'a, str>,
+    /// MD5 digest of the message attributes, absent when the message has none
+    #[serde(default, borrow, skip_serializing_if = "Option::is_none")]
+    pub md5_of_message_attributes: Option<Cow<'a, str>>,
+    /// Attributes SQS records for every message
+    pub attributes: SqsSystemAttributes,
+    /// Attributes provided by the message's sender
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub message_attributes: HashMap<String, SqsMessageAttribute>,
+    /// Always `aws:sqs`
+    #[serde(borrow)]
+    pub event_source: Cow<'a, str>,
+    /// The ARN of the queue
+    #[serde(rename = "eventSourceARN", borrow)]
+    pub event_source_arn: Cow<'a, str>,
+    /// The region of the queue
+    #[serde(borrow)]
+    pub aws_region: Cow<'a, str>,
+}
+
+impl SqsMessageRef<'_> {
+    /// Copy the message into an owned one
+    pub fn into_owned(self) -> SqsMessage {
+        SqsMessage {
+            message_id: self.message_id.into_owned(),
+            receipt_handle: self.receipt_handle.into_owned(),
+            body: self.body.into_owned(),
+            md5_of_body: self.md5_of_body.into_owned(),
+            md5_of_message_attributes: self.md5_of_message_attributes.map(Cow::into_owned),
+            attributes: self.attributes,
+            message_attributes: self.message_attributes,
+            event_source: self.event_source.into_owned(),
+            event_source_arn: self.event_source_arn.into_owned(),
+            aws_region: self.aws_region.into_owned(),
+        }
+    }
+
+    /// Return the message attribute `name` provided by the message's sender
+    pub fn attribute(&self, name: &str) -> Option<&SqsMessageAttribute> {
+        self.message_attributes.get(name)
+    }
+}
+
+/// Attributes SQS records for every message
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct SqsSystemAttributes {
+    /// The number of times the message has been received without being deleted
+    #[serde(with = "string_number")]
+    pub approximate_receive_count: u64,
+    /// When the message was sent, in milliseconds since the Unix epoch
+    #[serde(with = "string_number")]
+    pub sent_timestamp: u64,
+    /// The IAM user or role which sent the message
+    pub sender_id: String,
+    /// When the message was first received, in milliseconds since the Unix epoch
+    #[serde(with = "string_number")]
+    pub approximate_first_receive_timestamp: u64,
+    /// The X-Ray trace header of the sender, when it was traced
+    #[serde(default, rename = "AWSTraceHeader", skip_serializing_if = "Option::is_none")]
+    pub aws_trace_header: Option<String>,
+    /// The order of the message within its group, for FIFO queues
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence_number: Option<String>,
+    /// The group the message belongs to, for FIFO queues
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_group_id: Option<String>,
+    /// The token SQS deduplicated the message with, for FIFO queues
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_deduplication_id: Option<String>,
+}
+
+impl SqsSystemAttributes {
+    /// Return when the message was sent
+    pub fn sent_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.sent_timestamp)
+    }
+
+    /// Return when the message was first received
+    pub fn approximate_first_receive_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.approximate_first_receive_timestamp)
+    }
+}
+
+/// An attribute provided by a message's sender
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "RawMessageAttribute", into = "RawMessageAttribute")]
+pub struct SqsMessageAttribute {
+    /// The attribute's data type, including any custom type label, i.e. `Number.float`
+    pub data_type: String,
+    /// The attribute's value
+    pub value: SqsMessageAttributeValue,
+}
+
+/// The value of a message attribute, determined by the base of its data type
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqsMessageAttributeValue {
+    /// A `String` attribute
+    String(String),
+    /// A `Number` attribute, kept as text because SQS numbers have up to 38 digits of precision
+    Number(String),
+    /// A `Binary` attribute, decoded from base64
+    Binary(Vec<u8>),
+}
+
+impl SqsMessageAttribute {
+    /// Return the base of the attribute's data type: `String`, `Number` or `Binary`
+    pub fn base_type(&self) -> &str {
+        self.data_type.split('.').next().unwrap_or_default()
+    }
+
+    /// Return the custom type label following the base of the attribute's data type, like `float`
+    /// for `Number.float`
+    pub fn custom_type(&self) -> Option<&str> {
+        self.data_type.split_once('.').map(|(_, custom)| custom)
+    }
+
+    /// Return the attribute's text, if it is a `String` or `Number` attribute
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.value {
+            SqsMessageAttributeValue::String(value) | SqsMessageAttributeValue::Number(value) => Some(value),
+            SqsMessageAttributeValue::Binary(_) => None,
+        }
+    }
+
+    /// Return the attribute's text, failing unless it is a `String` attribute
+    pub fn as_string(&self) -> Result<&str, SqsMessageAttributeError> {
+        match &self.value {
+            SqsMessageAttributeValue::String(value) => Ok(value),
+            _ => Err(self.mismatch("String")),
+        }
+    }
+
+    /// Return the attribute parsed as a number, failing unless it is a `Number` attribute whose
+    /// value parses as a `T`
+    ///
+    /// ```rust
+    /// use lambda_events::sqs::SqsMessage;
+    ///
+    /// fn quantity(message: &SqsMessage) -> u32 {
+    ///     message
+    ///         .attribute("Quantity")
+    ///         .and_then(|quantity| quantity.as_number().ok())
+    ///         .unwrap_or(1)
+    /// }
+    /// ```
+    pub fn as_number<T>(&self) -> Result<T, SqsMessageAttributeError>
+    where
+        T: FromStr,
+    {
+        match &self.value {
+            SqsMessageAttributeValue::Number(value) => {
+                value.parse().map_err(|_| SqsMessageAttributeError::InvalidNumber {
+                    value: value.clone(),
+                    target: std::any::type_name::<T>(),
+                })
+            }
+            _ => Err(self.mismatch("Number")),
+        }
+    }
+
+    /// Return the attribute's bytes, if it is a `Binary` attribute
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match &self.value {
+            SqsMessageAttributeValue::Binary(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Return the attribute's bytes, failing unless it is a `Binary` attribute
+    pub fn as_binary(&self) -> Result<&[u8], SqsMessageAttributeError> {
+        self.as_bytes().ok_or_else(|| self.mismatch("Binary"))
+    }
+
+    fn mismatch(&self, expected: &'static str) -> SqsMessageAttributeError {
+        SqsMessageAttributeError::TypeMismatch {
+            expected,
+            data_type: self.data_type.clone(),
+        }
+    }
+}
+
+/// Returned when a message attribute can't be read as the requested type
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqsMessageAttributeError {
+    /// The attribute is of another data type
+    TypeMismatch {
+        /// The base data type requested
+        expected: &'static str,
+        /// The attribute's data type
+        data_type: String,
+    },
+    /// The value of a `Number` attribute doesn't parse as the requested type
+    InvalidNumber {
+        /// The attribute's value
+        value: String,
+        /// The name of the requested type
+        target: &'static str,
+    },
+}
+
+impl fmt::Display for SqsMessageAttributeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SqsMessageAttributeError::TypeMismatch { expected, data_type } => {
+                write!(f, "expected a {} message attribute, got {}", expected, data_type)
+            }
+            SqsMessageAttributeError::InvalidNumber { value, target } => {
+                write!(f, "Number message attribute {} is not a valid {}", value, target)
+            }
+        }
+    }
+}
+
+impl Error for SqsMessageAttributeError {}
+
+/// The wire representation of a message attribute
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RawMessageAttribute {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    string_value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    binary_value: Option<String>,
+    #[serde(default, deserialize_with = "nullable_default")]
+    string_list_values: Vec<String>,
+    #[serde(default, deserialize_with = "nullable_default")]
+    binary_list_values: Vec<String>,
+    data_type: String,
+}
+
+/// Returned when a message attribute's value does not match its data type
+#[derive(Debug)]
+struct InvalidMessageAttribute(String);
+
+impl fmt::Display for InvalidMessageAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<RawMessageAttribute> for SqsMessageAttribute {
+    type Error = InvalidMessageAttribute;
+
+    fn try_from(raw: RawMessageAttribute) -> Result<Self, Self::Error> {
+        let base = raw.data_type.split('.').next().unwrap_or_default();
+        let missing = || InvalidMessageAttribute(format!("{} message attribute has no value", raw.data_type));
+        let value = match base {
+            "String" => SqsMessageAttributeValue::String(raw.string_value.clone().ok_or_else(missing)?),
+            "Number" => SqsMessageAttributeValue::Number(raw.string_value.clone().ok_or_else(missing)?),
+            "Binary" => {
+                let encoded = raw.binary_value.as_ref().ok_or_else(missing)?;
+                SqsMessageAttributeValue::Binary(base64::decode(encoded).map_err(|e| {
+                    InvalidMessageAttribute(format!("Binary message attribute is not valid base64: {}", e))
+                })?)
+            }
+            other => {
+                return Err(InvalidMessageAttribute(format!(
+                    "unsupported message attribute data type {}",
+                    other
+                )))
+            }
+        };
+        Ok(Self {
+            data_type: raw.data_type,
+            value,
+        })
+    }
+}
+
+impl From<SqsMessageAttribute> for RawMessageAttribute {
+    fn from(attribute: SqsMessageAttribute) -> Self {
+        let (string_value, binary_value) = match attribute.value {
+            SqsMessageAttributeValue::String(value) | SqsMessageAttributeValue::Number(value) => (Some(value), None),
+            SqsMessageAttributeValue::Binary(value) => (None, Some(base64::encode(value))),
+        };
+        Self {
+            string_value,
+            binary_value,
+            string_list_values: Vec::new(),
+            binary_list_values: Vec::new(),
+            data_type: attribute.data_type,
+        }
+    }
+}
+
+/// A partial batch response naming the messages which failed to be processed
+///
+/// Serializes as `{"batchItemFailures":[{"itemIdentifier":"<messageId>"}]}`. An empty response
+/// reports the whole batch as processed.
+///
+/// ```rust
+/// use lambda_events::sqs::{SqsBatchResponse, SqsEvent};
+///
+/// fn handle(event: SqsEvent) -> SqsBatchResponse {
+///     let mut response = SqsBatchResponse::default();
+///     for message in &event.records {
+///         if message.body.is_empty() {
+///             response.add_failure(message);
+///         }
+///     }
+///     response
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SqsBatchResponse {
+    /// The messages which failed to be processed
+    pub batch_item_failures: Vec<BatchItemFailure>,
+}
+
+impl SqsBatchResponse {
+    /// Report `message` as failed
+    pub fn add_failure(&mut self, message: &SqsMessage) {
+        self.batch_item_failures.push(BatchItemFailure {
+            item_identifier: message.message_id.clone(),
+        });
+    }
+}
+
+impl<'a> FromIterator<&'a SqsMessage> for SqsBatchResponse {
+    fn from_iter<I>(failed: I) -> Self
+    where
+        I: IntoIterator<Item = &'a SqsMessage>,
+    {
+        let mut response = Self::default();
+        for message in failed {
+            response.add_failure(message);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_round_trips;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn deserializes_standard_queue_events() {
+        let event = assert_round_trips::<SqsEvent>(include_str!("../tests/data/sqs_event.json"));
+        assert_eq!(event.records.len(), 2);
+        let message = &event.records[0];
+        assert_eq!(message.message_id, "059f36b4-87a3-44ab-83d2-661975830a7d");
+        assert_eq!(message.body, r#"{"orderId":"1"}"#);
+        assert_eq!(message.event_source_arn, "arn:aws:sqs:us-east-2:123456789012:my-queue");
+        assert_eq!(message.attributes.approximate_receive_count, 1);
+        assert_eq!(message.attributes.sent_timestamp, 1_545_082_649_183);
+        assert_eq!(
+            message.attributes.sent_time(),
+            UNIX_EPOCH + Duration::from_millis(1_545_082_649_183)
+        );
+        assert!(message.attributes.aws_trace_header.is_some());
+        assert_eq!(message.attributes.message_group_id, None);
+        assert_eq!(event.records[1].attributes.approximate_receive_count, 3);
+    }
+
+    #[test]
+    fn deserializes_typed_message_attributes() {
+        let event = assert_round_trips::<SqsEvent>(include_str!("../tests/data/sqs_event.json"));
+        let attributes = &event.records[0].message_attributes;
+        assert_eq!(attributes["Author"].as_str(), Some("John Smith"));
+        assert_eq!(attributes["Author"].as_number::<f64>().ok(), None);
+        assert_eq!(attributes["Price"].data_type, "Number.float");
+        assert_eq!(attributes["Price"].as_number::<f64>().ok(), Some(12.5));
+        assert_eq!(attributes["Thumbnail"].as_bytes(), Some(&[0_u8, 1, 2, 3, 4][..]));
+        assert!(event.records[1].message_attributes.is_empty());
+    }
+
+    #[test]
+    fn reads_attributes_of_each_data_type() {
+        let event = assert_round_trips::<SqsEvent>(include_str!("../tests/data/sqs_event.json"));
+        let message = &event.records[0];
+        assert_eq!(message.attribute("Author").unwrap().as_string(), Ok("John Smith"));
+        assert_eq!(message.attribute("Price").unwrap().as_number::<f64>(), Ok(12.5));
+        assert_eq!(
+            message.attribute("Thumbnail").unwrap().as_binary(),
+            Ok(&[0_u8, 1, 2, 3, 4][..])
+        );
+        assert_eq!(message.attribute("Missing"), None);
+    }
+
+    #[test]
+    fn preserves_custom_data_types() {
+        let event = assert_round_trips::<SqsEvent>(include_str!("../tests/data/sqs_event.json"));
+        let price = event.records[0].attribute("Price").unwrap();
+        assert_eq!(price.base_type(), "Number");
+        assert_eq!(price.custom_type(), Some("float"));
+        let author = event.records[0].attribute("Author").unwrap();
+        assert_eq!(author.base_type(), "String");
+        assert_eq!(author.custom_type(), None);
+    }
+
+    #[test]
+    fn reports_data_type_mismatches() {
+        let event = assert_round_trips::<SqsEvent>(include_str!("../tests/data/sqs_event.json"));
+        let message = &event.records[0];
+        let error = message.attribute("Price").unwrap().as_string().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected a String message attribute, got Number.float"
+        );
+        let error = message.attribute("Author").unwrap().as_binary().unwrap_err();
+        assert_eq!(error.to_string(), "expected a Binary message attribute, got String");
+        let error = message.attribute("Price").unwrap().as_number::<u32>().unwrap_err();
+        assert_eq!(error.to_string(), "Number message attribute 12.50 is not a valid u32");
+    }
+
+    #[test]
+    fn deserializes_fifo_queue_events() {
+        let event = assert_round_trips::<SqsEvent>(include_str!("../tests/data/sqs_fifo_event.json"));
+        let attributes = &event.records[0].attributes;
+        assert_eq!(attributes.sequence_number.as_deref(), Some("18849496460467696128"));
+        assert_eq!(attributes.message_group_id.as_deref(), Some("1"));
+        assert_eq!(attributes.message_deduplication_id.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn rejects_invalid_binary_attributes() {
+        let mut fixture: Value = serde_json::from_str(include_str!("../tests/data/sqs_event.json")).unwrap();
+        fixture["Records"][0]["messageAttributes"]["Thumbnail"]["binaryValue"] = "not base64!".into();
+        let error = serde_json::from_value::<SqsEvent>(fixture).unwrap_err();
+        assert!(error.to_string().contains("not valid base64"), "{}", error);
+    }
+
+    #[test]
+    fn round_trips_events() {
+        for fixture in &[
+            include_str!("../tests/data/sqs_event.json"),
+            include_str!("../tests/data/sqs_fifo_event.json"),
+        ] {
+            assert_round_trips::<SqsEvent>(fixture);
+        }
+    }
+
+    #[test]
+    fn borrowed_events_match_owned_ones() {
+        for fixture in &[
+            include_str!("../tests/data/sqs_event.json"),
+            include_str!("../tests/data/sqs_fifo_event.json"),
+        ] {
+            let borrowed = SqsEventRef::from_slice(fixture.as_bytes()).expect("failed to deserialize event");
+            let message = &borrowed.records[0];
+            assert!(matches!(message.message_id, Cow::Borrowed(_)));
+            assert_eq!(
+                message.attribute("Author"),
+                assert_round_trips::<SqsEvent>(fixture).records[0].attribute("Author")
+            );
+            assert_eq!(
+                serde_json::to_value(&borrowed).unwrap(),
+                serde_json::to_value(assert_round_trips::<SqsEvent>(fixture)).unwrap()
+            );
+            assert_eq!(borrowed.into_owned(), assert_round_trips::<SqsEvent>(fixture));
+        }
+    }
+
+    #[test]
+    fn borrowed_bodies_are_only_copied_to_unescape_them() {
+        let escaped = include_str!("../tests/data/sqs_event.json");
+        let borrowed = SqsEventRef::from_slice(escaped.as_bytes()).expect("failed to deserialize event");
+        assert!(matches!(borrowed.records[0].body, Cow::Owned(_)));
+        assert_eq!(borrowed.records[0].body, r#"{"orderId":"1"}"#);
+
+        let plain = escaped.replace(r#""{\"orderId\":\"1\"}""#, r#""order 1""#);
+        let borrowed = SqsEventRef::from_slice(plain.as_bytes()).expect("failed to deserialize event");
+        assert!(matches!(borrowed.records[0].body, Cow::Borrowed("order 1")));
+    }
+
+    #[test]
+    fn process_reports_failed_messages() {
+        let event = assert_round_trips::<SqsEvent>(include_str!("../tests/data/sqs_event.json"));
+        let response = event.process(|message| {
+            if message.attributes.approximate_receive_count > 1 {
+                Err("retried too often")
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(
+            serde_json::to_value(&response).expect("failed to serialize response"),
+            json!({ "batchItemFailures": [{ "itemIdentifier": "2e1424d4-f796-459a-8184-9c92662be6da" }] })
+        );
+    }
+
+    #[test]
+    fn empty_batch_responses_serialize_an_empty_list() {
+        assert_eq!(
+            serde_json::to_string(&SqsBatchResponse::default()).expect("failed to serialize response"),
+            r#"{"batchItemFailures":[]}"#
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Partial batch response types for Kinesis and DynamoDB stream event sources
+//!
+//! Stream event source mappings with `ReportBatchItemFailures` enabled accept a
+//! [`StreamsBatchResponse`](struct.StreamsBatchResponse.html) naming the sequence number of the
+//! first record which failed. Unlike queues, streams are processed in order, so Lambda
+//! checkpoints the shard just before the lowest sequence number reported and retries the batch
+//! from that record onwards. Records after the first failure should therefore not be processed,
+//! since they will be delivered again.
+//!
+//! When the mapping also has `BisectBatchOnFunctionError` enabled, the retried batch is split at
+//! the reported record instead of being retried whole. Reporting a sequence number which
+//! isn't part of the batch, or an empty one, fails the entire batch, and an empty response
+//! reports the whole batch as processed.
+
+pub use crate::batch::BatchItemFailure;
+use crate::{dynamodb::DynamoDbRecord, kinesis::KinesisRecord};
+use serde_derive::{Deserialize, Serialize};
+use std::iter::FromIterator;
+
+/// A stream record, identified within its shard by a sequence number
+pub trait SequencedRecord {
+    /// Return the record's sequence number
+    fn sequence_number(&self) -> &str;
+}
+
+impl SequencedRecord for KinesisRecord {
+    fn sequence_number(&self) -> &str {
+        &self.kinesis.sequence_number
+    }
+}
+
+impl SequencedRecord for DynamoDbRecord {
+    fn sequence_number(&self) -> &str {
+        &self.dynamodb.sequence_number
+    }
+}
+
+/// A partial batch response naming the stream records which failed to be processed
+///
+/// Serializes as `{"batchItemFailures":[{"itemIdentifier":"<sequenceNumber>"}]}`.
+///
+/// ```rust
+/// use lambda_events::{dynamodb::DynamoDbEvent, streams::StreamsBatchResponse};
+///
+/// fn handle(event: DynamoDbEvent) -> StreamsBatchResponse {
+///     for record in &event.records {
+///         if record.dynamodb.new_image.is_none() {
+///             // the batch is retried from this record, so stop here
+///             return StreamsBatchResponse::failed_at(record);
+///         }
+///     }
+///     StreamsBatchResponse::default()
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamsBatchResponse {
+    /// The records which failed to be processed
+    pub batch_item_failures: Vec<BatchItemFailure>,
+}
+
+/// The partial batch response of Kinesis event source mappings
+pub type KinesisBatchResponse = StreamsBatchResponse;
+
+/// The partial batch response of DynamoDB stream event source mappings
+pub type DynamoDbBatchResponse = StreamsBatchResponse;
+
+impl StreamsBatchResponse {
+    /// Return a response reporting `record` as the first record which failed, so that the batch
+    /// is retried from it
+    pub fn failed_at<R>(record: &R) -> Self
+    where
+        R: SequencedRecord,
+    {
+        let mut response = Self::default();
+        response.add_failure(record);
+        response
+    }
+
+    /// Report `record` as failed
+    pub fn add_failure<R>(&mut self, record: &R)
+    where
+        R: SequencedRecord,
+    {
+        self.batch_item_failures.push(BatchItemFailure {
+            item_identifier: record.sequence_number().to_string(),
+        });
+    }
+}
+
+impl<'a, R> FromIterator<&'a R> for StreamsBatchResponse
+where
+    R: SequencedRecord + 'a,
+{
+    fn from_iter<I>(failed: I) -> Self
+    where
+        I: IntoIterator<Item = &'a R>,
+    {
+        let mut response = Self::default();
+        for record in failed {
+            response.add_failure(record);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dynamodb::DynamoDbEvent, kinesis::KinesisEvent};
+    use serde_json::json;
+
+    #[test]
+    fn empty_responses_serialize_an_empty_list() {
+        assert_eq!(
+            serde_json::to_string(&StreamsBatchResponse::default()).expect("failed to serialize response"),
+            r#"{"batchItemFailures":[]}"#
+        );
+    }
+
+    #[test]
+    fn identifies_dynamodb_records_by_sequence_number() {
+        let event: DynamoDbEvent = serde_json::from_str(include_str!("../tests/data/dynamodb_event.json"))
+            .expect("failed to deserialize event");
+        let response = StreamsBatchResponse::failed_at(&event.records[1]);
+        assert_eq!(
+            serde_json::to_value(&response).expect("failed to serialize response"),
+            json!({ "batchItemFailures": [{ "itemIdentifier": "222" }] })
+        );
+    }
+
+    #[test]
+    fn identifies_kinesis_records_by_sequence_number() {
+        let event: KinesisEvent = serde_json::from_str(include_str!("../tests/data/kinesis_event.json"))
+            .expect("failed to deserialize event");
+        let response: KinesisBatchResponse = event.records.iter().skip(1).collect();
+        assert_eq!(
+            serde_json::to_value(&response).expect("failed to serialize response"),
+            json!({
+                "batchItemFailures": [
+                    { "itemIdentifier": "49590338271490256608559692540925702759324208523137515618" }
+                ]
+            })
+        );
+    }
+}
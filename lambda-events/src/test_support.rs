@@ -0,0 +1,540 @@
+//! Builders of events for unit tests of handlers, with the `test-support` feature
+//!
+//! [`SqsEvent::builder`](../sqs/struct.SqsEvent.html#method.builder),
+//! [`S3Event::builder`](../s3/struct.S3Event.html#method.builder) and
+//! [`ApiGatewayV2Request::builder`](../apigw/struct.ApiGatewayV2Request.html#method.builder)
+//! assemble valid events from the few fields a test cares about, filling in the others with
+//! realistic values: ARNs in account `123456789012` and region `us-east-1`, ids unique
+//! within an event, and timestamps of 2020-06-01T09:30:00Z. MD5 digests are placeholders.
+//!
+//! ```rust
+//! use lambda_events::{
+//!     apigw::ApiGatewayV2Request,
+//!     sqs::{SqsBatchResponse, SqsEvent},
+//! };
+//! use serde_json::json;
+//!
+//! fn handle_orders(event: SqsEvent) -> SqsBatchResponse {
+//!     event.process(|message| serde_json::from_str::<serde_json::Value>(&message.body).map(|_| ()))
+//! }
+//!
+//! fn handle_request(request: ApiGatewayV2Request) -> u16 {
+//!     match (request.request_context.http.method.as_str(), request.body) {
+//!         ("POST", Some(_)) => 202,
+//!         _ => 400,
+//!     }
+//! }
+//!
+//! let event = SqsEvent::builder()
+//!     .message(r#"{"orderId":"1"}"#)
+//!     .message("not json")
+//!     .build();
+//! let failed = event.records[1].message_id.clone();
+//! assert_eq!(handle_orders(event).batch_item_failures[0].item_identifier, failed);
+//!
+//! let request = ApiGatewayV2Request::builder()
+//!     .method("POST")
+//!     .path("/orders")
+//!     .json_body(&json!({ "orderId": "1" }))
+//!     .build();
+//! assert_eq!(handle_request(request), 202);
+//! ```
+
+use crate::{
+    apigw::{ApiGatewayV2CustomAuthorizerHttp, ApiGatewayV2Request, ApiGatewayV2RequestContext},
+    s3::{S3Bucket, S3Entity, S3Event, S3EventName, S3EventRecord, S3Object, S3RequestParameters, S3UserIdentity},
+    sqs::{SqsEvent, SqsMessage, SqsMessageAttribute, SqsMessageAttributeValue, SqsSystemAttributes},
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const ACCOUNT_ID: &str = "123456789012";
+const REGION: &str = "us-east-1";
+/// 2020-06-01T09:30:00Z, in milliseconds since the Unix epoch
+const TIMESTAMP_MILLIS: u64 = 1_591_003_800_000;
+const SOURCE_IP: &str = "203.0.113.1";
+const MD5_PLACEHOLDER: &str = "d41d8cd98f00b204e9800998ecf8427e";
+
+fn timestamp() -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(TIMESTAMP_MILLIS)
+}
+
+/// A UUID shaped id, unique for each `index`
+fn id(index: usize) -> String {
+    format!("3f5c2b1e-7a9d-4e8f-b6c0-{:012x}", index + 1)
+}
+
+/// Percent encode `value` the way S3 encodes event keys and browsers encode query strings
+fn url_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(byte as char),
+            b' ' => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+impl SqsEvent {
+    /// Return a builder of a batch of messages from a queue named `my-queue`
+    pub fn builder() -> SqsEventBuilder {
+        SqsEventBuilder {
+            queue_arn: format!("arn:aws:sqs:{}:{}:my-queue", REGION, ACCOUNT_ID),
+            records: Vec::new(),
+        }
+    }
+}
+
+/// A builder of [`SqsEvent`](../sqs/struct.SqsEvent.html)s
+#[derive(Debug, Clone)]
+pub struct SqsEventBuilder {
+    queue_arn: String,
+    records: Vec<SqsMessage>,
+}
+
+impl SqsEventBuilder {
+    /// Set the ARN of the queue the messages were received from, taking its region from it
+    pub fn queue_arn<A>(self, queue_arn: A) -> Self
+    where
+        A: Into<String>,
+    {
+        Self {
+            queue_arn: queue_arn.into(),
+            ..self
+        }
+    }
+
+    /// Add a message with `body`
+    pub fn message<B>(mut self, body: B) -> Self
+    where
+        B: Into<String>,
+    {
+        let index = self.records.len();
+        self.records.push(SqsMessage {
+            message_id: id(index),
+            receipt_handle: format!("AQEBwJnKyrHigUMZj6rYigCgxlaS3SLy0a{}", index),
+            body: body.into(),
+            md5_of_body: MD5_PLACEHOLDER.into(),
+            md5_of_message_attributes: None,
+            attributes: SqsSystemAttributes {
+                approximate_receive_count: 1,
+                sent_timestamp: TIMESTAMP_MILLIS,
+                sender_id: "AIDAIENQZJOLO23YVJ4VO".into(),
+                approximate_first_receive_timestamp: TIMESTAMP_MILLIS + 2,
+                aws_trace_header: None,
+                sequence_number: None,
+                message_group_id: None,
+                message_deduplication_id: None,
+            },
+            message_attributes: HashMap::new(),
+            event_source: "aws:sqs".into(),
+            event_source_arn: String::new(),
+            aws_region: String::new(),
+        });
+        self
+    }
+
+    /// Add an attribute to the last message added
+    ///
+    /// # Panics
+    ///
+    /// Panics if no message was added yet.
+    pub fn message_attribute<N>(mut self, name: N, attribute: SqsMessageAttribute) -> Self
+    where
+        N: Into<String>,
+    {
+        let message = self
+            .records
+            .last_mut()
+            .expect("message attributes are added to the last message added");
+        message.md5_of_message_attributes = Some(MD5_PLACEHOLDER.into());
+        message.message_attributes.insert(name.into(), attribute);
+        self
+    }
+
+    /// Return the assembled event
+    pub fn build(self) -> SqsEvent {
+        let queue_arn = self.queue_arn;
+        let region = queue_arn.split(':').nth(3).unwrap_or(REGION).to_string();
+        let records = self
+            .records
+            .into_iter()
+            .map(|message| SqsMessage {
+                event_source_arn: queue_arn.clone(),
+                aws_region: region.clone(),
+                ..message
+            })
+            .collect();
+        SqsEvent { records }
+    }
+}
+
+impl SqsMessageAttribute {
+    /// Create a `String` attribute
+    pub fn string<V>(value: V) -> Self
+    where
+        V: Into<String>,
+    {
+        Self {
+            data_type: "String".into(),
+            value: SqsMessageAttributeValue::String(value.into()),
+        }
+    }
+
+    /// Create a `Number` attribute
+    pub fn number<V>(value: V) -> Self
+    where
+        V: ToString,
+    {
+        Self {
+            data_type: "Number".into(),
+            value: SqsMessageAttributeValue::Number(value.to_string()),
+        }
+    }
+
+    /// Create a `Binary` attribute
+    pub fn binary<V>(value: V) -> Self
+    where
+        V: Into<Vec<u8>>,
+    {
+        Self {
+            data_type: "Binary".into(),
+            value: SqsMessageAttributeValue::Binary(value.into()),
+        }
+    }
+}
+
+impl S3Event {
+    /// Return a builder of an event notification
+    pub fn builder() -> S3EventBuilder {
+        S3EventBuilder { records: Vec::new() }
+    }
+}
+
+/// A builder of [`S3Event`](../s3/struct.S3Event.html)s
+#[derive(Debug, Clone)]
+pub struct S3EventBuilder {
+    records: Vec<S3EventRecord>,
+}
+
+impl S3EventBuilder {
+    /// Add an `ObjectCreated:Put` record of an empty object, given its key as it appears in the
+    /// bucket
+    pub fn object_created<B, K>(self, bucket: B, key: K) -> Self
+    where
+        B: Into<String>,
+        K: AsRef<str>,
+    {
+        self.record(S3EventName::ObjectCreatedPut, bucket.into(), key.as_ref())
+    }
+
+    /// Add an `ObjectRemoved:Delete` record, given the object's key as it appears in the bucket
+    pub fn object_removed<B, K>(self, bucket: B, key: K) -> Self
+    where
+        B: Into<String>,
+        K: AsRef<str>,
+    {
+        self.record(S3EventName::ObjectRemovedDelete, bucket.into(), key.as_ref())
+    }
+
+    /// Set the size of the object of the last record added
+    ///
+    /// # Panics
+    ///
+    /// Panics if no record was added yet.
+    pub fn size(mut self, size: u64) -> Self {
+        self.records
+            .last_mut()
+            .expect("sizes are set on the last record added")
+            .s3
+            .object
+            .size = Some(size);
+        self
+    }
+
+    /// Return the assembled event
+    pub fn build(self) -> S3Event {
+        S3Event { records: self.records }
+    }
+
+    fn record(mut self, event_name: S3EventName, bucket: String, key: &str) -> Self {
+        let index = self.records.len();
+        let created = event_name.is_created();
+        let mut response_elements = HashMap::new();
+        response_elements.insert("x-amz-request-id".to_string(), format!("C3D13FE58DE4C8{:02X}", index));
+        response_elements.insert(
+            "x-amz-id-2".to_string(),
+            "FMyUVURIY8/IgAtTv8xRjskZQpcIZ9KG4V5Wp6S7S/JRWeUWerMUE5JgHvANOjpD".to_string(),
+        );
+        self.records.push(S3EventRecord {
+            event_version: "2.1".into(),
+            event_source: "aws:s3".into(),
+            aws_region: REGION.into(),
+            event_time: timestamp(),
+            event_name,
+            user_identity: S3UserIdentity {
+                principal_id: "AWS:AIDAINPONIXQXHT3IKHL2".into(),
+            },
+            request_parameters: S3RequestParameters {
+                source_ip_address: SOURCE_IP.into(),
+            },
+            response_elements,
+            s3: S3Entity {
+                schema_version: "1.0".into(),
+                configuration_id: "test-notification".into(),
+                bucket: S3Bucket {
+                    arn: format!("arn:aws:s3:::{}", bucket),
+                    name: bucket,
+                    owner_identity: S3UserIdentity {
+                        principal_id: "A3NL1KOZZKExample".into(),
+                    },
+                },
+                object: S3Object {
+                    key: url_encode(key),
+                    size: if created { Some(0) } else { None },
+                    e_tag: if created { Some(MD5_PLACEHOLDER.into()) } else { None },
+                    version_id: None,
+                    sequencer: format!("0055AED6DCD90281E{}", index),
+                },
+            },
+        });
+        self
+    }
+}
+
+impl ApiGatewayV2Request {
+    /// Return a builder of a `GET /` request to the default route and stage of an HTTP API
+    pub fn builder() -> ApiGatewayV2RequestBuilder {
+        ApiGatewayV2RequestBuilder {
+            method: "GET".into(),
+            path: "/".into(),
+            headers: HashMap::new(),
+            query: Vec::new(),
+            body: None,
+        }
+    }
+}
+
+/// A builder of [`ApiGatewayV2Request`](../apigw/struct.ApiGatewayV2Request.html)s
+#[derive(Debug, Clone)]
+pub struct ApiGatewayV2RequestBuilder {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>,
+    query: Vec<(String, String)>,
+    body: Option<String>,
+}
+
+impl ApiGatewayV2RequestBuilder {
+    /// Set the HTTP method
+    pub fn method<M>(self, method: M) -> Self
+    where
+        M: Into<String>,
+    {
+        Self {
+            method: method.into().to_uppercase(),
+            ..self
+        }
+    }
+
+    /// Set the path
+    pub fn path<P>(self, path: P) -> Self
+    where
+        P: Into<String>,
+    {
+        Self {
+            path: path.into(),
+            ..self
+        }
+    }
+
+    /// Set a header, replacing any previous value
+    ///
+    /// API Gateway lower cases header names, and so does the builder.
+    pub fn header<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: AsRef<str>,
+        V: Into<String>,
+    {
+        self.headers.insert(name.as_ref().to_lowercase(), value.into());
+        self
+    }
+
+    /// Add a query string parameter
+    pub fn query<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the body
+    pub fn body<B>(self, body: B) -> Self
+    where
+        B: Into<String>,
+    {
+        Self {
+            body: Some(body.into()),
+            ..self
+        }
+    }
+
+    /// Set the body to `body` serialized as JSON, along with a matching `content-type` header
+    ///
+    /// # Panics
+    ///
+    /// Panics if `body` fails to serialize.
+    pub fn json_body<B>(self, body: &B) -> Self
+    where
+        B: Serialize + ?Sized,
+    {
+        let body = serde_json::to_string(body).expect("failed to serialize body");
+        self.header("content-type", "application/json").body(body)
+    }
+
+    /// Return the assembled request
+    pub fn build(self) -> ApiGatewayV2Request {
+        let raw_query_string = self
+            .query
+            .iter()
+            .map(|(name, value)| format!("{}={}", url_encode(name), url_encode(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+        // API Gateway joins the values of repeated parameters with commas
+        let mut query_string_parameters = HashMap::<String, String>::new();
+        for (name, value) in self.query {
+            query_string_parameters
+                .entry(name)
+                .and_modify(|values| {
+                    values.push(',');
+                    values.push_str(&value);
+                })
+                .or_insert(value);
+        }
+        let mut headers = self.headers;
+        headers
+            .entry("host".into())
+            .or_insert_with(|| "abcdef123.execute-api.us-east-1.amazonaws.com".into());
+        headers
+            .entry("user-agent".into())
+            .or_insert_with(|| "lambda-events-test".into());
+        let user_agent = headers["user-agent"].clone();
+        ApiGatewayV2Request {
+            version: "2.0".into(),
+            route_key: "$default".into(),
+            raw_path: self.path.clone(),
+            raw_query_string,
+            cookies: Vec::new(),
+            headers,
+            query_string_parameters,
+            path_parameters: HashMap::new(),
+            stage_variables: HashMap::new(),
+            request_context: ApiGatewayV2RequestContext {
+                account_id: ACCOUNT_ID.into(),
+                api_id: "abcdef123".into(),
+                authorizer: None,
+                authentication: None,
+                domain_name: "abcdef123.execute-api.us-east-1.amazonaws.com".into(),
+                domain_prefix: "abcdef123".into(),
+                http: ApiGatewayV2CustomAuthorizerHttp {
+                    method: self.method,
+                    path: self.path,
+                    protocol: "HTTP/1.1".into(),
+                    source_ip: SOURCE_IP.into(),
+                    user_agent,
+                },
+                request_id: "JKJaXmPLvHcESHA=".into(),
+                route_key: "$default".into(),
+                stage: "$default".into(),
+                time: "01/Jun/2020:09:30:00 +0000".into(),
+                time_epoch: TIMESTAMP_MILLIS as i64,
+            },
+            body: self.body,
+            is_base64_encoded: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assert_round_trips, event::LambdaEvent};
+    use serde::de::DeserializeOwned;
+    use serde_json::json;
+    use std::fmt::Debug;
+
+    /// Built events must survive their own serialization, and be detected as what they are
+    fn round_trip<T>(built: &T) -> LambdaEvent
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        let serialized = serde_json::to_string(built).expect("failed to serialize event");
+        assert_eq!(&assert_round_trips::<T>(&serialized), built);
+        assert_round_trips(&serialized)
+    }
+
+    #[test]
+    fn builds_sqs_events() {
+        let event = SqsEvent::builder()
+            .queue_arn("arn:aws:sqs:eu-west-1:123456789012:orders")
+            .message("first")
+            .message_attribute("Author", SqsMessageAttribute::string("John Smith"))
+            .message_attribute("Price", SqsMessageAttribute::number(12.5))
+            .message_attribute("Thumbnail", SqsMessageAttribute::binary(vec![0, 1, 2]))
+            .message("second")
+            .build();
+        assert!(matches!(round_trip(&event), LambdaEvent::Sqs(_)));
+        let (first, second) = (&event.records[0], &event.records[1]);
+        assert_ne!(first.message_id, second.message_id);
+        assert_eq!(first.aws_region, "eu-west-1");
+        assert_eq!(first.event_source_arn, "arn:aws:sqs:eu-west-1:123456789012:orders");
+        assert_eq!(first.attribute("Price").unwrap().as_number::<f64>(), Ok(12.5));
+        assert!(second.message_attributes.is_empty());
+        assert_eq!(second.md5_of_message_attributes, None);
+    }
+
+    #[test]
+    fn builds_s3_events() {
+        let event = S3Event::builder()
+            .object_created("orders-assets", "customer images/café.jpg")
+            .size(1_024)
+            .object_removed("orders-assets", "old.jpg")
+            .build();
+        assert!(matches!(round_trip(&event), LambdaEvent::S3(_)));
+        let (created, removed) = (&event.records[0], &event.records[1]);
+        assert!(created.event_name.is_created());
+        assert_eq!(created.s3.object.key, "customer+images/caf%C3%A9.jpg");
+        assert_eq!(created.s3.object.url_decoded_key(), "customer images/café.jpg");
+        assert_eq!(created.s3.object.size, Some(1_024));
+        assert_eq!(created.s3.bucket.arn, "arn:aws:s3:::orders-assets");
+        assert!(removed.event_name.is_removed());
+        assert_eq!(removed.s3.object.size, None);
+    }
+
+    #[test]
+    fn builds_api_gateway_v2_requests() {
+        let request = ApiGatewayV2Request::builder()
+            .method("post")
+            .path("/orders")
+            .query("tag", "a b")
+            .query("tag", "c")
+            .header("X-Request-Id", "42")
+            .json_body(&json!({ "orderId": "1" }))
+            .build();
+        assert!(matches!(round_trip(&request), LambdaEvent::ApiGatewayV2(_)));
+        assert_eq!(request.request_context.http.method, "POST");
+        assert_eq!(request.raw_path, "/orders");
+        assert_eq!(request.raw_query_string, "tag=a+b&tag=c");
+        assert_eq!(request.query_string_parameters["tag"], "a b,c");
+        assert_eq!(request.headers["x-request-id"], "42");
+        assert_eq!(request.headers["content-type"], "application/json");
+        assert_eq!(request.body.as_deref(), Some(r#"{"orderId":"1"}"#));
+    }
+}
@@ -0,0 +1,28 @@
+use bytes::Bytes;
+use futures::stream::{self, Stream, StreamExt};
+use lambda_http::{
+    http::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE},
+    lambda::{self, Context},
+    streaming::{streaming_handler, StreamingResponse},
+    Request,
+};
+use std::time::Duration;
+
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    lambda::streaming::run(streaming_handler(func)).await?;
+    Ok(())
+}
+
+/// Sends a server-sent event every second, to a Function URL using the `RESPONSE_STREAM` invoke mode
+async fn func(_: Request, _: Context) -> Result<StreamingResponse<impl Stream<Item = Result<Bytes, Error>>>, Error> {
+    let events = stream::iter(1..=5).then(|n| async move {
+        tokio::time::delay_for(Duration::from_secs(1)).await;
+        Ok(Bytes::from(format!("id: {}\ndata: tick {}\n\n", n, n)))
+    });
+    Ok(StreamingResponse::new(events)
+        .header(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"))
+        .header(CACHE_CONTROL, HeaderValue::from_static("no-cache")))
+}
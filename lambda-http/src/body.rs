@@ -58,7 +58,7 @@ use serde::ser::{Error as SerError, Serialize, Serializer};
 ///
 /// For more information about API Gateway's body types,
 /// refer to [this documentation](https://docs.aws.amazon.com/apigateway/latest/developerguide/api-gateway-payload-encodings.html).
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Body {
     /// An empty body
     Empty,
@@ -0,0 +1,86 @@
+//! Percent-decoding of paths and query strings
+//!
+//! Query strings are decoded with URI semantics rather than `application/x-www-form-urlencoded`
+//! semantics, so a `+` is kept as a literal plus sign instead of becoming a space. Only
+//! request bodies parsed as forms, see [`RequestExt::payload`](../ext/trait.RequestExt.html#tymethod.payload),
+//! decode `+` as a space.
+
+use std::collections::HashMap;
+
+use crate::strmap::StrMap;
+
+/// Decodes percent-encoded octets, replacing invalid UTF-8
+pub(crate) fn percent_decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        // `from_str_radix` would accept a sign, so both digits are checked first
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Parses a raw query string into its parameters
+///
+/// The query is split on `&` and `=` before its names and values are decoded, so encoded
+/// separators like `%26` are kept within the name or value they appear in. Parameters without
+/// a `=` have an empty value.
+pub(crate) fn parse_query_string(query: &str) -> StrMap {
+    let mut params: HashMap<String, Vec<String>> = HashMap::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let mut parts = pair.splitn(2, '=');
+        let name = percent_decode(parts.next().unwrap_or_default());
+        let value = percent_decode(parts.next().unwrap_or_default());
+        params.entry(name).or_default().push(value);
+    }
+    params.into()
+}
+
+/// Decodes the names and values of query string parameters which were passed on as they were received
+pub(crate) fn decode_query_parameters(params: StrMap) -> StrMap {
+    let mut decoded: HashMap<String, Vec<String>> = HashMap::new();
+    for (name, values) in params.0.iter() {
+        decoded
+            .entry(percent_decode(name))
+            .or_default()
+            .extend(values.iter().map(|value| percent_decode(value)));
+    }
+    decoded.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decode_keeps_invalid_escapes() {
+        assert_eq!(percent_decode("caf%C3%A9%2"), "café%2");
+        assert_eq!(percent_decode("100%zz"), "100%zz");
+        assert_eq!(percent_decode("%+F%-1"), "%+F%-1");
+    }
+
+    #[test]
+    fn query_strings_are_split_before_decoding() {
+        let params = parse_query_string("a=a%2Bb&b=a+b&x%26y=z&empty=&flag&&a=%3D");
+        assert_eq!(params.get_all("a"), Some(vec!["a+b", "="]));
+        assert_eq!(params.get("b"), Some("a+b"));
+        assert_eq!(params.get("x&y"), Some("z"));
+        assert_eq!(params.get("empty"), Some(""));
+        assert_eq!(params.get("flag"), Some(""));
+        assert_eq!(params.iter().count(), 5);
+    }
+}
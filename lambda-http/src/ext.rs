@@ -1,9 +1,24 @@
 //! Extension methods for `http::Request` types
 
-use serde::{de::value::Error as SerdeError, Deserialize};
-use std::{error::Error, fmt};
+use serde::{
+    de::{
+        value::{Error as SerdeError, StrDeserializer},
+        IntoDeserializer,
+    },
+    Deserialize,
+};
+#[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+use serde_json::Value;
+#[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+use std::collections::HashMap;
+use std::{
+    error::Error,
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use crate::{request::RequestContext, strmap::StrMap, Body};
+use crate::{negotiate, request::RequestContext, strmap::StrMap, Body, Context};
 
 /// ALB/API gateway pre-parsed http query string parameters
 pub(crate) struct QueryStringParameters(pub(crate) StrMap);
@@ -19,6 +34,47 @@ pub(crate) struct PathParameters(pub(crate) StrMap);
 /// These will always be empty for ALB requests
 pub(crate) struct StageVariables(pub(crate) StrMap);
 
+/// Top level event fields not captured by the request representation
+#[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+pub(crate) struct ExtraFields(pub(crate) HashMap<String, Value>);
+
+/// The body of a request exactly as it was received in the triggering event,
+/// before any base64 decoding
+///
+/// This is useful for verifying signatures computed over the bytes a client sent,
+/// such as those of webhook payloads. Kept as an extension of each request, which
+/// [`RequestExt#raw_body()`](trait.RequestExt.html#tymethod.raw_body) returns
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RawBody {
+    body: Option<String>,
+    is_base64_encoded: bool,
+}
+
+impl RawBody {
+    pub(crate) fn new(body: Option<&str>, is_base64_encoded: bool) -> Self {
+        Self {
+            body: body.map(str::to_owned),
+            is_base64_encoded,
+        }
+    }
+
+    /// Return the body as it was received, base64 encoded if `is_base64_encoded()` is true,
+    /// or an empty string if the event did not carry a body
+    pub fn as_str(&self) -> &str {
+        self.body.as_deref().unwrap_or_default()
+    }
+
+    /// Return true if the event flagged its body as base64 encoded
+    pub fn is_base64_encoded(&self) -> bool {
+        self.is_base64_encoded
+    }
+
+    /// Return true if the event carried a body
+    pub fn is_present(&self) -> bool {
+        self.body.is_some()
+    }
+}
+
 /// Request payload deserialization errors
 ///
 /// Returned by [`RequestExt#payload()`](trait.RequestExt.html#tymethod.payload)
@@ -28,6 +84,10 @@ pub enum PayloadError {
     Json(serde_json::Error),
     /// Returned when `application/x-www-form-urlencoded` bodies fail to deserialize a payload
     WwwFormUrlEncoded(SerdeError),
+    /// Returned when `text/plain` bodies fail to parse into a payload
+    Text(Box<dyn Error + Send + Sync + 'static>),
+    /// Returned when a body's content type is not supported, carrying the received content type
+    UnsupportedMediaType(String),
 }
 
 impl fmt::Display for PayloadError {
@@ -39,6 +99,10 @@ impl fmt::Display for PayloadError {
                 "failed to parse payload from application/x-www-form-urlencoded {}",
                 form
             ),
+            PayloadError::Text(text) => writeln!(f, "failed to parse payload from text/plain {}", text),
+            PayloadError::UnsupportedMediaType(content_type) => {
+                writeln!(f, "unsupported payload content type {}", content_type)
+            }
         }
     }
 }
@@ -48,6 +112,8 @@ impl Error for PayloadError {
         match self {
             PayloadError::Json(json) => Some(json),
             PayloadError::WwwFormUrlEncoded(form) => Some(form),
+            PayloadError::Text(text) => Some(text.as_ref()),
+            PayloadError::UnsupportedMediaType(_) => None,
         }
     }
 }
@@ -116,6 +182,13 @@ pub trait RequestExt {
     ///
     /// No query parameters
     /// will yield an empty `StrMap`.
+    ///
+    /// Names and values are percent-decoded exactly once. A `+` is a literal plus sign
+    /// rather than a space, since that is `application/x-www-form-urlencoded` semantics which
+    /// only apply to form bodies. HTTP API and function URL parameters are parsed from the
+    /// raw query string, which is split on `&` and `=` before decoding, so an encoded `%26`
+    /// stays within its value. REST API parameters arrive already decoded and are left as they
+    /// are, while ALB parameters arrive as they were sent and are decoded.
     fn query_string_parameters(&self) -> StrMap;
 
     /// Configures instance with query string parameters under #[cfg(test)] configurations
@@ -158,6 +231,139 @@ pub trait RequestExt {
     /// Return request context data assocaited with the ALB or API gateway request
     fn request_context(&self) -> RequestContext;
 
+    /// Return the Lambda function invocation `Context` associated with the request
+    ///
+    /// ```rust,no_run
+    /// use lambda_http::{handler, lambda::{self, Context}, IntoResponse, Request, RequestExt};
+    ///
+    /// type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
+    ///     lambda::run(handler(hello)).await?;
+    ///     Ok(())
+    /// }
+    ///
+    /// async fn hello(request: Request, _: Context) -> Result<impl IntoResponse, Error> {
+    ///     let ctx = request.lambda_context();
+    ///     Ok(format!(
+    ///         "request {} has {}ms left",
+    ///         ctx.request_id,
+    ///         ctx.time_remaining().as_millis()
+    ///     ))
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the request was not provided by the `lambda_http` adapter
+    /// or configured with `with_lambda_context`
+    fn lambda_context(&self) -> &Context;
+
+    /// Configures instance with a Lambda function invocation `Context`
+    ///
+    /// This is intended for use in mock testing contexts.
+    fn with_lambda_context(self, context: Context) -> Self;
+
+    /// Return true if this request is an ALB target group health check
+    ///
+    /// Health checks are identified by the `ELB-HealthChecker` user agent and are only
+    /// ever recognized on ALB triggered requests so a client of an API gateway
+    /// can not spoof one.
+    fn is_alb_health_check(&self) -> bool;
+
+    /// Return the body of the request exactly as it was received in the triggering event,
+    /// before any base64 decoding, or `None` if it was not retained
+    ///
+    /// An event without a body yields an empty body. The event's `isBase64Encoded` flag is
+    /// kept alongside, so a signature can be checked against the bytes the client sent
+    ///
+    /// ```rust,no_run
+    /// use lambda_http::{handler, lambda::{self, Context}, IntoResponse, Request, RequestExt};
+    ///
+    /// type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
+    ///     lambda::run(handler(webhook)).await?;
+    ///     Ok(())
+    /// }
+    ///
+    /// async fn webhook(request: Request, _: Context) -> Result<impl IntoResponse, Error> {
+    ///     let raw = request.raw_body().cloned().unwrap_or_default();
+    ///     // verify a signature over the exact bytes the client sent
+    ///     Ok(format!(
+    ///         "received {} bytes, base64 encoded: {}",
+    ///         raw.as_str().len(),
+    ///         raw.is_base64_encoded()
+    ///     ))
+    /// }
+    /// ```
+    ///
+    /// Bodies are not retained when the request was not provided by the `lambda_http`
+    /// adapter, or the adapter was configured with `without_raw_body`
+    fn raw_body(&self) -> Option<&RawBody>;
+
+    /// Return the media type of `supported` most preferred by the request's `Accept` headers
+    ///
+    /// Media ranges are weighted by their `q` parameter, with the most specific range
+    /// matching a type determining its weight. Ties are broken by the order of `supported`.
+    /// Absent or malformed `Accept` headers select the first supported type, and `None` is
+    /// returned when none of `supported` is acceptable.
+    ///
+    /// ```rust
+    /// use lambda_http::{http::header::ACCEPT, Body, Request, RequestExt};
+    ///
+    /// let request: Request = lambda_http::http::Request::builder()
+    ///     .header(ACCEPT, "application/*;q=0.5, text/plain")
+    ///     .body(Body::Empty)
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     request.preferred_media_type(&["application/json", "text/plain"]),
+    ///     Some("text/plain")
+    /// );
+    /// ```
+    fn preferred_media_type<'a>(&self, supported: &[&'a str]) -> Option<&'a str>;
+
+    /// Return the time API Gateway received the request, in milliseconds since the Unix epoch
+    ///
+    /// This is read from the request context's `requestTimeEpoch` for REST API events and
+    /// `timeEpoch` for HTTP API and function URL events. Events lacking the epoch field fall
+    /// back to the common log format `requestTime` or `time` fields, which are only precise
+    /// to the second. ALB events carry no request time, so `None` is returned for them.
+    fn request_time_epoch(&self) -> Option<u64>;
+
+    /// Return the time API Gateway received the request
+    ///
+    /// See [`request_time_epoch`](#tymethod.request_time_epoch) for where this time is read from.
+    fn request_time(&self) -> Option<SystemTime>;
+
+    /// Return the time elapsed between API Gateway receiving the request and `now`
+    ///
+    /// This measures the delay between the edge and the function, when a request time is
+    /// available and `now` is not earlier than it.
+    ///
+    /// ```rust
+    /// use lambda_http::{Request, RequestExt};
+    /// use std::time::SystemTime;
+    ///
+    /// fn log_latency(request: &Request) {
+    ///     if let Some(latency) = request.gateway_latency(SystemTime::now()) {
+    ///         println!("request reached the function after {:?}", latency);
+    ///     }
+    /// }
+    /// ```
+    fn gateway_latency(&self, now: SystemTime) -> Option<Duration>;
+
+    /// Return the top level fields of the triggering event which `lambda_http`
+    /// does not otherwise model. Fields of the request context are captured
+    /// by the `extra` field of each [`RequestContext`](request/enum.RequestContext.html) variant.
+    ///
+    /// This is only available with the `extra-fields` feature, and not when the
+    /// `strict` feature is also enabled, which rejects unknown fields instead.
+    #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+    fn extra_fields(&self) -> HashMap<String, Value>;
+
     /// Return the Result of a payload parsed into a serde Deserializeable
     /// type
     ///
@@ -170,6 +376,45 @@ pub trait RequestExt {
     fn payload<D>(&self) -> Result<Option<D>, PayloadError>
     where
         for<'de> D: Deserialize<'de>;
+
+    /// Return the Result of a payload parsed into a serde Deserializeable
+    /// type according to the request's `Content-Type`
+    ///
+    /// * `application/json` bodies, and bodies without a content type, are parsed as JSON
+    /// * `application/x-www-form-urlencoded` bodies are parsed as forms
+    /// * `text/plain` bodies are deserialized from their text, so `D` should be a string type
+    ///
+    /// Content type parameters such as `charset` are ignored, and base64 encoded bodies
+    /// are decoded before they are parsed. Other content types result in a
+    /// `PayloadError::UnsupportedMediaType` naming the received type.
+    /// If no body is provided, `Ok(None)` will be returned.
+    fn payload_auto<D>(&self) -> Result<Option<D>, PayloadError>
+    where
+        for<'de> D: Deserialize<'de>;
+
+    /// Return the Result of a `text/plain` payload parsed with `FromStr`
+    ///
+    /// Bodies without a content type are parsed as text as well. Other content types result
+    /// in a `PayloadError::UnsupportedMediaType` naming the received type.
+    /// If no body is provided, `Ok(None)` will be returned.
+    fn payload_text<T>(&self) -> Result<Option<T>, PayloadError>
+    where
+        T: FromStr,
+        T::Err: Into<Box<dyn Error + Send + Sync + 'static>>;
+}
+
+/// Returns the lowercased media type of a request's `Content-Type` without its parameters
+fn media_type(request: &http::Request<Body>) -> Option<Result<String, PayloadError>> {
+    request.headers().get(http::header::CONTENT_TYPE).map(|ct| {
+        ct.to_str()
+            .map(|ct| ct.split(';').next().unwrap_or_default().trim().to_ascii_lowercase())
+            .map_err(|_| PayloadError::UnsupportedMediaType(String::from_utf8_lossy(ct.as_bytes()).into_owned()))
+    })
+}
+
+/// Returns the text of a body
+fn body_text(body: &Body) -> Result<&str, PayloadError> {
+    std::str::from_utf8(body.as_ref()).map_err(|e| PayloadError::Text(Box::new(e)))
 }
 
 impl RequestExt for http::Request<Body> {
@@ -229,6 +474,58 @@ impl RequestExt for http::Request<Body> {
             .expect("Request did not contain a request context")
     }
 
+    fn lambda_context(&self) -> &Context {
+        self.extensions()
+            .get::<Context>()
+            .expect("Request did not contain a lambda context")
+    }
+
+    fn with_lambda_context(self, context: Context) -> Self {
+        let mut s = self;
+        s.extensions_mut().insert(context);
+        s
+    }
+
+    fn is_alb_health_check(&self) -> bool {
+        matches!(self.extensions().get::<RequestContext>(), Some(RequestContext::Alb(_)))
+            && self
+                .headers()
+                .get(http::header::USER_AGENT)
+                .and_then(|ua| ua.to_str().ok())
+                .is_some_and(|ua| ua.starts_with("ELB-HealthChecker/"))
+    }
+
+    fn raw_body(&self) -> Option<&RawBody> {
+        self.extensions().get::<RawBody>()
+    }
+
+    fn preferred_media_type<'a>(&self, supported: &[&'a str]) -> Option<&'a str> {
+        negotiate::preferred_media_type(self.headers(), supported)
+    }
+
+    fn request_time_epoch(&self) -> Option<u64> {
+        self.extensions()
+            .get::<RequestContext>()
+            .and_then(RequestContext::request_time_epoch)
+    }
+
+    fn request_time(&self) -> Option<SystemTime> {
+        self.request_time_epoch()
+            .map(|epoch| UNIX_EPOCH + Duration::from_millis(epoch))
+    }
+
+    fn gateway_latency(&self, now: SystemTime) -> Option<Duration> {
+        now.duration_since(self.request_time()?).ok()
+    }
+
+    #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+    fn extra_fields(&self) -> HashMap<String, Value> {
+        self.extensions()
+            .get::<ExtraFields>()
+            .map(|ext| ext.0.clone())
+            .unwrap_or_default()
+    }
+
     fn payload<D>(&self) -> Result<Option<D>, PayloadError>
     where
         for<'de> D: Deserialize<'de>,
@@ -246,12 +543,54 @@ impl RequestExt for http::Request<Body> {
             })
             .unwrap_or_else(|| Ok(None))
     }
+
+    fn payload_auto<D>(&self) -> Result<Option<D>, PayloadError>
+    where
+        for<'de> D: Deserialize<'de>,
+    {
+        if self.body().is_empty() {
+            return Ok(None);
+        }
+        match media_type(self).transpose()?.as_deref() {
+            None | Some("application/json") => serde_json::from_slice::<D>(self.body().as_ref())
+                .map_err(PayloadError::Json)
+                .map(Some),
+            Some("application/x-www-form-urlencoded") => serde_urlencoded::from_bytes::<D>(self.body().as_ref())
+                .map_err(PayloadError::WwwFormUrlEncoded)
+                .map(Some),
+            Some("text/plain") => {
+                let text: StrDeserializer<'_, SerdeError> = body_text(self.body())?.into_deserializer();
+                D::deserialize(text)
+                    .map_err(|e| PayloadError::Text(Box::new(e)))
+                    .map(Some)
+            }
+            Some(other) => Err(PayloadError::UnsupportedMediaType(other.into())),
+        }
+    }
+
+    fn payload_text<T>(&self) -> Result<Option<T>, PayloadError>
+    where
+        T: FromStr,
+        T::Err: Into<Box<dyn Error + Send + Sync + 'static>>,
+    {
+        if self.body().is_empty() {
+            return Ok(None);
+        }
+        match media_type(self).transpose()?.as_deref() {
+            None | Some("text/plain") => body_text(self.body())?
+                .parse::<T>()
+                .map_err(|e| PayloadError::Text(e.into()))
+                .map(Some),
+            Some(other) => Err(PayloadError::UnsupportedMediaType(other.into())),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Body, Request, RequestExt};
+    use crate::{Body, Context, Request, RequestExt};
     use serde_derive::Deserialize;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
     #[test]
     fn requests_can_mock_query_string_parameters_ext() {
@@ -280,6 +619,83 @@ mod tests {
         assert_eq!(request.stage_variables(), mocked.into());
     }
 
+    #[test]
+    fn requests_can_mock_lambda_context_ext() {
+        let mut context = Context::default();
+        context.request_id = "my-request-id".into();
+        context.deadline = 1_542_409_706_888;
+        let request = Request::default().with_lambda_context(context.clone());
+        assert_eq!(request.lambda_context(), &context);
+    }
+
+    #[test]
+    fn requests_without_context_are_not_alb_health_checks() {
+        let request = http::Request::builder()
+            .header("User-Agent", "ELB-HealthChecker/2.0")
+            .body(Body::Empty)
+            .expect("failed to build request");
+        assert!(!request.is_alb_health_check());
+    }
+
+    #[test]
+    fn alb_health_checks_are_detected() {
+        let request = crate::request::from_str(include_str!("../tests/data/alb_health_check.json"))
+            .expect("failed to parse request");
+        assert!(request.is_alb_health_check());
+    }
+
+    #[test]
+    fn apigw_requests_have_epoch_request_times() {
+        let request = crate::request::from_str(include_str!("../tests/data/apigw_proxy_request_no_identity.json"))
+            .expect("failed to parse request");
+        assert_eq!(request.request_time_epoch(), Some(1_586_455_411_600));
+        assert_eq!(
+            request.request_time(),
+            Some(UNIX_EPOCH + Duration::from_millis(1_586_455_411_600))
+        );
+    }
+
+    #[test]
+    fn apigw_v2_requests_have_epoch_request_times() {
+        let request = crate::request::from_str(include_str!("../tests/data/apigw_v2_proxy_request.json"))
+            .expect("failed to parse request");
+        assert_eq!(request.request_time_epoch(), Some(1_583_348_638_390));
+    }
+
+    #[test]
+    fn requests_without_epoch_fall_back_to_common_log_request_times() {
+        let mut event: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/data/apigw_proxy_request_no_identity.json"))
+                .expect("failed to parse fixture");
+        event["requestContext"]
+            .as_object_mut()
+            .expect("fixture has no request context")
+            .remove("requestTimeEpoch");
+        let request = crate::request::from_str(&event.to_string()).expect("failed to parse request");
+        // "09/Apr/2020:18:03:31 +0000" is only precise to the second
+        assert_eq!(request.request_time_epoch(), Some(1_586_455_411_000));
+    }
+
+    #[test]
+    fn alb_requests_have_no_request_time() {
+        let request =
+            crate::request::from_str(include_str!("../tests/data/alb_request.json")).expect("failed to parse request");
+        assert_eq!(request.request_time(), None);
+        assert_eq!(request.gateway_latency(SystemTime::now()), None);
+    }
+
+    #[test]
+    fn gateway_latency_measures_time_since_request() {
+        let request = crate::request::from_str(include_str!("../tests/data/apigw_v2_proxy_request.json"))
+            .expect("failed to parse request");
+        let received = UNIX_EPOCH + Duration::from_millis(1_583_348_638_390);
+        assert_eq!(
+            request.gateway_latency(received + Duration::from_millis(250)),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(request.gateway_latency(received - Duration::from_millis(1)), None);
+    }
+
     #[test]
     fn requests_have_form_post_parsable_payloads() {
         #[derive(Deserialize, PartialEq, Debug)]
@@ -335,4 +751,116 @@ mod tests {
         let payload: Option<Payload> = request.payload().unwrap_or_default();
         assert_eq!(payload, None);
     }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct AutoPayload {
+        foo: String,
+        baz: usize,
+    }
+
+    fn auto_payload_request(content_type: Option<&str>, body: &str) -> Request {
+        let mut builder = http::Request::builder();
+        if let Some(content_type) = content_type {
+            builder = builder.header("Content-Type", content_type);
+        }
+        builder.body(Body::from(body)).expect("failed to build request")
+    }
+
+    #[test]
+    fn requests_have_auto_parsed_json_payloads() {
+        let request = auto_payload_request(Some("application/json; charset=utf-8"), r#"{"foo":"bar","baz":2}"#);
+        assert_eq!(
+            request.payload_auto::<AutoPayload>().expect("failed to parse payload"),
+            Some(AutoPayload {
+                foo: "bar".into(),
+                baz: 2
+            })
+        );
+    }
+
+    #[test]
+    fn requests_have_auto_parsed_form_payloads() {
+        let request = auto_payload_request(
+            Some("Application/X-WWW-Form-Urlencoded; charset=UTF-8"),
+            "foo=bar&baz=2",
+        );
+        assert_eq!(
+            request.payload_auto::<AutoPayload>().expect("failed to parse payload"),
+            Some(AutoPayload {
+                foo: "bar".into(),
+                baz: 2
+            })
+        );
+    }
+
+    #[test]
+    fn requests_have_auto_parsed_text_payloads() {
+        let request = auto_payload_request(Some("text/plain; charset=utf-8"), "hello");
+        assert_eq!(
+            request.payload_auto::<String>().expect("failed to parse payload"),
+            Some("hello".to_string())
+        );
+        assert_eq!(
+            request.payload_text::<String>().expect("failed to parse payload"),
+            Some("hello".to_string())
+        );
+        let request = auto_payload_request(Some("text/plain"), "42");
+        assert_eq!(
+            request.payload_text::<u32>().expect("failed to parse payload"),
+            Some(42)
+        );
+        match auto_payload_request(Some("text/plain"), "forty two").payload_text::<u32>() {
+            Err(crate::ext::PayloadError::Text(_)) => (),
+            other => panic!("expected a text error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn requests_omitting_content_types_auto_parse_json_payloads() {
+        let request = auto_payload_request(None, r#"{"foo":"bar","baz":2}"#);
+        assert_eq!(
+            request.payload_auto::<AutoPayload>().expect("failed to parse payload"),
+            Some(AutoPayload {
+                foo: "bar".into(),
+                baz: 2
+            })
+        );
+        assert!(auto_payload_request(None, "")
+            .payload_auto::<AutoPayload>()
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn requests_with_unsupported_content_types_fail_auto_parsing() {
+        let request = auto_payload_request(Some("application/xml"), "<foo/>");
+        match request.payload_auto::<AutoPayload>() {
+            Err(crate::ext::PayloadError::UnsupportedMediaType(content_type)) => {
+                assert_eq!(content_type, "application/xml")
+            }
+            other => panic!("expected an unsupported media type error, got {:?}", other),
+        }
+        match request.payload_text::<String>() {
+            Err(crate::ext::PayloadError::UnsupportedMediaType(content_type)) => {
+                assert_eq!(content_type, "application/xml")
+            }
+            other => panic!("expected an unsupported media type error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn requests_auto_parse_base64_encoded_payloads() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Item {
+            item: usize,
+        }
+        let request = crate::request::from_str(include_str!(
+            "../tests/data/apigw_v2_proxy_request_lambda_authorizer.json"
+        ))
+        .expect("failed to parse request");
+        assert_eq!(
+            request.payload_auto::<Item>().expect("failed to parse payload"),
+            Some(Item { item: 42 })
+        );
+    }
 }
@@ -95,11 +95,22 @@ pub use lambda::{self, Context};
 pub use lambda_attributes::lambda;
 
 mod body;
+mod encoding;
 pub mod ext;
+mod negotiate;
 pub mod request;
 mod response;
+pub mod responses;
+pub mod router;
+pub mod streaming;
 mod strmap;
-pub use crate::{body::Body, ext::RequestExt, response::IntoResponse, strmap::StrMap};
+pub use crate::{
+    body::Body,
+    ext::RequestExt,
+    negotiate::Negotiated,
+    response::{IntoResponse, PreserveHeaderCase},
+    strmap::StrMap,
+};
 use crate::{request::LambdaRequest, response::LambdaResponse};
 use std::{
     future::Future,
@@ -129,7 +140,11 @@ pub trait Handler: Sized {
 
 /// Adapts a [`Handler`](trait.Handler.html) to the `lambda::run` interface
 pub fn handler<H: Handler>(handler: H) -> Adapter<H> {
-    Adapter { handler }
+    Adapter {
+        handler,
+        alb_health_check_response: None,
+        retain_raw_body: true,
+    }
 }
 
 /// An implementation of `Handler` for a given closure return a `Future` representing the computed response
@@ -150,7 +165,15 @@ where
 #[doc(hidden)]
 pub struct TransformResponse<R, E> {
     is_alb: bool,
-    fut: Pin<Box<dyn Future<Output = Result<R, E>>>>,
+    state: TransformState<R, E>,
+}
+
+/// The source of a `TransformResponse`'s eventual response
+enum TransformState<R, E> {
+    /// The response is computed by the wrapped handler
+    Handler(Pin<Box<dyn Future<Output = Result<R, E>>>>),
+    /// The response was decided before the handler needed to run
+    Ready(Option<Response<Body>>),
 }
 
 impl<R, E> Future for TransformResponse<R, E>
@@ -159,11 +182,18 @@ where
 {
     type Output = Result<LambdaResponse, E>;
     fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext) -> Poll<Self::Output> {
-        match self.fut.as_mut().poll(cx) {
-            Poll::Ready(result) => {
-                Poll::Ready(result.map(|resp| LambdaResponse::from_response(self.is_alb, resp.into_response())))
-            }
-            Poll::Pending => Poll::Pending,
+        let is_alb = self.is_alb;
+        match &mut self.state {
+            TransformState::Handler(fut) => match fut.as_mut().poll(cx) {
+                Poll::Ready(result) => {
+                    Poll::Ready(result.map(|resp| LambdaResponse::from_response(is_alb, resp.into_response())))
+                }
+                Poll::Pending => Poll::Pending,
+            },
+            TransformState::Ready(resp) => Poll::Ready(Ok(LambdaResponse::from_response(
+                is_alb,
+                resp.take().expect("TransformResponse polled after completion"),
+            ))),
         }
     }
 }
@@ -177,6 +207,50 @@ where
 /// for a larger explaination of why this is nessessary
 pub struct Adapter<H: Handler> {
     handler: H,
+    alb_health_check_response: Option<Body>,
+    retain_raw_body: bool,
+}
+
+impl<H: Handler> Adapter<H> {
+    /// Answer ALB health check requests with a `200 OK` response carrying the provided body
+    /// without invoking the wrapped handler.
+    ///
+    /// This is off by default. Only ALB triggered events are considered health checks,
+    /// see [`RequestExt#is_alb_health_check()`](trait.RequestExt.html#tymethod.is_alb_health_check)
+    ///
+    /// ```rust,no_run
+    /// use lambda_http::{handler, lambda};
+    ///
+    /// type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
+    ///     let func = handler(|request, context| async { Ok("👋 world!") }).with_alb_health_check_response("OK");
+    ///     lambda::run(func).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_alb_health_check_response<B>(self, body: B) -> Self
+    where
+        B: Into<Body>,
+    {
+        Adapter {
+            alb_health_check_response: Some(body.into()),
+            ..self
+        }
+    }
+
+    /// Do not retain a copy of each request's undecoded body for
+    /// [`RequestExt#raw_body()`](trait.RequestExt.html#tymethod.raw_body)
+    ///
+    /// Retention is on by default. Turning it off avoids holding request bodies
+    /// in memory twice for functions which do not need the raw body.
+    pub fn without_raw_body(self) -> Self {
+        Adapter {
+            retain_raw_body: false,
+            ..self
+        }
+    }
 }
 
 impl<H: Handler> Handler for Adapter<H> {
@@ -193,7 +267,153 @@ impl<H: Handler> LambdaHandler<LambdaRequest<'_>, LambdaResponse> for Adapter<H>
     type Fut = TransformResponse<H::Response, Self::Error>;
     fn call(&mut self, event: LambdaRequest<'_>, context: Context) -> Self::Fut {
         let is_alb = event.is_alb();
-        let fut = Box::pin(self.handler.call(event.into(), context));
-        TransformResponse { is_alb, fut }
+        let mut request = event.into_request(self.retain_raw_body);
+        request.extensions_mut().insert(context.clone());
+        let state = match &self.alb_health_check_response {
+            Some(body) if request.is_alb_health_check() => TransformState::Ready(Some(Response::new(body.clone()))),
+            _ => TransformState::Handler(Box::pin(self.handler.call(request, context))),
+        };
+        TransformResponse { is_alb, state }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{request::LambdaRequest, Error, RequestExt};
+    use serde_json::Value;
+
+    async fn handled(_: Request, _: Context) -> Result<&'static str, Error> {
+        Ok("handled")
+    }
+
+    fn health_check_from(fixture: &str) -> LambdaRequest<'static> {
+        let mut event: Value = serde_json::from_str(fixture).expect("failed to parse fixture");
+        let headers = event["headers"].as_object_mut().expect("fixture has no headers");
+        headers.remove("User-Agent");
+        headers.insert("user-agent".into(), "ELB-HealthChecker/2.0".into());
+        serde_json::from_value(event).expect("failed to deserialize request")
+    }
+
+    #[tokio::test]
+    async fn adapter_provides_lambda_context() {
+        let mut adapter =
+            handler(|request: Request, _| async move { Ok::<_, Error>(request.lambda_context().request_id.clone()) });
+        let event = serde_json::from_str(include_str!("../tests/data/apigw_proxy_request.json"))
+            .expect("failed to deserialize request");
+        let mut context = Context::default();
+        context.request_id = "my-request-id".into();
+        let response = LambdaHandler::call(&mut adapter, event, context)
+            .await
+            .expect("failed to handle request");
+        assert_eq!(response.body, Some(Body::from("my-request-id")));
+    }
+
+    fn hmac_sha256(bytes: &[u8]) -> Vec<u8> {
+        use hmac::{Hmac, Mac, NewMac};
+        let mut mac = Hmac::<sha2::Sha256>::new_varkey(b"webhook-secret").expect("invalid key");
+        mac.update(bytes);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    async fn raw_body_signature(fixture: &str, is_base64_encoded: bool) -> (Vec<u8>, Vec<u8>) {
+        let mut adapter = handler(move |request: Request, _| async move {
+            let raw = request.raw_body().expect("raw body was not retained");
+            assert_eq!(raw.is_base64_encoded(), is_base64_encoded);
+            Ok::<_, Error>(Body::from(hmac_sha256(raw.as_str().as_bytes())))
+        });
+        let event = serde_json::from_str(fixture).expect("failed to deserialize request");
+        let response = LambdaHandler::call(&mut adapter, event, Context::default())
+            .await
+            .expect("failed to handle request");
+        let original: Value = serde_json::from_str(fixture).expect("failed to deserialize fixture");
+        let expected = hmac_sha256(original["body"].as_str().expect("fixture has no body").as_bytes());
+        let signed = match response.body {
+            Some(Body::Binary(signature)) => signature,
+            other => panic!("expected a signature, got {:?}", other),
+        };
+        (signed, expected)
+    }
+
+    #[tokio::test]
+    async fn raw_body_signatures_match_plain_event_bodies() {
+        let (signed, expected) = raw_body_signature(
+            include_str!("../tests/data/apigw_multi_value_proxy_request.json"),
+            false,
+        )
+        .await;
+        assert_eq!(signed, expected);
+    }
+
+    #[tokio::test]
+    async fn raw_body_signatures_match_base64_event_bodies() {
+        let (signed, expected) = raw_body_signature(
+            include_str!("../tests/data/apigw_v2_proxy_request_lambda_authorizer.json"),
+            true,
+        )
+        .await;
+        assert_eq!(signed, expected);
+    }
+
+    #[tokio::test]
+    async fn raw_bodies_retain_base64_flag() {
+        let mut adapter = handler(|request: Request, _| async move {
+            Ok::<_, Error>(format!(
+                "{}",
+                request.raw_body().is_some_and(ext::RawBody::is_base64_encoded)
+            ))
+        });
+        let event = serde_json::from_str(include_str!(
+            "../tests/data/apigw_v2_proxy_request_lambda_authorizer.json"
+        ))
+        .expect("failed to deserialize request");
+        let response = LambdaHandler::call(&mut adapter, event, Context::default())
+            .await
+            .expect("failed to handle request");
+        assert_eq!(response.body, Some(Body::from("true")));
+    }
+
+    #[tokio::test]
+    async fn raw_body_retention_can_be_disabled() {
+        let mut adapter =
+            handler(|request: Request, _| async move { Ok::<_, Error>(format!("{}", request.raw_body().is_none())) })
+                .without_raw_body();
+        let event = serde_json::from_str(include_str!("../tests/data/apigw_proxy_request.json"))
+            .expect("failed to deserialize request");
+        let response = LambdaHandler::call(&mut adapter, event, Context::default())
+            .await
+            .expect("failed to handle request");
+        assert_eq!(response.body, Some(Body::from("true")));
+    }
+
+    #[tokio::test]
+    async fn alb_health_checks_short_circuit_when_enabled() {
+        let mut adapter = handler(handled).with_alb_health_check_response("healthy");
+        let event = health_check_from(include_str!("../tests/data/alb_health_check.json"));
+        let response = LambdaHandler::call(&mut adapter, event, Context::default())
+            .await
+            .expect("failed to handle request");
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.body, Some(Body::from("healthy")));
+    }
+
+    #[tokio::test]
+    async fn alb_health_checks_reach_handler_when_disabled() {
+        let mut adapter = handler(handled);
+        let event = health_check_from(include_str!("../tests/data/alb_health_check.json"));
+        let response = LambdaHandler::call(&mut adapter, event, Context::default())
+            .await
+            .expect("failed to handle request");
+        assert_eq!(response.body, Some(Body::from("handled")));
+    }
+
+    #[tokio::test]
+    async fn apigw_requests_spoofing_health_checks_reach_handler() {
+        let mut adapter = handler(handled).with_alb_health_check_response("healthy");
+        let event = health_check_from(include_str!("../tests/data/apigw_proxy_request.json"));
+        let response = LambdaHandler::call(&mut adapter, event, Context::default())
+            .await
+            .expect("failed to handle request");
+        assert_eq!(response.body, Some(Body::from("handled")));
     }
 }
@@ -0,0 +1,341 @@
+//! Content negotiation driven by `Accept` request headers
+
+use http::{
+    header::{HeaderMap, ACCEPT, CONTENT_TYPE},
+    Response, StatusCode,
+};
+use serde::Serialize;
+use std::fmt::Display;
+
+use crate::{Body, IntoResponse, Request, RequestExt};
+
+/// The media types a [`Negotiated`](struct.Negotiated.html) value can be rendered as, in order of preference
+///
+/// `text/html` is left out, since `Display` output is not escaped for HTML.
+const NEGOTIATED_MEDIA_TYPES: &[&str] = &["application/json", "text/plain"];
+
+/// A single `Accept` header media range with its weight
+#[derive(Debug, PartialEq)]
+struct MediaRange<'a> {
+    kind: &'a str,
+    subtype: &'a str,
+    /// weight in thousandths, the precision of an RFC 7231 qvalue
+    quality: u16,
+}
+
+impl<'a> MediaRange<'a> {
+    /// Return how specifically this range matches a media type, if it does at all
+    fn specificity(&self, kind: &str, subtype: &str) -> Option<u8> {
+        match (self.kind, self.subtype) {
+            ("*", "*") => Some(0),
+            (k, "*") if k.eq_ignore_ascii_case(kind) => Some(1),
+            (k, s) if k.eq_ignore_ascii_case(kind) && s.eq_ignore_ascii_case(subtype) => Some(2),
+            _ => None,
+        }
+    }
+}
+
+/// Parses an RFC 7231 qvalue into thousandths
+fn parse_quality(value: &str) -> Option<u16> {
+    let (whole, fraction) = match value.find('.') {
+        Some(dot) => (&value[..dot], &value[dot + 1..]),
+        None => (value, ""),
+    };
+    if fraction.len() > 3 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let thousandths = format!("{:0<3}", fraction).parse::<u16>().ok()?;
+    match whole {
+        "0" => Some(thousandths),
+        "1" if thousandths == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+/// Parses a single `media-range [ accept-params ]` element
+fn parse_media_range(element: &str) -> Option<MediaRange<'_>> {
+    let mut parts = element.split(';').map(str::trim);
+    let range = parts.next()?;
+    let slash = range.find('/')?;
+    let (kind, subtype) = (&range[..slash], &range[slash + 1..]);
+    if kind.is_empty() || subtype.is_empty() || (kind == "*" && subtype != "*") {
+        return None;
+    }
+    let mut quality = 1000;
+    for param in parts {
+        let eq = param.find('=')?;
+        let (name, value) = (param[..eq].trim(), param[eq + 1..].trim());
+        if name.eq_ignore_ascii_case("q") {
+            quality = parse_quality(value)?;
+        }
+    }
+    Some(MediaRange { kind, subtype, quality })
+}
+
+/// Parses all `Accept` headers, returning `None` when any of them is malformed
+fn parse_accept(headers: &HeaderMap) -> Option<Vec<MediaRange<'_>>> {
+    let mut ranges = Vec::new();
+    for value in headers.get_all(ACCEPT) {
+        for element in value.to_str().ok()?.split(',').map(str::trim) {
+            if !element.is_empty() {
+                ranges.push(parse_media_range(element)?);
+            }
+        }
+    }
+    Some(ranges)
+}
+
+/// Selects the supported media type most preferred by the `Accept` headers
+///
+/// Absent, empty or malformed `Accept` headers select the first supported type.
+/// Ties are broken by the order of `supported`.
+pub(crate) fn preferred_media_type<'a>(headers: &HeaderMap, supported: &[&'a str]) -> Option<&'a str> {
+    let ranges = match parse_accept(headers) {
+        Some(ranges) if !ranges.is_empty() => ranges,
+        _ => return supported.first().copied(),
+    };
+    let mut best: Option<(&'a str, u16)> = None;
+    for candidate in supported {
+        let essence = candidate.split(';').next().unwrap_or_default().trim();
+        let mut halves = essence.splitn(2, '/');
+        let (kind, subtype) = (halves.next().unwrap_or_default(), halves.next().unwrap_or_default());
+        // the most specific matching range determines a type's weight
+        let quality = ranges
+            .iter()
+            .filter_map(|range| range.specificity(kind, subtype).map(|s| (s, range.quality)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, quality)| quality)
+            .unwrap_or_default();
+        if quality > 0 && best.is_none_or(|(_, q)| quality > q) {
+            best = Some((candidate, quality));
+        }
+    }
+    best.map(|(candidate, _)| candidate)
+}
+
+/// A value rendered as JSON or plain text depending on the request's `Accept` headers
+///
+/// JSON is rendered with `serde_json` and text with the value's `Display` implementation.
+/// JSON is preferred when a request does not state a preference. Requests accepting none of
+/// these types get an empty `406 Not Acceptable` response, and values which fail to serialize
+/// an empty `500 Internal Server Error` response.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use lambda_http::{handler, lambda::{self, Context}, IntoResponse, Negotiated, Request};
+/// use serde_derive::Serialize;
+/// use std::fmt;
+///
+/// type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+///
+/// #[derive(Serialize)]
+/// struct Greeting {
+///     message: String,
+/// }
+///
+/// impl fmt::Display for Greeting {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         f.write_str(&self.message)
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     lambda::run(handler(hello)).await?;
+///     Ok(())
+/// }
+///
+/// async fn hello(request: Request, _: Context) -> Result<impl IntoResponse, Error> {
+///     let greeting = Greeting {
+///         message: "👋 world".into(),
+///     };
+///     Ok(Negotiated::new(&request, greeting))
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Negotiated<T> {
+    media_type: Option<&'static str>,
+    value: T,
+}
+
+impl<T> Negotiated<T> {
+    /// Return a value which will be rendered as the media type preferred by a request
+    pub fn new(request: &Request, value: T) -> Self {
+        Self {
+            media_type: request.preferred_media_type(NEGOTIATED_MEDIA_TYPES),
+            value,
+        }
+    }
+}
+
+impl<T> IntoResponse for Negotiated<T>
+where
+    T: Serialize + Display,
+{
+    fn into_response(self) -> Response<Body> {
+        let builder = Response::builder();
+        match self.media_type {
+            Some("application/json") => match serde_json::to_string(&self.value) {
+                Ok(json) => builder.header(CONTENT_TYPE, "application/json").body(json.into()),
+                Err(_) => builder.status(StatusCode::INTERNAL_SERVER_ERROR).body(Body::Empty),
+            },
+            Some(text) => builder
+                .header(CONTENT_TYPE, format!("{}; charset=utf-8", text))
+                .body(self.value.to_string().into()),
+            None => builder.status(StatusCode::NOT_ACCEPTABLE).body(Body::Empty),
+        }
+        .expect("unable to build http::Response")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::HeaderValue;
+
+    fn accepting(accept: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_str(accept).expect("invalid header"));
+        headers
+    }
+
+    const SUPPORTED: &[&str] = &["application/json", "text/plain"];
+
+    #[test]
+    fn absent_accept_selects_first_supported() {
+        assert_eq!(
+            preferred_media_type(&HeaderMap::new(), SUPPORTED),
+            Some("application/json")
+        );
+        assert_eq!(preferred_media_type(&HeaderMap::new(), &[]), None);
+    }
+
+    #[test]
+    fn any_selects_first_supported() {
+        assert_eq!(
+            preferred_media_type(&accepting("*/*"), SUPPORTED),
+            Some("application/json")
+        );
+    }
+
+    #[test]
+    fn weights_select_preferred_type() {
+        assert_eq!(
+            preferred_media_type(&accepting("application/*;q=0.5, text/plain"), SUPPORTED),
+            Some("text/plain")
+        );
+        assert_eq!(
+            preferred_media_type(&accepting("text/plain;q=0.2, application/*;q=0.5"), SUPPORTED),
+            Some("application/json")
+        );
+    }
+
+    #[test]
+    fn most_specific_range_determines_weight() {
+        assert_eq!(
+            preferred_media_type(&accepting("*/*;q=0.9, application/json;q=0.1"), SUPPORTED),
+            Some("text/plain")
+        );
+    }
+
+    #[test]
+    fn zero_weight_excludes_types() {
+        assert_eq!(
+            preferred_media_type(&accepting("application/json;q=0, text/*;q=0"), SUPPORTED),
+            None
+        );
+    }
+
+    #[test]
+    fn malformed_accept_is_treated_as_absent() {
+        for accept in &[
+            "json",
+            "text/plain;q=2",
+            "*/plain",
+            "text/plain;q=0.0001",
+            "text/html;level",
+        ] {
+            assert_eq!(
+                preferred_media_type(&accepting(accept), SUPPORTED),
+                Some("application/json"),
+                "{}",
+                accept
+            );
+        }
+    }
+
+    #[test]
+    fn matching_is_case_insensitive() {
+        assert_eq!(
+            preferred_media_type(&accepting("TEXT/Plain"), SUPPORTED),
+            Some("text/plain")
+        );
+    }
+
+    #[derive(Serialize)]
+    struct Greeting {
+        message: &'static str,
+    }
+
+    impl Display for Greeting {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(self.message)
+        }
+    }
+
+    fn negotiate(accept: &str) -> Response<Body> {
+        let request = http::Request::builder()
+            .header(ACCEPT, accept)
+            .body(Body::Empty)
+            .expect("failed to build request");
+        Negotiated::new(&request, Greeting { message: "hello" }).into_response()
+    }
+
+    #[test]
+    fn negotiated_renders_json() {
+        let response = negotiate("application/json");
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(response.body(), &Body::from(r#"{"message":"hello"}"#));
+    }
+
+    #[test]
+    fn negotiated_renders_text() {
+        let response = negotiate("text/html, text/plain;q=0.5");
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/plain; charset=utf-8");
+        assert_eq!(response.body(), &Body::from("hello"));
+    }
+
+    #[test]
+    fn negotiated_rejects_unacceptable_requests() {
+        for accept in &["image/png", "text/html"] {
+            let response = negotiate(accept);
+            assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE, "{}", accept);
+        }
+    }
+
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("unserializable"))
+        }
+    }
+
+    impl Display for Unserializable {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("unserializable")
+        }
+    }
+
+    #[test]
+    fn negotiated_fails_values_which_do_not_serialize() {
+        let request = http::Request::builder()
+            .header(ACCEPT, "application/json")
+            .body(Body::Empty)
+            .expect("failed to build request");
+        let response = Negotiated::new(&request, Unserializable).into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.body(), &Body::Empty);
+    }
+}
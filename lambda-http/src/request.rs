@@ -3,14 +3,18 @@
 //! Typically these are exposed via the `request_context`
 //! request extension method provided by [lambda_http::RequestExt](../trait.RequestExt.html)
 //!
+use lambda_events::time::days_from_civil;
 use serde::de::{Deserialize, Deserializer, Error as DeError, MapAccess, Visitor};
 use serde_derive::Deserialize;
 use serde_json::{error::Error as JsonError, Value};
-use std::{borrow::Cow, collections::HashMap, fmt, io::Read, mem};
+use std::{borrow::Cow, collections::HashMap, convert::TryFrom, fmt, io::Read, mem};
 
+#[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+use crate::ext::ExtraFields;
 use crate::{
     body::Body,
-    ext::{PathParameters, QueryStringParameters, StageVariables},
+    encoding::{decode_query_parameters, parse_query_string},
+    ext::{PathParameters, QueryStringParameters, RawBody, StageVariables},
     strmap::StrMap,
 };
 
@@ -19,62 +23,79 @@ use crate::{
 ///
 /// This is not intended to be a type consumed by crate users directly. The order
 /// of the variants are notable. Serde will try to deserialize in this order.
+///
+/// Fields which AWS may omit are either optional or defaulted and unknown fields
+/// are ignored, unless the `strict` feature is enabled, in which case they are rejected.
 #[doc(hidden)]
 #[derive(Deserialize, Debug)]
 #[serde(untagged)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+// only ever short lived as an intermediate of the conversion into an http::Request
+#[allow(clippy::large_enum_variant)]
 pub enum LambdaRequest<'a> {
     #[serde(rename_all = "camelCase")]
     ApiGatewayV2 {
         version: Cow<'a, str>,
+        #[serde(default)]
         route_key: Cow<'a, str>,
         raw_path: Cow<'a, str>,
+        #[serde(default)]
         raw_query_string: Cow<'a, str>,
         cookies: Option<Vec<Cow<'a, str>>>,
-        #[serde(deserialize_with = "deserialize_headers")]
+        #[serde(default, deserialize_with = "deserialize_headers")]
         headers: http::HeaderMap,
-        #[serde(default)]
+        #[serde(default, deserialize_with = "nullable_default")]
         query_string_parameters: StrMap,
-        #[serde(default)]
+        #[serde(default, deserialize_with = "nullable_default")]
         path_parameters: StrMap,
-        #[serde(default)]
+        #[serde(default, deserialize_with = "nullable_default")]
         stage_variables: StrMap,
         body: Option<Cow<'a, str>>,
-        #[serde(default)]
+        #[serde(default, deserialize_with = "nullable_default")]
         is_base64_encoded: bool,
         request_context: ApiGatewayV2RequestContext,
+        #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+        #[serde(flatten)]
+        extra: HashMap<String, Value>,
     },
     #[serde(rename_all = "camelCase")]
     Alb {
         path: Cow<'a, str>,
         #[serde(deserialize_with = "deserialize_method")]
         http_method: http::Method,
-        #[serde(deserialize_with = "deserialize_headers")]
+        /// For alb events these are absent when
+        /// the `lambda.multi_value_headers.enabled` target group setting turned on
+        #[serde(default, deserialize_with = "deserialize_headers")]
         headers: http::HeaderMap,
         /// For alb events these are only present when
         /// the `lambda.multi_value_headers.enabled` target group setting turned on
         #[serde(default, deserialize_with = "deserialize_multi_value_headers")]
         multi_value_headers: http::HeaderMap,
-        #[serde(deserialize_with = "nullable_default")]
+        #[serde(default, deserialize_with = "nullable_default")]
         query_string_parameters: StrMap,
         /// For alb events these are only present when
         /// the `lambda.multi_value_headers.enabled` target group setting turned on
         #[serde(default, deserialize_with = "nullable_default")]
         multi_value_query_string_parameters: StrMap,
         body: Option<Cow<'a, str>>,
-        #[serde(default)]
+        #[serde(default, deserialize_with = "nullable_default")]
         is_base64_encoded: bool,
         request_context: AlbRequestContext,
+        #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+        #[serde(flatten)]
+        extra: HashMap<String, Value>,
     },
     #[serde(rename_all = "camelCase")]
     ApiGateway {
+        resource: Option<Cow<'a, str>>,
         path: Cow<'a, str>,
         #[serde(deserialize_with = "deserialize_method")]
         http_method: http::Method,
-        #[serde(deserialize_with = "deserialize_headers")]
+        #[serde(default, deserialize_with = "deserialize_headers")]
         headers: http::HeaderMap,
         #[serde(default, deserialize_with = "deserialize_multi_value_headers")]
         multi_value_headers: http::HeaderMap,
-        #[serde(deserialize_with = "nullable_default")]
+        #[serde(default, deserialize_with = "nullable_default")]
         query_string_parameters: StrMap,
         #[serde(default, deserialize_with = "nullable_default")]
         multi_value_query_string_parameters: StrMap,
@@ -83,9 +104,12 @@ pub enum LambdaRequest<'a> {
         #[serde(default, deserialize_with = "nullable_default")]
         stage_variables: StrMap,
         body: Option<Cow<'a, str>>,
-        #[serde(default)]
+        #[serde(default, deserialize_with = "nullable_default")]
         is_base64_encoded: bool,
         request_context: ApiGatewayRequestContext,
+        #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+        #[serde(flatten)]
+        extra: HashMap<String, Value>,
     },
 }
 
@@ -101,49 +125,103 @@ impl LambdaRequest<'_> {
     }
 }
 
+/// API Gateway HTTP API and Lambda function URL request context
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct ApiGatewayV2RequestContext {
+    #[serde(default)]
     pub account_id: String,
-    pub api_id: String,
     #[serde(default)]
+    pub api_id: String,
+    /// Client certificate information for mutual TLS
+    pub authentication: Option<Value>,
+    #[serde(default, deserialize_with = "nullable_default")]
     pub authorizer: HashMap<String, Value>,
+    #[serde(default)]
     pub domain_name: String,
+    #[serde(default)]
     pub domain_prefix: String,
     pub http: Http,
+    #[serde(default)]
     pub request_id: String,
+    #[serde(default)]
     pub route_key: String,
+    #[serde(default)]
     pub stage: String,
+    #[serde(default)]
     pub time: String,
+    #[serde(default)]
     pub time_epoch: usize,
+    /// Fields present in the event which are not otherwise captured by this type
+    #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
+/// API Gateway REST API request context
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct ApiGatewayRequestContext {
-    //pub path: String,
+    #[serde(default)]
     pub account_id: String,
+    #[serde(default)]
     pub resource_id: String,
+    /// The request path including the stage
+    pub path: Option<String>,
+    /// The request protocol, e.g. `HTTP/1.1`
+    pub protocol: Option<String>,
+    #[serde(default)]
     pub stage: String,
+    /// The full domain name used to invoke the API
+    pub domain_name: Option<String>,
+    /// The first label of the domain name used to invoke the API
+    pub domain_prefix: Option<String>,
+    #[serde(default)]
     pub request_id: String,
+    /// The extended request id API Gateway generates and records in its logs
+    pub extended_request_id: Option<String>,
+    /// The request time in common log format
+    pub request_time: Option<String>,
+    /// The request time in epoch milliseconds
+    pub request_time_epoch: Option<i64>,
+    #[serde(default)]
     pub resource_path: String,
-    pub http_method: String,
     #[serde(default)]
+    pub http_method: String,
+    #[serde(default, deserialize_with = "nullable_default")]
     pub authorizer: HashMap<String, Value>,
+    #[serde(default)]
     pub api_id: String,
+    /// The operation name configured for the API method
+    pub operation_name: Option<String>,
+    /// Absent or `null` for some integrations, in which case it is defaulted
+    #[serde(default, deserialize_with = "nullable_default")]
     pub identity: Identity,
+    /// Fields present in the event which are not otherwise captured by this type
+    #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
+/// ALB request context
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct AlbRequestContext {
     pub elb: Elb,
+    /// Fields present in the event which are not otherwise captured by this type
+    #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 /// Event request context as an enumeration of request contexts
 /// for both ALB and API Gateway and HTTP API events
 #[derive(Deserialize, Debug, Clone)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum RequestContext {
     /// API Gateway v2 request context
     ApiGatewayV2(ApiGatewayV2RequestContext),
@@ -153,9 +231,68 @@ pub enum RequestContext {
     Alb(AlbRequestContext),
 }
 
+impl RequestContext {
+    /// The time API Gateway received the request, in milliseconds since the Unix epoch
+    ///
+    /// The epoch field of the payload format is preferred over its common log format time,
+    /// which is only precise to the second.
+    pub(crate) fn request_time_epoch(&self) -> Option<u64> {
+        match self {
+            RequestContext::ApiGatewayV2(ctx) => u64::try_from(ctx.time_epoch)
+                .ok()
+                .filter(|epoch| *epoch > 0)
+                .or_else(|| parse_common_log_time(&ctx.time)),
+            RequestContext::ApiGateway(ctx) => ctx
+                .request_time_epoch
+                .and_then(|epoch| u64::try_from(epoch).ok())
+                .filter(|epoch| *epoch > 0)
+                .or_else(|| ctx.request_time.as_deref().and_then(parse_common_log_time)),
+            RequestContext::Alb(_) => None,
+        }
+    }
+}
+
+/// Month abbreviations of common log format times
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Parses a common log format time, e.g. `10/Mar/2020:00:03:59 +0000`, into milliseconds since the Unix epoch
+fn parse_common_log_time(time: &str) -> Option<u64> {
+    let mut halves = time.trim().splitn(2, ' ');
+    let (datetime, offset) = (halves.next()?, halves.next()?.trim());
+    let mut fields = datetime.splitn(4, ':');
+    let mut date = fields.next()?.splitn(3, '/');
+    let day: i64 = date.next()?.parse().ok()?;
+    let month = date.next()?;
+    let month = MONTHS.iter().position(|name| *name == month)? + 1;
+    let year: i64 = date.next()?.parse().ok()?;
+    let mut clock = [0_i64; 3];
+    for part in &mut clock {
+        *part = fields.next()?.parse().ok()?;
+    }
+    let [hour, minute, second] = clock;
+    if !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let (sign, offset) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    if offset.len() != 4 || !offset.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let offset_minutes = offset[..2].parse::<i64>().ok()? * 60 + offset[2..].parse::<i64>().ok()?;
+    let seconds = days_from_civil(year, month as i64, day) * 86_400 + hour * 3_600 + minute * 60 + second
+        - sign * offset_minutes * 60;
+    u64::try_from(seconds).ok().map(|seconds| seconds * 1_000)
+}
+
 /// Elastic load balancer context information
 #[derive(Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Elb {
     /// AWS ARN identifier for the ELB Target Group this lambda was triggered by
     pub target_group_arn: String,
@@ -164,19 +301,26 @@ pub struct Elb {
 /// Http information captured API Gateway v2 request context
 #[derive(Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Http {
     #[serde(deserialize_with = "deserialize_method")]
     pub method: http::Method,
+    #[serde(default)]
     pub path: String,
+    #[serde(default)]
     pub protocol: String,
+    #[serde(default)]
     pub source_ip: String,
+    #[serde(default)]
     pub user_agent: String,
 }
 
 /// Identity assoicated with API Gateway request
 #[derive(Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Identity {
+    #[serde(default)]
     pub source_ip: String,
     pub cognito_identity_id: Option<String>,
     pub cognito_identity_pool_id: Option<String>,
@@ -185,10 +329,16 @@ pub struct Identity {
     pub account_id: Option<String>,
     pub caller: Option<String>,
     pub api_key: Option<String>,
+    /// The id of the API key used to make the request
+    pub api_key_id: Option<String>,
     pub access_key: Option<String>,
+    /// The AWS organization id of the caller
+    pub principal_org_id: Option<String>,
     pub user: Option<String>,
     pub user_agent: Option<String>,
     pub user_arn: Option<String>,
+    /// Client certificate information for mutual TLS
+    pub client_cert: Option<Value>,
 }
 
 /// Deserialize a str into an http::Method
@@ -216,7 +366,7 @@ where
     deserializer.deserialize_str(MethodVisitor)
 }
 
-/// Deserialize a map of Cow<'_, str> => Vec<Cow<'_, str>> into an http::HeaderMap
+/// Deserialize a (nullable) map of Cow<'_, str> => Vec<Cow<'_, str>> into an http::HeaderMap
 fn deserialize_multi_value_headers<'de, D>(deserializer: D) -> Result<http::HeaderMap, D::Error>
 where
     D: Deserializer<'de>,
@@ -230,6 +380,20 @@ where
             write!(formatter, "a multi valued HeaderMap<HeaderValue>")
         }
 
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: DeError,
+        {
+            Ok(http::HeaderMap::new())
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_map(self)
+        }
+
         fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
         where
             A: MapAccess<'de>,
@@ -255,10 +419,10 @@ where
         }
     }
 
-    deserializer.deserialize_map(HeaderVisitor)
+    deserializer.deserialize_option(HeaderVisitor)
 }
 
-/// Deserialize a map of Cow<'_, str> => Cow<'_, str> into an http::HeaderMap
+/// Deserialize a (nullable) map of Cow<'_, str> => Cow<'_, str> into an http::HeaderMap
 fn deserialize_headers<'de, D>(deserializer: D) -> Result<http::HeaderMap, D::Error>
 where
     D: Deserializer<'de>,
@@ -272,6 +436,20 @@ where
             write!(formatter, "a HeaderMap<HeaderValue>")
         }
 
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: DeError,
+        {
+            Ok(http::HeaderMap::new())
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_map(self)
+        }
+
         fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
         where
             A: MapAccess<'de>,
@@ -290,7 +468,7 @@ where
         }
     }
 
-    deserializer.deserialize_map(HeaderVisitor)
+    deserializer.deserialize_option(HeaderVisitor)
 }
 
 /// deserializes (json) null values to their default values
@@ -307,7 +485,16 @@ where
 /// Converts LambdaRequest types into `http::Request<Body>` types
 impl<'a> From<LambdaRequest<'a>> for http::Request<Body> {
     fn from(value: LambdaRequest<'_>) -> Self {
-        match value {
+        value.into_request(true)
+    }
+}
+
+impl<'a> LambdaRequest<'a> {
+    /// Converts into an `http::Request<Body>`, retaining a copy of the undecoded
+    /// body as a `RawBody` extension when `retain_raw_body` is true
+    pub(crate) fn into_request(self, retain_raw_body: bool) -> http::Request<Body> {
+        let raw_body = if retain_raw_body { Some(self.raw_body()) } else { None };
+        let mut req = match self {
             LambdaRequest::ApiGatewayV2 {
                 raw_path,
                 raw_query_string,
@@ -319,6 +506,8 @@ impl<'a> From<LambdaRequest<'a>> for http::Request<Body> {
                 is_base64_encoded,
                 request_context,
                 cookies,
+                #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+                extra,
                 ..
             } => {
                 if let Some(cookies) = cookies {
@@ -348,7 +537,13 @@ impl<'a> From<LambdaRequest<'a>> for http::Request<Body> {
                         }
                         url
                     })
-                    .extension(QueryStringParameters(query_string_parameters))
+                    // queryStringParameters joins repeated parameters with commas, which
+                    // is ambiguous for values containing commas, so the raw query is preferred
+                    .extension(QueryStringParameters(if raw_query_string.is_empty() {
+                        query_string_parameters
+                    } else {
+                        parse_query_string(&raw_query_string)
+                    }))
                     .extension(PathParameters(path_parameters))
                     .extension(StageVariables(stage_variables))
                     .extension(RequestContext::ApiGatewayV2(request_context));
@@ -360,6 +555,9 @@ impl<'a> From<LambdaRequest<'a>> for http::Request<Body> {
                 // no builder method that sets headers in batch
                 mem::replace(req.headers_mut(), headers);
 
+                #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+                req.extensions_mut().insert(ExtraFields(extra));
+
                 req
             }
             LambdaRequest::ApiGateway {
@@ -374,17 +572,32 @@ impl<'a> From<LambdaRequest<'a>> for http::Request<Body> {
                 body,
                 is_base64_encoded,
                 request_context,
+                #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+                extra,
+                ..
             } => {
+                // merge headers into multi_value_headers and make
+                // multi-value_headers our cannoncial source of request headers
+                for (key, value) in headers {
+                    // see HeaderMap#into_iter() docs for cases when key element may be None
+                    if let Some(first_key) = key {
+                        // if it contains the key, avoid appending a duplicate value
+                        if !multi_value_headers.contains_key(&first_key) {
+                            multi_value_headers.append(first_key, value);
+                        }
+                    }
+                }
+
                 let builder = http::Request::builder()
                     .method(http_method)
                     .uri({
                         format!(
                             "{}://{}{}",
-                            headers
+                            multi_value_headers
                                 .get("X-Forwarded-Proto")
                                 .and_then(|val| val.to_str().ok())
                                 .unwrap_or_else(|| "https"),
-                            headers
+                            multi_value_headers
                                 .get(http::header::HOST)
                                 .and_then(|val| val.to_str().ok())
                                 .unwrap_or_default(),
@@ -393,7 +606,8 @@ impl<'a> From<LambdaRequest<'a>> for http::Request<Body> {
                     })
                     // multi-valued query string parameters are always a super
                     // set of singly valued query string parameters,
-                    // when present, multi-valued query string parameters are preferred
+                    // when present, multi-valued query string parameters are preferred.
+                    // API Gateway has already decoded them, so they are not decoded again
                     .extension(QueryStringParameters(
                         if multi_value_query_string_parameters.is_empty() {
                             query_string_parameters
@@ -409,21 +623,12 @@ impl<'a> From<LambdaRequest<'a>> for http::Request<Body> {
                     .body(body.map_or_else(Body::default, |b| Body::from_maybe_encoded(is_base64_encoded, b)))
                     .expect("failed to build request");
 
-                // merge headers into multi_value_headers and make
-                // multi-value_headers our cannoncial source of request headers
-                for (key, value) in headers {
-                    // see HeaderMap#into_iter() docs for cases when key element may be None
-                    if let Some(first_key) = key {
-                        // if it contains the key, avoid appending a duplicate value
-                        if !multi_value_headers.contains_key(&first_key) {
-                            multi_value_headers.append(first_key, value);
-                        }
-                    }
-                }
-
                 // no builder method that sets headers in batch
                 mem::replace(req.headers_mut(), multi_value_headers);
 
+                #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+                req.extensions_mut().insert(ExtraFields(extra));
+
                 req
             }
             LambdaRequest::Alb {
@@ -436,57 +641,92 @@ impl<'a> From<LambdaRequest<'a>> for http::Request<Body> {
                 body,
                 is_base64_encoded,
                 request_context,
+                #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+                extra,
             } => {
+                // merge headers into multi_value_headers and make
+                // multi-value_headers our cannoncial source of request headers
+                for (key, value) in headers {
+                    // see HeaderMap#into_iter() docs for cases when key element may be None
+                    if let Some(first_key) = key {
+                        // if it contains the key, avoid appending a duplicate value
+                        if !multi_value_headers.contains_key(&first_key) {
+                            multi_value_headers.append(first_key, value);
+                        }
+                    }
+                }
+
                 // build an http::Request<lambda_http::Body> from a lambda_http::LambdaRequest
                 let builder = http::Request::builder()
                     .method(http_method)
                     .uri({
-                        format!(
-                            "{}://{}{}",
-                            headers
-                                .get("X-Forwarded-Proto")
-                                .and_then(|val| val.to_str().ok())
-                                .unwrap_or_else(|| "https"),
-                            headers
-                                .get(http::header::HOST)
-                                .and_then(|val| val.to_str().ok())
-                                .unwrap_or_default(),
-                            path
-                        )
+                        // alb health checks are sent without a host header
+                        match multi_value_headers
+                            .get(http::header::HOST)
+                            .and_then(|val| val.to_str().ok())
+                        {
+                            Some(host) => format!(
+                                "{}://{}{}",
+                                multi_value_headers
+                                    .get("X-Forwarded-Proto")
+                                    .and_then(|val| val.to_str().ok())
+                                    .unwrap_or_else(|| "https"),
+                                host,
+                                path
+                            ),
+                            None => path.into_owned(),
+                        }
                     })
                     // multi valued query string parameters are always a super
                     // set of singly valued query string parameters,
-                    // when present, multi-valued query string parameters are preferred
-                    .extension(QueryStringParameters(
+                    // when present, multi-valued query string parameters are preferred.
+                    // ALBs pass them on as they were received, so they are decoded here
+                    .extension(QueryStringParameters(decode_query_parameters(
                         if multi_value_query_string_parameters.is_empty() {
                             query_string_parameters
                         } else {
                             multi_value_query_string_parameters
                         },
-                    ))
+                    )))
                     .extension(RequestContext::Alb(request_context));
 
                 let mut req = builder
                     .body(body.map_or_else(Body::default, |b| Body::from_maybe_encoded(is_base64_encoded, b)))
                     .expect("failed to build request");
 
-                // merge headers into multi_value_headers and make
-                // multi-value_headers our cannoncial source of request headers
-                for (key, value) in headers {
-                    // see HeaderMap#into_iter() docs for cases when key element may be None
-                    if let Some(first_key) = key {
-                        // if it contains the key, avoid appending a duplicate value
-                        if !multi_value_headers.contains_key(&first_key) {
-                            multi_value_headers.append(first_key, value);
-                        }
-                    }
-                }
-
                 // no builder method that sets headers in batch
                 mem::replace(req.headers_mut(), multi_value_headers);
 
+                #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+                req.extensions_mut().insert(ExtraFields(extra));
+
                 req
             }
+        };
+        if let Some(raw_body) = raw_body {
+            req.extensions_mut().insert(raw_body);
+        }
+        req
+    }
+
+    /// The body of the event as it was received, before any base64 decoding
+    fn raw_body(&self) -> RawBody {
+        match self {
+            LambdaRequest::ApiGatewayV2 {
+                body,
+                is_base64_encoded,
+                ..
+            }
+            | LambdaRequest::ApiGateway {
+                body,
+                is_base64_encoded,
+                ..
+            }
+            | LambdaRequest::Alb {
+                body,
+                is_base64_encoded,
+                ..
+            } => RawBody::new(body.as_deref(), *is_base64_encoded),
         }
     }
 }
@@ -662,6 +902,132 @@ mod tests {
         );
     }
 
+    #[test]
+    fn deserializes_captured_event_corpus() {
+        // trigger events captured across integrations must all deserialize,
+        // regardless of which optional fields each one includes
+        let corpus = vec![
+            (
+                "alb_health_check",
+                include_str!("../tests/data/alb_health_check.json"),
+                "alb",
+            ),
+            (
+                "alb_multi_value_headers_only_request",
+                include_str!("../tests/data/alb_multi_value_headers_only_request.json"),
+                "alb",
+            ),
+            (
+                "alb_multi_value_request",
+                include_str!("../tests/data/alb_multi_value_request.json"),
+                "alb",
+            ),
+            ("alb_request", include_str!("../tests/data/alb_request.json"), "alb"),
+            (
+                "apigw_multi_value_proxy_request",
+                include_str!("../tests/data/apigw_multi_value_proxy_request.json"),
+                "apigw",
+            ),
+            (
+                "apigw_proxy_request",
+                include_str!("../tests/data/apigw_proxy_request.json"),
+                "apigw",
+            ),
+            (
+                "apigw_proxy_request_lambda_authorizer",
+                include_str!("../tests/data/apigw_proxy_request_lambda_authorizer.json"),
+                "apigw",
+            ),
+            (
+                "apigw_proxy_request_no_identity",
+                include_str!("../tests/data/apigw_proxy_request_no_identity.json"),
+                "apigw",
+            ),
+            (
+                "apigw_v2_proxy_request",
+                include_str!("../tests/data/apigw_v2_proxy_request.json"),
+                "apigw_v2",
+            ),
+            (
+                "apigw_v2_proxy_request_lambda_authorizer",
+                include_str!("../tests/data/apigw_v2_proxy_request_lambda_authorizer.json"),
+                "apigw_v2",
+            ),
+            (
+                "apigw_v2_proxy_request_minimal",
+                include_str!("../tests/data/apigw_v2_proxy_request_minimal.json"),
+                "apigw_v2",
+            ),
+            (
+                "lambda_function_url_request",
+                include_str!("../tests/data/lambda_function_url_request.json"),
+                "apigw_v2",
+            ),
+        ];
+        for (name, input, expected) in corpus {
+            let event = serde_json::from_str::<LambdaRequest<'_>>(input)
+                .unwrap_or_else(|e| panic!("{} was not parsed as expected {}", name, e));
+            let actual = match event {
+                LambdaRequest::ApiGatewayV2 { .. } => "apigw_v2",
+                LambdaRequest::Alb { .. } => "alb",
+                LambdaRequest::ApiGateway { .. } => "apigw",
+            };
+            assert_eq!(actual, expected, "{} deserialized as the wrong event type", name);
+        }
+    }
+
+    #[test]
+    fn deserializes_apigw_request_events_without_identity() {
+        let input = include_str!("../tests/data/apigw_proxy_request_no_identity.json");
+        let req = from_str(input).expect("failed to parse request");
+        assert_eq!(req.uri(), "https://abcdef1234.execute-api.eu-west-1.amazonaws.com/");
+        assert!(req.headers().contains_key(http::header::HOST));
+        assert!(req.query_string_parameters().is_empty());
+        assert_eq!(req.body(), &Body::Empty);
+        match req.request_context() {
+            RequestContext::ApiGateway(ctx) => assert_eq!(ctx.identity.source_ip, ""),
+            other => panic!("unexpected request context {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deserializes_alb_request_events_with_only_multi_value_fields() {
+        let input = include_str!("../tests/data/alb_multi_value_headers_only_request.json");
+        let req = from_str(input).expect("failed to parse request");
+        assert_eq!(
+            req.uri(),
+            "https://lambda-alb-123578498.us-east-2.elb.amazonaws.com/upload"
+        );
+        assert_eq!(req.query_string_parameters().get_all("tag"), Some(vec!["a", "b"]));
+        assert_eq!(req.body(), &Body::from(vec![0u8, 1, 2, 3, 4]));
+    }
+
+    #[cfg(all(feature = "extra-fields", not(feature = "strict")))]
+    #[test]
+    fn captures_unmodeled_fields() {
+        let mut event: Value =
+            serde_json::from_str(include_str!("../tests/data/apigw_proxy_request.json")).expect("invalid fixture");
+        event["newTopLevelField"] = "new".into();
+        event["requestContext"]["newContextField"] = 1.into();
+        let req: crate::Request = serde_json::from_value::<LambdaRequest<'_>>(event)
+            .expect("failed to parse request")
+            .into();
+        assert_eq!(req.extra_fields().get("newTopLevelField"), Some(&Value::from("new")));
+        match req.request_context() {
+            RequestContext::ApiGateway(ctx) => assert_eq!(ctx.extra.get("newContextField"), Some(&Value::from(1))),
+            other => panic!("unexpected request context {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "strict")]
+    #[test]
+    fn rejects_unmodeled_fields() {
+        let mut event: Value =
+            serde_json::from_str(include_str!("../tests/data/apigw_proxy_request.json")).expect("invalid fixture");
+        event["newTopLevelField"] = "new".into();
+        assert!(serde_json::from_value::<LambdaRequest<'_>>(event).is_err());
+    }
+
     #[test]
     fn deserialize_with_null() {
         #[derive(Debug, PartialEq, Deserialize)]
@@ -675,4 +1041,93 @@ mod tests {
             Test { foo: HashMap::new() }
         )
     }
+
+    #[test]
+    fn parses_common_log_times() {
+        assert_eq!(
+            parse_common_log_time("10/Mar/2020:00:03:59 +0000"),
+            Some(1_583_798_639_000)
+        );
+        assert_eq!(
+            parse_common_log_time("10/Mar/2020:01:33:59 +0130"),
+            Some(1_583_798_639_000)
+        );
+        assert_eq!(
+            parse_common_log_time("09/Mar/2020:19:03:59 -0500"),
+            Some(1_583_798_639_000)
+        );
+        assert_eq!(
+            parse_common_log_time("29/Feb/2020:00:00:00 +0000"),
+            Some(1_582_934_400_000)
+        );
+        for invalid in &[
+            "",
+            "10/Mar/2020:00:03:59",
+            "10/March/2020:00:03:59 +0000",
+            "10/Mar/2020:24:00:00 +0000",
+        ] {
+            assert_eq!(parse_common_log_time(invalid), None, "{}", invalid);
+        }
+    }
+
+    /// Asserts the parameters a handler observes for the query
+    /// `a=a%2Bb&b=a+b&x%26y=z&empty=&flag&pct=100%2525`
+    fn assert_tricky_query_parameters(event: Value) {
+        let request = from_str(&event.to_string()).expect("failed to parse request");
+        let params = request.query_string_parameters();
+        assert_eq!(params.get("a"), Some("a+b"), "encoded plus");
+        assert_eq!(params.get("b"), Some("a+b"), "literal plus is not a space");
+        assert_eq!(params.get("x&y"), Some("z"), "encoded separator");
+        assert_eq!(params.get("empty"), Some(""), "empty value");
+        assert_eq!(params.get("flag"), Some(""), "name without value");
+        assert_eq!(params.get("pct"), Some("100%25"), "decoded once");
+        assert_eq!(params.iter().count(), 6);
+    }
+
+    #[test]
+    fn apigw_v2_query_parameters_are_parsed_from_the_raw_query() {
+        let mut event: Value =
+            serde_json::from_str(include_str!("../tests/data/apigw_v2_proxy_request.json")).expect("invalid fixture");
+        event["rawQueryString"] = "a=a%2Bb&b=a+b&x%26y=z&empty=&flag&pct=100%2525".into();
+        // API Gateway's own decoding of the query, which is ambiguous
+        event["queryStringParameters"] = serde_json::json!({
+            "a": "a+b", "b": "a+b", "x&y": "z", "empty": "", "flag": "", "pct": "100%25"
+        });
+        assert_tricky_query_parameters(event);
+    }
+
+    #[test]
+    fn apigw_v2_raw_query_keeps_commas_in_repeated_parameters() {
+        let mut event: Value =
+            serde_json::from_str(include_str!("../tests/data/apigw_v2_proxy_request.json")).expect("invalid fixture");
+        event["rawQueryString"] = "tag=a,b&tag=c".into();
+        event["queryStringParameters"] = serde_json::json!({ "tag": "a,b,c" });
+        let request = from_str(&event.to_string()).expect("failed to parse request");
+        assert_eq!(request.query_string_parameters().get_all("tag"), Some(vec!["a,b", "c"]));
+    }
+
+    #[test]
+    fn apigw_query_parameters_are_not_decoded_again() {
+        let mut event: Value =
+            serde_json::from_str(include_str!("../tests/data/apigw_proxy_request.json")).expect("invalid fixture");
+        // API Gateway REST APIs decode query parameters before invoking the function
+        event["queryStringParameters"] = serde_json::json!({
+            "a": "a+b", "b": "a+b", "x&y": "z", "empty": "", "flag": "", "pct": "100%25"
+        });
+        event["multiValueQueryStringParameters"] = serde_json::json!({
+            "a": ["a+b"], "b": ["a+b"], "x&y": ["z"], "empty": [""], "flag": [""], "pct": ["100%25"]
+        });
+        assert_tricky_query_parameters(event);
+    }
+
+    #[test]
+    fn alb_query_parameters_are_decoded() {
+        let mut event: Value =
+            serde_json::from_str(include_str!("../tests/data/alb_request.json")).expect("invalid fixture");
+        // ALBs pass query parameters on as they were received
+        event["queryStringParameters"] = serde_json::json!({
+            "a": "a%2Bb", "b": "a+b", "x%26y": "z", "empty": "", "flag": "", "pct": "100%2525"
+        });
+        assert_tricky_query_parameters(event);
+    }
 }
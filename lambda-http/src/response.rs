@@ -1,7 +1,7 @@
 //! Response types
 
 use http::{
-    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
     Response,
 };
 use serde::{
@@ -12,6 +12,28 @@ use serde_derive::Serialize;
 
 use crate::body::Body;
 
+/// Exact-cased response headers, emitted verbatim in place of the lowercased names of a
+/// response's `HeaderMap`
+///
+/// `http::HeaderMap` normalizes header names to lowercase. Clients which match response
+/// headers case-sensitively can be served by adding this extension to a response with the
+/// names and values to send as they are. When a cased entry and the `HeaderMap` carry the
+/// same header, compared case-insensitively, only the cased entries are sent. Repeating a
+/// name, in any case, sends each of its values in `multiValueHeaders` and the first one in
+/// `headers`, under the spelling of its first entry. A name or value which isn't a valid
+/// header fails the serialization of the response.
+///
+/// ```rust
+/// use lambda_http::{IntoResponse, PreserveHeaderCase, Response};
+///
+/// let mut response = Response::new("hello").into_response();
+/// response
+///     .extensions_mut()
+///     .insert(PreserveHeaderCase(vec![("X-Request-ID".into(), "abc123".into())]));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PreserveHeaderCase(pub Vec<(String, String)>);
+
 /// Representation of API Gateway response
 #[doc(hidden)]
 #[derive(Serialize, Debug)]
@@ -23,15 +45,50 @@ pub struct LambdaResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status_description: Option<String>,
     #[serde(serialize_with = "serialize_headers")]
-    pub headers: HeaderMap<HeaderValue>,
+    pub headers: ResponseHeaders,
     #[serde(serialize_with = "serialize_multi_value_headers")]
-    pub multi_value_headers: HeaderMap<HeaderValue>,
+    pub multi_value_headers: ResponseHeaders,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub body: Option<Body>,
     // This field is optional for API Gateway but required for ALB
     pub is_base64_encoded: bool,
 }
 
+/// Response headers along with any exact-cased headers which replace them
+#[doc(hidden)]
+#[derive(Debug, Default, Clone)]
+pub struct ResponseHeaders {
+    pub map: HeaderMap<HeaderValue>,
+    pub cased: Vec<(String, String)>,
+}
+
+impl ResponseHeaders {
+    /// The `HeaderMap` entries which are not replaced by a cased entry
+    fn uncased(&self) -> impl Iterator<Item = &HeaderName> {
+        self.map.keys().filter(move |key| {
+            !self
+                .cased
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case(key.as_str()))
+        })
+    }
+
+    /// The cased entries' names, spelled as they first appear and in that order, with all of the
+    /// values of the entries naming the same header
+    fn cased(&self) -> Result<Vec<(&str, Vec<&str>)>, String> {
+        let mut grouped: Vec<(&str, Vec<&str>)> = Vec::new();
+        for (name, value) in &self.cased {
+            HeaderName::from_bytes(name.as_bytes()).map_err(|e| format!("invalid header name {:?}: {}", name, e))?;
+            HeaderValue::from_str(value).map_err(|e| format!("invalid value of header {}: {}", name, e))?;
+            match grouped.iter_mut().find(|(seen, _)| seen.eq_ignore_ascii_case(name)) {
+                Some((_, values)) => values.push(value),
+                None => grouped.push((name, vec![value])),
+            }
+        }
+        Ok(grouped)
+    }
+}
+
 #[cfg(test)]
 impl Default for LambdaResponse {
     fn default() -> Self {
@@ -47,31 +104,39 @@ impl Default for LambdaResponse {
 }
 
 /// Serialize a http::HeaderMap into a serde str => str map
-fn serialize_multi_value_headers<S>(headers: &HeaderMap<HeaderValue>, serializer: S) -> Result<S::Ok, S::Error>
+fn serialize_multi_value_headers<S>(headers: &ResponseHeaders, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    let mut map = serializer.serialize_map(Some(headers.keys_len()))?;
-    for key in headers.keys() {
+    let cased = headers.cased().map_err(S::Error::custom)?;
+    let mut map = serializer.serialize_map(None)?;
+    for key in headers.uncased() {
         let mut map_values = Vec::new();
-        for value in headers.get_all(key) {
+        for value in headers.map.get_all(key) {
             map_values.push(value.to_str().map_err(S::Error::custom)?)
         }
         map.serialize_entry(key.as_str(), &map_values)?;
     }
+    for (name, values) in &cased {
+        map.serialize_entry(name, values)?;
+    }
     map.end()
 }
 
 /// Serialize a http::HeaderMap into a serde str => Vec<str> map
-fn serialize_headers<S>(headers: &HeaderMap<HeaderValue>, serializer: S) -> Result<S::Ok, S::Error>
+fn serialize_headers<S>(headers: &ResponseHeaders, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    let mut map = serializer.serialize_map(Some(headers.keys_len()))?;
-    for key in headers.keys() {
-        let map_value = headers[key].to_str().map_err(S::Error::custom)?;
+    let cased = headers.cased().map_err(S::Error::custom)?;
+    let mut map = serializer.serialize_map(None)?;
+    for key in headers.uncased() {
+        let map_value = headers.map[key].to_str().map_err(S::Error::custom)?;
         map.serialize_entry(key.as_str(), map_value)?;
     }
+    for (name, values) in &cased {
+        map.serialize_entry(name, values[0])?;
+    }
     map.end()
 }
 
@@ -81,12 +146,20 @@ impl LambdaResponse {
     where
         T: Into<Body>,
     {
-        let (parts, bod) = value.into_parts();
+        let (mut parts, bod) = value.into_parts();
         let (is_base64_encoded, body) = match bod.into() {
             Body::Empty => (false, None),
             b @ Body::Text(_) => (false, Some(b)),
             b @ Body::Binary(_) => (true, Some(b)),
         };
+        let headers = ResponseHeaders {
+            map: parts.headers,
+            cased: parts
+                .extensions
+                .remove::<PreserveHeaderCase>()
+                .map(|PreserveHeaderCase(cased)| cased)
+                .unwrap_or_default(),
+        };
         Self {
             status_code: parts.status.as_u16(),
             status_description: if is_alb {
@@ -99,8 +172,8 @@ impl LambdaResponse {
                 None
             },
             body,
-            headers: parts.headers.clone(),
-            multi_value_headers: parts.headers,
+            headers: headers.clone(),
+            multi_value_headers: headers,
             is_base64_encoded,
         }
     }
@@ -161,7 +234,7 @@ impl IntoResponse for serde_json::Value {
 
 #[cfg(test)]
 mod tests {
-    use super::{Body, IntoResponse, LambdaResponse};
+    use super::{Body, IntoResponse, LambdaResponse, PreserveHeaderCase};
     use http::{header::CONTENT_TYPE, Response};
     use serde_json::{self, json};
 
@@ -229,4 +302,66 @@ mod tests {
             r#"{"statusCode":200,"headers":{"multi":"a"},"multiValueHeaders":{"multi":["a","b"]},"isBase64Encoded":false}"#
         )
     }
+
+    fn cased_response() -> Response<Body> {
+        let mut response = Response::builder()
+            .header("x-request-id", "lowercased")
+            .header("multi", "a")
+            .header("multi", "b")
+            .header(CONTENT_TYPE, "text/plain")
+            .body(Body::from("hello"))
+            .expect("failed to create response");
+        response.extensions_mut().insert(PreserveHeaderCase(vec![
+            ("X-Request-ID".into(), "abc123".into()),
+            ("X-Legacy".into(), "1".into()),
+            ("X-Legacy".into(), "2".into()),
+        ]));
+        response
+    }
+
+    #[test]
+    fn serialize_alb_preserved_header_case() {
+        let res = LambdaResponse::from_response(true, cased_response());
+        let json = serde_json::to_string(&res).expect("failed to serialize to json");
+        assert_eq!(
+            json,
+            r#"{"statusCode":200,"statusDescription":"200 OK","headers":{"multi":"a","content-type":"text/plain","X-Request-ID":"abc123","X-Legacy":"1"},"multiValueHeaders":{"multi":["a","b"],"content-type":["text/plain"],"X-Request-ID":["abc123"],"X-Legacy":["1","2"]},"body":"hello","isBase64Encoded":false}"#
+        )
+    }
+
+    #[test]
+    fn serialize_apigw_preserved_header_case() {
+        let res = LambdaResponse::from_response(false, cased_response());
+        let json = serde_json::to_string(&res).expect("failed to serialize to json");
+        assert_eq!(
+            json,
+            r#"{"statusCode":200,"headers":{"multi":"a","content-type":"text/plain","X-Request-ID":"abc123","X-Legacy":"1"},"multiValueHeaders":{"multi":["a","b"],"content-type":["text/plain"],"X-Request-ID":["abc123"],"X-Legacy":["1","2"]},"body":"hello","isBase64Encoded":false}"#
+        )
+    }
+
+    #[test]
+    fn preserved_header_case_groups_names_case_insensitively() {
+        let mut response = Response::new(Body::Empty);
+        response.extensions_mut().insert(PreserveHeaderCase(vec![
+            ("X-Id".into(), "a".into()),
+            ("x-ID".into(), "b".into()),
+        ]));
+        let res = LambdaResponse::from_response(false, response);
+        let json = serde_json::to_value(&res).expect("failed to serialize to json");
+        assert_eq!(json["headers"], json!({ "X-Id": "a" }));
+        assert_eq!(json["multiValueHeaders"], json!({ "X-Id": ["a", "b"] }));
+    }
+
+    #[test]
+    fn preserved_header_case_rejects_invalid_headers() {
+        for cased in [
+            ("X-Id\r\nX-Injected".to_owned(), "a".to_owned()),
+            ("X-Id".into(), "a\r\nb".into()),
+        ] {
+            let mut response = Response::new(Body::Empty);
+            response.extensions_mut().insert(PreserveHeaderCase(vec![cased]));
+            let res = LambdaResponse::from_response(false, response);
+            assert!(serde_json::to_string(&res).is_err());
+        }
+    }
 }
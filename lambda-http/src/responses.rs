@@ -0,0 +1,247 @@
+//! Constructors for common responses
+//!
+//! Each constructor returns a `Response<Body>`, which implements [`IntoResponse`](../trait.IntoResponse.html),
+//! so they may be returned directly from handlers.
+//!
+//! ```rust,no_run
+//! use lambda_http::{handler, http::StatusCode, lambda::{self, Context}, responses, IntoResponse, Request};
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     lambda::run(handler(moved)).await?;
+//!     Ok(())
+//! }
+//!
+//! async fn moved(_: Request, _: Context) -> Result<impl IntoResponse, Error> {
+//!     Ok(responses::redirect(StatusCode::MOVED_PERMANENTLY, "/new home")?)
+//! }
+//! ```
+
+use http::{
+    header::{CONTENT_TYPE, LOCATION},
+    HeaderValue, Response, StatusCode, Uri,
+};
+use serde_derive::Serialize;
+use std::{error::Error, fmt};
+
+use crate::{Body, IntoResponse};
+
+/// Response construction errors
+#[derive(Debug, PartialEq)]
+pub enum ResponseError {
+    /// Returned when a redirect is requested with a status which is not a `3xx` redirection
+    InvalidRedirectStatus(StatusCode),
+    /// Returned when a location is not a valid URI reference
+    InvalidLocation(String),
+}
+
+impl fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseError::InvalidRedirectStatus(status) => write!(f, "{} is not a redirection status", status),
+            ResponseError::InvalidLocation(location) => write!(f, "{} is not a valid URI reference", location),
+        }
+    }
+}
+
+impl Error for ResponseError {}
+
+/// An [RFC 7807](https://tools.ietf.org/html/rfc7807) problem details object
+#[derive(Serialize)]
+struct Problem<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    title: &'a str,
+    status: u16,
+    detail: &'a str,
+}
+
+/// Return true for bytes which may appear unescaped in a URI reference
+fn is_uri_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~:/?#[]@!$&'()*+,;=".contains(&b)
+}
+
+/// Percent-encodes the bytes of a location which may not appear in a URI reference,
+/// leaving existing percent-encoded octets as they are
+fn encode_location(location: &str) -> String {
+    let bytes = location.as_bytes();
+    let mut encoded = String::with_capacity(bytes.len());
+    for (i, &b) in bytes.iter().enumerate() {
+        let escaped =
+            b == b'%' && bytes.len() > i + 2 && bytes[i + 1].is_ascii_hexdigit() && bytes[i + 2].is_ascii_hexdigit();
+        if is_uri_char(b) || escaped {
+            encoded.push(char::from(b));
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+/// Returns the header value of a location once it is percent-encoded and validated
+fn location_header(location: &str) -> Result<HeaderValue, ResponseError> {
+    let invalid = || ResponseError::InvalidLocation(location.into());
+    let encoded = encode_location(location);
+    if encoded.is_empty() {
+        return Err(invalid());
+    }
+    // relative references without a leading slash are not accepted by `Uri` on their own
+    // so they are validated as if they were relative to the root
+    let valid = encoded.parse::<Uri>().is_ok() || {
+        let first_segment = encoded.split(&['/', '?', '#'][..]).next();
+        !first_segment.unwrap_or_default().contains(':') && format!("/{}", encoded).parse::<Uri>().is_ok()
+    };
+    if !valid {
+        return Err(invalid());
+    }
+    HeaderValue::from_str(&encoded).map_err(|_| invalid())
+}
+
+/// Return a redirect to `location` with a `3xx` status
+///
+/// Characters which may not appear in a URI, like spaces, are percent-encoded.
+///
+/// # Errors
+///
+/// Returns an error when `status` is not a redirection status or `location` is not a URI reference
+pub fn redirect<L>(status: StatusCode, location: L) -> Result<Response<Body>, ResponseError>
+where
+    L: AsRef<str>,
+{
+    if !status.is_redirection() {
+        return Err(ResponseError::InvalidRedirectStatus(status));
+    }
+    let mut response = Response::new(Body::Empty);
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(LOCATION, location_header(location.as_ref())?);
+    Ok(response)
+}
+
+/// Return an empty `204 No Content` response
+pub fn no_content() -> Response<Body> {
+    let mut response = Response::new(Body::Empty);
+    *response.status_mut() = StatusCode::NO_CONTENT;
+    response
+}
+
+/// Return a `201 Created` response for a resource at `location`
+///
+/// The body is converted with [`IntoResponse`](../trait.IntoResponse.html), so its headers,
+/// like the `Content-Type` of a `serde_json::Value`, are kept.
+///
+/// # Errors
+///
+/// Returns an error when `location` is not a URI reference
+pub fn created<L, B>(location: L, body: B) -> Result<Response<Body>, ResponseError>
+where
+    L: AsRef<str>,
+    B: IntoResponse,
+{
+    let location = location_header(location.as_ref())?;
+    let mut response = body.into_response();
+    *response.status_mut() = StatusCode::CREATED;
+    response.headers_mut().insert(LOCATION, location);
+    Ok(response)
+}
+
+/// Return an [RFC 7807](https://tools.ietf.org/html/rfc7807) `application/problem+json` error response
+///
+/// The problem's type is `about:blank`, which is why `title` should be a short, fixed summary
+/// of the status while `detail` explains this occurrence of the problem.
+pub fn problem<T, D>(status: StatusCode, title: T, detail: D) -> Response<Body>
+where
+    T: AsRef<str>,
+    D: AsRef<str>,
+{
+    let body = serde_json::to_string(&Problem {
+        kind: "about:blank",
+        title: title.as_ref(),
+        status: status.as_u16(),
+        detail: detail.as_ref(),
+    })
+    .expect("unable to serialize problem details");
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn redirect_sets_status_and_location() {
+        let response = redirect(StatusCode::FOUND, "https://example.com/login?next=/").expect("invalid redirect");
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers().len(), 1);
+        assert_eq!(response.headers()[LOCATION], "https://example.com/login?next=/");
+        assert_eq!(response.body(), &Body::Empty);
+    }
+
+    #[test]
+    fn redirect_percent_encodes_location() {
+        let response = redirect(StatusCode::SEE_OTHER, "/my files/résumé.pdf?a=1%202").expect("invalid redirect");
+        assert_eq!(response.headers()[LOCATION], "/my%20files/r%C3%A9sum%C3%A9.pdf?a=1%202");
+    }
+
+    #[test]
+    fn redirect_accepts_relative_references() {
+        let response = redirect(StatusCode::TEMPORARY_REDIRECT, "../sibling").expect("invalid redirect");
+        assert_eq!(response.headers()[LOCATION], "../sibling");
+    }
+
+    #[test]
+    fn redirect_rejects_non_redirection_status() {
+        assert_eq!(
+            redirect(StatusCode::OK, "/").unwrap_err(),
+            ResponseError::InvalidRedirectStatus(StatusCode::OK)
+        );
+    }
+
+    #[test]
+    fn redirect_rejects_invalid_locations() {
+        for location in &["", "http://exa mple.com:port/", "a:b:c"] {
+            assert_eq!(
+                redirect(StatusCode::FOUND, location).unwrap_err(),
+                ResponseError::InvalidLocation(location.to_string()),
+            );
+        }
+    }
+
+    #[test]
+    fn no_content_has_no_body_or_content_type() {
+        let response = no_content();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(response.headers().is_empty());
+        assert_eq!(response.body(), &Body::Empty);
+    }
+
+    #[test]
+    fn created_keeps_body_headers() {
+        let response = created("/users/1", json!({ "id": 1 })).expect("invalid location");
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[LOCATION], "/users/1");
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(response.body(), &Body::from(r#"{"id":1}"#));
+    }
+
+    #[test]
+    fn problem_emits_problem_json() {
+        let response = problem(StatusCode::NOT_FOUND, "Not Found", "no user with id 1");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers().len(), 1);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+        assert_eq!(
+            response.body(),
+            &Body::from(r#"{"type":"about:blank","title":"Not Found","status":404,"detail":"no user with id 1"}"#)
+        );
+    }
+}
@@ -0,0 +1,486 @@
+//! Dispatches requests to handlers by method and path
+//!
+//! A [`Router`](struct.Router.html) is itself a [`Handler`](../trait.Handler.html), so a single
+//! function can serve several routes of an API.
+//!
+//! # Route patterns
+//!
+//! Patterns are matched segment by segment against the request path.
+//!
+//! * literal segments, like `users`, match themselves exactly
+//! * `:name` segments match any single non-empty segment
+//! * a final `*name` segment greedily matches the rest of the path, including further `/`s,
+//!   and may match nothing at all
+//!
+//! Captured segments are percent-decoded and exposed with
+//! [`RequestExt#path_parameters()`](../trait.RequestExt.html#tymethod.path_parameters),
+//! alongside any path parameters API Gateway provided.
+//!
+//! A trailing slash is not significant, so `/users/` is routed like `/users`. When several
+//! routes match a path, the one with the most literal segments, from left to right, is chosen,
+//! preferring `:name` segments to `*name` segments, and earlier routes break remaining ties.
+//!
+//! HTTP API requests to a named stage carry the stage as the first path segment. It is
+//! stripped before routing so routes are written as they are declared in API Gateway.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use lambda_http::{handler, lambda::{self, Context}, router::Router, IntoResponse, Request, RequestExt};
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     let router = Router::new()
+//!         .get("/users/:id", get_user)
+//!         .post("/users", |_, _| async { Ok::<_, Error>("created") });
+//!     lambda::run(handler(router)).await?;
+//!     Ok(())
+//! }
+//!
+//! async fn get_user(request: Request, _: Context) -> Result<impl IntoResponse, Error> {
+//!     Ok(format!("user {}", request.path_parameters().get("id").unwrap_or_default()))
+//! }
+//! ```
+
+use http::{header::ALLOW, HeaderValue, Method, Response, StatusCode};
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
+
+use crate::{
+    encoding::percent_decode, ext::PathParameters, request::RequestContext, strmap::StrMap, Body, Context, Error,
+    Handler, IntoResponse, Request, RequestExt,
+};
+
+/// The future returned by a [`Router`](struct.Router.html)
+pub type RouteFuture = Pin<Box<dyn Future<Output = Result<Response<Body>, Error>>>>;
+
+type BoxHandler = Box<dyn FnMut(Request, Context) -> RouteFuture>;
+
+/// A single segment of a route pattern
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    Wildcard(String),
+}
+
+impl Segment {
+    /// Rank of a segment when choosing between overlapping routes, lower ranks win
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Literal(_) => 0,
+            Segment::Param(_) => 1,
+            Segment::Wildcard(_) => 2,
+        }
+    }
+}
+
+struct Route {
+    method: Method,
+    segments: Vec<Segment>,
+    handler: BoxHandler,
+}
+
+/// Routes requests to handlers by HTTP method and path
+///
+/// Requests whose path matches no route are answered by the `not_found` handler, or an empty
+/// `404 Not Found` response when none is set. Requests whose path matches a route registered only
+/// for other methods get an empty `405 Method Not Allowed` response with an `Allow` header.
+///
+/// # Panics
+///
+/// Registering a route panics when its pattern does not start with `/`, has an unnamed
+/// `:` or `*` segment, or has a `*` segment which is not its last.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    not_found: Option<BoxHandler>,
+}
+
+impl Router {
+    /// Return a new router without routes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Route requests with `method` to paths matching `pattern` to `handler`
+    pub fn route<H>(mut self, method: Method, pattern: &str, handler: H) -> Self
+    where
+        H: Handler + 'static,
+        H::Error: Into<Error>,
+    {
+        self.routes.push(Route {
+            method,
+            segments: parse_pattern(pattern),
+            handler: boxed(handler),
+        });
+        self
+    }
+
+    /// Route `GET` requests to paths matching `pattern` to `handler`
+    pub fn get<H>(self, pattern: &str, handler: H) -> Self
+    where
+        H: Handler + 'static,
+        H::Error: Into<Error>,
+    {
+        self.route(Method::GET, pattern, handler)
+    }
+
+    /// Route `POST` requests to paths matching `pattern` to `handler`
+    pub fn post<H>(self, pattern: &str, handler: H) -> Self
+    where
+        H: Handler + 'static,
+        H::Error: Into<Error>,
+    {
+        self.route(Method::POST, pattern, handler)
+    }
+
+    /// Route `PUT` requests to paths matching `pattern` to `handler`
+    pub fn put<H>(self, pattern: &str, handler: H) -> Self
+    where
+        H: Handler + 'static,
+        H::Error: Into<Error>,
+    {
+        self.route(Method::PUT, pattern, handler)
+    }
+
+    /// Route `PATCH` requests to paths matching `pattern` to `handler`
+    pub fn patch<H>(self, pattern: &str, handler: H) -> Self
+    where
+        H: Handler + 'static,
+        H::Error: Into<Error>,
+    {
+        self.route(Method::PATCH, pattern, handler)
+    }
+
+    /// Route `DELETE` requests to paths matching `pattern` to `handler`
+    pub fn delete<H>(self, pattern: &str, handler: H) -> Self
+    where
+        H: Handler + 'static,
+        H::Error: Into<Error>,
+    {
+        self.route(Method::DELETE, pattern, handler)
+    }
+
+    /// Answer requests matching no route with `handler`
+    pub fn not_found<H>(self, handler: H) -> Self
+    where
+        H: Handler + 'static,
+        H::Error: Into<Error>,
+    {
+        Self {
+            not_found: Some(boxed(handler)),
+            ..self
+        }
+    }
+
+    /// Dispatch a request to the best matching route
+    fn dispatch(&mut self, request: Request, context: Context) -> RouteFuture {
+        let path = routed_path(&request);
+        let segments: Vec<&str> = path.split('/').skip(1).collect();
+        let mut best: Option<(usize, Vec<u8>, HashMap<String, String>)> = None;
+        let mut allowed: Vec<&Method> = Vec::new();
+        for (index, route) in self.routes.iter().enumerate() {
+            let params = match matches(&route.segments, &segments) {
+                Some(params) => params,
+                None => continue,
+            };
+            if route.method != request.method() {
+                if !allowed.contains(&&route.method) {
+                    allowed.push(&route.method);
+                }
+                continue;
+            }
+            let ranks: Vec<u8> = route.segments.iter().map(Segment::rank).collect();
+            if best.as_ref().is_none_or(|(_, best_ranks, _)| ranks < *best_ranks) {
+                best = Some((index, ranks, params));
+            }
+        }
+
+        match best {
+            Some((index, _, params)) => {
+                let request = with_captured_parameters(request, params);
+                (self.routes[index].handler)(request, context)
+            }
+            None if !allowed.is_empty() => {
+                let allow = allowed
+                    .iter()
+                    .map(|method| method.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let response = Response::builder()
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .header(ALLOW, HeaderValue::from_str(&allow).expect("invalid allow header"))
+                    .body(Body::Empty)
+                    .expect("unable to build http::Response");
+                Box::pin(async move { Ok(response) })
+            }
+            None => match &mut self.not_found {
+                Some(not_found) => not_found(request, context),
+                None => {
+                    let mut response = Response::new(Body::Empty);
+                    *response.status_mut() = StatusCode::NOT_FOUND;
+                    Box::pin(async move { Ok(response) })
+                }
+            },
+        }
+    }
+}
+
+impl Handler for Router {
+    type Response = Response<Body>;
+    type Error = Error;
+    type Fut = RouteFuture;
+    fn call(&mut self, event: Request, context: Context) -> Self::Fut {
+        self.dispatch(event, context)
+    }
+}
+
+/// Erases the type of a handler
+fn boxed<H>(mut handler: H) -> BoxHandler
+where
+    H: Handler + 'static,
+    H::Error: Into<Error>,
+{
+    Box::new(move |request, context| {
+        let fut = handler.call(request, context);
+        Box::pin(async move { fut.await.map(IntoResponse::into_response).map_err(Into::into) })
+    })
+}
+
+/// Parses a route pattern into segments
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    assert!(pattern.starts_with('/'), "route pattern {} must start with /", pattern);
+    let trimmed = pattern.trim_end_matches('/');
+    let parts: Vec<&str> = if trimmed.is_empty() {
+        Vec::new()
+    } else {
+        trimmed.split('/').skip(1).collect()
+    };
+    let last = parts.len().saturating_sub(1);
+    parts
+        .iter()
+        .enumerate()
+        .map(|(position, part)| {
+            if let Some(name) = part.strip_prefix(':') {
+                assert!(!name.is_empty(), "route pattern {} has an unnamed parameter", pattern);
+                Segment::Param(name.into())
+            } else if let Some(name) = part.strip_prefix('*') {
+                assert!(!name.is_empty(), "route pattern {} has an unnamed wildcard", pattern);
+                assert!(
+                    position == last,
+                    "route pattern {} has a wildcard before its end",
+                    pattern
+                );
+                Segment::Wildcard(name.into())
+            } else {
+                Segment::Literal((*part).into())
+            }
+        })
+        .collect()
+}
+
+/// Matches path segments against pattern segments, returning the captured parameters
+fn matches(pattern: &[Segment], path: &[&str]) -> Option<HashMap<String, String>> {
+    let mut params = HashMap::new();
+    for (position, segment) in pattern.iter().enumerate() {
+        match segment {
+            Segment::Wildcard(name) => {
+                params.insert(
+                    name.clone(),
+                    percent_decode(&path[position.min(path.len())..].join("/")),
+                );
+                return Some(params);
+            }
+            Segment::Param(name) => match path.get(position) {
+                Some(value) if !value.is_empty() => {
+                    params.insert(name.clone(), percent_decode(value));
+                }
+                _ => return None,
+            },
+            Segment::Literal(literal) => {
+                if path.get(position) != Some(&literal.as_str()) {
+                    return None;
+                }
+            }
+        }
+    }
+    if path.len() == pattern.len() {
+        Some(params)
+    } else {
+        None
+    }
+}
+
+/// Returns the request path without a trailing slash or the HTTP API stage prefix
+fn routed_path(request: &Request) -> String {
+    let mut path = request.uri().path();
+    if let Some(RequestContext::ApiGatewayV2(context)) = request.extensions().get::<RequestContext>() {
+        if !context.stage.is_empty() && context.stage != "$default" {
+            let prefix = format!("/{}", context.stage);
+            if path == prefix {
+                path = "/";
+            } else if path.starts_with(&prefix) && path[prefix.len()..].starts_with('/') {
+                path = &path[prefix.len()..];
+            }
+        }
+    }
+    path.trim_end_matches('/').into()
+}
+
+/// Adds captured parameters to the request's path parameters, replacing provided ones of the same name
+fn with_captured_parameters(request: Request, captured: HashMap<String, String>) -> Request {
+    if captured.is_empty() {
+        return request;
+    }
+    let mut params: HashMap<String, Vec<String>> = request.path_parameters().0.as_ref().clone();
+    for (name, value) in captured {
+        params.insert(name, vec![value]);
+    }
+    let mut request = request;
+    request
+        .extensions_mut()
+        .insert(PathParameters(StrMap(Arc::new(params))));
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::LambdaRequest;
+
+    fn request(method: Method, uri: &str) -> Request {
+        http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::Empty)
+            .expect("failed to build request")
+    }
+
+    fn echo(name: &'static str) -> impl Handler<Error = Error> + 'static {
+        move |request: Request, _: Context| async move {
+            let mut params: Vec<_> = request
+                .path_parameters()
+                .0
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v.join(",")))
+                .collect();
+            params.sort();
+            Ok::<_, Error>(format!("{} {}", name, params.join("&")).trim_end().to_string())
+        }
+    }
+
+    async fn route(router: &mut Router, method: Method, uri: &str) -> Response<Body> {
+        router
+            .call(request(method, uri), Context::default())
+            .await
+            .expect("failed to route request")
+    }
+
+    fn body(response: &Response<Body>) -> &str {
+        match response.body() {
+            Body::Text(text) => text,
+            other => panic!("expected a text body, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn routes_by_method_and_path() {
+        let mut router = Router::new()
+            .get("/users/:id", echo("get"))
+            .post("/users", echo("create"))
+            .get("/", echo("root"));
+        assert_eq!(body(&route(&mut router, Method::GET, "/users/42").await), "get id=42");
+        assert_eq!(body(&route(&mut router, Method::POST, "/users").await), "create");
+        assert_eq!(body(&route(&mut router, Method::GET, "/").await), "root");
+    }
+
+    #[tokio::test]
+    async fn trailing_slashes_are_not_significant() {
+        let mut router = Router::new()
+            .get("/users/", echo("list"))
+            .get("/users/:id", echo("get"));
+        assert_eq!(body(&route(&mut router, Method::GET, "/users").await), "list");
+        assert_eq!(body(&route(&mut router, Method::GET, "/users/").await), "list");
+        assert_eq!(body(&route(&mut router, Method::GET, "/users/7/").await), "get id=7");
+    }
+
+    #[tokio::test]
+    async fn wildcards_capture_the_rest_of_the_path() {
+        let mut router = Router::new().get("/files/*path", echo("file"));
+        assert_eq!(
+            body(&route(&mut router, Method::GET, "/files/a/b%20c.txt").await),
+            "file path=a/b c.txt"
+        );
+        assert_eq!(body(&route(&mut router, Method::GET, "/files").await), "file path=");
+    }
+
+    #[tokio::test]
+    async fn overlapping_routes_prefer_literal_segments() {
+        let mut router = Router::new()
+            .get("/users/*rest", echo("rest"))
+            .get("/users/:id", echo("param"))
+            .get("/users/me", echo("literal"))
+            .get("/users/:id", echo("shadowed"));
+        assert_eq!(body(&route(&mut router, Method::GET, "/users/me").await), "literal");
+        assert_eq!(body(&route(&mut router, Method::GET, "/users/1").await), "param id=1");
+        assert_eq!(
+            body(&route(&mut router, Method::GET, "/users/1/posts").await),
+            "rest rest=1/posts"
+        );
+    }
+
+    #[tokio::test]
+    async fn other_methods_are_not_allowed() {
+        let mut router = Router::new()
+            .get("/users/:id", echo("get"))
+            .delete("/users/:id", echo("delete"))
+            .post("/users", echo("create"));
+        let response = route(&mut router, Method::PUT, "/users/1").await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[ALLOW], "GET, DELETE");
+    }
+
+    #[tokio::test]
+    async fn unmatched_paths_are_not_found() {
+        let mut router = Router::new().get("/users", echo("list"));
+        let response = route(&mut router, Method::GET, "/posts").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.body(), &Body::Empty);
+
+        let mut router = router.not_found(echo("missing"));
+        assert_eq!(body(&route(&mut router, Method::GET, "/posts").await), "missing");
+    }
+
+    #[tokio::test]
+    async fn captured_parameters_extend_provided_ones() {
+        let mut router = Router::new().get("/users/:id", echo("get"));
+        let mut provided = HashMap::new();
+        provided.insert("proxy".to_string(), vec!["users/1".to_string()]);
+        provided.insert("id".to_string(), vec!["replaced".to_string()]);
+        let request = request(Method::GET, "/users/1").with_path_parameters(provided);
+        let response = router.call(request, Context::default()).await.expect("failed to route");
+        assert_eq!(body(&response), "get id=1&proxy=users/1");
+    }
+
+    #[tokio::test]
+    async fn http_api_stages_are_stripped() {
+        let event: LambdaRequest<'_> = serde_json::from_str(include_str!(
+            "../tests/data/apigw_v2_proxy_request_lambda_authorizer.json"
+        ))
+        .expect("failed to deserialize request");
+        let mut router = Router::new().post("/orders", echo("orders"));
+        let response = router
+            .call(event.into(), Context::default())
+            .await
+            .expect("failed to route");
+        assert_eq!(body(&response), "orders");
+    }
+
+    #[test]
+    #[should_panic(expected = "wildcard before its end")]
+    fn wildcards_must_be_last() {
+        Router::new().get("/files/*path/meta", echo("file"));
+    }
+}
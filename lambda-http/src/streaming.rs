@@ -0,0 +1,439 @@
+//! Streamed responses for Lambda Function URLs
+//!
+//! Function URLs configured with the `RESPONSE_STREAM` invoke mode can send their response
+//! body as it is produced. Handlers return a [`StreamingResponse`](struct.StreamingResponse.html)
+//! wrapping a stream of body chunks and are run with
+//! [`lambda::streaming::run`](../lambda/streaming/fn.run.html).
+//!
+//! ```rust,no_run
+//! use bytes::Bytes;
+//! use futures::stream::{self, Stream};
+//! use lambda_http::{
+//!     http::header::{HeaderValue, CONTENT_TYPE},
+//!     lambda::{self, Context},
+//!     streaming::{streaming_handler, StreamingResponse},
+//!     Request,
+//! };
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     lambda::streaming::run(streaming_handler(hello)).await?;
+//!     Ok(())
+//! }
+//!
+//! async fn hello(_: Request, _: Context) -> Result<StreamingResponse<impl Stream<Item = Result<Bytes, Error>>>, Error> {
+//!     let body = stream::iter(vec![Ok(Bytes::from("hello ")), Ok(Bytes::from("world"))]);
+//!     Ok(StreamingResponse::new(body).header(CONTENT_TYPE, HeaderValue::from_static("text/plain")))
+//! }
+//! ```
+
+use bytes::Bytes;
+use futures::{
+    future::FutureExt,
+    stream::{self, Stream, StreamExt, TryStreamExt},
+};
+use http::{
+    header::{HeaderMap, HeaderValue, IntoHeaderName, SET_COOKIE},
+    Response, StatusCode,
+};
+use lambda::{streaming::StreamResponse, Handler as LambdaHandler};
+use serde_derive::Serialize;
+use std::{collections::BTreeMap, future::Future, pin::Pin};
+
+use crate::{request::LambdaRequest, Context, Error, Request};
+
+/// The content type the Runtime API expects of streamed Function URL responses
+const HTTP_INTEGRATION_CONTENT_TYPE: &str = "application/vnd.awslambda.http-integration-response";
+
+/// Separates the JSON prelude from the body of a streamed Function URL response
+const PRELUDE_SEPARATOR: [u8; 8] = [0; 8];
+
+/// The body stream sent to the Runtime API, a JSON prelude followed by the response's chunks
+pub type StreamingBody = Pin<Box<dyn Stream<Item = Result<Bytes, Error>>>>;
+
+/// A response whose body is streamed to the client as it is produced
+///
+/// Headers with several values are sent joined by commas, with the exception of `Set-Cookie`
+/// headers which are sent as cookies.
+#[derive(Debug)]
+pub struct StreamingResponse<S> {
+    status: StatusCode,
+    headers: HeaderMap,
+    cookies: Vec<String>,
+    body: S,
+}
+
+impl<S> StreamingResponse<S> {
+    /// Return a `200 OK` response streaming `body`
+    pub fn new(body: S) -> Self {
+        Self {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            cookies: Vec::new(),
+            body,
+        }
+    }
+
+    /// Set the response status
+    pub fn status(self, status: StatusCode) -> Self {
+        Self { status, ..self }
+    }
+
+    /// Append a response header
+    pub fn header<K>(mut self, name: K, value: HeaderValue) -> Self
+    where
+        K: IntoHeaderName,
+    {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Append a cookie, in the form of a `Set-Cookie` header value
+    pub fn cookie<C>(mut self, cookie: C) -> Self
+    where
+        C: Into<String>,
+    {
+        self.cookies.push(cookie.into());
+        self
+    }
+}
+
+impl<S> From<Response<S>> for StreamingResponse<S> {
+    fn from(response: Response<S>) -> Self {
+        let (mut parts, body) = response.into_parts();
+        let cookies = parts
+            .headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(String::from)
+            .collect();
+        parts.headers.remove(SET_COOKIE);
+        Self {
+            status: parts.status,
+            headers: parts.headers,
+            cookies,
+            body,
+        }
+    }
+}
+
+/// The JSON prelude carrying a streamed response's status, headers and cookies
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Prelude<'a> {
+    status_code: u16,
+    headers: BTreeMap<&'a str, String>,
+    cookies: &'a [String],
+}
+
+impl<S, E> StreamingResponse<S>
+where
+    S: Stream<Item = Result<Bytes, E>> + 'static,
+    E: Into<Error> + 'static,
+{
+    /// Converts the response into the prelude and body chunks expected by the Runtime API
+    fn into_stream_response(self) -> StreamResponse<StreamingBody> {
+        let mut headers = BTreeMap::new();
+        for name in self.headers.keys() {
+            let values = self
+                .headers
+                .get_all(name)
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()))
+                .collect::<Vec<_>>();
+            headers.insert(name.as_str(), values.join(", "));
+        }
+        let mut prelude = serde_json::to_vec(&Prelude {
+            status_code: self.status.as_u16(),
+            headers,
+            cookies: &self.cookies,
+        })
+        .expect("unable to serialize response prelude");
+        prelude.extend_from_slice(&PRELUDE_SEPARATOR);
+        let body = stream::once(async { Ok(Bytes::from(prelude)) }).chain(self.body.map_err(Into::into));
+        StreamResponse::new(HTTP_INTEGRATION_CONTENT_TYPE, Box::pin(body) as StreamingBody)
+    }
+}
+
+/// Functions serving streamed Function URL responses must conform to this type.
+///
+/// This mirrors [`Handler`](../trait.Handler.html), with handlers returning a
+/// [`StreamingResponse`](struct.StreamingResponse.html) rather than an `IntoResponse` type
+pub trait StreamingHandler: Sized {
+    /// The type of error the response body stream may end with
+    type BodyError: Into<Error> + 'static;
+    /// The type of the response body stream
+    type Body: Stream<Item = Result<Bytes, Self::BodyError>> + 'static;
+    /// The type of Future this Handler will return
+    type Fut: Future<Output = Result<StreamingResponse<Self::Body>, Error>> + 'static;
+    /// Function used to execute handler behavior
+    fn call(&mut self, event: Request, context: Context) -> Self::Fut;
+}
+
+/// An implementation of `StreamingHandler` for a given closure return a `Future` representing the computed response
+impl<F, Fut, S, E> StreamingHandler for F
+where
+    F: FnMut(Request, Context) -> Fut,
+    Fut: Future<Output = Result<StreamingResponse<S>, Error>> + Send + 'static,
+    S: Stream<Item = Result<Bytes, E>> + 'static,
+    E: Into<Error> + 'static,
+{
+    type BodyError = E;
+    type Body = S;
+    type Fut = Fut;
+    fn call(&mut self, event: Request, context: Context) -> Self::Fut {
+        (*self)(event, context)
+    }
+}
+
+/// Adapts a [`StreamingHandler`](trait.StreamingHandler.html) to the `lambda::streaming::run` interface
+pub fn streaming_handler<H: StreamingHandler>(handler: H) -> StreamingAdapter<H> {
+    StreamingAdapter { handler }
+}
+
+/// Exists only to satisfy the trait cover rule for `lambda::Handler` impl
+///
+/// User code should never need to interact with this type directly.
+pub struct StreamingAdapter<H: StreamingHandler> {
+    handler: H,
+}
+
+impl<H: StreamingHandler> LambdaHandler<LambdaRequest<'_>, StreamResponse<StreamingBody>> for StreamingAdapter<H> {
+    type Error = Error;
+    type Fut = Pin<Box<dyn Future<Output = Result<StreamResponse<StreamingBody>, Error>>>>;
+    fn call(&mut self, event: LambdaRequest<'_>, context: Context) -> Self::Fut {
+        let mut request = event.into_request(true);
+        request.extensions_mut().insert(context.clone());
+        Box::pin(
+            self.handler
+                .call(request, context)
+                .map(|response| response.map(StreamingResponse::into_stream_response)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestExt;
+    use http::header::CONTENT_TYPE;
+    use serde_json::{json, Value};
+    use std::io;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    fn chunks(chunks: Vec<Result<&'static str, &'static str>>) -> impl Stream<Item = Result<Bytes, Error>> {
+        stream::iter(
+            chunks
+                .into_iter()
+                .map(|chunk| chunk.map(Bytes::from).map_err(Error::from)),
+        )
+    }
+
+    fn split_prelude(body: &[u8]) -> (Value, &[u8]) {
+        let separator = body
+            .windows(PRELUDE_SEPARATOR.len())
+            .position(|window| window == PRELUDE_SEPARATOR)
+            .expect("no prelude separator");
+        let prelude = serde_json::from_slice(&body[..separator]).expect("prelude is not json");
+        (prelude, &body[separator + PRELUDE_SEPARATOR.len()..])
+    }
+
+    #[test]
+    fn from_response_moves_set_cookie_headers_to_cookies() {
+        let response = Response::builder()
+            .status(StatusCode::ACCEPTED)
+            .header(CONTENT_TYPE, "text/plain")
+            .header(SET_COOKIE, "a=1")
+            .header(SET_COOKIE, "b=2; Secure")
+            .body(())
+            .expect("failed to build response");
+        let response = StreamingResponse::from(response);
+        assert_eq!(response.status, StatusCode::ACCEPTED);
+        assert_eq!(response.headers.len(), 1);
+        assert_eq!(response.cookies, vec!["a=1", "b=2; Secure"]);
+    }
+
+    #[tokio::test]
+    async fn adapter_streams_prelude_then_chunks() {
+        let mut adapter = streaming_handler(|request: Request, _| async move {
+            assert_eq!(request.lambda_context().request_id, "my-request-id");
+            Ok(
+                StreamingResponse::new(chunks(vec![Ok("data: 1\n\n"), Ok("data: 2\n\n")]))
+                    .status(StatusCode::CREATED)
+                    .header(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"))
+                    .header("x-trace", HeaderValue::from_static("a"))
+                    .header("x-trace", HeaderValue::from_static("b"))
+                    .cookie("session=1"),
+            )
+        });
+        let event = serde_json::from_str(include_str!("../tests/data/apigw_v2_proxy_request.json"))
+            .expect("failed to deserialize request");
+        let mut context = Context::default();
+        context.request_id = "my-request-id".into();
+        let response = LambdaHandler::call(&mut adapter, event, context)
+            .await
+            .expect("failed to handle request");
+        assert_eq!(response.content_type(), HTTP_INTEGRATION_CONTENT_TYPE);
+        let sent: Vec<Bytes> = response.into_body().try_collect().await.expect("response body failed");
+
+        let (prelude, rest) = split_prelude(&sent[0]);
+        assert_eq!(
+            prelude,
+            json!({
+                "statusCode": 201,
+                "headers": { "content-type": "text/event-stream", "x-trace": "a, b" },
+                "cookies": ["session=1"]
+            })
+        );
+        assert!(rest.is_empty());
+        assert_eq!(&sent[1..], &[Bytes::from("data: 1\n\n"), Bytes::from("data: 2\n\n")]);
+    }
+
+    /// What a stub Runtime API received for a streamed response
+    struct Recorded {
+        head: String,
+        chunks: Vec<Vec<u8>>,
+        trailers: Vec<String>,
+    }
+
+    /// Reads an HTTP/1.1 request head, returning its lines
+    async fn read_head<R>(reader: &mut R) -> io::Result<Vec<String>>
+    where
+        R: AsyncBufReadExt + Unpin,
+    {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await?;
+            let line = line.trim_end().to_owned();
+            if line.is_empty() {
+                return Ok(lines);
+            }
+            lines.push(line);
+        }
+    }
+
+    /// Serves a single invocation of `event`, then records the streamed response to it
+    async fn stub_runtime(mut listener: TcpListener, event: &'static str) -> io::Result<Recorded> {
+        loop {
+            let (socket, _) = listener.accept().await?;
+            let mut reader = BufReader::new(socket);
+            let head = read_head(&mut reader).await?;
+            if head[0].starts_with("GET /2018-06-01/runtime/invocation/next ") {
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nlambda-runtime-aws-request-id: my-request-id\r\nlambda-runtime-deadline-ms: 1542409706888\r\nlambda-runtime-invoked-function-arn: arn:aws:lambda:us-east-2:123456789012:function:custom-runtime\r\nlambda-runtime-trace-id: Root=1-5bef4de7-ad49b0e87f6ef6c87fc2e700\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    event.len(),
+                    event
+                );
+                reader.get_mut().write_all(response.as_bytes()).await?;
+                continue;
+            }
+            let mut chunks = Vec::new();
+            loop {
+                let mut size = String::new();
+                reader.read_line(&mut size).await?;
+                let size = usize::from_str_radix(size.trim_end(), 16).expect("invalid chunk size");
+                if size == 0 {
+                    break;
+                }
+                let mut chunk = vec![0; size + 2];
+                reader.read_exact(&mut chunk).await?;
+                chunk.truncate(size);
+                chunks.push(chunk);
+            }
+            let trailers = read_head(&mut reader).await?;
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\n\r\n")
+                .await?;
+            return Ok(Recorded {
+                head: head.join("\r\n"),
+                chunks,
+                trailers,
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn runtime_receives_prelude_separator_and_chunks() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("failed to bind");
+        let url = format!("http://{}", listener.local_addr().expect("no local address"));
+        let runtime = tokio::spawn(stub_runtime(
+            listener,
+            include_str!("../tests/data/apigw_v2_proxy_request.json"),
+        ));
+        let func = streaming_handler(|_, _| async {
+            Ok(
+                StreamingResponse::new(chunks(vec![Ok("data: 1\n\n"), Ok("data: 2\n\n")]))
+                    .header(CONTENT_TYPE, HeaderValue::from_static("text/event-stream")),
+            )
+        });
+        lambda::streaming::run_simulated(func, &url)
+            .await
+            .expect("failed to run function");
+        let recorded = runtime
+            .await
+            .expect("stub runtime panicked")
+            .expect("stub runtime failed");
+
+        assert!(recorded
+            .head
+            .starts_with("POST /2018-06-01/runtime/invocation/my-request-id/response HTTP/1.1"));
+        assert!(recorded
+            .head
+            .contains("lambda-runtime-function-response-mode: streaming"));
+        assert!(recorded
+            .head
+            .contains(&format!("content-type: {}", HTTP_INTEGRATION_CONTENT_TYPE)));
+        assert!(recorded.trailers.is_empty());
+        let (prelude, rest) = split_prelude(&recorded.chunks[0]);
+        assert_eq!(
+            prelude,
+            json!({ "statusCode": 200, "headers": { "content-type": "text/event-stream" }, "cookies": [] })
+        );
+        assert!(rest.is_empty());
+        assert_eq!(
+            &recorded.chunks[1..],
+            &[b"data: 1\n\n".to_vec(), b"data: 2\n\n".to_vec()]
+        );
+    }
+
+    #[tokio::test]
+    async fn runtime_receives_mid_stream_errors_as_trailers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("failed to bind");
+        let url = format!("http://{}", listener.local_addr().expect("no local address"));
+        let runtime = tokio::spawn(stub_runtime(
+            listener,
+            include_str!("../tests/data/apigw_v2_proxy_request.json"),
+        ));
+        let func = streaming_handler(|_, _| async {
+            Ok(StreamingResponse::new(chunks(vec![
+                Ok("data: 1\n\n"),
+                Err("upstream closed"),
+            ])))
+        });
+        lambda::streaming::run_simulated(func, &url)
+            .await
+            .expect("failed to run function");
+        let recorded = runtime
+            .await
+            .expect("stub runtime panicked")
+            .expect("stub runtime failed");
+
+        assert_eq!(recorded.chunks.len(), 2);
+        assert_eq!(recorded.trailers.len(), 2);
+        assert!(recorded.trailers[0].starts_with("Lambda-Runtime-Function-Error-Type: "));
+        let body = recorded.trailers[1]
+            .strip_prefix("Lambda-Runtime-Function-Error-Body: ")
+            .expect("no error body trailer");
+        let diagnostic: Value =
+            serde_json::from_slice(&base64::decode(body).expect("error body is not base64")).expect("not json");
+        assert_eq!(diagnostic["errorMessage"], "\"upstream closed\"");
+    }
+}
@@ -0,0 +1,137 @@
+//! Compares reporting the error of a failed invocation, through the event loop of
+//! `run_with_client`, with formatting its message into a `String` first, as the runtime used to,
+//! and with succeeding with a response of about the same size
+//!
+//! The error's `Debug` output is a few kilobytes long, like those of errors wrapping the
+//! responses of failed requests. The formatted case fails with the `String` the runtime used to
+//! format, which the loop then only copies into the report.
+//!
+//! Run with `cargo bench -p lambda --bench error_path`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use futures::future::BoxFuture;
+use http::{Request, Response, StatusCode};
+use hyper::Body;
+use lambda::{handler_fn, Context, Handler, LambdaRuntimeApi, RuntimeConfig};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tokio::runtime::{Builder, Runtime};
+
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// The invocations each iteration handles
+const INVOCATIONS: usize = 100;
+
+/// A failure carrying the response of the request which failed
+#[derive(Debug)]
+#[allow(dead_code)]
+struct UpstreamError {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl UpstreamError {
+    fn new() -> Self {
+        Self {
+            status: 503,
+            headers: (0..20)
+                .map(|i| (format!("x-upstream-header-{}", i), "a \"quoted\" value".repeat(4)))
+                .collect(),
+            body: "the service is unavailable, retry later\n".repeat(50),
+        }
+    }
+}
+
+/// A failure whose message was formatted into a `String` before it was reported
+struct Formatted(String);
+
+impl fmt::Debug for Formatted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A Runtime API serving `INVOCATIONS` empty events, then failing to serve more to end the loop
+struct Api {
+    served: AtomicUsize,
+}
+
+impl LambdaRuntimeApi for Api {
+    fn call(&self, req: Request<Body>) -> BoxFuture<'_, Result<Response<Body>, Error>> {
+        Box::pin(async move {
+            if req.uri().path() != "/2018-06-01/runtime/invocation/next" {
+                hyper::body::to_bytes(req.into_body()).await?;
+                return Ok(Response::builder().status(StatusCode::ACCEPTED).body(Body::empty())?);
+            }
+            let served = self.served.fetch_add(1, Ordering::Relaxed);
+            if served == INVOCATIONS {
+                return Err("the invocations were served".into());
+            }
+            Ok(Response::builder()
+                .header("lambda-runtime-aws-request-id", format!("{:036}", served))
+                .header("lambda-runtime-deadline-ms", "1542409706888")
+                .header(
+                    "lambda-runtime-invoked-function-arn",
+                    "arn:aws:lambda:us-east-2:123456789012:function:custom-runtime",
+                )
+                .header("lambda-runtime-trace-id", "Root=1-5bef4de7-ad49b0e87f6ef6c87fc2e700")
+                .body(Body::from("{}"))?)
+        })
+    }
+}
+
+/// Handles `INVOCATIONS` invocations with `handler`
+fn run<F, B>(rt: &mut Runtime, handler: F)
+where
+    F: Handler<Value, B>,
+    <F as Handler<Value, B>>::Error: fmt::Debug + 'static,
+    B: Serialize,
+{
+    let api = Api {
+        served: AtomicUsize::new(0),
+    };
+    let res = rt.block_on(lambda::run_with_client(api, handler, RuntimeConfig::new()));
+    assert!(res.is_err(), "the loop ended without its Runtime API failing");
+}
+
+fn invocations(c: &mut Criterion) {
+    let mut rt = Builder::new().basic_scheduler().enable_all().build().unwrap();
+    let mut group = c.benchmark_group("invocations");
+    group.bench_function("failed/formatted", |b| {
+        b.iter(|| {
+            run(
+                &mut rt,
+                handler_fn(|_: Value, _: Context| async {
+                    Err::<String, _>(Formatted(format!("{:?}", UpstreamError::new())))
+                }),
+            )
+        })
+    });
+    group.bench_function("failed/streamed", |b| {
+        b.iter(|| {
+            run(
+                &mut rt,
+                handler_fn(|_: Value, _: Context| async { Err::<String, _>(UpstreamError::new()) }),
+            )
+        })
+    });
+    group.bench_function("succeeded", |b| {
+        b.iter(|| {
+            run(
+                &mut rt,
+                handler_fn(|_: Value, _: Context| async {
+                    Ok::<_, UpstreamError>(format!("{:?}", UpstreamError::new()))
+                }),
+            )
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, invocations);
+criterion_main!(benches);
@@ -1,7 +1,8 @@
 use crate::{
-    requests::{IntoResponse, NextEventResponse},
+    requests::{IntoResponse, NextEventResponse, FUNCTION_ERROR_TYPE_HEADER},
     Error,
 };
+use futures::future::BoxFuture;
 use http::{
     uri::{PathAndQuery, Scheme},
     HeaderValue, Method, Request, Response, StatusCode, Uri,
@@ -61,10 +62,26 @@ where
     }
 }
 
+/// What a [`Client`] sends besides the requests it is given.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ClientConfig {
+    /// Leaves the `Lambda-Runtime-Function-Error-Type` header out of error reports, which then
+    /// name the type of their error in their body only.
+    pub omit_error_type_header: bool,
+}
+
 #[derive(Debug)]
 pub(crate) struct Client<C = HttpConnector> {
     base: Uri,
-    client: hyper::Client<C>,
+    hyper: hyper::Client<C>,
+    config: ClientConfig,
+}
+
+impl Client {
+    /// Creates a client of the API at `base`, over hyper's default connector.
+    pub fn new(base: Uri) -> Self {
+        Self::with(base, hyper::Client::new())
+    }
 }
 
 impl<C> Client<C>
@@ -72,7 +89,16 @@ where
     C: hyper::client::connect::Connect + Sync + Send + Clone + 'static,
 {
     pub fn with(base: Uri, client: hyper::Client<C>) -> Self {
-        Self { base, client }
+        Self {
+            base,
+            hyper: client,
+            config: ClientConfig::default(),
+        }
+    }
+
+    pub const fn with_config(mut self, config: ClientConfig) -> Self {
+        self.config = config;
+        self
     }
 
     fn set_origin<B>(&self, req: Request<B>) -> Result<Request<B>, Error> {
@@ -96,15 +122,27 @@ where
     }
 
     pub(crate) async fn call(&self, req: Request<Body>) -> Result<Response<Body>, Error> {
-        let req = self.set_origin(req)?;
+        let mut req = self.set_origin(req)?;
+        if self.config.omit_error_type_header {
+            req.headers_mut().remove(FUNCTION_ERROR_TYPE_HEADER);
+        }
         let (parts, body) = req.into_parts();
         let body = Body::from(body);
         let req = Request::from_parts(parts, body);
-        let response = self.client.request(req).await?;
+        let response = self.hyper.request(req).await?;
         Ok(response)
     }
 }
 
+impl<C> crate::LambdaRuntimeApi for Client<C>
+where
+    C: hyper::client::connect::Connect + Sync + Send + Clone + 'static,
+{
+    fn call(&self, req: Request<Body>) -> BoxFuture<'_, Result<Response<Body>, Error>> {
+        Box::pin(Self::call(self, req))
+    }
+}
+
 async fn next_event(req: &Request<Body>) -> Result<Response<Body>, Error> {
     let path = "/2018-06-01/runtime/invocation/next";
     assert_eq!(req.method(), Method::GET);
@@ -140,7 +178,7 @@ async fn event_err(req: &Request<Body>, id: &str) -> Result<Response<Body>, Erro
 
     assert_eq!(req.method(), Method::POST);
     let header = "lambda-runtime-function-error-type";
-    let expected = "unhandled";
+    let expected = "InvalidEventDataError";
     assert_eq!(req.headers()[header], HeaderValue::try_from(expected)?);
 
     let rsp = Response::builder().status(StatusCode::ACCEPTED).body(Body::empty())?;
@@ -0,0 +1,361 @@
+//! The configuration Lambda hands the runtime through environment variables.
+use crate::{
+    client::{Client, ClientConfig},
+    init,
+    requests::{InitErrorRequest, IntoRequest},
+    types::Diagnostic,
+    Error,
+};
+use http::Uri;
+use std::{convert::TryFrom, env, fmt};
+
+/// The error type reported to the Runtime API when the configuration is invalid
+const INIT_ERROR_TYPE: &str = "Runtime.InvalidConfiguration";
+
+const RUNTIME_API: &str = "AWS_LAMBDA_RUNTIME_API";
+const FUNCTION_NAME: &str = "AWS_LAMBDA_FUNCTION_NAME";
+const FUNCTION_MEMORY_SIZE: &str = "AWS_LAMBDA_FUNCTION_MEMORY_SIZE";
+const FUNCTION_VERSION: &str = "AWS_LAMBDA_FUNCTION_VERSION";
+const LOG_STREAM_NAME: &str = "AWS_LAMBDA_LOG_STREAM_NAME";
+const LOG_GROUP_NAME: &str = "AWS_LAMBDA_LOG_GROUP_NAME";
+
+/// Configuration derived from environment variables.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// The host and port of the [runtime API](https://docs.aws.amazon.com/lambda/latest/dg/runtimes-api.html).
+    pub endpoint: String,
+    /// The name of the function.
+    pub function_name: String,
+    /// The amount of memory available to the function in MB.
+    pub memory: i32,
+    /// The version of the function being executed.
+    pub version: String,
+    /// The name of the Amazon `CloudWatch` Logs stream for the function.
+    pub log_stream: String,
+    /// The name of the Amazon `CloudWatch` Logs group for the function.
+    pub log_group: String,
+}
+
+impl Config {
+    /// Attempts to read configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Fails with every required variable which is missing or invalid, not just the first.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        init::started();
+        Self::from_vars(|name| env::var(name))
+    }
+
+    /// Reads the configuration from the variables `lookup` finds
+    fn from_vars(lookup: impl Fn(&str) -> Result<String, env::VarError>) -> Result<Self, ConfigError> {
+        let mut invalid = Vec::new();
+        let mut var = |name: &'static str, expected: &'static str, valid: fn(&str) -> bool| {
+            let problem = match lookup(name) {
+                Ok(value) if value.is_empty() => InvalidVariable::Missing { name, expected },
+                Ok(value) if valid(&value) => return Some(value),
+                Ok(value) => InvalidVariable::Malformed { name, value, expected },
+                Err(env::VarError::NotPresent) => InvalidVariable::Missing { name, expected },
+                Err(env::VarError::NotUnicode(value)) => InvalidVariable::Malformed {
+                    name,
+                    value: value.to_string_lossy().into_owned(),
+                    expected,
+                },
+            };
+            invalid.push(problem);
+            None
+        };
+        let endpoint = var(
+            RUNTIME_API,
+            "the host and port of the Runtime API, like 127.0.0.1:9001",
+            |value| Uri::try_from(value).is_ok_and(|uri| uri.authority().is_some()),
+        );
+        let function_name = var(FUNCTION_NAME, "the name of the function", |_| true);
+        let memory = var(
+            FUNCTION_MEMORY_SIZE,
+            "the memory of the function in MB, a whole number like 128",
+            |value| value.parse::<i32>().is_ok_and(|memory| memory > 0),
+        );
+        let version = var(FUNCTION_VERSION, "the version of the function, like $LATEST", |_| true);
+        let log_stream = var(
+            LOG_STREAM_NAME,
+            "the name of the CloudWatch Logs stream of the function",
+            |_| true,
+        );
+        let log_group = var(
+            LOG_GROUP_NAME,
+            "the name of the CloudWatch Logs group of the function",
+            |_| true,
+        );
+
+        match (endpoint, function_name, memory, version, log_stream, log_group) {
+            (Some(endpoint), Some(function_name), Some(memory), Some(version), Some(log_stream), Some(log_group)) => {
+                Ok(Self {
+                    endpoint,
+                    function_name,
+                    memory: memory.parse().expect("validated memory size"),
+                    version,
+                    log_stream,
+                    log_group,
+                })
+            }
+            (endpoint, ..) => Err(ConfigError { invalid, endpoint }),
+        }
+    }
+}
+
+/// The environment variables which were missing or invalid when the runtime started.
+///
+/// The runtime requires `AWS_LAMBDA_RUNTIME_API`, `AWS_LAMBDA_FUNCTION_NAME`,
+/// `AWS_LAMBDA_FUNCTION_MEMORY_SIZE`, `AWS_LAMBDA_FUNCTION_VERSION`, `AWS_LAMBDA_LOG_STREAM_NAME`
+/// and `AWS_LAMBDA_LOG_GROUP_NAME`, which Lambda sets for every function. The variables it reads
+/// besides those are optional, and never part of this error:
+///
+/// - `AWS_LAMBDA_INITIALIZATION_TYPE`, which [`init`](init/index.html) reports
+/// - `_X_AMZN_TRACE_ID`, the trace of the current invocation
+/// - `LAMBDA_RUNTIME_RECORD_DIR`, where [`record`](record/index.html) writes invocations
+/// - with the `xray` feature, `AWS_XRAY_DAEMON_ADDRESS`
+/// - with the `local` feature, `AWS_LAMBDA_FUNCTION_TIMEOUT`, and `AWS_LAMBDA_FUNCTION_NAME`
+///   and `AWS_LAMBDA_FUNCTION_MEMORY_SIZE` standing in for the function's, which local
+///   invocations don't require
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    invalid: Vec<InvalidVariable>,
+    /// The endpoint of the Runtime API, if it is valid itself
+    endpoint: Option<String>,
+}
+
+impl ConfigError {
+    /// Every variable which was missing or invalid, in the order the runtime reads them.
+    #[must_use]
+    pub fn variables(&self) -> &[InvalidVariable] {
+        &self.invalid
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid Lambda runtime configuration:")?;
+        for variable in &self.invalid {
+            write!(f, "\n  {variable}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A required environment variable which was missing or invalid.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvalidVariable {
+    /// The variable is unset or empty.
+    Missing {
+        /// The name of the variable.
+        name: &'static str,
+        /// What its value should look like.
+        expected: &'static str,
+    },
+    /// The value of the variable doesn't have the expected format.
+    Malformed {
+        /// The name of the variable.
+        name: &'static str,
+        /// Its value, lossily converted to UTF-8 if it wasn't.
+        value: String,
+        /// What its value should look like.
+        expected: &'static str,
+    },
+}
+
+impl InvalidVariable {
+    /// The name of the variable.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Missing { name, .. } | Self::Malformed { name, .. } => name,
+        }
+    }
+}
+
+impl fmt::Display for InvalidVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { name, expected } => write!(f, "{name} is not set; expected {expected}"),
+            Self::Malformed { name, value, expected } => write!(f, "{name} is {value:?}; expected {expected}"),
+        }
+    }
+}
+
+/// Reads the configuration from the environment, reporting why it is invalid before failing:
+/// to the `init/error` endpoint of the Runtime API when `AWS_LAMBDA_RUNTIME_API` is valid
+/// itself, through a client configured with `client`, and to stderr otherwise.
+pub async fn configure(client: ClientConfig) -> Result<Config, Error> {
+    init::started();
+    configure_from(|name| env::var(name), client).await
+}
+
+pub async fn configure_from(
+    lookup: impl Fn(&str) -> Result<String, env::VarError>,
+    client: ClientConfig,
+) -> Result<Config, Error> {
+    let e = match Config::from_vars(lookup) {
+        Ok(config) => return Ok(config),
+        Err(e) => e,
+    };
+    let reported = match &e.endpoint {
+        Some(endpoint) => report(endpoint, client, &e).await,
+        None => Err("the Runtime API is unknown".into()),
+    };
+    if reported.is_err() {
+        eprintln!("{e}");
+    }
+    Err(e.into())
+}
+
+async fn report(endpoint: &str, client: ClientConfig, e: &ConfigError) -> Result<(), Error> {
+    let client = Client::new(Uri::try_from(endpoint)?).with_config(client);
+    let req = InitErrorRequest {
+        diagnostic: Diagnostic {
+            error_type: INIT_ERROR_TYPE.to_owned(),
+            error_message: e.to_string(),
+        },
+    };
+    let rsp = client.call(req.into_req()?).await?;
+    if rsp.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("the Runtime API answered {}", rsp.status()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub::Stub;
+    use http::Method;
+    use std::collections::HashMap;
+
+    const REQUIRED: [&str; 6] = [
+        RUNTIME_API,
+        FUNCTION_NAME,
+        FUNCTION_MEMORY_SIZE,
+        FUNCTION_VERSION,
+        LOG_STREAM_NAME,
+        LOG_GROUP_NAME,
+    ];
+
+    fn environment() -> HashMap<&'static str, String> {
+        [
+            (RUNTIME_API, "127.0.0.1:9001"),
+            (FUNCTION_NAME, "helloWorld"),
+            (FUNCTION_MEMORY_SIZE, "128"),
+            (FUNCTION_VERSION, "$LATEST"),
+            (LOG_STREAM_NAME, "2020/01/01/[$LATEST]8476a536"),
+            (LOG_GROUP_NAME, "/aws/lambda/helloWorld"),
+        ]
+        .iter()
+        .map(|(name, value)| (*name, (*value).to_owned()))
+        .collect()
+    }
+
+    fn read(vars: &HashMap<&'static str, String>) -> Result<Config, ConfigError> {
+        Config::from_vars(|name| vars.get(name).cloned().ok_or(env::VarError::NotPresent))
+    }
+
+    #[test]
+    fn reads_complete_environments() {
+        let config = read(&environment()).unwrap();
+        assert_eq!(config.endpoint, "127.0.0.1:9001");
+        assert_eq!(config.memory, 128);
+        assert_eq!(config.log_group, "/aws/lambda/helloWorld");
+    }
+
+    #[test]
+    fn names_each_missing_variable() {
+        for name in REQUIRED {
+            let mut vars = environment();
+            vars.remove(name);
+            let e = read(&vars).unwrap_err();
+            assert_eq!(e.variables().len(), 1, "{e}");
+            assert!(matches!(e.variables()[0], InvalidVariable::Missing { name: missing, .. } if missing == name));
+            assert!(
+                e.to_string().contains(&format!("{name} is not set; expected ")),
+                "{}",
+                e
+            );
+            // only an invalid endpoint keeps the error from being reported to it
+            assert_eq!(e.endpoint.is_some(), name != RUNTIME_API);
+        }
+    }
+
+    #[test]
+    fn names_every_invalid_variable_at_once() {
+        let mut vars = environment();
+        vars.remove(RUNTIME_API);
+        vars.remove(LOG_STREAM_NAME);
+        vars.insert(FUNCTION_NAME, String::new());
+        vars.insert(FUNCTION_MEMORY_SIZE, "lots".to_owned());
+        let e = read(&vars).unwrap_err();
+        let names: Vec<_> = e.variables().iter().map(InvalidVariable::name).collect();
+        assert_eq!(
+            names,
+            [RUNTIME_API, FUNCTION_NAME, FUNCTION_MEMORY_SIZE, LOG_STREAM_NAME]
+        );
+        assert_eq!(
+            e.to_string(),
+            "invalid Lambda runtime configuration:\n  \
+             AWS_LAMBDA_RUNTIME_API is not set; expected the host and port of the Runtime API, like 127.0.0.1:9001\n  \
+             AWS_LAMBDA_FUNCTION_NAME is not set; expected the name of the function\n  \
+             AWS_LAMBDA_FUNCTION_MEMORY_SIZE is \"lots\"; expected the memory of the function in MB, a whole number like 128\n  \
+             AWS_LAMBDA_LOG_STREAM_NAME is not set; expected the name of the CloudWatch Logs stream of the function"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_endpoints_and_memory_sizes() {
+        for (name, value) in [
+            (RUNTIME_API, "http://"),
+            (FUNCTION_MEMORY_SIZE, "-128"),
+            (FUNCTION_MEMORY_SIZE, "128MB"),
+        ] {
+            let mut vars = environment();
+            vars.insert(name, value.to_owned());
+            let e = read(&vars).unwrap_err();
+            assert_eq!(e.variables().len(), 1, "{e}");
+            assert!(
+                matches!(&e.variables()[0], InvalidVariable::Malformed { name: malformed, value: read, .. } if *malformed == name && read == value),
+                "{}",
+                e
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn runtimes_report_invalid_configurations_to_init_error() -> Result<(), Error> {
+        for omit_error_type_header in [false, true] {
+            let stub = Stub::default();
+            let addr = stub.start().to_string();
+            let lookup = |name: &str| match name {
+                "AWS_LAMBDA_RUNTIME_API" => Ok(addr.clone()),
+                "AWS_LAMBDA_FUNCTION_MEMORY_SIZE" => Ok("lots".to_owned()),
+                _ => Err(std::env::VarError::NotPresent),
+            };
+            let client = crate::client::ClientConfig { omit_error_type_header };
+            let e = crate::config::configure_from(lookup, client).await.unwrap_err();
+
+            let received = stub.received();
+            assert_eq!(received.len(), 1, "{received:?}");
+            assert_eq!(received[0].method, Method::POST);
+            assert_eq!(received[0].path, "/2018-06-01/runtime/init/error");
+            assert_eq!(received[0].body["errorType"], "Runtime.InvalidConfiguration");
+            assert_eq!(received[0].body["errorMessage"], e.to_string());
+            assert_eq!(
+                received[0].function_error_type.as_deref(),
+                Some("Runtime.InvalidConfiguration").filter(|_| !omit_error_type_header)
+            );
+            // every variable but the endpoint is missing or invalid
+            assert_eq!(e.to_string().lines().count(), 6);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+//! An error for handlers to fail with quickly, naming the `errorType` Lambda reports, and the
+//! errors the event loop ends with.
+use crate::Error;
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    borrow::Cow,
+    fmt,
+};
+
+/// The `errorType` of errors made with [`HandlerError::new`] or converted from strings
+const HANDLER_ERROR: &str = "HandlerError";
+
+/// An error a handler fails with, reported to Lambda with its own `errorType`.
+///
+/// Strings convert into it, so that `?` turns a `&str` or `String` error into a failure of the
+/// invocation, and the category constructors set the conventional `errorType`s which Step
+/// Functions catches or callers of the function can branch on:
+///
+/// ```no_run
+/// use lambda::{handler_fn, Context, HandlerError};
+/// use serde_json::{json, Value};
+///
+/// type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     lambda::run(handler_fn(func)).await?;
+///     Ok(())
+/// }
+///
+/// async fn func(event: Value, _: Context) -> Result<Value, HandlerError> {
+///     // reported with the `errorType` `HandlerError`
+///     let name = event["name"].as_str().ok_or("the event has no name")?;
+///     if name.is_empty() {
+///         // reported with the `errorType` `BadRequest`
+///         return Err(HandlerError::bad_request("the name is empty"));
+///     }
+///     Ok(json!({ "greeting": format!("Hello, {}!", name) }))
+/// }
+/// ```
+///
+/// The `errorType` is reported as well when the error is boxed into a
+/// `Box<dyn std::error::Error + Send + Sync>`. The error captures a backtrace where it is made
+/// when `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` enable them, appended to its `errorMessage`.
+pub struct HandlerError {
+    error_type: Cow<'static, str>,
+    message: Cow<'static, str>,
+    backtrace: Backtrace,
+}
+
+impl HandlerError {
+    /// Creates an error with `message`, reported with the `errorType` `HandlerError`.
+    #[must_use]
+    pub fn new(message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            error_type: Cow::Borrowed(HANDLER_ERROR),
+            message: message.into(),
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Creates an error for an event the handler can't process, reported with the `errorType`
+    /// `BadRequest`.
+    #[must_use]
+    pub fn bad_request(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(message).with_error_type("BadRequest")
+    }
+
+    /// Creates an error for something the event names which doesn't exist, reported with the
+    /// `errorType` `NotFound`.
+    #[must_use]
+    pub fn not_found(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(message).with_error_type("NotFound")
+    }
+
+    /// Creates an error for a failure of the handler itself, reported with the `errorType`
+    /// `Internal`.
+    #[must_use]
+    pub fn internal(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(message).with_error_type("Internal")
+    }
+
+    /// Reports the error with `error_type` instead.
+    #[must_use]
+    pub fn with_error_type(mut self, error_type: impl Into<Cow<'static, str>>) -> Self {
+        self.error_type = error_type.into();
+        self
+    }
+
+    /// The `errorType` the error is reported with.
+    #[must_use]
+    pub fn error_type(&self) -> &str {
+        &self.error_type
+    }
+
+    /// The message of the error.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The backtrace captured when the error was made, if backtraces are enabled.
+    pub const fn backtrace(&self) -> &Backtrace {
+        &self.backtrace
+    }
+}
+
+impl fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// The message, then the backtrace when one was captured, which the `errorMessage` reported
+/// to Lambda is made of
+impl fmt::Debug for HandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if self.backtrace.status() == BacktraceStatus::Captured {
+            write!(f, "\n\nStack backtrace:\n{}", self.backtrace)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for HandlerError {}
+
+impl From<&str> for HandlerError {
+    fn from(message: &str) -> Self {
+        Self::new(message.to_owned())
+    }
+}
+
+impl From<String> for HandlerError {
+    fn from(message: String) -> Self {
+        Self::new(message)
+    }
+}
+
+/// Why an event loop stopped polling for events.
+///
+/// Handlers which fail or panic don't stop it: their errors are reported as those of their
+/// invocations. What does is a transport which fails, or an invocation the loop can't make
+/// sense of.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RuntimeError {
+    /// The Runtime API couldn't be reached, or failed a request.
+    Api(Error),
+    /// The Runtime API answered a request for the next event with something other than an
+    /// invocation.
+    Invocation(Error),
+    /// An event couldn't be deserialized into the type its handler takes.
+    Deserialize(Error),
+    /// The response of a handler couldn't be serialized.
+    Serialize(Error),
+    /// An invocation couldn't be recorded.
+    Record(Error),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Api(e) => write!(f, "the Runtime API failed: {e}"),
+            Self::Invocation(e) => write!(f, "the Runtime API sent an invalid invocation: {e}"),
+            Self::Deserialize(e) => write!(f, "failed to deserialize the event: {e}"),
+            Self::Serialize(e) => write!(f, "failed to serialize the response: {e}"),
+            Self::Record(e) => write!(f, "failed to record the invocation: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Api(e) | Self::Invocation(e) | Self::Deserialize(e) | Self::Serialize(e) | Self::Record(e) => {
+                Some(&**e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ErrorDiagnostic;
+    use serde_json::{json, Value};
+
+    fn reported<E: fmt::Debug + 'static>(error: &E) -> Value {
+        serde_json::to_value(ErrorDiagnostic::new(error)).unwrap()
+    }
+
+    #[test]
+    fn categories_set_conventional_error_types() {
+        for (error, error_type) in [
+            (HandlerError::new("the order has no items"), "HandlerError"),
+            (HandlerError::from("the order has no items"), "HandlerError"),
+            (
+                HandlerError::from(String::from("the order has no items")),
+                "HandlerError",
+            ),
+            (HandlerError::bad_request("the order has no items"), "BadRequest"),
+            (HandlerError::not_found("the order has no items"), "NotFound"),
+            (HandlerError::internal("the order has no items"), "Internal"),
+            (
+                HandlerError::new("the order has no items").with_error_type("OrderRefused"),
+                "OrderRefused",
+            ),
+        ] {
+            assert_eq!(error.error_type(), error_type);
+            assert_eq!(error.to_string(), "the order has no items");
+            let diagnostic = reported(&error);
+            assert_eq!(diagnostic["errorType"], error_type);
+            assert!(
+                diagnostic["errorMessage"]
+                    .as_str()
+                    .unwrap()
+                    .starts_with("the order has no items"),
+                "{}",
+                diagnostic
+            );
+        }
+    }
+
+    #[test]
+    fn boxed_errors_keep_their_error_type() {
+        let error: Error = HandlerError::not_found("no order 42").into();
+        assert_eq!(reported(&error)["errorType"], "NotFound");
+
+        // other errors are still named after their type
+        let error: Error = "no order 42".into();
+        assert_eq!(reported(&error)["errorType"], std::any::type_name::<Error>());
+    }
+
+    #[test]
+    fn question_marks_convert_strings() {
+        fn find(id: u32) -> Result<Value, HandlerError> {
+            let order: Option<Value> = None;
+            let order = order.ok_or("no such order")?;
+            Ok(json!({ "id": id, "order": order }))
+        }
+
+        let error = find(42).unwrap_err();
+        assert_eq!(error.error_type(), "HandlerError");
+        assert_eq!(error.message(), "no such order");
+    }
+}
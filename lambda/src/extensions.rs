@@ -0,0 +1,1557 @@
+//! A client for the [Lambda Extensions API](https://docs.aws.amazon.com/lambda/latest/dg/runtimes-extensions-api.html).
+//!
+//! Extensions run alongside a function in its execution environment, to prefetch secrets or
+//! forward telemetry for instance. An [`ExtensionClient`] talks to the Extensions API directly,
+//! while an [`Extension`] registers, hands every event to a handler and returns once the
+//! environment shuts down. A [`LogsSubscription`] subscribes a registered extension to the
+//! logs of the function, of the platform and of extensions, and a
+//! [`TelemetrySubscription`](telemetry::TelemetrySubscription) to their telemetry.
+//!
+//! ```no_run
+//! use lambda::extensions::{Extension, NextEvent};
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     Extension::new("telemetry-forwarder")
+//!         .run(|event| async move {
+//!             match event {
+//!                 NextEvent::Invoke { request_id, .. } => println!("invoked for {}", request_id),
+//!                 NextEvent::Shutdown { reason, .. } => println!("shutting down after {:?}", reason),
+//!             }
+//!             Ok::<(), Error>(())
+//!         })
+//!         .await
+//! }
+//! ```
+use crate::{client::Client, requests::IntoRequest, types::Diagnostic, Error};
+use futures::{future, FutureExt};
+use http::{Method, Request, Response, Uri};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Server,
+};
+use serde::{
+    de::{self, DeserializeOwned, Deserializer},
+    Deserialize, Serialize,
+};
+use serde_json::{json, Value};
+use std::{
+    convert::{Infallible, TryFrom, TryInto},
+    env,
+    future::Future,
+    panic::AssertUnwindSafe,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::{
+    runtime,
+    signal::unix::{signal, SignalKind},
+    sync::{mpsc, oneshot},
+};
+use tracing::error;
+
+pub mod telemetry;
+
+/// The header naming an extension when it registers
+pub(crate) const EXTENSION_NAME_HEADER: &str = "Lambda-Extension-Name";
+
+/// The header carrying the identifier the Extensions API assigned to a registered extension
+pub(crate) const EXTENSION_ID_HEADER: &str = "Lambda-Extension-Identifier";
+
+/// The header carrying the type of an error an extension reports
+pub(crate) const EXTENSION_ERROR_TYPE_HEADER: &str = "Lambda-Extension-Function-Error-Type";
+
+/// The error type reported by [`Extension::run`] when its handler fails
+const HANDLER_ERROR_TYPE: &str = "Extension.HandlerError";
+
+/// The error type reported by [`Extension::run_with_init`] when its initialization fails
+const INIT_ERROR_TYPE: &str = "Extension.InitError";
+
+/// The error type reported by [`Extension::run`] when its initialization or handler panics
+const PANIC_ERROR_TYPE: &str = "Extension.Panic";
+
+/// The version of the Logs API's schema subscriptions use
+const LOGS_SCHEMA_VERSION: &str = "2020-08-15";
+
+/// The host name Lambda reaches extensions' listeners at
+const DESTINATION_HOST: &str = "sandbox.localdomain";
+
+/// The path Lambda posts batches of log and telemetry records to
+const DESTINATION_PATH: &str = "/records";
+
+/// An event an extension can register for.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ExtensionEvent {
+    /// The function is invoked.
+    Invoke,
+    /// The execution environment is about to shut down. Only external extensions can register
+    /// for it: Lambda sends `SIGTERM` to the function's process instead.
+    Shutdown,
+}
+
+/// An event delivered to an extension.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "eventType", rename_all = "UPPERCASE")]
+pub enum NextEvent {
+    /// The function is invoked.
+    #[serde(rename_all = "camelCase")]
+    Invoke {
+        /// The request ID of the invocation.
+        request_id: String,
+        /// The date the invocation times out, in Unix time milliseconds.
+        deadline_ms: u64,
+        /// The ARN of the function, version or alias invoked.
+        invoked_function_arn: String,
+        /// The X-Ray tracing header of the invocation.
+        #[serde(default)]
+        tracing: Tracing,
+    },
+    /// The execution environment is about to shut down.
+    #[serde(rename_all = "camelCase")]
+    Shutdown {
+        /// Why the execution environment shuts down.
+        #[serde(rename = "shutdownReason")]
+        reason: ShutdownReason,
+        /// The date by which the extension has to exit, in Unix time milliseconds.
+        deadline_ms: u64,
+    },
+}
+
+/// The tracing header of an invocation.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Tracing {
+    /// The kind of header, `X-Amzn-Trace-Id`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The value of the header.
+    pub value: String,
+}
+
+/// Why an execution environment shuts down.
+///
+/// Reasons Lambda may add in the future deserialize into `Other`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String")]
+#[non_exhaustive]
+pub enum ShutdownReason {
+    /// `spindown`: the environment was idle.
+    Spindown,
+    /// `timeout`: the function or an extension timed out.
+    Timeout,
+    /// `failure`: the function or an extension failed.
+    Failure,
+    /// A reason this crate doesn't know about.
+    Other(String),
+}
+
+impl From<String> for ShutdownReason {
+    fn from(reason: String) -> Self {
+        match reason.as_str() {
+            "spindown" => Self::Spindown,
+            "timeout" => Self::Timeout,
+            "failure" => Self::Failure,
+            _ => Self::Other(reason),
+        }
+    }
+}
+
+/// A client for the Extensions API, bound to a registered extension.
+#[derive(Debug)]
+pub struct ExtensionClient {
+    client: Client,
+    extension_id: String,
+}
+
+impl ExtensionClient {
+    /// Registers the extension `name` for `events` with the Extensions API of the execution
+    /// environment, found through the `AWS_LAMBDA_RUNTIME_API` environment variable.
+    ///
+    /// `name` has to be the file name of the extension's executable.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `AWS_LAMBDA_RUNTIME_API` isn't set, or for the reasons `register_at` does.
+    pub async fn register(name: &str, events: &[ExtensionEvent]) -> Result<Self, Error> {
+        let endpoint = env::var("AWS_LAMBDA_RUNTIME_API")?;
+        Self::register_at(&endpoint, name, events).await
+    }
+
+    /// Registers the extension `name` for `events` with the Extensions API at `endpoint`, like
+    /// `127.0.0.1:9001`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the Extensions API can't be reached or refuses the registration.
+    pub async fn register_at(endpoint: &str, name: &str, events: &[ExtensionEvent]) -> Result<Self, Error> {
+        let uri: Uri = endpoint.try_into()?;
+        let client = Client::new(uri);
+        let req = RegisterRequest { name, events }.into_req()?;
+        let rsp = accepted(client.call(req).await?, "registration").await?;
+        let extension_id = rsp
+            .headers()
+            .get(EXTENSION_ID_HEADER)
+            .ok_or("The Extensions API did not assign an extension identifier")?
+            .to_str()?
+            .to_owned();
+        Ok(Self { client, extension_id })
+    }
+
+    /// The identifier the Extensions API assigned to the extension.
+    #[must_use]
+    pub fn extension_id(&self) -> &str {
+        &self.extension_id
+    }
+
+    /// Waits for the next event the extension registered for.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the Extensions API can't be reached, refuses the request or sends an event
+    /// which isn't understood.
+    pub async fn next_event(&self) -> Result<NextEvent, Error> {
+        let req = NextExtensionEventRequest {
+            extension_id: &self.extension_id,
+        }
+        .into_req()?;
+        let rsp = accepted(self.client.call(req).await?, "next event").await?;
+        let body = hyper::body::to_bytes(rsp.into_body()).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Reports that the extension failed to initialize, after which Lambda restarts the
+    /// execution environment.
+    ///
+    /// `error_type` is of the form `Extension.<Reason>`, like `Extension.ConfigInvalid`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the Extensions API can't be reached or refuses the report.
+    pub async fn init_error(&self, error_type: &str, error_message: &str) -> Result<(), Error> {
+        self.report("init", error_type, error_message).await
+    }
+
+    /// Reports that the extension is about to exit because of an error.
+    ///
+    /// `error_type` is of the form `Extension.<Reason>`, like `Extension.UnknownReason`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the Extensions API can't be reached or refuses the report.
+    pub async fn exit_error(&self, error_type: &str, error_message: &str) -> Result<(), Error> {
+        self.report("exit", error_type, error_message).await
+    }
+
+    /// Reports `e` as a failure of `phase`, returning it for the extension to exit with
+    async fn fail(&self, phase: &str, error_type: &str, e: Error) -> Error {
+        if let Err(report_error) = self.report(phase, error_type, &e.to_string()).await {
+            error!(message = "Failed to report an extension error", e = %report_error);
+        }
+        e
+    }
+
+    async fn report(&self, phase: &str, error_type: &str, error_message: &str) -> Result<(), Error> {
+        let req = ExtensionErrorRequest {
+            extension_id: &self.extension_id,
+            phase,
+            diagnostic: Diagnostic {
+                error_type: error_type.to_owned(),
+                error_message: error_message.to_owned(),
+            },
+        }
+        .into_req()?;
+        accepted(self.client.call(req).await?, "error report").await?;
+        Ok(())
+    }
+}
+
+/// An extension handing the events it registered for to a handler until the execution
+/// environment shuts down.
+#[derive(Debug, Clone)]
+pub struct Extension {
+    name: String,
+    events: Vec<ExtensionEvent>,
+    endpoint: Option<String>,
+}
+
+impl Extension {
+    /// Creates the extension `name`, registering for invocations and shutdowns.
+    ///
+    /// `name` has to be the file name of the extension's executable.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            events: vec![ExtensionEvent::Invoke, ExtensionEvent::Shutdown],
+            endpoint: None,
+        }
+    }
+
+    /// Registers for `events` instead. Internal extensions can only register for invocations.
+    #[must_use]
+    pub fn with_events(self, events: &[ExtensionEvent]) -> Self {
+        Self {
+            events: events.to_vec(),
+            ..self
+        }
+    }
+
+    /// Talks to the Extensions API at `endpoint`, like `127.0.0.1:9001`, instead of the one
+    /// `AWS_LAMBDA_RUNTIME_API` points to.
+    #[must_use]
+    pub fn with_endpoint(self, endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: Some(endpoint.into()),
+            ..self
+        }
+    }
+
+    /// Registers the extension and calls `handler` with every event, returning once it handled
+    /// the shutdown event.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the extension can't register or receive events. When `handler` fails or panics,
+    /// the failure is reported to the Extensions API's `exit/error` endpoint and returned, so
+    /// that returning it from `main` exits the process with a non-zero status.
+    pub async fn run<F, Fut, E>(self, handler: F) -> Result<(), Error>
+    where
+        F: FnMut(NextEvent) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+        E: Into<Error>,
+    {
+        self.run_with_init(|| future::ok::<(), Error>(()), handler).await
+    }
+
+    /// Registers the extension, awaits `init` and then calls `handler` with every event,
+    /// returning once it handled the shutdown event.
+    ///
+    /// `init` runs during the initialization phase of the execution environment, which ends once
+    /// every extension asked for its first event, so it can prepare what `handler` needs before
+    /// the function is invoked.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the extension can't register or receive events. When `init` fails or panics, the
+    /// failure is reported to the Extensions API's `init/error` endpoint and returned. Later
+    /// failures of `handler` are reported to `exit/error` instead. Failures to register can't be
+    /// reported, as the Extensions API only takes reports of registered extensions.
+    pub async fn run_with_init<I, IFut, IE, F, Fut, E>(self, init: I, mut handler: F) -> Result<(), Error>
+    where
+        I: FnOnce() -> IFut,
+        IFut: Future<Output = Result<(), IE>>,
+        IE: Into<Error>,
+        F: FnMut(NextEvent) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+        E: Into<Error>,
+    {
+        let client = match &self.endpoint {
+            Some(endpoint) => ExtensionClient::register_at(endpoint, &self.name, &self.events).await?,
+            None => ExtensionClient::register(&self.name, &self.events).await?,
+        };
+        if let Err((error_type, e)) = guarded(init(), INIT_ERROR_TYPE).await {
+            return Err(client.fail("init", error_type, e).await);
+        }
+        loop {
+            let event = client.next_event().await?;
+            let shutdown = matches!(event, NextEvent::Shutdown { .. });
+            if let Err((error_type, e)) = guarded(handler(event), HANDLER_ERROR_TYPE).await {
+                return Err(client.fail("exit", error_type, e).await);
+            }
+            if shutdown {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Awaits `fut`, telling its errors, of `error_type`, apart from its panics
+async fn guarded<Fut, E>(fut: Fut, error_type: &'static str) -> Result<(), (&'static str, Error)>
+where
+    Fut: Future<Output = Result<(), E>>,
+    E: Into<Error>,
+{
+    match AssertUnwindSafe(fut).catch_unwind().await {
+        Ok(res) => res.map_err(|e| (error_type, e.into())),
+        Err(panic) => Err((PANIC_ERROR_TYPE, crate::panics::message(&*panic).into())),
+    }
+}
+
+/// A kind of logs, or telemetry, an extension can subscribe to.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogType {
+    /// Records the Lambda platform emits, like the start and report of invocations.
+    Platform,
+    /// Lines the function writes to stdout and stderr.
+    Function,
+    /// Lines extensions write to stdout and stderr.
+    Extension,
+}
+
+/// A log record, along with the time Lambda received it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEvent {
+    /// When Lambda received the record, like `2020-08-20T12:31:32.123Z`.
+    pub time: String,
+    /// The record.
+    pub record: LogRecord,
+}
+
+/// A log record, told apart by its `type`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogRecord {
+    /// A line written by the function.
+    Function(String),
+    /// A line written by an extension.
+    Extension(String),
+    /// `platform.start`: an invocation started.
+    PlatformStart(PlatformStart),
+    /// `platform.end`: an invocation ended.
+    PlatformEnd(PlatformEnd),
+    /// `platform.report`: the metrics of an invocation.
+    PlatformReport(PlatformReport),
+    /// `platform.extension`: an extension registered.
+    PlatformExtension(PlatformExtension),
+    /// `platform.logsSubscription`: an extension subscribed to logs.
+    PlatformLogsSubscription(PlatformLogsSubscription),
+    /// `platform.logsDropped`: records were dropped because an extension didn't keep up.
+    PlatformLogsDropped(PlatformLogsDropped),
+    /// `platform.fault`: the function or an extension failed.
+    PlatformFault(String),
+    /// A record of a type this crate doesn't know yet.
+    Other {
+        /// The type of the record.
+        kind: String,
+        /// The record.
+        record: Value,
+    },
+}
+
+/// The record of an invocation's start.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformStart {
+    /// The request ID of the invocation.
+    pub request_id: String,
+    /// The version of the function invoked.
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+/// The record of an invocation's end.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformEnd {
+    /// The request ID of the invocation.
+    pub request_id: String,
+}
+
+/// The report of an invocation.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformReport {
+    /// The request ID of the invocation.
+    pub request_id: String,
+    /// The metrics of the invocation.
+    pub metrics: ReportMetrics,
+}
+
+/// The metrics of an invocation.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportMetrics {
+    /// How long the invocation took, in milliseconds.
+    pub duration_ms: f64,
+    /// How long the invocation was billed for, in milliseconds.
+    pub billed_duration_ms: u64,
+    /// The memory available to the function, in MB.
+    #[serde(rename = "memorySizeMB")]
+    pub memory_size_mb: u64,
+    /// The most memory the function used, in MB.
+    #[serde(rename = "maxMemoryUsedMB")]
+    pub max_memory_used_mb: u64,
+    /// How long initializing the execution environment took, in milliseconds, for the first
+    /// invocation of an environment.
+    #[serde(default)]
+    pub init_duration_ms: Option<f64>,
+    /// How long restoring a `SnapStart` snapshot took, in milliseconds, for the first invocation
+    /// of a restored environment.
+    #[serde(default)]
+    pub restore_duration_ms: Option<f64>,
+    /// How long restoring a `SnapStart` snapshot was billed for, in milliseconds.
+    #[serde(default)]
+    pub billed_restore_duration_ms: Option<u64>,
+}
+
+/// The record of an extension's registration.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlatformExtension {
+    /// The name of the extension.
+    pub name: String,
+    /// The state of the extension, like `Ready`.
+    pub state: String,
+    /// The events the extension registered for, like `INVOKE`.
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+/// The record of an extension's subscription to logs.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlatformLogsSubscription {
+    /// The name of the extension.
+    pub name: String,
+    /// The state of the subscription, like `Subscribed`.
+    pub state: String,
+    /// The kinds of logs subscribed to, like `function`.
+    #[serde(default)]
+    pub types: Vec<String>,
+}
+
+/// The record of dropped log records.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformLogsDropped {
+    /// Why the records were dropped.
+    pub reason: String,
+    /// How many records were dropped.
+    pub dropped_records: u64,
+    /// How many bytes were dropped.
+    pub dropped_bytes: u64,
+}
+
+impl<'de> Deserialize<'de> for LogEvent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct RawLogEvent {
+            time: String,
+            #[serde(rename = "type")]
+            kind: String,
+            record: Value,
+        }
+
+        fn typed<T, E>(record: Value) -> Result<T, E>
+        where
+            T: DeserializeOwned,
+            E: de::Error,
+        {
+            serde_json::from_value(record).map_err(E::custom)
+        }
+
+        let RawLogEvent { time, kind, record } = RawLogEvent::deserialize(deserializer)?;
+        let record = match kind.as_str() {
+            "function" => LogRecord::Function(typed(record)?),
+            "extension" => LogRecord::Extension(typed(record)?),
+            "platform.start" => LogRecord::PlatformStart(typed(record)?),
+            "platform.end" => LogRecord::PlatformEnd(typed(record)?),
+            "platform.report" => LogRecord::PlatformReport(typed(record)?),
+            "platform.extension" => LogRecord::PlatformExtension(typed(record)?),
+            "platform.logsSubscription" => LogRecord::PlatformLogsSubscription(typed(record)?),
+            "platform.logsDropped" => LogRecord::PlatformLogsDropped(typed(record)?),
+            "platform.fault" => LogRecord::PlatformFault(typed(record)?),
+            _ => LogRecord::Other { kind, record },
+        };
+        Ok(Self { time, record })
+    }
+}
+
+/// A builder of subscriptions to the [Logs API](https://docs.aws.amazon.com/lambda/latest/dg/runtimes-logs-api.html).
+///
+/// Subscribing starts an HTTP listener Lambda posts batches of log records to, and returns a
+/// channel yielding them. The listener only answers Lambda once a batch is queued on the channel,
+/// so an extension falling behind holds Lambda back until its buffer is full, at which point
+/// Lambda drops records and reports it with a `platform.logsDropped` record.
+///
+/// ```no_run
+/// use lambda::extensions::{ExtensionClient, ExtensionEvent, LogRecord, LogType, LogsSubscription};
+///
+/// type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     let client = ExtensionClient::register("logs-forwarder", &[ExtensionEvent::Shutdown]).await?;
+///     let mut batches = LogsSubscription::new(&[LogType::Function, LogType::Platform])
+///         .with_max_items(1_000)
+///         .subscribe(&client)
+///         .await?;
+///     while let Some(batch) = batches.recv().await {
+///         for event in batch {
+///             if let LogRecord::Function(line) = event.record {
+///                 println!("{}", line);
+///             }
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LogsSubscription {
+    types: Vec<LogType>,
+    delivery: Delivery,
+}
+
+impl LogsSubscription {
+    /// Creates a subscription to the logs of `types`, delivered to port 8080, with Lambda's
+    /// default buffering of 10,000 records, 256 KiB or a second, whichever comes first.
+    #[must_use]
+    pub fn new(types: &[LogType]) -> Self {
+        Self {
+            types: types.to_vec(),
+            delivery: Delivery::default(),
+        }
+    }
+
+    /// Listens on `port` instead, or on a port the system picks with 0.
+    #[must_use]
+    pub const fn with_port(mut self, port: u16) -> Self {
+        self.delivery.port = port;
+        self
+    }
+
+    /// Has Lambda post to `host` instead of the execution environment's `sandbox.localdomain`.
+    #[must_use]
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.delivery.host = host.into();
+        self
+    }
+
+    /// Has Lambda buffer up to `max_items` records, between 1,000 and 10,000, before posting them.
+    #[must_use]
+    pub const fn with_max_items(mut self, max_items: u32) -> Self {
+        self.delivery.max_items = max_items;
+        self
+    }
+
+    /// Has Lambda buffer up to `max_bytes` bytes, between 262,144 and 1,048,576, before posting
+    /// them.
+    #[must_use]
+    pub const fn with_max_bytes(mut self, max_bytes: u32) -> Self {
+        self.delivery.max_bytes = max_bytes;
+        self
+    }
+
+    /// Has Lambda buffer records for up to `timeout`, between 25 milliseconds and 30 seconds,
+    /// before posting them.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.delivery.timeout_ms = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+        self
+    }
+
+    /// Queues up to `queued_batches` batches on the channel before holding Lambda back.
+    #[must_use]
+    pub const fn with_queued_batches(mut self, queued_batches: usize) -> Self {
+        self.delivery.queued_batches = queued_batches;
+        self
+    }
+
+    /// Starts the listener and subscribes the extension of `client` to logs, returning the
+    /// channel the received batches are queued on.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the listener can't bind its port, or if the Logs API can't be reached or refuses
+    /// the subscription.
+    pub async fn subscribe(self, client: &ExtensionClient) -> Result<mpsc::Receiver<Vec<LogEvent>>, Error> {
+        self.delivery
+            .subscribe(client, "/2020-08-15/logs", LOGS_SCHEMA_VERSION, &self.types)
+            .await
+    }
+}
+
+/// Where and how Lambda delivers the records of a Logs or Telemetry API subscription
+#[derive(Debug, Clone)]
+struct Delivery {
+    host: String,
+    port: u16,
+    max_items: u32,
+    max_bytes: u32,
+    timeout_ms: u32,
+    queued_batches: usize,
+}
+
+impl Default for Delivery {
+    fn default() -> Self {
+        Self {
+            host: DESTINATION_HOST.to_owned(),
+            port: 8080,
+            max_items: 10_000,
+            max_bytes: 262_144,
+            timeout_ms: 1_000,
+            queued_batches: 16,
+        }
+    }
+}
+
+impl Delivery {
+    /// Starts a listener queuing the batches Lambda posts, then subscribes the extension of
+    /// `client` to `types` through the API at `uri`
+    async fn subscribe<T>(
+        self,
+        client: &ExtensionClient,
+        uri: &'static str,
+        schema_version: &str,
+        types: &[LogType],
+    ) -> Result<mpsc::Receiver<Vec<T>>, Error>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(self.queued_batches);
+        let make_service = make_service_fn(move |_| {
+            let tx = tx.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| receive_batch(tx.clone(), req))) }
+        });
+        // the listener has to be up before subscribing, as Lambda may post right away
+        let server = Server::try_bind(&([0, 0, 0, 0], self.port).into())?.serve(make_service);
+        let port = server.local_addr().port();
+        tokio::spawn(async move {
+            if let Err(e) = server.await {
+                error!(message = "Subscription listener failed", e = %e);
+            }
+        });
+
+        let req = SubscriptionRequest {
+            uri,
+            extension_id: &client.extension_id,
+            body: json!({
+                "schemaVersion": schema_version,
+                "destination": {
+                    "protocol": "HTTP",
+                    "URI": format!("http://{}:{}{}", self.host, port, DESTINATION_PATH),
+                },
+                "types": types,
+                "buffering": {
+                    "maxItems": self.max_items,
+                    "maxBytes": self.max_bytes,
+                    "timeoutMs": self.timeout_ms,
+                },
+            }),
+        }
+        .into_req()?;
+        accepted(client.client.call(req).await?, "subscription").await?;
+        Ok(rx)
+    }
+}
+
+/// Queues a batch of records Lambda posted
+async fn receive_batch<T>(mut tx: mpsc::Sender<Vec<T>>, req: Request<Body>) -> Result<Response<Body>, Error>
+where
+    T: DeserializeOwned,
+{
+    let body = hyper::body::to_bytes(req.into_body()).await?;
+    let rsp = match serde_json::from_slice(&body) {
+        Ok(batch) => {
+            // waiting for room on the channel holds Lambda back when records aren't consumed
+            if tx.send(batch).await.is_err() {
+                error!(message = "Dropped a batch of records, the receiver is gone");
+            }
+            Response::new(Body::empty())
+        }
+        Err(e) => {
+            error!(message = "Received malformed records", e = %e);
+            Response::builder().status(400).body(Body::empty())?
+        }
+    };
+    Ok(rsp)
+}
+
+/// A closure run when the execution environment shuts down, with the time left before it is
+/// reclaimed
+pub(crate) type ShutdownHook = Box<dyn FnOnce(Duration) + Send>;
+
+/// A closure handling the batches of a logs subscription of the runtime
+pub(crate) type LogsSink = Box<dyn FnMut(Vec<LogEvent>) + Send>;
+
+/// A closure handling the batches of a telemetry subscription of the runtime
+pub(crate) type TelemetrySink = Box<dyn FnMut(Vec<telemetry::TelemetryEvent>) + Send>;
+
+/// The name a runtime registers with as an internal extension
+const RUNTIME_EXTENSION_NAME: &str = "lambda-runtime";
+
+/// How long Lambda leaves a process with internal extensions between the `SIGTERM` it sends on
+/// shutdown and the `SIGKILL` which ends it
+const SHUTDOWN_GRACE: Duration = Duration::from_millis(500);
+
+/// What the internal extension of a runtime does: subscribe to logs or telemetry and run
+/// closures on shutdown
+#[derive(Default)]
+pub(crate) struct Internal {
+    pub(crate) shutdown_hooks: Vec<ShutdownHook>,
+    pub(crate) logs: Option<(LogsSubscription, LogsSink)>,
+    pub(crate) telemetry: Option<(telemetry::TelemetrySubscription, TelemetrySink)>,
+    /// Whether the failures of the extension, to subscribe among them, stop the runtime, rather
+    /// than leaving it running without the extension or subscription
+    pub(crate) abort_on_failure: bool,
+}
+
+impl Internal {
+    /// Whether there is nothing for the extension to do, so that it needn't register
+    pub(crate) fn is_empty(&self) -> bool {
+        self.shutdown_hooks.is_empty() && self.logs.is_none() && self.telemetry.is_none()
+    }
+
+    /// Subscribes the extension of `client`, spawning the consumers of the batches on the
+    /// current executor
+    async fn subscribe(&mut self, client: &ExtensionClient) -> Result<(), Error> {
+        if let Some((subscription, mut sink)) = self.logs.take() {
+            let mut batches = subscription.subscribe(client).await?;
+            tokio::spawn(async move {
+                while let Some(batch) = batches.recv().await {
+                    sink(batch);
+                }
+            });
+        }
+        if let Some((subscription, mut sink)) = self.telemetry.take() {
+            let mut batches = subscription.subscribe(client).await?;
+            tokio::spawn(async move {
+                while let Some(batch) = batches.recv().await {
+                    sink(batch);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Registers the runtime as an internal extension and sets `internal` up, returning a future
+/// which resolves once its shutdown hooks ran on shutdown, or with the error the extension
+/// stopped with.
+///
+/// Internal extensions can't register for the `SHUTDOWN` event, so the extension registers for
+/// no events and its hooks run when Lambda sends the process `SIGTERM` instead, which it does to
+/// processes with registered extensions. The extension listens for the signal and waits on the
+/// Extensions API on a thread of its own, with its own executor, so that subscriptions and hooks
+/// are served however busy handlers keep the runtime's executor.
+/// Registration and subscriptions are complete when this function returns, so that they
+/// happen during initialization. Dropping the returned future stops the extension.
+pub(crate) async fn start(
+    endpoint: &str,
+    mut internal: Internal,
+) -> Result<impl Future<Output = Result<(), Error>>, Error> {
+    let (started_tx, started_rx) = oneshot::channel::<Result<(), Error>>();
+    let (done_tx, done_rx) = oneshot::channel();
+    let (stop_tx, stop_rx) = oneshot::channel::<()>();
+    let endpoint = endpoint.to_owned();
+    thread::Builder::new().name("lambda-extension".into()).spawn(move || {
+        let mut rt = match runtime::Builder::new().basic_scheduler().enable_all().build() {
+            Ok(rt) => rt,
+            Err(e) => {
+                let _ = started_tx.send(Err(e.into()));
+                return;
+            }
+        };
+        rt.block_on(async move {
+            // listening before registering, as Lambda may signal the shutdown from then on
+            let mut terminated = match signal(SignalKind::terminate()) {
+                Ok(terminated) => terminated,
+                Err(e) => {
+                    let _ = started_tx.send(Err(e.into()));
+                    return;
+                }
+            };
+            let registration = ExtensionClient::register_at(&endpoint, RUNTIME_EXTENSION_NAME, &[]).await;
+            let client = match registration {
+                Ok(client) => client,
+                Err(e) => {
+                    let _ = started_tx.send(Err(e));
+                    return;
+                }
+            };
+            if let Err(e) = internal.subscribe(&client).await {
+                if internal.abort_on_failure {
+                    let _ = started_tx.send(Err(client.fail("init", INIT_ERROR_TYPE, e).await));
+                    return;
+                }
+                // the extension still has to ask for its events for initialization to complete
+                error!(message = "Failed to subscribe, continuing without the subscription", e = %e);
+            }
+            let _ = started_tx.send(Ok(()));
+
+            let stopped = async move {
+                let _ = stop_rx.await;
+            };
+            tokio::pin!(stopped);
+            loop {
+                // no events are registered for, but initialization only completes once the
+                // extension asked for its first one
+                let event = tokio::select! {
+                    event = client.next_event() => event,
+                    _ = terminated.recv() => {
+                        let deadline = Instant::now() + SHUTDOWN_GRACE;
+                        for hook in internal.shutdown_hooks {
+                            hook(deadline.saturating_duration_since(Instant::now()));
+                        }
+                        let _ = done_tx.send(Ok(()));
+                        return;
+                    }
+                    () = &mut stopped => return,
+                };
+                if let Err(e) = event {
+                    let _ = done_tx.send(Err(e));
+                    return;
+                }
+            }
+        });
+    })?;
+    started_rx.await??;
+
+    Ok(async move {
+        // the extension stops once this future is dropped
+        let _stop = stop_tx;
+        done_rx
+            .await
+            .unwrap_or_else(|_| Err("The internal extension stopped unexpectedly".into()))
+    })
+}
+
+/// Logs the error `extension` stops with and never resolves then, so that the runtime keeps
+/// going without its extension
+pub(crate) async fn degraded(extension: impl Future<Output = Result<(), Error>>) {
+    if let Err(e) = extension.await {
+        error!(message = "The internal extension failed, continuing without it", e = %e);
+        future::pending::<()>().await;
+    }
+}
+
+/// Returns `rsp` if the Extensions API accepted the request, and an error describing the refusal
+/// otherwise
+async fn accepted(rsp: Response<Body>, request: &str) -> Result<Response<Body>, Error> {
+    if rsp.status().is_success() {
+        return Ok(rsp);
+    }
+    let status = rsp.status();
+    let body = hyper::body::to_bytes(rsp.into_body()).await?;
+    Err(format!(
+        "The Extensions API refused the {} request with {}: {}",
+        request,
+        status,
+        String::from_utf8_lossy(&body)
+    )
+    .into())
+}
+
+// /extension/register
+struct RegisterRequest<'a> {
+    name: &'a str,
+    events: &'a [ExtensionEvent],
+}
+
+impl IntoRequest for RegisterRequest<'_> {
+    fn into_req(self) -> Result<Request<Body>, Error> {
+        let body = serde_json::to_vec(&json!({ "events": self.events }))?;
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(Uri::from_static("/2020-01-01/extension/register"))
+            .header(EXTENSION_NAME_HEADER, self.name)
+            .body(Body::from(body))?;
+        Ok(req)
+    }
+}
+
+#[test]
+fn test_register_request() {
+    let req = RegisterRequest {
+        name: "prefetcher",
+        events: &[ExtensionEvent::Invoke, ExtensionEvent::Shutdown],
+    };
+    let req = req.into_req().unwrap();
+    assert_eq!(req.method(), Method::POST);
+    assert_eq!(req.uri(), &Uri::from_static("/2020-01-01/extension/register"));
+    assert_eq!(req.headers()[EXTENSION_NAME_HEADER], "prefetcher");
+}
+
+// /extension/event/next
+struct NextExtensionEventRequest<'a> {
+    extension_id: &'a str,
+}
+
+impl IntoRequest for NextExtensionEventRequest<'_> {
+    fn into_req(self) -> Result<Request<Body>, Error> {
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(Uri::from_static("/2020-01-01/extension/event/next"))
+            .header(EXTENSION_ID_HEADER, self.extension_id)
+            .body(Body::empty())?;
+        Ok(req)
+    }
+}
+
+#[test]
+fn test_next_extension_event_request() {
+    let req = NextExtensionEventRequest { extension_id: "id" };
+    let req = req.into_req().unwrap();
+    assert_eq!(req.method(), Method::GET);
+    assert_eq!(req.uri(), &Uri::from_static("/2020-01-01/extension/event/next"));
+    assert_eq!(req.headers()[EXTENSION_ID_HEADER], "id");
+}
+
+// /extension/init/error and /extension/exit/error
+struct ExtensionErrorRequest<'a> {
+    extension_id: &'a str,
+    phase: &'a str,
+    diagnostic: Diagnostic,
+}
+
+impl IntoRequest for ExtensionErrorRequest<'_> {
+    fn into_req(self) -> Result<Request<Body>, Error> {
+        let uri = format!("/2020-01-01/extension/{}/error", self.phase);
+        let uri = Uri::from_str(&uri)?;
+        let body = serde_json::to_vec(&self.diagnostic)?;
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(EXTENSION_ID_HEADER, self.extension_id)
+            .header(EXTENSION_ERROR_TYPE_HEADER, self.diagnostic.error_type.as_str())
+            .body(Body::from(body))?;
+        Ok(req)
+    }
+}
+
+#[test]
+fn test_extension_error_request() {
+    let req = ExtensionErrorRequest {
+        extension_id: "id",
+        phase: "exit",
+        diagnostic: Diagnostic {
+            error_type: "Extension.UnknownReason".to_string(),
+            error_message: "Lost the connection to the collector".to_string(),
+        },
+    };
+    let req = req.into_req().unwrap();
+    assert_eq!(req.method(), Method::POST);
+    assert_eq!(req.uri(), &Uri::from_static("/2020-01-01/extension/exit/error"));
+    assert_eq!(req.headers()[EXTENSION_ERROR_TYPE_HEADER], "Extension.UnknownReason");
+}
+
+// /logs and /telemetry
+struct SubscriptionRequest<'a> {
+    uri: &'static str,
+    extension_id: &'a str,
+    body: Value,
+}
+
+impl IntoRequest for SubscriptionRequest<'_> {
+    fn into_req(self) -> Result<Request<Body>, Error> {
+        let body = serde_json::to_vec(&self.body)?;
+        let req = Request::builder()
+            .method(Method::PUT)
+            .uri(Uri::from_static(self.uri))
+            .header(EXTENSION_ID_HEADER, self.extension_id)
+            .body(Body::from(body))?;
+        Ok(req)
+    }
+}
+
+#[test]
+fn test_subscription_request() {
+    let req = SubscriptionRequest {
+        uri: "/2020-08-15/logs",
+        extension_id: "id",
+        body: json!({ "schemaVersion": LOGS_SCHEMA_VERSION }),
+    };
+    let req = req.into_req().unwrap();
+    assert_eq!(req.method(), Method::PUT);
+    assert_eq!(req.uri(), &Uri::from_static("/2020-08-15/logs"));
+    assert_eq!(req.headers()[EXTENSION_ID_HEADER], "id");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        telemetry::{PlatformEvent, Status, TelemetryRecord, TelemetrySubscription},
+        *,
+    };
+    use crate::{
+        handler_fn,
+        stub::{Received, Stub},
+        Context, Runtime,
+    };
+    use serde_json::Value;
+    use std::{
+        process::Command,
+        sync::{Arc, Mutex},
+    };
+
+    fn invoke(request_id: &str) -> Value {
+        json!({
+            "eventType": "INVOKE",
+            "deadlineMs": 1_542_409_706_888_u64,
+            "requestId": request_id,
+            "invokedFunctionArn": "arn:aws:lambda:us-east-2:123456789012:function:custom-runtime",
+            "tracing": {
+                "type": "X-Amzn-Trace-Id",
+                "value": "Root=1-5bef4de7-ad49b0e87f6ef6c87fc2e700;Parent=9a9197af755a6419;Sampled=1"
+            }
+        })
+    }
+
+    fn shutdown() -> Value {
+        json!({ "eventType": "SHUTDOWN", "shutdownReason": "spindown", "deadlineMs": 1_542_409_708_888_u64 })
+    }
+
+    #[test]
+    fn deserializes_events() {
+        let event: NextEvent = serde_json::from_value(invoke("8476a536-e9f4-11e8-9739-2dfe598c3fcd")).unwrap();
+        assert_eq!(
+            event,
+            NextEvent::Invoke {
+                request_id: "8476a536-e9f4-11e8-9739-2dfe598c3fcd".into(),
+                deadline_ms: 1_542_409_706_888,
+                invoked_function_arn: "arn:aws:lambda:us-east-2:123456789012:function:custom-runtime".into(),
+                tracing: Tracing {
+                    kind: "X-Amzn-Trace-Id".into(),
+                    value: "Root=1-5bef4de7-ad49b0e87f6ef6c87fc2e700;Parent=9a9197af755a6419;Sampled=1".into(),
+                },
+            }
+        );
+        let event: NextEvent = serde_json::from_value(shutdown()).unwrap();
+        assert_eq!(
+            event,
+            NextEvent::Shutdown {
+                reason: ShutdownReason::Spindown,
+                deadline_ms: 1_542_409_708_888,
+            }
+        );
+        let event: NextEvent = serde_json::from_value(json!({
+            "eventType": "SHUTDOWN",
+            "shutdownReason": "reclaimed",
+            "deadlineMs": 1_542_409_708_888_u64
+        }))
+        .unwrap();
+        assert!(
+            matches!(&event, NextEvent::Shutdown { reason: ShutdownReason::Other(reason), .. } if reason == "reclaimed"),
+            "{:?}",
+            event
+        );
+    }
+
+    #[tokio::test]
+    async fn registers_and_handles_events_until_shutdown() -> Result<(), Error> {
+        let stub = Stub::serving(vec![invoke("request-1"), invoke("request-2"), shutdown()]);
+        let addr = stub.start();
+
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let events = handled.clone();
+        Extension::new("prefetcher")
+            .with_endpoint(addr.to_string())
+            .run(move |event| {
+                events.lock().unwrap().push(event);
+                async { Ok::<(), Error>(()) }
+            })
+            .await?;
+
+        let handled = std::mem::take(&mut *handled.lock().unwrap());
+        let request_ids: Vec<_> = handled
+            .iter()
+            .filter_map(|event| match event {
+                NextEvent::Invoke { request_id, .. } => Some(request_id.as_str()),
+                NextEvent::Shutdown { .. } => None,
+            })
+            .collect();
+        assert_eq!(request_ids, vec!["request-1", "request-2"]);
+        assert!(matches!(
+            handled.last(),
+            Some(NextEvent::Shutdown {
+                reason: ShutdownReason::Spindown,
+                ..
+            })
+        ));
+
+        let received = stub.received();
+        assert_eq!(received.len(), 4);
+        assert_eq!(received[0].method, Method::POST);
+        assert_eq!(received[0].path, "/2020-01-01/extension/register");
+        assert_eq!(received[0].body, json!({ "events": ["INVOKE", "SHUTDOWN"] }));
+        for next in &received[1..] {
+            assert_eq!(next.method, Method::GET);
+            assert_eq!(next.path, "/2020-01-01/extension/event/next");
+            assert_eq!(next.extension_id.as_deref(), Some("ext-1"));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_handler_errors_before_exiting() -> Result<(), Error> {
+        let stub = Stub::serving(vec![invoke("request-1"), invoke("request-2"), shutdown()]);
+        let addr = stub.start();
+
+        let e = Extension::new("forwarder")
+            .with_endpoint(addr.to_string())
+            .with_events(&[ExtensionEvent::Invoke])
+            .run(|_| async { Err("collector unreachable") })
+            .await
+            .expect_err("handler errors should end the extension");
+        assert_eq!(e.to_string(), "collector unreachable");
+
+        let received = stub.received();
+        assert_eq!(received[0].body, json!({ "events": ["INVOKE"] }));
+        let report = received.last().expect("no requests received");
+        assert_eq!(report.path, "/2020-01-01/extension/exit/error");
+        assert_eq!(report.extension_id.as_deref(), Some("ext-1"));
+        assert_eq!(report.error_type.as_deref(), Some(HANDLER_ERROR_TYPE));
+        assert_eq!(
+            report.body,
+            json!({ "errorType": HANDLER_ERROR_TYPE, "errorMessage": "collector unreachable" })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_failures_of_each_phase_where_they_happened() -> Result<(), Error> {
+        let stub = Stub::serving(vec![invoke("request-1"), shutdown()]);
+        let addr = stub.start();
+        let e = Extension::new("forwarder")
+            .with_endpoint(addr.to_string())
+            .run_with_init(
+                || async { Err("SECRET_ARN is not set") },
+                |_| future::ok::<_, Error>(()),
+            )
+            .await
+            .expect_err("init errors should end the extension");
+        assert_eq!(e.to_string(), "SECRET_ARN is not set");
+        let received = stub.received();
+        assert!(received
+            .iter()
+            .all(|req| req.path != "/2020-01-01/extension/event/next"));
+        let report = received.last().expect("no requests received");
+        assert_eq!(report.path, "/2020-01-01/extension/init/error");
+        assert_eq!(report.error_type.as_deref(), Some(INIT_ERROR_TYPE));
+        assert_eq!(
+            report.body,
+            json!({ "errorType": INIT_ERROR_TYPE, "errorMessage": "SECRET_ARN is not set" })
+        );
+
+        let stub = Stub::serving(vec![invoke("request-1"), shutdown()]);
+        let addr = stub.start();
+        Extension::new("forwarder")
+            .with_endpoint(addr.to_string())
+            .run_with_init(
+                || future::lazy(|_| -> Result<(), Error> { panic!("config file is corrupt") }),
+                |_| async { Ok::<_, Error>(()) },
+            )
+            .await
+            .expect_err("init panics should end the extension");
+        let report = stub.received().pop().expect("no requests received");
+        assert_eq!(report.path, "/2020-01-01/extension/init/error");
+        assert_eq!(report.error_type.as_deref(), Some(PANIC_ERROR_TYPE));
+        assert_eq!(report.body["errorMessage"], "config file is corrupt");
+
+        let stub = Stub::serving(vec![invoke("request-1"), shutdown()]);
+        let addr = stub.start();
+        Extension::new("forwarder")
+            .with_endpoint(addr.to_string())
+            .run_with_init(
+                || future::ok::<_, Error>(()),
+                |_| {
+                    future::lazy(|_| -> Result<(), Error> {
+                        panic!("index out of bounds: the len is {} but the index is {}", 0, 3)
+                    })
+                },
+            )
+            .await
+            .expect_err("handler panics should end the extension");
+        let report = stub.received().pop().expect("no requests received");
+        assert_eq!(report.path, "/2020-01-01/extension/exit/error");
+        assert_eq!(report.error_type.as_deref(), Some(PANIC_ERROR_TYPE));
+        assert_eq!(
+            report.body["errorMessage"],
+            "index out of bounds: the len is 0 but the index is 3"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn extensions_exit_with_failure_statuses_on_panics() {
+        // the harness reports the results tests return as `main` does, failing the process
+        let output = Command::new(env::current_exe().unwrap())
+            .args([
+                "extensions::tests::panicking_extension",
+                "--exact",
+                "--ignored",
+                "--nocapture",
+            ])
+            .output()
+            .expect("failed to run the panicking extension");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success(), "{}", stderr);
+        assert!(
+            stderr.contains("Error: \"index out of bounds: the len is 0 but the index is 3\""),
+            "{}",
+            stderr
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "run by extensions_exit_with_failure_statuses_on_panics, in a process of its own"]
+    async fn panicking_extension() -> Result<(), Error> {
+        let stub = Stub::serving(vec![invoke("request-1"), shutdown()]);
+        let addr = stub.start();
+        Extension::new("forwarder")
+            .with_endpoint(addr.to_string())
+            .run(|_| {
+                future::lazy(|_| -> Result<(), Error> {
+                    panic!("index out of bounds: the len is {} but the index is {}", 0, 3)
+                })
+            })
+            .await
+    }
+
+    #[test]
+    fn runtimes_run_cleanup_closures_on_shutdown() {
+        // Lambda signals shutdowns to the whole process, which the other tests share
+        let output = Command::new(env::current_exe().unwrap())
+            .args([
+                "extensions::tests::terminated_runtime",
+                "--exact",
+                "--ignored",
+                "--nocapture",
+            ])
+            .output()
+            .expect("failed to run the terminated runtime");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "{}{}",
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        // the harness names the test on the line the record starts on
+        let cleanups: Vec<Value> = stdout
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(&line[line.find('{')?..]).ok())
+            .filter(|record| record.get("remaining").is_some())
+            .collect();
+        assert_eq!(
+            cleanups.iter().map(|cleanup| &cleanup["message"]).collect::<Vec<_>>(),
+            ["rolled back", "flushed"],
+            "{stdout}"
+        );
+        for cleanup in cleanups {
+            let remaining = cleanup["remaining"].as_f64().unwrap();
+            assert!(remaining > 0.0 && remaining <= 0.5, "{}", remaining);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "run by runtimes_run_cleanup_closures_on_shutdown, in a process of its own"]
+    async fn terminated_runtime() -> Result<(), Error> {
+        let stub = Stub::default().invoking(vec![json!({ "message": "hello" })]);
+        let addr = stub.start();
+        let cleanup = |message: &'static str| {
+            move |remaining: Duration| {
+                println!(
+                    "{}",
+                    json!({ "message": message, "remaining": remaining.as_secs_f64() })
+                );
+            }
+        };
+        let runtime = Runtime::new()
+            .with_endpoint(addr.to_string())
+            .on_shutdown(cleanup("rolled back"))
+            .on_shutdown(cleanup("flushed"))
+            .run(handler_fn(|event: Value, _: Context| async { Ok::<_, Error>(event) }));
+        tokio::pin!(runtime);
+        let mut received = Vec::new();
+        while !received.iter().any(|req: &Received| req.path.ends_with("/response")) {
+            tokio::select! {
+                res = &mut runtime => return Err(format!("the runtime stopped before shutting down: {res:?}").into()),
+                () = tokio::time::delay_for(Duration::from_millis(10)) => {}
+            }
+            received.extend(stub.received());
+        }
+        // the runtime registers, for no events, before it asks for the first one
+        assert_eq!(received[0].path, "/2020-01-01/extension/register");
+        assert_eq!(received[0].body, json!({ "events": [] }));
+
+        // how Lambda shuts down processes with internal extensions
+        let status = Command::new("kill")
+            .args(["-TERM", &std::process::id().to_string()])
+            .status()?;
+        assert!(status.success());
+        tokio::time::timeout(Duration::from_secs(5), runtime).await?
+    }
+
+    #[tokio::test]
+    async fn subscribes_to_logs_and_decodes_batches() -> Result<(), Error> {
+        let stub = Stub::default();
+        let addr = stub.start().to_string();
+        let client = ExtensionClient::register_at(&addr, "logs-forwarder", &[ExtensionEvent::Shutdown]).await?;
+        let mut batches = LogsSubscription::new(&[LogType::Platform, LogType::Function])
+            .with_host("127.0.0.1")
+            .with_port(0)
+            .with_max_items(1_000)
+            .with_max_bytes(524_288)
+            .with_timeout(Duration::from_millis(25))
+            .subscribe(&client)
+            .await?;
+
+        let subscription = stub.received().pop().expect("no requests received");
+        assert_eq!(subscription.method, Method::PUT);
+        assert_eq!(subscription.path, "/2020-08-15/logs");
+        assert_eq!(subscription.extension_id.as_deref(), Some("ext-1"));
+        assert_eq!(subscription.body["schemaVersion"], "2020-08-15");
+        assert_eq!(subscription.body["types"], json!(["platform", "function"]));
+        assert_eq!(
+            subscription.body["buffering"],
+            json!({ "maxItems": 1000, "maxBytes": 524_288, "timeoutMs": 25 })
+        );
+        let destination = subscription.body["destination"]["URI"].as_str().unwrap().to_owned();
+        assert!(destination.starts_with("http://127.0.0.1:"), "{}", destination);
+
+        let http = hyper::Client::new();
+        for batch in &[
+            json!([
+                {
+                    "time": "2020-08-20T12:31:32.123Z",
+                    "type": "platform.start",
+                    "record": { "requestId": "6f7f0961f83442118a7af6fe80b88d56", "version": "$LATEST" }
+                },
+                { "time": "2020-08-20T12:31:32.124Z", "type": "function", "record": "Hello from the handler\n" },
+                {
+                    "time": "2020-08-20T12:31:32.125Z",
+                    "type": "platform.end",
+                    "record": { "requestId": "6f7f0961f83442118a7af6fe80b88d56" }
+                },
+                {
+                    "time": "2020-08-20T12:31:32.126Z",
+                    "type": "platform.report",
+                    "record": {
+                        "requestId": "6f7f0961f83442118a7af6fe80b88d56",
+                        "metrics": {
+                            "durationMs": 1.23,
+                            "billedDurationMs": 2,
+                            "memorySizeMB": 128,
+                            "maxMemoryUsedMB": 60,
+                            "initDurationMs": 212.34
+                        }
+                    }
+                }
+            ]),
+            json!([
+                {
+                    "time": "2020-08-20T12:32:01.000Z",
+                    "type": "platform.logsDropped",
+                    "record": { "reason": "Consumer seems to have fallen behind", "droppedRecords": 3, "droppedBytes": 1024 }
+                },
+                {
+                    "time": "2020-08-20T12:32:01.001Z",
+                    "type": "platform.runtimeDone",
+                    "record": { "requestId": "6f7f0961f83442118a7af6fe80b88d56", "status": "success" }
+                }
+            ]),
+        ] {
+            let req = Request::post(destination.as_str()).body(Body::from(batch.to_string()))?;
+            assert!(http.request(req).await?.status().is_success());
+        }
+
+        let first = batches.recv().await.expect("missing first batch");
+        assert_eq!(first.len(), 4);
+        assert_eq!(first[0].time, "2020-08-20T12:31:32.123Z");
+        assert_eq!(
+            first[0].record,
+            LogRecord::PlatformStart(PlatformStart {
+                request_id: "6f7f0961f83442118a7af6fe80b88d56".into(),
+                version: Some("$LATEST".into()),
+            })
+        );
+        assert_eq!(first[1].record, LogRecord::Function("Hello from the handler\n".into()));
+        match &first[3].record {
+            LogRecord::PlatformReport(report) => {
+                assert_eq!(report.metrics.billed_duration_ms, 2);
+                assert_eq!(report.metrics.max_memory_used_mb, 60);
+                assert_eq!(report.metrics.init_duration_ms, Some(212.34));
+            }
+            record => panic!("expected a report, got {:?}", record),
+        }
+
+        let second = batches.recv().await.expect("missing second batch");
+        assert_eq!(
+            second[0].record,
+            LogRecord::PlatformLogsDropped(PlatformLogsDropped {
+                reason: "Consumer seems to have fallen behind".into(),
+                dropped_records: 3,
+                dropped_bytes: 1024,
+            })
+        );
+        assert!(matches!(
+            &second[1].record,
+            LogRecord::Other { kind, .. } if kind == "platform.runtimeDone"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn subscribes_to_telemetry() -> Result<(), Error> {
+        let stub = Stub::default();
+        let addr = stub.start().to_string();
+        let client = ExtensionClient::register_at(&addr, "telemetry-forwarder", &[ExtensionEvent::Shutdown]).await?;
+        let mut batches = TelemetrySubscription::new(&[LogType::Platform])
+            .with_host("127.0.0.1")
+            .with_port(0)
+            .subscribe(&client)
+            .await?;
+
+        let subscription = stub.received().pop().expect("no requests received");
+        assert_eq!(subscription.method, Method::PUT);
+        assert_eq!(subscription.path, "/2022-07-01/telemetry");
+        assert_eq!(subscription.body["schemaVersion"], "2022-12-13");
+        assert_eq!(subscription.body["types"], json!(["platform"]));
+        assert_eq!(
+            subscription.body["buffering"],
+            json!({ "maxItems": 10_000, "maxBytes": 262_144, "timeoutMs": 1_000 })
+        );
+
+        let destination = subscription.body["destination"]["URI"].as_str().unwrap().to_owned();
+        let batch = json!([{
+            "time": "2022-10-12T00:00:15.180Z",
+            "type": "platform.runtimeDone",
+            "record": { "requestId": "6d68ca91-49c9-448d-89b8-7ca3e6dc66aa", "status": "timeout" }
+        }]);
+        let req = Request::post(destination.as_str()).body(Body::from(batch.to_string()))?;
+        assert!(hyper::Client::new().request(req).await?.status().is_success());
+
+        let batch = batches.recv().await.expect("missing batch");
+        match &batch[0].record {
+            TelemetryRecord::Platform(PlatformEvent::RuntimeDone(done)) => assert_eq!(done.status, Status::Timeout),
+            record => panic!("expected a runtimeDone record, got {:?}", record),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_init_errors() -> Result<(), Error> {
+        let stub = Stub::default();
+        let addr = stub.start().to_string();
+
+        let client = ExtensionClient::register_at(&addr, "prefetcher", &[ExtensionEvent::Shutdown]).await?;
+        assert_eq!(client.extension_id(), "ext-1");
+        client
+            .init_error("Extension.ConfigInvalid", "SECRET_ARN is not set")
+            .await?;
+
+        let report = stub.received().pop().expect("no requests received");
+        assert_eq!(report.method, Method::POST);
+        assert_eq!(report.path, "/2020-01-01/extension/init/error");
+        assert_eq!(report.error_type.as_deref(), Some("Extension.ConfigInvalid"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fails_refused_registrations() {
+        let stub = Stub::default();
+        let addr = stub.start().to_string();
+
+        let e = ExtensionClient::register_at(&addr, "forbidden", &[ExtensionEvent::Shutdown])
+            .await
+            .expect_err("registration should fail");
+        assert!(e.to_string().contains("403"), "{}", e);
+    }
+}
@@ -0,0 +1,682 @@
+//! Subscriptions to the [Telemetry API](https://docs.aws.amazon.com/lambda/latest/dg/telemetry-api.html).
+//!
+//! The Telemetry API supersedes the Logs API. Besides log lines, it reports every phase of an
+//! execution environment, initialization, invocations and `SnapStart` restores, with their status,
+//! metrics and spans.
+//!
+//! ```no_run
+//! use lambda::extensions::{
+//!     telemetry::{PlatformEvent, TelemetryRecord, TelemetrySubscription},
+//!     ExtensionClient, ExtensionEvent, LogType,
+//! };
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     let client = ExtensionClient::register("telemetry-forwarder", &[ExtensionEvent::Shutdown]).await?;
+//!     let mut batches = TelemetrySubscription::new(&[LogType::Platform]).subscribe(&client).await?;
+//!     while let Some(batch) = batches.recv().await {
+//!         for event in batch {
+//!             if let TelemetryRecord::Platform(PlatformEvent::Report(report)) = event.record {
+//!                 println!("{} billed {}ms", report.request_id, report.metrics.billed_duration_ms);
+//!             }
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//! ```
+use super::{Delivery, ExtensionClient, LogType, PlatformExtension, PlatformLogsDropped, ReportMetrics};
+use crate::Error;
+use serde::{
+    de::{self, DeserializeOwned, Deserializer},
+    Deserialize,
+};
+use serde_json::Value;
+use std::{convert::TryFrom, time::Duration};
+use tokio::sync::mpsc;
+
+/// The version of the Telemetry API's schema subscriptions use
+const TELEMETRY_SCHEMA_VERSION: &str = "2022-12-13";
+
+/// A telemetry record, along with the time Lambda emitted it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetryEvent {
+    /// When Lambda emitted the record, like `2022-10-12T00:00:15.064Z`.
+    pub time: String,
+    /// The record.
+    pub record: TelemetryRecord,
+}
+
+/// A telemetry record, told apart by its `type`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TelemetryRecord {
+    /// A line written by the function, a string or, for JSON log formats, an object.
+    Function(Value),
+    /// A line written by an extension, a string or, for JSON log formats, an object.
+    Extension(Value),
+    /// A record of the Lambda platform, of a `platform.*` type.
+    Platform(PlatformEvent),
+    /// A record of a type this crate doesn't know yet.
+    Other {
+        /// The type of the record.
+        kind: String,
+        /// The record.
+        record: Value,
+    },
+}
+
+/// A record of the Lambda platform.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlatformEvent {
+    /// `platform.initStart`: initialization started.
+    InitStart(InitStart),
+    /// `platform.initRuntimeDone`: the runtime finished initializing.
+    InitRuntimeDone(InitRuntimeDone),
+    /// `platform.initReport`: the metrics of initialization.
+    InitReport(InitReport),
+    /// `platform.start`: an invocation started.
+    Start(InvokeStart),
+    /// `platform.runtimeDone`: the runtime finished handling an invocation.
+    RuntimeDone(RuntimeDone),
+    /// `platform.report`: the metrics of an invocation.
+    Report(InvokeReport),
+    /// `platform.restoreStart`: restoring a `SnapStart` snapshot started.
+    RestoreStart(RestoreStart),
+    /// `platform.restoreRuntimeDone`: the runtime finished its restore hooks.
+    RestoreRuntimeDone(RestoreRuntimeDone),
+    /// `platform.restoreReport`: the metrics of a restore.
+    RestoreReport(RestoreReport),
+    /// `platform.extension`: an extension registered.
+    Extension(PlatformExtension),
+    /// `platform.telemetrySubscription`: an extension subscribed to telemetry.
+    TelemetrySubscription(TelemetrySubscriptionRecord),
+    /// `platform.logsDropped`: records were dropped because an extension didn't keep up.
+    LogsDropped(PlatformLogsDropped),
+    /// A platform record of a type this crate doesn't know yet.
+    Other {
+        /// The type of the record, like `platform.initStart`.
+        kind: String,
+        /// The record.
+        record: Value,
+    },
+}
+
+/// The outcome of a phase.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String")]
+pub enum Status {
+    /// The phase succeeded.
+    Success,
+    /// The phase failed with an error.
+    Error,
+    /// The phase failed, like when the runtime crashed.
+    Failure,
+    /// The phase timed out.
+    Timeout,
+    /// A status this crate doesn't know yet.
+    Other(String),
+}
+
+impl From<String> for Status {
+    fn from(status: String) -> Self {
+        match status.as_str() {
+            "success" => Self::Success,
+            "error" => Self::Error,
+            "failure" => Self::Failure,
+            "timeout" => Self::Timeout,
+            _ => Self::Other(status),
+        }
+    }
+}
+
+/// A timed part of a phase, like `responseLatency`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Span {
+    /// The name of the span.
+    pub name: String,
+    /// When the span started, like `2022-10-12T00:00:15.064Z`.
+    pub start: String,
+    /// How long the span took, in milliseconds.
+    pub duration_ms: f64,
+}
+
+/// The X-Ray trace context of an invocation.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceContext {
+    /// The id of the span, for the records which have one.
+    #[serde(default)]
+    pub span_id: Option<String>,
+    /// The kind of trace context, `X-Amzn-Trace-Id`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The value of the trace context.
+    pub value: String,
+}
+
+/// The record of initialization's start.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct InitStart {
+    /// How the environment is initialized, like `on-demand` or `provisioned-concurrency`.
+    pub initialization_type: String,
+    /// The phase initialization happens in, `init` or, after an earlier failure, `invoke`.
+    pub phase: String,
+    /// The version of the runtime.
+    #[serde(default)]
+    pub runtime_version: Option<String>,
+    /// The ARN of the version of the runtime.
+    #[serde(default)]
+    pub runtime_version_arn: Option<String>,
+    /// The name of the function.
+    #[serde(default)]
+    pub function_name: Option<String>,
+    /// The version of the function.
+    #[serde(default)]
+    pub function_version: Option<String>,
+    /// The id of the execution environment.
+    #[serde(default)]
+    pub instance_id: Option<String>,
+    /// The memory available to the execution environment, in MB.
+    #[serde(default)]
+    pub instance_max_memory: Option<u64>,
+}
+
+/// The record of the runtime's finished initialization.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InitRuntimeDone {
+    /// How the environment is initialized, like `on-demand` or `provisioned-concurrency`.
+    pub initialization_type: String,
+    /// The phase initialization happened in, `init` or, after an earlier failure, `invoke`.
+    pub phase: String,
+    /// The outcome of initialization.
+    pub status: Status,
+    /// The type of the error, for failed initializations.
+    #[serde(default)]
+    pub error_type: Option<String>,
+    /// The spans of initialization.
+    #[serde(default)]
+    pub spans: Vec<Span>,
+}
+
+/// The metrics of initialization.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InitReport {
+    /// How the environment was initialized, like `on-demand` or `provisioned-concurrency`.
+    pub initialization_type: String,
+    /// The phase initialization happened in, `init` or, after an earlier failure, `invoke`.
+    pub phase: String,
+    /// The outcome of initialization.
+    #[serde(default)]
+    pub status: Option<Status>,
+    /// The type of the error, for failed initializations.
+    #[serde(default)]
+    pub error_type: Option<String>,
+    /// The metrics of initialization.
+    pub metrics: PhaseMetrics,
+    /// The spans of initialization.
+    #[serde(default)]
+    pub spans: Vec<Span>,
+}
+
+/// The metrics of a phase.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseMetrics {
+    /// How long the phase took, in milliseconds.
+    pub duration_ms: f64,
+}
+
+/// The record of an invocation's start.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct InvokeStart {
+    /// The request ID of the invocation.
+    pub request_id: String,
+    /// The version of the function invoked.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// The trace context of the invocation, when it is traced.
+    #[serde(default)]
+    pub tracing: Option<TraceContext>,
+}
+
+/// The record of the runtime's finished invocation.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeDone {
+    /// The request ID of the invocation.
+    pub request_id: String,
+    /// The outcome of the invocation.
+    pub status: Status,
+    /// The type of the error, for failed invocations.
+    #[serde(default)]
+    pub error_type: Option<String>,
+    /// The metrics of the invocation, when the runtime responded.
+    #[serde(default)]
+    pub metrics: Option<RuntimeDoneMetrics>,
+    /// The spans of the invocation, like `responseLatency` and `responseDuration`.
+    #[serde(default)]
+    pub spans: Vec<Span>,
+    /// The trace context of the invocation, when it is traced.
+    #[serde(default)]
+    pub tracing: Option<TraceContext>,
+}
+
+/// The metrics of the runtime's finished invocation.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeDoneMetrics {
+    /// How long the invocation took, in milliseconds.
+    pub duration_ms: f64,
+    /// How many bytes the function responded with.
+    #[serde(default)]
+    pub produced_bytes: Option<u64>,
+}
+
+/// The report of an invocation.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InvokeReport {
+    /// The request ID of the invocation.
+    pub request_id: String,
+    /// The outcome of the invocation.
+    pub status: Status,
+    /// The type of the error, for failed invocations.
+    #[serde(default)]
+    pub error_type: Option<String>,
+    /// The metrics of the invocation.
+    pub metrics: ReportMetrics,
+    /// The spans of the invocation.
+    #[serde(default)]
+    pub spans: Vec<Span>,
+    /// The trace context of the invocation, when it is traced.
+    #[serde(default)]
+    pub tracing: Option<TraceContext>,
+}
+
+/// The record of a restore's start.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreStart {
+    /// The version of the runtime.
+    #[serde(default)]
+    pub runtime_version: Option<String>,
+    /// The ARN of the version of the runtime.
+    #[serde(default)]
+    pub runtime_version_arn: Option<String>,
+    /// The name of the function.
+    #[serde(default)]
+    pub function_name: Option<String>,
+    /// The version of the function.
+    #[serde(default)]
+    pub function_version: Option<String>,
+    /// The id of the execution environment.
+    #[serde(default)]
+    pub instance_id: Option<String>,
+    /// The memory available to the execution environment, in MB.
+    #[serde(default)]
+    pub instance_max_memory: Option<u64>,
+}
+
+/// The record of the runtime's finished restore hooks.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreRuntimeDone {
+    /// The outcome of the restore.
+    pub status: Status,
+    /// The type of the error, for failed restores.
+    #[serde(default)]
+    pub error_type: Option<String>,
+    /// The spans of the restore.
+    #[serde(default)]
+    pub spans: Vec<Span>,
+}
+
+/// The metrics of a restore.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    /// The outcome of the restore.
+    pub status: Status,
+    /// The type of the error, for failed restores.
+    #[serde(default)]
+    pub error_type: Option<String>,
+    /// The metrics of the restore.
+    #[serde(default)]
+    pub metrics: Option<PhaseMetrics>,
+    /// The spans of the restore.
+    #[serde(default)]
+    pub spans: Vec<Span>,
+}
+
+/// The record of an extension's subscription to telemetry.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TelemetrySubscriptionRecord {
+    /// The name of the extension.
+    pub name: String,
+    /// The state of the subscription, like `Subscribed`.
+    pub state: String,
+    /// The kinds of telemetry subscribed to, like `platform`.
+    #[serde(default)]
+    pub types: Vec<String>,
+}
+
+impl<'de> Deserialize<'de> for TelemetryEvent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct RawTelemetryEvent {
+            time: String,
+            #[serde(rename = "type")]
+            kind: String,
+            record: Value,
+        }
+
+        fn typed<T, E>(record: Value) -> Result<T, E>
+        where
+            T: DeserializeOwned,
+            E: de::Error,
+        {
+            serde_json::from_value(record).map_err(E::custom)
+        }
+
+        let RawTelemetryEvent { time, kind, record } = RawTelemetryEvent::deserialize(deserializer)?;
+        let record = match kind.as_str() {
+            "function" => TelemetryRecord::Function(record),
+            "extension" => TelemetryRecord::Extension(record),
+            "platform.initStart" => TelemetryRecord::Platform(PlatformEvent::InitStart(typed(record)?)),
+            "platform.initRuntimeDone" => TelemetryRecord::Platform(PlatformEvent::InitRuntimeDone(typed(record)?)),
+            "platform.initReport" => TelemetryRecord::Platform(PlatformEvent::InitReport(typed(record)?)),
+            "platform.start" => TelemetryRecord::Platform(PlatformEvent::Start(typed(record)?)),
+            "platform.runtimeDone" => TelemetryRecord::Platform(PlatformEvent::RuntimeDone(typed(record)?)),
+            "platform.report" => TelemetryRecord::Platform(PlatformEvent::Report(typed(record)?)),
+            "platform.restoreStart" => TelemetryRecord::Platform(PlatformEvent::RestoreStart(typed(record)?)),
+            "platform.restoreRuntimeDone" => {
+                TelemetryRecord::Platform(PlatformEvent::RestoreRuntimeDone(typed(record)?))
+            }
+            "platform.restoreReport" => TelemetryRecord::Platform(PlatformEvent::RestoreReport(typed(record)?)),
+            "platform.extension" => TelemetryRecord::Platform(PlatformEvent::Extension(typed(record)?)),
+            "platform.telemetrySubscription" => {
+                TelemetryRecord::Platform(PlatformEvent::TelemetrySubscription(typed(record)?))
+            }
+            "platform.logsDropped" => TelemetryRecord::Platform(PlatformEvent::LogsDropped(typed(record)?)),
+            platform if platform.starts_with("platform.") => {
+                TelemetryRecord::Platform(PlatformEvent::Other { kind, record })
+            }
+            _ => TelemetryRecord::Other { kind, record },
+        };
+        Ok(Self { time, record })
+    }
+}
+
+/// A builder of subscriptions to the Telemetry API, with schema version `2022-12-13`.
+///
+/// Subscribing starts an HTTP listener Lambda posts batches of telemetry records to, and returns
+/// a channel yielding them. Like for [`LogsSubscription`](super::LogsSubscription)s, the listener
+/// only answers Lambda once a batch is queued on the channel.
+#[derive(Debug, Clone)]
+pub struct TelemetrySubscription {
+    types: Vec<LogType>,
+    delivery: Delivery,
+}
+
+impl TelemetrySubscription {
+    /// Creates a subscription to the telemetry of `types`, delivered to port 8080, with Lambda's
+    /// default buffering of 10,000 records, 256 KiB or a second, whichever comes first.
+    #[must_use]
+    pub fn new(types: &[LogType]) -> Self {
+        Self {
+            types: types.to_vec(),
+            delivery: Delivery::default(),
+        }
+    }
+
+    /// Listens on `port` instead, or on a port the system picks with 0.
+    #[must_use]
+    pub const fn with_port(mut self, port: u16) -> Self {
+        self.delivery.port = port;
+        self
+    }
+
+    /// Has Lambda post to `host` instead of the execution environment's `sandbox.localdomain`.
+    #[must_use]
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.delivery.host = host.into();
+        self
+    }
+
+    /// Has Lambda buffer up to `max_items` records, between 1,000 and 10,000, before posting them.
+    #[must_use]
+    pub const fn with_max_items(mut self, max_items: u32) -> Self {
+        self.delivery.max_items = max_items;
+        self
+    }
+
+    /// Has Lambda buffer up to `max_bytes` bytes, between 262,144 and 1,048,576, before posting
+    /// them.
+    #[must_use]
+    pub const fn with_max_bytes(mut self, max_bytes: u32) -> Self {
+        self.delivery.max_bytes = max_bytes;
+        self
+    }
+
+    /// Has Lambda buffer records for up to `timeout`, between 25 milliseconds and 30 seconds,
+    /// before posting them.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.delivery.timeout_ms = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+        self
+    }
+
+    /// Queues up to `queued_batches` batches on the channel before holding Lambda back.
+    #[must_use]
+    pub const fn with_queued_batches(mut self, queued_batches: usize) -> Self {
+        self.delivery.queued_batches = queued_batches;
+        self
+    }
+
+    /// Starts the listener and subscribes the extension of `client` to telemetry, returning the
+    /// channel the received batches are queued on.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the listener can't bind its port, or if the Telemetry API can't be reached or
+    /// refuses the subscription.
+    pub async fn subscribe(self, client: &ExtensionClient) -> Result<mpsc::Receiver<Vec<TelemetryEvent>>, Error> {
+        self.delivery
+            .subscribe(client, "/2022-07-01/telemetry", TELEMETRY_SCHEMA_VERSION, &self.types)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A batch captured from a function's first invocation
+    fn captured_batch() -> Value {
+        json!([
+            {
+                "time": "2022-10-12T00:00:15.064Z",
+                "type": "platform.initStart",
+                "record": {
+                    "initializationType": "on-demand",
+                    "phase": "init",
+                    "runtimeVersion": "provided:al2.v12",
+                    "runtimeVersionArn": "arn:aws:lambda:us-east-1::runtime:6a4bd3b5f35b0bd1b9d1ba4dab4b4e816b3e7f8e3ee8bcf4b9ad0e2d3b06c1a1",
+                    "functionName": "telemetry-demo",
+                    "functionVersion": "$LATEST",
+                    "instanceId": "2023/01/01/[$LATEST]5f4b4c3ad5ad4e999b47e2b3f7d0f5a8",
+                    "instanceMaxMemory": 128
+                }
+            },
+            {
+                "time": "2022-10-12T00:00:15.164Z",
+                "type": "platform.initReport",
+                "record": {
+                    "initializationType": "on-demand",
+                    "phase": "init",
+                    "status": "success",
+                    "metrics": { "durationMs": 100.2 }
+                }
+            },
+            {
+                "time": "2022-10-12T00:00:15.165Z",
+                "type": "platform.start",
+                "record": {
+                    "requestId": "6d68ca91-49c9-448d-89b8-7ca3e6dc66aa",
+                    "version": "$LATEST",
+                    "tracing": {
+                        "spanId": "54565fb41ac79632",
+                        "type": "X-Amzn-Trace-Id",
+                        "value": "Root=1-62e900b2-710d76f009d6e7785905449a;Parent=0efbd19962d95b05;Sampled=1"
+                    }
+                }
+            },
+            { "time": "2022-10-12T00:00:15.170Z", "type": "function", "record": "Handling order 42\n" },
+            {
+                "time": "2022-10-12T00:00:15.180Z",
+                "type": "platform.runtimeDone",
+                "record": {
+                    "requestId": "6d68ca91-49c9-448d-89b8-7ca3e6dc66aa",
+                    "status": "success",
+                    "metrics": { "durationMs": 14.5, "producedBytes": 53 },
+                    "spans": [
+                        { "name": "responseLatency", "start": "2022-10-12T00:00:15.166Z", "durationMs": 12.8 },
+                        { "name": "responseDuration", "start": "2022-10-12T00:00:15.179Z", "durationMs": 0.4 }
+                    ],
+                    "tracing": {
+                        "spanId": "54565fb41ac79632",
+                        "type": "X-Amzn-Trace-Id",
+                        "value": "Root=1-62e900b2-710d76f009d6e7785905449a;Parent=0efbd19962d95b05;Sampled=1"
+                    }
+                }
+            },
+            {
+                "time": "2022-10-12T00:00:15.181Z",
+                "type": "platform.report",
+                "record": {
+                    "requestId": "6d68ca91-49c9-448d-89b8-7ca3e6dc66aa",
+                    "status": "success",
+                    "metrics": {
+                        "durationMs": 15.6,
+                        "billedDurationMs": 16,
+                        "memorySizeMB": 128,
+                        "maxMemoryUsedMB": 38,
+                        "initDurationMs": 100.2
+                    }
+                }
+            },
+            {
+                "time": "2022-10-12T00:00:15.182Z",
+                "type": "platform.fastPathReport",
+                "record": { "requestId": "6d68ca91-49c9-448d-89b8-7ca3e6dc66aa" }
+            }
+        ])
+    }
+
+    #[test]
+    fn decodes_captured_batches() {
+        let batch: Vec<TelemetryEvent> = serde_json::from_value(captured_batch()).expect("failed to decode batch");
+        assert_eq!(batch.len(), 7);
+
+        match &batch[0].record {
+            TelemetryRecord::Platform(PlatformEvent::InitStart(start)) => {
+                assert_eq!(start.initialization_type, "on-demand");
+                assert_eq!(start.phase, "init");
+                assert_eq!(start.instance_max_memory, Some(128));
+            }
+            record => panic!("expected an initStart record, got {:?}", record),
+        }
+        assert_eq!(batch[1].time, "2022-10-12T00:00:15.164Z");
+        assert!(matches!(
+            &batch[1].record,
+            TelemetryRecord::Platform(PlatformEvent::InitReport(InitReport {
+                status: Some(Status::Success),
+                ..
+            }))
+        ));
+        assert_eq!(batch[3].record, TelemetryRecord::Function(json!("Handling order 42\n")));
+
+        match &batch[4].record {
+            TelemetryRecord::Platform(PlatformEvent::RuntimeDone(done)) => {
+                assert_eq!(done.status, Status::Success);
+                assert_eq!(
+                    done.metrics.as_ref().and_then(|metrics| metrics.produced_bytes),
+                    Some(53)
+                );
+                assert_eq!(
+                    done.spans,
+                    vec![
+                        Span {
+                            name: "responseLatency".into(),
+                            start: "2022-10-12T00:00:15.166Z".into(),
+                            duration_ms: 12.8,
+                        },
+                        Span {
+                            name: "responseDuration".into(),
+                            start: "2022-10-12T00:00:15.179Z".into(),
+                            duration_ms: 0.4,
+                        },
+                    ]
+                );
+                assert_eq!(
+                    done.tracing.as_ref().and_then(|tracing| tracing.span_id.as_deref()),
+                    Some("54565fb41ac79632")
+                );
+            }
+            record => panic!("expected a runtimeDone record, got {:?}", record),
+        }
+        match &batch[5].record {
+            TelemetryRecord::Platform(PlatformEvent::Report(report)) => {
+                assert_eq!(report.metrics.billed_duration_ms, 16);
+                assert_eq!(report.metrics.init_duration_ms, Some(100.2));
+            }
+            record => panic!("expected a report record, got {:?}", record),
+        }
+        assert!(matches!(
+            &batch[6].record,
+            TelemetryRecord::Platform(PlatformEvent::Other { kind, .. }) if kind == "platform.fastPathReport"
+        ));
+    }
+
+    #[test]
+    fn decodes_unknown_types_and_statuses() {
+        let batch: Vec<TelemetryEvent> = serde_json::from_value(json!([
+            {
+                "time": "2022-10-12T00:01:00.000Z",
+                "type": "platform.restoreRuntimeDone",
+                "record": { "status": "throttled", "spans": [] }
+            },
+            { "time": "2022-10-12T00:01:00.001Z", "type": "metrics", "record": { "cpu": 0.5 } },
+            { "time": "2022-10-12T00:01:00.002Z", "type": "extension", "record": { "level": "INFO", "message": "ready" } }
+        ]))
+        .expect("failed to decode batch");
+        assert!(matches!(
+            &batch[0].record,
+            TelemetryRecord::Platform(PlatformEvent::RestoreRuntimeDone(RestoreRuntimeDone {
+                status: Status::Other(status),
+                ..
+            })) if status == "throttled"
+        ));
+        assert_eq!(
+            batch[1].record,
+            TelemetryRecord::Other {
+                kind: "metrics".into(),
+                record: json!({ "cpu": 0.5 }),
+            }
+        );
+        assert_eq!(
+            batch[2].record,
+            TelemetryRecord::Extension(json!({ "level": "INFO", "message": "ready" }))
+        );
+    }
+}
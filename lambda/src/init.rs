@@ -0,0 +1,415 @@
+//! Timing of the initialization of the execution environment.
+//!
+//! The runtime notes the first time the crate is touched, or, on Linux, when the process
+//! started, then when it asks the Runtime API for the first event and when that event arrives.
+//! On the first invocation it writes one JSON record of those durations to stdout:
+//!
+//! ```json
+//! {"level":"INFO","message":"initialized","initDurationMs":182.4,"firstEventLatencyMs":3.1,"initializationType":"on-demand","coldStart":"on-demand","requestId":"8476a536-e9f4-11e8-9739-2dfe598c3fcd"}
+//! ```
+//!
+//! and hands the same numbers to the handler through [`Context::init_report`](crate::Context::init_report).
+//!
+//! How the environment was initialized, read once from `AWS_LAMBDA_INITIALIZATION_TYPE`, tells
+//! a true cold start, where the first invocation waited for initialization, from one whose
+//! environment was initialized ahead of it: see [`initialization_type`] and [`ColdStartKind`].
+use serde_json::{json, Value};
+use std::{
+    env, fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+/// The variable Lambda sets to how the execution environment was initialized
+const INITIALIZATION_TYPE: &str = "AWS_LAMBDA_INITIALIZATION_TYPE";
+
+/// The clock ticks per second `/proc` counts times in, fixed by Linux for userspace
+const USER_HZ: f64 = 100.0;
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+static READY: OnceLock<Instant> = OnceLock::new();
+static REPORTED: AtomicBool = AtomicBool::new(false);
+static INITIALIZATION: OnceLock<InitializationType> = OnceLock::new();
+
+/// How the execution environment was initialized, the value of `AWS_LAMBDA_INITIALIZATION_TYPE`.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InitializationType {
+    /// Initialized for the invocation which found no environment to run in, `on-demand`, and
+    /// what the runtime assumes when the variable is unset.
+    OnDemand,
+    /// Initialized for provisioned concurrency, ahead of any invocation,
+    /// `provisioned-concurrency`.
+    ProvisionedConcurrency,
+    /// Restored from a snapshot taken after initialization, `snap-start`.
+    SnapStart,
+    /// Any other value.
+    Other(String),
+}
+
+impl InitializationType {
+    /// Parses the value of `AWS_LAMBDA_INITIALIZATION_TYPE`, `None` when it is unset
+    fn from_var(value: Option<&str>) -> Self {
+        match value {
+            None | Some("on-demand") => Self::OnDemand,
+            Some("provisioned-concurrency") => Self::ProvisionedConcurrency,
+            Some("snap-start") => Self::SnapStart,
+            Some(other) => Self::Other(other.to_owned()),
+        }
+    }
+
+    /// The value of `AWS_LAMBDA_INITIALIZATION_TYPE` this stands for.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::OnDemand => "on-demand",
+            Self::ProvisionedConcurrency => "provisioned-concurrency",
+            Self::SnapStart => "snap-start",
+            Self::Other(other) => other,
+        }
+    }
+
+    /// What the first invocation of an environment initialized this way waited for.
+    #[must_use]
+    pub const fn cold_start(&self) -> ColdStartKind {
+        match self {
+            Self::ProvisionedConcurrency => ColdStartKind::Provisioned,
+            Self::SnapStart => ColdStartKind::Restored,
+            Self::OnDemand | Self::Other(_) => ColdStartKind::OnDemand,
+        }
+    }
+}
+
+/// How the execution environment was initialized, read from `AWS_LAMBDA_INITIALIZATION_TYPE`
+/// the first time it is asked for.
+pub fn initialization_type() -> &'static InitializationType {
+    INITIALIZATION.get_or_init(|| InitializationType::from_var(env::var(INITIALIZATION_TYPE).ok().as_deref()))
+}
+
+/// What the first invocation of an execution environment waited for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColdStartKind {
+    /// The whole initialization: a true cold start.
+    OnDemand,
+    /// Nothing, the environment being initialized ahead of time for provisioned concurrency.
+    Provisioned,
+    /// The restore of the snapshot the environment was initialized from.
+    Restored,
+}
+
+impl ColdStartKind {
+    /// The name of the kind in logs, like `on-demand`.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::OnDemand => "on-demand",
+            Self::Provisioned => "provisioned",
+            Self::Restored => "restored",
+        }
+    }
+}
+
+/// How long the initialization of the execution environment took.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub struct InitReport {
+    /// The time from the start of the process to the runtime asking for the first event.
+    ///
+    /// Where `/proc` can't tell when the process started, it counts from the first time the
+    /// runtime was touched instead, missing the time spent before `main`.
+    pub init_duration: Duration,
+    /// The time from the runtime asking for the first event to that event arriving.
+    pub first_event_latency: Duration,
+    /// How the environment was initialized.
+    pub initialization_type: InitializationType,
+}
+
+impl InitReport {
+    /// Whether the environment was initialized for provisioned concurrency, ahead of its
+    /// first invocation.
+    #[must_use]
+    pub fn provisioned(&self) -> bool {
+        self.initialization_type == InitializationType::ProvisionedConcurrency
+    }
+
+    /// What the first invocation waited for.
+    #[must_use]
+    pub const fn cold_start(&self) -> ColdStartKind {
+        self.initialization_type.cold_start()
+    }
+
+    /// The record logged for the first invocation, `request_id`
+    fn record(&self, request_id: &str) -> Value {
+        json!({
+            "level": "INFO",
+            "message": "initialized",
+            "initDurationMs": millis(self.init_duration),
+            "firstEventLatencyMs": millis(self.first_event_latency),
+            "initializationType": self.initialization_type.as_str(),
+            "coldStart": self.cold_start().as_str(),
+            "requestId": request_id,
+        })
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// The start of initialization, noted the first time the runtime is touched
+pub(crate) fn started() -> Instant {
+    *STARTED.get_or_init(|| {
+        let now = Instant::now();
+        process_age().and_then(|age| now.checked_sub(age)).unwrap_or(now)
+    })
+}
+
+/// Notes that the runtime is about to ask for its first event
+pub(crate) fn ready() {
+    READY.get_or_init(|| {
+        started();
+        Instant::now()
+    });
+}
+
+/// Logs and returns the report of initialization for the first invocation of the process,
+/// `request_id`, and `None` for every other invocation.
+pub(crate) fn first_invocation(request_id: &str) -> Option<InitReport> {
+    let arrived = Instant::now();
+    let ready = *READY.get()?;
+    if REPORTED.swap(true, Ordering::SeqCst) {
+        return None;
+    }
+    let report = InitReport {
+        init_duration: ready.saturating_duration_since(started()),
+        first_event_latency: arrived.saturating_duration_since(ready),
+        initialization_type: initialization_type().clone(),
+    };
+    println!("{}", report.record(request_id));
+    Some(report)
+}
+
+/// How long ago the process started, read from `/proc`
+fn process_age() -> Option<Duration> {
+    // the start time is the 22nd field, the 20th after the parenthesized name of the command
+    let stat = fs::read_to_string("/proc/self/stat").ok()?;
+    let (_, fields) = stat.rsplit_once(')')?;
+    let start_ticks: f64 = fields.split_whitespace().nth(19)?.parse().ok()?;
+    let uptime = fs::read_to_string("/proc/uptime").ok()?;
+    let uptime: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    let age = uptime - start_ticks / USER_HZ;
+    if age.is_finite() && age >= 0.0 {
+        Some(Duration::from_secs_f64(age))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handler_fn,
+        metrics::names,
+        stub::{Recording, Stub},
+        Context, Error, Runtime,
+    };
+    use std::env;
+
+    #[test]
+    fn records_name_the_invocation_and_initialization_type() {
+        let report = InitReport {
+            init_duration: Duration::from_micros(182_400),
+            first_event_latency: Duration::from_micros(3_100),
+            initialization_type: InitializationType::ProvisionedConcurrency,
+        };
+        assert!(report.provisioned());
+        assert_eq!(report.cold_start(), ColdStartKind::Provisioned);
+        let record = report.record("8476a536-e9f4-11e8-9739-2dfe598c3fcd");
+        assert_eq!(record["message"], "initialized");
+        assert_eq!(record["initializationType"], "provisioned-concurrency");
+        assert_eq!(record["coldStart"], "provisioned");
+        assert_eq!(record["requestId"], "8476a536-e9f4-11e8-9739-2dfe598c3fcd");
+        assert!((record["initDurationMs"].as_f64().unwrap() - 182.4).abs() < 1e-9);
+        assert!((record["firstEventLatencyMs"].as_f64().unwrap() - 3.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parses_every_initialization_type() {
+        for (value, parsed, cold_start) in [
+            (None, InitializationType::OnDemand, ColdStartKind::OnDemand),
+            (Some("on-demand"), InitializationType::OnDemand, ColdStartKind::OnDemand),
+            (
+                Some("provisioned-concurrency"),
+                InitializationType::ProvisionedConcurrency,
+                ColdStartKind::Provisioned,
+            ),
+            (
+                Some("snap-start"),
+                InitializationType::SnapStart,
+                ColdStartKind::Restored,
+            ),
+            (
+                Some("lambda-managed"),
+                InitializationType::Other("lambda-managed".to_owned()),
+                ColdStartKind::OnDemand,
+            ),
+        ] {
+            let initialization_type = InitializationType::from_var(value);
+            assert_eq!(initialization_type, parsed);
+            assert_eq!(initialization_type.cold_start(), cold_start);
+            assert_eq!(initialization_type.as_str(), value.unwrap_or("on-demand"));
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn reads_the_age_of_the_process() {
+        let age = process_age().unwrap();
+        assert!(age.as_secs() < 600, "{:?}", age);
+    }
+
+    #[test]
+    fn runtimes_report_initialization_once() {
+        for (value, initialization_type, (kind, cold_start), counted) in [
+            (None, "OnDemand", ("OnDemand", "on-demand"), names::COLD_STARTS),
+            (
+                Some("on-demand"),
+                "OnDemand",
+                ("OnDemand", "on-demand"),
+                names::COLD_STARTS,
+            ),
+            (
+                Some("provisioned-concurrency"),
+                "ProvisionedConcurrency",
+                ("Provisioned", "provisioned"),
+                names::PREWARMED_STARTS,
+            ),
+            (
+                Some("snap-start"),
+                "SnapStart",
+                ("Restored", "restored"),
+                names::PREWARMED_STARTS,
+            ),
+            (
+                Some("lambda-managed"),
+                "Other(\"lambda-managed\")",
+                ("OnDemand", "on-demand"),
+                names::COLD_STARTS,
+            ),
+        ] {
+            // like the panic hook, the report goes to the stdout of the process
+            let mut command = std::process::Command::new(env::current_exe().unwrap());
+            command.args([
+                "init::tests::initializing_runtime",
+                "--exact",
+                "--ignored",
+                "--nocapture",
+            ]);
+            match value {
+                Some(value) => command.env("AWS_LAMBDA_INITIALIZATION_TYPE", value),
+                None => command.env_remove("AWS_LAMBDA_INITIALIZATION_TYPE"),
+            };
+            let output = command.output().expect("failed to run the initializing runtime");
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(
+                output.status.success(),
+                "{}{}",
+                stdout,
+                String::from_utf8_lossy(&output.stderr)
+            );
+
+            // the harness names the test on the line the record starts on
+            let records: Vec<Value> = stdout
+                .lines()
+                .filter_map(|line| serde_json::from_str::<Value>(&line[line.find('{')?..]).ok())
+                .collect();
+            let initialized: Vec<_> = records
+                .iter()
+                .filter(|record| record["message"] == "initialized")
+                .collect();
+            assert_eq!(initialized.len(), 1, "{stdout}");
+            let record = initialized[0];
+            assert_eq!(record["level"], "INFO");
+            assert_eq!(record["initializationType"], value.unwrap_or("on-demand"));
+            assert_eq!(record["coldStart"], cold_start);
+            assert_eq!(record["requestId"], "8476a536-e9f4-11e8-9739-2dfe598c3fcd");
+            for field in &["initDurationMs", "firstEventLatencyMs"] {
+                let ms = record[field].as_f64().unwrap();
+                assert!(ms > 0.0 && ms < 60_000.0, "{}: {}", field, ms);
+            }
+
+            let observed = records
+                .iter()
+                .find(|record| record["message"] == "observed")
+                .unwrap_or_else(|| panic!("{}", stdout));
+            assert_eq!(observed["initializationType"], initialization_type);
+            assert_eq!(observed["coldStart"], kind);
+            assert_eq!(observed["provisioned"], value == Some("provisioned-concurrency"));
+            assert_eq!(observed["starts"], json!([format!("count {counted} 1")]));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "run by runtimes_report_initialization_once, in a process of its own"]
+    async fn initializing_runtime() -> Result<(), Error> {
+        let stub = Stub::default().invoking(vec![json!({}), json!({})]);
+        let addr = stub.start();
+        let sink = Recording::default();
+        let runtime = Runtime::new()
+            .with_endpoint(addr.to_string())
+            .with_metrics(sink.clone())
+            .run(handler_fn(|_: Value, ctx: Context| async move {
+                let report = ctx.init_report().map(|report| {
+                    json!({
+                        "initDuration": report.init_duration.as_secs_f64(),
+                        "provisioned": report.provisioned(),
+                    })
+                });
+                Ok::<_, Error>(json!({
+                    "report": report,
+                    "initializationType": format!("{:?}", ctx.initialization_type()),
+                    "coldStart": ctx.cold_start().map(|kind| format!("{kind:?}")),
+                }))
+            }));
+        tokio::pin!(runtime);
+        let mut responses = Vec::new();
+        while responses.len() < 2 {
+            tokio::select! {
+                res = &mut runtime => return res,
+                () = tokio::time::delay_for(Duration::from_millis(10)) => {}
+            }
+            let received = stub.received().into_iter();
+            responses.extend(
+                received
+                    .filter(|req| req.path.ends_with("/response"))
+                    .map(|req| req.body),
+            );
+        }
+        // only the first invocation is handed the report, and is a cold start of some kind
+        assert!(responses[0]["report"]["initDuration"].as_f64().unwrap() > 0.0);
+        assert!(responses[0]["coldStart"].is_string());
+        assert_eq!(responses[1]["report"], Value::Null);
+        assert_eq!(responses[1]["coldStart"], Value::Null);
+        assert_eq!(responses[0]["initializationType"], responses[1]["initializationType"]);
+
+        let starts: Vec<String> = std::mem::take(&mut *sink.0.lock().unwrap())
+            .into_iter()
+            .filter(|record| record.contains(names::COLD_STARTS) || record.contains(names::PREWARMED_STARTS))
+            .collect();
+        println!(
+            "{}",
+            json!({
+                "message": "observed",
+                "initializationType": responses[0]["initializationType"],
+                "coldStart": responses[0]["coldStart"],
+                "provisioned": responses[0]["report"]["provisioned"],
+                "starts": starts,
+            })
+        );
+        Ok(())
+    }
+}
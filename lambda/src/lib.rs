@@ -34,60 +34,70 @@
 //! }
 //! ```
 pub use crate::types::Context;
-use client::Client;
-use futures::stream::{Stream, StreamExt};
+pub use bytes::Bytes;
+use client::{Client, ClientConfig};
+use futures::{
+    future::{self, BoxFuture},
+    stream::{Stream, StreamExt},
+    FutureExt,
+};
 pub use lambda_attributes::lambda;
 use serde::{Deserialize, Serialize};
 use std::{
     convert::{TryFrom, TryInto},
-    env, fmt,
+    fmt,
     future::Future,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    time::{Duration, Instant},
 };
 
 mod client;
+mod config;
+mod error;
+pub mod extensions;
+pub mod init;
+#[cfg(feature = "local")]
+pub mod local;
+#[cfg(target_os = "linux")]
+pub mod memory;
+pub mod metrics;
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
+pub mod panics;
+pub mod record;
 mod requests;
+pub mod service;
 mod simulated;
+mod single;
+pub mod streaming;
+#[cfg(test)]
+mod stub;
 /// Types available to a Lambda function.
 mod types;
+#[cfg(feature = "xray")]
+pub mod xray;
+
+pub use config::{Config, ConfigError, InvalidVariable};
+pub use error::{HandlerError, RuntimeError};
+use hyper::body::HttpBody;
+use init::ColdStartKind;
+use metrics::{names, Metrics, MetricsSink};
+use requests::{EventCompletionRequest, EventErrorRequest, IntoRequest, NextEventRequest, FUNCTION_ERROR_TYPE_HEADER};
+use tracing::error;
+use tracing_futures::Instrument;
+use types::ErrorDiagnostic;
 
-use requests::{EventCompletionRequest, EventErrorRequest, IntoRequest, NextEventRequest};
-use types::Diagnostic;
+/// The size of the largest event a [`Runtime`] reads by default, generously above the 6 MB payloads
+/// Lambda accepts for synchronous invocations.
+pub const DEFAULT_MAX_EVENT_BYTES: usize = 8 * 1024 * 1024;
+
+/// The error type of invocations whose events exceed the configured limit
+const EVENT_TOO_LARGE: &str = "Runtime.EventTooLarge";
 
 /// Error type that lambdas may result in
 pub(crate) type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
 
-/// Configuration derived from environment variables.
-#[derive(Debug, Default, Clone, PartialEq)]
-pub struct Config {
-    /// The host and port of the [runtime API](https://docs.aws.amazon.com/lambda/latest/dg/runtimes-api.html).
-    pub endpoint: String,
-    /// The name of the function.
-    pub function_name: String,
-    /// The amount of memory available to the function in MB.
-    pub memory: i32,
-    /// The version of the function being executed.
-    pub version: String,
-    /// The name of the Amazon CloudWatch Logs stream for the function.
-    pub log_stream: String,
-    /// The name of the Amazon CloudWatch Logs group for the function.
-    pub log_group: String,
-}
-
-impl Config {
-    /// Attempts to read configuration from environment variables.
-    pub fn from_env() -> Result<Self, Error> {
-        let conf = Config {
-            endpoint: env::var("AWS_LAMBDA_RUNTIME_API")?,
-            function_name: env::var("AWS_LAMBDA_FUNCTION_NAME")?,
-            memory: env::var("AWS_LAMBDA_FUNCTION_MEMORY_SIZE")?.parse::<i32>()?,
-            version: env::var("AWS_LAMBDA_FUNCTION_VERSION")?,
-            log_stream: env::var("AWS_LAMBDA_LOG_STREAM_NAME")?,
-            log_group: env::var("AWS_LAMBDA_LOG_GROUP_NAME")?,
-        };
-        Ok(conf)
-    }
-}
-
 /// A trait describing an asynchronous function `A` to `B.
 pub trait Handler<A, B> {
     /// Errors returned by this handler.
@@ -98,6 +108,25 @@ pub trait Handler<A, B> {
     fn call(&mut self, event: A, context: Context) -> Self::Fut;
 }
 
+/// A transport to the [Runtime API](https://docs.aws.amazon.com/lambda/latest/dg/runtimes-api.html),
+/// or to anything serving its routes, which [`run_with_client`] polls for events.
+///
+/// The runtime sends requests whose URIs are only a path, like
+/// `/2018-06-01/runtime/invocation/next`, and expects the responses the Runtime API would send.
+pub trait LambdaRuntimeApi: Send + Sync {
+    /// Sends `req`, returning the response to it.
+    fn call(&self, req: http::Request<hyper::Body>) -> BoxFuture<'_, Result<http::Response<hyper::Body>, Error>>;
+}
+
+impl<T> LambdaRuntimeApi for &T
+where
+    T: LambdaRuntimeApi + ?Sized,
+{
+    fn call(&self, req: http::Request<hyper::Body>) -> BoxFuture<'_, Result<http::Response<hyper::Body>, Error>> {
+        (**self).call(req)
+    }
+}
+
 /// Returns a new `HandlerFn` with the given closure.
 pub fn handler_fn<F>(f: F) -> HandlerFn<F> {
     HandlerFn { f }
@@ -146,17 +175,142 @@ where
 pub async fn run<A, B, F>(handler: F) -> Result<(), Error>
 where
     F: Handler<A, B>,
-    <F as Handler<A, B>>::Error: fmt::Debug,
+    <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+    A: for<'de> Deserialize<'de>,
+    B: Serialize,
+{
+    Runtime::new().run(handler).await
+}
+
+/// Polls `client` for events and handles each with `handler`, like [`run`] does over the Runtime
+/// API `AWS_LAMBDA_RUNTIME_API` points to.
+///
+/// Nothing is read from the environment but the optional `AWS_LAMBDA_INITIALIZATION_TYPE`,
+/// reported once with the first invocation, so `client` may front any service which mimics the
+/// Runtime API. Everything else the loop does is configured by `config`.
+///
+/// # Errors
+///
+/// Runs until `client` fails, or answers a request for the next event with something other than
+/// an invocation, and returns why. Handlers which fail or panic don't end the loop: their errors
+/// are reported to `client` as those of their invocations.
+pub async fn run_with_client<C, A, B, F>(client: C, handler: F, config: RuntimeConfig) -> Result<(), RuntimeError>
+where
+    C: LambdaRuntimeApi,
+    F: Handler<A, B>,
+    <F as Handler<A, B>>::Error: fmt::Debug + 'static,
     A: for<'de> Deserialize<'de>,
     B: Serialize,
 {
     let mut handler = handler;
-    let config = Config::from_env()?;
-    let uri = config.endpoint.try_into().expect("Unable to convert to URL");
-    let client = Client::with(uri, hyper::Client::new());
-    let incoming = incoming(&client);
-    run_inner(&client, incoming, &mut handler).await?;
+    let mut config = config;
+    run_inner(
+        &client,
+        incoming(&client),
+        &mut handler,
+        json,
+        &mut Serialized,
+        &mut config,
+    )
+    .await
+}
+
+/// How an event loop handles invocations, be it that of a [`Runtime`] or one
+/// [`run_with_client`] runs over a transport of its own.
+///
+/// Unlike a `Runtime`, a configuration reads nothing from the environment: invocations are only
+/// recorded with a recorder it is given, and handlers are handed the function configuration it
+/// is given.
+#[derive(Debug)]
+pub struct RuntimeConfig {
+    env_config: Config,
+    max_event_bytes: Option<usize>,
+    metrics: Metrics,
+    recorder: Option<record::Recorder>,
+    omit_error_type_header: bool,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            env_config: Config::default(),
+            max_event_bytes: Some(DEFAULT_MAX_EVENT_BYTES),
+            metrics: Metrics::default(),
+            recorder: None,
+            omit_error_type_header: false,
+        }
+    }
+}
+
+impl RuntimeConfig {
+    /// Creates a configuration failing the invocations of events larger than
+    /// [8 MiB](DEFAULT_MAX_EVENT_BYTES), without metrics sinks or a recorder, handing handlers
+    /// an empty function configuration.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hands handlers `env_config` as the [`env_config`](Context::env_config) of every
+    /// invocation.
+    #[must_use]
+    pub fn with_env_config(self, env_config: Config) -> Self {
+        Self { env_config, ..self }
+    }
+
+    /// Fails the invocations of events larger than `max_event_bytes`, or never with `None`.
+    ///
+    /// The body of an event is read as it arrives, and abandoned once it grows past the limit,
+    /// whatever its headers announced: the invocation is reported to have failed with a
+    /// `Runtime.EventTooLarge` error, and the loop goes on with the next event.
+    #[must_use]
+    pub const fn with_max_event_bytes(mut self, max_event_bytes: Option<usize>) -> Self {
+        self.max_event_bytes = max_event_bytes;
+        self
+    }
+
+    /// Feeds `sink` the [measurements](metrics::names) of every invocation, after the sinks
+    /// registered before it.
+    #[must_use]
+    pub fn with_metrics(mut self, sink: impl MetricsSink + 'static) -> Self {
+        self.metrics.push(sink);
+        self
+    }
+
+    /// Records every invocation with `recorder`.
+    #[must_use]
+    pub fn with_recorder(self, recorder: record::Recorder) -> Self {
+        Self {
+            recorder: Some(recorder),
+            ..self
+        }
+    }
+
+    /// Leaves the `Lambda-Runtime-Function-Error-Type` header out of the reports of failed
+    /// invocations, which then name the type of their error in their body only.
+    #[must_use]
+    pub const fn without_error_type_header(mut self) -> Self {
+        self.omit_error_type_header = true;
+        self
+    }
+
+    /// What is sent besides the requests made for invocations and initializations
+    const fn client(&self) -> ClientConfig {
+        ClientConfig {
+            omit_error_type_header: self.omit_error_type_header,
+        }
+    }
+}
 
+/// Sends `client` the request `req` made for an invocation, with what `config` adds
+async fn send<C>(client: &C, mut req: http::Request<hyper::Body>, config: ClientConfig) -> Result<(), RuntimeError>
+where
+    C: LambdaRuntimeApi,
+{
+    if config.omit_error_type_header {
+        req.headers_mut().remove(FUNCTION_ERROR_TYPE_HEADER);
+    }
+    client.call(req).await.map_err(RuntimeError::Api)?;
     Ok(())
 }
 
@@ -164,21 +318,267 @@ where
 pub async fn run_simulated<A, B, F>(handler: F, url: &str) -> Result<(), Error>
 where
     F: Handler<A, B>,
-    <F as Handler<A, B>>::Error: fmt::Debug,
+    <F as Handler<A, B>>::Error: fmt::Debug + 'static,
     A: for<'de> Deserialize<'de>,
     B: Serialize,
 {
     let mut handler = handler;
     let uri = url.try_into().expect("Unable to convert to URL");
-    let client = Client::with(uri, hyper::Client::new());
+    let client = Client::new(uri);
     let incoming = incoming(&client).take(1);
-    run_inner(&client, incoming, &mut handler).await?;
+    run_inner(
+        &client,
+        incoming,
+        &mut handler,
+        json,
+        &mut Serialized,
+        &mut RuntimeConfig::default(),
+    )
+    .await?;
 
     Ok(())
 }
 
-fn incoming(client: &Client) -> impl Stream<Item = Result<http::Response<hyper::Body>, Error>> + '_ {
+/// A runtime configured before it starts polling for events on the [Lambda Runtime
+/// APIs](https://docs.aws.amazon.com/lambda/latest/dg/runtimes-api.html).
+///
+/// Closures registered with [`on_shutdown`](Runtime::on_shutdown) run when the execution
+/// environment shuts down, to flush buffered telemetry or roll back open transactions, say.
+///
+/// # Example
+/// ```no_run
+/// use lambda::{handler_fn, Context, Runtime};
+/// use serde_json::Value;
+///
+/// type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     Runtime::new()
+///         .on_shutdown(|remaining| println!("flushing with {:?} left", remaining))
+///         .run(handler_fn(func))
+///         .await?;
+///     Ok(())
+/// }
+///
+/// async fn func(event: Value, _: Context) -> Result<Value, Error> {
+///     Ok(event)
+/// }
+/// ```
+#[derive(Default)]
+pub struct Runtime {
+    endpoint: Option<String>,
+    internal: extensions::Internal,
+    panic_hook: bool,
+    config: RuntimeConfig,
+}
+
+impl Runtime {
+    /// Creates a runtime talking to the Runtime API `AWS_LAMBDA_RUNTIME_API` points to.
+    #[must_use]
+    pub fn new() -> Self {
+        init::started();
+        Self::default()
+    }
+
+    /// Talks to the Runtime API at `endpoint`, like `127.0.0.1:9001`, instead.
+    #[must_use]
+    pub fn with_endpoint(self, endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: Some(endpoint.into()),
+            ..self
+        }
+    }
+
+    /// Runs `cleanup` with the time left before the execution environment is reclaimed once it
+    /// shuts down, after the closures registered before it.
+    ///
+    /// Lambda only notifies processes which registered with the Extensions API of shutdowns, so
+    /// a runtime with cleanup closures registers itself as an internal extension before it asks
+    /// the Runtime API for the first event. Internal extensions can't register for the
+    /// `SHUTDOWN` event: Lambda sends the process `SIGTERM` instead, and kills it 500ms later. A
+    /// thread of its own listens for the signal, while the event loop keeps using the Runtime
+    /// API, and runs the closures with what is left of those 500ms. Once they ran,
+    /// [`run`](Runtime::run) stops the event loop, dropping any invocation in progress, and
+    /// returns so that the process can exit.
+    #[must_use]
+    pub fn on_shutdown<F>(mut self, cleanup: F) -> Self
+    where
+        F: FnOnce(Duration) + Send + 'static,
+    {
+        self.internal.shutdown_hooks.push(Box::new(cleanup));
+        self
+    }
+
+    /// Installs the [structured panic hook](panics::install_hook) when the runtime starts, so
+    /// that panics of handlers are logged as single records naming their invocation.
+    #[must_use]
+    pub fn with_panic_hook(self) -> Self {
+        Self {
+            panic_hook: true,
+            ..self
+        }
+    }
+
+    /// Feeds `sink` the [measurements](metrics::names) of every invocation, after the sinks
+    /// registered before it.
+    #[must_use]
+    pub fn with_metrics(mut self, sink: impl MetricsSink + 'static) -> Self {
+        self.config = self.config.with_metrics(sink);
+        self
+    }
+
+    /// Records every invocation with `recorder`, rather than with the recorder
+    /// `LAMBDA_RUNTIME_RECORD_DIR` configures, if any.
+    #[must_use]
+    pub fn with_recorder(mut self, recorder: record::Recorder) -> Self {
+        self.config = self.config.with_recorder(recorder);
+        self
+    }
+
+    /// Leaves the `Lambda-Runtime-Function-Error-Type` header out of the reports of failed
+    /// invocations and initializations, which then name the type of their error in their body
+    /// only.
+    #[must_use]
+    pub const fn without_error_type_header(mut self) -> Self {
+        self.config.omit_error_type_header = true;
+        self
+    }
+
+    /// Fails the invocations of events larger than `max_event_bytes`, [8 MiB](DEFAULT_MAX_EVENT_BYTES)
+    /// by default, or never with `None`.
+    ///
+    /// The body of an event is read as it arrives, and abandoned once it grows past the limit,
+    /// whatever its headers announced: the invocation is reported to have failed with a
+    /// `Runtime.EventTooLarge` error, and the runtime goes on with the next event.
+    #[must_use]
+    pub const fn with_max_event_bytes(mut self, max_event_bytes: Option<usize>) -> Self {
+        self.config.max_event_bytes = max_event_bytes;
+        self
+    }
+
+    /// Starts the runtime and begins polling for events, until the execution environment shuts
+    /// down if cleanup closures are registered.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the configuration can't be read from the environment or the Runtime API can't
+    /// be reached. Should the runtime fail to register as an extension, or to listen for
+    /// `SIGTERM`, the failure is logged and its cleanup closures won't run, but events still do.
+    pub async fn run<A, B, F>(self, handler: F) -> Result<(), Error>
+    where
+        F: Handler<A, B>,
+        <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+        A: for<'de> Deserialize<'de>,
+        B: Serialize,
+    {
+        self.run_with(handler, json).await
+    }
+
+    /// Starts the runtime like [`run`](Runtime::run), calling `handler` with the body of every
+    /// event as it was received, without deserializing it.
+    ///
+    /// The body isn't copied, so that handlers of large events can deserialize it into types
+    /// borrowing their strings from it, with [`serde_json::from_slice`] and `#[serde(borrow)]`
+    /// fields, instead of allocating every one of them.
+    ///
+    /// ```no_run
+    /// use lambda::{handler_fn, Bytes, Context, Runtime};
+    /// use serde::Deserialize;
+    /// use std::borrow::Cow;
+    ///
+    /// type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Upload<'a> {
+    ///     #[serde(borrow)]
+    ///     content: Cow<'a, str>,
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Error> {
+    ///     Runtime::new().run_raw(handler_fn(func)).await?;
+    ///     Ok(())
+    /// }
+    ///
+    /// async fn func(body: Bytes, _: Context) -> Result<usize, Error> {
+    ///     let upload: Upload<'_> = serde_json::from_slice(&body)?;
+    ///     Ok(upload.content.len())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`run`](Runtime::run).
+    pub async fn run_raw<B, F>(self, handler: F) -> Result<(), Error>
+    where
+        F: Handler<Bytes, B>,
+        <F as Handler<Bytes, B>>::Error: fmt::Debug + 'static,
+        B: Serialize,
+    {
+        self.run_with(handler, raw).await
+    }
+
+    async fn run_with<A, B, F>(self, handler: F, decode: Decode<A>) -> Result<(), Error>
+    where
+        F: Handler<A, B>,
+        <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+        B: Serialize,
+    {
+        let mut handler = handler;
+        let mut config = self.config;
+        if config.recorder.is_none() {
+            config.recorder = record::Recorder::from_env();
+        }
+        if self.panic_hook {
+            panics::install_hook();
+        }
+        if self.endpoint.is_none() {
+            config.env_config = crate::config::configure(config.client()).await?;
+        }
+        let endpoint = self.endpoint.unwrap_or_else(|| config.env_config.endpoint.clone());
+        let uri = endpoint.as_str().try_into()?;
+        let client = Client::new(uri);
+        if self.internal.is_empty() {
+            return Ok(run_inner(
+                &client,
+                incoming(&client),
+                &mut handler,
+                decode,
+                &mut Serialized,
+                &mut config,
+            )
+            .await?);
+        }
+
+        // registering completes before the first event is asked for, which ends initialization
+        let abort_on_failure = self.internal.abort_on_failure;
+        let started = extensions::start(&endpoint, self.internal).await;
+        let extension: Pin<Box<dyn Future<Output = Result<(), Error>> + Send>> = match started {
+            Ok(extension) if abort_on_failure => Box::pin(extension),
+            // should the extension lose the Extensions API, the hooks won't run but events still do
+            Ok(extension) => Box::pin(extensions::degraded(extension).map(Ok)),
+            Err(e) if abort_on_failure => return Err(e),
+            Err(e) => {
+                error!(message = "The internal extension failed to start, continuing without it", e = %e);
+                Box::pin(future::pending())
+            }
+        };
+        let mut sink = Serialized;
+        let invocations = run_inner(&client, incoming(&client), &mut handler, decode, &mut sink, &mut config);
+        tokio::select! {
+            res = invocations => Ok(res?),
+            res = extension => res,
+        }
+    }
+}
+
+fn incoming<C>(client: &C) -> impl Stream<Item = Result<http::Response<hyper::Body>, Error>> + '_
+where
+    C: LambdaRuntimeApi,
+{
     async_stream::stream! {
+        init::ready();
         loop {
             let req = NextEventRequest.into_req().expect("Unable to construct request");
             let res = client.call(req).await;
@@ -187,47 +587,528 @@ fn incoming(client: &Client) -> impl Stream<Item = Result<http::Response<hyper::
     }
 }
 
-async fn run_inner<A, B, F>(
-    client: &Client,
+async fn run_inner<C, A, B, F, R>(
+    client: &C,
     incoming: impl Stream<Item = Result<http::Response<hyper::Body>, Error>>,
     handler: &mut F,
-) -> Result<(), Error>
+    decode: Decode<A>,
+    sink: &mut R,
+    config: &mut RuntimeConfig,
+) -> Result<(), RuntimeError>
 where
+    C: LambdaRuntimeApi,
     F: Handler<A, B>,
-    <F as Handler<A, B>>::Error: fmt::Debug,
-    A: for<'de> Deserialize<'de>,
-    B: Serialize,
+    <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+    R: ResponseSink<B>,
 {
     tokio::pin!(incoming);
+    // error reports are serialized into a buffer with room for the largest of them, whose bytes
+    // each report takes
+    let mut report = Vec::new();
 
     while let Some(event) = incoming.next().await {
-        let event = event?;
-        let (parts, body) = event.into_parts();
-
-        let ctx: Context = Context::try_from(parts.headers)?;
-        let body = hyper::body::to_bytes(body).await?;
-        let body = serde_json::from_slice(&body)?;
+        let event = event.map_err(RuntimeError::Api)?;
+        let invocation = next_invocation(client, event, decode, config.max_event_bytes, config.client()).await?;
+        let Some((mut ctx, body, event)) = invocation else {
+            continue;
+        };
+        ctx.env_config = config.env_config.clone();
+        let metrics = &mut config.metrics;
+        record_start(metrics, &ctx, event.len());
+        let invocation = config.recorder.as_ref().map(|_| (ctx.clone(), event));
 
         let request_id = &ctx.request_id.clone();
-        let f = handler.call(body, ctx);
-
-        let req = match f.await {
-            Ok(res) => EventCompletionRequest { request_id, body: res }.into_req()?,
-            Err(e) => EventErrorRequest {
-                request_id,
-                diagnostic: Diagnostic {
-                    error_message: format!("{:?}", e),
-                    error_type: type_name_of_val(e).to_owned(),
-                },
+        let span = invocation_span(&ctx);
+        let f = panics::InInvocation::new(request_id, async { handler.call(body, ctx).await }).instrument(span);
+
+        let started = Instant::now();
+        let res = AssertUnwindSafe(f).catch_unwind().await;
+        metrics.duration(names::DURATION, started.elapsed());
+        let failed = !matches!(res, Ok(Ok(_)));
+        let req = match res {
+            Ok(Ok(res)) => sink.respond(request_id, res).await?,
+            Ok(Err(e)) => {
+                metrics.count(names::ERRORS, 1);
+                let req = EventErrorRequest {
+                    request_id,
+                    diagnostic: ErrorDiagnostic::new(&e),
+                };
+                Some(req.into_req_with(&mut report).map_err(RuntimeError::Serialize)?)
+            }
+            Err(panic) => {
+                metrics.count(names::ERRORS, 1);
+                metrics.count(names::PANICS, 1);
+                let req = EventErrorRequest {
+                    request_id,
+                    diagnostic: panics::diagnostic(&*panic),
+                };
+                Some(req.into_req_with(&mut report).map_err(RuntimeError::Serialize)?)
             }
-            .into_req()?,
         };
-        client.call(req).await?;
+        // streamed responses were sent, and can't be recorded, as they were produced
+        if let Some(req) = req {
+            let req = match (config.recorder.as_mut(), invocation) {
+                (Some(recorder), Some((ctx, event))) => recorder
+                    .record(&ctx, event, failed, req)
+                    .await
+                    .map_err(RuntimeError::Record)?,
+                _ => req,
+            };
+            if let Some(response_bytes) = HttpBody::size_hint(req.body()).exact() {
+                #[allow(clippy::cast_precision_loss)]
+                config.metrics.gauge(names::RESPONSE_BYTES, response_bytes as f64);
+            }
+            send(client, req, config.client()).await?;
+        }
+        config.metrics.flush();
     }
 
     Ok(())
 }
 
-fn type_name_of_val<T>(_: T) -> &'static str {
-    std::any::type_name::<T>()
+/// Records the measurements of an invocation known before its handler is called
+fn record_start(metrics: &mut Metrics, ctx: &Context, request_bytes: usize) {
+    metrics.count(names::INVOCATIONS, 1);
+    if let Some(report) = ctx.init_report() {
+        match report.cold_start() {
+            ColdStartKind::OnDemand => metrics.count(names::COLD_STARTS, 1),
+            ColdStartKind::Provisioned | ColdStartKind::Restored => metrics.count(names::PREWARMED_STARTS, 1),
+        }
+        metrics.duration(names::INIT_DURATION, report.init_duration);
+    }
+    #[allow(clippy::cast_precision_loss)]
+    metrics.gauge(names::REQUEST_BYTES, request_bytes as f64);
+}
+
+/// The span the handler of the invocation `ctx` runs in, naming how its environment was
+/// initialized and, on its first invocation, the kind of cold start
+fn invocation_span(ctx: &Context) -> tracing::Span {
+    tracing::info_span!(
+        "invocation",
+        request_id = %ctx.request_id,
+        initialization_type = ctx.initialization_type().as_str(),
+        cold_start = ctx.cold_start().map_or("none", ColdStartKind::as_str),
+    )
+}
+
+/// Turns the body of an invocation into the event its handler is called with
+type Decode<A> = fn(Bytes) -> Result<A, Error>;
+
+/// What the event loop hands the responses of handlers to
+pub(crate) trait ResponseSink<B> {
+    /// Turns `response`, to the invocation `request_id`, into the request which reports it, or
+    /// sends it itself, returning `None` then.
+    async fn respond(
+        &mut self,
+        request_id: &str,
+        response: B,
+    ) -> Result<Option<http::Request<hyper::Body>>, RuntimeError>;
+}
+
+/// Reports responses serialized into JSON, as the body of a single request
+pub(crate) struct Serialized;
+
+impl<B> ResponseSink<B> for Serialized
+where
+    B: Serialize,
+{
+    async fn respond(
+        &mut self,
+        request_id: &str,
+        response: B,
+    ) -> Result<Option<http::Request<hyper::Body>>, RuntimeError> {
+        let req = EventCompletionRequest {
+            request_id,
+            body: response,
+        };
+        Ok(Some(req.into_req().map_err(RuntimeError::Serialize)?))
+    }
+}
+
+/// Deserializes events from JSON
+// takes the body by value to share the signature of `raw`
+#[allow(clippy::needless_pass_by_value)]
+fn json<A>(body: Bytes) -> Result<A, Error>
+where
+    A: for<'de> Deserialize<'de>,
+{
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Hands bodies over as they were received
+#[allow(clippy::unnecessary_wraps)]
+const fn raw(body: Bytes) -> Result<Bytes, Error> {
+    Ok(body)
+}
+
+/// Splits a next invocation response into its `Context`, decoded event and the event as it was
+/// received, or reports its invocation failed, returning `None`, if the event is larger than
+/// `max_event_bytes`
+async fn next_invocation<C, A>(
+    client: &C,
+    event: http::Response<hyper::Body>,
+    decode: Decode<A>,
+    max_event_bytes: Option<usize>,
+    config: ClientConfig,
+) -> Result<Option<(Context, A, Bytes)>, RuntimeError>
+where
+    C: LambdaRuntimeApi,
+{
+    let (parts, body) = event.into_parts();
+
+    let mut ctx: Context = Context::try_from(parts.headers).map_err(RuntimeError::Invocation)?;
+    ctx.init_report = init::first_invocation(&ctx.request_id);
+    let body = match read_event(body, max_event_bytes).await.map_err(RuntimeError::Api)? {
+        Ok(body) => body,
+        Err(read) => {
+            let limit = max_event_bytes.unwrap_or_default();
+            error!(
+                message = "The event exceeds the configured limit, failing its invocation",
+                request_id = %ctx.request_id,
+                read,
+                limit
+            );
+            let req = EventErrorRequest {
+                request_id: &ctx.request_id,
+                diagnostic: types::Diagnostic {
+                    error_type: EVENT_TOO_LARGE.to_owned(),
+                    error_message: format!(
+                        "event payload of at least {read} bytes exceeds configured limit of {limit} bytes"
+                    ),
+                },
+            };
+            send(client, req.into_req().map_err(RuntimeError::Serialize)?, config).await?;
+            return Ok(None);
+        }
+    };
+    let event = decode(body.clone()).map_err(RuntimeError::Deserialize)?;
+    Ok(Some((ctx, event, body)))
+}
+
+/// Reads the body of an event as it arrives, or stops once it is larger than `max_event_bytes`,
+/// returning how many bytes it counted by then
+async fn read_event(mut body: hyper::Body, max_event_bytes: Option<usize>) -> Result<Result<Bytes, usize>, Error> {
+    let limit = max_event_bytes.unwrap_or(usize::MAX);
+    let announced = usize::try_from(HttpBody::size_hint(&body).lower()).unwrap_or(usize::MAX);
+    if announced > limit {
+        return Ok(Err(announced));
+    }
+    let mut chunks = Vec::new();
+    let mut read = 0;
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        read += chunk.len();
+        if read > limit {
+            // dropping the rest of the body closes the connection rather than reading it
+            return Ok(Err(read));
+        }
+        chunks.push(chunk);
+    }
+    // most events arrive in one chunk, which is handed over without being copied
+    if chunks.len() == 1 {
+        Ok(Ok(chunks.remove(0)))
+    } else {
+        Ok(Ok(chunks.concat().into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::{IntoResponse, NextEventResponse};
+    use crate::stub::Stub;
+    use serde_json::{json, Value};
+    use std::{collections::VecDeque, sync::Mutex};
+
+    /// A Runtime API serving `invocations` in order, then failing to serve more, and recording
+    /// the requests it receives
+    #[derive(Default)]
+    struct Mock {
+        invocations: Mutex<VecDeque<Value>>,
+        /// Whether to fail responses and errors sent to it, rather than accepting them
+        unreachable_after_invoking: bool,
+        received: Mutex<Vec<(String, Value)>>,
+        /// The error type headers of the errors sent to it
+        error_types: Mutex<Vec<Option<String>>>,
+    }
+
+    impl Mock {
+        fn invoking(invocations: Vec<Value>) -> Self {
+            Self {
+                invocations: Mutex::new(invocations.into()),
+                ..Self::default()
+            }
+        }
+
+        fn paths(&self) -> Vec<String> {
+            self.received
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(path, _)| path.clone())
+                .collect()
+        }
+    }
+
+    impl LambdaRuntimeApi for Mock {
+        fn call(&self, req: http::Request<hyper::Body>) -> BoxFuture<'_, Result<http::Response<hyper::Body>, Error>> {
+            Box::pin(async move {
+                let (parts, body) = req.into_parts();
+                let body = hyper::body::to_bytes(body).await?;
+                let path = parts.uri.path().to_owned();
+                if path.ends_with("/error") {
+                    let error_type = parts.headers.get(FUNCTION_ERROR_TYPE_HEADER);
+                    let error_type = error_type.map(|value| value.to_str().unwrap().to_owned());
+                    self.error_types.lock().unwrap().push(error_type);
+                }
+                let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+                self.received.lock().unwrap().push((path.clone(), body));
+
+                if path == "/2018-06-01/runtime/invocation/next" {
+                    let invocation = self.invocations.lock().unwrap().pop_front();
+                    match invocation {
+                        Some(invocation) => NextEventResponse {
+                            request_id: "8476a536-e9f4-11e8-9739-2dfe598c3fcd",
+                            deadline: 1_542_409_706_888,
+                            arn: "arn:aws:lambda:us-east-2:123456789012:function:custom-runtime",
+                            trace_id: "Root=1-5bef4de7-ad49b0e87f6ef6c87fc2e700;Parent=9a9197af755a6419",
+                            body: serde_json::to_vec(&invocation)?,
+                        }
+                        .into_rsp(),
+                        None => Err("no more invocations".into()),
+                    }
+                } else if self.unreachable_after_invoking {
+                    Err("connection reset".into())
+                } else {
+                    Ok(http::Response::builder().status(202).body(hyper::Body::empty())?)
+                }
+            })
+        }
+    }
+
+    async fn divide(event: Value, _: Context) -> Result<Value, Error> {
+        match event["divisor"].as_i64() {
+            Some(0) => Err("division by zero".into()),
+            Some(divisor) => Ok(json!(100 / divisor)),
+            None => panic!("no divisor"),
+        }
+    }
+
+    #[tokio::test]
+    async fn loops_respond_to_every_invocation_until_the_api_fails() {
+        let client = Mock::invoking(vec![json!({"divisor": 4}), json!({"divisor": 5})]);
+        let e = run_with_client(&client, handler_fn(divide), RuntimeConfig::new())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&e, RuntimeError::Api(e) if e.to_string() == "no more invocations"),
+            "{:?}",
+            e
+        );
+        let received = std::mem::take(&mut *client.received.lock().unwrap());
+        let paths: Vec<_> = received.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "/2018-06-01/runtime/invocation/next",
+                "/2018-06-01/runtime/invocation/8476a536-e9f4-11e8-9739-2dfe598c3fcd/response",
+                "/2018-06-01/runtime/invocation/next",
+                "/2018-06-01/runtime/invocation/8476a536-e9f4-11e8-9739-2dfe598c3fcd/response",
+                "/2018-06-01/runtime/invocation/next",
+            ]
+        );
+        assert_eq!(received[1].1, 25);
+        assert_eq!(received[3].1, 20);
+    }
+
+    #[tokio::test]
+    async fn loops_report_handler_errors_and_panics_then_go_on() {
+        let client = Mock::invoking(vec![json!({"divisor": 0}), json!({}), json!({"divisor": 10})]);
+        let e = run_with_client(&client, handler_fn(divide), RuntimeConfig::new())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&e, RuntimeError::Api(e) if e.to_string() == "no more invocations"),
+            "{:?}",
+            e
+        );
+        let received = std::mem::take(&mut *client.received.lock().unwrap());
+        assert_eq!(received.len(), 7);
+        assert_eq!(
+            received[1].0,
+            "/2018-06-01/runtime/invocation/8476a536-e9f4-11e8-9739-2dfe598c3fcd/error"
+        );
+        assert!(received[1].1["errorMessage"]
+            .as_str()
+            .unwrap()
+            .contains("division by zero"));
+        assert_eq!(
+            received[3].0,
+            "/2018-06-01/runtime/invocation/8476a536-e9f4-11e8-9739-2dfe598c3fcd/error"
+        );
+        assert_eq!(received[3].1["errorType"], "Panic");
+        assert_eq!(received[5].1, 10);
+    }
+
+    #[tokio::test]
+    async fn loops_end_when_results_cant_be_sent() {
+        let client = Mock {
+            unreachable_after_invoking: true,
+            ..Mock::invoking(vec![json!({"divisor": 4}), json!({"divisor": 5})])
+        };
+        let e = run_with_client(&client, handler_fn(divide), RuntimeConfig::new())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&e, RuntimeError::Api(e) if e.to_string() == "connection reset"),
+            "{:?}",
+            e
+        );
+        // the second invocation is never asked for
+        assert_eq!(
+            client.paths(),
+            [
+                "/2018-06-01/runtime/invocation/next",
+                "/2018-06-01/runtime/invocation/8476a536-e9f4-11e8-9739-2dfe598c3fcd/response",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn loops_follow_their_configuration() {
+        let client = Mock::invoking(vec![
+            json!({"divisor": 0}),
+            json!({"divisor": 5, "padding": "well past the limit of the configuration"}),
+            json!({"divisor": 4}),
+        ]);
+        let config = RuntimeConfig::new()
+            .with_max_event_bytes(Some(32))
+            .without_error_type_header();
+        let e = run_with_client(&client, handler_fn(divide), config).await.unwrap_err();
+        assert!(matches!(e, RuntimeError::Api(_)), "{:?}", e);
+        let received = std::mem::take(&mut *client.received.lock().unwrap());
+        assert_eq!(received[3].1["errorType"], EVENT_TOO_LARGE);
+        assert_eq!(received[5].1, 25);
+        assert_eq!(*client.error_types.lock().unwrap(), [None, None]);
+
+        // which the header names otherwise
+        let client = Mock::invoking(vec![json!({"divisor": 0})]);
+        run_with_client(&client, handler_fn(divide), RuntimeConfig::new())
+            .await
+            .unwrap_err();
+        let error_types = std::mem::take(&mut *client.error_types.lock().unwrap());
+        assert_eq!(error_types.len(), 1);
+        assert!(error_types[0].is_some());
+    }
+
+    #[tokio::test]
+    async fn loops_hand_handlers_their_function_configuration() {
+        let client = Mock::invoking(vec![json!({})]);
+        let env_config = Config {
+            memory: 256,
+            ..Config::default()
+        };
+        let handler = handler_fn(|_: Value, ctx: Context| async move { Ok::<_, Error>(ctx.env_config.memory) });
+        run_with_client(&client, handler, RuntimeConfig::new().with_env_config(env_config))
+            .await
+            .unwrap_err();
+        assert_eq!(client.received.lock().unwrap()[1].1, 256);
+    }
+
+    #[tokio::test]
+    async fn loops_end_on_events_which_dont_deserialize() {
+        let client = Mock::invoking(vec![json!("not a divisor")]);
+        let e = run_with_client(
+            &client,
+            handler_fn(|event: u32, _| async move { Ok::<_, Error>(event) }),
+            RuntimeConfig::new(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(e, RuntimeError::Deserialize(_)), "{:?}", e);
+        assert_eq!(client.paths(), ["/2018-06-01/runtime/invocation/next"]);
+    }
+
+    #[tokio::test]
+    async fn runtimes_fail_invocations_of_oversized_events_and_go_on() -> Result<(), Error> {
+        let oversized = json!({ "padding": "x".repeat(4096) });
+        for max_event_bytes in [Some(1024), None] {
+            let stub = Stub::default()
+                .chunked(512)
+                .invoking(vec![oversized.clone(), json!({ "message": "hello" })]);
+            let addr = stub.start();
+            let runtime = Runtime::new()
+                .with_endpoint(addr.to_string())
+                .with_max_event_bytes(max_event_bytes)
+                .run(handler_fn(
+                    |event: Value, _: Context| async move { Ok::<_, Error>(event) },
+                ));
+            tokio::pin!(runtime);
+            let mut reports = Vec::new();
+            while reports.len() < 2 {
+                tokio::select! {
+                    res = &mut runtime => return res,
+                    () = tokio::time::delay_for(Duration::from_millis(10)) => {}
+                }
+                reports.extend(
+                    stub.received()
+                        .into_iter()
+                        .filter(|req| req.path.starts_with("/2018-06-01/runtime/invocation/8476a536")),
+                );
+            }
+
+            // the oversized event fails its invocation, without reaching the handler
+            if max_event_bytes.is_some() {
+                assert!(reports[0].path.ends_with("/error"), "{:?}", reports[0]);
+                assert_eq!(reports[0].body["errorType"], "Runtime.EventTooLarge");
+                assert_eq!(reports[0].function_error_type.as_deref(), Some("Runtime.EventTooLarge"));
+                let message = reports[0].body["errorMessage"].as_str().unwrap();
+                assert!(message.starts_with("event payload of at least "), "{}", message);
+                assert!(
+                    message.ends_with(" bytes exceeds configured limit of 1024 bytes"),
+                    "{}",
+                    message
+                );
+            } else {
+                assert!(reports[0].path.ends_with("/response"), "{:?}", reports[0]);
+                assert_eq!(reports[0].body, oversized);
+            }
+            // and the next one is still handled
+            assert!(reports[1].path.ends_with("/response"), "{:?}", reports[1]);
+            assert_eq!(reports[1].body, json!({ "message": "hello" }));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn raw_runtimes_hand_over_bodies_as_received() -> Result<(), Error> {
+        #[derive(serde::Deserialize)]
+        struct Message<'a> {
+            #[serde(borrow)]
+            body: std::borrow::Cow<'a, str>,
+        }
+
+        let stub = Stub::default().invoking(vec![json!({ "body": "hello" })]);
+        let addr = stub.start();
+        let runtime = Runtime::new().with_endpoint(addr.to_string()).run_raw(handler_fn(
+            |body: crate::Bytes, _: Context| async move {
+                let message: Message<'_> = serde_json::from_slice(&body)?;
+                let borrowed = matches!(message.body, std::borrow::Cow::Borrowed(_));
+                Ok::<_, Error>(json!({ "raw": std::str::from_utf8(&body)?, "borrowed": borrowed }))
+            },
+        ));
+        tokio::pin!(runtime);
+        let response = loop {
+            tokio::select! {
+                res = &mut runtime => return res,
+                () = tokio::time::delay_for(Duration::from_millis(10)) => {}
+            }
+            let response = stub.received().into_iter().find(|req| req.path.ends_with("/response"));
+            if let Some(response) = response {
+                break response;
+            }
+        };
+        assert_eq!(response.body, json!({ "raw": r#"{"body":"hello"}"#, "borrowed": true }));
+        Ok(())
+    }
 }
@@ -0,0 +1,427 @@
+//! Invoking handlers outside of Lambda, with the `local` feature.
+//!
+//! [`invoke_file`] calls a handler once with the event in a JSON file, and
+//! [`invoke_http`] serves invocations the way the [Runtime Interface
+//! Emulator](https://github.com/aws/aws-lambda-runtime-interface-emulator) does, on
+//! `POST /2015-03-31/functions/function/invocations`:
+//!
+//! ```no_run
+//! use lambda::{handler_fn, local, Context};
+//! use serde_json::Value;
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     local::invoke_file(handler_fn(func), "events/order.json").await?;
+//!     Ok(())
+//! }
+//!
+//! async fn func(event: Value, _: Context) -> Result<Value, Error> {
+//!     Ok(event)
+//! }
+//! ```
+//!
+//! Either way, each event goes through the same loop as in Lambda, against a stand-in for the
+//! Runtime API serving that event alone, so that handlers see the same context and their
+//! responses, errors and panics are serialized exactly as the Runtime API would receive them.
+//! The context holds a made-up request ID, and the deadline, memory and function name an
+//! [`Invoker`] is configured with.
+use crate::{
+    record::RecordedOutcome, requests::NextEventResponse, single::Single, Config, Error, Handler, RuntimeConfig,
+};
+use bytes::Bytes;
+use futures::lock::Mutex as AsyncMutex;
+use http::{Method, Request, Response, StatusCode};
+use hyper::{server::conn::Http, service::service_fn, Body};
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::{Infallible, TryFrom},
+    env, fmt,
+    net::SocketAddr,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::net::TcpListener;
+
+/// The path the Runtime Interface Emulator takes invocations on
+const INVOCATIONS_PATH: &str = "/2015-03-31/functions/function/invocations";
+
+/// The timeout of invocations unless configured otherwise, that of new functions
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The memory of the function unless configured otherwise, in MB, that of new functions
+const DEFAULT_MEMORY: i32 = 128;
+
+/// Invocations made by this process, numbering their request IDs
+static INVOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// The settings of local invocations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Invoker {
+    timeout: Duration,
+    memory: i32,
+    function_name: String,
+}
+
+impl Invoker {
+    /// Creates an invoker with a timeout of 3 seconds, 128 MB of memory, and a function named
+    /// `function`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            memory: DEFAULT_MEMORY,
+            function_name: "function".to_owned(),
+        }
+    }
+
+    /// Creates an invoker taking the timeout, in seconds, memory, in MB, and function name from
+    /// `AWS_LAMBDA_FUNCTION_TIMEOUT`, `AWS_LAMBDA_FUNCTION_MEMORY_SIZE` and
+    /// `AWS_LAMBDA_FUNCTION_NAME` where they are set, like the Runtime Interface Emulator.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let invoker = Self::new();
+        let timeout = env::var("AWS_LAMBDA_FUNCTION_TIMEOUT")
+            .ok()
+            .and_then(|timeout| timeout.parse().ok())
+            .map_or(invoker.timeout, Duration::from_secs);
+        let memory = env::var("AWS_LAMBDA_FUNCTION_MEMORY_SIZE")
+            .ok()
+            .and_then(|memory| memory.parse().ok())
+            .unwrap_or(invoker.memory);
+        let function_name = env::var("AWS_LAMBDA_FUNCTION_NAME").unwrap_or(invoker.function_name);
+        Self {
+            timeout,
+            memory,
+            function_name,
+        }
+    }
+
+    /// Gives every invocation `timeout` before its deadline.
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Configures the function with `memory` MB.
+    #[must_use]
+    pub const fn with_memory(mut self, memory: i32) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    /// Names the function `function_name`.
+    #[must_use]
+    pub fn with_function_name(self, function_name: impl Into<String>) -> Self {
+        Self {
+            function_name: function_name.into(),
+            ..self
+        }
+    }
+
+    /// Calls `handler` with `event`, returning what the runtime sends the Runtime API for it.
+    ///
+    /// # Errors
+    ///
+    /// Fails like the runtime would, if `event` can't be deserialized.
+    pub async fn invoke<A, B, F>(&self, handler: &mut F, event: impl Into<Bytes>) -> Result<RecordedOutcome, Error>
+    where
+        F: Handler<A, B>,
+        <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+        A: for<'de> Deserialize<'de>,
+        B: Serialize,
+    {
+        let api = self.single(&event.into())?;
+        let config = RuntimeConfig::new().with_env_config(self.config());
+        api.invoke(handler, config).await
+    }
+
+    /// Calls `handler` with the event in the file at `path`, then prints the response or error
+    /// the runtime sends the Runtime API for it to stdout and returns it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read, or its event can't be deserialized.
+    pub async fn invoke_file<A, B, F>(&self, handler: F, path: impl AsRef<Path>) -> Result<RecordedOutcome, Error>
+    where
+        F: Handler<A, B>,
+        <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+        A: for<'de> Deserialize<'de>,
+        B: Serialize,
+    {
+        let mut handler = handler;
+        let event = tokio::fs::read(path).await?;
+        let outcome = self.invoke(&mut handler, event).await?;
+        match &outcome {
+            RecordedOutcome::Response { body } => println!("{}", String::from_utf8_lossy(body)),
+            RecordedOutcome::Error { error } => println!("{error}"),
+        }
+        Ok(outcome)
+    }
+
+    /// Serves invocations of `handler` on `addr`, one at a time, the way the Runtime Interface
+    /// Emulator does: responses are sent with a status of 200, and so are errors, marked by an
+    /// `X-Amz-Function-Error` header.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `addr` can't be bound, or connections can't be accepted anymore.
+    pub async fn invoke_http<A, B, F>(&self, handler: F, addr: SocketAddr) -> Result<(), Error>
+    where
+        F: Handler<A, B>,
+        <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+        A: for<'de> Deserialize<'de>,
+        B: Serialize,
+    {
+        let handler = AsyncMutex::new(handler);
+        let mut listener = TcpListener::bind(addr).await?;
+        let mut http = Http::new().with_executor(Inline);
+        http.http1_only(true).keep_alive(false);
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let service = service_fn(|req| async {
+                let mut handler = handler.lock().await;
+                Ok::<_, Infallible>(self.answer(&mut *handler, req).await)
+            });
+            if let Err(e) = http.serve_connection(stream, service).await {
+                tracing::error!(message = "Failed to serve a local invocation", e = %e);
+            }
+        }
+    }
+
+    async fn answer<A, B, F>(&self, handler: &mut F, req: Request<Body>) -> Response<Body>
+    where
+        F: Handler<A, B>,
+        <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+        A: for<'de> Deserialize<'de>,
+        B: Serialize,
+    {
+        if req.method() != Method::POST || req.uri().path() != INVOCATIONS_PATH {
+            return status(StatusCode::NOT_FOUND, Body::empty());
+        }
+        let res = match hyper::body::to_bytes(req.into_body()).await {
+            Ok(event) => self.invoke(handler, event).await,
+            Err(e) => Err(e.into()),
+        };
+        match res {
+            Ok(RecordedOutcome::Response { body }) => status(StatusCode::OK, Body::from(body)),
+            Ok(RecordedOutcome::Error { error }) => Response::builder()
+                .header("x-amz-function-error", "Unhandled")
+                .body(Body::from(error.to_string()))
+                .expect("Unable to construct response"),
+            Err(e) => status(StatusCode::INTERNAL_SERVER_ERROR, Body::from(e.to_string())),
+        }
+    }
+
+    /// A Runtime API serving an invocation of `event`, with a made-up request and trace ID
+    fn single(&self, event: &[u8]) -> Result<Single, Error> {
+        let invocation = INVOCATIONS.fetch_add(1, Ordering::SeqCst) + 1;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let request_id = format!("00000000-0000-4000-8000-{invocation:012x}");
+        let arn = format!("arn:aws:lambda:us-east-1:012345678912:function:{}", self.function_name);
+        let trace_id = format!(
+            "Root=1-{:08x}-{invocation:024x};Parent={invocation:016x};Sampled=0",
+            now.as_secs()
+        );
+        let deadline = u64::try_from((now + self.timeout).as_millis())?;
+        Single::new(NextEventResponse {
+            request_id: &request_id,
+            deadline,
+            arn: &arn,
+            trace_id: &trace_id,
+            body: event.to_vec(),
+        })
+    }
+
+    /// The configuration of the function handlers are called with
+    fn config(&self) -> Config {
+        Config {
+            function_name: self.function_name.clone(),
+            memory: self.memory,
+            version: "$LATEST".to_owned(),
+            ..Config::default()
+        }
+    }
+}
+
+impl Default for Invoker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Calls `handler` with the event in the file at `path`, configured by the environment like
+/// [`Invoker::from_env`], then prints and returns the response or error the runtime sends the
+/// Runtime API for it.
+///
+/// # Errors
+///
+/// Fails if the file can't be read, or its event can't be deserialized.
+pub async fn invoke_file<A, B, F>(handler: F, path: impl AsRef<Path>) -> Result<RecordedOutcome, Error>
+where
+    F: Handler<A, B>,
+    <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+    A: for<'de> Deserialize<'de>,
+    B: Serialize,
+{
+    Invoker::from_env().invoke_file(handler, path).await
+}
+
+/// Serves invocations of `handler` on `addr` like the Runtime Interface Emulator, configured
+/// by the environment like [`Invoker::from_env`].
+///
+/// # Errors
+///
+/// Fails if `addr` can't be bound, or connections can't be accepted anymore.
+pub async fn invoke_http<A, B, F>(handler: F, addr: SocketAddr) -> Result<(), Error>
+where
+    F: Handler<A, B>,
+    <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+    A: for<'de> Deserialize<'de>,
+    B: Serialize,
+{
+    Invoker::from_env().invoke_http(handler, addr).await
+}
+
+fn status(status: StatusCode, body: Body) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(body)
+        .expect("Unable to construct response")
+}
+
+/// The executor of connections serving HTTP/1 alone, which never spawns anything
+#[derive(Clone, Copy)]
+struct Inline;
+
+impl<F> hyper::rt::Executor<F> for Inline {
+    fn execute(&self, _: F) {
+        unreachable!("HTTP/2 is disabled");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler_fn, Context, RuntimeError};
+    use serde_json::{json, Value};
+
+    async fn order(event: Value, ctx: Context) -> Result<Value, Error> {
+        match event["items"].as_u64() {
+            Some(0) => Err("the order has no items".into()),
+            items => Ok(json!({
+                "accepted": items,
+                "memory": ctx.env_config.memory,
+                "function": ctx.env_config.function_name,
+                "requestId": ctx.request_id,
+            })),
+        }
+    }
+
+    fn event_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = env::temp_dir().join(format!("lambda-local-{}-{name}.json", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn invokes_handlers_with_events_from_files() -> Result<(), Error> {
+        let path = event_file("success", r#"{"items": 3}"#);
+        let invoker = Invoker::new().with_memory(256).with_function_name("orders");
+        let outcome = invoker.invoke_file(handler_fn(order), &path).await?;
+        std::fs::remove_file(&path)?;
+
+        let body = match outcome {
+            RecordedOutcome::Response { body } => serde_json::from_slice::<Value>(&body)?,
+            RecordedOutcome::Error { error } => panic!("{}", error),
+        };
+        assert_eq!(body["accepted"], 3);
+        assert_eq!(body["memory"], 256);
+        assert_eq!(body["function"], "orders");
+        assert!(body["requestId"]
+            .as_str()
+            .unwrap()
+            .starts_with("00000000-0000-4000-8000-"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn returns_handler_errors_as_the_runtime_reports_them() -> Result<(), Error> {
+        let path = event_file("error", r#"{"items": 0}"#);
+        let outcome = Invoker::new().invoke_file(handler_fn(order), &path).await?;
+        std::fs::remove_file(&path)?;
+
+        let error = Error::from("the order has no items");
+        assert_eq!(
+            outcome,
+            RecordedOutcome::Error {
+                error: serde_json::to_value(crate::types::ErrorDiagnostic::new(&error))?
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fails_on_malformed_events() {
+        let path = event_file("malformed", r#"{"items": "#);
+        let res = Invoker::new().invoke_file(handler_fn(order), &path).await;
+        std::fs::remove_file(&path).unwrap();
+        let e = res.unwrap_err();
+        assert!(
+            matches!(e.downcast_ref(), Some(RuntimeError::Deserialize(e)) if e.is::<serde_json::Error>()),
+            "{:?}",
+            e
+        );
+
+        let res = Invoker::new()
+            .invoke_file(handler_fn(order), "no/such/event.json")
+            .await;
+        assert!(res.unwrap_err().is::<std::io::Error>());
+    }
+
+    #[tokio::test]
+    async fn serves_invocations_like_the_emulator() -> Result<(), Error> {
+        // a port which was free a moment ago
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let invoker = Invoker::new();
+        let server = invoker.invoke_http(handler_fn(order), addr);
+        tokio::pin!(server);
+
+        let client = hyper::Client::new();
+        let post = |event: &'static str| {
+            let req = Request::post(format!("http://{addr}{INVOCATIONS_PATH}"))
+                .body(Body::from(event))
+                .unwrap();
+            client.request(req)
+        };
+        // the server only runs while it's polled, and binds once it is
+        tokio::select! {
+            res = &mut server => return res,
+            () = tokio::time::delay_for(Duration::from_millis(50)) => {}
+        }
+        let rsp = tokio::select! {
+            res = &mut server => return res,
+            rsp = post(r#"{"items": 3}"#) => rsp?,
+        };
+        assert_eq!(rsp.status(), StatusCode::OK);
+        assert!(!rsp.headers().contains_key("x-amz-function-error"));
+        let body: Value = serde_json::from_slice(&hyper::body::to_bytes(rsp.into_body()).await?)?;
+        assert_eq!(body["accepted"], 3);
+
+        let rsp = tokio::select! {
+            res = &mut server => return res,
+            rsp = post(r#"{"items": 0}"#) => rsp?,
+        };
+        assert_eq!(rsp.status(), StatusCode::OK);
+        assert_eq!(rsp.headers()["x-amz-function-error"], "Unhandled");
+        let error: Value = serde_json::from_slice(&hyper::body::to_bytes(rsp.into_body()).await?)?;
+        assert!(error["errorMessage"]
+            .as_str()
+            .unwrap()
+            .contains("the order has no items"));
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+//! Memory usage of invocations, on Linux.
+//!
+//! The `REPORT` line Lambda writes after every invocation only tells the most memory the
+//! execution environment ever used. A handler wrapped with [`reported`] resets the peak resident
+//! set size of the process before every invocation, then reads it back with the current one from
+//! `/proc/self/status` once the invocation is handled, and writes one JSON record of them to
+//! stdout, next to the memory the function is configured with, as the
+//! [`env_config`](crate::Context::env_config) of the invocation tells it:
+//!
+//! ```json
+//! {"level":"INFO","message":"memory usage","requestId":"8476a536-e9f4-11e8-9739-2dfe598c3fcd","rssKb":14052,"hwmKb":71788,"memoryLimitMb":128,"usagePercent":54.8,"overThreshold":false}
+//! ```
+//!
+//! Invocations whose peak exceeds a share of the configured memory, 90% unless
+//! [`warn_above`](Reported::warn_above) says otherwise, are logged at the `WARN` level instead,
+//! with `overThreshold` set. Where there is no procfs, nothing is logged. Where the peak can't
+//! be reset, the high-water mark is that of the process rather than of the invocation.
+//!
+//! Resetting the peak writes `5` to `/proc/self/clear_refs` before every invocation. The reset
+//! applies to the whole process: anything else in it watching `VmHWM`, or clearing the page
+//! reference bits `clear_refs` takes other values for, sees it too.
+//!
+//! ```no_run
+//! use lambda::{handler_fn, memory, Context};
+//! use serde_json::Value;
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     lambda::run(memory::reported(handler_fn(func)).warn_above(75)).await?;
+//!     Ok(())
+//! }
+//!
+//! async fn func(event: Value, _: Context) -> Result<Value, Error> {
+//!     Ok(event)
+//! }
+//! ```
+use crate::{Context, Handler};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use std::{convert::TryFrom, fmt, fs};
+
+/// The share of the configured memory, in percents, above which invocations are warned about
+/// unless configured otherwise
+const DEFAULT_THRESHOLD: u8 = 90;
+
+/// The resident set sizes of the process, in kB
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Usage {
+    /// The resident set size once the invocation was handled
+    rss_kb: u64,
+    /// The peak resident set size during the invocation
+    hwm_kb: u64,
+}
+
+impl Usage {
+    /// Reads the usage of the process from procfs, if there is one
+    fn read() -> Option<Self> {
+        let status = fs::read_to_string("/proc/self/status").ok()?;
+        let field = |name: &str| {
+            let line = status.lines().find(|line| line.starts_with(name))?;
+            line[name.len()..].trim().trim_end_matches("kB").trim().parse().ok()
+        };
+        Some(Self {
+            rss_kb: field("VmRSS:")?,
+            hwm_kb: field("VmHWM:")?,
+        })
+    }
+
+    /// The record of the usage of invocation `request_id`, for a function configured with
+    /// `limit_mb` of memory
+    fn record(self, request_id: &str, limit_mb: Option<u64>, threshold: u8) -> Value {
+        #[allow(clippy::cast_precision_loss)]
+        let percent = limit_mb
+            .filter(|limit_mb| *limit_mb > 0)
+            .map(|limit_mb| self.hwm_kb as f64 * 100.0 / (limit_mb * 1024) as f64);
+        let over = percent.is_some_and(|percent| percent > f64::from(threshold));
+        json!({
+            "level": if over { "WARN" } else { "INFO" },
+            "message": "memory usage",
+            "requestId": request_id,
+            "rssKb": self.rss_kb,
+            "hwmKb": self.hwm_kb,
+            "memoryLimitMb": limit_mb,
+            "usagePercent": percent,
+            "overThreshold": over,
+        })
+    }
+}
+
+/// Resets the peak resident set size of the whole process to its current one, where Linux
+/// allows it
+fn reset_peak() {
+    let _ = fs::write("/proc/self/clear_refs", "5");
+}
+
+/// Wraps `handler` to log the memory usage of every invocation once it is handled.
+pub const fn reported<H>(handler: H) -> Reported<H> {
+    Reported {
+        handler,
+        threshold: DEFAULT_THRESHOLD,
+    }
+}
+
+/// A `Handler` logging the memory usage of every invocation, created by [`reported`].
+pub struct Reported<H> {
+    handler: H,
+    threshold: u8,
+}
+
+impl<H> Reported<H> {
+    /// Warns about invocations whose peak usage exceeds `percent` of the configured memory.
+    #[must_use]
+    pub const fn warn_above(mut self, percent: u8) -> Self {
+        self.threshold = percent;
+        self
+    }
+}
+
+impl<H> fmt::Debug for Reported<H>
+where
+    H: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reported")
+            .field("handler", &self.handler)
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
+
+impl<H, A, B> Handler<A, B> for Reported<H>
+where
+    H: Handler<A, B>,
+    H::Fut: Send + 'static,
+    H::Error: fmt::Debug,
+{
+    type Error = H::Error;
+    type Fut = BoxFuture<'static, Result<B, H::Error>>;
+
+    fn call(&mut self, event: A, ctx: Context) -> Self::Fut {
+        let request_id = ctx.request_id.clone();
+        let limit_mb = u64::try_from(ctx.env_config.memory)
+            .ok()
+            .filter(|limit_mb| *limit_mb > 0);
+        let threshold = self.threshold;
+        reset_peak();
+        let fut = self.handler.call(event, ctx);
+        Box::pin(async move {
+            let res = fut.await;
+            if let Some(usage) = Usage::read() {
+                println!("{}", usage.record(&request_id, limit_mb, threshold));
+            }
+            res
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler_fn, Config, Error};
+    use std::env;
+
+    #[test]
+    fn flags_usage_above_the_threshold() {
+        let usage = Usage {
+            rss_kb: 14_052,
+            hwm_kb: 71_788,
+        };
+        let record = usage.record("8476a536-e9f4-11e8-9739-2dfe598c3fcd", Some(128), 50);
+        assert_eq!(record["level"], "WARN");
+        assert_eq!(record["overThreshold"], true);
+        assert_eq!(record["memoryLimitMb"], 128);
+        let percent = record["usagePercent"].as_f64().unwrap();
+        assert!((percent - 54.77).abs() < 0.01, "{}", percent);
+
+        let record = usage.record("8476a536-e9f4-11e8-9739-2dfe598c3fcd", Some(128), 90);
+        assert_eq!(record["level"], "INFO");
+        assert_eq!(record["overThreshold"], false);
+
+        // without a configured limit, there is nothing to compare with
+        let record = usage.record("8476a536-e9f4-11e8-9739-2dfe598c3fcd", None, 50);
+        assert_eq!(record["overThreshold"], false);
+        assert_eq!(record["usagePercent"], Value::Null);
+    }
+
+    #[test]
+    fn reports_the_peak_of_each_invocation() {
+        // the records go to the stdout of the process, which only a parent process can read
+        let output = std::process::Command::new(env::current_exe().unwrap())
+            .args([
+                "memory::tests::allocating_handler",
+                "--exact",
+                "--ignored",
+                "--nocapture",
+            ])
+            .output()
+            .expect("failed to run the allocating handler");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "{}{}",
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        // the harness names the test on the line the first record starts on
+        let records: Vec<Value> = stdout
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(&line[line.find('{')?..]).ok())
+            .filter(|record| record["message"] == "memory usage")
+            .collect();
+        assert_eq!(records.len(), 2, "{stdout}");
+        let (small, large) = (&records[0], &records[1]);
+        assert_eq!(small["level"], "INFO");
+        assert_eq!(large["level"], "WARN");
+        assert_eq!(large["memoryLimitMb"], 256);
+        let grown = large["hwmKb"].as_u64().unwrap() - small["hwmKb"].as_u64().unwrap();
+        assert!(grown >= 60 * 1024, "{}", stdout);
+        // the buffer was freed before the invocation ended
+        assert!(large["rssKb"].as_u64().unwrap() < large["hwmKb"].as_u64().unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore = "run by reports_the_peak_of_each_invocation, in a process of its own"]
+    async fn allocating_handler() -> Result<(), Error> {
+        let mut handler = reported(handler_fn(|size: usize, _: Context| async move {
+            let buffer = vec![1_u8; size];
+            Ok::<_, Error>(
+                std::hint::black_box(buffer)
+                    .iter()
+                    .map(|byte| usize::from(*byte))
+                    .sum::<usize>(),
+            )
+        }))
+        .warn_above(20);
+        let ctx = Context {
+            env_config: Config {
+                memory: 256,
+                ..Config::default()
+            },
+            ..Context::default()
+        };
+        assert_eq!(handler.call(1024, ctx.clone()).await?, 1024);
+        assert_eq!(handler.call(64 << 20, ctx).await?, 64 << 20);
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+//! Measurements of invocations, exported through pluggable sinks.
+//!
+//! A [`Runtime`](crate::Runtime) or [`LambdaService`](crate::service::LambdaService) with sinks
+//! registered through `with_metrics` feeds every one of them the standard measurements of each
+//! invocation, [named](names) the same whatever the sink, then flushes them once the result of
+//! the invocation is sent to the Runtime API. Sinks never fail invocations: errors flushing
+//! them are logged.
+//!
+//! [`StatsdSink`] sends them to a `StatsD` daemon, like one running as an extension:
+//!
+//! ```no_run
+//! use lambda::{handler_fn, metrics::StatsdSink, Context, Runtime};
+//! use serde_json::Value;
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     let sink = StatsdSink::new().with_prefix("orders");
+//!     Runtime::new().with_metrics(sink).run(handler_fn(func)).await?;
+//!     Ok(())
+//! }
+//!
+//! async fn func(event: Value, _: Context) -> Result<Value, Error> {
+//!     Ok(event)
+//! }
+//! ```
+use crate::Error;
+use std::{
+    fmt::{self, Write},
+    net::{SocketAddr, UdpSocket},
+    time::Duration,
+};
+use tracing::error;
+
+/// The names of the measurements the runtime records for every invocation.
+pub mod names {
+    /// The duration of a handler call, up to its response, error or panic.
+    pub const DURATION: &str = "invocation.duration";
+    /// One for every invocation.
+    pub const INVOCATIONS: &str = "invocation.count";
+    /// One for every invocation whose handler failed or panicked.
+    pub const ERRORS: &str = "invocation.errors";
+    /// One for every invocation whose handler panicked.
+    pub const PANICS: &str = "invocation.panics";
+    /// One for the first invocation of an execution environment initialized on demand, which
+    /// waited for the initialization.
+    pub const COLD_STARTS: &str = "invocation.coldstarts";
+    /// One for the first invocation of an execution environment initialized ahead of it, for
+    /// provisioned concurrency or from a `SnapStart` snapshot.
+    pub const PREWARMED_STARTS: &str = "invocation.prewarmed_starts";
+    /// The duration of the initialization, recorded for the first invocation only.
+    pub const INIT_DURATION: &str = "init.duration";
+    /// The size of the event, in bytes.
+    pub const REQUEST_BYTES: &str = "invocation.request_bytes";
+    /// The size of the response or error sent for the invocation, in bytes.
+    pub const RESPONSE_BYTES: &str = "invocation.response_bytes";
+}
+
+/// A destination for the measurements of invocations.
+pub trait MetricsSink: Send {
+    /// Records that `name` took `duration`.
+    fn record_duration(&mut self, name: &str, duration: Duration);
+    /// Adds `count` to the counter `name`.
+    fn record_count(&mut self, name: &str, count: u64);
+    /// Sets the gauge `name` to `value`.
+    fn record_gauge(&mut self, name: &str, value: f64);
+    /// Exports the measurements recorded since the last flush, called after every invocation.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the measurements can't be exported; the runtime logs the error and goes on.
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// The sinks registered on a runtime
+#[derive(Default)]
+pub(crate) struct Metrics {
+    sinks: Vec<Box<dyn MetricsSink>>,
+}
+
+impl Metrics {
+    pub(crate) fn push(&mut self, sink: impl MetricsSink + 'static) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    pub(crate) fn duration(&mut self, name: &str, duration: Duration) {
+        for sink in &mut self.sinks {
+            sink.record_duration(name, duration);
+        }
+    }
+
+    pub(crate) fn count(&mut self, name: &str, count: u64) {
+        for sink in &mut self.sinks {
+            sink.record_count(name, count);
+        }
+    }
+
+    pub(crate) fn gauge(&mut self, name: &str, value: f64) {
+        for sink in &mut self.sinks {
+            sink.record_gauge(name, value);
+        }
+    }
+
+    /// Flushes every sink, logging those which fail
+    pub(crate) fn flush(&mut self) {
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.flush() {
+                error!(message = "Failed to flush metrics", e = %e);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics").field("sinks", &self.sinks.len()).finish()
+    }
+}
+
+/// Where a `StatsD` daemon listens unless configured otherwise
+const DEFAULT_STATSD_ADDRESS: ([u8; 4], u16) = ([127, 0, 0, 1], 8125);
+
+/// The size datagrams are kept under, to stay clear of fragmentation
+const MAX_DATAGRAM: usize = 1432;
+
+/// A sink sending measurements to a `StatsD` daemon over UDP, as timers, counters and gauges.
+///
+/// Measurements are buffered until flushed, then sent in as few datagrams as fit them.
+#[derive(Debug)]
+pub struct StatsdSink {
+    address: SocketAddr,
+    prefix: Option<String>,
+    lines: Vec<String>,
+    socket: Option<UdpSocket>,
+}
+
+impl StatsdSink {
+    /// Creates a sink sending to the daemon at `127.0.0.1:8125`, without a prefix.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            address: DEFAULT_STATSD_ADDRESS.into(),
+            prefix: None,
+            lines: Vec::new(),
+            socket: None,
+        }
+    }
+
+    /// Sends to the daemon at `address` instead.
+    #[must_use]
+    pub const fn with_address(mut self, address: SocketAddr) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Prefixes the names of measurements with `prefix` and a dot.
+    #[must_use]
+    pub fn with_prefix(self, prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: Some(prefix.into()),
+            ..self
+        }
+    }
+
+    fn push(&mut self, name: &str, value: fmt::Arguments<'_>, kind: &str) {
+        let mut line = String::new();
+        if let Some(prefix) = &self.prefix {
+            let _ = write!(line, "{prefix}.");
+        }
+        let _ = write!(line, "{name}:{value}|{kind}");
+        self.lines.push(line);
+    }
+}
+
+impl Default for StatsdSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsSink for StatsdSink {
+    fn record_duration(&mut self, name: &str, duration: Duration) {
+        self.push(name, format_args!("{}", duration.as_secs_f64() * 1000.0), "ms");
+    }
+
+    fn record_count(&mut self, name: &str, count: u64) {
+        self.push(name, format_args!("{count}"), "c");
+    }
+
+    fn record_gauge(&mut self, name: &str, value: f64) {
+        self.push(name, format_args!("{value}"), "g");
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+        let lines = std::mem::take(&mut self.lines);
+        let socket = match self.socket.take() {
+            Some(socket) => socket,
+            None => UdpSocket::bind(("0.0.0.0", 0))?,
+        };
+        let mut datagram = String::new();
+        for line in lines {
+            if !datagram.is_empty() && datagram.len() + 1 + line.len() > MAX_DATAGRAM {
+                socket.send_to(datagram.as_bytes(), self.address)?;
+                datagram.clear();
+            }
+            if !datagram.is_empty() {
+                datagram.push('\n');
+            }
+            datagram.push_str(&line);
+        }
+        socket.send_to(datagram.as_bytes(), self.address)?;
+        self.socket = Some(socket);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handler_fn,
+        stub::{Recording, Stub},
+        Context, Runtime,
+    };
+    use serde_json::{json, Value};
+
+    #[test]
+    fn sends_prefixed_measurements_when_flushed() -> Result<(), Error> {
+        let daemon = UdpSocket::bind("127.0.0.1:0")?;
+        daemon.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut sink = StatsdSink::new()
+            .with_address(daemon.local_addr()?)
+            .with_prefix("orders");
+        sink.record_duration(names::DURATION, Duration::from_micros(1500));
+        sink.record_count(names::INVOCATIONS, 1);
+        sink.record_gauge(names::REQUEST_BYTES, 42.0);
+        sink.flush()?;
+
+        let mut buf = [0; MAX_DATAGRAM];
+        let len = daemon.recv(&mut buf)?;
+        assert_eq!(
+            std::str::from_utf8(&buf[..len])?,
+            "orders.invocation.duration:1.5|ms\norders.invocation.count:1|c\norders.invocation.request_bytes:42|g"
+        );
+
+        // nothing is left to send
+        sink.flush()?;
+        daemon.set_read_timeout(Some(Duration::from_millis(50)))?;
+        assert!(daemon.recv(&mut buf).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn splits_datagrams_too_large_to_send_at_once() -> Result<(), Error> {
+        let daemon = UdpSocket::bind("127.0.0.1:0")?;
+        daemon.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut sink = StatsdSink::new().with_address(daemon.local_addr()?);
+        for _ in 0..100 {
+            sink.record_count(names::INVOCATIONS, 1);
+        }
+        sink.flush()?;
+
+        let mut buf = vec![0; 64 * 1024];
+        let mut received = 0;
+        while received < 100 {
+            let len = daemon.recv(&mut buf)?;
+            assert!(len <= MAX_DATAGRAM);
+            received += std::str::from_utf8(&buf[..len])?.lines().count();
+        }
+        assert_eq!(received, 100);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn runtimes_feed_metrics_of_every_outcome() -> Result<(), Error> {
+        let stub = Stub::default().invoking(vec![
+            json!({ "outcome": "success" }),
+            json!({ "outcome": "error" }),
+            json!({ "outcome": "panic" }),
+        ]);
+        let addr = stub.start();
+        let sink = Recording::default();
+        let runtime = Runtime::new()
+            .with_endpoint(addr.to_string())
+            .with_metrics(sink.clone())
+            .run(handler_fn(|event: Value, _: Context| async move {
+                match event["outcome"].as_str() {
+                    Some("error") => Err(Error::from("the order was refused")),
+                    Some("panic") => panic!("the order has no items"),
+                    _ => Ok(json!("ok")),
+                }
+            }));
+        tokio::pin!(runtime);
+        // failing flushes are logged, and leave every invocation handled
+        while sink
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|record| *record == "flush")
+            .count()
+            < 3
+        {
+            tokio::select! {
+                res = &mut runtime => return res,
+                () = tokio::time::delay_for(Duration::from_millis(10)) => {}
+            }
+        }
+
+        let records = sink.0.lock().unwrap().clone();
+        let invocations: Vec<Vec<String>> = records
+            .split(|record| record == "flush")
+            .take(3)
+            .map(|invocation| {
+                invocation
+                    .iter()
+                    // only the first invocation of the process is a cold start
+                    .filter(|record| !record.contains(names::COLD_STARTS) && !record.contains(names::INIT_DURATION))
+                    .cloned()
+                    .collect()
+            })
+            .collect();
+        let request_bytes = |bytes: usize| format!("gauge {} {}", names::REQUEST_BYTES, bytes);
+        let response_bytes = |bytes: usize| format!("gauge {} {}", names::RESPONSE_BYTES, bytes);
+        let invoked = format!("count {} 1", names::INVOCATIONS);
+        let duration = format!("duration {}", names::DURATION);
+        let errored = format!("count {} 1", names::ERRORS);
+        let panicked = format!("count {} 1", names::PANICS);
+        // `{"outcome":"success"}` answered with `"ok"`
+        assert_eq!(
+            invocations[0],
+            vec![invoked.clone(), request_bytes(21), duration.clone(), response_bytes(4)]
+        );
+        // the size of the error depends on the name of its type
+        assert_eq!(invocations[1].len(), 5, "{:?}", invocations[1]);
+        assert_eq!(
+            invocations[1][..4],
+            [invoked.clone(), request_bytes(19), duration.clone(), errored.clone()]
+        );
+        assert!(invocations[1][4].starts_with(&format!("gauge {} ", names::RESPONSE_BYTES)));
+        // `{"errorType":"Panic","errorMessage":"the order has no items"}`
+        assert_eq!(
+            invocations[2],
+            vec![
+                invoked,
+                request_bytes(19),
+                duration,
+                errored,
+                panicked,
+                response_bytes(61)
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+//! [OpenTelemetry](https://opentelemetry.io) tracing of invocations, behind the `opentelemetry`
+//! feature.
+//!
+//! Lambda starts an X-Ray trace for every invocation, and hands its context to the runtime in the
+//! `Lambda-Runtime-Trace-Id` header, like `Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`.
+//! A handler wrapped with [`traced`] runs every invocation in a span whose parent is that
+//! context, converted to W3C identifiers, so that the spans it exports connect to the trace of
+//! the caller. As the execution environment may be frozen as soon as the function responds, the
+//! tracer provider is flushed after every invocation.
+//!
+//! ```no_run
+//! use lambda::{handler_fn, otel, Context};
+//! use opentelemetry_sdk::trace::TracerProvider;
+//! use serde_json::Value;
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     let provider = TracerProvider::builder().build();
+//!     lambda::run(otel::traced(handler_fn(func), provider)).await?;
+//!     Ok(())
+//! }
+//!
+//! async fn func(event: Value, _: Context) -> Result<Value, Error> {
+//!     Ok(event)
+//! }
+//! ```
+use crate::{Context, Handler};
+use futures::future::BoxFuture;
+use opentelemetry::{
+    propagation::{text_map_propagator::FieldIter, Extractor, Injector, TextMapPropagator},
+    trace::{
+        Span, SpanContext, SpanId, SpanKind, Status, TraceContextExt, TraceFlags, TraceId, TraceState, Tracer,
+        TracerProvider as _,
+    },
+    KeyValue,
+};
+use opentelemetry_sdk::trace::TracerProvider;
+use std::{env, fmt};
+use tracing::error;
+
+/// The header carrying X-Ray trace contexts
+const TRACE_HEADER: &str = "X-Amzn-Trace-Id";
+
+/// Parses an X-Ray trace header, like
+/// `Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`, into a remote
+/// span context.
+///
+/// The trace ID is the root's timestamp and identifier concatenated, and the span ID the
+/// parent's. Headers without a valid root or parent yield `None`.
+#[must_use]
+pub fn span_context(header: &str) -> Option<SpanContext> {
+    let mut trace_id = None;
+    let mut span_id = None;
+    let mut sampled = false;
+    for field in header.split(';') {
+        match field.trim().split_once('=') {
+            Some(("Root", root)) => {
+                let mut parts = root.split('-');
+                trace_id = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                    (Some("1"), Some(time), Some(id), None) if time.len() == 8 && id.len() == 24 => {
+                        TraceId::from_hex(&[time, id].concat()).ok()
+                    }
+                    _ => None,
+                };
+            }
+            Some(("Parent", parent)) if parent.len() == 16 => span_id = SpanId::from_hex(parent).ok(),
+            Some(("Sampled", flag)) => sampled = flag == "1",
+            _ => {}
+        }
+    }
+    let flags = if sampled {
+        TraceFlags::SAMPLED
+    } else {
+        TraceFlags::default()
+    };
+    Some(SpanContext::new(
+        trace_id?,
+        span_id?,
+        flags,
+        true,
+        TraceState::default(),
+    ))
+    .filter(SpanContext::is_valid)
+}
+
+/// Formats `span_context` as an X-Ray trace header.
+#[must_use]
+pub fn trace_header(span_context: &SpanContext) -> String {
+    let trace_id = format!("{:032x}", span_context.trace_id());
+    format!(
+        "Root=1-{}-{};Parent={:016x};Sampled={}",
+        &trace_id[..8],
+        &trace_id[8..],
+        span_context.span_id(),
+        if span_context.is_sampled() { '1' } else { '0' }
+    )
+}
+
+/// A propagator of span contexts in the `X-Amzn-Trace-Id` header, in the X-Ray format.
+#[derive(Debug, Clone)]
+pub struct XRayPropagator {
+    fields: [String; 1],
+}
+
+impl XRayPropagator {
+    /// Creates a propagator.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            fields: [TRACE_HEADER.to_lowercase()],
+        }
+    }
+}
+
+impl Default for XRayPropagator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextMapPropagator for XRayPropagator {
+    fn inject_context(&self, cx: &opentelemetry::Context, injector: &mut dyn Injector) {
+        let span = cx.span();
+        let span_context = span.span_context();
+        if span_context.is_valid() {
+            injector.set(TRACE_HEADER, trace_header(span_context));
+        }
+    }
+
+    fn extract_with_context(&self, cx: &opentelemetry::Context, extractor: &dyn Extractor) -> opentelemetry::Context {
+        extractor
+            .get(TRACE_HEADER)
+            .and_then(span_context)
+            .map_or_else(|| cx.clone(), |span_context| cx.with_remote_span_context(span_context))
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        FieldIter::new(&self.fields)
+    }
+}
+
+/// Wraps `handler` to run every invocation in a span traced by `provider`, flushing it once the
+/// invocation is handled.
+pub fn traced<H>(handler: H, provider: TracerProvider) -> Traced<H> {
+    let tracer = provider.tracer("lambda");
+    Traced {
+        handler,
+        provider,
+        tracer,
+    }
+}
+
+/// A `Handler` running every invocation in a span, created by [`traced`].
+pub struct Traced<H> {
+    handler: H,
+    provider: TracerProvider,
+    tracer: opentelemetry_sdk::trace::Tracer,
+}
+
+impl<H> fmt::Debug for Traced<H>
+where
+    H: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Traced")
+            .field("handler", &self.handler)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H, A, B> Handler<A, B> for Traced<H>
+where
+    H: Handler<A, B>,
+    H::Fut: Send + 'static,
+    H::Error: fmt::Debug,
+{
+    type Error = H::Error;
+    type Fut = BoxFuture<'static, Result<B, H::Error>>;
+
+    fn call(&mut self, event: A, ctx: Context) -> Self::Fut {
+        let header = if ctx.xray_trace_id.is_empty() {
+            env::var("_X_AMZN_TRACE_ID").unwrap_or_default()
+        } else {
+            ctx.xray_trace_id.clone()
+        };
+        let parent = span_context(&header).map_or_else(opentelemetry::Context::new, |span_context| {
+            opentelemetry::Context::new().with_remote_span_context(span_context)
+        });
+        let arn: Vec<_> = ctx.invoked_function_arn.split(':').collect();
+        let name = arn.get(6).map_or("invocation", |name| *name).to_owned();
+        let mut attributes = vec![
+            KeyValue::new("faas.invocation_id", ctx.request_id.clone()),
+            KeyValue::new("faas.coldstart", ctx.cold_start().is_some()),
+            KeyValue::new("cloud.provider", "aws"),
+            KeyValue::new("cloud.resource_id", ctx.invoked_function_arn.clone()),
+        ];
+        if let Some(account_id) = arn.get(4) {
+            attributes.push(KeyValue::new("cloud.account.id", (*account_id).to_owned()));
+        }
+        let mut span = self
+            .tracer
+            .span_builder(name)
+            .with_kind(SpanKind::Server)
+            .with_attributes(attributes)
+            .start_with_context(&self.tracer, &parent);
+
+        let fut = self.handler.call(event, ctx);
+        let provider = self.provider.clone();
+        Box::pin(async move {
+            let res = fut.await;
+            if let Err(e) = &res {
+                span.set_status(Status::error(format!("{e:?}")));
+            }
+            span.end();
+            // the execution environment may be frozen as soon as the function responds
+            for res in provider.force_flush() {
+                if let Err(e) = res {
+                    error!(message = "Failed to flush spans", e = %e);
+                }
+            }
+            res
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handler_fn,
+        init::{InitReport, InitializationType},
+        Config, Error,
+    };
+    use opentelemetry::Value;
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    const HEADER: &str = "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1";
+
+    fn context(trace_header: &str) -> Context {
+        Context {
+            request_id: "8476a536-e9f4-11e8-9739-2dfe598c3fcd".into(),
+            deadline: 1_542_409_706_888,
+            invoked_function_arn: "arn:aws:lambda:us-east-2:123456789012:function:custom-runtime".into(),
+            xray_trace_id: trace_header.into(),
+            client_context: None,
+            identity: None,
+            env_config: Config::default(),
+            init_report: None,
+        }
+    }
+
+    #[test]
+    fn converts_xray_headers() {
+        let parsed = span_context(HEADER).expect("failed to parse header");
+        assert_eq!(
+            parsed.trace_id(),
+            TraceId::from_hex("5759e988bd862e3fe1be46a994272793").unwrap()
+        );
+        assert_eq!(parsed.span_id(), SpanId::from_hex("53995c3f42cd8ad8").unwrap());
+        assert!(parsed.is_sampled());
+        assert!(parsed.is_remote());
+        assert_eq!(trace_header(&parsed), HEADER);
+
+        let unsampled = span_context("Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=0");
+        assert!(!unsampled.expect("failed to parse header").is_sampled());
+        assert!(span_context("Root=1-5759e988-bd862e3fe1be46a994272793").is_none());
+        assert!(span_context("Root=2-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8").is_none());
+    }
+
+    #[test]
+    fn propagates_xray_headers() {
+        let propagator = XRayPropagator::new();
+        let mut headers = HashMap::new();
+        headers.insert("x-amzn-trace-id".to_owned(), HEADER.to_owned());
+        let cx = propagator.extract(&headers);
+        assert_eq!(
+            cx.span().span_context().trace_id(),
+            TraceId::from_hex("5759e988bd862e3fe1be46a994272793").unwrap()
+        );
+
+        let mut injected = HashMap::new();
+        propagator.inject_context(&cx, &mut injected);
+        assert_eq!(injected.get("x-amzn-trace-id").map(String::as_str), Some(HEADER));
+        assert_eq!(propagator.fields().collect::<Vec<_>>(), vec!["x-amzn-trace-id"]);
+    }
+
+    #[tokio::test]
+    async fn traces_invocations_as_children_of_the_xray_trace() -> Result<(), Error> {
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder().with_simple_exporter(exporter.clone()).build();
+        let mut handler = traced(
+            handler_fn(|event: String, _: Context| async move {
+                if event == "fail" {
+                    Err("failed")
+                } else {
+                    Ok(event)
+                }
+            }),
+            provider,
+        );
+
+        // the first invocation of the environment carries the report of its initialization
+        let first = Context {
+            init_report: Some(InitReport {
+                init_duration: Duration::from_millis(120),
+                first_event_latency: Duration::from_millis(3),
+                initialization_type: InitializationType::OnDemand,
+            }),
+            ..context(HEADER)
+        };
+        handler.call("hello".to_owned(), first).await?;
+        handler.call("fail".to_owned(), context("")).await.unwrap_err();
+
+        // spans are flushed before the invocation completes
+        let spans = exporter.get_finished_spans()?;
+        assert_eq!(spans.len(), 2);
+        let span = &spans[0];
+        assert_eq!(span.name, "custom-runtime");
+        assert_eq!(span.span_kind, SpanKind::Server);
+        assert_eq!(
+            span.span_context.trace_id(),
+            TraceId::from_hex("5759e988bd862e3fe1be46a994272793").unwrap()
+        );
+        assert_eq!(span.parent_span_id, SpanId::from_hex("53995c3f42cd8ad8").unwrap());
+        let attributes: HashMap<_, _> = span
+            .attributes
+            .iter()
+            .map(|kv| (kv.key.as_str().to_owned(), kv.value.clone()))
+            .collect();
+        assert_eq!(
+            attributes.get("faas.invocation_id"),
+            Some(&Value::from("8476a536-e9f4-11e8-9739-2dfe598c3fcd"))
+        );
+        assert_eq!(attributes.get("faas.coldstart"), Some(&Value::from(true)));
+        assert_eq!(attributes.get("cloud.account.id"), Some(&Value::from("123456789012")));
+        assert_eq!(
+            attributes.get("cloud.resource_id"),
+            Some(&Value::from(
+                "arn:aws:lambda:us-east-2:123456789012:function:custom-runtime"
+            ))
+        );
+
+        // without a trace header, invocations start traces of their own
+        let span = &spans[1];
+        assert_eq!(span.parent_span_id, SpanId::INVALID);
+        assert_eq!(span.status, Status::error("\"failed\""));
+        assert!(span
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "faas.coldstart" && kv.value == Value::from(false)));
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+//! Structured records of panics.
+//!
+//! The default panic hook prints a bare message, over several lines when a backtrace is
+//! captured, which the function's logs split into as many events without telling which
+//! invocation they belong to. The hook that [`install_hook`] installs writes one JSON record
+//! per panic to stderr first, with the panic's message and location, the request ID of the
+//! invocation being handled, and the backtrace when `RUST_BACKTRACE` enables them:
+//!
+//! ```json
+//! {"level":"ERROR","message":"index out of bounds","location":"src/main.rs:12:5","requestId":"8476a536-e9f4-11e8-9739-2dfe598c3fcd"}
+//! ```
+//!
+//! The runtime then reports the panic to the Runtime API as the invocation's error.
+use crate::types::Diagnostic;
+use serde_json::{json, Value};
+use std::{
+    any::Any,
+    backtrace::{Backtrace, BacktraceStatus},
+    cell::RefCell,
+    future::Future,
+    panic::{self, PanicHookInfo},
+    pin::Pin,
+    sync::Once,
+    task::{self, Poll},
+};
+
+/// The error type of invocations whose handler panicked
+const PANIC_ERROR_TYPE: &str = "Panic";
+
+thread_local! {
+    /// The request ID of the invocation whose handler is being polled on this thread
+    static REQUEST_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Installs the structured panic hook, chained to the hook installed before it.
+///
+/// Installing it more than once has no effect.
+pub fn install_hook() {
+    static INSTALLED: Once = Once::new();
+    INSTALLED.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            eprintln!("{}", record(info));
+            previous(info);
+        }));
+    });
+}
+
+/// The record of the panic of `info`
+fn record(info: &PanicHookInfo<'_>) -> Value {
+    let mut record = json!({
+        "level": "ERROR",
+        "message": message(info.payload()),
+    });
+    if let Some(location) = info.location() {
+        record["location"] = location.to_string().into();
+    }
+    if let Some(request_id) = REQUEST_ID.with(|request_id| request_id.borrow().clone()) {
+        record["requestId"] = request_id.into();
+    }
+    let backtrace = Backtrace::capture();
+    if backtrace.status() == BacktraceStatus::Captured {
+        record["backtrace"] = backtrace.to_string().into();
+    }
+    record
+}
+
+/// The message a panic was started with
+pub(crate) fn message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| (*message).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_owned())
+}
+
+/// The diagnostic the Runtime API is sent for a panic with `payload`
+pub(crate) fn diagnostic(payload: &(dyn Any + Send)) -> Diagnostic {
+    Diagnostic {
+        error_type: PANIC_ERROR_TYPE.to_owned(),
+        error_message: message(payload),
+    }
+}
+
+/// A future polled with the request ID of its invocation set for the panic hook
+pub(crate) struct InInvocation<F> {
+    request_id: String,
+    fut: Pin<Box<F>>,
+}
+
+impl<F> InInvocation<F> {
+    pub(crate) fn new(request_id: &str, fut: F) -> Self {
+        Self {
+            request_id: request_id.to_owned(),
+            fut: Box::pin(fut),
+        }
+    }
+}
+
+impl<F> Future for InInvocation<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        /// Restores the request ID set before, including when the future unwinds
+        struct Restore(Option<String>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                REQUEST_ID.with(|request_id| *request_id.borrow_mut() = previous);
+            }
+        }
+
+        let previous = REQUEST_ID.with(|request_id| request_id.replace(Some(self.request_id.clone())));
+        let _restore = Restore(previous);
+        self.fut.as_mut().poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler_fn, stub::Stub, Context, Error, Runtime};
+    use futures::future;
+    use std::{env, time::Duration};
+
+    #[test]
+    fn runtimes_log_panics_of_handlers() {
+        // the hook writes to the stderr of the process, which only a parent process can read
+        let output = std::process::Command::new(env::current_exe().unwrap())
+            .args([
+                "panics::tests::panicking_runtime",
+                "--exact",
+                "--ignored",
+                "--nocapture",
+            ])
+            .output()
+            .expect("failed to run the panicking runtime");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{}", stderr);
+
+        let records: Vec<Value> = stderr
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .collect();
+        assert_eq!(records.len(), 1, "{stderr}");
+        let record = &records[0];
+        assert_eq!(record["level"], "ERROR");
+        assert_eq!(record["message"], "the order has no items");
+        assert_eq!(record["requestId"], "8476a536-e9f4-11e8-9739-2dfe598c3fcd");
+        let location = record["location"].as_str().unwrap();
+        assert!(location.starts_with("lambda/src/panics.rs:"), "{}", location);
+        // the previous hook, here the default one, still runs
+        assert!(stderr.contains("panicked at"), "{}", stderr);
+    }
+
+    #[tokio::test]
+    #[ignore = "run by runtimes_log_panics_of_handlers, in a process of its own"]
+    async fn panicking_runtime() -> Result<(), Error> {
+        let stub = Stub::default().invoking(vec![json!({ "items": [] })]);
+        let addr = stub.start();
+        let runtime = Runtime::new()
+            .with_endpoint(addr.to_string())
+            .with_panic_hook()
+            .run(handler_fn(|_: Value, _: Context| {
+                future::lazy(|_| -> Result<Value, Error> { panic!("the order has no items") })
+            }));
+        tokio::pin!(runtime);
+        // the runtime keeps going, waiting for the next invocation, once it reported the panic
+        let report = loop {
+            tokio::select! {
+                res = &mut runtime => return res,
+                () = tokio::time::delay_for(Duration::from_millis(10)) => {}
+            }
+            let report = stub.received().into_iter().find(|req| req.path.ends_with("/error"));
+            if let Some(report) = report {
+                break report;
+            }
+        };
+        assert_eq!(
+            report.path,
+            "/2018-06-01/runtime/invocation/8476a536-e9f4-11e8-9739-2dfe598c3fcd/error"
+        );
+        assert_eq!(
+            report.body,
+            json!({ "errorType": "Panic", "errorMessage": "the order has no items" })
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,411 @@
+//! Recording invocations to local files, and replaying them against handlers.
+//!
+//! A [`Runtime`](crate::Runtime) configured with a [`Recorder`], through
+//! [`with_recorder`](crate::Runtime::with_recorder) or by pointing `LAMBDA_RUNTIME_RECORD_DIR`
+//! at a directory, writes every invocation it handles to a file of its own there: one JSON
+//! envelope holding the event as it was received, the request ID, function ARN, deadline and
+//! trace ID it came with, and the response or error sent for it.
+//!
+//! ```json
+//! {"requestId":"8476a536-e9f4-11e8-9739-2dfe598c3fcd","invokedFunctionArn":"arn:aws:lambda:us-east-2:123456789012:function:custom-runtime","deadlineMs":1542409706888,"traceId":"Root=1-5bef4de7-ad49b0e87f6ef6c87fc2e700;Parent=9a9197af755a6419","event":"{\"divisor\":4}","outcome":{"type":"response","body":"25"}}
+//! ```
+//!
+//! Recording stops once [`max_invocations`](Recorder::max_invocations) were recorded, and skips
+//! envelopes which would take the files past [`max_bytes`](Recorder::max_bytes), so that it
+//! can't fill `/tmp`. Failures to record are logged, never failing invocations.
+//!
+//! [`replay_invocation`] calls a handler with a recorded event, to reproduce the invocation in a
+//! test:
+//!
+//! ```no_run
+//! use lambda::{handler_fn, record::{replay_invocation, Recording}, Context};
+//! use serde_json::Value;
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! # async fn test() -> Result<(), Error> {
+//! let path = "recorded/000001-8476a536-e9f4-11e8-9739-2dfe598c3fcd.json";
+//! let replayed = replay_invocation(path, handler_fn(func)).await?;
+//! assert_eq!(replayed, Recording::load(path)?.outcome);
+//! # Ok(())
+//! # }
+//!
+//! async fn func(event: Value, _: Context) -> Result<Value, Error> {
+//!     Ok(event)
+//! }
+//! ```
+use crate::{requests::NextEventResponse, single::Single, Context, Error, Handler, RuntimeConfig};
+use bytes::Bytes;
+use http::Request;
+use hyper::Body;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+use tracing::error;
+
+/// The variable naming the directory to record invocations to, when the runtime is given no
+/// recorder
+const RECORD_DIR: &str = "LAMBDA_RUNTIME_RECORD_DIR";
+
+/// How many invocations are recorded unless configured otherwise
+const DEFAULT_MAX_INVOCATIONS: usize = 100;
+
+/// How many bytes of envelopes are written unless configured otherwise, an eighth of the
+/// `/tmp` of a function
+const DEFAULT_MAX_BYTES: u64 = 64 << 20;
+
+/// An invocation, as recorded to its file.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recording {
+    /// The request ID of the invocation.
+    pub request_id: String,
+    /// The ARN of the function invoked.
+    pub invoked_function_arn: String,
+    /// The deadline of the invocation, in milliseconds since the epoch.
+    pub deadline_ms: u64,
+    /// The X-Ray trace ID of the invocation.
+    pub trace_id: String,
+    /// The event, as received from the Runtime API.
+    #[serde(with = "payload")]
+    pub event: Bytes,
+    /// The result sent to the Runtime API.
+    pub outcome: RecordedOutcome,
+}
+
+impl Recording {
+    /// Reads the recording at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read, or doesn't hold a recording.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// The context the invocation was handled with, bar the configuration of the function.
+    #[must_use]
+    pub fn context(&self) -> Context {
+        Context {
+            request_id: self.request_id.clone(),
+            deadline: self.deadline_ms,
+            invoked_function_arn: self.invoked_function_arn.clone(),
+            xray_trace_id: self.trace_id.clone(),
+            ..Context::default()
+        }
+    }
+}
+
+/// The result of an invocation, as sent to the Runtime API.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RecordedOutcome {
+    /// The handler succeeded with a response of `body`.
+    Response {
+        /// The serialized response.
+        #[serde(with = "payload")]
+        body: Bytes,
+    },
+    /// The handler failed or panicked, and `error` was reported.
+    Error {
+        /// The error reported, with its `errorType` and `errorMessage`.
+        error: Value,
+    },
+}
+
+/// Writes invocations to files in a directory, one each.
+#[derive(Debug)]
+pub struct Recorder {
+    dir: PathBuf,
+    max_invocations: usize,
+    max_bytes: u64,
+    recorded: usize,
+    written: u64,
+}
+
+impl Recorder {
+    /// Creates a recorder writing to `dir`, created if it doesn't exist, up to 100 invocations
+    /// and 64 MiB.
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_invocations: DEFAULT_MAX_INVOCATIONS,
+            max_bytes: DEFAULT_MAX_BYTES,
+            recorded: 0,
+            written: 0,
+        }
+    }
+
+    /// Creates a recorder writing to the directory `LAMBDA_RUNTIME_RECORD_DIR` names, if it is
+    /// set.
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        env::var_os(RECORD_DIR).filter(|dir| !dir.is_empty()).map(Self::new)
+    }
+
+    /// Stops recording once `max` invocations were recorded.
+    #[must_use]
+    pub const fn max_invocations(mut self, max: usize) -> Self {
+        self.max_invocations = max;
+        self
+    }
+
+    /// Skips the envelopes which would take the recorded files past `max` bytes.
+    #[must_use]
+    pub const fn max_bytes(mut self, max: u64) -> Self {
+        self.max_bytes = max;
+        self
+    }
+
+    /// Records the invocation `ctx` of `event`, whose result `req` is about to be sent, and
+    /// returns `req` to send
+    pub(crate) async fn record(
+        &mut self,
+        ctx: &Context,
+        event: Bytes,
+        failed: bool,
+        req: Request<Body>,
+    ) -> Result<Request<Body>, Error> {
+        if self.recorded >= self.max_invocations {
+            return Ok(req);
+        }
+        let (parts, body) = req.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        let outcome = if failed {
+            RecordedOutcome::Error {
+                error: serde_json::from_slice(&body)?,
+            }
+        } else {
+            RecordedOutcome::Response { body: body.clone() }
+        };
+        let recording = Recording {
+            request_id: ctx.request_id.clone(),
+            invoked_function_arn: ctx.invoked_function_arn.clone(),
+            deadline_ms: ctx.deadline,
+            trace_id: ctx.xray_trace_id.clone(),
+            event,
+            outcome,
+        };
+        if let Err(e) = self.write(&recording) {
+            error!(message = "Failed to record the invocation", e = %e);
+        }
+        Ok(Request::from_parts(parts, Body::from(body)))
+    }
+
+    fn write(&mut self, recording: &Recording) -> Result<(), Error> {
+        let envelope = serde_json::to_vec(recording)?;
+        if self.written + envelope.len() as u64 > self.max_bytes {
+            return Err(format!(
+                "recording {} would exceed {} bytes",
+                recording.request_id, self.max_bytes
+            )
+            .into());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let name = format!("{:06}-{}.json", self.recorded + 1, recording.request_id);
+        fs::write(self.dir.join(name), &envelope)?;
+        self.recorded += 1;
+        self.written += envelope.len() as u64;
+        Ok(())
+    }
+}
+
+/// Calls `handler` with the event recorded at `path`, in the context it was recorded with,
+/// and returns the outcome the runtime would send for it.
+///
+/// The invocation goes through the same loop as in Lambda, against a stand-in for the Runtime
+/// API serving the recorded invocation alone, like local invocations with the `local` feature.
+///
+/// # Errors
+///
+/// Fails if the recording can't be loaded, or its event can't be deserialized.
+pub async fn replay_invocation<A, B, F>(path: impl AsRef<Path>, handler: F) -> Result<RecordedOutcome, Error>
+where
+    F: Handler<A, B>,
+    <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+    A: for<'de> Deserialize<'de>,
+    B: Serialize,
+{
+    let mut handler = handler;
+    let recording = Recording::load(path)?;
+    let api = Single::new(NextEventResponse {
+        request_id: &recording.request_id,
+        deadline: recording.deadline_ms,
+        arn: &recording.invoked_function_arn,
+        trace_id: &recording.trace_id,
+        body: recording.event.to_vec(),
+    })?;
+    api.invoke(&mut handler, RuntimeConfig::new()).await
+}
+
+/// Payloads, as text when they are UTF-8 and in base64 otherwise
+mod payload {
+    use bytes::Bytes;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Encoded {
+        Text(String),
+        Binary { base64: String },
+    }
+
+    pub fn serialize<S>(payload: &Bytes, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match std::str::from_utf8(payload) {
+            Ok(text) => Encoded::Text(text.to_owned()).serialize(serializer),
+            Err(_) => Encoded::Binary {
+                base64: base64::encode(payload),
+            }
+            .serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Bytes, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Encoded::deserialize(deserializer)? {
+            Encoded::Text(text) => Ok(Bytes::from(text)),
+            Encoded::Binary { base64 } => base64::decode(base64).map(Bytes::from).map_err(de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handler_fn,
+        stub::{Received, Stub},
+        Runtime,
+    };
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn recording(event: &'static [u8]) -> Recording {
+        Recording {
+            request_id: "8476a536-e9f4-11e8-9739-2dfe598c3fcd".to_owned(),
+            invoked_function_arn: "arn:aws:lambda:us-east-2:123456789012:function:custom-runtime".to_owned(),
+            deadline_ms: 1_542_409_706_888,
+            trace_id: "Root=1-5bef4de7-ad49b0e87f6ef6c87fc2e700;Parent=9a9197af755a6419".to_owned(),
+            event: Bytes::from_static(event),
+            outcome: RecordedOutcome::Error {
+                error: serde_json::json!({"errorType": "Panic", "errorMessage": "no divisor"}),
+            },
+        }
+    }
+
+    #[test]
+    fn keeps_payloads_byte_for_byte() -> Result<(), Error> {
+        for event in [&b"{\"divisor\": 4}"[..], &[0xff, 0x00, 0x7b][..]] {
+            let recorded = recording(event);
+            let envelope = serde_json::to_vec(&recorded)?;
+            assert_eq!(serde_json::from_slice::<Recording>(&envelope)?, recorded);
+        }
+        let envelope = serde_json::to_value(recording(b"{\"divisor\": 4}"))?;
+        assert_eq!(envelope["event"], "{\"divisor\": 4}");
+        let envelope = serde_json::to_value(recording(&[0xff, 0x00, 0x7b]))?;
+        assert_eq!(envelope["event"]["base64"], "/wB7");
+        Ok(())
+    }
+
+    #[test]
+    fn stops_at_the_caps() -> Result<(), Error> {
+        let dir = env::temp_dir().join(format!("lambda-record-caps-{}", std::process::id()));
+        let envelope = serde_json::to_vec(&recording(b"{}"))?.len() as u64;
+
+        let mut recorder = Recorder::new(&dir).max_bytes(envelope * 2 + 1);
+        for _ in 0..3 {
+            let _ = recorder.write(&recording(b"{}"));
+        }
+        assert_eq!(fs::read_dir(&dir)?.count(), 2);
+        fs::remove_dir_all(&dir)?;
+
+        let mut recorder = Recorder::new(&dir).max_invocations(1);
+        for _ in 0..3 {
+            let req = Request::new(Body::from("25"));
+            let _ = futures::executor::block_on(recorder.record(&Context::default(), Bytes::new(), false, req))?;
+        }
+        assert_eq!(fs::read_dir(&dir)?.count(), 1);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn runtimes_record_invocations_for_replay() -> Result<(), Error> {
+        use crate::record::{replay_invocation, RecordedOutcome, Recorder};
+
+        let stub = Stub::default().invoking(vec![json!({ "items": 3 }), json!({ "items": 0 })]);
+        let addr = stub.start();
+        let dir = std::env::temp_dir().join(format!("lambda-record-{}", std::process::id()));
+        let handler = |event: Value, _: Context| async move {
+            match event["items"].as_u64() {
+                Some(0) => Err(Error::from("the order has no items")),
+                items => Ok(json!({ "accepted": items })),
+            }
+        };
+        let runtime = Runtime::new()
+            .with_endpoint(addr.to_string())
+            .with_recorder(Recorder::new(&dir))
+            .run(handler_fn(handler));
+        tokio::pin!(runtime);
+        let responded = |received: &[Received]| {
+            received
+                .iter()
+                .filter(|received| received.path.starts_with("/2018-06-01/runtime/invocation/8476a536"))
+                .count()
+        };
+        let mut received = Vec::new();
+        while responded(&received) < 2 {
+            tokio::select! {
+                res = &mut runtime => return res,
+                () = tokio::time::delay_for(Duration::from_millis(10)) => received.extend(stub.received()),
+            }
+        }
+
+        let mut paths: Vec<_> = std::fs::read_dir(&dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        paths.sort();
+        assert_eq!(paths.len(), 2, "{paths:?}");
+        let recorded: Vec<_> = paths
+            .iter()
+            .map(crate::record::Recording::load)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(recorded[0].event, r#"{"items":3}"#);
+        assert_eq!(recorded[0].request_id, "8476a536-e9f4-11e8-9739-2dfe598c3fcd");
+        assert_eq!(recorded[0].deadline_ms, 1_542_409_706_888);
+        assert_eq!(
+            recorded[0].outcome,
+            RecordedOutcome::Response {
+                body: r#"{"accepted":3}"#.into()
+            }
+        );
+        // the error recorded is the one sent
+        let sent = received
+            .iter()
+            .find(|received| received.path.ends_with("/error"))
+            .unwrap();
+        assert_eq!(
+            recorded[1].outcome,
+            RecordedOutcome::Error {
+                error: sent.body.clone()
+            }
+        );
+
+        for (path, recorded) in paths.iter().zip(&recorded) {
+            assert_eq!(replay_invocation(path, handler_fn(handler)).await?, recorded.outcome);
+        }
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,8 +1,11 @@
-use crate::{types::Diagnostic, Error};
+use crate::{
+    types::{Diagnostic, ErrorDiagnostic},
+    Error,
+};
 use http::{Method, Request, Response, Uri};
 use hyper::Body;
 use serde::Serialize;
-use std::str::FromStr;
+use std::{mem, str::FromStr};
 
 pub(crate) trait IntoRequest {
     fn into_req(self) -> Result<Request<Body>, Error>;
@@ -12,6 +15,48 @@ pub(crate) trait IntoResponse {
     fn into_rsp(self) -> Result<Response<Body>, Error>;
 }
 
+/// The header error reports name the type of their error in, besides their body
+pub const FUNCTION_ERROR_TYPE_HEADER: &str = "lambda-runtime-function-error-type";
+
+/// The error type of reports whose error has no name a header can carry
+const UNHANDLED: &str = "Unhandled";
+
+/// A diagnostic whose type is named in the header of its report as well
+pub trait ErrorType {
+    fn error_type(&self) -> &str;
+}
+
+impl ErrorType for Diagnostic {
+    fn error_type(&self) -> &str {
+        &self.error_type
+    }
+}
+
+impl<E> ErrorType for ErrorDiagnostic<'_, E> {
+    fn error_type(&self) -> &str {
+        self.error_type()
+    }
+}
+
+/// The type of an error in the `Category.Reason` shape the Runtime API documents for the
+/// header: the segments of the path of a Rust type, like `orders::OrderError`, are joined with
+/// dots, and its generic arguments and reference sigils dropped. Types which still aren't made
+/// of letters, digits, dots, underscores and dashes are sent as `Unhandled`, rather than
+/// mangled into a name nobody wrote.
+pub fn header_error_type(error_type: &str) -> String {
+    let path = error_type.split('<').next().unwrap_or_default().trim_start_matches('&');
+    let name = path.replace("::", ".");
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    {
+        name
+    } else {
+        UNHANDLED.to_owned()
+    }
+}
+
 // /runtime/invocation/next
 #[derive(Debug, PartialEq)]
 pub(crate) struct NextEventRequest;
@@ -93,27 +138,51 @@ fn test_event_completion_request() {
 }
 
 // /runtime/invocation/{AwsRequestId}/error
-pub(crate) struct EventErrorRequest<'a> {
+pub(crate) struct EventErrorRequest<'a, D = Diagnostic> {
     pub(crate) request_id: &'a str,
-    pub(crate) diagnostic: Diagnostic,
+    pub(crate) diagnostic: D,
 }
 
-impl<'a> IntoRequest for EventErrorRequest<'a> {
-    fn into_req(self) -> Result<Request<Body>, Error> {
+impl<D> EventErrorRequest<'_, D>
+where
+    D: Serialize + ErrorType,
+{
+    /// Makes the request like `into_req`, serializing the diagnostic into `buf` and handing its
+    /// bytes to the body without copying them. `buf` is left empty, with room for a report as
+    /// large as the largest so far, so that the next one is serialized without growing it
+    pub(crate) fn into_req_with(self, buf: &mut Vec<u8>) -> Result<Request<Body>, Error> {
+        buf.clear();
+        serde_json::to_writer(&mut *buf, &self.diagnostic)?;
+        let capacity = buf.capacity();
+        self.request(Body::from(mem::replace(buf, Vec::with_capacity(capacity))))
+    }
+
+    fn request(&self, body: Body) -> Result<Request<Body>, Error> {
         let uri = format!("/2018-06-01/runtime/invocation/{}/error", self.request_id);
         let uri = Uri::from_str(&uri)?;
-        let body = serde_json::to_vec(&self.diagnostic)?;
-        let body = Body::from(body);
 
         let req = Request::builder()
             .method(Method::POST)
             .uri(uri)
-            .header("lambda-runtime-function-error-type", "unhandled")
+            .header(
+                FUNCTION_ERROR_TYPE_HEADER,
+                header_error_type(self.diagnostic.error_type()),
+            )
             .body(body)?;
         Ok(req)
     }
 }
 
+impl<'a, D> IntoRequest for EventErrorRequest<'a, D>
+where
+    D: Serialize + ErrorType,
+{
+    fn into_req(self) -> Result<Request<Body>, Error> {
+        let body = serde_json::to_vec(&self.diagnostic)?;
+        self.request(Body::from(body))
+    }
+}
+
 #[test]
 fn test_event_error_request() {
     let req = EventErrorRequest {
@@ -127,30 +196,153 @@ fn test_event_error_request() {
     let expected = Uri::from_static("/2018-06-01/runtime/invocation/id/error");
     assert_eq!(req.method(), Method::POST);
     assert_eq!(req.uri(), &expected);
+    assert_eq!(req.headers()[FUNCTION_ERROR_TYPE_HEADER], "InvalidEventDataError");
+}
+
+#[tokio::test]
+async fn event_error_requests_serialize_into_handed_over_buffers() {
+    let diagnostic = |message: &str| Diagnostic {
+        error_type: "InvalidEventDataError".to_string(),
+        error_message: message.to_string(),
+    };
+    let mut buf = Vec::new();
+    for message in &["Error parsing event data: expected value at line 1 column 1", "EOF"] {
+        let req = EventErrorRequest {
+            request_id: "id",
+            diagnostic: diagnostic(message),
+        };
+        let reused = req.into_req_with(&mut buf).unwrap();
+        let req = EventErrorRequest {
+            request_id: "id",
+            diagnostic: diagnostic(message),
+        };
+        let fresh = req.into_req().unwrap();
+        assert_eq!(reused.uri(), fresh.uri());
+        assert_eq!(reused.headers(), fresh.headers());
+        let reused = hyper::body::to_bytes(reused.into_body()).await.unwrap();
+        let fresh = hyper::body::to_bytes(fresh.into_body()).await.unwrap();
+        assert_eq!(reused, fresh);
+        assert!(buf.is_empty());
+    }
+    // the buffer kept room for the first, larger, report
+    assert!(buf.capacity() > 70, "{}", buf.capacity());
 }
 
 // /runtime/init/error
-struct InitErrorRequest;
+pub struct InitErrorRequest<D = Diagnostic> {
+    pub diagnostic: D,
+}
 
-impl IntoRequest for InitErrorRequest {
+impl<D> IntoRequest for InitErrorRequest<D>
+where
+    D: Serialize + ErrorType,
+{
     fn into_req(self) -> Result<Request<Body>, Error> {
         let uri = format!("/2018-06-01/runtime/init/error");
         let uri = Uri::from_str(&uri)?;
+        let body = serde_json::to_vec(&self.diagnostic)?;
+        let body = Body::from(body);
 
         let req = Request::builder()
             .method(Method::POST)
             .uri(uri)
-            .header("lambda-runtime-function-error-type", "unhandled")
-            .body(Body::empty())?;
+            .header(
+                FUNCTION_ERROR_TYPE_HEADER,
+                header_error_type(self.diagnostic.error_type()),
+            )
+            .body(body)?;
         Ok(req)
     }
 }
 
 #[test]
 fn test_init_error_request() {
-    let req = InitErrorRequest;
+    let req = InitErrorRequest {
+        diagnostic: Diagnostic {
+            error_type: "Runtime.InvalidConfiguration".to_string(),
+            error_message: "AWS_LAMBDA_FUNCTION_NAME is not set".to_string(),
+        },
+    };
     let req = req.into_req().unwrap();
     let expected = Uri::from_static("/2018-06-01/runtime/init/error");
     assert_eq!(req.method(), Method::POST);
     assert_eq!(req.uri(), &expected);
+    assert_eq!(
+        req.headers()[FUNCTION_ERROR_TYPE_HEADER],
+        "Runtime.InvalidConfiguration"
+    );
+}
+
+#[test]
+fn header_error_types_take_the_category_reason_shape() {
+    for (error_type, header) in [
+        ("Runtime.InvalidConfiguration", "Runtime.InvalidConfiguration"),
+        ("Panic", "Panic"),
+        ("orders::OrderError", "orders.OrderError"),
+        (
+            "alloc::boxed::Box<dyn core::error::Error + Send + Sync>",
+            "alloc.boxed.Box",
+        ),
+        ("Order refused: \"no stock\"", "Unhandled"),
+        ("Runtime.Exit\r\nX-Injected: 1", "Unhandled"),
+        ("&str", "str"),
+        ("<()>", "Unhandled"),
+    ] {
+        assert_eq!(header_error_type(error_type), header);
+    }
+}
+
+#[tokio::test]
+async fn runtimes_name_error_types_in_headers() -> Result<(), Error> {
+    use crate::{handler_fn, stub::Stub, Context, Runtime};
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    for omit in [false, true] {
+        let stub = Stub::default().invoking(vec![json!({ "outcome": "refused" }), json!({ "outcome": "panic" })]);
+        let addr = stub.start();
+        let runtime = Runtime::new().with_endpoint(addr.to_string());
+        let runtime = if omit {
+            runtime.without_error_type_header()
+        } else {
+            runtime
+        };
+        let runtime = runtime.run(handler_fn(|event: Value, _: Context| async move {
+            match event["outcome"].as_str() {
+                Some("refused") => Err(crate::HandlerError::new("no stock").with_error_type("Order.Refused")),
+                Some("panic") => panic!("the order has no items"),
+                _ => Ok(json!("ok")),
+            }
+        }));
+        tokio::pin!(runtime);
+        let mut reports = Vec::new();
+        while reports.len() < 2 {
+            tokio::select! {
+                res = &mut runtime => return res,
+                () = tokio::time::delay_for(Duration::from_millis(10)) => {}
+            }
+            reports.extend(stub.received().into_iter().filter(|req| req.path.ends_with("/error")));
+        }
+
+        // the bodies are the same with or without the header, backtraces aside
+        assert_eq!(reports[0].body["errorType"], "Order.Refused");
+        assert!(reports[0].body["errorMessage"]
+            .as_str()
+            .unwrap()
+            .starts_with("no stock"));
+        assert_eq!(
+            reports[1].body,
+            json!({ "errorType": "Panic", "errorMessage": "the order has no items" })
+        );
+        let headers: Vec<_> = reports
+            .iter()
+            .map(|report| report.function_error_type.as_deref())
+            .collect();
+        if omit {
+            assert_eq!(headers, [None, None]);
+        } else {
+            assert_eq!(headers, [Some("Order.Refused"), Some("Panic")]);
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,350 @@
+//! A function's event loop run alongside an internal extension of the same process.
+//!
+//! A [`LambdaService`] owns both halves: the event loop polling the Runtime API, and an internal
+//! extension, registered with the Extensions API, subscribing to logs or telemetry and running
+//! closures on shutdown. Running it sequences what Lambda expects of them. The extension registers
+//! and subscribes, its listener being up beforehand, before the event loop asks for the first
+//! event, and a shutdown stops both once the closures ran.
+//!
+//! ```no_run
+//! use lambda::{
+//!     extensions::{telemetry::TelemetrySubscription, LogType},
+//!     handler_fn,
+//!     service::LambdaService,
+//!     Context,
+//! };
+//! use serde_json::Value;
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     LambdaService::new(handler_fn(func))
+//!         .with_telemetry(TelemetrySubscription::new(&[LogType::Platform]), |batch| {
+//!             println!("received {} telemetry records", batch.len())
+//!         })
+//!         .on_shutdown(|remaining| println!("flushing with {:?} left", remaining))
+//!         .run()
+//!         .await
+//! }
+//!
+//! async fn func(event: Value, _: Context) -> Result<Value, Error> {
+//!     Ok(event)
+//! }
+//! ```
+use crate::{
+    extensions::{telemetry::TelemetryEvent, telemetry::TelemetrySubscription, LogEvent, LogsSubscription},
+    metrics::MetricsSink,
+    record::Recorder,
+    Bytes, Error, Handler, Runtime,
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::Duration};
+
+/// What a [`LambdaService`] does when its extension fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnExtensionFailure {
+    /// Log the failure and keep processing events without the extension, the default.
+    Degrade,
+    /// Stop processing events, returning the failure from [`run`](LambdaService::run).
+    Abort,
+}
+
+/// A handler's event loop and the internal extension serving it, configured before they start.
+///
+/// The event loop is a [`Runtime`]'s, configured with the same builders. See the
+/// [module documentation](self) for an example.
+pub struct LambdaService<F> {
+    handler: F,
+    runtime: Runtime,
+}
+
+impl<F> LambdaService<F> {
+    /// Creates a service handling events with `handler`, talking to the APIs
+    /// `AWS_LAMBDA_RUNTIME_API` points to.
+    #[must_use]
+    pub fn new(handler: F) -> Self {
+        Self {
+            handler,
+            runtime: Runtime::new(),
+        }
+    }
+
+    /// Talks to the APIs at `endpoint`, like `127.0.0.1:9001`, instead.
+    #[must_use]
+    pub fn with_endpoint(self, endpoint: impl Into<String>) -> Self {
+        Self {
+            runtime: self.runtime.with_endpoint(endpoint),
+            ..self
+        }
+    }
+
+    /// Subscribes to logs with `subscription`, calling `sink` with every batch received.
+    ///
+    /// `sink` runs on the extension's thread, so that it is called however busy the handler
+    /// keeps the event loop.
+    #[must_use]
+    pub fn with_logs<S>(mut self, subscription: LogsSubscription, sink: S) -> Self
+    where
+        S: FnMut(Vec<LogEvent>) + Send + 'static,
+    {
+        self.runtime.internal.logs = Some((subscription, Box::new(sink)));
+        self
+    }
+
+    /// Subscribes to telemetry with `subscription`, calling `sink` with every batch received.
+    ///
+    /// `sink` runs on the extension's thread, so that it is called however busy the handler
+    /// keeps the event loop.
+    #[must_use]
+    pub fn with_telemetry<S>(mut self, subscription: TelemetrySubscription, sink: S) -> Self
+    where
+        S: FnMut(Vec<TelemetryEvent>) + Send + 'static,
+    {
+        self.runtime.internal.telemetry = Some((subscription, Box::new(sink)));
+        self
+    }
+
+    /// Runs `cleanup` with the time left before the execution environment is reclaimed once it
+    /// shuts down, after the closures registered before it.
+    #[must_use]
+    pub fn on_shutdown<C>(self, cleanup: C) -> Self
+    where
+        C: FnOnce(Duration) + Send + 'static,
+    {
+        Self {
+            runtime: self.runtime.on_shutdown(cleanup),
+            ..self
+        }
+    }
+
+    /// Sets what happens when the extension fails to register, to subscribe or to receive its
+    /// events.
+    #[must_use]
+    pub fn on_extension_failure(mut self, on_extension_failure: OnExtensionFailure) -> Self {
+        self.runtime.internal.abort_on_failure = on_extension_failure == OnExtensionFailure::Abort;
+        self
+    }
+
+    /// Installs the [structured panic hook](crate::panics::install_hook) when the service
+    /// starts, so that panics of the handler are logged as single records naming their
+    /// invocation.
+    #[must_use]
+    pub fn with_panic_hook(self) -> Self {
+        Self {
+            runtime: self.runtime.with_panic_hook(),
+            ..self
+        }
+    }
+
+    /// Feeds `sink` the [measurements](crate::metrics::names) of every invocation, after the
+    /// sinks registered before it.
+    #[must_use]
+    pub fn with_metrics(self, sink: impl MetricsSink + 'static) -> Self {
+        Self {
+            runtime: self.runtime.with_metrics(sink),
+            ..self
+        }
+    }
+
+    /// Records every invocation with `recorder`, like
+    /// [`Runtime::with_recorder`] does.
+    #[must_use]
+    pub fn with_recorder(self, recorder: Recorder) -> Self {
+        Self {
+            runtime: self.runtime.with_recorder(recorder),
+            ..self
+        }
+    }
+
+    /// Leaves the `Lambda-Runtime-Function-Error-Type` header out of the reports of failed
+    /// invocations and initializations, which then name the type of their error in their body
+    /// only.
+    #[must_use]
+    pub fn without_error_type_header(self) -> Self {
+        Self {
+            runtime: self.runtime.without_error_type_header(),
+            ..self
+        }
+    }
+
+    /// Fails the invocations of events larger than `max_event_bytes`, like
+    /// [`Runtime::with_max_event_bytes`] does.
+    #[must_use]
+    pub fn with_max_event_bytes(self, max_event_bytes: Option<usize>) -> Self {
+        Self {
+            runtime: self.runtime.with_max_event_bytes(max_event_bytes),
+            ..self
+        }
+    }
+
+    /// Starts the extension, then processes events until the execution environment shuts down.
+    ///
+    /// The extension registers and subscribes first, so that it is done when the event loop
+    /// asks for the first event, which ends initialization. On shutdown, the shutdown closures
+    /// run and both halves stop. The first failure of either half stops both, unless the
+    /// extension's failures [degrade](OnExtensionFailure::Degrade) the service instead.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the configuration can't be read from the environment or the Runtime API can't
+    /// be reached, or with the extension's failure when failures abort the service.
+    pub async fn run<A, B>(self) -> Result<(), Error>
+    where
+        F: Handler<A, B>,
+        <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+        A: for<'de> Deserialize<'de>,
+        B: Serialize,
+    {
+        self.runtime.run(self.handler).await
+    }
+
+    /// Starts the service like [`run`](LambdaService::run), calling the handler with the body
+    /// of every event as it was received, like [`Runtime::run_raw`] does.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`run`](LambdaService::run).
+    pub async fn run_raw<B>(self) -> Result<(), Error>
+    where
+        F: Handler<Bytes, B>,
+        <F as Handler<Bytes, B>>::Error: fmt::Debug + 'static,
+        B: Serialize,
+    {
+        self.runtime.run_raw(self.handler).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        extensions::{
+            telemetry::{PlatformEvent, TelemetryEvent, TelemetryRecord},
+            LogType,
+        },
+        handler_fn,
+        stub::{Received, Stub},
+        Context,
+    };
+    use http::Request;
+    use hyper::Body;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn services_run_invocations_alongside_telemetry() -> Result<(), Error> {
+        let stub = Stub::default().invoking(vec![json!({ "message": "hello" })]);
+        let addr = stub.start();
+
+        let (batch_tx, batch_rx) = tokio::sync::mpsc::unbounded_channel();
+        let batch_rx = Arc::new(tokio::sync::Mutex::new(batch_rx));
+        let posting = stub.clone();
+        let handler = handler_fn(move |event: Value, _: Context| {
+            let stub = posting.clone();
+            let batch_rx = batch_rx.clone();
+            async move {
+                // Lambda posts the telemetry of the invocation while it is handled
+                let destination = stub
+                    .received
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|req| req.path == "/2022-07-01/telemetry")
+                    .map(|req| req.body["destination"]["URI"].as_str().unwrap().to_owned())
+                    .expect("the extension didn't subscribe before the first invocation");
+                let batch = json!([{
+                    "time": "2022-10-12T00:00:15.165Z",
+                    "type": "platform.start",
+                    "record": { "requestId": "8476a536-e9f4-11e8-9739-2dfe598c3fcd" }
+                }]);
+                let req = Request::post(destination.as_str()).body(Body::from(batch.to_string()))?;
+                hyper::Client::new().request(req).await?;
+                let batch: Vec<TelemetryEvent> =
+                    batch_rx.lock().await.recv().await.expect("the sink dropped the batch");
+                assert!(matches!(
+                    batch[0].record,
+                    TelemetryRecord::Platform(PlatformEvent::Start(_))
+                ));
+                Ok::<_, Error>(event)
+            }
+        });
+        let service = LambdaService::new(handler)
+            .with_endpoint(addr.to_string())
+            .with_telemetry(
+                TelemetrySubscription::new(&[LogType::Platform])
+                    .with_host("127.0.0.1")
+                    .with_port(0),
+                move |batch| batch_tx.send(batch).unwrap(),
+            )
+            .on_extension_failure(OnExtensionFailure::Abort)
+            .run();
+        tokio::pin!(service);
+        // shutdowns are signalled to the process, see
+        // `extensions::tests::runtimes_run_cleanup_closures_on_shutdown`
+        let mut received = Vec::new();
+        while !received.iter().any(|req: &Received| req.path.ends_with("/response")) {
+            tokio::select! {
+                res = &mut service => return Err(format!("the service stopped: {res:?}").into()),
+                () = tokio::time::delay_for(Duration::from_millis(10)) => {}
+            }
+            received.extend(stub.received());
+        }
+        let paths: Vec<_> = received.iter().map(|req| req.path.as_str()).collect();
+        // registration and subscription are complete before the first event is asked for
+        assert_eq!(paths[..2], ["/2020-01-01/extension/register", "/2022-07-01/telemetry"]);
+        assert!(paths[2..].contains(&"/2018-06-01/runtime/invocation/next"));
+        assert!(received
+            .iter()
+            .any(|req| req.path.ends_with("/response") && req.body == json!({ "message": "hello" })));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn extension_failures_abort_or_degrade_services() -> Result<(), Error> {
+        let echo = || handler_fn(|event: Value, _: Context| async { Ok::<_, Error>(event) });
+
+        // an event the extension can't make sense of fails it
+        let stub = Stub::serving(vec![json!({ "eventType": "RESTART" })]).invoking(vec![json!({ "message": "hello" })]);
+        let e = LambdaService::new(echo())
+            .with_endpoint(stub.start().to_string())
+            .on_shutdown(|_| {})
+            .on_extension_failure(OnExtensionFailure::Abort)
+            .run()
+            .await
+            .expect_err("extension failures should abort the service");
+        assert!(e.to_string().contains("RESTART"), "{}", e);
+
+        // a port the stub already listens on fails the subscription
+        let stub = Stub::serving(vec![]).invoking(vec![json!({ "message": "hello" })]);
+        let addr = stub.start();
+        let taken = TelemetrySubscription::new(&[LogType::Platform]).with_port(addr.port());
+        let e = LambdaService::new(echo())
+            .with_endpoint(addr.to_string())
+            .with_telemetry(taken.clone(), |_| {})
+            .on_extension_failure(OnExtensionFailure::Abort)
+            .run()
+            .await
+            .expect_err("extension failures should abort the service");
+        let report = stub.received().pop().expect("no requests received");
+        assert_eq!(report.path, "/2020-01-01/extension/init/error");
+        assert_eq!(report.body["errorMessage"], e.to_string());
+
+        let stub = Stub::serving(vec![]).invoking(vec![json!({ "message": "hello" }), json!({ "message": "again" })]);
+        let addr = stub.start();
+        let service = LambdaService::new(echo())
+            .with_endpoint(addr.to_string())
+            .with_telemetry(taken.with_port(addr.port()), |_| {})
+            .run();
+        tokio::time::timeout(Duration::from_millis(200), service)
+            .await
+            .expect_err("extension failures shouldn't stop a degraded service");
+        let responses = stub
+            .received()
+            .into_iter()
+            .filter(|req| req.path.ends_with("/response"))
+            .count();
+        assert_eq!(responses, 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+//! A stand-in for the Runtime API serving a single invocation, which local invocations and
+//! replays of recordings go through the event loop against.
+use crate::{
+    incoming, json,
+    record::RecordedOutcome,
+    requests::{IntoResponse, NextEventResponse},
+    run_inner, Error, Handler, LambdaRuntimeApi, RuntimeConfig, Serialized,
+};
+use futures::future::BoxFuture;
+use http::{Request, Response, StatusCode};
+use hyper::Body;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::{Mutex, PoisonError},
+};
+
+/// A Runtime API serving a single invocation, keeping the outcome the runtime sends for it,
+/// then failing to serve more to end the loop
+pub struct Single {
+    invocation: Mutex<Option<Response<Body>>>,
+    outcome: Mutex<Option<RecordedOutcome>>,
+}
+
+impl Single {
+    /// Serves `invocation`, the invocation the Runtime API would send for it
+    pub fn new(invocation: NextEventResponse<'_>) -> Result<Self, Error> {
+        Ok(Self {
+            invocation: Mutex::new(Some(invocation.into_rsp()?)),
+            outcome: Mutex::new(None),
+        })
+    }
+
+    /// Runs the event loop with `handler` and `config` until the invocation is handled, and
+    /// returns the outcome sent for it
+    pub async fn invoke<A, B, F>(self, handler: &mut F, mut config: RuntimeConfig) -> Result<RecordedOutcome, Error>
+    where
+        F: Handler<A, B>,
+        <F as Handler<A, B>>::Error: fmt::Debug + 'static,
+        A: for<'de> Deserialize<'de>,
+        B: Serialize,
+    {
+        let res = run_inner(&self, incoming(&self), handler, json, &mut Serialized, &mut config).await;
+        let outcome = self.outcome.into_inner().unwrap_or_else(PoisonError::into_inner);
+        match (outcome, res) {
+            (Some(outcome), _) => Ok(outcome),
+            (None, Err(e)) => Err(e.into()),
+            (None, Ok(())) => Err("the invocation ended without an outcome".into()),
+        }
+    }
+}
+
+impl LambdaRuntimeApi for Single {
+    fn call(&self, req: Request<Body>) -> BoxFuture<'_, Result<Response<Body>, Error>> {
+        Box::pin(async move {
+            if req.uri().path() == "/2018-06-01/runtime/invocation/next" {
+                let invocation = self.invocation.lock().unwrap().take();
+                return invocation.ok_or_else(|| "the invocation was handled".into());
+            }
+            let failed = req.uri().path().ends_with("/error");
+            let body = hyper::body::to_bytes(req.into_body()).await?;
+            let outcome = if failed {
+                RecordedOutcome::Error {
+                    error: serde_json::from_slice(&body)?,
+                }
+            } else {
+                RecordedOutcome::Response { body }
+            };
+            *self.outcome.lock().unwrap() = Some(outcome);
+            Ok(Response::builder()
+                .status(StatusCode::ACCEPTED)
+                .body(Body::empty())
+                .expect("Unable to construct response"))
+        })
+    }
+}
@@ -0,0 +1,418 @@
+//! Streamed function responses over the Runtime API's
+//! [response streaming](https://docs.aws.amazon.com/lambda/latest/dg/runtimes-custom.html#runtimes-custom-response-streaming) support.
+//!
+//! A handler returning a [`StreamResponse`] has its body sent to the Runtime API as it is produced.
+//! When the body stream yields an error, the response is ended and the error is reported with the
+//! `Lambda-Runtime-Function-Error-Type` and `Lambda-Runtime-Function-Error-Body` trailers.
+//!
+//! ```no_run
+//! use bytes::Bytes;
+//! use futures::stream::{self, Stream};
+//! use lambda::{handler_fn, streaming::StreamResponse, Context};
+//! use serde_json::Value;
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     lambda::streaming::run(handler_fn(func)).await?;
+//!     Ok(())
+//! }
+//!
+//! async fn func(_: Value, _: Context) -> Result<StreamResponse<impl Stream<Item = Result<Bytes, Error>>>, Error> {
+//!     let chunks = stream::iter(vec![Ok(Bytes::from("hello ")), Ok(Bytes::from("world"))]);
+//!     Ok(StreamResponse::new("text/plain", chunks))
+//! }
+//! ```
+use crate::{
+    client::Client,
+    incoming, json,
+    requests::{header_error_type, EventErrorRequest, IntoRequest},
+    run_inner,
+    types::{Diagnostic, ErrorDiagnostic},
+    Error, Handler, ResponseSink, RuntimeConfig, RuntimeError,
+};
+use bytes::Bytes;
+use futures::{future::poll_fn, stream::Stream, StreamExt};
+use http::{header, HeaderValue, Request, Uri};
+use hyper::{client::HttpConnector, Body};
+use serde::Deserialize;
+use std::{convert::TryFrom, fmt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tower_service::Service;
+
+/// The header announcing a streamed response to the Runtime API
+const RESPONSE_MODE_HEADER: &str = "Lambda-Runtime-Function-Response-Mode";
+
+/// The trailer carrying the type of an error which ended a streamed response
+const ERROR_TYPE_TRAILER: &str = "Lambda-Runtime-Function-Error-Type";
+
+/// The trailer carrying the base64 encoded JSON diagnostic of an error which ended a streamed response
+const ERROR_BODY_TRAILER: &str = "Lambda-Runtime-Function-Error-Body";
+
+/// The `errorType` of invocations whose response has a content type which isn't a header value
+const INVALID_CONTENT_TYPE: &str = "Runtime.InvalidContentType";
+
+/// A function response whose body is streamed to the Runtime API as it is produced.
+#[derive(Debug)]
+pub struct StreamResponse<S> {
+    content_type: String,
+    body: S,
+}
+
+impl<S> StreamResponse<S> {
+    /// Returns a new response streaming `body` with the given content type.
+    ///
+    /// A content type which isn't a valid header value, such as one spanning lines, fails the
+    /// invocation instead of being sent.
+    pub fn new<T>(content_type: T, body: S) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            content_type: content_type.into(),
+            body,
+        }
+    }
+
+    /// Returns the content type of the response.
+    #[must_use]
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    /// Returns the stream of body chunks.
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn into_body(self) -> S {
+        self.body
+    }
+}
+
+/// Sends streamed responses over connections made by a connector.
+///
+/// `hyper` does not send HTTP/1.1 trailers, which the Runtime API relies on to report
+/// errors which happen mid-stream, so requests are written directly to the connection.
+pub(crate) struct StreamingClient<C> {
+    base: Uri,
+    connector: C,
+}
+
+impl<C> StreamingClient<C>
+where
+    C: Service<Uri>,
+    C::Response: AsyncRead + AsyncWrite + Unpin,
+    C::Error: Into<Error>,
+{
+    pub(crate) const fn with(base: Uri, connector: C) -> Self {
+        Self { base, connector }
+    }
+
+    /// Streams `body` with `content_type` as the response to an invocation, returning once the
+    /// Runtime API has accepted it.
+    pub(crate) async fn send<S, E>(&mut self, request_id: &str, content_type: HeaderValue, body: S) -> Result<(), Error>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+        E: fmt::Debug + 'static,
+    {
+        let authority = self.base.authority().ok_or("Authority not found")?.clone();
+        let req = Request::post(format!("/2018-06-01/runtime/invocation/{request_id}/response"))
+            .header(header::HOST, authority.as_str())
+            .header(RESPONSE_MODE_HEADER, "streaming")
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::TRANSFER_ENCODING, "chunked")
+            .header(header::TRAILER, format!("{ERROR_TYPE_TRAILER}, {ERROR_BODY_TRAILER}"))
+            .header(header::CONNECTION, "close")
+            .body(())?;
+        let uri = Uri::builder()
+            .scheme(self.base.scheme().cloned().unwrap_or(http::uri::Scheme::HTTP))
+            .authority(authority)
+            .path_and_query(req.uri().path())
+            .build()?;
+        poll_fn(|cx| self.connector.poll_ready(cx)).await.map_err(Into::into)?;
+        let mut io = self.connector.call(uri).await.map_err(Into::into)?;
+        io.write_all(&head(&req)).await?;
+
+        let mut body = body;
+        let mut trailers = Vec::new();
+        while let Some(chunk) = body.next().await {
+            match chunk {
+                // an empty chunk would end the body
+                Ok(bytes) if bytes.is_empty() => {}
+                Ok(bytes) => {
+                    let mut frame = format!("{len:X}\r\n", len = bytes.len()).into_bytes();
+                    frame.extend_from_slice(&bytes);
+                    frame.extend_from_slice(b"\r\n");
+                    io.write_all(&frame).await?;
+                    io.flush().await?;
+                }
+                Err(e) => {
+                    let diagnostic = ErrorDiagnostic::new(&e);
+                    let encoded = base64::encode(serde_json::to_vec(&diagnostic)?);
+                    trailers = format!(
+                        "{}: {}\r\n{}: {}\r\n",
+                        ERROR_TYPE_TRAILER,
+                        header_error_type(diagnostic.error_type()),
+                        ERROR_BODY_TRAILER,
+                        encoded
+                    )
+                    .into_bytes();
+                    break;
+                }
+            }
+        }
+
+        let mut end = b"0\r\n".to_vec();
+        end.extend_from_slice(&trailers);
+        end.extend_from_slice(b"\r\n");
+        io.write_all(&end).await?;
+        io.flush().await?;
+
+        let status = read_status(&mut io).await?;
+        if (200..300).contains(&status) {
+            Ok(())
+        } else {
+            Err(format!("Runtime API rejected streamed response with status {status}").into())
+        }
+    }
+}
+
+impl<C, S, E> ResponseSink<StreamResponse<S>> for StreamingClient<C>
+where
+    C: Service<Uri>,
+    C::Response: AsyncRead + AsyncWrite + Unpin,
+    C::Error: Into<Error>,
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: fmt::Debug + 'static,
+{
+    async fn respond(
+        &mut self,
+        request_id: &str,
+        response: StreamResponse<S>,
+    ) -> Result<Option<Request<Body>>, RuntimeError> {
+        let Ok(content_type) = HeaderValue::from_str(response.content_type()) else {
+            let req = EventErrorRequest {
+                request_id,
+                diagnostic: Diagnostic {
+                    error_type: INVALID_CONTENT_TYPE.to_owned(),
+                    error_message: format!(
+                        "the content type {:?} of the response isn't a valid header value",
+                        response.content_type()
+                    ),
+                },
+            };
+            return Ok(Some(req.into_req().map_err(RuntimeError::Serialize)?));
+        };
+        self.send(request_id, content_type, response.into_body())
+            .await
+            .map_err(RuntimeError::Api)?;
+        Ok(None)
+    }
+}
+
+/// The request line and headers of `req`, ending with the empty line which starts its body
+fn head(req: &Request<()>) -> Vec<u8> {
+    let mut head = format!("{} {} HTTP/1.1\r\n", req.method(), req.uri()).into_bytes();
+    for (name, value) in req.headers() {
+        head.extend_from_slice(name.as_str().as_bytes());
+        head.extend_from_slice(b": ");
+        head.extend_from_slice(value.as_bytes());
+        head.extend_from_slice(b"\r\n");
+    }
+    head.extend_from_slice(b"\r\n");
+    head
+}
+
+/// Reads the status code of an HTTP/1.1 response, ignoring the rest of it.
+async fn read_status<I>(io: &mut I) -> Result<u16, Error>
+where
+    I: AsyncRead + Unpin,
+{
+    let mut head = Vec::new();
+    let mut buf = [0_u8; 512];
+    while !head.windows(2).any(|w| w == b"\r\n") {
+        let read = io.read(&mut buf).await?;
+        if read == 0 {
+            return Err("Runtime API closed the connection without responding".into());
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or("Runtime API sent a malformed response")?;
+    Ok(status)
+}
+
+/// Starts the Lambda Rust runtime for a handler which streams its responses.
+///
+/// Errors returned by the handler before streaming starts are reported like those of
+/// [`run`](../fn.run.html) handlers.
+///
+/// # Errors
+///
+/// Returns an error when the runtime's environment variables are missing or invalid, or the
+/// Runtime API cannot be reached.
+pub async fn run<A, F, S, E>(handler: F) -> Result<(), Error>
+where
+    F: Handler<A, StreamResponse<S>>,
+    <F as Handler<A, StreamResponse<S>>>::Error: fmt::Debug + 'static,
+    A: for<'de> Deserialize<'de>,
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: fmt::Debug + 'static,
+{
+    let mut config = RuntimeConfig::default();
+    let env_config = crate::config::configure(config.client()).await?;
+    let uri = Uri::try_from(env_config.endpoint.as_str())?;
+    config = config.with_env_config(env_config);
+    let client = Client::new(uri.clone());
+    let mut streaming = StreamingClient::with(uri, HttpConnector::new());
+    let mut handler = handler;
+    run_inner(
+        &client,
+        incoming(&client),
+        &mut handler,
+        json,
+        &mut streaming,
+        &mut config,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Runs a streaming lambda function for a single event against a Runtime API at `url`. This is meant for testing.
+///
+/// # Errors
+///
+/// Returns an error when `url` is not a valid URL or the Runtime API cannot be reached.
+pub async fn run_simulated<A, F, S, E>(handler: F, url: &str) -> Result<(), Error>
+where
+    F: Handler<A, StreamResponse<S>>,
+    <F as Handler<A, StreamResponse<S>>>::Error: fmt::Debug + 'static,
+    A: for<'de> Deserialize<'de>,
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: fmt::Debug + 'static,
+{
+    let uri = Uri::try_from(url)?;
+    let client = Client::new(uri.clone());
+    let mut streaming = StreamingClient::with(uri, HttpConnector::new());
+    let mut handler = handler;
+    let incoming = incoming(&client).take(1);
+    run_inner(
+        &client,
+        incoming,
+        &mut handler,
+        json,
+        &mut streaming,
+        &mut RuntimeConfig::default(),
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulated::{chan, SimulatedConnector};
+    use futures::stream;
+
+    /// Reads a streamed request until its last chunk and trailers, then accepts it
+    async fn accept_streamed<I>(mut io: I) -> String
+    where
+        I: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut received = Vec::new();
+        let mut buf = [0_u8; 512];
+        loop {
+            let read = io.read(&mut buf).await.expect("failed to read request");
+            received.extend_from_slice(&buf[..read]);
+            let text = String::from_utf8_lossy(&received);
+            if let Some(last) = text.find("\r\n0\r\n") {
+                if text[last + 3..].contains("\r\n\r\n") {
+                    break;
+                }
+            }
+        }
+        io.write_all(b"HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\n\r\n")
+            .await
+            .expect("failed to respond");
+        String::from_utf8(received).expect("request was not utf-8")
+    }
+
+    #[tokio::test]
+    async fn streams_chunks() -> Result<(), Error> {
+        let (client, server) = chan();
+        let server = tokio::spawn(accept_streamed(server));
+        let mut streaming = StreamingClient::with(
+            Uri::from_static("http://localhost:9001"),
+            SimulatedConnector { inner: client },
+        );
+        let body = stream::iter(vec![
+            Ok::<_, Error>(Bytes::from("hello ")),
+            Ok(Bytes::new()),
+            Ok(Bytes::from("world")),
+        ]);
+        let report = streaming
+            .respond("8476a536", StreamResponse::new("text/plain", body))
+            .await?;
+        assert!(report.is_none());
+        let request = server.await?;
+        assert!(request.starts_with("POST /2018-06-01/runtime/invocation/8476a536/response HTTP/1.1\r\n"));
+        assert!(request.contains("\r\nlambda-runtime-function-response-mode: streaming\r\n"));
+        assert!(request.contains("\r\ncontent-type: text/plain\r\n"));
+        assert!(request.ends_with("\r\n\r\n6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_mid_stream_errors_in_trailers() -> Result<(), Error> {
+        let (client, server) = chan();
+        let server = tokio::spawn(accept_streamed(server));
+        let mut streaming = StreamingClient::with(
+            Uri::from_static("http://localhost:9001"),
+            SimulatedConnector { inner: client },
+        );
+        let body = stream::iter(vec![
+            Ok(Bytes::from("partial")),
+            Err("upstream went away"),
+            Ok(Bytes::from("never")),
+        ]);
+        let report = streaming
+            .respond("8476a536", StreamResponse::new("text/plain", body))
+            .await?;
+        assert!(report.is_none());
+        let request = server.await?;
+        let trailers = request.split("\r\n0\r\n").nth(1).expect("no last chunk");
+        assert!(!request.contains("never"));
+        // named like the `errorType` header of error reports
+        assert!(trailers.starts_with("Lambda-Runtime-Function-Error-Type: str\r\n"));
+        let encoded = trailers
+            .lines()
+            .find_map(|line| line.strip_prefix("Lambda-Runtime-Function-Error-Body: "))
+            .expect("no error body trailer");
+        let diagnostic: Diagnostic = serde_json::from_slice(&base64::decode(encoded)?)?;
+        assert_eq!(diagnostic.error_message, "\"upstream went away\"");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fails_invocations_of_content_types_which_arent_header_values() -> Result<(), Error> {
+        let (client, _server) = chan();
+        let mut streaming = StreamingClient::with(
+            Uri::from_static("http://localhost:9001"),
+            SimulatedConnector { inner: client },
+        );
+        let body = stream::iter(vec![Ok::<_, Error>(Bytes::from("hello"))]);
+        let report = streaming
+            .respond("8476a536", StreamResponse::new("text/plain\r\nX-Injected: 1", body))
+            .await?
+            .expect("the invocation should fail");
+        assert_eq!(report.uri().path(), "/2018-06-01/runtime/invocation/8476a536/error");
+        assert_eq!(
+            report.headers()[crate::requests::FUNCTION_ERROR_TYPE_HEADER],
+            INVALID_CONTENT_TYPE
+        );
+        Ok(())
+    }
+}
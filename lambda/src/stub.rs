@@ -0,0 +1,194 @@
+//! A stub of the Runtime and Extensions APIs, and a metrics sink recording what it is fed, for
+//! the tests of every module.
+use crate::{
+    extensions::{EXTENSION_ERROR_TYPE_HEADER, EXTENSION_ID_HEADER, EXTENSION_NAME_HEADER},
+    metrics::MetricsSink,
+    requests::{IntoResponse, NextEventResponse},
+    Error,
+};
+use futures::future;
+use http::{Method, Request, Response};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Server,
+};
+use serde_json::Value;
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+/// A request received by the stub
+#[derive(Debug)]
+pub struct Received {
+    pub method: Method,
+    pub path: String,
+    pub extension_id: Option<String>,
+    pub error_type: Option<String>,
+    pub function_error_type: Option<String>,
+    pub body: Value,
+}
+
+/// An Extensions API serving `events` in order, and recording the requests it receives
+///
+/// With `invoking`, it also serves a Runtime API, and holds the extension events back until
+/// the runtime responded to every invocation.
+#[derive(Clone, Default)]
+pub struct Stub {
+    events: Arc<Mutex<VecDeque<Value>>>,
+    invocations: Arc<Mutex<VecDeque<Value>>>,
+    responded: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    held_until_responded: Arc<Mutex<Option<oneshot::Receiver<()>>>>,
+    pub received: Arc<Mutex<Vec<Received>>>,
+    /// The size of the chunks invocations are streamed in, if they aren't sent whole
+    chunk_bytes: Option<usize>,
+}
+
+impl Stub {
+    pub fn serving(events: Vec<Value>) -> Self {
+        Self {
+            events: Arc::new(Mutex::new(events.into())),
+            ..Self::default()
+        }
+    }
+
+    pub fn invoking(self, invocations: Vec<Value>) -> Self {
+        let (responded, held_until_responded) = oneshot::channel();
+        Self {
+            invocations: Arc::new(Mutex::new(invocations.into())),
+            responded: Arc::new(Mutex::new(Some(responded))),
+            held_until_responded: Arc::new(Mutex::new(Some(held_until_responded))),
+            ..self
+        }
+    }
+
+    /// Streams invocations in chunks of `chunk_bytes`, without announcing their length
+    pub fn chunked(self, chunk_bytes: usize) -> Self {
+        Self {
+            chunk_bytes: Some(chunk_bytes),
+            ..self
+        }
+    }
+
+    /// Starts the stub on an ephemeral port, returning its address
+    pub fn start(&self) -> SocketAddr {
+        let stub = self.clone();
+        let make_service = make_service_fn(move |_| {
+            let stub = stub.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| stub.clone().handle(req))) }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    pub async fn handle(self, req: Request<Body>) -> Result<Response<Body>, Error> {
+        let (parts, body) = req.into_parts();
+        let header = |name: &str| parts.headers.get(name).map(|value| value.to_str().unwrap().to_owned());
+        let body = hyper::body::to_bytes(body).await?;
+        self.received.lock().unwrap().push(Received {
+            method: parts.method.clone(),
+            path: parts.uri.path().to_owned(),
+            extension_id: header(EXTENSION_ID_HEADER),
+            error_type: header(EXTENSION_ERROR_TYPE_HEADER),
+            function_error_type: header(crate::requests::FUNCTION_ERROR_TYPE_HEADER),
+            body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+        });
+
+        let rsp = Response::builder();
+        let rsp = match parts.uri.path() {
+            "/2020-01-01/extension/register" if header(EXTENSION_NAME_HEADER).as_deref() == Some("forbidden") => rsp
+                .status(403)
+                .body(Body::from(r#"{"errorType":"Extension.Forbidden"}"#))?,
+            "/2020-01-01/extension/register" => rsp.header(EXTENSION_ID_HEADER, "ext-1").body(Body::from(
+                r#"{"functionName":"helloWorld","functionVersion":"$LATEST","handler":"bootstrap"}"#,
+            ))?,
+            "/2020-01-01/extension/event/next" => {
+                let held = self.held_until_responded.lock().unwrap().take();
+                if let Some(held) = held {
+                    let _ = held.await;
+                }
+                let event = self.events.lock().unwrap().pop_front();
+                match event {
+                    Some(event) => rsp.body(Body::from(serde_json::to_vec(&event)?))?,
+                    // like Lambda, leave the extension waiting once it handled every event
+                    None => return future::pending().await,
+                }
+            }
+            "/2020-08-15/logs" | "/2022-07-01/telemetry" => rsp.body(Body::from("OK"))?,
+            "/2018-06-01/runtime/invocation/next" => {
+                let invocation = self.invocations.lock().unwrap().pop_front();
+                match invocation {
+                    Some(invocation) => {
+                        let rsp = NextEventResponse {
+                            request_id: "8476a536-e9f4-11e8-9739-2dfe598c3fcd",
+                            deadline: 1_542_409_706_888,
+                            arn: "arn:aws:lambda:us-east-2:123456789012:function:custom-runtime",
+                            trace_id: "Root=1-5bef4de7-ad49b0e87f6ef6c87fc2e700;Parent=9a9197af755a6419",
+                            body: serde_json::to_vec(&invocation)?,
+                        }
+                        .into_rsp()?;
+                        match self.chunk_bytes {
+                            Some(chunk_bytes) => {
+                                let (mut parts, body) = rsp.into_parts();
+                                parts.headers.remove(http::header::CONTENT_LENGTH);
+                                let body = hyper::body::to_bytes(body).await?;
+                                let chunks: Vec<Result<_, Infallible>> =
+                                    body.chunks(chunk_bytes).map(|chunk| Ok(chunk.to_vec())).collect();
+                                Response::from_parts(parts, Body::wrap_stream(futures::stream::iter(chunks)))
+                            }
+                            None => rsp,
+                        }
+                    }
+                    // like Lambda, leave the runtime waiting once it handled every invocation
+                    None => return future::pending().await,
+                }
+            }
+            path if path.starts_with("/2018-06-01/runtime/invocation/") => {
+                let handled_all = self.invocations.lock().unwrap().is_empty();
+                let responded = self.responded.lock().unwrap().take();
+                match responded {
+                    Some(responded) if handled_all => {
+                        let _ = responded.send(());
+                    }
+                    responded => *self.responded.lock().unwrap() = responded,
+                }
+                rsp.status(202).body(Body::empty())?
+            }
+            _ => rsp.status(202).body(Body::empty())?,
+        };
+        Ok(rsp)
+    }
+
+    pub fn received(&self) -> Vec<Received> {
+        std::mem::take(&mut *self.received.lock().unwrap())
+    }
+}
+
+/// A sink recording the names of the measurements it is fed, and its flushes
+#[derive(Clone, Default)]
+pub struct Recording(pub Arc<Mutex<Vec<String>>>);
+
+impl MetricsSink for Recording {
+    fn record_duration(&mut self, name: &str, _: Duration) {
+        self.0.lock().unwrap().push(format!("duration {name}"));
+    }
+
+    fn record_count(&mut self, name: &str, count: u64) {
+        self.0.lock().unwrap().push(format!("count {name} {count}"));
+    }
+
+    fn record_gauge(&mut self, name: &str, value: f64) {
+        self.0.lock().unwrap().push(format!("gauge {name} {value}"));
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.0.lock().unwrap().push("flush".to_owned());
+        Err("the sidecar is gone".into())
+    }
+}
@@ -1,7 +1,16 @@
-use crate::{Config, Error};
+use crate::{
+    init::{self, ColdStartKind, InitReport, InitializationType},
+    Config, Error, HandlerError,
+};
 use http::HeaderMap;
-use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, convert::TryFrom};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+use std::{
+    any::Any,
+    collections::HashMap,
+    convert::TryFrom,
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -10,6 +19,120 @@ pub(crate) struct Diagnostic {
     pub(crate) error_message: String,
 }
 
+/// The diagnostic of a handler which failed with `error`, serialized like a [`Diagnostic`]
+/// whose message is the `Debug` output of the error.
+///
+/// The message is written straight into the serialized diagnostic, without formatting it into
+/// a `String` first. The type is the name of the type of the error, or the `errorType` of a
+/// [`HandlerError`], bare or boxed.
+pub struct ErrorDiagnostic<'a, E> {
+    error_type: &'a str,
+    error: &'a E,
+}
+
+impl<'a, E> ErrorDiagnostic<'a, E>
+where
+    E: fmt::Debug + 'static,
+{
+    pub fn new(error: &'a E) -> Self {
+        let any: &'a dyn Any = error;
+        let handler_error = any
+            .downcast_ref::<HandlerError>()
+            .or_else(|| any.downcast_ref::<Error>()?.downcast_ref());
+        // `map_or_else` would infer the `'static` lifetime of the name of the type for both
+        #[allow(clippy::option_if_let_else)]
+        let error_type = match handler_error {
+            Some(handler_error) => handler_error.error_type(),
+            None => std::any::type_name::<E>(),
+        };
+        Self { error_type, error }
+    }
+}
+
+impl<'a, E> ErrorDiagnostic<'a, E> {
+    /// The type the error is reported with.
+    pub const fn error_type(&self) -> &'a str {
+        self.error_type
+    }
+}
+
+impl<E> Serialize for ErrorDiagnostic<'_, E>
+where
+    E: fmt::Debug,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        /// Displays an error as its `Debug` output
+        struct Message<'a, E>(&'a E);
+
+        impl<E> fmt::Display for Message<'_, E>
+        where
+            E: fmt::Debug,
+        {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(self.0, f)
+            }
+        }
+
+        impl<E> Serialize for Message<'_, E>
+        where
+            E: fmt::Debug,
+        {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.collect_str(self)
+            }
+        }
+
+        let mut diagnostic = serializer.serialize_struct("Diagnostic", 2)?;
+        diagnostic.serialize_field("errorType", self.error_type)?;
+        diagnostic.serialize_field("errorMessage", &Message(self.error))?;
+        diagnostic.end()
+    }
+}
+
+#[test]
+fn error_diagnostics_serialize_like_formatted_ones() -> Result<(), Error> {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    enum OrderError {
+        Refused { reason: String },
+        Missing(u32),
+    }
+
+    let errors = [
+        OrderError::Refused {
+            reason: "the card was declined:\n\t\"insufficient funds\" \u{1F4B3}".to_owned(),
+        },
+        OrderError::Missing(42),
+    ];
+    for error in &errors {
+        let formatted = Diagnostic {
+            error_type: std::any::type_name::<OrderError>().to_owned(),
+            error_message: format!("{error:?}"),
+        };
+        assert_eq!(
+            serde_json::to_vec(&ErrorDiagnostic::new(error))?,
+            serde_json::to_vec(&formatted)?,
+        );
+    }
+
+    let error: Error = "the order has no items".into();
+    let formatted = Diagnostic {
+        error_type: std::any::type_name::<Error>().to_owned(),
+        error_message: format!("{error:?}"),
+    };
+    assert_eq!(
+        serde_json::to_vec(&ErrorDiagnostic::new(&error))?,
+        serde_json::to_vec(&formatted)?,
+    );
+    Ok(())
+}
+
 #[test]
 fn round_trip_lambda_error() -> Result<(), Error> {
     use serde_json::{json, Value};
@@ -114,6 +237,38 @@ pub struct Context {
     /// Includes information such as the function name, memory allocation,
     /// version, and log streams.
     pub env_config: Config,
+    /// How long initialization took, on the first invocation only.
+    pub(crate) init_report: Option<InitReport>,
+}
+
+impl Context {
+    /// The time remaining before the execution deadline of the current invocation,
+    /// which is zero once the deadline has passed.
+    #[must_use]
+    pub fn time_remaining(&self) -> Duration {
+        let deadline = UNIX_EPOCH + Duration::from_millis(self.deadline);
+        deadline.duration_since(SystemTime::now()).unwrap_or_default()
+    }
+
+    /// How long the initialization of the execution environment took, for the first
+    /// invocation the environment handles, and `None` for every later one.
+    #[must_use]
+    pub const fn init_report(&self) -> Option<&InitReport> {
+        self.init_report.as_ref()
+    }
+
+    /// How the execution environment was initialized, the same for every invocation.
+    #[must_use]
+    pub fn initialization_type(&self) -> &'static InitializationType {
+        init::initialization_type()
+    }
+
+    /// What the first invocation the environment handles waited for, and `None` for every
+    /// later one.
+    #[must_use]
+    pub fn cold_start(&self) -> Option<ColdStartKind> {
+        self.init_report.as_ref().map(InitReport::cold_start)
+    }
 }
 
 impl TryFrom<HeaderMap> for Context {
@@ -141,3 +296,23 @@ impl TryFrom<HeaderMap> for Context {
         Ok(ctx)
     }
 }
+
+#[test]
+fn time_remaining_is_zero_after_deadline() {
+    let ctx = Context {
+        deadline: 1_542_409_706_888,
+        ..Context::default()
+    };
+    assert_eq!(ctx.time_remaining(), Duration::default());
+}
+
+#[test]
+fn time_remaining_counts_down_to_deadline() {
+    let in_half_a_minute = SystemTime::now() + Duration::from_secs(30);
+    let ctx = Context {
+        deadline: u64::try_from(in_half_a_minute.duration_since(UNIX_EPOCH).unwrap().as_millis()).unwrap(),
+        ..Context::default()
+    };
+    assert!(ctx.time_remaining() > Duration::from_secs(20));
+    assert!(ctx.time_remaining() <= Duration::from_secs(30));
+}
@@ -0,0 +1,347 @@
+//! Custom [X-Ray](https://docs.aws.amazon.com/xray/latest/devguide/xray-api-segmentdocuments.html)
+//! subsegments, sent to the X-Ray daemon, behind the `xray` feature.
+//!
+//! Lambda records a segment for every traced invocation. The subsegments of an [`XRaySegment`]
+//! are recorded under it, linked through the trace and parent IDs of the invocation's trace
+//! header, and sent as independent subsegment documents over UDP to the daemon at
+//! `AWS_XRAY_DAEMON_ADDRESS` once they end. Sending is done on a best effort basis: failures are
+//! logged and never affect the invocation, and nothing is sent for invocations which aren't
+//! sampled.
+//!
+//! ```no_run
+//! use lambda::{xray, Context};
+//! use serde_json::Value;
+//!
+//! type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+//!
+//! async fn func(event: Value, ctx: Context) -> Result<Value, Error> {
+//!     let total = xray::subsegment("sum", &ctx, |segment| {
+//!         segment.annotate("items", 3);
+//!         1 + 2 + 3
+//!     });
+//!     Ok(total.into())
+//! }
+//! ```
+use crate::Context;
+use serde_json::{json, Map, Value};
+use std::{
+    collections::hash_map::RandomState,
+    env,
+    hash::{BuildHasher, Hasher},
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::error;
+
+/// The header line preceding every document sent to the daemon
+const DAEMON_HEADER: &str = r#"{"format":"json","version":1}"#;
+
+/// Where the daemon listens when `AWS_XRAY_DAEMON_ADDRESS` isn't set
+const DEFAULT_DAEMON_ADDRESS: &str = "127.0.0.1:2000";
+
+/// A subsegment of an invocation's trace, sent to the X-Ray daemon when it ends.
+///
+/// Subsegments which aren't ended explicitly end when dropped.
+#[derive(Debug)]
+pub struct XRaySegment {
+    name: String,
+    id: String,
+    trace: Option<Trace>,
+    start_time: f64,
+    annotations: Map<String, Value>,
+    metadata: Map<String, Value>,
+    error: bool,
+    daemon: Option<SocketAddr>,
+    ended: bool,
+}
+
+/// The trace a subsegment is recorded in
+#[derive(Debug, Clone)]
+struct Trace {
+    trace_id: String,
+    parent_id: String,
+}
+
+impl XRaySegment {
+    /// Begins the subsegment `name` of the invocation of `ctx`, under the segment Lambda
+    /// records for it.
+    #[must_use]
+    pub fn begin(name: impl Into<String>, ctx: &Context) -> Self {
+        let header = if ctx.xray_trace_id.is_empty() {
+            env::var("_X_AMZN_TRACE_ID").unwrap_or_default()
+        } else {
+            ctx.xray_trace_id.clone()
+        };
+        Self::new(name.into(), parse_trace_header(&header), daemon_address())
+    }
+
+    /// Begins the subsegment `name` under this one.
+    #[must_use]
+    pub fn child(&self, name: impl Into<String>) -> Self {
+        let trace = self.trace.as_ref().map(|trace| Trace {
+            trace_id: trace.trace_id.clone(),
+            parent_id: self.id.clone(),
+        });
+        Self::new(name.into(), trace, self.daemon)
+    }
+
+    fn new(name: String, trace: Option<Trace>, daemon: Option<SocketAddr>) -> Self {
+        Self {
+            name,
+            id: new_id(),
+            trace,
+            start_time: now(),
+            annotations: Map::new(),
+            metadata: Map::new(),
+            error: false,
+            daemon,
+            ended: false,
+        }
+    }
+
+    /// The ID of the subsegment, which its children name as their parent.
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Adds the annotation `key`, indexed by X-Ray for filter expressions. Values are strings,
+    /// numbers or booleans.
+    pub fn annotate(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.annotations.insert(key.into(), value.into());
+    }
+
+    /// Adds `key` to the metadata of the subsegment, in the `default` namespace. Unlike
+    /// annotations, metadata isn't indexed and takes any JSON value.
+    pub fn add_metadata(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.metadata.insert(key.into(), value.into());
+    }
+
+    /// Records that the subsegment failed.
+    pub const fn mark_error(&mut self) {
+        self.error = true;
+    }
+
+    /// Ends the subsegment and sends it to the daemon.
+    pub fn end(mut self) {
+        self.emit();
+    }
+
+    /// The document of the subsegment, ending now
+    fn document(&self, trace: &Trace) -> Value {
+        let mut document = json!({
+            "name": self.name,
+            "id": self.id,
+            "trace_id": trace.trace_id,
+            "parent_id": trace.parent_id,
+            "type": "subsegment",
+            "start_time": self.start_time,
+            "end_time": now(),
+        });
+        if !self.annotations.is_empty() {
+            document["annotations"] = Value::Object(self.annotations.clone());
+        }
+        if !self.metadata.is_empty() {
+            document["metadata"] = json!({ "default": self.metadata });
+        }
+        if self.error {
+            document["error"] = Value::Bool(true);
+        }
+        document
+    }
+
+    fn emit(&mut self) {
+        if self.ended {
+            return;
+        }
+        self.ended = true;
+        let (Some(trace), Some(daemon)) = (&self.trace, self.daemon) else {
+            return;
+        };
+        let datagram = format!("{DAEMON_HEADER}\n{}", self.document(trace));
+        let sent = UdpSocket::bind(("0.0.0.0", 0)).and_then(|socket| socket.send_to(datagram.as_bytes(), daemon));
+        if let Err(e) = sent {
+            error!(message = "Failed to send a subsegment to the X-Ray daemon", e = %e);
+        }
+    }
+}
+
+impl Drop for XRaySegment {
+    fn drop(&mut self) {
+        self.emit();
+    }
+}
+
+/// Runs `f` in the subsegment `name` of the invocation of `ctx`, sending it once `f` returns.
+pub fn subsegment<F, T>(name: impl Into<String>, ctx: &Context, f: F) -> T
+where
+    F: FnOnce(&mut XRaySegment) -> T,
+{
+    let mut segment = XRaySegment::begin(name, ctx);
+    let output = f(&mut segment);
+    segment.end();
+    output
+}
+
+/// Reads the trace and parent IDs of a sampled invocation from its trace header, like
+/// `Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`
+fn parse_trace_header(header: &str) -> Option<Trace> {
+    let mut trace_id = None;
+    let mut parent_id = None;
+    let mut sampled = false;
+    for field in header.split(';') {
+        match field.trim().split_once('=') {
+            Some(("Root", root)) => trace_id = Some(root.to_owned()),
+            Some(("Parent", parent)) => parent_id = Some(parent.to_owned()),
+            Some(("Sampled", flag)) => sampled = flag == "1",
+            _ => {}
+        }
+    }
+    if sampled {
+        Some(Trace {
+            trace_id: trace_id?,
+            parent_id: parent_id?,
+        })
+    } else {
+        None
+    }
+}
+
+/// The UDP address of the daemon, from `AWS_XRAY_DAEMON_ADDRESS`, which is either an address,
+/// like `169.254.79.129:2000`, or separate TCP and UDP ones, like
+/// `tcp:127.0.0.1:2000 udp:127.0.0.2:2000`
+fn daemon_address() -> Option<SocketAddr> {
+    let configured = env::var("AWS_XRAY_DAEMON_ADDRESS").unwrap_or_else(|_| DEFAULT_DAEMON_ADDRESS.to_owned());
+    let address = configured
+        .split_whitespace()
+        .find_map(|address| address.strip_prefix("udp:"))
+        .unwrap_or(&configured);
+    match address.to_socket_addrs().map(|mut addrs| addrs.next()) {
+        Ok(address) => address,
+        Err(e) => {
+            error!(message = "Invalid X-Ray daemon address", address = %configured, e = %e);
+            None
+        }
+    }
+}
+
+/// A random 64-bit ID, in hexadecimal
+fn new_id() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    format!("{:016x}", hasher.finish())
+}
+
+/// The current time, in seconds since the epoch
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::time::Duration;
+
+    const TRACE_ID: &str = "1-5759e988-bd862e3fe1be46a994272793";
+
+    fn context(trace_header: &str) -> Context {
+        Context {
+            request_id: "8476a536-e9f4-11e8-9739-2dfe598c3fcd".into(),
+            deadline: 1_542_409_706_888,
+            invoked_function_arn: "arn:aws:lambda:us-east-2:123456789012:function:custom-runtime".into(),
+            xray_trace_id: trace_header.into(),
+            client_context: None,
+            identity: None,
+            env_config: Config::default(),
+            init_report: None,
+        }
+    }
+
+    /// A UDP socket standing in for the daemon
+    fn daemon() -> UdpSocket {
+        let daemon = UdpSocket::bind("127.0.0.1:0").unwrap();
+        daemon.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        daemon
+    }
+
+    fn receive(daemon: &UdpSocket) -> Value {
+        let mut buf = vec![0; 64 * 1024];
+        let len = daemon.recv(&mut buf).expect("the daemon didn't receive a subsegment");
+        let datagram = std::str::from_utf8(&buf[..len]).unwrap();
+        let (header, document) = datagram.split_once('\n').expect("missing header line");
+        assert_eq!(header, r#"{"format":"json","version":1}"#);
+        serde_json::from_str(document).unwrap()
+    }
+
+    #[test]
+    fn sends_subsegments_under_the_invocation() {
+        let daemon = daemon();
+        let ctx = context(&format!("Root={TRACE_ID};Parent=53995c3f42cd8ad8;Sampled=1"));
+        let mut segment = XRaySegment::begin("checkout", &ctx);
+        segment.daemon = Some(daemon.local_addr().unwrap());
+        segment.annotate("order_id", "42");
+        segment.annotate("items", 3);
+        segment.add_metadata("cart", json!({ "currency": "EUR" }));
+
+        let mut child = segment.child("charge");
+        child.mark_error();
+        child.end();
+        let segment_id = segment.id().to_owned();
+        segment.end();
+
+        let child = receive(&daemon);
+        assert_eq!(child["name"], "charge");
+        assert_eq!(child["trace_id"], TRACE_ID);
+        assert_eq!(child["parent_id"], segment_id.as_str());
+        assert_eq!(child["error"], true);
+
+        let segment = receive(&daemon);
+        assert_eq!(segment["name"], "checkout");
+        assert_eq!(segment["id"], segment_id.as_str());
+        assert_eq!(segment["id"].as_str().unwrap().len(), 16);
+        assert_eq!(segment["type"], "subsegment");
+        assert_eq!(segment["trace_id"], TRACE_ID);
+        assert_eq!(segment["parent_id"], "53995c3f42cd8ad8");
+        assert_eq!(segment["annotations"], json!({ "order_id": "42", "items": 3 }));
+        assert_eq!(
+            segment["metadata"],
+            json!({ "default": { "cart": { "currency": "EUR" } } })
+        );
+        assert!(segment["end_time"].as_f64().unwrap() >= segment["start_time"].as_f64().unwrap());
+        assert!(segment.get("error").is_none());
+    }
+
+    #[test]
+    fn sends_nothing_for_unsampled_invocations() {
+        let daemon = daemon();
+        daemon.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        for header in &[
+            format!("Root={TRACE_ID};Parent=53995c3f42cd8ad8;Sampled=0"),
+            format!("Root={TRACE_ID}"),
+        ] {
+            let mut segment = XRaySegment::begin("checkout", &context(header));
+            segment.daemon = Some(daemon.local_addr().unwrap());
+        }
+        let mut buf = [0; 1024];
+        assert!(daemon.recv(&mut buf).is_err());
+    }
+
+    #[test]
+    fn reads_udp_daemon_addresses() {
+        env::set_var("AWS_XRAY_DAEMON_ADDRESS", "tcp:127.0.0.1:2000 udp:127.0.0.2:2001");
+        assert_eq!(daemon_address(), Some(([127, 0, 0, 2], 2001).into()));
+        env::set_var("AWS_XRAY_DAEMON_ADDRESS", "169.254.79.129:2000");
+        assert_eq!(daemon_address(), Some(([169, 254, 79, 129], 2000).into()));
+        env::remove_var("AWS_XRAY_DAEMON_ADDRESS");
+        assert_eq!(daemon_address(), Some(([127, 0, 0, 1], 2000).into()));
+    }
+}